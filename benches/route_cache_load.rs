@@ -0,0 +1,54 @@
+use arb_rust_bot::route_cache::{DEXType, RoutePath};
+use arb_rust_bot::route_cache_codec::RouteCacheFormat;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dashmap::DashMap;
+use ethers::types::H160;
+use std::sync::Arc;
+
+/// Roughly a production cache: thousands of tokens with a few dozen 2- and 3-hop cycles each
+const TOKENS: u32 = 5_000;
+const ROUTES_PER_TOKEN: u32 = 40;
+
+fn realistic_cache() -> DashMap<u32, Arc<Vec<RoutePath>>> {
+    let dex_types = [DEXType::PancakeV2, DEXType::PancakeV3, DEXType::BiSwapV2, DEXType::Other("MDEX".to_string())];
+    let cache = DashMap::new();
+    for token in 1..=TOKENS {
+        let routes = (0..ROUTES_PER_TOKEN)
+            .map(|i| {
+                let pool = |n: u32| H160::from_low_u64_be(((token as u64) << 32) | (i as u64) << 8 | n as u64);
+                let dex = |n: u32| dex_types[((token + i + n) % dex_types.len() as u32) as usize].clone();
+                if i % 2 == 0 {
+                    RoutePath { hops: vec![0, token, 0], pools: vec![pool(0), pool(1)], dex_types: vec![dex(0), dex(1)], ..Default::default() }
+                } else {
+                    let other = (token + i) % TOKENS + 1;
+                    RoutePath {
+                        hops: vec![0, token, other, 0],
+                        pools: vec![pool(0), pool(1), pool(2)],
+                        dex_types: vec![dex(0), dex(1), dex(2)],
+                        ..Default::default()
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        cache.insert(token, Arc::new(routes));
+    }
+    cache
+}
+
+fn bench_route_cache_load(c: &mut Criterion) {
+    let cache = realistic_cache();
+    let mut group = c.benchmark_group("route_cache_load");
+    group.sample_size(10);
+    for format in [RouteCacheFormat::Json, RouteCacheFormat::Binary] {
+        let bytes = format.encode(&cache).expect("encode route cache");
+        println!("{:?}: {} bytes for {} routes", format, bytes.len(), TOKENS * ROUTES_PER_TOKEN);
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("decode", format!("{:?}", format)), &bytes, |b, bytes| {
+            b.iter(|| RouteCacheFormat::decode(black_box(bytes)).expect("decode route cache"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_route_cache_load);
+criterion_main!(benches);
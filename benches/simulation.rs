@@ -0,0 +1,217 @@
+use arb_rust_bot::arbitrage_finder::find_arbitrage_opportunity_blocking;
+use arb_rust_bot::cache::{PoolState, PoolType, ReserveCache};
+use arb_rust_bot::config::Config;
+use arb_rust_bot::opportunity::DecodedSwap;
+use arb_rust_bot::price_oracle::{seed_prices, PriceOracle};
+use arb_rust_bot::route_cache::{DEXType, RoutePath};
+use arb_rust_bot::simulate_swap_path::{
+    simulate_buy_path_amounts_array, simulate_sell_path_amounts_array,
+};
+use arb_rust_bot::split_route_path::{precompute_route_legs, route_legs};
+use arb_rust_bot::token_index::TokenIndexMap;
+use arb_rust_bot::token_tax::TokenTaxMap;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dashmap::DashMap;
+use ethers::types::{H160, U256};
+use std::sync::Arc;
+use std::time::Instant;
+
+const ROUTES_PER_TOKEN: u64 = 200;
+
+// Same allocator as the bot, so `--features mimalloc|jemalloc` benchmarks what would ship
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[derive(Clone, Copy)]
+enum Market {
+    V2Only,
+    V3Only,
+    Mixed,
+}
+
+impl Market {
+    fn name(&self) -> &'static str {
+        match self {
+            Market::V2Only => "v2",
+            Market::V3Only => "v3",
+            Market::Mixed => "mixed",
+        }
+    }
+
+    /// Pool type of the i-th pool in the synthetic market
+    fn pool_type(&self, i: u64) -> PoolType {
+        match self {
+            Market::V2Only => PoolType::V2,
+            Market::V3Only => PoolType::V3,
+            Market::Mixed if i % 2 == 0 => PoolType::V2,
+            Market::Mixed => PoolType::V3,
+        }
+    }
+}
+
+struct Fixture {
+    reserve_cache: Arc<ReserveCache>,
+    token_index: Arc<TokenIndexMap>,
+    route_cache: Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
+    token_tax_map: Arc<TokenTaxMap>,
+    config: Config,
+    price_oracle: PriceOracle,
+    token_x: u32,
+    token_x_address: H160,
+    affected_pool: H160,
+}
+
+fn pool(pool_type: PoolType, token0: H160, token1: H160) -> PoolState {
+    let deep = U256::exp10(24);
+    match pool_type {
+        PoolType::V2 => PoolState {
+            pool_type: PoolType::V2,
+            token0,
+            token1,
+            reserve0: Some(deep),
+            reserve1: Some(deep + U256::exp10(21)),
+            dex_name: Some("PancakeSwap V2".to_string()),
+            ..Default::default()
+        },
+        PoolType::V3 => PoolState {
+            pool_type: PoolType::V3,
+            token0,
+            token1,
+            sqrt_price_x96: Some(U256::one() << 96),
+            liquidity: Some(deep),
+            tick: Some(0),
+            fee: Some(2500),
+            tick_spacing: Some(50),
+            dex_name: Some("PancakeSwap V3".to_string()),
+            ..Default::default()
+        },
+        PoolType::StableN { .. } => unreachable!("markets only mix V2 and V3 pools"),
+    }
+}
+
+fn dex_type(pool_type: &PoolType) -> DEXType {
+    match pool_type {
+        PoolType::V2 => DEXType::PancakeV2,
+        PoolType::V3 => DEXType::PancakeV3,
+        PoolType::StableN { .. } => DEXType::StableSwap,
+    }
+}
+
+/// Base → X (shared affected pool) → Y_i → base, for ROUTES_PER_TOKEN distinct Y tokens
+fn build_fixture(market: Market) -> Fixture {
+    let config = Config::default();
+    // An enabled base token with a seed price, so detection simulates the routes from it
+    let base = config.get_base_token_by_symbol("WBNB").unwrap().address;
+    let token_x = H160::from_low_u64_be(2);
+    let affected_pool = H160::from_low_u64_be(10_000);
+
+    let reserve_cache = ReserveCache::new();
+    reserve_cache.insert(affected_pool, pool(market.pool_type(0), base, token_x));
+    let mut pool_specs = Vec::new();
+    for i in 0..ROUTES_PER_TOKEN {
+        let token_y = H160::from_low_u64_be(100 + i);
+        let xy = H160::from_low_u64_be(20_000 + i);
+        let yb = H160::from_low_u64_be(30_000 + i);
+        let xy_type = market.pool_type(i + 1);
+        let yb_type = market.pool_type(i);
+        reserve_cache.insert(xy, pool(xy_type.clone(), token_x, token_y));
+        reserve_cache.insert(yb, pool(yb_type.clone(), token_y, base));
+        pool_specs.push((token_y, xy, yb, xy_type, yb_type));
+    }
+
+    let token_index = TokenIndexMap::build_from_reserve_cache(&reserve_cache);
+    let idx = |t: &H160| token_index.address_to_index[t];
+    let affected_type = market.pool_type(0);
+    let routes: Vec<RoutePath> = pool_specs
+        .iter()
+        .map(|(token_y, xy, yb, xy_type, yb_type)| RoutePath {
+            hops: vec![idx(&base), idx(&token_x), idx(token_y), idx(&base)],
+            pools: vec![affected_pool, *xy, *yb],
+            dex_types: vec![dex_type(&affected_type), dex_type(xy_type), dex_type(yb_type)],
+            ..Default::default()
+        })
+        .collect();
+    let token_x_idx = idx(&token_x);
+    let route_cache = DashMap::new();
+    route_cache.insert(token_x_idx, Arc::new(routes));
+    // Legs are precomputed the way the bot's route cache is built
+    precompute_route_legs(&route_cache);
+    let price_oracle = PriceOracle::new(seed_prices());
+    price_oracle.set_gas_price(U256::from(config.gas_price));
+
+    Fixture {
+        reserve_cache: Arc::new(reserve_cache),
+        token_index: Arc::new(token_index),
+        route_cache: Arc::new(route_cache),
+        token_tax_map: Arc::new(TokenTaxMap::new()),
+        config,
+        price_oracle,
+        token_x: token_x_idx,
+        token_x_address: token_x,
+        affected_pool,
+    }
+}
+
+fn bench_route_simulation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("route_simulation");
+    let amount = U256::exp10(18);
+    for market in [Market::V2Only, Market::V3Only, Market::Mixed] {
+        let f = build_fixture(market);
+        let route = f.route_cache.get(&f.token_x).unwrap()[0].clone();
+        let legs = route_legs(&route, f.token_x).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(market.name()), &market, |b, _| {
+            b.iter(|| {
+                let buy = simulate_buy_path_amounts_array(
+                    black_box(&legs.buy), amount, &f.reserve_cache, &f.token_index, &f.token_tax_map, &f.config,
+                );
+                let sell = simulate_sell_path_amounts_array(
+                    black_box(&legs.sell), amount, &f.reserve_cache, &f.token_index, &f.token_tax_map, &f.config,
+                );
+                black_box((buy, sell))
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Full detection for one swap on the affected pool, as the price tracker and IPC listener run
+/// it: route filtering, quote cache, gas and profit floors and the detection budget included.
+/// The swap repeats unchanged, so after the first iteration quotes come from the quote cache
+/// the way back-to-back events on an unchanged pool would.
+fn bench_opportunity_detection(c: &mut Criterion) {
+    let mut group = c.benchmark_group("opportunity_detection");
+    for market in [Market::V2Only, Market::V3Only, Market::Mixed] {
+        let f = build_fixture(market);
+        let decoded_swap = DecodedSwap {
+            pool_address: f.affected_pool,
+            token_x: f.token_x_address,
+            token_x_amount: U256::exp10(18),
+            block_number: 1,
+            ..Default::default()
+        };
+        group.bench_with_input(BenchmarkId::new(market.name(), ROUTES_PER_TOKEN), &market, |b, _| {
+            b.iter(|| {
+                black_box(find_arbitrage_opportunity_blocking(
+                    Instant::now(),
+                    black_box(&decoded_swap),
+                    &f.reserve_cache,
+                    &f.token_index,
+                    &f.route_cache,
+                    &f.token_tax_map,
+                    &f.config,
+                    &f.config.route_filter,
+                    &f.price_oracle,
+                ))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_route_simulation, bench_opportunity_detection);
+criterion_main!(benches);
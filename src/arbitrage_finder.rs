@@ -1,38 +1,247 @@
 // File: src/arb_path_simulator.rs
 
-use crate::route_cache::RoutePath;
+use crate::route_cache::{RouteFilter, RoutePath};
 use crate::cache::ReserveCache;
 use crate::token_index::TokenIndexMap;
-use crate::simulate_swap_path::{simulate_buy_path_amounts_array, simulate_sell_path_amounts_array};
-use crate::split_route_path::split_route_around_token_x;
-use crate::token_tax::TokenTaxMap;
-use crate::config::Config;
+use crate::simulate_swap_path::{
+    apply_path_to_snapshot, simulate_buy_path_amounts_array, simulate_buy_path_amounts_into, simulate_sell_path_amounts_array,
+    simulate_sell_path_amounts_into, snapshot_pools, u256_to_f64_lossy, with_route_sim_buffers,
+};
+use crate::split_route_path::route_legs;
+use crate::token_tax::{tax_detector, TokenTaxMap};
+use crate::config::{Config, RouteTieBreak};
+use crate::cache::PoolType;
+use crate::executor::gas_cost_in_base_token;
+use crate::latency_metrics::{self, Stage};
+use crate::opportunity::{ArbitrageOpportunity, DecodedSwap};
+use crate::opportunity_queue::next_opportunity_id;
+use crate::price_oracle::PriceOracle;
+use crate::quote_cache::{quote_live, QuoteKind};
+use crate::token_activity::{token_activity, unix_now, IDLE_TOKEN_SKIPS};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use dashmap::DashMap;
 use ethers::types::{H160, U256};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
 /// Result of simulating a full arbitrage path (buy+sell) in router-style amounts array
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SimulatedRoute {
     pub merged_amounts: Vec<U256>,
     pub buy_amounts: Vec<U256>,      // [baseIn, ..., tokenX, ..., baseOut]
     pub sell_amounts: Vec<U256>,      // [baseIn, ..., tokenX, ..., baseOut]
     // pub merged_tokens: Vec<u32>,        // token indices for each hop
     // pub merged_symbols: Vec<String>,    // human-readable token symbols (if available)
-    pub buy_symbols: Vec<String>,    // human-readable token symbols (if available)
-    pub sell_symbols: Vec<String>,    // human-readable token symbols (if available)
+    pub buy_tokens: Vec<H160>,       // token address for each buy hop
+    pub sell_tokens: Vec<H160>,      // token address for each sell hop
     pub buy_pools: Vec<H160>,        // pool addresses for each hop
     pub sell_pools: Vec<H160>,        // pool addresses for each hop
 
     pub merged_pools: Vec<H160>,        // pool addresses for each hop
-    pub profit: U256,                   // baseOut - baseIn
+    pub profit: U256,                   // baseOut - baseIn, in the buy-side base token (see `normalized_profit`)
     pub profit_percentage: f64,         // (profit / amount_in) * 100
     pub buy_path: RoutePath,
     pub sell_path: RoutePath,
     // pub sell_test_amounts: Vec<U256>,
 }
 
+impl SimulatedRoute {
+    /// Display form (`0x…`) of the buy hop tokens; only built when logging
+    pub fn buy_symbols(&self) -> Vec<String> {
+        self.buy_tokens.iter().map(|t| format!("0x{:x}", t)).collect()
+    }
+
+    /// Display form (`0x…`) of the sell hop tokens; only built when logging
+    pub fn sell_symbols(&self) -> Vec<String> {
+        self.sell_tokens.iter().map(|t| format!("0x{:x}", t)).collect()
+    }
+
+    /// Simulate buying then selling `token_x_amount` of tokenX along the given paths.
+    /// Profit is `amount_out - amount_in`, so the paths must form a cycle.
+    pub fn simulate(
+        buy_path: &RoutePath,
+        sell_path: &RoutePath,
+        token_x_amount: U256,
+        reserve_cache: &ReserveCache,
+        token_index: &TokenIndexMap,
+        token_tax_map: &Arc<TokenTaxMap>,
+        config: &Config,
+    ) -> Option<Self> {
+        let buy_amounts = simulate_buy_path_amounts_array(buy_path, token_x_amount, reserve_cache, token_index, token_tax_map, config)?;
+        let sell_amounts = simulate_sell_path_amounts_array(sell_path, token_x_amount, reserve_cache, token_index, token_tax_map, config)?;
+        let mut merged_amounts = buy_amounts.clone();
+        merged_amounts.extend_from_slice(sell_amounts.get(1..).unwrap_or(&[]));
+        let (amount_in, amount_out) = (*buy_amounts.first()?, *sell_amounts.last()?);
+        let profit = amount_out.saturating_sub(amount_in);
+        let profit_percentage = if amount_in > U256::zero() {
+            u256_to_f64_lossy(&profit) / u256_to_f64_lossy(&amount_in) * 100.0
+        } else {
+            0.0
+        };
+        let mut merged_pools = buy_path.pools.clone();
+        merged_pools.extend_from_slice(&sell_path.pools);
+        Some(Self {
+            merged_amounts,
+            buy_amounts,
+            sell_amounts,
+            buy_tokens: hop_tokens(&buy_path.hops, token_index)?,
+            sell_tokens: hop_tokens(&sell_path.hops, token_index)?,
+            buy_pools: buy_path.pools.clone(),
+            sell_pools: sell_path.pools.clone(),
+            merged_pools,
+            profit,
+            profit_percentage,
+            buy_path: buy_path.clone(),
+            sell_path: sell_path.clone(),
+        })
+    }
+
+    /// tokenX amount the route was simulated with
+    pub fn token_x_amount(&self) -> U256 {
+        self.sell_amounts.first().copied().unwrap_or_default()
+    }
+}
+
+/// Split `token_x_amount` across up to `max_routes` profitable routes that share no pools and
+/// start from the same base token as the most profitable one. The amount is handed out in `steps`
+/// equal chunks, each going to the route whose profit grows most from it; since every route's
+/// profit is concave in size (price impact), this maximizes the aggregate. Routes left without an
+/// allocation are dropped, and each returned route is re-simulated at its share. Cross-base
+/// routes are left out since the re-simulation can't price them.
+pub fn plan_split_routes(
+    routes: &[SimulatedRoute],
+    token_x_amount: U256,
+    max_routes: usize,
+    steps: usize,
+    reserve_cache: &ReserveCache,
+    token_index: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+) -> Vec<SimulatedRoute> {
+    let mut ranked: Vec<&SimulatedRoute> = routes
+        .iter()
+        .filter(|r| !r.profit.is_zero() && r.buy_path.hops.first() == r.sell_path.hops.last())
+        .collect();
+    ranked.sort_by(|a, b| b.profit.cmp(&a.profit));
+    let Some(base) = ranked.first().and_then(|r| r.buy_path.hops.first().copied()) else {
+        return Vec::new();
+    };
+
+    // Top routes by profit with the same base and no pool in common
+    let mut candidates: Vec<&SimulatedRoute> = Vec::new();
+    let mut used_pools = std::collections::HashSet::new();
+    for route in ranked {
+        if candidates.len() >= max_routes.max(1) {
+            break;
+        }
+        if route.buy_path.hops.first() != Some(&base) || route.merged_pools.iter().any(|p| used_pools.contains(p)) {
+            continue;
+        }
+        used_pools.extend(route.merged_pools.iter().copied());
+        candidates.push(route);
+    }
+
+    // Greedy marginal allocation in units of token_x_amount / steps
+    let steps = steps.max(1);
+    let amount_for = |units: usize| token_x_amount * U256::from(units) / U256::from(steps);
+    let mut units = vec![0usize; candidates.len()];
+    let mut planned: Vec<Option<SimulatedRoute>> = vec![None; candidates.len()];
+    for _ in 0..steps {
+        let best = candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, route)| {
+                let next = SimulatedRoute::simulate(
+                    &route.buy_path,
+                    &route.sell_path,
+                    amount_for(units[i] + 1),
+                    reserve_cache,
+                    token_index,
+                    token_tax_map,
+                    config,
+                )?;
+                let current = planned[i].as_ref().map(|r| r.profit).unwrap_or_default();
+                let gain = next.profit.saturating_sub(current);
+                (!gain.is_zero()).then_some((i, gain, next))
+            })
+            .max_by(|a, b| a.1.cmp(&b.1));
+        let Some((i, _, next)) = best else { break };
+        units[i] += 1;
+        planned[i] = Some(next);
+    }
+    planned.into_iter().flatten().collect()
+}
+
+/// Rough gas of one swap hop by pool type, only used to rank otherwise tied routes
+fn estimated_hop_gas(pool_type: Option<&PoolType>) -> u64 {
+    match pool_type {
+        Some(PoolType::V3) => 110_000,
+        Some(PoolType::StableN { .. }) => 140_000,
+        Some(PoolType::V2) | None => 65_000,
+    }
+}
+
+/// Liquidity of the route's shallowest pool: `sqrt(reserve0 * reserve1)` for V2 (the same L as
+/// V3 `liquidity`), the mean balance for stable pools, 0 for pools missing from the cache
+fn bottleneck_liquidity(route: &SimulatedRoute, reserve_cache: &ReserveCache) -> f64 {
+    route
+        .merged_pools
+        .iter()
+        .map(|pool| {
+            let Some(state) = reserve_cache.get(pool) else { return 0.0 };
+            match &state.pool_type {
+                PoolType::V2 => {
+                    let r0 = state.reserve0.map(|r| u256_to_f64_lossy(&r)).unwrap_or_default();
+                    let r1 = state.reserve1.map(|r| u256_to_f64_lossy(&r)).unwrap_or_default();
+                    (r0 * r1).sqrt()
+                }
+                PoolType::V3 => state.liquidity.map(|l| u256_to_f64_lossy(&l)).unwrap_or_default(),
+                PoolType::StableN { balances, .. } if !balances.is_empty() => {
+                    balances.iter().map(u256_to_f64_lossy).sum::<f64>() / balances.len() as f64
+                }
+                PoolType::StableN { .. } => 0.0,
+            }
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Highest profit percentage wins; exact ties are settled by `tie_break` in order, and routes
+/// still tied after that keep the first one in `routes`, so selection doesn't flip between runs
+pub fn pick_best_route<'a>(
+    routes: &'a [SimulatedRoute],
+    reserve_cache: &ReserveCache,
+    tie_break: &[RouteTieBreak],
+) -> Option<&'a SimulatedRoute> {
+    use std::cmp::Ordering;
+    // Orders "better" as Greater, so the best route is the max
+    let rank = |a: &SimulatedRoute, b: &SimulatedRoute| -> Ordering {
+        let by_profit = a.profit_percentage.partial_cmp(&b.profit_percentage).unwrap_or(Ordering::Equal);
+        tie_break.iter().fold(by_profit, |order, criterion| {
+            order.then_with(|| match criterion {
+                RouteTieBreak::FewerHops => b.merged_pools.len().cmp(&a.merged_pools.len()),
+                RouteTieBreak::HigherLiquidity => bottleneck_liquidity(a, reserve_cache)
+                    .partial_cmp(&bottleneck_liquidity(b, reserve_cache))
+                    .unwrap_or(Ordering::Equal),
+                RouteTieBreak::LowerGas => {
+                    let gas = |r: &SimulatedRoute| -> u64 {
+                        r.merged_pools.iter().map(|p| estimated_hop_gas(reserve_cache.get(p).as_deref().map(|s| &s.pool_type))).sum()
+                    };
+                    gas(b).cmp(&gas(a))
+                }
+            })
+        })
+    };
+    routes.iter().reduce(|best, route| if rank(route, best) == Ordering::Greater { route } else { best })
+}
+
+/// Map token indices to addresses; None if any index is unknown
+pub fn hop_tokens(hops: &[u32], token_index: &TokenIndexMap) -> Option<Vec<H160>> {
+    hops.iter().map(|idx| token_index.index_to_address.get(idx).copied()).collect()
+}
+
 /// Helper to map token index to symbol (extend as needed)
 pub fn token_index_to_symbol(idx: u32, token_index: &TokenIndexMap) -> String {
     // Try to get address, then symbol from config or fallback
@@ -44,34 +253,113 @@ pub fn token_index_to_symbol(idx: u32, token_index: &TokenIndexMap) -> String {
     }
 }
 
-/// Simulate all arbitrage paths for tokenX and affected pool, returning router-style merged arrays
+/// Profit of a route in its input base token. For a cycle this is `amount_out - amount_in`.
+/// For a cross-base route (`base_in -> X -> base_out`) the output is first converted into
+/// `base_in` at the USD prices from `price_oracle`, so profit is compared in one numeraire
+/// and stays directly comparable with gas cost and `max_input_per_base`, which are also in
+/// the input base. Base tokens are assumed to use 18 decimals. `None` when a cross-base
+/// route can't be priced.
+pub fn normalized_profit(
+    amount_in: U256,
+    base_in: &H160,
+    amount_out: U256,
+    base_out: &H160,
+    price_oracle: impl Fn(&H160) -> Option<f64>,
+) -> Option<U256> {
+    if base_in == base_out {
+        return Some(amount_out.saturating_sub(amount_in));
+    }
+    let in_usd = price_oracle(base_in)?;
+    let out_usd = price_oracle(base_out)?;
+    if in_usd <= 0.0 || out_usd <= 0.0 {
+        return None;
+    }
+    // Same 1e9 scaled integer rate as `gas_cost_in_base_token`
+    let rate_scaled = ((out_usd / in_usd) * 1e9) as u128;
+    let out_in_base = amount_out.saturating_mul(U256::from(rate_scaled)) / U256::from(1_000_000_000u64);
+    Some(out_in_base.saturating_sub(amount_in))
+}
+
+/// Simulate all arbitrage paths for tokenX and affected pool, returning router-style merged arrays.
+/// Taxes, DEX fees, `route_filter` and `sequential_leg_simulation` are applied as in the price
+/// tracker's detection, so both agree on the same input; unlike it, zero-profit routes are kept
+/// and cross-base routes skipped.
 pub fn simulate_all_paths_for_token_x(
     token_x_index: u32,
     token_x_amount: U256,
     affected_pool: H160,
-    route_cache: &DashMap<u32, Vec<RoutePath>>,
+    route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>,
+    reserve_cache: &ReserveCache,
+    token_index: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+) -> Vec<SimulatedRoute> {
+    simulate_routes_for_token_x(token_x_index, token_x_amount, Some(affected_pool), route_cache, reserve_cache, token_index, token_tax_map, config)
+}
+
+/// `simulate_all_paths_for_token_x` over every cached route of tokenX, whichever pools it uses
+pub fn simulate_all_routes_for_token_x(
+    token_x_index: u32,
+    token_x_amount: U256,
+    route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>,
+    reserve_cache: &ReserveCache,
+    token_index: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+) -> Vec<SimulatedRoute> {
+    simulate_routes_for_token_x(token_x_index, token_x_amount, None, route_cache, reserve_cache, token_index, token_tax_map, config)
+}
+
+fn simulate_routes_for_token_x(
+    token_x_index: u32,
+    token_x_amount: U256,
+    affected_pool: Option<H160>,
+    route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>,
     reserve_cache: &ReserveCache,
     token_index: &TokenIndexMap,
     token_tax_map: &Arc<TokenTaxMap>,
     config: &Config,
 ) -> Vec<SimulatedRoute> {
+    // A refcount on the entry, so its shard lock is released before simulating
     let candidate_routes = route_cache
         .get(&token_x_index)
         .map(|entry| entry.value().clone())
         .unwrap_or_default();
 
     candidate_routes
-        .into_par_iter()
+        .par_iter()
         .filter_map(|route| {
-            if !route.pools.contains(&affected_pool) {
+            if affected_pool.is_some_and(|pool| !route.pools.contains(&pool)) || !config.route_filter.allows(&route, token_index) {
                 return None;
             }
-            let (buy_path, sell_path) = match split_route_around_token_x(&route, token_x_index) {
-                Some(parts) => parts,
-                None => return None,
-            };
+            let base = route.hops.first().and_then(|idx| token_index.index_to_address.get(idx))?;
+            if !config.is_base_token_enabled(base) {
+                return None;
+            }
+            // Cross-base routes need a price oracle to compare output with input;
+            // the price tracker's detection prices them
+            if !route.is_cycle() {
+                return None;
+            }
+            if !route_taxes_known(&route, token_index) {
+                return None;
+            }
+            // Same leg handling as the price tracker: a pool in both legs needs sequential simulation
+            let legs = route_legs(&route, token_x_index)?;
+            if legs.shares_pool && !config.sequential_leg_simulation {
+                return None;
+            }
+            let (buy_path, sell_path) = (&legs.buy, &legs.sell);
             let buy_amounts = simulate_buy_path_amounts_array(&buy_path, token_x_amount, reserve_cache, token_index, token_tax_map, config)?;
-            let sell_amounts = simulate_sell_path_amounts_array(&sell_path, token_x_amount, reserve_cache, token_index, token_tax_map, config)?;
+            // Sell what the buy actually delivers, against the pools as the buy leaves them
+            let token_x_out = *buy_amounts.last()?;
+            let sell_amounts = if config.sequential_leg_simulation {
+                let snapshot = snapshot_pools(&[buy_path, sell_path], reserve_cache);
+                apply_path_to_snapshot(buy_path, &buy_amounts, &snapshot, token_index)?;
+                simulate_sell_path_amounts_array(&sell_path, token_x_out, &snapshot, token_index, token_tax_map, config)?
+            } else {
+                simulate_sell_path_amounts_array(&sell_path, token_x_out, reserve_cache, token_index, token_tax_map, config)?
+            };
             if buy_amounts.is_empty() || sell_amounts.is_empty() {
                 return None;
             }
@@ -79,10 +367,12 @@ pub fn simulate_all_paths_for_token_x(
             let mut merged_amounts = buy_amounts.clone();
             merged_amounts.extend_from_slice(&sell_amounts[1..]);
             // Defensive checks for overflow/underflow
-            if merged_amounts.len() < 2
-                || merged_amounts[0].is_zero()
-                || merged_amounts.last().unwrap().is_zero()
-                || merged_amounts.iter().any(|x| x.bits() > 128 && *x > U256::from_dec_str("1000000000000000000000000000000000000000").unwrap())
+            let [amount_in, .., amount_out] = merged_amounts[..] else {
+                return None;
+            };
+            if amount_in.is_zero()
+                || amount_out.is_zero()
+                || merged_amounts.iter().any(|x| x.bits() > 128 && *x > U256::exp10(39))
             {
                 println!("⚠️  Skipping path due to invalid or suspicious amounts: {:?}", merged_amounts);
                 return None;
@@ -104,12 +394,12 @@ pub fn simulate_all_paths_for_token_x(
             let mut merged_pools = buy_path.pools.clone();
             merged_pools.extend_from_slice(&sell_path.pools);
             // Profit: last - first (saturating to avoid panic)
-            let profit = merged_amounts.last().unwrap().saturating_sub(merged_amounts[0]);
+            let profit = amount_out.saturating_sub(amount_in);
             
             // Calculate profit percentage
-            let profit_percentage = if merged_amounts[0] > U256::zero() {
-                let profit_f64 = profit.as_u128() as f64;
-                let amount_in_f64 = merged_amounts[0].as_u128() as f64;
+            let profit_percentage = if amount_in > U256::zero() {
+                let profit_f64 = u256_to_f64_lossy(&profit);
+                let amount_in_f64 = u256_to_f64_lossy(&amount_in);
                 (profit_f64 / amount_in_f64) * 100.0
             } else {
                 0.0
@@ -121,8 +411,8 @@ pub fn simulate_all_paths_for_token_x(
                 sell_amounts,
                 // merged_tokens,
                 // merged_symbols,
-                buy_symbols: buy_path.hops.iter().map(|&idx| token_index_to_symbol(idx, token_index)).collect(),
-                sell_symbols: sell_path.hops.iter().map(|&idx| token_index_to_symbol(idx, token_index)).collect(),
+                buy_tokens: hop_tokens(&buy_path.hops, token_index)?,
+                sell_tokens: hop_tokens(&sell_path.hops, token_index)?,
                 merged_pools: merged_pools.clone(),
                 buy_pools: buy_path.pools.clone(),
                 sell_pools: sell_path.pools.clone(),
@@ -148,3 +438,773 @@ pub fn simulate_all_paths_for_token_x(
 //         route.profit_percentage
 //     );
 // }
+
+/// Detections that ran out of `max_detection_us` before simulating every candidate route
+pub static DETECTION_BUDGET_EXHAUSTED: AtomicU64 = AtomicU64::new(0);
+/// Profitable routes whose profit doesn't cover their estimated gas
+pub static REJECTED_BY_GAS: AtomicU64 = AtomicU64::new(0);
+/// Profitable routes rejected by `min_net_profit_usd` / `min_profit_bps`
+pub static REJECTED_BY_MIN_USD: AtomicU64 = AtomicU64::new(0);
+pub static REJECTED_BY_MIN_BPS: AtomicU64 = AtomicU64::new(0);
+
+/// Profit floor a route missed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfitFloor {
+    Usd, // `min_net_profit_usd`
+    Bps, // `min_profit_bps`
+}
+
+impl ProfitFloor {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProfitFloor::Usd => "min_net_profit_usd",
+            ProfitFloor::Bps => "min_profit_bps",
+        }
+    }
+
+    fn counter(&self) -> &'static AtomicU64 {
+        match self {
+            ProfitFloor::Usd => &REJECTED_BY_MIN_USD,
+            ProfitFloor::Bps => &REJECTED_BY_MIN_BPS,
+        }
+    }
+}
+
+/// First floor the route misses (USD checked first), or None if it clears both
+pub fn profit_floor_rejection(profit_usd: f64, profit_bps: f64, config: &Config) -> Option<ProfitFloor> {
+    if profit_usd < config.min_net_profit_usd {
+        Some(ProfitFloor::Usd)
+    } else if profit_bps < config.min_profit_bps {
+        Some(ProfitFloor::Bps)
+    } else {
+        None
+    }
+}
+
+/// Gas of a `pools`-swap route (`gas_per_hop` each at `gas_price`) in `base` units, Some(0)
+/// when `gas_per_hop` is 0. The one estimate detection, the send gate and the queue all use.
+pub fn route_gas_cost(
+    pools: usize,
+    base: H160,
+    gas_price: U256,
+    config: &Config,
+    price_oracle: impl Fn(&H160) -> Option<f64>,
+) -> Option<U256> {
+    if config.gas_per_hop == 0 {
+        return Some(U256::zero());
+    }
+    let gas_used = U256::from(config.gas_per_hop).saturating_mul(U256::from(pools));
+    gas_cost_in_base_token(gas_used, gas_price, base, price_oracle)
+}
+
+/// `profit` minus `route_gas_cost`, both in `base` units. None if gas takes all of it, or
+/// can't be priced in `base`.
+pub fn profit_after_gas(
+    profit: U256,
+    pools: usize,
+    base: H160,
+    gas_price: U256,
+    config: &Config,
+    price_oracle: impl Fn(&H160) -> Option<f64>,
+) -> Option<U256> {
+    if config.gas_per_hop == 0 {
+        return Some(profit);
+    }
+    let gas_cost = route_gas_cost(pools, base, gas_price, config, price_oracle)?;
+    profit.checked_sub(gas_cost).filter(|net| !net.is_zero())
+}
+
+/// Point after which detection stops starting new route simulations (`None` = no budget)
+fn detection_deadline(start: Instant, max_detection_us: u64) -> Option<Instant> {
+    (max_detection_us > 0).then(|| start + std::time::Duration::from_micros(max_detection_us))
+}
+
+/// Find arbitrage opportunities for a decoded swap; shared by the WS price tracker and the IPC listener.
+/// The rayon simulation runs on the blocking pool so it never stalls WS reads or tx sends
+/// on the async runtime. Routes not yet started when `config.max_detection_us` runs out
+/// are skipped, so the result is the best of the routes simulated within the budget.
+pub async fn find_arbitrage_opportunity(
+    decoded_swap: &DecodedSwap,
+    reserve_cache: &Arc<ReserveCache>,
+    token_index: &Arc<TokenIndexMap>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Arc<Config>, // routes are filtered with its `route_filter`
+    price_oracle: &Arc<PriceOracle>,
+) -> Option<(ArbitrageOpportunity, u128)> {
+    // Start latency timer; time spent queued for a blocking thread counts against the budget
+    let start_time = std::time::Instant::now();
+    // Only reference counts are bumped here; the caches and config are shared with the blocking thread
+    let (decoded_swap, reserve_cache, token_index, precomputed_route_cache, token_tax_map, config, price_oracle) = (
+        decoded_swap.clone(),
+        reserve_cache.clone(),
+        token_index.clone(),
+        precomputed_route_cache.clone(),
+        token_tax_map.clone(),
+        config.clone(),
+        price_oracle.clone(),
+    );
+    let result = tokio::task::spawn_blocking(move || {
+        latency_metrics::record(Stage::Handoff, start_time.elapsed());
+        find_arbitrage_opportunity_blocking(
+            start_time,
+            &decoded_swap,
+            &reserve_cache,
+            &token_index,
+            &precomputed_route_cache,
+            &token_tax_map,
+            &config,
+            &config.route_filter,
+            &price_oracle,
+        )
+    })
+    .await
+    .unwrap_or_else(|e| {
+        eprintln!("❌ [DETECT] Route simulation task failed: {}", e);
+        None
+    });
+    latency_metrics::record(Stage::Total, start_time.elapsed());
+    result
+}
+
+/// Whether every token on `route` has a known tax. A token missing from the tax map isn't
+/// assumed untaxed: it's probed in the background and the route waits for the result.
+/// Without a tax detector every route passes.
+fn route_taxes_known(route: &RoutePath, token_index: &TokenIndexMap) -> bool {
+    let Some(detector) = tax_detector() else {
+        return true;
+    };
+    route
+        .hops
+        .iter()
+        .all(|idx| token_index.index_to_address.get(idx).is_some_and(|t| detector.is_known_or_detect(*t)))
+}
+
+pub fn find_arbitrage_opportunity_blocking(
+    start_time: Instant,
+    decoded_swap: &DecodedSwap,
+    reserve_cache: &Arc<ReserveCache>,
+    token_index: &Arc<TokenIndexMap>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+    route_filter: &RouteFilter,
+    price_oracle: &PriceOracle,
+) -> Option<(ArbitrageOpportunity, u128)> {
+    let usd_price = |token: &H160| price_oracle.get_token_usd_value(token);
+    let gas_price = price_oracle.gas_price();
+    let deadline = detection_deadline(start_time, config.max_detection_us);
+    let skipped = AtomicUsize::new(0);
+    // Tokens that haven't been profitable for a long time are only probed now and then
+    let now = unix_now();
+    let activity = token_activity().filter(|_| config.idle_token_window_secs > 0);
+    if activity.is_some_and(|a| !a.should_simulate(decoded_swap.token_x, now, config)) {
+        IDLE_TOKEN_SKIPS.fetch_add(1, Ordering::Relaxed);
+        return None;
+    }
+    // Get token index
+    let token_x_index = token_index.address_to_index.get(&decoded_swap.token_x)?;
+    let token_x_index_u32 = *token_x_index as u32;
+
+    // println!(
+    //     "🔍 [Price Tracker] Finding arbitrage for tokenX (idx {}): {}",
+    //     token_x_index, decoded_swap.token_x
+    // );
+
+    // Candidate routes for the token. The entry's Arc is cloned out of the map so its shard
+    // lock isn't held while they simulate (route cache writers would wait on it)
+    let Some(candidate_routes) = precomputed_route_cache.get(&token_x_index_u32).map(|entry| entry.value().clone()) else {
+        latency_metrics::record(Stage::Search, start_time.elapsed());
+        return None;
+    };
+
+    // println!(
+    //     "📊 [Price Tracker] Found {} candidate routes for tokenX",
+    //     candidate_routes.len()
+    // );
+
+    // Filter routes that contain the affected pool, pass the caller's filter and start from an enabled base token
+    let filtered_routes: Vec<&RoutePath> = candidate_routes
+        .iter()
+        .filter(|route| route.pools.contains(&decoded_swap.pool_address))
+        .filter(|route| route_filter.allows(route, token_index))
+        .filter(|route| {
+            route
+                .hops
+                .first()
+                .and_then(|idx| token_index.index_to_address.get(idx))
+                .is_some_and(|base| config.is_base_token_enabled(base))
+        })
+        .filter(|route| route_taxes_known(route, token_index))
+        .collect();
+    let search_done = Instant::now();
+    latency_metrics::record(Stage::Search, search_done - start_time);
+
+    if filtered_routes.is_empty() {
+        return None;
+    }
+
+    // Simulate all filtered routes in parallel. Amounts go into per-thread scratch buffers;
+    // owned Vecs are only built for routes that pass the profit check.
+    let profitable_routes: Vec<SimulatedRoute> = filtered_routes
+        .par_iter()
+        .filter_map(|route| {
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                skipped.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            // Buy/sell legs, precomputed when the route cache was built. With sequential
+            // simulation a pool may appear in both legs, since the sell leg then sees the
+            // buy's effect on it.
+            let legs = route_legs(route, token_x_index_u32)?;
+            if legs.shares_pool && !config.sequential_leg_simulation {
+                return None;
+            }
+            let (buy_path, sell_path) = (&legs.buy, &legs.sell);
+
+            with_route_sim_buffers(|buy_amounts, sell_amounts| {
+                // Simulate buy path (base -> tokenX). Quotes against the live cache may be
+                // served from the quote cache, for a bucketed tokenX amount.
+                quote_live(buy_path, QuoteKind::ExactOut, decoded_swap.token_x_amount, buy_amounts, |amount, out| {
+                    simulate_buy_path_amounts_into(buy_path, amount, reserve_cache, token_index, token_tax_map, config, out)
+                })?;
+                let token_x_amount = *buy_amounts.last()?;
+
+                // Per-opportunity snapshot with the buy applied, or the shared cache as-is
+                let post_buy = if config.sequential_leg_simulation {
+                    let snapshot = snapshot_pools(&[buy_path, sell_path], reserve_cache);
+                    apply_path_to_snapshot(buy_path, buy_amounts, &snapshot, token_index)?;
+                    Some(snapshot)
+                } else {
+                    None
+                };
+
+                // Simulate sell path (tokenX -> base); snapshots bypass the quote cache
+                match &post_buy {
+                    Some(snapshot) => simulate_sell_path_amounts_into(
+                        sell_path,
+                        token_x_amount,
+                        snapshot,
+                        token_index,
+                        token_tax_map,
+                        config,
+                        sell_amounts,
+                    ),
+                    None => quote_live(sell_path, QuoteKind::ExactIn, token_x_amount, sell_amounts, |amount, out| {
+                        simulate_sell_path_amounts_into(sell_path, amount, reserve_cache, token_index, token_tax_map, config, out)
+                    }),
+                }?;
+
+                // Merged amounts are [buy_amounts..., sell_amounts[1..]]
+                if buy_amounts.len() + sell_amounts.len() < 3 {
+                    return None;
+                }
+                let amount_in = *buy_amounts.first()?;
+                let amount_out = if sell_amounts.len() > 1 {
+                    *sell_amounts.last()?
+                } else {
+                    *buy_amounts.last()?
+                };
+                // Profit in the input base token; cross-base output is valued at oracle prices
+                let base_in = buy_path.hops.first().and_then(|idx| token_index.index_to_address.get(idx))?;
+                let base_out = sell_path.hops.last().and_then(|idx| token_index.index_to_address.get(idx))?;
+                let profit = normalized_profit(amount_in, base_in, amount_out, base_out, usd_price)?;
+
+                // Calculate profit percentage (profit / amount_in * 100)
+                let profit_percentage = if amount_in > U256::zero() {
+                    // Convert to f64 for percentage calculation
+                    let profit_f64 = u256_to_f64_lossy(&profit);
+                    let amount_in_f64 = u256_to_f64_lossy(&amount_in);
+                    (profit_f64 / amount_in_f64) * 100.0
+                } else {
+                    0.0
+                };
+
+                // Only consider profitable trades that clear both floors
+                if profit.is_zero() {
+                    return None;
+                }
+                let pool_count = buy_path.pools.len() + sell_path.pools.len();
+                let Some(net_profit) = profit_after_gas(profit, pool_count, *base_in, gas_price, config, usd_price) else {
+                    REJECTED_BY_GAS.fetch_add(1, Ordering::Relaxed);
+                    if config.trace_profit_floors {
+                        println!("[TRACE] Route {:?} rejected: profit {} doesn't cover gas for {} swaps", route.pools, profit, pool_count);
+                    }
+                    return None;
+                };
+                let price_usd = usd_price(base_in).unwrap_or(0.0);
+                let amount = u256_to_f64_lossy(&net_profit) / 10_f64.powi(18 as i32);
+                let profit_usd = amount * price_usd;
+                let profit_bps = profit_percentage * 100.0;
+                if let Some(floor) = profit_floor_rejection(profit_usd, profit_bps, config) {
+                    floor.counter().fetch_add(1, Ordering::Relaxed);
+                    if config.trace_profit_floors {
+                        println!(
+                            "[TRACE] Route {:?} rejected by {}: ${:.4} / {:.2} bps (floors ${} / {} bps)",
+                            route.pools, floor.name(), profit_usd, profit_bps, config.min_net_profit_usd, config.min_profit_bps
+                        );
+                    }
+                    return None;
+                }
+
+                let sell_tail = sell_amounts.get(1..).unwrap_or(&[]);
+                let mut merged_amounts = Vec::with_capacity(buy_amounts.len() + sell_tail.len());
+                merged_amounts.extend_from_slice(buy_amounts);
+                merged_amounts.extend_from_slice(sell_tail);
+
+                // Merge pools
+                let mut merged_pools = buy_path.pools.clone();
+                merged_pools.extend_from_slice(&sell_path.pools);
+
+                Some(SimulatedRoute {
+                    merged_amounts,
+                    buy_amounts: buy_amounts.clone(),
+                    sell_amounts: sell_amounts.clone(),
+                    buy_tokens: hop_tokens(&buy_path.hops, token_index)?,
+                    sell_tokens: hop_tokens(&sell_path.hops, token_index)?,
+                    buy_pools: buy_path.pools.clone(),
+                    sell_pools: sell_path.pools.clone(),
+                    merged_pools,
+                    profit,
+                    profit_percentage,
+                    buy_path: buy_path.clone(),
+                    sell_path: sell_path.clone(),
+                })
+            })
+        })
+        .collect();
+
+    latency_metrics::record(Stage::Sim, search_done.elapsed());
+
+    // println!(
+    //     "💰 [Price Tracker] Found {} profitable routes",
+    //     profitable_routes.len()
+    // );
+
+    let skipped = skipped.into_inner();
+    if skipped > 0 {
+        DETECTION_BUDGET_EXHAUSTED.fetch_add(1, Ordering::Relaxed);
+        println!(
+            "⏱️ [DETECT] Budget of {}µs exhausted, skipped {}/{} routes",
+            config.max_detection_us,
+            skipped,
+            filtered_routes.len()
+        );
+    }
+
+    if profitable_routes.is_empty() {
+        return None;
+    }
+    if let Some(activity) = activity {
+        activity.record_profitable(decoded_swap.token_x, now);
+    }
+
+    // Find the most profitable route by percentage (better for multiple base tokens);
+    // ties go to the shorter / deeper / cheaper route per `route_tie_break`
+    let best_route = pick_best_route(&profitable_routes, reserve_cache, &config.route_tie_break).cloned();
+
+    let estimated_profit = best_route
+        .as_ref()
+        .map(|r| r.profit)
+        .unwrap_or(U256::zero());
+
+    // End latency timer
+    let detection_latency = start_time.elapsed();
+    let latency = detection_latency.as_millis();
+
+    Some((
+        ArbitrageOpportunity {
+            id: next_opportunity_id(),
+            decoded_swap: decoded_swap.clone(),
+            profitable_routes,
+            best_route,
+            estimated_profit,
+            detection_latency,
+        },
+        latency,
+    ))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{PoolState, PoolType};
+    use crate::route_cache::DEXType;
+
+    #[test]
+    fn test_tied_routes_prefer_fewer_hops() {
+        use crate::testkit::{pool_address, pool_v2, token, units};
+        let cache = ReserveCache::new();
+        let pools: Vec<H160> = (1..=5).map(pool_address).collect();
+        for pool in &pools {
+            cache.insert(*pool, pool_v2(token(1), token(2), units(1_000), units(1_000)));
+        }
+        let route = |pools: &[H160]| SimulatedRoute { merged_pools: pools.to_vec(), profit_percentage: 0.5, ..Default::default() };
+        let (three_hop, two_hop) = (route(&pools[..3]), route(&pools[3..]));
+        let order = Config::default().route_tie_break;
+
+        // Same profit percentage: the shorter route wins whichever comes first
+        let routes = vec![three_hop.clone(), two_hop.clone()];
+        assert_eq!(pick_best_route(&routes, &cache, &order).unwrap().merged_pools, two_hop.merged_pools);
+        let routes = vec![two_hop.clone(), three_hop.clone()];
+        assert_eq!(pick_best_route(&routes, &cache, &order).unwrap().merged_pools, two_hop.merged_pools);
+
+        // Profit still comes first, and with no tie-breakers the first route is kept
+        let better = SimulatedRoute { profit_percentage: 0.6, ..three_hop.clone() };
+        let routes = vec![two_hop.clone(), better.clone()];
+        assert_eq!(pick_best_route(&routes, &cache, &order).unwrap().profit_percentage, 0.6);
+        let routes = vec![three_hop.clone(), two_hop];
+        assert_eq!(pick_best_route(&routes, &cache, &[]).unwrap().merged_pools, three_hop.merged_pools);
+    }
+
+    #[test]
+    fn test_split_beats_single_route() {
+        let base = H160::from_low_u64_be(1);
+        let token_x = H160::from_low_u64_be(2);
+        let v2 = |r_base: u64, r_x: u64| PoolState {
+            pool_type: PoolType::V2,
+            token0: base,
+            token1: token_x,
+            reserve0: Some(U256::from(r_base) * U256::exp10(18)),
+            reserve1: Some(U256::from(r_x) * U256::exp10(18)),
+            dex_name: Some("PancakeSwap V2".to_string()),
+            ..Default::default()
+        };
+        // Two independent routes, each buying X cheap in one pool and selling it dear in another
+        let pools: Vec<H160> = (100..105).map(H160::from_low_u64_be).collect();
+        let cache = ReserveCache::new();
+        cache.insert(pools[0], v2(1_000, 1_100));
+        cache.insert(pools[1], v2(1_100, 1_000));
+        cache.insert(pools[2], v2(1_000, 1_100));
+        cache.insert(pools[3], v2(1_100, 1_000));
+        cache.insert(pools[4], v2(1_100, 1_000));
+        let index = TokenIndexMap::build_from_reserve_cache(&cache);
+        let (b, x) = (index.address_to_index[&base], index.address_to_index[&token_x]);
+        let taxes = Arc::new(TokenTaxMap::new());
+        let config = Config::default();
+
+        let path = |from: usize, to: usize| {
+            (
+                RoutePath { hops: vec![b, x], pools: vec![pools[from]], dex_types: vec![DEXType::PancakeV2], ..Default::default() },
+                RoutePath { hops: vec![x, b], pools: vec![pools[to]], dex_types: vec![DEXType::PancakeV2], ..Default::default() },
+            )
+        };
+        let token_x_amount = U256::from(60u64) * U256::exp10(18);
+        let simulate = |(buy, sell): (RoutePath, RoutePath)| {
+            SimulatedRoute::simulate(&buy, &sell, token_x_amount, &cache, &index, &taxes, &config).unwrap()
+        };
+        // The third route reuses pool 0 and must not be picked alongside the first
+        let routes = vec![simulate(path(0, 1)), simulate(path(2, 3)), simulate(path(0, 4))];
+        let best_single = routes.iter().map(|r| r.profit).max().unwrap();
+
+        let plan = plan_split_routes(&routes, token_x_amount, 3, 10, &cache, &index, &taxes, &config);
+        assert_eq!(plan.len(), 2);
+        let planned_pools: Vec<H160> = plan.iter().flat_map(|r| r.merged_pools.clone()).collect();
+        assert!(!planned_pools.contains(&pools[4]));
+        let allocated = plan.iter().fold(U256::zero(), |acc, r| acc + r.token_x_amount());
+        assert!(allocated <= token_x_amount);
+        let aggregate = plan.iter().fold(U256::zero(), |acc, r| acc + r.profit);
+        assert!(aggregate > best_single, "split {} vs single {}", aggregate, best_single);
+    }
+
+    #[test]
+    fn test_same_pool_round_trip_has_no_phantom_profit() {
+        let base = H160::from_low_u64_be(1);
+        let token_x = H160::from_low_u64_be(2);
+        let pool = H160::from_low_u64_be(100);
+        let cache = ReserveCache::new();
+        cache.insert(pool, PoolState {
+            pool_type: PoolType::V2,
+            token0: base,
+            token1: token_x,
+            reserve0: Some(U256::from(1_000u64) * U256::exp10(18)),
+            reserve1: Some(U256::from(1_000u64) * U256::exp10(18)),
+            dex_name: Some("PancakeSwap V2".to_string()),
+            ..Default::default()
+        });
+        let index = TokenIndexMap::build_from_reserve_cache(&cache);
+        let (b, x) = (index.address_to_index[&base], index.address_to_index[&token_x]);
+        // Buying X from the pool and selling it straight back used to be simulated
+        // against the pre-buy reserves on both legs
+        let route_cache = DashMap::new();
+        route_cache.insert(x, Arc::new(vec![RoutePath {
+            hops: vec![b, x, b],
+            pools: vec![pool, pool],
+            dex_types: vec![DEXType::PancakeV2; 2],
+            ..Default::default()
+        }]));
+        let taxes = Arc::new(TokenTaxMap::new());
+        let routes = simulate_all_paths_for_token_x(
+            x,
+            U256::exp10(18),
+            pool,
+            &route_cache,
+            &cache,
+            &index,
+            &taxes,
+            &Config::default(),
+        );
+        assert!(routes.is_empty());
+    }
+
+    #[test]
+    fn test_normalized_profit_across_bases() {
+        let (usdt, wbnb, unknown) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3));
+        let oracle = |token: &H160| match token.to_low_u64_be() {
+            1 => Some(1.0),
+            2 => Some(600.0),
+            _ => None,
+        };
+        let e18 = |n: u64| U256::from(n) * U256::exp10(18);
+        // Cycles are plain out - in, priced or not
+        assert_eq!(normalized_profit(e18(10), &unknown, e18(11), &unknown, oracle), Some(e18(1)));
+        // 600 USDT in, 1.01 WBNB out: 6 USDT profit in the input base
+        let profit = normalized_profit(e18(600), &usdt, e18(101) / 100, &wbnb, oracle).unwrap();
+        assert_eq!(profit, e18(6));
+        // 1 WBNB in, 590 USDT out is a loss once valued in WBNB
+        assert!(normalized_profit(e18(1), &wbnb, e18(590), &usdt, oracle).unwrap().is_zero());
+        assert!(normalized_profit(e18(1), &usdt, e18(1), &unknown, oracle).is_none());
+    }
+
+    #[test]
+    fn test_detectors_agree_on_best_route() {
+        use crate::testkit::{pool_address, pool_v2, pool_v3, token, units, MarketBuilder};
+        use crate::token_tax::TokenTaxInfo;
+
+        let mut config = Config::default();
+        let wbnb = config.get_base_token_by_symbol("WBNB").unwrap().address;
+        let x = token(2);
+        let (v3, cheap, dear) = (pool_address(1), pool_address(2), pool_address(3));
+        config.dex_fees.insert("PancakeSwap V2".to_string(), 30);
+        config.sequential_leg_simulation = true;
+        let market = MarketBuilder::new()
+            .pool(v3, pool_v3(wbnb, x, 1.05, units(1_000_000), 2500))
+            .pool(cheap, pool_v2(wbnb, x, units(1_000), units(1_100)))
+            .pool(dear, pool_v2(wbnb, x, units(1_000), units(950)))
+            .route(&[wbnb, x, wbnb], &[cheap, dear])
+            .route(&[wbnb, x, wbnb], &[v3, dear])
+            .route(&[wbnb, x, wbnb], &[cheap, v3])
+            .config(config)
+            .build();
+        // X is taxed on transfer, which both detectors must apply
+        market.token_tax_map.insert(x, TokenTaxInfo { buy_tax: 1.0, sell_tax: 1.0, transfer_tax: 0.0, simulation_success: true });
+
+        let decoded_swap = DecodedSwap {
+            tx_hash: H160::zero(),
+            pool_address: dear,
+            token_x: x,
+            token_x_amount: units(5),
+            block_number: 1,
+            timestamp: 0,
+        };
+        let (opportunity, _) = find_arbitrage_opportunity_blocking(
+            Instant::now(),
+            &decoded_swap,
+            &market.reserve_cache,
+            &market.token_index,
+            &market.route_cache,
+            &market.token_tax_map,
+            &market.config,
+            &market.config.route_filter,
+            &market.price_oracle,
+        )
+        .expect("detection found no opportunity");
+        let tracker_best = opportunity.best_route.unwrap();
+
+        let routes = simulate_all_paths_for_token_x(
+            market.index(x),
+            units(5),
+            dear,
+            &market.route_cache,
+            &market.reserve_cache,
+            &market.token_index,
+            &market.token_tax_map,
+            &market.config,
+        );
+        let profitable: Vec<_> = routes.into_iter().filter(|r| !r.profit.is_zero()).collect();
+        let finder_best = pick_best_route(&profitable, &market.reserve_cache, &market.config.route_tie_break).unwrap();
+
+        assert_eq!(finder_best.merged_pools, tracker_best.merged_pools);
+        assert_eq!(finder_best.merged_amounts, tracker_best.merged_amounts);
+        assert_eq!(finder_best.profit, tracker_best.profit);
+    }
+
+    #[tokio::test]
+    async fn test_async_detection_hands_off_to_a_blocking_thread() {
+        use crate::testkit::{pool_address, pool_v2, token, units, MarketBuilder};
+
+        let config = Config::default();
+        let wbnb = config.get_base_token_by_symbol("WBNB").unwrap().address;
+        let x = token(2);
+        let (cheap, dear) = (pool_address(1), pool_address(2));
+        let market = MarketBuilder::new()
+            .pool(cheap, pool_v2(wbnb, x, units(1_000), units(1_100)))
+            .pool(dear, pool_v2(wbnb, x, units(1_000), units(950)))
+            .route(&[wbnb, x, wbnb], &[cheap, dear])
+            .config(config)
+            .build();
+        let decoded_swap = DecodedSwap { pool_address: dear, token_x: x, token_x_amount: units(5), block_number: 1, ..Default::default() };
+        let blocking = find_arbitrage_opportunity_blocking(
+            Instant::now(),
+            &decoded_swap,
+            &market.reserve_cache,
+            &market.token_index,
+            &market.route_cache,
+            &market.token_tax_map,
+            &market.config,
+            &market.config.route_filter,
+            &market.price_oracle,
+        )
+        .expect("detection found no opportunity");
+
+        let handoffs = || latency_metrics::snapshot()["handoff"]["count"].as_u64().unwrap();
+        let before = handoffs();
+        let config = Arc::new(market.config.clone());
+        let (opportunity, _) = find_arbitrage_opportunity(
+            &decoded_swap,
+            &market.reserve_cache,
+            &market.token_index,
+            &market.route_cache,
+            &market.token_tax_map,
+            &config,
+            &market.price_oracle,
+        )
+        .await
+        .expect("async detection found no opportunity");
+        assert_eq!(opportunity.estimated_profit, blocking.0.estimated_profit);
+        assert!(handoffs() > before, "the wait for a blocking thread wasn't measured");
+    }
+
+    #[test]
+    fn test_profit_floors_bind_independently() {
+        let mut config = Config::default();
+        config.min_net_profit_usd = 0.50;
+        config.min_profit_bps = 30.0;
+        assert_eq!(profit_floor_rejection(1.00, 45.0, &config), None);
+        // Big trade, thin margin: only the percentage floor binds
+        assert_eq!(profit_floor_rejection(25.0, 10.0, &config), Some(ProfitFloor::Bps));
+        // Fat margin on a tiny trade: only the USD floor binds
+        assert_eq!(profit_floor_rejection(0.10, 500.0, &config), Some(ProfitFloor::Usd));
+        // Missing both reports the USD floor
+        assert_eq!(profit_floor_rejection(0.10, 1.0, &config), Some(ProfitFloor::Usd));
+        // Exactly at a floor passes
+        assert_eq!(profit_floor_rejection(0.50, 30.0, &config), None);
+    }
+
+    #[test]
+    fn test_gas_cost_filters_routes_profitable_only_gross() {
+        use crate::testkit::{pool_address, pool_v2, token, units, MarketBuilder};
+
+        let mut config = Config::default();
+        let wbnb = config.get_base_token_by_symbol("WBNB").unwrap().address;
+        let x = token(2);
+        let (cheap, dear) = (pool_address(1), pool_address(2));
+        config.dex_fees.insert("PancakeSwap V2".to_string(), 30);
+        config.min_net_profit_usd = 0.0;
+        config.gas_per_hop = 0;
+        let mut market = MarketBuilder::new()
+            .pool(cheap, pool_v2(wbnb, x, units(1_000), units(1_100)))
+            .pool(dear, pool_v2(wbnb, x, units(1_000), units(950)))
+            .route(&[wbnb, x, wbnb], &[cheap, dear])
+            .config(config)
+            .build();
+        let decoded_swap = DecodedSwap {
+            tx_hash: H160::zero(),
+            pool_address: dear,
+            token_x: x,
+            token_x_amount: units(5),
+            block_number: 1,
+            timestamp: 0,
+        };
+        let detect = |market: &crate::testkit::Market| {
+            find_arbitrage_opportunity_blocking(
+                Instant::now(),
+                &decoded_swap,
+                &market.reserve_cache,
+                &market.token_index,
+                &market.route_cache,
+                &market.token_tax_map,
+                &market.config,
+                &market.config.route_filter,
+                &market.price_oracle,
+            )
+            .map(|(opportunity, _)| opportunity.best_route.unwrap().profit)
+        };
+        let gross = detect(&market).expect("route is profitable before gas");
+
+        // Two swaps whose gas costs half the profit: kept, gross profit unchanged
+        let gas_price = market.price_oracle.gas_price();
+        let break_even_per_hop = (gross / (gas_price * U256::from(2u64))).as_u64();
+        market.config.gas_per_hop = break_even_per_hop / 2;
+        assert_eq!(detect(&market), Some(gross));
+        // The same gas at a resolved bid four times higher costs twice the profit
+        market.price_oracle.set_gas_price(gas_price * U256::from(4u64));
+        assert_eq!(detect(&market), None);
+        market.price_oracle.set_gas_price(gas_price);
+        // Gas just above the gross profit: filtered out
+        market.config.gas_per_hop = break_even_per_hop + 1;
+        assert_eq!(detect(&market), None);
+
+        // WBNB routes pay gas in their own token; other bases convert at oracle prices
+        let usdt = market.config.get_base_token_by_symbol("USDT").unwrap().address;
+        let net = profit_after_gas(units(2), 3, usdt, gas_price, &market.config, |t| Some(if *t == wbnb { 600.0 } else { 1.0 }));
+        let gas_usdt = U256::from(market.config.gas_per_hop * 3) * gas_price * U256::from(600u64);
+        assert_eq!(net, units(2).checked_sub(gas_usdt));
+    }
+
+    #[test]
+    fn test_detection_deadline() {
+        let start = Instant::now();
+        assert_eq!(detection_deadline(start, 0), None);
+        let deadline = detection_deadline(start, 1_500).unwrap();
+        assert_eq!(deadline - start, std::time::Duration::from_micros(1_500));
+    }
+
+    #[test]
+    fn test_detection_budget_skips_routes_once_exhausted() {
+        use crate::testkit::{pool_address, pool_v2, token, units, MarketBuilder};
+
+        let mut config = Config::default();
+        let wbnb = config.get_base_token_by_symbol("WBNB").unwrap().address;
+        let x = token(2);
+        let (cheap, mid, dear) = (pool_address(1), pool_address(2), pool_address(3));
+        config.max_detection_us = 1_000;
+        let mut market = MarketBuilder::new()
+            .pool(cheap, pool_v2(wbnb, x, units(1_000), units(1_100)))
+            .pool(mid, pool_v2(wbnb, x, units(1_000), units(1_050)))
+            .pool(dear, pool_v2(wbnb, x, units(1_000), units(950)))
+            .route(&[wbnb, x, wbnb], &[cheap, dear])
+            .route(&[wbnb, x, wbnb], &[mid, dear])
+            .config(config)
+            .build();
+        let decoded_swap = DecodedSwap { pool_address: dear, token_x: x, token_x_amount: units(5), block_number: 1, ..Default::default() };
+        let detect = |market: &crate::testkit::Market, start_time: Instant| {
+            find_arbitrage_opportunity_blocking(
+                start_time,
+                &decoded_swap,
+                &market.reserve_cache,
+                &market.token_index,
+                &market.route_cache,
+                &market.token_tax_map,
+                &market.config,
+                &market.config.route_filter,
+                &market.price_oracle,
+            )
+            .map(|(opportunity, _)| opportunity.best_route.unwrap())
+        };
+
+        // The budget ran out before detection started: no route is simulated
+        let before = DETECTION_BUDGET_EXHAUSTED.load(Ordering::Relaxed);
+        let late = Instant::now().checked_sub(std::time::Duration::from_millis(10)).unwrap();
+        assert!(detect(&market, late).is_none());
+        assert!(DETECTION_BUDGET_EXHAUSTED.load(Ordering::Relaxed) > before, "exhausted budget not counted");
+
+        // A budget that doesn't run out picks the same best route as no budget at all
+        market.config.max_detection_us = 60_000_000;
+        let budgeted = detect(&market, Instant::now()).expect("detection found no opportunity");
+        market.config.max_detection_us = 0;
+        let unlimited = detect(&market, Instant::now()).expect("detection found no opportunity");
+        assert_eq!(budgeted.merged_pools, unlimited.merged_pools);
+        assert_eq!(budgeted.profit, unlimited.profit);
+        assert_eq!(unlimited.merged_pools, vec![cheap, dear]);
+    }
+}
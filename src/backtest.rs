@@ -0,0 +1,188 @@
+use crate::cache::{read_snapshot, PoolType, ReserveCache};
+use crate::config::{Config, DexVersion};
+use crate::mirror_log::{read_decisions, DecisionRecord};
+use crate::route_cache::{DEXType, RoutePath};
+use crate::simulate_swap_path::quote_exact_in;
+use crate::token_index::TokenIndexMap;
+use crate::token_tax::{load_token_tax_map, TokenTaxMap};
+use ethers::types::{I256, U256};
+use std::path::Path;
+use std::sync::Arc;
+
+/// A recorded decision re-simulated against the snapshot's pool state
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayedDecision {
+    pub record: DecisionRecord,
+    pub replayed_profit: Option<U256>, // None: route not simulable on the snapshot (missing pool, stable hop)
+}
+
+impl ReplayedDecision {
+    /// Replayed minus recorded profit, in base token wei
+    pub fn profit_delta(&self) -> Option<I256> {
+        let replayed = I256::try_from(self.replayed_profit?).ok()?;
+        let recorded = I256::try_from(self.record.expected_profit).ok()?;
+        replayed.checked_sub(recorded)
+    }
+}
+
+/// Totals printed at the end of a backtest
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BacktestSummary {
+    pub decisions: usize,
+    pub replayed: usize,
+    pub skipped: usize,       // route couldn't be rebuilt or simulated
+    pub still_profitable: usize,
+}
+
+/// The route a decision took, rebuilt from its pools: each hop leaves through the pool's other
+/// token, starting from the base token. None if a pool is missing or has more than two coins.
+pub fn decision_route(record: &DecisionRecord, reserve_cache: &ReserveCache, token_index: &TokenIndexMap) -> Option<RoutePath> {
+    let mut token = record.base_token;
+    let mut hops = vec![*token_index.address_to_index.get(&token)?];
+    let mut dex_types = Vec::with_capacity(record.pools.len());
+    for pool in &record.pools {
+        let state = reserve_cache.get(pool)?;
+        let version = match state.pool_type {
+            PoolType::V2 => DexVersion::V2,
+            PoolType::V3 => DexVersion::V3,
+            PoolType::StableN { .. } => return None,
+        };
+        token = if state.token0 == token {
+            state.token1
+        } else if state.token1 == token {
+            state.token0
+        } else {
+            return None;
+        };
+        hops.push(*token_index.address_to_index.get(&token)?);
+        dex_types.push(DEXType::from_dex_name(state.dex_name.as_deref().unwrap_or(""), &version));
+    }
+    Some(RoutePath { hops, pools: record.pools.clone(), dex_types, ..Default::default() })
+}
+
+/// Re-simulate one decision with its recorded input; profit is zero when the route loses
+pub fn replay_decision(
+    record: &DecisionRecord,
+    reserve_cache: &ReserveCache,
+    token_index: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+) -> ReplayedDecision {
+    let replayed_profit = decision_route(record, reserve_cache, token_index)
+        .and_then(|route| quote_exact_in(&route, record.input, reserve_cache, token_index, token_tax_map, config))
+        .and_then(|amounts| amounts.last().copied())
+        .map(|out| out.saturating_sub(record.input));
+    ReplayedDecision { record: record.clone(), replayed_profit }
+}
+
+/// Replay every decision and print one line per decision plus the totals
+pub fn run_backtest(
+    decisions: &[DecisionRecord],
+    snapshot_block: u64,
+    reserve_cache: &ReserveCache,
+    token_index: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+) -> BacktestSummary {
+    let mut summary = BacktestSummary { decisions: decisions.len(), ..Default::default() };
+    let off_block = decisions.iter().filter(|d| d.block != snapshot_block).count();
+    if off_block > 0 {
+        println!(
+            "⚠️ [BACKTEST] {} of {} decisions were recorded at a block other than the snapshot's ({}); they are replayed against the snapshot anyway",
+            off_block,
+            decisions.len(),
+            snapshot_block
+        );
+    }
+    for record in decisions {
+        let replay = replay_decision(record, reserve_cache, token_index, token_tax_map, config);
+        match replay.replayed_profit {
+            Some(profit) => {
+                summary.replayed += 1;
+                if !profit.is_zero() {
+                    summary.still_profitable += 1;
+                }
+                println!(
+                    "[BACKTEST] block {} token {:?} pools {:?}: recorded profit {} replayed {} (delta {})",
+                    record.block,
+                    record.token,
+                    record.pools,
+                    record.expected_profit,
+                    profit,
+                    replay.profit_delta().map_or("n/a".to_string(), |d| d.to_string())
+                );
+            }
+            None => {
+                summary.skipped += 1;
+                println!("[BACKTEST] block {} token {:?} pools {:?}: not simulable on the snapshot", record.block, record.token, record.pools);
+            }
+        }
+    }
+    println!(
+        "📊 [BACKTEST] {} decisions: {} replayed ({} still profitable), {} skipped",
+        summary.decisions, summary.replayed, summary.still_profitable, summary.skipped
+    );
+    summary
+}
+
+/// `backtest` subcommand: load the snapshot and the recorded decisions, then replay them.
+/// With `pinned_block` set the snapshot must have been taken at that block.
+pub fn run_backtest_from_files(config: &Config, snapshot: &Path, decisions: &Path) -> anyhow::Result<BacktestSummary> {
+    let snapshot = read_snapshot(snapshot)?;
+    if let Some(block) = config.pinned_block.filter(|block| *block != snapshot.block) {
+        anyhow::bail!("state snapshot was taken at block {}, but the run is pinned to block {}", snapshot.block, block);
+    }
+    let reserve_cache = ReserveCache::new();
+    for (address, state) in snapshot.pools {
+        reserve_cache.insert(address, state);
+    }
+    let token_index = TokenIndexMap::build_from_reserve_cache(&reserve_cache);
+    let token_tax_map = Arc::new(load_token_tax_map(&config.token_tax_file));
+    let decisions = read_decisions(decisions)
+        .map_err(|e| anyhow::anyhow!("failed to read decisions {}: {}", decisions.display(), e))?;
+    println!(
+        "[BACKTEST] Replaying {} decisions against {} pools at block {}",
+        decisions.len(),
+        reserve_cache.len(),
+        snapshot.block
+    );
+    Ok(run_backtest(&decisions, snapshot.block, &reserve_cache, &token_index, &token_tax_map, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{pool_address, pool_v2, token, units, MarketBuilder};
+
+    #[test]
+    fn test_replay_recorded_decision_on_snapshot() {
+        let (base, x) = (token(1), token(2));
+        let (p1, p2) = (pool_address(1), pool_address(2));
+        let market = MarketBuilder::new()
+            .pool(p1, pool_v2(base, x, units(1_000), units(2_000)))
+            .pool(p2, pool_v2(x, base, units(2_000), units(1_100)))
+            .build();
+        let route = market.route(&[base, x, base], &[p1, p2]);
+        let amounts = quote_exact_in(&route, units(1), &market.reserve_cache, &market.token_index, &market.token_tax_map, &market.config).unwrap();
+        let expected_profit = amounts[2] - units(1);
+        let record = DecisionRecord { block: 100, timestamp: 0, token: x, base_token: base, pools: vec![p1, p2], input: units(1), expected_profit };
+
+        // The route is rebuilt from the pools alone, in swap order
+        assert_eq!(decision_route(&record, &market.reserve_cache, &market.token_index).unwrap().hops, route.hops);
+        let replay = replay_decision(&record, &market.reserve_cache, &market.token_index, &market.token_tax_map, &market.config);
+        assert_eq!(replay.replayed_profit, Some(expected_profit));
+        assert_eq!(replay.profit_delta(), Some(I256::zero()));
+
+        // A pool the snapshot doesn't have can't be replayed
+        let missing = DecisionRecord { pools: vec![p1, pool_address(9)], ..record.clone() };
+        let summary = run_backtest(
+            &[record, missing],
+            100,
+            &market.reserve_cache,
+            &market.token_index,
+            &market.token_tax_map,
+            &market.config,
+        );
+        assert_eq!(summary, BacktestSummary { decisions: 2, replayed: 1, skipped: 1, still_profitable: 1 });
+    }
+}
@@ -10,7 +10,7 @@ abigen!(
     ]"#
 );
 
-// Uniswap V3 Pool ABI (slot0, liquidity, tickSpacing, fee)
+// Uniswap V3 Pool ABI (slot0, liquidity, tickSpacing, fee, tick bitmap and ticks)
 abigen!(
     UniswapV3Pool,
     r#"[
@@ -21,6 +21,8 @@ abigen!(
         function tickSpacing() external view returns (int24)
         function fee() external view returns (uint24)
         function factory() external view returns (address)
+        function tickBitmap(int16 wordPosition) external view returns (uint256)
+        function ticks(int24 tick) external view returns (uint128 liquidityGross, int128 liquidityNet, uint256 feeGrowthOutside0X128, uint256 feeGrowthOutside1X128, int56 tickCumulativeOutside, uint160 secondsPerLiquidityOutsideX128, uint32 secondsOutside, bool initialized)
     ]"#
 );
 
@@ -28,7 +30,29 @@ abigen!(
     DirectSwapExecutor,
     r#"[
         function executeSwap(address[],address[],uint8[],uint256[],bytes[],uint256)
-        function buySellExecution(address[],address[],uint8[],uint256[],address[],address[],uint8[],uint256[])
+        function buySellExecution(address[],address[],uint8[],uint256[],address[],address[],uint8[],uint256[],uint256)
         function withdrawToken(address,address,uint256)
     ]"#
 );
+
+// Curve-style StableSwap pool (coins/balances indexed by coin number)
+abigen!(
+    CurveStablePool,
+    r#"[
+        function coins(uint256) external view returns (address)
+        function balances(uint256) external view returns (uint256)
+        function A() external view returns (uint256)
+        function fee() external view returns (uint256)
+        function get_dy(int128,int128,uint256) external view returns (uint256)
+    ]"#
+);
+
+// Minimal ERC20 ABI (balances for inventory tracking, allowances for the executor)
+abigen!(
+    IERC20,
+    r#"[
+        function balanceOf(address) external view returns (uint256)
+        function allowance(address,address) external view returns (uint256)
+        function approve(address,uint256) external returns (bool)
+    ]"#
+);
@@ -1,23 +1,47 @@
 use dashmap::DashMap;
-use ethers::types::H160;
+use ethers::abi::Token;
+use ethers::contract::{Multicall, MulticallVersion};
+use ethers::types::{Bytes, H160};
 use primitive_types::U256;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use crate::fetch_pairs::PairInfo;
-use crate::config::DexVersion;
-use crate::bindings::{UniswapV2Pair, UniswapV3Pool};
-use ethers::providers::{Provider, Middleware, Http};
+use crate::config::{DexVersion, PreloadPolicy, TokenOrderCheck};
+use crate::bindings::{CurveStablePool, UniswapV2Pair, UniswapV3Pool};
+use ethers::providers::Middleware;
+use crate::failover_provider::{classify_provider_error, ErrorClass, HttpProvider};
+use crate::simulate_swap_path::u256_to_f64_lossy;
+use crate::v3_math::{sqrt_price_x96_to_price, TickInfo};
 use ethers::types::Address;
+use std::path::Path;
 use std::sync::Arc;
 use futures::stream::{self, StreamExt};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use rayon::prelude::*;
-use futures::stream::{FuturesUnordered};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PoolType {
     V2,
     V3,
+    /// Curve-style StableSwap pool with two or more coins. `balances` follow `tokens` order and
+    /// `amp` is the pool's `A()`; `PoolState::fee` holds its `fee()` (1e10 denominator) and
+    /// `token0`/`token1` mirror the first two coins.
+    StableN {
+        tokens: Vec<H160>,
+        balances: Vec<U256>,
+        amp: U256,
+    },
+}
+
+impl PoolType {
+    /// Coin indices (i, j) a swap of `token_in` for `token_out` uses in a StableN pool
+    pub fn stable_indices(&self, token_in: H160, token_out: H160) -> Option<(usize, usize)> {
+        let PoolType::StableN { tokens, .. } = self else { return None };
+        let i = tokens.iter().position(|t| *t == token_in)?;
+        let j = tokens.iter().position(|t| *t == token_out)?;
+        (i != j).then_some((i, j))
+    }
 }
 
 impl Default for PoolType {
@@ -26,7 +50,9 @@ impl Default for PoolType {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+/// Serialized as part of the versioned schema (see `schema`): new fields must tolerate being absent
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PoolState {
     pub pool_type: PoolType,
     pub token0: H160,
@@ -38,12 +64,70 @@ pub struct PoolState {
     pub tick: Option<i32>,             // V3
     pub fee: Option<u32>,              // V3
     pub tick_spacing: Option<i32>,     // V3
+    pub ticks: Option<Vec<TickInfo>>,  // V3 initialized ticks near the price, ascending (None = single-range math)
+    pub tick_range: Option<(i32, i32)>, // V3 ticks covered by the loaded bitmap words, inclusive; `ticks` is complete inside it
     pub dex_name: Option<String>,      // DEX name for fee lookup
     pub last_updated: u64,
+    pub last_updated_block: u64,       // block of the last Sync/Swap applied (0 = preload or RPC refresh); older events are ignored
+    pub inactive: bool,                // V3 pool never initialized (zero sqrtPrice or liquidity at preload)
+    pub decimals0: Option<u8>,         // token0 decimals, if the pair file has them (18 assumed otherwise)
+    pub decimals1: Option<u8>,         // token1 decimals
+}
+
+impl PoolState {
+    /// Current price of token0 in token1, in whole tokens (decimals applied):
+    /// `reserve1 / reserve0` for V2, `sqrt_price_x96_to_price` for V3.
+    /// None for empty or uninitialized pools and for stable pools.
+    pub fn spot_price(&self) -> Option<f64> {
+        let raw = match self.pool_type {
+            PoolType::V2 => {
+                let (reserve0, reserve1) = (self.reserve0?, self.reserve1?);
+                if reserve0.is_zero() || reserve1.is_zero() {
+                    return None;
+                }
+                u256_to_f64_lossy(&reserve1) / u256_to_f64_lossy(&reserve0)
+            }
+            PoolType::V3 => {
+                let sqrt_price_x96 = self.sqrt_price_x96?;
+                if sqrt_price_x96.is_zero() {
+                    return None;
+                }
+                sqrt_price_x96_to_price(sqrt_price_x96)
+            }
+            PoolType::StableN { .. } => return None,
+        };
+        let decimals_shift = self.decimals0.unwrap_or(18) as i32 - self.decimals1.unwrap_or(18) as i32;
+        Some(raw * 10f64.powi(decimals_shift))
+    }
 }
 
 pub type ReserveCache = DashMap<H160, PoolState>;
 
+/// Bumped on every write to the live reserve cache; cached quotes are keyed on it
+static RESERVE_VERSION: AtomicU64 = AtomicU64::new(0);
+
+pub fn reserve_version() -> u64 {
+    RESERVE_VERSION.load(Ordering::Acquire)
+}
+
+/// Call after a live pool write has landed, never before it: a quote taken from the old
+/// state must not end up stored under the new version
+pub fn bump_reserve_version() {
+    RESERVE_VERSION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Queries over the reserve cache that skip inactive pools
+pub trait ReserveCacheExt {
+    /// Pools that can be simulated and should be subscribed to
+    fn active_pools(&self) -> impl Iterator<Item = dashmap::mapref::multiple::RefMulti<'_, H160, PoolState>>;
+}
+
+impl ReserveCacheExt for ReserveCache {
+    fn active_pools(&self) -> impl Iterator<Item = dashmap::mapref::multiple::RefMulti<'_, H160, PoolState>> {
+        self.iter().filter(|entry| !entry.value().inactive)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum DexType {
     V2,
@@ -72,11 +156,483 @@ pub struct TokenMeta {
     // Add more fields as needed
 }
 
-/// Helper async function to fetch reserve for a single pair
-async fn fetch_reserve(
+/// Summary of reserve cache contents for health checks
+#[derive(Clone, Debug, Default)]
+pub struct CacheHealthReport {
+    pub total_pools: usize,
+    pub v2_pools: usize,
+    pub v3_pools: usize,
+    pub stable_pools: usize,
+    pub empty_pools: usize, // zero reserves / zero liquidity
+    pub stale_pools: usize, // not updated within max_age_secs
+}
+
+/// Build a health report for the reserve cache
+pub fn health_report(reserve_cache: &ReserveCache, max_age_secs: u64) -> CacheHealthReport {
+    let now = chrono::Utc::now().timestamp() as u64;
+    let mut report = CacheHealthReport::default();
+    for entry in reserve_cache.iter() {
+        let state = entry.value();
+        report.total_pools += 1;
+        let empty = match &state.pool_type {
+            PoolType::V2 => {
+                report.v2_pools += 1;
+                state.reserve0.unwrap_or_default().is_zero() || state.reserve1.unwrap_or_default().is_zero()
+            }
+            PoolType::V3 => {
+                report.v3_pools += 1;
+                state.liquidity.unwrap_or_default().is_zero() || state.sqrt_price_x96.unwrap_or_default().is_zero()
+            }
+            PoolType::StableN { balances, .. } => {
+                report.stable_pools += 1;
+                balances.iter().any(|b| b.is_zero())
+            }
+        };
+        if empty {
+            report.empty_pools += 1;
+        }
+        if now.saturating_sub(state.last_updated) > max_age_secs {
+            report.stale_pools += 1;
+        }
+    }
+    report
+}
+
+/// Number of cache entries corrected by the consistency checker since startup
+pub static CACHE_DRIFT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Relative difference between two values in basis points
+fn diff_bps(a: U256, b: U256) -> u64 {
+    if a == b {
+        return 0;
+    }
+    let (hi, lo) = if a > b { (a, b) } else { (b, a) };
+    ((hi - lo).saturating_mul(U256::from(10_000u32)) / hi).low_u64()
+}
+
+/// Relative difference between two spot prices in basis points (a missing price counts as
+/// fully drifted unless both are missing)
+fn price_diff_bps(a: Option<f64>, b: Option<f64>) -> u64 {
+    match (a, b) {
+        (Some(a), Some(b)) if a > 0.0 && b > 0.0 => (((a - b).abs() / a.max(b)) * 10_000.0).round() as u64,
+        (None, None) => 0,
+        _ => 10_000,
+    }
+}
+
+/// Compare a random sample of cached pools against chain state (via Multicall3) and
+/// correct any entry drifting beyond `tolerance_bps`. Returns the number of corrected pools.
+pub async fn check_cache_consistency(
+    reserve_cache: &Arc<ReserveCache>,
+    provider: Arc<HttpProvider>,
+    sample_size: usize,
+    tolerance_bps: u64,
+) -> anyhow::Result<usize> {
+    use crate::v3_swap::decode_int24;
+    use rand::seq::SliceRandom;
+
+    let sample: Vec<(H160, PoolType)> = {
+        let pools: Vec<(H160, PoolType)> = reserve_cache
+            .active_pools()
+            // Stable pools have no multicall-friendly state getter; refresh_pools covers them
+            .filter(|e| !matches!(e.value().pool_type, PoolType::StableN { .. }))
+            .map(|e| (*e.key(), e.value().pool_type.clone()))
+            .collect();
+        let mut rng = rand::thread_rng();
+        pools.choose_multiple(&mut rng, sample_size).cloned().collect()
+    };
+    if sample.is_empty() {
+        return Ok(0);
+    }
+
+    // V2: getReserves; V3: slot0 + liquidity
+    let mut multicall = Multicall::new(provider.clone(), None).await?;
+    for (pool, pool_type) in &sample {
+        match pool_type {
+            PoolType::V2 => {
+                multicall.add_call(UniswapV2Pair::new(*pool, provider.clone()).get_reserves(), true);
+            }
+            PoolType::V3 => {
+                let contract = UniswapV3Pool::new(*pool, provider.clone());
+                multicall.add_call(contract.slot_0(), true);
+                multicall.add_call(contract.liquidity(), true);
+            }
+            PoolType::StableN { .. } => {}
+        }
+    }
+    let results = multicall.call_raw().await?;
+
+    let mut corrected = 0;
+    let mut idx = 0;
+    let now = chrono::Utc::now().timestamp() as u64;
+    for (pool, pool_type) in &sample {
+        match pool_type {
+            PoolType::V2 => {
+                let res = results.get(idx);
+                idx += 1;
+                let Some(Ok(Token::Tuple(vals))) = res else { continue };
+                let (Some(r0), Some(r1)) = (
+                    vals.get(0).and_then(|t| t.clone().into_uint()),
+                    vals.get(1).and_then(|t| t.clone().into_uint()),
+                ) else { continue };
+                if let Some(mut state) = reserve_cache.get_mut(pool) {
+                    let chain = PoolState { reserve0: Some(r0), reserve1: Some(r1), ..state.clone() };
+                    let price_drift = price_diff_bps(state.spot_price(), chain.spot_price());
+                    let drift = diff_bps(state.reserve0.unwrap_or_default(), r0)
+                        .max(diff_bps(state.reserve1.unwrap_or_default(), r1))
+                        .max(price_drift);
+                    if drift > tolerance_bps {
+                        println!("[CACHE CHECK] V2 {:?} drifted {} bps (price {} bps): cached ({:?}, {:?}) chain ({}, {})",
+                            pool, drift, price_drift, state.reserve0, state.reserve1, r0, r1);
+                        state.reserve0 = Some(r0);
+                        state.reserve1 = Some(r1);
+                        state.last_updated = now;
+                        crate::price_feed::publish(*pool, &state, None);
+                        bump_reserve_version();
+                        corrected += 1;
+                    }
+                }
+            }
+            PoolType::V3 => {
+                let slot0 = results.get(idx);
+                let liquidity = results.get(idx + 1);
+                idx += 2;
+                let (Some(Ok(Token::Tuple(vals))), Some(Ok(Token::Uint(liquidity)))) = (slot0, liquidity) else { continue };
+                let (Some(sqrt_price), Some(tick_token)) =
+                    (vals.get(0).and_then(|t| t.clone().into_uint()), vals.get(1)) else { continue };
+                // Malformed slot0 data: skip the pool rather than panic the checker
+                let Ok(tick) = decode_int24(tick_token) else { continue };
+                if let Some(mut state) = reserve_cache.get_mut(pool) {
+                    let chain = PoolState { sqrt_price_x96: Some(sqrt_price), ..state.clone() };
+                    // Price moves ~2x the sqrtPrice, so compare spot prices rather than sqrtPriceX96
+                    let drift = price_diff_bps(state.spot_price(), chain.spot_price())
+                        .max(diff_bps(state.liquidity.unwrap_or_default(), *liquidity));
+                    if drift > tolerance_bps || state.tick != Some(tick) {
+                        println!("[CACHE CHECK] V3 {:?} drifted {} bps: cached price {:?} chain price {:?}, cached tick {:?} chain tick {}",
+                            pool, drift, state.spot_price(), chain.spot_price(), state.tick, tick);
+                        state.sqrt_price_x96 = Some(sqrt_price);
+                        state.liquidity = Some(*liquidity);
+                        state.tick = Some(tick);
+                        state.last_updated = now;
+                        crate::price_feed::publish(*pool, &state, None);
+                        bump_reserve_version();
+                        corrected += 1;
+                    }
+                }
+            }
+            PoolType::StableN { .. } => {}
+        }
+    }
+    CACHE_DRIFT_COUNT.fetch_add(corrected, Ordering::Relaxed);
+    println!("[CACHE CHECK] Checked {} pools, corrected {} (total drift: {})",
+        sample.len(), corrected, CACHE_DRIFT_COUNT.load(Ordering::Relaxed));
+    Ok(corrected)
+}
+
+/// Run the consistency check in the background every `interval_secs`
+pub fn spawn_consistency_checker(
+    reserve_cache: Arc<ReserveCache>,
+    provider: Arc<HttpProvider>,
+    interval_secs: u64,
+    sample_size: usize,
+    tolerance_bps: u64,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(interval_secs)).await;
+            if let Err(e) = check_cache_consistency(&reserve_cache, provider.clone(), sample_size, tolerance_bps).await {
+                eprintln!("[CACHE CHECK] Consistency check failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Re-fetch the on-chain state of the given pools (at `at_block`, or latest if `None`) and
+/// overwrite their cache entries (used after a reorg). Their `last_updated_block` drops back to
+/// `at_block` (0 for latest), so canonical events below an orphaned block are accepted again.
+/// Returns the number of pools refreshed.
+pub async fn refresh_pools<M: Middleware + 'static>(
+    reserve_cache: &Arc<ReserveCache>,
+    pools: &[H160],
+    provider: Arc<M>,
+    at_block: Option<u64>,
+) -> usize {
+    let block: ethers::types::BlockId = match at_block {
+        Some(n) => n.into(),
+        None => ethers::types::BlockNumber::Latest.into(),
+    };
+    let now = chrono::Utc::now().timestamp() as u64;
+    let mut refreshed = 0;
+    for pool in pools {
+        let Some(pool_type) = reserve_cache.get(pool).map(|s| s.pool_type.clone()) else { continue };
+        match pool_type {
+            PoolType::V2 => {
+                let contract = UniswapV2Pair::new(*pool, provider.clone());
+                match contract.get_reserves().block(block).call().await {
+                    Ok(res) => {
+                        if let Some(mut state) = reserve_cache.get_mut(pool) {
+                            state.reserve0 = Some(res.0.into());
+                            state.reserve1 = Some(res.1.into());
+                            state.last_updated = now;
+                            state.last_updated_block = at_block.unwrap_or(0);
+                            crate::price_feed::publish(*pool, &state, at_block);
+                            bump_reserve_version();
+                            refreshed += 1;
+                        }
+                    }
+                    Err(e) => eprintln!("[CACHE] Failed to refresh V2 pool {:?}: {}", pool, e),
+                }
+            }
+            PoolType::V3 => {
+                let contract = UniswapV3Pool::new(*pool, provider.clone());
+                // Pools that came up without a known fee get another chance at it here
+                let missing_fee = reserve_cache.get(pool).is_some_and(|s| s.fee.is_none());
+                let fee = if missing_fee { contract.fee().call().await.ok() } else { None };
+                match (contract.slot_0().block(block).call().await, contract.liquidity().block(block).call().await) {
+                    (Ok(slot0), Ok(liquidity)) => {
+                        if let Some(mut state) = reserve_cache.get_mut(pool) {
+                            state.sqrt_price_x96 = Some(slot0.0);
+                            state.tick = Some(slot0.1);
+                            state.liquidity = Some(liquidity.into());
+                            if fee.is_some() {
+                                state.fee = fee;
+                            }
+                            state.last_updated = now;
+                            state.last_updated_block = at_block.unwrap_or(0);
+                            crate::price_feed::publish(*pool, &state, at_block);
+                            bump_reserve_version();
+                            refreshed += 1;
+                        }
+                    }
+                    _ => eprintln!("[CACHE] Failed to refresh V3 pool {:?}", pool),
+                }
+            }
+            PoolType::StableN { .. } => {
+                match fetch_stable_pool(*pool, provider.clone(), Some(block)).await {
+                    Ok(fresh) => {
+                        if let Some(mut state) = reserve_cache.get_mut(pool) {
+                            state.pool_type = fresh.pool_type;
+                            state.fee = fresh.fee;
+                            state.last_updated = now;
+                            crate::price_feed::publish(*pool, &state, at_block);
+                            bump_reserve_version();
+                            refreshed += 1;
+                        }
+                    }
+                    Err(e) => eprintln!("[CACHE] Failed to refresh stable pool {:?}: {}", pool, e),
+                }
+            }
+        }
+    }
+    refreshed
+}
+
+/// Re-read the stable pools' coins, balances, `A()` and `fee()` every `interval_secs`. The price
+/// tracker decodes no StableSwap events, so this is what keeps their balances current.
+pub fn spawn_stable_pool_refresh(
+    reserve_cache: Arc<ReserveCache>,
+    pools: Vec<H160>,
+    provider: Arc<HttpProvider>,
+    interval_secs: u64,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(interval_secs)).await;
+            let refreshed = refresh_pools(&reserve_cache, &pools, provider.clone(), None).await;
+            if refreshed < pools.len() {
+                eprintln!("[CACHE] Refreshed {}/{} stable pools", refreshed, pools.len());
+            }
+        }
+    });
+}
+
+/// Upper bound on coins probed by `fetch_stable_pool`
+const MAX_STABLE_COINS: u64 = 8;
+
+/// Read a Curve-style stable pool's coins, balances, `A()` and `fee()` (at `block`, or latest)
+pub async fn fetch_stable_pool<M: Middleware + 'static>(
+    pool: H160,
+    provider: Arc<M>,
+    block: Option<ethers::types::BlockId>,
+) -> anyhow::Result<PoolState> {
+    let block = block.unwrap_or_else(|| ethers::types::BlockNumber::Latest.into());
+    let contract = CurveStablePool::new(pool, provider);
+    let mut tokens = Vec::new();
+    let mut balances = Vec::new();
+    // coins(i) reverts past the last coin
+    for i in 0..MAX_STABLE_COINS {
+        let Ok(token) = contract.coins(U256::from(i)).block(block).call().await else { break };
+        balances.push(contract.balances(U256::from(i)).block(block).call().await?);
+        tokens.push(token);
+    }
+    if tokens.len() < 2 {
+        anyhow::bail!("{:?} is not a stable pool: found {} coins", pool, tokens.len());
+    }
+    let amp = contract.a().block(block).call().await?;
+    let fee = u32::try_from(contract.fee().block(block).call().await?)
+        .map_err(|_| anyhow::anyhow!("{:?} fee out of range", pool))?;
+    let inactive = balances.iter().any(|b| b.is_zero());
+    Ok(PoolState {
+        token0: tokens[0],
+        token1: tokens[1],
+        pool_type: PoolType::StableN { tokens, balances, amp },
+        fee: Some(fee),
+        dex_name: Some("StableSwap".to_string()),
+        last_updated: chrono::Utc::now().timestamp() as u64,
+        inactive,
+        ..Default::default()
+    })
+}
+
+/// Pool states captured at one block, for offline warmup and backtests.
+/// Stored in the versioned schema envelope; see "State Snapshots" in the README.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateSnapshot {
+    pub block: u64,
+    pub pools: HashMap<H160, PoolState>,
+}
+
+/// Read a state snapshot file (versioned envelope)
+pub fn read_snapshot(path: &Path) -> anyhow::Result<StateSnapshot> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read state snapshot {}: {}", path.display(), e))?;
+    crate::schema::from_versioned_json(&json)
+}
+
+/// Populate the cache from a state snapshot file instead of live RPC. With `pinned_block`
+/// set, the snapshot must have been taken at exactly that block. Returns the pools loaded.
+pub fn preload_from_snapshot(
+    path: &Path,
+    reserve_cache: &ReserveCache,
+    pinned_block: Option<u64>,
+) -> anyhow::Result<usize> {
+    let snapshot = read_snapshot(path)?;
+    if let Some(block) = pinned_block {
+        if snapshot.block != block {
+            anyhow::bail!(
+                "state snapshot {} was taken at block {}, but the run is pinned to block {}",
+                path.display(),
+                snapshot.block,
+                block
+            );
+        }
+    }
+    let loaded = snapshot.pools.len();
+    for (address, state) in snapshot.pools {
+        reserve_cache.insert(address, state);
+    }
+    println!("[CACHE] Loaded {} pools from state snapshot {} (block {})", loaded, path.display(), snapshot.block);
+    Ok(loaded)
+}
+
+/// Load the configured stable pools into the cache; returns how many loaded
+pub async fn preload_stable_pools(
+    pools: &[H160],
+    provider: Arc<HttpProvider>,
+    reserve_cache: &Arc<ReserveCache>,
+) -> usize {
+    let mut loaded = 0;
+    for pool in pools {
+        match fetch_stable_pool(*pool, provider.clone(), None).await {
+            Ok(state) => {
+                reserve_cache.insert(*pool, state);
+                loaded += 1;
+            }
+            Err(e) => eprintln!("[CACHE] Failed to load stable pool {:?}: {}", pool, e),
+        }
+    }
+    println!("[CACHE] Stable pools loaded: {}/{}", loaded, pools.len());
+    loaded
+}
+
+/// How a pool's pair-file token order compares with the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenOrder {
+    Consistent,
+    Swapped,  // token0/token1 reversed in the pair file
+    Mismatch, // on-chain token0 is neither of the cached tokens
+}
+
+/// Compare the pair file's tokens with the pool's on-chain `token0()`. Without it, fall back
+/// to the `token0 < token1` ordering V2 and V3 factories enforce.
+pub fn check_token_order(token0: H160, token1: H160, onchain_token0: Option<H160>) -> TokenOrder {
+    match onchain_token0 {
+        Some(actual) if actual == token0 => TokenOrder::Consistent,
+        Some(actual) if actual == token1 => TokenOrder::Swapped,
+        Some(_) => TokenOrder::Mismatch,
+        None if token0 > token1 => TokenOrder::Swapped,
+        None => TokenOrder::Consistent,
+    }
+}
+
+/// Check `state`'s tokens against the chain and put a reversed pair back in on-chain order.
+/// Reserves and slot0 are read from the pool itself, so only the token fields can be wrong.
+fn reconcile_token_order(address: H160, state: &mut PoolState, onchain_token0: Option<H160>) -> TokenOrder {
+    let order = check_token_order(state.token0, state.token1, onchain_token0);
+    match order {
+        TokenOrder::Consistent => {}
+        TokenOrder::Swapped => {
+            eprintln!(
+                "[CACHE] Pool {:?} has token0/token1 reversed in the pair file ({:?} is token0); corrected",
+                address, state.token1
+            );
+            std::mem::swap(&mut state.token0, &mut state.token1);
+            std::mem::swap(&mut state.decimals0, &mut state.decimals1);
+        }
+        TokenOrder::Mismatch => eprintln!(
+            "[CACHE] Pool {:?} token0 on-chain is {:?}, pair file says {:?}/{:?}",
+            address,
+            onchain_token0.unwrap_or_default(),
+            state.token0,
+            state.token1
+        ),
+    }
+    order
+}
+
+/// Bitmap word positions within `words` of the word holding `tick` (a word covers 256 tick
+/// spacings), limited to the positions the pool's `int16` index can address
+fn tick_word_positions(tick: i32, tick_spacing: i32, words: u32) -> std::ops::RangeInclusive<i32> {
+    let word = tick.div_euclid(tick_spacing) >> 8;
+    (word - words as i32).max(i16::MIN as i32)..=(word + words as i32).min(i16::MAX as i32)
+}
+
+/// First and last tick covered by the words `fetch_ticks` loads, i.e. the `tick_range` its ticks are complete in
+pub fn loaded_tick_range(tick: i32, tick_spacing: i32, words: u32) -> (i32, i32) {
+    let positions = tick_word_positions(tick, tick_spacing, words);
+    ((*positions.start() << 8) * tick_spacing, ((*positions.end() << 8) + 255) * tick_spacing)
+}
+
+/// Initialized ticks of a V3 pool within `words` tick-bitmap words either side of the word
+/// holding `tick`, ascending. None if any call fails.
+async fn fetch_ticks<M: Middleware>(contract: &UniswapV3Pool<M>, tick: i32, tick_spacing: i32, words: u32) -> Option<Vec<TickInfo>> {
+    if tick_spacing <= 0 {
+        return None;
+    }
+    let mut indices = Vec::new();
+    for position in tick_word_positions(tick, tick_spacing, words) {
+        let bitmap = contract.tick_bitmap(position as i16).call().await.ok()?;
+        indices.extend((0..256).filter(|bit| bitmap.bit(*bit)).map(|bit| ((position << 8) + bit as i32) * tick_spacing));
+    }
+    let calls = indices.iter().map(|index| async move {
+        let (_, liquidity_net, ..) = contract.ticks(*index).call().await.ok()?;
+        Some(TickInfo { index: *index, liquidity_net })
+    });
+    futures::future::join_all(calls).await.into_iter().collect()
+}
+
+/// Helper async function to fetch reserve for a single pair.
+/// V3 pools also get their initialized ticks within `tick_words` bitmap words (0 = none).
+/// Fails if the core state (`getReserves`, or V3 `slot0` and `liquidity`) can't be read;
+/// secondary calls fall back as noted below.
+async fn fetch_reserve<M: Middleware + 'static>(
     pair: PairInfo,
-    provider: Arc<Provider<Http>>,
-) -> Option<(H160, PoolState)> {
+    provider: Arc<M>,
+    token_order_check: TokenOrderCheck,
+    tick_words: u32,
+) -> anyhow::Result<(H160, PoolState, TokenOrder)> {
+    let verify = token_order_check != TokenOrderCheck::Off;
     let address = pair.pair_address;
     let token0 = pair.token0;
     let token1 = pair.token1;
@@ -87,7 +643,8 @@ async fn fetch_reserve(
             let contract = UniswapV2Pair::new(address, provider.clone());
             match contract.get_reserves().call().await {
                 Ok(res) => {
-                    Some((address, PoolState {
+                    let onchain_token0 = if verify { contract.token_0().call().await.ok() } else { None };
+                    let mut state = PoolState {
                         pool_type: PoolType::V2,
                         token0,
                         token1,
@@ -98,27 +655,56 @@ async fn fetch_reserve(
                         tick: None,
                         fee: None,
                         tick_spacing: None,
+                        ticks: None,
+                        tick_range: None,
                         dex_name: Some(dex_name),
                         last_updated: now,
-                    }))
+                        last_updated_block: 0,
+                        inactive: false,
+                        decimals0: pair.token0_decimals,
+                        decimals1: pair.token1_decimals,
+                    };
+                    let order = if verify { reconcile_token_order(address, &mut state, onchain_token0) } else { TokenOrder::Consistent };
+                    Ok((address, state, order))
                 }
-                Err(_) => None,
+                Err(e) => Err(anyhow::anyhow!("getReserves: {}", e)),
             }
         }
         DexVersion::V3 => {
             let contract = UniswapV3Pool::new(address, provider.clone());
-            let slot0_res = contract.slot_0().call().await;
-            let liquidity_res = contract.liquidity().call().await;
-            let fee_res = contract.fee().call().await;
-            let tick_spacing_res = contract.tick_spacing().call().await;
-            
-            // Extract values with fallbacks
-            let slot0 = slot0_res.unwrap_or((U256::zero(), 0, 0, 0, 0, 0, false));
-            let liquidity = liquidity_res.unwrap_or(0u128);
-            let fee = fee_res.unwrap_or(3000);
-            let tick_spacing = tick_spacing_res.unwrap_or(60);
+            // A failed read is an RPC problem, not an empty pool: let the caller retry it
+            let slot0 = contract.slot_0().call().await.map_err(|e| anyhow::anyhow!("slot0: {}", e))?;
+            let liquidity = contract.liquidity().call().await.map_err(|e| anyhow::anyhow!("liquidity: {}", e))?;
+            // Created but never initialized (or drained): nothing to simulate, skip the remaining calls
+            let inactive = slot0.0.is_zero() || liquidity == 0;
+            // The fee tier varies per pool even within one fork: read it, never assume 3000.
+            // If fee() fails the tier from the pair file's PoolCreated event is used; pools with
+            // neither keep `fee: None` and are skipped by the simulator.
+            let (fee, tick_spacing) = if inactive {
+                (pair.fee, 60)
+            } else {
+                (
+                    contract.fee().call().await.ok().or(pair.fee),
+                    contract.tick_spacing().call().await.unwrap_or(60),
+                )
+            };
+            if fee.is_none() && !inactive {
+                eprintln!("[CACHE] V3 pool {:?}: fee() failed, pool will be skipped until it's known", address);
+            }
+            let onchain_token0 = if verify && !inactive { contract.token_0().call().await.ok() } else { None };
+            // Without ticks (disabled, inactive or a failed call) the pool is simulated as one range
+            let ticks = if tick_words > 0 && !inactive {
+                let ticks = fetch_ticks(&contract, slot0.1, tick_spacing, tick_words).await;
+                if ticks.is_none() {
+                    eprintln!("[CACHE] V3 pool {:?}: tick fetch failed, using single-range math", address);
+                }
+                ticks
+            } else {
+                None
+            };
+            let tick_range = ticks.as_ref().map(|_| loaded_tick_range(slot0.1, tick_spacing, tick_words));
             
-            Some((address, PoolState {
+            let mut state = PoolState {
                 pool_type: PoolType::V3,
                 token0,
                 token1,
@@ -127,66 +713,195 @@ async fn fetch_reserve(
                 sqrt_price_x96: Some(slot0.0),
                 liquidity: Some(liquidity.into()),
                 tick: Some(slot0.1),
-                fee: Some(fee),
+                fee,
                 tick_spacing: Some(tick_spacing),
+                ticks,
+                tick_range,
                 dex_name: Some(dex_name),
                 last_updated: now,
-            }))
+                last_updated_block: 0,
+                inactive,
+                decimals0: pair.token0_decimals,
+                decimals1: pair.token1_decimals,
+            };
+            let order = if verify { reconcile_token_order(address, &mut state, onchain_token0) } else { TokenOrder::Consistent };
+            Ok((address, state, order))
         }
     }
 }
 
-/// Preload all reserves and state for all pools into the ReserveCache using batching and rayon
-pub async fn preload_reserve_cache(
+/// What `preload_reserve_cache` did, for the caller to log or check
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PreloadSummary {
+    pub succeeded: usize,  // pools loaded into the cache (inactive and mismatched-but-kept included)
+    pub failed: usize,     // last attempt returned an error
+    pub timed_out: usize,  // last attempt hit `pool_timeout_ms`
+    pub retries: usize,    // extra attempts across all pools
+    pub fallbacks: usize,  // pools the multicall path handed to the per-pool path
+    pub v2: usize,
+    pub v3: usize,
+    pub inactive: usize,   // V3 pools never initialized or without liquidity
+    pub reordered: usize,  // pair-file token order reversed and corrected
+    pub mismatched: usize, // tokens don't match the chain (dropped under `TokenOrderCheck::Drop`)
+}
+
+impl std::fmt::Display for PreloadSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} loaded ({} V2, {} V3, {} inactive), {} failed, {} timed out, {} retries, {} per-pool fallbacks, {} reordered, {} mismatched",
+            self.succeeded, self.v2, self.v3, self.inactive, self.failed, self.timed_out, self.retries, self.fallbacks, self.reordered, self.mismatched
+        )
+    }
+}
+
+/// Final outcome of one pool's preload attempts
+enum PoolPreload {
+    Loaded(H160, PoolState, TokenOrder),
+    Failed(anyhow::Error),
+    TimedOut,
+}
+
+/// Fetch one pool, retrying failures and timeouts per `policy.retry`. Fatal errors (rejected
+/// credentials, unsupported method) are not retried. Returns the outcome and the retries used.
+async fn preload_pool<M: Middleware + 'static>(
+    pair: PairInfo,
+    provider: Arc<M>,
+    policy: &PreloadPolicy,
+    token_order_check: TokenOrderCheck,
+    tick_words: u32,
+) -> (PoolPreload, usize) {
+    let timeout = std::time::Duration::from_millis(policy.pool_timeout_ms);
+    let mut attempt = 0;
+    loop {
+        let fetch = fetch_reserve(pair.clone(), provider.clone(), token_order_check, tick_words);
+        let (outcome, class) = match tokio::time::timeout(timeout, fetch).await {
+            Ok(Ok((address, state, order))) => return (PoolPreload::Loaded(address, state, order), attempt as usize),
+            Ok(Err(e)) => {
+                let class = classify_provider_error(&e.to_string());
+                (PoolPreload::Failed(e), class)
+            }
+            Err(_) => (PoolPreload::TimedOut, ErrorClass::Retryable),
+        };
+        attempt += 1;
+        let delay = if attempt > policy.retry.max_retries { None } else { policy.retry.delay_for(class, attempt) };
+        let Some(delay) = delay else {
+            return (outcome, attempt as usize - 1);
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Preload all reserves and state for all pools into the ReserveCache. At most
+/// `policy.concurrency` pools are in flight, each attempt is bounded by `policy.pool_timeout_ms`
+/// and failures are retried with backoff, so a few hanging calls can't stall the rest.
+/// Pair-file token order is verified per `token_order_check`.
+pub async fn preload_reserve_cache<M: Middleware + 'static>(
     pairs: &[PairInfo],
-    provider: Arc<Provider<Http>>,
+    provider: Arc<M>,
     reserve_cache: &Arc<ReserveCache>,
-    _max_concurrent: usize,
-) {
-    let batch_size = 1000;
-    let total_pairs = pairs.len();
+    policy: &PreloadPolicy,
+    token_order_check: TokenOrderCheck,
+    tick_words: u32,
+) -> PreloadSummary {
     let start_time = std::time::Instant::now();
-    println!("[CACHE] Starting preload for {} pairs in batches of {}", total_pairs, batch_size);
-    let mut success_count = 0;
-    let mut error_count = 0;
-    let mut v2_loaded = 0;
-    let mut v3_loaded = 0;
-
-    for (i, batch) in pairs.chunks(batch_size).enumerate() {
-        println!("[CACHE] Processing batch {} ({} pairs)", i + 1, batch.len());
-        // 1. Fetch all reserves in parallel (async)
-        let mut futs = FuturesUnordered::new();
-        for pair in batch.iter().cloned() {
-            let provider = provider.clone();
-            futs.push(fetch_reserve(pair, provider));
+    println!(
+        "[CACHE] Starting preload for {} pairs ({} at once, {} ms per pool, {} retries)",
+        pairs.len(), policy.concurrency, policy.pool_timeout_ms, policy.retry.max_retries
+    );
+    let summary = preload_pools_individually(pairs, provider, reserve_cache, policy, token_order_check, tick_words).await;
+    log_preload_done(start_time, pairs.len(), &summary, reserve_cache);
+    summary
+}
+
+/// The per-pool preload loop, without the start and completion logs
+async fn preload_pools_individually<M: Middleware + 'static>(
+    pairs: &[PairInfo],
+    provider: Arc<M>,
+    reserve_cache: &Arc<ReserveCache>,
+    policy: &PreloadPolicy,
+    token_order_check: TokenOrderCheck,
+    tick_words: u32,
+) -> PreloadSummary {
+    let mut summary = PreloadSummary::default();
+    let mut results = stream::iter(pairs.iter().cloned())
+        .map(|pair| {
+            let address = pair.pair_address;
+            let fetch = preload_pool(pair, provider.clone(), policy, token_order_check, tick_words);
+            async move { (address, fetch.await) }
+        })
+        .buffer_unordered(policy.concurrency.max(1));
+    let mut done = 0;
+    while let Some((address, (outcome, retries))) = results.next().await {
+        done += 1;
+        summary.retries += retries;
+        match outcome {
+            PoolPreload::Loaded(address, state, order) => {
+                summary.record_loaded(reserve_cache, address, state, order, token_order_check);
+            }
+            PoolPreload::Failed(e) => {
+                summary.failed += 1;
+                eprintln!("[CACHE] Pool {:?} not loaded: {}", address, e);
+            }
+            PoolPreload::TimedOut => {
+                summary.timed_out += 1;
+                eprintln!("[CACHE] Pool {:?} not loaded: timed out after {} ms", address, policy.pool_timeout_ms);
+            }
         }
-        let mut results = Vec::with_capacity(batch.len());
-        while let Some(res) = futs.next().await {
-            results.push(res);
+        if done % 1000 == 0 {
+            println!("[CACHE] {}/{} pools processed", done, pairs.len());
         }
-        // 2. Process results in parallel (Rayon)
-        results.par_iter().for_each(|res| {
-            if let Some((address, state)) = res {
-                reserve_cache.insert(*address, state.clone());
-            }
-        });
-        // 3. Stats
-        let batch_success = results.iter().filter(|x| x.is_some()).count();
-        let batch_error = results.len() - batch_success;
-        let batch_v2 = results.iter().filter(|x| x.as_ref().map(|(_, s)| s.pool_type == PoolType::V2).unwrap_or(false)).count();
-        let batch_v3 = results.iter().filter(|x| x.as_ref().map(|(_, s)| s.pool_type == PoolType::V3).unwrap_or(false)).count();
-        success_count += batch_success;
-        error_count += batch_error;
-        v2_loaded += batch_v2;
-        v3_loaded += batch_v3;
-        println!("[CACHE][BATCH {}] Success: {}, Errors: {}, V2: {}, V3: {}", i + 1, batch_success, batch_error, batch_v2, batch_v3);
     }
+    summary
+}
+
+impl PreloadSummary {
+    /// Count a fetched pool and insert it, unless its tokens mismatch under `TokenOrderCheck::Drop`
+    fn record_loaded(
+        &mut self,
+        reserve_cache: &ReserveCache,
+        address: H160,
+        state: PoolState,
+        order: TokenOrder,
+        token_order_check: TokenOrderCheck,
+    ) {
+        match order {
+            TokenOrder::Swapped => self.reordered += 1,
+            TokenOrder::Mismatch => self.mismatched += 1,
+            TokenOrder::Consistent => {}
+        }
+        if order == TokenOrder::Mismatch && token_order_check == TokenOrderCheck::Drop {
+            return;
+        }
+        self.succeeded += 1;
+        match state.pool_type {
+            PoolType::V2 => self.v2 += 1,
+            PoolType::V3 => self.v3 += 1,
+            PoolType::StableN { .. } => {}
+        }
+        self.inactive += state.inactive as usize;
+        reserve_cache.insert(address, state);
+    }
+
+    fn merge(&mut self, other: PreloadSummary) {
+        self.succeeded += other.succeeded;
+        self.failed += other.failed;
+        self.timed_out += other.timed_out;
+        self.retries += other.retries;
+        self.fallbacks += other.fallbacks;
+        self.v2 += other.v2;
+        self.v3 += other.v3;
+        self.inactive += other.inactive;
+        self.reordered += other.reordered;
+        self.mismatched += other.mismatched;
+    }
+}
+
+fn log_preload_done(start_time: std::time::Instant, total_pairs: usize, summary: &PreloadSummary, reserve_cache: &ReserveCache) {
     let duration = start_time.elapsed();
-    println!("[CACHE] Preload completed in {:.2?}", duration);
-    println!("[CACHE] Success: {}, Errors: {}, Total: {}", success_count, error_count, total_pairs);
-    println!("[CACHE] V2 pools: {}, V3 pools: {}", v2_loaded, v3_loaded);
+    println!("[CACHE] Preload completed in {:.2?}: {}", duration, summary);
     println!("[CACHE] Average speed: {:.2} pools/sec", total_pairs as f64 / duration.as_secs_f64());
-    
+
     // Debug: Show V3 pool fees
     let mut v3_fees = std::collections::HashMap::new();
     for entry in reserve_cache.iter() {
@@ -201,3 +916,524 @@ pub async fn preload_reserve_cache(
         println!("  {} bps ({}%): {} pools", fee, *fee as f64 / 100.0, count);
     }
 }
+
+/// Calls one pool adds to a Multicall3 batch: V2 `getReserves`; V3 `slot0`, `liquidity`, `fee`
+/// and `tickSpacing`; plus `token0` when the token order is verified
+fn multicall_call_count(pair: &PairInfo, verify: bool) -> usize {
+    let core = match pair.dex_version {
+        DexVersion::V2 => 1,
+        DexVersion::V3 => 4,
+    };
+    core + verify as usize
+}
+
+fn add_multicall_calls<M: Middleware>(multicall: &mut Multicall<M>, pair: &PairInfo, provider: Arc<M>, verify: bool) {
+    match pair.dex_version {
+        DexVersion::V2 => {
+            let contract = UniswapV2Pair::new(pair.pair_address, provider);
+            multicall.add_call(contract.get_reserves(), true);
+            if verify {
+                multicall.add_call(contract.token_0(), true);
+            }
+        }
+        DexVersion::V3 => {
+            let contract = UniswapV3Pool::new(pair.pair_address, provider);
+            multicall.add_call(contract.slot_0(), true);
+            multicall.add_call(contract.liquidity(), true);
+            multicall.add_call(contract.fee(), true);
+            multicall.add_call(contract.tick_spacing(), true);
+            if verify {
+                multicall.add_call(contract.token_0(), true);
+            }
+        }
+    }
+}
+
+/// Decode one pool's slice of an `aggregate3` result (laid out as in `add_multicall_calls`).
+/// None if a core call (`getReserves`, or V3 `slot0`/`liquidity`) reverted or returned data
+/// that doesn't decode; secondary calls fall back like the per-pool path. Also returns the
+/// on-chain `token0` when it was read.
+fn decode_multicall_pool(pair: &PairInfo, results: &[Result<Token, Bytes>], now: u64) -> Option<(PoolState, Option<H160>)> {
+    let token0_at = |idx: usize| results.get(idx).and_then(|r| r.clone().ok()).and_then(Token::into_address);
+    let mut state = PoolState {
+        pool_type: PoolType::V2,
+        token0: pair.token0,
+        token1: pair.token1,
+        dex_name: Some(pair.dex_name.clone()),
+        last_updated: now,
+        decimals0: pair.token0_decimals,
+        decimals1: pair.token1_decimals,
+        ..PoolState::default()
+    };
+    match pair.dex_version {
+        DexVersion::V2 => {
+            let Some(Ok(Token::Tuple(vals))) = results.first() else { return None };
+            state.reserve0 = Some(vals.first().cloned().and_then(Token::into_uint)?);
+            state.reserve1 = Some(vals.get(1).cloned().and_then(Token::into_uint)?);
+            Some((state, token0_at(1)))
+        }
+        DexVersion::V3 => {
+            let (Some(Ok(Token::Tuple(slot0))), Some(Ok(Token::Uint(liquidity)))) = (results.first(), results.get(1)) else {
+                return None;
+            };
+            let sqrt_price = slot0.first().cloned().and_then(Token::into_uint)?;
+            let tick = crate::v3_swap::decode_int24(slot0.get(1)?).ok()?;
+            let inactive = sqrt_price.is_zero() || liquidity.is_zero();
+            let fee = match results.get(2) {
+                Some(Ok(Token::Uint(fee))) if !inactive => Some(fee.low_u32()),
+                _ => None,
+            };
+            let tick_spacing = match results.get(3) {
+                Some(Ok(token)) if !inactive => crate::v3_swap::decode_int24(token).unwrap_or(60),
+                _ => 60,
+            };
+            if fee.is_none() && pair.fee.is_none() && !inactive {
+                eprintln!("[CACHE] V3 pool {:?}: fee() failed, pool will be skipped until it's known", pair.pair_address);
+            }
+            state.pool_type = PoolType::V3;
+            state.sqrt_price_x96 = Some(sqrt_price);
+            state.liquidity = Some(*liquidity);
+            state.tick = Some(tick);
+            state.fee = fee.or(pair.fee);
+            state.tick_spacing = Some(tick_spacing);
+            state.inactive = inactive;
+            Some((state, if inactive { None } else { token0_at(4) }))
+        }
+    }
+}
+
+/// Decode a whole batch's `aggregate3` result. Returns the loaded pools and the pairs to
+/// fetch again one by one; a result of the wrong length sends the whole batch back.
+fn decode_multicall_batch(
+    pairs: &[PairInfo],
+    results: &[Result<Token, Bytes>],
+    token_order_check: TokenOrderCheck,
+) -> (Vec<(H160, PoolState, TokenOrder)>, Vec<PairInfo>) {
+    let verify = token_order_check != TokenOrderCheck::Off;
+    let expected: usize = pairs.iter().map(|p| multicall_call_count(p, verify)).sum();
+    if results.len() != expected {
+        eprintln!("[CACHE] Multicall returned {} results for {} calls, fetching the batch per pool", results.len(), expected);
+        return (Vec::new(), pairs.to_vec());
+    }
+    let now = chrono::Utc::now().timestamp() as u64;
+    let mut loaded = Vec::new();
+    let mut fallback = Vec::new();
+    let mut idx = 0;
+    for pair in pairs {
+        let count = multicall_call_count(pair, verify);
+        match decode_multicall_pool(pair, &results[idx..idx + count], now) {
+            Some((mut state, onchain_token0)) => {
+                let order = if verify {
+                    reconcile_token_order(pair.pair_address, &mut state, onchain_token0)
+                } else {
+                    TokenOrder::Consistent
+                };
+                loaded.push((pair.pair_address, state, order));
+            }
+            None => fallback.push(pair.clone()),
+        }
+        idx += count;
+    }
+    (loaded, fallback)
+}
+
+/// Like `preload_reserve_cache`, but reads `policy.multicall_batch_size` pools per Multicall3
+/// `aggregate3` call, `policy.multicall_concurrency` calls at a time, instead of several
+/// round trips per pool. Pools that revert inside the aggregate, and whole batches that fail
+/// or take longer than `pool_timeout_ms`, go through the per-pool path with its retries.
+/// V3 ticks (`tick_words` > 0) still need per-pool bitmap calls after the batch.
+pub async fn preload_reserve_cache_multicall<M: Middleware + 'static>(
+    pairs: &[PairInfo],
+    provider: Arc<M>,
+    reserve_cache: &Arc<ReserveCache>,
+    policy: &PreloadPolicy,
+    token_order_check: TokenOrderCheck,
+    tick_words: u32,
+) -> PreloadSummary {
+    let start_time = std::time::Instant::now();
+    let template = match Multicall::new(provider.clone(), None).await {
+        Ok(multicall) => multicall.version(MulticallVersion::Multicall3),
+        Err(e) => {
+            eprintln!("[CACHE] Multicall3 unavailable ({}), preloading per pool", e);
+            return preload_reserve_cache(pairs, provider, reserve_cache, policy, token_order_check, tick_words).await;
+        }
+    };
+    let batch_size = policy.multicall_batch_size.max(1);
+    println!(
+        "[CACHE] Starting multicall preload for {} pairs ({} pools per call, {} calls at once)",
+        pairs.len(), batch_size, policy.multicall_concurrency
+    );
+    let verify = token_order_check != TokenOrderCheck::Off;
+    let timeout = std::time::Duration::from_millis(policy.pool_timeout_ms);
+
+    let mut summary = PreloadSummary::default();
+    let mut fallback = Vec::new();
+    let mut batches = stream::iter(pairs.chunks(batch_size))
+        .map(|batch| {
+            let mut multicall = template.clone();
+            for pair in batch {
+                add_multicall_calls(&mut multicall, pair, provider.clone(), verify);
+            }
+            async move {
+                let results = match tokio::time::timeout(timeout, multicall.call_raw()).await {
+                    Ok(Ok(results)) => results,
+                    Ok(Err(e)) => {
+                        eprintln!("[CACHE] Multicall of {} pools failed ({}), fetching them per pool", batch.len(), e);
+                        return (Vec::new(), batch.to_vec());
+                    }
+                    Err(_) => {
+                        eprintln!("[CACHE] Multicall of {} pools timed out, fetching them per pool", batch.len());
+                        return (Vec::new(), batch.to_vec());
+                    }
+                };
+                decode_multicall_batch(batch, &results, token_order_check)
+            }
+        })
+        .buffer_unordered(policy.multicall_concurrency.max(1));
+    let mut done = 0;
+    while let Some((loaded, failed)) = batches.next().await {
+        done += loaded.len() + failed.len();
+        let loaded = if tick_words > 0 { with_ticks(loaded, provider.clone(), policy, tick_words).await } else { loaded };
+        for (address, state, order) in loaded {
+            summary.record_loaded(reserve_cache, address, state, order, token_order_check);
+        }
+        fallback.extend(failed);
+        println!("[CACHE] {}/{} pools processed", done, pairs.len());
+    }
+
+    if !fallback.is_empty() {
+        println!("[CACHE] Fetching {} pools that failed in the multicall per pool", fallback.len());
+        summary.fallbacks += fallback.len();
+        let individual = preload_pools_individually(&fallback, provider, reserve_cache, policy, token_order_check, tick_words).await;
+        summary.merge(individual);
+    }
+    log_preload_done(start_time, pairs.len(), &summary, reserve_cache);
+    summary
+}
+
+/// Fill in the initialized ticks of the active V3 pools in a decoded batch
+async fn with_ticks<M: Middleware + 'static>(
+    loaded: Vec<(H160, PoolState, TokenOrder)>,
+    provider: Arc<M>,
+    policy: &PreloadPolicy,
+    tick_words: u32,
+) -> Vec<(H160, PoolState, TokenOrder)> {
+    stream::iter(loaded)
+        .map(|(address, mut state, order)| {
+            let provider = provider.clone();
+            async move {
+                if let (PoolType::V3, false, Some(tick), Some(tick_spacing)) =
+                    (&state.pool_type, state.inactive, state.tick, state.tick_spacing)
+                {
+                    let contract = UniswapV3Pool::new(address, provider);
+                    state.ticks = fetch_ticks(&contract, tick, tick_spacing, tick_words).await;
+                    state.tick_range = state.ticks.as_ref().map(|_| loaded_tick_range(tick, tick_spacing, tick_words));
+                    if state.ticks.is_none() {
+                        eprintln!("[CACHE] V3 pool {:?}: tick fetch failed, using single-range math", address);
+                    }
+                }
+                (address, state, order)
+            }
+        })
+        .buffer_unordered(policy.concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_bps() {
+        assert_eq!(diff_bps(U256::from(1000u64), U256::from(1000u64)), 0);
+        assert_eq!(diff_bps(U256::from(1000u64), U256::from(999u64)), 10);
+        assert_eq!(diff_bps(U256::from(999u64), U256::from(1000u64)), 10);
+        assert_eq!(diff_bps(U256::zero(), U256::from(5u64)), 10_000);
+    }
+
+    #[test]
+    fn test_loaded_tick_range_covers_whole_words() {
+        // Word 0 at spacing 60 plus one word either side: compressed ticks -256..=511
+        assert_eq!(loaded_tick_range(0, 60, 1), (-15_360, 30_660));
+        assert_eq!(loaded_tick_range(-1, 60, 0), (-15_360, -60));
+        // Word positions stop at the int16 bitmap index
+        assert_eq!(loaded_tick_range(887_220, 1, 40_000).1, i16::MAX as i32 * 256 + 255);
+    }
+
+    #[test]
+    fn test_spot_price() {
+        let v2 = PoolState {
+            pool_type: PoolType::V2,
+            reserve0: Some(U256::exp10(18) * 2),
+            reserve1: Some(U256::exp10(18) * 600),
+            ..Default::default()
+        };
+        assert!((v2.spot_price().unwrap() - 300.0).abs() < 1e-9);
+        // 18-decimal token0 against a 6-decimal token1: 2 token0 = 600 token1
+        let mixed = PoolState { reserve1: Some(U256::from(600_000_000u64)), decimals1: Some(6), ..v2.clone() };
+        assert!((mixed.spot_price().unwrap() - 300.0).abs() < 1e-9);
+        assert_eq!(PoolState { reserve0: Some(U256::zero()), ..v2.clone() }.spot_price(), None);
+
+        // sqrtPriceX96 = 2^96 * 2 -> price 4
+        let v3 = PoolState {
+            pool_type: PoolType::V3,
+            sqrt_price_x96: Some(U256::from(2u64) << 96),
+            liquidity: Some(U256::exp10(18)),
+            ..Default::default()
+        };
+        assert!((v3.spot_price().unwrap() - 4.0).abs() < 1e-9);
+        let v3_mixed = PoolState { decimals0: Some(6), decimals1: Some(18), ..v3.clone() };
+        assert!((v3_mixed.spot_price().unwrap() - 4e-12).abs() < 1e-21);
+        assert_eq!(PoolState { sqrt_price_x96: Some(U256::zero()), ..v3 }.spot_price(), None);
+
+        assert_eq!(price_diff_bps(Some(100.0), Some(99.0)), 100);
+        assert_eq!(price_diff_bps(Some(100.0), None), 10_000);
+        assert_eq!(price_diff_bps(None, None), 0);
+    }
+
+    #[test]
+    fn test_preload_from_snapshot() {
+        let (v2, v3) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let mut snapshot = StateSnapshot { block: 40_000_000, ..Default::default() };
+        snapshot.pools.insert(v2, PoolState {
+            pool_type: PoolType::V2,
+            reserve0: Some(U256::from(1_000u64)),
+            reserve1: Some(U256::from(2_000u64)),
+            ..Default::default()
+        });
+        snapshot.pools.insert(v3, PoolState {
+            pool_type: PoolType::V3,
+            sqrt_price_x96: Some(U256::one() << 96),
+            liquidity: Some(U256::from(5u64)),
+            tick: Some(0),
+            ..Default::default()
+        });
+        let path = std::env::temp_dir().join(format!("state_snapshot_test_{}.json", std::process::id()));
+        std::fs::write(&path, crate::schema::to_versioned_json(&snapshot).unwrap()).unwrap();
+
+        let cache = ReserveCache::new();
+        assert_eq!(preload_from_snapshot(&path, &cache, Some(40_000_000)).unwrap(), 2);
+        assert_eq!(cache.get(&v2).unwrap().reserve1, Some(U256::from(2_000u64)));
+        assert_eq!(cache.get(&v3).unwrap().sqrt_price_x96, Some(U256::one() << 96));
+
+        // A snapshot from another block must not be mixed into a pinned run
+        let other = ReserveCache::new();
+        let err = preload_from_snapshot(&path, &other, Some(40_000_001)).unwrap_err();
+        assert!(err.to_string().contains("block 40000000"), "{}", err);
+        assert!(other.is_empty());
+        assert_eq!(preload_from_snapshot(&path, &other, None).unwrap(), 2);
+        std::fs::remove_file(&path).ok();
+        assert!(preload_from_snapshot(&path, &other, None).is_err());
+    }
+
+    #[test]
+    fn test_active_pools_skips_inactive() {
+        let cache = ReserveCache::new();
+        cache.insert(H160::from_low_u64_be(1), PoolState { pool_type: PoolType::V2, ..Default::default() });
+        cache.insert(H160::from_low_u64_be(2), PoolState { pool_type: PoolType::V3, inactive: true, ..Default::default() });
+        cache.insert(H160::from_low_u64_be(3), PoolState { pool_type: PoolType::V3, ..Default::default() });
+        let mut active: Vec<H160> = cache.active_pools().map(|e| *e.key()).collect();
+        active.sort();
+        assert_eq!(active, vec![H160::from_low_u64_be(1), H160::from_low_u64_be(3)]);
+    }
+
+    #[test]
+    fn test_token_order_is_checked_against_chain() {
+        let (a, b, c) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3));
+        assert_eq!(check_token_order(a, b, Some(a)), TokenOrder::Consistent);
+        assert_eq!(check_token_order(b, a, Some(a)), TokenOrder::Swapped);
+        assert_eq!(check_token_order(a, b, Some(c)), TokenOrder::Mismatch);
+        // No on-chain answer: canonical ordering decides
+        assert_eq!(check_token_order(a, b, None), TokenOrder::Consistent);
+        assert_eq!(check_token_order(b, a, None), TokenOrder::Swapped);
+
+        let mut state = PoolState {
+            pool_type: PoolType::V2,
+            token0: b,
+            token1: a,
+            reserve0: Some(U256::from(10u64)),
+            reserve1: Some(U256::from(20u64)),
+            ..Default::default()
+        };
+        assert_eq!(reconcile_token_order(H160::zero(), &mut state, Some(a)), TokenOrder::Swapped);
+        // Reserves came from the pool itself and stay as they are
+        assert_eq!((state.token0, state.token1), (a, b));
+        assert_eq!((state.reserve0, state.reserve1), (Some(U256::from(10u64)), Some(U256::from(20u64))));
+    }
+
+    /// Answers `getReserves` for every pool, except `slow` pools never answer and `flaky`
+    /// pools fail their first call
+    #[derive(Debug, Default)]
+    struct PreloadRpc {
+        slow: Vec<H160>,
+        flaky: Vec<H160>,
+        calls: Arc<std::sync::Mutex<HashMap<H160, usize>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ethers::providers::JsonRpcClient for PreloadRpc {
+        type Error = ethers::providers::MockError;
+
+        async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+        where
+            T: std::fmt::Debug + Serialize + Send + Sync,
+            R: serde::de::DeserializeOwned + Send,
+        {
+            assert_eq!(method, "eth_call");
+            let params = serde_json::to_value(&params).map_err(ethers::providers::MockError::SerdeJson)?;
+            let to: H160 = params[0]["to"].as_str().unwrap().parse().unwrap();
+            let calls = {
+                let mut calls = self.calls.lock().unwrap();
+                let count = calls.entry(to).or_default();
+                *count += 1;
+                *count
+            };
+            if self.slow.contains(&to) {
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            }
+            if self.flaky.contains(&to) && calls == 1 {
+                return Err(ethers::providers::MockError::EmptyResponses);
+            }
+            let reserves = ethers::abi::encode(&[
+                Token::Uint(U256::from(to.to_low_u64_be() * 1000)),
+                Token::Uint(U256::from(2000u64)),
+                Token::Uint(U256::from(1u64)),
+            ]);
+            serde_json::from_value(serde_json::json!(Bytes::from(reserves)))
+                .map_err(ethers::providers::MockError::SerdeJson)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preload_survives_timeouts_and_retries_failures() {
+        let pair = |n: u64| PairInfo {
+            pair_address: H160::from_low_u64_be(n),
+            token0: H160::from_low_u64_be(100),
+            token1: H160::from_low_u64_be(101),
+            dex_name: "TestSwap".to_string(),
+            dex_version: DexVersion::V2,
+            factory_address: H160::zero(),
+            block_number: 0,
+            transaction_hash: String::new(),
+            token0_symbol: None,
+            token1_symbol: None,
+            token0_decimals: None,
+            token1_decimals: None,
+            liquidity_usd: None,
+            reserve0: None,
+            reserve1: None,
+            fee: None,
+        };
+        let pairs: Vec<PairInfo> = (1..=10).map(pair).collect();
+        let (slow, flaky) = (vec![H160::from_low_u64_be(3), H160::from_low_u64_be(7)], vec![H160::from_low_u64_be(5)]);
+        let calls = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let rpc = PreloadRpc { slow: slow.clone(), flaky: flaky.clone(), calls: calls.clone() };
+        let provider = Arc::new(ethers::providers::Provider::new(rpc));
+        let policy = PreloadPolicy {
+            concurrency: 4,
+            pool_timeout_ms: 50,
+            retry: crate::config::RetryPolicy { max_retries: 1, initial_backoff_secs: 0, ..Default::default() },
+        };
+        let cache = Arc::new(ReserveCache::default());
+
+        let started = std::time::Instant::now();
+        let summary = preload_reserve_cache(&pairs, provider, &cache, &policy, TokenOrderCheck::Off, 0).await;
+        // Hanging pools are abandoned at the per-call timeout, not after the 10s sleep
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        assert_eq!((summary.succeeded, summary.failed, summary.timed_out), (8, 0, 2));
+        assert_eq!(summary.v2, 8);
+        // One retry for each slow pool and one for the flaky pool
+        assert_eq!(summary.retries, 3);
+        assert_eq!(cache.len(), 8);
+        for pool in &slow {
+            assert!(cache.get(pool).is_none());
+        }
+        let state = cache.get(&flaky[0]).expect("flaky pool loaded on retry");
+        assert_eq!(state.reserve0, Some(U256::from(5000u64)));
+        assert_eq!(calls.lock().unwrap()[&flaky[0]], 2);
+    }
+
+    #[test]
+    fn test_multicall_batch_decodes_mixed_pools() {
+        let pair = |n: u64, dex_version: DexVersion, fee: Option<u32>| PairInfo {
+            pair_address: H160::from_low_u64_be(n),
+            token0: H160::from_low_u64_be(100),
+            token1: H160::from_low_u64_be(101),
+            dex_name: "TestSwap".to_string(),
+            dex_version,
+            factory_address: H160::zero(),
+            block_number: 0,
+            transaction_hash: String::new(),
+            token0_symbol: None,
+            token1_symbol: None,
+            token0_decimals: Some(18),
+            token1_decimals: Some(6),
+            liquidity_usd: None,
+            reserve0: None,
+            reserve1: None,
+            fee,
+        };
+        let uint = |v: u64| Token::Uint(U256::from(v));
+        let int24 = |v: i32| Token::Int(ethers::types::I256::from(v).into_raw());
+        let slot0 = |sqrt_price: U256, tick: i32| {
+            Ok(Token::Tuple(vec![Token::Uint(sqrt_price), int24(tick), uint(0), uint(1), uint(1), uint(0), Token::Bool(true)]))
+        };
+        let reverted = || Err(Bytes::from(vec![0x08, 0xc3, 0x79, 0xa0]));
+        let pairs = vec![
+            pair(1, DexVersion::V2, None),
+            pair(2, DexVersion::V3, Some(3000)),
+            pair(3, DexVersion::V2, None),  // getReserves reverts
+            pair(4, DexVersion::V3, Some(500)), // fee() reverts: falls back to the pair file
+            pair(5, DexVersion::V3, Some(100)), // slot0 reverts
+            pair(6, DexVersion::V3, None),  // never initialized
+        ];
+        let sqrt_price = U256::from(2u64) << 96;
+        let results = vec![
+            Ok(Token::Tuple(vec![uint(1_000), uint(2_000), uint(7)])),
+            slot0(sqrt_price, -887),
+            Ok(uint(5_000)),
+            Ok(uint(2500)),
+            Ok(int24(50)),
+            reverted(),
+            slot0(sqrt_price, 10),
+            Ok(uint(9_000)),
+            reverted(),
+            Ok(int24(10)),
+            reverted(),
+            Ok(uint(1)),
+            Ok(uint(100)),
+            Ok(int24(1)),
+            slot0(U256::zero(), 0),
+            Ok(uint(0)),
+            Ok(uint(3000)),
+            Ok(int24(60)),
+        ];
+
+        let (loaded, fallback) = decode_multicall_batch(&pairs, &results, TokenOrderCheck::Off);
+        let fallback: Vec<H160> = fallback.iter().map(|p| p.pair_address).collect();
+        assert_eq!(fallback, vec![H160::from_low_u64_be(3), H160::from_low_u64_be(5)]);
+        let state = |n: u64| &loaded.iter().find(|(a, _, _)| *a == H160::from_low_u64_be(n)).unwrap().1;
+        assert_eq!(loaded.len(), 4);
+
+        let v2 = state(1);
+        assert_eq!(v2.pool_type, PoolType::V2);
+        assert_eq!((v2.reserve0, v2.reserve1), (Some(U256::from(1_000u64)), Some(U256::from(2_000u64))));
+        assert_eq!((v2.decimals0, v2.decimals1), (Some(18), Some(6)));
+
+        // The on-chain fee tier wins over the pair file's
+        let v3 = state(2);
+        assert_eq!(v3.pool_type, PoolType::V3);
+        assert_eq!((v3.sqrt_price_x96, v3.liquidity, v3.tick), (Some(sqrt_price), Some(U256::from(5_000u64)), Some(-887)));
+        assert_eq!((v3.fee, v3.tick_spacing, v3.inactive), (Some(2500), Some(50), false));
+
+        assert_eq!(state(4).fee, Some(500));
+        assert!(state(6).inactive);
+
+        // A result count that doesn't match the calls sends the whole batch back
+        let (loaded, fallback) = decode_multicall_batch(&pairs, &results[1..], TokenOrderCheck::Off);
+        assert!(loaded.is_empty());
+        assert_eq!(fallback.len(), pairs.len());
+    }
+}
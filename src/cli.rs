@@ -0,0 +1,80 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use ethers::types::{H160, U256};
+use std::path::PathBuf;
+
+/// Command line options for the arbitrage bot
+#[derive(Debug, Parser)]
+#[command(name = "arb-rust-bot", about = "Ultra-low latency BSC arbitrage bot")]
+pub struct Cli {
+    /// Path to a JSON config file (missing fields use defaults)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<String>,
+
+    /// Console verbosity
+    #[arg(long, global = true, value_enum, default_value_t = LogLevel::Info)]
+    pub log_level: LogLevel,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the live arbitrage bot (default)
+    Run,
+    /// Keep the reserve cache synced and publish pool updates over IPC (no route cache, no execution)
+    Feed,
+    /// Detect opportunities and log the trades that would be sent, without executing (shadow run)
+    Mirror,
+    /// Run with warm caches but don't execute until promoted (SIGUSR1 or POST /promote)
+    Standby,
+    /// Fetch pairs from DEX factories and exit
+    FetchPairs {
+        /// Continue from the saved per-factory checkpoint, appending to the pair files
+        /// (default: start over, refusing if the pair files already hold pairs)
+        #[arg(long, conflicts_with = "fresh")]
+        resume: bool,
+        /// Start over even if the pair files hold pairs, truncating them
+        #[arg(long)]
+        fresh: bool,
+    },
+    /// Simulate all cached routes for one token and exit (no live loop)
+    Simulate {
+        /// TokenX address
+        #[arg(long, value_name = "ADDR", value_parser = parse_h160)]
+        token: H160,
+        /// TokenX amount in wei
+        #[arg(long, value_name = "N", value_parser = parse_u256)]
+        amount: Option<U256>,
+        /// Only simulate routes through this pool (default: every pool the token trades in)
+        #[arg(long, value_name = "ADDR", value_parser = parse_h160)]
+        pool: Option<H160>,
+    },
+    /// Replay recorded opportunities (a mirror log) against a historical state snapshot and exit
+    Backtest {
+        /// State snapshot to replay against (default: `state_snapshot` from the config)
+        #[arg(long, value_name = "PATH")]
+        snapshot: Option<PathBuf>,
+        /// JSON lines decision log to replay (default: `mirror_log_path` in the log directory)
+        #[arg(long, value_name = "PATH")]
+        decisions: Option<PathBuf>,
+    },
+    /// Pre-flight checks for RPC, WS, contract and caches
+    Health,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+fn parse_h160(s: &str) -> Result<H160, String> {
+    s.parse::<H160>().map_err(|e| format!("invalid address {s}: {e}"))
+}
+
+fn parse_u256(s: &str) -> Result<U256, String> {
+    U256::from_dec_str(s).map_err(|e| format!("invalid amount {s}: {e:?}"))
+}
@@ -1,6 +1,12 @@
-use std::collections::HashMap;
-use ethers::types::Address;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use ethers::types::{Address, U256};
 use serde::{Deserialize, Serialize};
+use crate::failover_provider::ErrorClass;
+use crate::route_cache::RouteFilter;
+use crate::route_cache_codec::RouteCacheFormat;
 
 /// DEX Factory Addresses on BSC
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +23,257 @@ pub enum DexVersion {
     V3,
 }
 
+/// What the process runs: the full bot, only the cache + price tracker publishing updates,
+/// detection only with every intended trade written to `mirror_log_path` instead of sent,
+/// or the full bot with execution held back until it is promoted (warm standby)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum RunMode {
+    #[default]
+    Trade,
+    Feed,
+    Mirror,
+    Standby,
+}
+
+/// How the executor prices gas just before sending a transaction
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum GasStrategy {
+    /// Always bid this gas price (wei)
+    Fixed(U256),
+    /// Multiple of the node's `eth_gasPrice` (e.g. 1.5 = bid 150%)
+    OracleMultiple(f64),
+    /// Latest base fee plus this percentile (0-100) of recent priority fees from `eth_feeHistory`
+    FeeHistoryPercentile(u8),
+    /// EIP-1559 transaction tipping `priority_gwei`, with `maxFeePerGas` = latest base fee ×
+    /// `max_multiplier` + tip. Falls back to a legacy `eth_gasPrice` bid if the node reports no base fee.
+    Eip1559 { priority_gwei: f64, max_multiplier: f64 },
+}
+
+/// Where signed arbitrage transactions are sent
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub enum Submission {
+    /// Broadcast through the RPC node to the public mempool
+    #[default]
+    Public,
+    /// Send each transaction as a one-transaction bundle for the next block to a private
+    /// relay's `eth_sendBundle` (48Club, merkle, bloXroute's BSC endpoint). `auth_env` names
+    /// the env var holding the relay's `Authorization` header, if it needs one.
+    PrivateRelay { endpoint: String, auth_env: Option<String> },
+}
+
+/// What the reserve preload does about pools whose pair-file token order disagrees with the
+/// pool's on-chain `token0()`. Reversed pairs are corrected in every mode but `Off`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum TokenOrderCheck {
+    /// Trust the pair files (no extra `token0()` call per pool)
+    Off,
+    /// Keep pools whose on-chain token0 is neither cached token, with a warning
+    Warn,
+    /// Leave such pools out of the cache
+    #[default]
+    Drop,
+}
+
+/// How much the executor contract is approved to pull when a token's allowance runs short
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ApprovalMode {
+    /// One `U256::MAX` approval per token; no approval tx after the first trade
+    #[default]
+    Max,
+    /// Exactly the trade's input each time: no standing allowance, one extra tx per trade
+    Exact,
+    /// This amount (wei), or the trade's input if that is larger
+    Amount(U256),
+}
+
+impl ApprovalMode {
+    /// Allowance to approve for a trade that needs `min_amount`
+    pub fn approval_amount(&self, min_amount: U256) -> U256 {
+        match self {
+            ApprovalMode::Max => U256::MAX,
+            ApprovalMode::Exact => min_amount,
+            ApprovalMode::Amount(amount) => (*amount).max(min_amount),
+        }
+    }
+}
+
+/// Criterion for ordering routes whose profit percentage is exactly equal (`route_tie_break`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RouteTieBreak {
+    FewerHops,       // fewer pools to swap through
+    HigherLiquidity, // larger bottleneck (shallowest pool) liquidity
+    LowerGas,        // lower estimated swap gas (V2 < V3 < stable per hop)
+}
+
+/// Which executor contract a route is sent to (see `Config::executor_contract`):
+/// `Flashloan` when the input exceeds the inventory held in the contract,
+/// otherwise `V3` if any hop is a V3 pool (needs the swap callback), else `V2`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ExecutorStrategy {
+    V2,
+    V3,
+    Flashloan,
+}
+
+/// Timeouts for the main opportunity loop and the price tracker's WS sessions, in seconds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Timeouts {
+    pub heartbeat_timeout_secs: u64, // main loop: health warning after this long without an opportunity
+    pub heartbeat_interval_secs: u64, // main loop: periodic heartbeat log
+    pub opportunity_recv_timeout_secs: u64, // main loop: wait for the next opportunity before looping
+    pub activity_timeout_secs: u64, // price tracker: restart a session after this long without events
+    pub tracker_heartbeat_interval_secs: u64, // price tracker: periodic V2 heartbeat log
+    pub ws_connect_timeout_secs: u64, // connecting to a WS endpoint
+    pub subscribe_timeout_secs: u64, // subscribing to V2/V3 logs
+    pub stream_poll_timeout_secs: u64, // wait for the next log before re-checking activity
+    pub event_processing_timeout_secs: u64, // handling one Sync/Swap event
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            heartbeat_timeout_secs: 300, // 5 minutes
+            heartbeat_interval_secs: 60,
+            opportunity_recv_timeout_secs: 30,
+            activity_timeout_secs: 300, // 5 minutes
+            tracker_heartbeat_interval_secs: 30,
+            ws_connect_timeout_secs: 10,
+            subscribe_timeout_secs: 10,
+            stream_poll_timeout_secs: 10,
+            event_processing_timeout_secs: 10,
+        }
+    }
+}
+
+/// Where log files are written and how the maintenance task keeps the directory in check
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct LogPolicy {
+    pub dir: PathBuf, // executor.log, latency and per-second opportunity logs, trades.csv and the mirror log all go here
+    pub max_file_bytes: u64, // live files this large are compressed into an archive (0 = no limit)
+    pub max_age_secs: u64, // files not written to for this long are archived, e.g. finished per-second files (0 = never)
+    pub total_cap_bytes: u64, // oldest archives are deleted while the directory is larger than this (0 = no cap)
+    pub maintenance_interval_secs: u64, // 0 = maintenance off
+}
+
+impl Default for LogPolicy {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("logs"),
+            max_file_bytes: 64 << 20, // 64 MiB
+            max_age_secs: 3_600, // 1 hour
+            total_cap_bytes: 2 << 30, // 2 GiB
+            maintenance_interval_secs: 300, // 5 minutes
+        }
+    }
+}
+
+/// Slippage buffer per hop, by pool type. Concentrated V3 liquidity moves further for the
+/// same competing flow than a V2 pool, and a StableSwap pool barely moves at all.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PoolTypeSlippage {
+    pub v2: u32, // basis points
+    pub v3: u32,
+    pub stable: u32,
+}
+
+impl Default for PoolTypeSlippage {
+    fn default() -> Self {
+        Self {
+            v2: 30, // 0.3%
+            v3: 80, // 0.8%
+            stable: 10, // 0.1%
+        }
+    }
+}
+
+impl PoolTypeSlippage {
+    /// Buffer for an executor pool type code (0 = V2, 1 = V3, 2 = StableSwap)
+    pub fn for_pool_type(&self, pool_type: u8) -> u32 {
+        match pool_type {
+            1 => self.v3,
+            2 => self.stable,
+            _ => self.v2,
+        }
+    }
+
+    pub fn max(&self) -> u32 {
+        self.v2.max(self.v3).max(self.stable)
+    }
+}
+
+/// How often a failed V2/V3 monitoring session is retried on the same WS endpoint
+/// before the supervisor fails over to the next one
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff_secs: u64, // doubled after every failed attempt
+    pub max_backoff_secs: u64,
+    pub rate_limit_backoff_factor: u32, // rate-limited attempts wait this many times the normal backoff
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3, // kept low so a bad endpoint is abandoned quickly
+            initial_backoff_secs: 2,
+            max_backoff_secs: 32,
+            rate_limit_backoff_factor: 4,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `attempt` (1-based): initial * 2^(attempt-1), capped
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(32);
+        Duration::from_secs(self.initial_backoff_secs.saturating_mul(factor).min(self.max_backoff_secs))
+    }
+
+    /// Wait before retry number `attempt` after an error of `class`; None for fatal errors,
+    /// which aren't retried
+    pub fn delay_for(&self, class: ErrorClass, attempt: u32) -> Option<Duration> {
+        match class {
+            ErrorClass::Fatal => None,
+            ErrorClass::RateLimited => Some(self.backoff(attempt) * self.rate_limit_backoff_factor.max(1)),
+            ErrorClass::Retryable => Some(self.backoff(attempt)),
+        }
+    }
+
+    /// `delay_for` scaled by a random factor in [0.5, 1.0], so clients that lost the same
+    /// endpoint don't all come back in lockstep
+    pub fn jittered_delay_for(&self, class: ErrorClass, attempt: u32) -> Option<Duration> {
+        use rand::Rng;
+        self.delay_for(class, attempt).map(|delay| delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0)))
+    }
+}
+
+/// How the startup reserve preload spreads its RPC calls
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PreloadPolicy {
+    pub concurrency: usize, // pools fetched at once
+    pub pool_timeout_ms: u64, // one attempt at a pool's calls is abandoned after this
+    pub retry: RetryPolicy, // failed and timed-out pools are retried with backoff; fatal errors are not
+    pub multicall_batch_size: usize, // pools per Multicall3 aggregate3 call; 0 = fetch every pool on its own
+    pub multicall_concurrency: usize, // aggregate3 calls in flight
+}
+
+impl Default for PreloadPolicy {
+    fn default() -> Self {
+        Self {
+            concurrency: 200,
+            pool_timeout_ms: 10_000,
+            retry: RetryPolicy { max_retries: 2, initial_backoff_secs: 1, max_backoff_secs: 8, rate_limit_backoff_factor: 4 },
+            multicall_batch_size: 500,
+            multicall_concurrency: 8,
+        }
+    }
+}
+
 /// Base tokens for arbitrage
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BaseToken {
@@ -24,11 +281,24 @@ pub struct BaseToken {
     pub address: Address,
     pub decimals: u8,
     pub is_stable: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool, // disabled bases are left out of route building and detection
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 /// Main configuration for the arbitrage bot
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    // Run Mode
+    pub mode: RunMode,
+    pub feed_socket_path: PathBuf, // unix socket the price feed publishes pool updates on
+    pub mirror_log_path: PathBuf, // JSON lines decision log written in mirror mode, relative to `logs.dir`
+    pub dry_run: bool, // run the full pipeline but only log each built trade to executor.log; nothing is sent
+    
     // DEX Configuration
     pub dexes: Vec<DexConfig>,
     
@@ -38,26 +308,151 @@ pub struct Config {
     // Base Tokens
     pub base_tokens: Vec<BaseToken>,
     
+    // Data Files
+    pub pair_files: Vec<PathBuf>,
+    pub token_tax_file: PathBuf,
+    pub tax_detection: bool, // probe tokens missing from token_tax_file on a fork before routing through them
+    pub tax_probe_router: Address, // V2 router the probe buys and sells through, against WBNB
+    
     // Network Configuration
     pub rpc_url: String,
+    pub rpc_urls: Vec<String>, // extra HTTP endpoints for failover, tried after rpc_url
     pub ws_url: String,
+    pub ws_urls: Vec<String>, // extra WS endpoints for price tracker failover, tried after ws_url
     pub chain_id: u64,
+    pub confirmation_blocks: u64, // blocks an event must be buried before the cache applies it (0 = latest)
     
     // Arbitrage Settings
     pub min_profit_threshold: u128, // Minimum profit in wei
-    pub max_slippage: u32, // Maximum slippage in basis points
+    #[serde(alias = "min_profit_usd")]
+    pub min_net_profit_usd: f64, // detection drops routes earning less than this after gas, in USD...
+    pub min_profit_bps: f64, // ...or less than this share of the input; both must hold
+    pub trace_profit_floors: bool, // log each route rejected by a profit floor, and which one
+    pub slippage_bps: PoolTypeSlippage, // per-hop buffer, compounded over the route's hops
+    pub adaptive_slippage: bool, // widen the slippage buffer on InsufficientProfit reverts, narrow it on success
+    pub min_slippage_bps: u32, // lower bound for the adaptive buffer
+    pub max_adaptive_slippage_bps: u32, // upper bound for the adaptive buffer
+    pub slippage_step_bps: u32, // adjustment per InsufficientProfit revert
     pub gas_limit: u64,
     pub gas_price: u64,
+    pub gas_strategy: GasStrategy, // gas price bid at send time
+    pub submission: Submission, // public mempool or a private bundle relay
+    pub gas_per_hop: u64, // gas estimate per swap, priced at the resolved gas_strategy bid (0 = ignore gas)
+    pub gas_refresh_interval_secs: u64, // gas_strategy is re-resolved for gas estimates this often (0 = estimate at gas_price)
+    pub price_refresh_interval_secs: u64, // USD prices are re-read from stable pairs in the reserve cache this often (0 = seed prices only)
+    pub tx_deadline_secs: u64, // executor reverts the trade if it's mined later than this after being built
+    pub approval_mode: ApprovalMode, // allowance the wallet grants the executor for a trade's input token
+    pub max_input_per_base: HashMap<Address, U256>, // Base token -> max input amount in wei
+    pub inventory_reconcile_interval: u64, // seconds
+    pub executor_contracts: HashMap<ExecutorStrategy, Address>, // strategies without an entry use CONTRACT_ADDRESS
+    pub route_filter: RouteFilter, // excluded pools, hop bounds, required base for candidate routes
+    pub token_allowlist: HashSet<Address>, // if non-empty, routes only go through these tokens (base tokens always allowed)
+    pub token_denylist: HashSet<Address>, // routes never touch these tokens, bases included
+    pub enable_split_execution: bool, // split the input across the top non-overlapping routes
+    pub split_max_routes: usize, // routes a split may use
+    pub split_steps: usize, // allocation granularity: input is split in this many chunks
+    pub route_tie_break: Vec<RouteTieBreak>, // applied in order when routes tie on profit percentage
+    pub sequential_leg_simulation: bool, // simulate the sell leg against post-buy pool state (slower, more accurate)
+    pub max_route_hops: usize, // longest cycle, in pools, the route cache is built with (2..=6)
+    pub route_cache_file: Option<PathBuf>, // built route cache is saved here and reused while its inputs match; None = rebuild every start
+    pub route_cache_format: RouteCacheFormat, // encoding of route_cache_file
+    pub cross_base_routes: bool, // also build base_a -> X -> base_b routes, profit valued in base_a at oracle prices
+    pub stable_pools: Vec<Address>, // Curve-style StableSwap pools to load alongside the pair files
+    pub stable_refresh_interval_secs: u64, // re-read stable pool balances this often (0 = only after a reorg)
+    pub token_order_check: TokenOrderCheck, // verify pair-file token0/token1 against the chain during preload
+    pub v3_tick_words: u32, // tick-bitmap words loaded either side of each V3 pool's price at preload (0 = single-range math)
+    pub state_snapshot: Option<PathBuf>, // load pool state from this snapshot file instead of RPC (offline runs)
+    pub pinned_block: Option<u64>, // block offline runs are pinned to; state_snapshot must match it
     
     // Performance Settings
     pub max_parallel_workers: usize,
+    pub preload: PreloadPolicy, // concurrency, per-pool timeout and retries of the startup reserve preload
+    pub max_detection_us: u64, // per-event route simulation budget; remaining routes are skipped (0 = unlimited)
+    pub opportunity_queue_size: usize, // pending opportunities; the lowest net profit is dropped when full
+    pub opportunity_max_age_ms: u64, // queued opportunities older than this are discarded unsent
+    pub max_opportunity_age_blocks: u64, // drop opportunities detected more than this many blocks before the head
+    pub max_concurrent_executions: usize, // executor transactions in flight at once
+    pub latency_report_interval_secs: u64, // log detection latency percentiles this often (0 = off)
+    pub quote_cache_size: usize, // route quotes kept for reuse until any reserve update (0 = off)
+    pub idle_token_window_secs: u64, // tokens without a profitable opportunity this long are only probed (0 = never demote)
+    pub idle_token_probe_rate: u64, // an idle token's routes are still simulated on 1 in N of its events
+    pub token_activity_file: PathBuf, // per-token profitability stats, kept across restarts
+    pub token_activity_save_interval_secs: u64,
     pub cache_update_interval: u64, // milliseconds
     pub event_buffer_size: usize,
+    
+    // Cache Consistency Check
+    pub consistency_check_interval: u64, // seconds
+    pub consistency_sample_size: usize, // pools checked per run
+    pub consistency_tolerance_bps: u64, // allowed drift before correcting
+    
+    // Timeouts & Retries
+    pub timeouts: Timeouts,
+    pub retry_policy: RetryPolicy, // price tracker session retries before WS failover
+    pub ws_reconnect: RetryPolicy, // rebuilding a dead WS connection; the bot exits once these run out
+    
+    // Logs
+    pub logs: LogPolicy,
+    
+    // Dashboard
+    pub dashboard_addr: Option<SocketAddr>, // read-only state feed (HTTP + WebSocket); None = disabled
+    pub metrics_port: Option<u16>, // Prometheus `GET /metrics` on 0.0.0.0:port; None = disabled
+    pub routes_api_addr: Option<SocketAddr>, // `GET /routes/{token}` route quotes from the live caches; None = disabled
+    pub control_addr: Option<SocketAddr>, // standby status/promotion endpoint; None = SIGUSR1 only
+}
+
+/// Highest V2 swap fee accepted, in basis points. Anything above 10% is almost certainly a
+/// V3-style fee (hundredths of a bip, e.g. 2500) entered in the wrong unit.
+const MAX_V2_FEE_BPS: u32 = 1_000;
+
+/// Longest route cycle `max_route_hops` accepts
+const MAX_ROUTE_HOPS: usize = 6;
+
+/// One problem found by `Config::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    ZeroAddress { field: String },
+    NoBaseTokens,
+    NoEnabledBaseTokens,
+    InvalidUrl { field: String, url: String, expected: &'static str },
+    FeeOutOfRange { field: String, fee: u32, max: u32 },
+    OutOfRange { field: String, value: u64, reason: &'static str },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::ZeroAddress { field } => write!(f, "{} is the zero address", field),
+            ConfigError::NoBaseTokens => write!(f, "base_tokens is empty"),
+            ConfigError::NoEnabledBaseTokens => write!(f, "no base token is enabled"),
+            ConfigError::InvalidUrl { field, url, expected } => {
+                write!(f, "{} {:?} is not a valid URL (expected {})", field, url, expected)
+            }
+            ConfigError::FeeOutOfRange { field, fee, max } => {
+                write!(f, "{} is {} bps, above the {} bps limit", field, fee, max)
+            }
+            ConfigError::OutOfRange { field, value, reason } => write!(f, "{} = {}: {}", field, value, reason),
+        }
+    }
+}
+
+/// `url` uses one of `schemes` (`"http"` matches `http://…`) and has something after it
+fn has_scheme(url: &str, schemes: &[&str]) -> bool {
+    schemes.iter().any(|scheme| {
+        url.strip_prefix(scheme)
+            .and_then(|rest| rest.strip_prefix("://"))
+            .is_some_and(|host| !host.is_empty() && !host.starts_with('/') && !host.contains(char::is_whitespace))
+    })
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            mode: RunMode::Trade,
+            feed_socket_path: PathBuf::from("/tmp/arb_price_feed.sock"),
+            mirror_log_path: PathBuf::from("mirror.jsonl"),
+            dry_run: false,
+            
             dexes: vec![
                 // PancakeSwap V2
                 DexConfig {
@@ -152,6 +547,7 @@ impl Default for Config {
                         .unwrap(),
                     decimals: 18,
                     is_stable: false,
+                    enabled: true,
                 },
                 // BUSD
                 BaseToken {
@@ -161,6 +557,7 @@ impl Default for Config {
                         .unwrap(),
                     decimals: 18,
                     is_stable: true,
+                    enabled: true,
                 },
                 // USDT
                 BaseToken {
@@ -170,6 +567,7 @@ impl Default for Config {
                         .unwrap(),
                     decimals: 18,
                     is_stable: true,
+                    enabled: true,
                 },
                 // USDC
                 BaseToken {
@@ -179,6 +577,7 @@ impl Default for Config {
                         .unwrap(),
                     decimals: 18,
                     is_stable: true,
+                    enabled: true,
                 },
                 // CAKE
                 BaseToken {
@@ -188,6 +587,7 @@ impl Default for Config {
                         .unwrap(),
                     decimals: 18,
                     is_stable: false,
+                    enabled: true,
                 },
                 
                 // BTCB
@@ -198,6 +598,7 @@ impl Default for Config {
                         .unwrap(),
                     decimals: 18,
                     is_stable: false,
+                    enabled: true,
                 },
                 //weth
                 BaseToken {
@@ -207,29 +608,243 @@ impl Default for Config {
                         .unwrap(),
                     decimals: 18,
                     is_stable: false,
+                    enabled: true,
                 },
             ],
             
+            // Pair data files (JSONL)
+            pair_files: vec![
+                PathBuf::from("data/liquid_pairs_v2_accurate_taxed.jsonl"),
+                PathBuf::from("data/liquid_pairs_v3_new.jsonl"),
+            ],
+            token_tax_file: PathBuf::from("data/token_zero_transfer_tax.jsonl"),
+            tax_detection: true,
+            tax_probe_router: "0x10ED43C718714eb63d5aA57B78B54704E256024E".parse().unwrap(), // PancakeSwap V2
+            
             // Local node configuration
             rpc_url: "http://127.0.0.1:8545".to_string(),
+            rpc_urls: Vec::new(),
             ws_url: "ws://127.0.0.1:8546".to_string(),
+            ws_urls: Vec::new(),
             chain_id: 56,
+            confirmation_blocks: 0, // act on latest block
             
             // Arbitrage Settings
             min_profit_threshold: 1000000000000000, // 0.001 BNB in wei
-            max_slippage: 100, // 1%
+            min_net_profit_usd: 0.02,
+            min_profit_bps: 0.0,
+            trace_profit_floors: false,
+            slippage_bps: PoolTypeSlippage::default(),
+            adaptive_slippage: false,
+            min_slippage_bps: 10,
+            max_adaptive_slippage_bps: 500, // 5%
+            slippage_step_bps: 25,
             gas_limit: 500000,
             gas_price: 5000000000, // 5 Gwei
+            gas_strategy: GasStrategy::OracleMultiple(1.0), // node's eth_gasPrice
+            submission: Submission::Public,
+            gas_per_hop: 100_000, // a V2 swap plus its share of the executor's overhead
+            gas_refresh_interval_secs: 3, // ~1 BSC block
+            price_refresh_interval_secs: 30,
+            tx_deadline_secs: 30, // ~10 BSC blocks
+            approval_mode: ApprovalMode::Max,
+            max_input_per_base: {
+                let mut caps = HashMap::new();
+                let one = U256::exp10(18);
+                caps.insert("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c".parse().unwrap(), one * 10);   // 10 WBNB
+                caps.insert("0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56".parse().unwrap(), one * 5000); // 5000 BUSD
+                caps.insert("0x55d398326f99059fF775485246999027B3197955".parse().unwrap(), one * 5000); // 5000 USDT
+                caps.insert("0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d".parse().unwrap(), one * 5000); // 5000 USDC
+                caps
+            },
+            inventory_reconcile_interval: 30, // 30s
+            executor_contracts: HashMap::new(),
+            route_filter: RouteFilter::default(),
+            token_allowlist: HashSet::new(),
+            token_denylist: HashSet::new(),
+            enable_split_execution: false,
+            split_max_routes: 3,
+            split_steps: 10,
+            route_tie_break: vec![RouteTieBreak::FewerHops, RouteTieBreak::HigherLiquidity, RouteTieBreak::LowerGas],
+            sequential_leg_simulation: false,
+            max_route_hops: 3,
+            route_cache_file: Some(PathBuf::from("data/route_cache.bin")),
+            route_cache_format: RouteCacheFormat::Binary,
+            cross_base_routes: false,
+            stable_pools: Vec::new(),
+            stable_refresh_interval_secs: 3, // about one BSC block
+            token_order_check: TokenOrderCheck::Drop,
+            v3_tick_words: 1, // 256 tick spacings either side, ~±15% at the 60 spacing
+            state_snapshot: None,
+            pinned_block: None,
             
             // Performance Settings
             max_parallel_workers: num_cpus::get(),
+            preload: PreloadPolicy::default(),
+            max_detection_us: 250_000, // 250ms, far above a normal detection
+            opportunity_queue_size: 64,
+            opportunity_max_age_ms: 1_500, // half a BSC block
+            max_opportunity_age_blocks: 1, // detected at N: still sent at N+1, dropped at N+2
+            max_concurrent_executions: 4,
+            latency_report_interval_secs: 60,
+            quote_cache_size: 4096,
+            idle_token_window_secs: 7 * 86_400, // a week
+            idle_token_probe_rate: 20,
+            token_activity_file: PathBuf::from("data/token_activity.json"),
+            token_activity_save_interval_secs: 300, // 5 minutes
             cache_update_interval: 100, // 100ms
             event_buffer_size: 10000,
+            
+            // Cache Consistency Check
+            consistency_check_interval: 60, // 1 minute
+            consistency_sample_size: 50,
+            consistency_tolerance_bps: 10, // 0.1%
+            
+            // Timeouts & Retries
+            timeouts: Timeouts::default(),
+            retry_policy: RetryPolicy::default(),
+            ws_reconnect: RetryPolicy { max_retries: 10, initial_backoff_secs: 1, max_backoff_secs: 60, rate_limit_backoff_factor: 4 },
+            
+            // Logs
+            logs: LogPolicy::default(),
+            
+            // Dashboard
+            dashboard_addr: None,
+            metrics_port: None,
+            routes_api_addr: None,
+            control_addr: None,
         }
     }
 }
 
 impl Config {
+    /// Load config from a JSON file; fields missing from the file keep their defaults
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+    
+    /// Check addresses, base tokens, endpoint URLs and fee/slippage ranges.
+    /// Returns every problem found, not just the first, so they can be fixed in one go.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        let zero = |field: String, address: &Address, errors: &mut Vec<ConfigError>| {
+            if address.is_zero() {
+                errors.push(ConfigError::ZeroAddress { field });
+            }
+        };
+
+        if self.base_tokens.is_empty() {
+            errors.push(ConfigError::NoBaseTokens);
+        } else if !self.base_tokens.iter().any(|token| token.enabled) {
+            errors.push(ConfigError::NoEnabledBaseTokens);
+        }
+        for token in &self.base_tokens {
+            zero(format!("base_tokens[{}].address", token.symbol), &token.address, &mut errors);
+        }
+        for dex in &self.dexes {
+            zero(format!("dexes[{}].factory_address", dex.name), &dex.factory_address, &mut errors);
+            if dex.version == DexVersion::V2 && dex.fee > MAX_V2_FEE_BPS {
+                errors.push(ConfigError::FeeOutOfRange {
+                    field: format!("dexes[{}].fee", dex.name),
+                    fee: dex.fee,
+                    max: MAX_V2_FEE_BPS,
+                });
+            }
+        }
+        let mut dex_fees: Vec<_> = self.dex_fees.iter().collect();
+        dex_fees.sort();
+        for (name, fee) in dex_fees {
+            if *fee > MAX_V2_FEE_BPS {
+                errors.push(ConfigError::FeeOutOfRange { field: format!("dex_fees[{}]", name), fee: *fee, max: MAX_V2_FEE_BPS });
+            }
+        }
+        let mut executors: Vec<_> = self.executor_contracts.iter().collect();
+        executors.sort_by_key(|(strategy, _)| format!("{:?}", strategy));
+        for (strategy, address) in executors {
+            zero(format!("executor_contracts[{:?}]", strategy), address, &mut errors);
+        }
+
+        let urls = std::iter::once(("rpc_url", &self.rpc_url)).chain(self.rpc_urls.iter().map(|url| ("rpc_urls", url)));
+        for (field, url) in urls {
+            if !has_scheme(url, &["http", "https"]) {
+                errors.push(ConfigError::InvalidUrl { field: field.to_string(), url: url.clone(), expected: "http:// or https://" });
+            }
+        }
+        if let Submission::PrivateRelay { endpoint, .. } = &self.submission {
+            if !has_scheme(endpoint, &["http", "https"]) {
+                errors.push(ConfigError::InvalidUrl {
+                    field: "submission.PrivateRelay.endpoint".to_string(),
+                    url: endpoint.clone(),
+                    expected: "http:// or https://",
+                });
+            }
+        }
+        let urls = std::iter::once(("ws_url", &self.ws_url)).chain(self.ws_urls.iter().map(|url| ("ws_urls", url)));
+        for (field, url) in urls {
+            if !has_scheme(url, &["ws", "wss"]) {
+                errors.push(ConfigError::InvalidUrl { field: field.to_string(), url: url.clone(), expected: "ws:// or wss://" });
+            }
+        }
+
+        if self.chain_id == 0 {
+            errors.push(ConfigError::OutOfRange { field: "chain_id".to_string(), value: 0, reason: "must be non-zero" });
+        }
+        for (field, bps) in [
+            ("slippage_bps.v2", self.slippage_bps.v2),
+            ("slippage_bps.v3", self.slippage_bps.v3),
+            ("slippage_bps.stable", self.slippage_bps.stable),
+            ("min_slippage_bps", self.min_slippage_bps),
+            ("max_adaptive_slippage_bps", self.max_adaptive_slippage_bps),
+        ] {
+            if bps >= 10_000 {
+                errors.push(ConfigError::OutOfRange { field: field.to_string(), value: bps as u64, reason: "must be below 10000 bps (100%)" });
+            }
+        }
+        if self.preload.concurrency == 0 {
+            errors.push(ConfigError::OutOfRange { field: "preload.concurrency".to_string(), value: 0, reason: "must be at least 1" });
+        }
+        if self.preload.multicall_batch_size > 0 && self.preload.multicall_concurrency == 0 {
+            errors.push(ConfigError::OutOfRange {
+                field: "preload.multicall_concurrency".to_string(),
+                value: 0,
+                reason: "must be at least 1 when multicall_batch_size is set",
+            });
+        }
+        if !(2..=MAX_ROUTE_HOPS).contains(&self.max_route_hops) {
+            errors.push(ConfigError::OutOfRange {
+                field: "max_route_hops".to_string(),
+                value: self.max_route_hops as u64,
+                reason: "must be between 2 and 6 pools",
+            });
+        }
+        if self.min_slippage_bps > self.max_adaptive_slippage_bps {
+            errors.push(ConfigError::OutOfRange {
+                field: "min_slippage_bps".to_string(),
+                value: self.min_slippage_bps as u64,
+                reason: "must not exceed max_adaptive_slippage_bps",
+            });
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+    
+    /// Check that configured data files exist. Errors if the tax file or every pair file is missing;
+    /// returns the missing pair files otherwise so the caller can warn about them.
+    pub fn validate_paths(&self) -> Result<Vec<PathBuf>, String> {
+        let missing_pairs: Vec<PathBuf> = self.pair_files.iter().filter(|p| !p.exists()).cloned().collect();
+        let mut errors = Vec::new();
+        if !self.token_tax_file.exists() {
+            errors.push(format!("token_tax_file not found: {}", self.token_tax_file.display()));
+        }
+        if self.pair_files.is_empty() {
+            errors.push("pair_files is empty".to_string());
+        } else if missing_pairs.len() == self.pair_files.len() {
+            errors.push(format!("none of the pair_files exist: {:?}", self.pair_files));
+        }
+        if errors.is_empty() { Ok(missing_pairs) } else { Err(errors.join("; ")) }
+    }
+    
     /// Get DEX by name
     pub fn get_dex_by_name(&self, name: &str) -> Option<&DexConfig> {
         self.dexes.iter().find(|dex| dex.name == name)
@@ -245,6 +860,27 @@ impl Config {
         self.base_tokens.iter().find(|token| token.address == address)
     }
     
+    /// Base tokens routes may start and end in
+    pub fn enabled_base_tokens(&self) -> Vec<&BaseToken> {
+        self.base_tokens.iter().filter(|token| token.enabled).collect()
+    }
+    
+    /// Whether `address` is a configured base token that is currently enabled
+    pub fn is_base_token_enabled(&self, address: &Address) -> bool {
+        self.base_tokens.iter().any(|token| token.enabled && token.address == *address)
+    }
+    
+    /// Whether routes may pass through `address` under the token allow/deny lists.
+    /// The denylist wins; base tokens don't need to be on the allowlist.
+    pub fn is_token_routable(&self, address: &Address) -> bool {
+        if self.token_denylist.contains(address) {
+            return false;
+        }
+        self.token_allowlist.is_empty()
+            || self.token_allowlist.contains(address)
+            || self.base_tokens.iter().any(|token| token.address == *address)
+    }
+    
     /// Get all V2 DEXes
     pub fn get_v2_dexes(&self) -> Vec<&DexConfig> {
         self.dexes.iter().filter(|dex| matches!(dex.version, DexVersion::V2)).collect()
@@ -264,6 +900,48 @@ impl Config {
     pub fn get_v2_fee(&self, dex_name: &str) -> u32 {
         self.dex_fees.get(dex_name).copied().unwrap_or(25) // Default to 0.25% if not found
     }
+    
+    /// All HTTP endpoints in failover order: `rpc_url` first, then `rpc_urls` (duplicates dropped)
+    pub fn http_endpoints(&self) -> Vec<String> {
+        let mut urls = vec![self.rpc_url.clone()];
+        for url in &self.rpc_urls {
+            if !urls.contains(url) {
+                urls.push(url.clone());
+            }
+        }
+        urls
+    }
+    
+    /// All WS endpoints in failover order: `ws_url` first, then `ws_urls` (duplicates dropped)
+    pub fn ws_endpoints(&self) -> Vec<String> {
+        let mut urls = vec![self.ws_url.clone()];
+        for url in &self.ws_urls {
+            if !urls.contains(url) {
+                urls.push(url.clone());
+            }
+        }
+        urls
+    }
+    
+    /// Executor contract for `strategy`, falling back to `default` (CONTRACT_ADDRESS)
+    pub fn executor_contract(&self, strategy: ExecutorStrategy, default: Address) -> Address {
+        self.executor_contracts.get(&strategy).copied().unwrap_or(default)
+    }
+
+    /// Executor contracts that trade from their own inventory (the V2 and V3 ones), deduplicated
+    pub fn inventory_holders(&self, default: Address) -> Vec<Address> {
+        let mut holders = vec![
+            self.executor_contract(ExecutorStrategy::V2, default),
+            self.executor_contract(ExecutorStrategy::V3, default),
+        ];
+        holders.dedup();
+        holders
+    }
+    
+    /// Get max input cap for a base token (None = uncapped)
+    pub fn get_max_input_for_base(&self, base_token: &Address) -> Option<U256> {
+        self.max_input_per_base.get(base_token).copied()
+    }
 }
 
 #[cfg(test)]
@@ -304,4 +982,164 @@ mod tests {
         let biswap = config.get_dex_by_name("BiSwap").unwrap();
         assert_eq!(biswap.fee, 10); // 0.1%
     }
+    
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config: Config = serde_json::from_str(r#"{"rpc_url": "http://10.0.0.1:8545"}"#).unwrap();
+        assert_eq!(config.rpc_url, "http://10.0.0.1:8545");
+        assert_eq!(config.ws_url, Config::default().ws_url);
+        assert!(!config.dexes.is_empty());
+    }
+    
+    #[test]
+    fn test_base_token_enabled_flag() {
+        // A base token without `enabled` in the file stays enabled
+        let config: Config = serde_json::from_str(
+            r#"{"base_tokens": [
+                {"symbol": "WBNB", "address": "0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c", "decimals": 18, "is_stable": false, "enabled": false},
+                {"symbol": "USDT", "address": "0x55d398326f99059fF775485246999027B3197955", "decimals": 18, "is_stable": true}
+            ]}"#,
+        ).unwrap();
+        let enabled: Vec<&str> = config.enabled_base_tokens().iter().map(|t| t.symbol.as_str()).collect();
+        assert_eq!(enabled, vec!["USDT"]);
+        assert!(!config.is_base_token_enabled(&config.base_tokens[0].address));
+        assert!(config.is_base_token_enabled(&config.base_tokens[1].address));
+        assert!(Config::default().base_tokens.iter().all(|t| t.enabled));
+    }
+    
+    #[test]
+    fn test_executor_contract_falls_back_to_default() {
+        let config: Config = serde_json::from_str(
+            r#"{"executor_contracts": {"Flashloan": "0x00000000000000000000000000000000000000f1"}}"#,
+        ).unwrap();
+        let default = Address::from_low_u64_be(1);
+        assert_eq!(config.executor_contract(ExecutorStrategy::Flashloan, default), Address::from_low_u64_be(0xf1));
+        assert_eq!(config.executor_contract(ExecutorStrategy::V3, default), default);
+        // The flashloan executor borrows its input, so only the shared default holds inventory
+        assert_eq!(config.inventory_holders(default), vec![default]);
+    }
+    
+    #[test]
+    fn test_endpoints_put_primary_first_without_duplicates() {
+        let config: Config = serde_json::from_str(
+            r#"{"ws_url": "ws://a", "ws_urls": ["ws://b", "ws://a", "ws://c"], "rpc_urls": ["http://x"]}"#,
+        ).unwrap();
+        assert_eq!(config.ws_endpoints(), vec!["ws://a", "ws://b", "ws://c"]);
+        assert_eq!(config.http_endpoints(), vec![Config::default().rpc_url, "http://x".to_string()]);
+    }
+    
+    #[test]
+    fn test_validate_reports_every_problem() {
+        assert_eq!(Config::default().validate(), Ok(()));
+
+        let mut config = Config::default();
+        config.base_tokens.iter_mut().for_each(|token| token.enabled = false);
+        config.base_tokens[0].address = Address::zero();
+        config.rpc_url = "127.0.0.1:8545".to_string();
+        config.ws_urls = vec!["wss://ok.example".to_string(), "http://wrong-scheme".to_string()];
+        config.dexes[0].fee = 2500;
+        config.executor_contracts.insert(ExecutorStrategy::Flashloan, Address::zero());
+        let errors = config.validate().unwrap_err();
+        let symbol = config.base_tokens[0].symbol.clone();
+        assert_eq!(errors, vec![
+            ConfigError::NoEnabledBaseTokens,
+            ConfigError::ZeroAddress { field: format!("base_tokens[{}].address", symbol) },
+            ConfigError::FeeOutOfRange { field: "dexes[PancakeSwap V2].fee".to_string(), fee: 2500, max: MAX_V2_FEE_BPS },
+            ConfigError::ZeroAddress { field: format!("executor_contracts[{:?}]", ExecutorStrategy::Flashloan) },
+            ConfigError::InvalidUrl { field: "rpc_url".to_string(), url: "127.0.0.1:8545".to_string(), expected: "http:// or https://" },
+            ConfigError::InvalidUrl { field: "ws_urls".to_string(), url: "http://wrong-scheme".to_string(), expected: "ws:// or wss://" },
+        ]);
+        assert_eq!(errors[0].to_string(), "no base token is enabled");
+
+        config.base_tokens.clear();
+        assert!(config.validate().unwrap_err().contains(&ConfigError::NoBaseTokens));
+        let one_hop = Config { max_route_hops: 1, ..Config::default() };
+        assert_eq!(one_hop.validate().unwrap_err(), vec![ConfigError::OutOfRange {
+            field: "max_route_hops".to_string(),
+            value: 1,
+            reason: "must be between 2 and 6 pools",
+        }]);
+        assert!(has_scheme("https://bsc-dataseed.binance.org", &["http", "https"]));
+        assert!(!has_scheme("http://", &["http"]));
+    }
+    
+    #[test]
+    fn test_validate_paths() {
+        let dir = std::env::temp_dir();
+        let existing = dir.join(format!("config_test_{}.jsonl", std::process::id()));
+        std::fs::write(&existing, "").unwrap();
+        let missing = dir.join("config_test_does_not_exist.jsonl");
+        
+        let mut config = Config::default();
+        config.token_tax_file = existing.clone();
+        config.pair_files = vec![existing.clone(), missing.clone()];
+        assert_eq!(config.validate_paths().unwrap(), vec![missing.clone()]);
+        
+        config.pair_files = vec![missing.clone()];
+        assert!(config.validate_paths().is_err());
+        
+        config.pair_files = vec![existing.clone()];
+        config.token_tax_file = missing;
+        assert!(config.validate_paths().is_err());
+        
+        std::fs::remove_file(existing).ok();
+    }
+    
+    #[test]
+    fn test_max_input_per_base() {
+        let config = Config::default();
+        
+        let wbnb = config.get_base_token_by_symbol("WBNB").unwrap().address;
+        assert_eq!(config.get_max_input_for_base(&wbnb), Some(U256::exp10(18) * 10));
+        
+        // Tokens without a cap are uncapped
+        assert!(config.get_max_input_for_base(&Address::zero()).is_none());
+    }
+    
+    #[test]
+    fn test_token_lists() {
+        let wbnb: Address = "0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c".parse().unwrap();
+        let (cake, scam) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let mut config = Config::default();
+        assert!(config.is_token_routable(&scam));
+
+        config.token_denylist.insert(scam);
+        assert!(!config.is_token_routable(&scam) && config.is_token_routable(&cake));
+
+        // With an allowlist only listed tokens and the bases pass; the denylist still wins
+        config.token_allowlist.insert(scam);
+        assert!(!config.is_token_routable(&cake) && !config.is_token_routable(&scam));
+        assert!(config.is_token_routable(&wbnb));
+        config.token_denylist.insert(wbnb);
+        assert!(!config.is_token_routable(&wbnb));
+    }
+
+    #[test]
+    fn test_timeouts_and_retry_policy() {
+        let config: Config = serde_json::from_str(
+            r#"{"timeouts": {"activity_timeout_secs": 120}, "retry_policy": {"max_retries": 10}}"#,
+        ).unwrap();
+        assert_eq!(config.timeouts.activity_timeout_secs, 120);
+        assert_eq!(config.timeouts.heartbeat_timeout_secs, 300);
+        assert_eq!(config.retry_policy.max_retries, 10);
+        assert_eq!(config.retry_policy.initial_backoff_secs, 2);
+        
+        // 2, 4, 8, ... capped at max_backoff_secs
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(8));
+        assert_eq!(policy.backoff(6), Duration::from_secs(32));
+        assert_eq!(policy.backoff(100), Duration::from_secs(32));
+
+        assert_eq!(policy.delay_for(ErrorClass::Retryable, 2), Some(Duration::from_secs(4)));
+        assert_eq!(policy.delay_for(ErrorClass::RateLimited, 2), Some(Duration::from_secs(16)));
+        assert_eq!(policy.delay_for(ErrorClass::Fatal, 1), None);
+
+        // Jitter only ever shortens the wait, by at most half
+        for _ in 0..100 {
+            let delay = policy.jittered_delay_for(ErrorClass::Retryable, 3).unwrap();
+            assert!(delay >= Duration::from_secs(4) && delay <= Duration::from_secs(8), "{:?}", delay);
+        }
+        assert_eq!(policy.jittered_delay_for(ErrorClass::Fatal, 1), None);
+    }
 }
@@ -0,0 +1,214 @@
+use crate::cache::{ReserveCache, ReserveCacheExt, CACHE_DRIFT_COUNT};
+use crate::inventory::InventoryManager;
+use crate::latency_metrics;
+use crate::opportunity::ArbitrageOpportunity;
+use crate::price_tracker::{ACTIVE_WS_ENDPOINT, WS_FAILOVER_COUNT};
+use crate::quote_cache::live_quotes;
+use crate::schema::SCHEMA_VERSION;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Opportunities kept for the `recent_opportunities` list
+const RECENT_OPPORTUNITIES: usize = 20;
+/// Window for `opportunities_per_min`
+const RATE_WINDOW_SECS: u64 = 60;
+
+/// Compact view of one opportunity for the dashboard
+#[derive(Debug, Clone, Serialize)]
+pub struct OpportunitySummary {
+    pub timestamp: u64,
+    pub block: u64,
+    pub pool: String,
+    pub token_x: String,
+    pub profit: String, // wei of the route's base token
+    pub profit_percentage: f64,
+    pub routes: usize, // profitable routes found for the event
+    pub pools: Vec<String>, // best route, buy then sell
+}
+
+#[derive(Default)]
+struct OpportunityLog {
+    recent: VecDeque<OpportunitySummary>,
+    timestamps: VecDeque<u64>, // within RATE_WINDOW_SECS
+}
+
+impl OpportunityLog {
+    fn push(&mut self, summary: OpportunitySummary, now: u64) {
+        self.timestamps.push_back(now);
+        self.prune(now);
+        if self.recent.len() == RECENT_OPPORTUNITIES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(summary);
+    }
+
+    /// Drop timestamps older than the rate window, so the log stays bounded without readers
+    fn prune(&mut self, now: u64) {
+        while self.timestamps.front().is_some_and(|&t| t + RATE_WINDOW_SECS <= now) {
+            self.timestamps.pop_front();
+        }
+    }
+}
+
+static OPPORTUNITY_COUNT: AtomicU64 = AtomicU64::new(0);
+static OPPORTUNITIES: Lazy<Mutex<OpportunityLog>> = Lazy::new(|| Mutex::new(OpportunityLog::default()));
+
+/// Record an opportunity handed to the executor; only called with the dashboard enabled
+pub fn record_opportunity(opportunity: &ArbitrageOpportunity) {
+    OPPORTUNITY_COUNT.fetch_add(1, Ordering::Relaxed);
+    let now = chrono::Utc::now().timestamp() as u64;
+    let best = opportunity.best_route.as_ref();
+    let summary = OpportunitySummary {
+        timestamp: now,
+        block: opportunity.decoded_swap.block_number,
+        pool: format!("{:?}", opportunity.decoded_swap.pool_address),
+        token_x: format!("{:?}", opportunity.decoded_swap.token_x),
+        profit: opportunity.estimated_profit.to_string(),
+        profit_percentage: best.map(|r| r.profit_percentage).unwrap_or_default(),
+        routes: opportunity.profitable_routes.len(),
+        pools: best.map(|r| r.merged_pools.iter().map(|p| format!("{:?}", p)).collect()).unwrap_or_default(),
+    };
+    OPPORTUNITIES.lock().unwrap_or_else(|e| e.into_inner()).push(summary, now);
+}
+
+/// What the snapshot reads besides the global counters
+#[derive(Clone)]
+pub struct DashboardContext {
+    pub reserve_cache: Arc<ReserveCache>,
+    pub inventory: Arc<InventoryManager>,
+}
+
+impl DashboardContext {
+    /// Current bot state; the schema is documented in the README ("Dashboard")
+    pub fn snapshot(&self) -> serde_json::Value {
+        let now = chrono::Utc::now().timestamp() as u64;
+        let (per_min, last_profit, recent) = {
+            let mut log = OPPORTUNITIES.lock().unwrap_or_else(|e| e.into_inner());
+            log.prune(now);
+            let last_profit = log.recent.back().map(|o| o.profit.clone());
+            (log.timestamps.len(), last_profit, log.recent.iter().rev().cloned().collect::<Vec<_>>())
+        };
+        // Executor contract -> token -> balance and reservations
+        let mut inventory = serde_json::Map::new();
+        for (holder, token, entry) in self.inventory.snapshot() {
+            let tokens = inventory.entry(format!("{:?}", holder)).or_insert_with(|| json!({}));
+            tokens[format!("{:?}", token)] = json!({
+                "balance": entry.balance.to_string(),
+                "reserved": entry.reserved.to_string(),
+            });
+        }
+        let quote_cache = live_quotes().map(|quotes| {
+            let (hits, misses) = quotes.stats();
+            json!({ "hits": hits, "misses": misses, "hit_rate": quotes.hit_rate() })
+        });
+        json!({
+            "schema_version": SCHEMA_VERSION,
+            "timestamp": now,
+            "opportunities_total": OPPORTUNITY_COUNT.load(Ordering::Relaxed),
+            "opportunities_per_min": per_min,
+            "last_profit": last_profit,
+            "cache_size": self.reserve_cache.len(),
+            "active_subscriptions": self.reserve_cache.active_pools().count(),
+            "ws_endpoint": ACTIVE_WS_ENDPOINT.load(Ordering::Relaxed),
+            "ws_failovers": WS_FAILOVER_COUNT.load(Ordering::Relaxed),
+            "cache_drift_corrections": CACHE_DRIFT_COUNT.load(Ordering::Relaxed),
+            "inventory": inventory,
+            "latency": latency_metrics::snapshot(),
+            "quote_cache": quote_cache,
+            "recent_opportunities": recent,
+        })
+    }
+}
+
+/// Serve the read-only dashboard: `GET /state` returns one snapshot,
+/// `GET /ws` upgrades to a WebSocket that pushes a snapshot every second
+pub async fn start_dashboard(addr: SocketAddr, ctx: DashboardContext) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/state", get(state_handler))
+        .route("/ws", get(ws_handler))
+        .with_state(Arc::new(ctx));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!("📊 [DASHBOARD] Serving bot state on http://{}/state and ws://{}/ws", addr, addr);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("[DASHBOARD] Server stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+async fn state_handler(State(ctx): State<Arc<DashboardContext>>) -> Json<serde_json::Value> {
+    Json(ctx.snapshot())
+}
+
+async fn ws_handler(ws: WebSocketUpgrade, State(ctx): State<Arc<DashboardContext>>) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| stream_snapshots(socket, ctx))
+}
+
+async fn stream_snapshots(mut socket: WebSocket, ctx: Arc<DashboardContext>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        if socket.send(Message::Text(ctx.snapshot().to_string())).await.is_err() {
+            break; // client went away
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::PoolState;
+    use ethers::types::{H160, U256};
+
+    #[test]
+    fn test_snapshot_fields() {
+        let reserve_cache = Arc::new(ReserveCache::new());
+        reserve_cache.insert(H160::from_low_u64_be(1), PoolState::default());
+        reserve_cache.insert(H160::from_low_u64_be(2), PoolState { inactive: true, ..Default::default() });
+        let inventory = Arc::new(InventoryManager::new());
+        let (holder, token) = (H160::from_low_u64_be(0xc0), H160::from_low_u64_be(9));
+        inventory.set_balance(holder, token, U256::from(500u64));
+        assert!(inventory.try_reserve(holder, token, U256::from(200u64)));
+
+        let snapshot = DashboardContext { reserve_cache, inventory }.snapshot();
+        assert_eq!(snapshot["cache_size"], 2);
+        assert_eq!(snapshot["active_subscriptions"], 1);
+        let entry = &snapshot["inventory"][format!("{:?}", holder)][format!("{:?}", token)];
+        assert_eq!(entry["balance"], "500");
+        assert_eq!(entry["reserved"], "200");
+        assert!(snapshot["recent_opportunities"].is_array());
+    }
+
+    #[test]
+    fn test_opportunity_log_stays_bounded_without_snapshots() {
+        let summary = OpportunitySummary {
+            timestamp: 0,
+            block: 0,
+            pool: String::new(),
+            token_x: String::new(),
+            profit: "0".to_string(),
+            profit_percentage: 0.0,
+            routes: 1,
+            pools: Vec::new(),
+        };
+        let mut log = OpportunityLog::default();
+        // One opportunity a second for ten minutes, never read
+        for now in 0..600 {
+            log.push(summary.clone(), now);
+        }
+        assert_eq!(log.timestamps.len(), RATE_WINDOW_SECS as usize);
+        assert_eq!(log.recent.len(), RECENT_OPPORTUNITIES);
+    }
+}
@@ -1,13 +1,31 @@
 use ethers::types::{H160, U256};
 use crate::arbitrage_finder::SimulatedRoute;
 use crate::route_cache::PoolMeta;
+use crate::cache::ReserveCache;
+use crate::config::{ApprovalMode, Config, ExecutorStrategy, GasStrategy, PoolTypeSlippage};
+use crate::token_tax::TokenTaxMap;
+use crate::price_oracle::PriceOracle;
+use crate::inventory::InventoryManager;
+use crate::token_index::TokenIndexMap;
+use crate::trade_ledger;
+use crate::simulate_swap_path::{simulate_buy_path_amounts_array, simulate_sell_path_amounts_array};
 use std::collections::HashMap;
-use crate::bindings::DirectSwapExecutor;
+use crate::bindings::{DirectSwapExecutor, IERC20};
 use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
 use std::sync::Arc;
+use crate::failover_provider::HttpProvider;
+use crate::submitter::Submitter;
 use hex;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use crate::utils::ethers_tx_to_revm_txenv;
+use crate::log_maintenance::log_path;
+use std::io::Write;
+use revm::context::TxEnv;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BuySellExecutionData {
     // Buy path data
     pub buy_tokens: Vec<H160>,
@@ -20,14 +38,33 @@ pub struct BuySellExecutionData {
     pub sell_pools: Vec<H160>,
     pub sell_pool_types: Vec<u8>,
     pub sell_amounts: Vec<U256>,
+
+    // Which executor contract the route needs
+    pub strategy: ExecutorStrategy,
+
+    // Unix timestamp after which the contract rejects the trade
+    pub deadline: U256,
+
+    // Buffer for the whole route: per-hop `Config::slippage_bps`, compounded
+    pub slippage_bps: u32,
+
+    // Smallest final output accepted; sent in place of the sell leg's last amount so the contract reverts below it
+    pub min_amount_out: U256,
+
+    // Opportunity the trade came from, for log correlation (0 = none; set by the caller)
+    pub opportunity_id: u64,
 }
 
 impl BuySellExecutionData {
-    /// Build from a SimulatedRoute, using a pool address -> PoolMeta map
+    /// Build from a SimulatedRoute, using a pool address -> PoolMeta map.
+    /// The buy input is clamped to `config.max_input_per_base` and the route re-simulated if capped.
     pub fn from_simulated_route(
         route: &SimulatedRoute,
         pool_meta_map: &HashMap<H160, PoolMeta>,
         token_index_map: &crate::token_index::TokenIndexMap,
+        reserve_cache: &ReserveCache,
+        token_tax_map: &Arc<TokenTaxMap>,
+        config: &Config,
     ) -> Option<Self> {
         // Convert token indices to addresses for buy path
         let buy_tokens: Vec<H160> = route.buy_path.hops.iter()
@@ -45,65 +82,180 @@ impl BuySellExecutionData {
             return None;
         }
 
-        // Build buy pool types
-        let mut buy_pool_types = Vec::new();
-        for pool_addr in &route.buy_pools {
-            if let Some(meta) = pool_meta_map.get(pool_addr) {
-                match &meta.dex_type {
-                    crate::route_cache::DEXType::PancakeV3
-                    | crate::route_cache::DEXType::BiSwapV3
-                    | crate::route_cache::DEXType::ApeSwapV3
-                    | crate::route_cache::DEXType::BakeryV3
-                    | crate::route_cache::DEXType::SushiV3 => {
-                        buy_pool_types.push(1u8);
-                    }
-                    crate::route_cache::DEXType::Other(name) if name.contains("V3") => {
-                        buy_pool_types.push(1u8);
-                    }
-                    _ => {
-                        buy_pool_types.push(0u8);
-                    }
-                }
-            } else {
-                return None;
-            }
-        }
+        // Executor pool type per hop
+        let buy_pool_types: Vec<u8> = route.buy_pools.iter()
+            .map(|pool| pool_meta_map.get(pool).map(|meta| pool_type_code(&meta.dex_type)))
+            .collect::<Option<_>>()?;
+        let sell_pool_types: Vec<u8> = route.sell_pools.iter()
+            .map(|pool| pool_meta_map.get(pool).map(|meta| pool_type_code(&meta.dex_type)))
+            .collect::<Option<_>>()?;
 
-        // Build sell pool types
-        let mut sell_pool_types = Vec::new();
-        for pool_addr in &route.sell_pools {
-            if let Some(meta) = pool_meta_map.get(pool_addr) {
-                match &meta.dex_type {
-                    crate::route_cache::DEXType::PancakeV3
-                    | crate::route_cache::DEXType::BiSwapV3
-                    | crate::route_cache::DEXType::ApeSwapV3
-                    | crate::route_cache::DEXType::BakeryV3
-                    | crate::route_cache::DEXType::SushiV3 => {
-                        sell_pool_types.push(1u8);
-                    }
-                    crate::route_cache::DEXType::Other(name) if name.contains("V3") => {
-                        sell_pool_types.push(1u8);
-                    }
-                    _ => {
-                        sell_pool_types.push(0u8);
-                    }
-                }
-            } else {
-                return None;
+        // --- Position size cap per base token ---
+        let mut buy_amounts = route.buy_amounts.clone();
+        let mut sell_amounts = route.sell_amounts.clone();
+        let base_token = *buy_tokens.first()?;
+        let amount_in = *buy_amounts.first()?;
+        if let Some(cap) = config.get_max_input_for_base(&base_token) {
+            if amount_in > cap {
+                // Shrink the tokenX amount proportionally; buy cost is convex so the new input stays under the cap
+                let token_x_amount = *buy_amounts.last()?;
+                let capped_x_amount = token_x_amount.saturating_mul(cap) / amount_in;
+                buy_amounts = simulate_buy_path_amounts_array(
+                    &route.buy_path,
+                    capped_x_amount,
+                    reserve_cache,
+                    token_index_map,
+                    token_tax_map,
+                    config,
+                )?;
+                sell_amounts = simulate_sell_path_amounts_array(
+                    &route.sell_path,
+                    capped_x_amount,
+                    reserve_cache,
+                    token_index_map,
+                    token_tax_map,
+                    config,
+                )?;
+                println!(
+                    "[RISK] Trade size-capped for base {:?}: input {} -> {} (cap {}), expected out {:?}",
+                    base_token,
+                    amount_in,
+                    buy_amounts.first()?,
+                    cap,
+                    sell_amounts.last()
+                );
             }
         }
 
+        let strategy = route_strategy(&buy_pool_types, &sell_pool_types);
+        let slippage_bps = route_slippage_bps(buy_pool_types.iter().chain(&sell_pool_types), &config.slippage_bps);
+        let round_trip_input = (sell_tokens.last() == Some(&base_token)).then(|| buy_amounts[0]);
+        let min_amount_out = min_amount_out(*sell_amounts.last()?, slippage_bps, round_trip_input);
         Some(Self {
             buy_tokens,
             buy_pools: route.buy_pools.clone(),
             buy_pool_types,
-            buy_amounts: route.buy_amounts.clone(),
+            buy_amounts,
             sell_tokens,
             sell_pools: route.sell_pools.clone(),
             sell_pool_types,
-            sell_amounts: route.sell_amounts.clone(),
+            sell_amounts,
+            strategy,
+            deadline: deadline_from_now(config.tx_deadline_secs),
+            slippage_bps,
+            min_amount_out,
+            opportunity_id: 0,
         })
     }
+
+    /// Sell leg amounts as sent: the simulated ones with the final output lowered to `min_amount_out`
+    pub fn sell_amounts_with_min_out(&self) -> Vec<U256> {
+        let mut amounts = self.sell_amounts.clone();
+        if let Some(last) = amounts.last_mut() {
+            *last = self.min_amount_out;
+        }
+        amounts
+    }
+
+    /// The deadline has already passed; sending would only burn gas on a revert
+    pub fn is_expired(&self) -> bool {
+        self.deadline <= U256::from(chrono::Utc::now().timestamp().max(0) as u64)
+    }
+}
+
+/// Executor pool type code for a DEX: 0 = V2, 1 = V3, 2 = StableSwap
+pub fn pool_type_code(dex_type: &crate::route_cache::DEXType) -> u8 {
+    match dex_type {
+        crate::route_cache::DEXType::PancakeV3
+        | crate::route_cache::DEXType::BiSwapV3
+        | crate::route_cache::DEXType::ApeSwapV3
+        | crate::route_cache::DEXType::BakeryV3
+        | crate::route_cache::DEXType::SushiV3 => 1,
+        crate::route_cache::DEXType::Other(name) if name.contains("V3") => 1,
+        crate::route_cache::DEXType::StableSwap => 2,
+        _ => 0,
+    }
+}
+
+/// Route-wide buffer from each hop's pool type buffer, compounded: every hop can lose its own
+/// share of what the previous hops delivered. Rounded up.
+pub fn route_slippage_bps<'a>(pool_types: impl IntoIterator<Item = &'a u8>, slippage: &PoolTypeSlippage) -> u32 {
+    // Share of the output kept, in hundredths of a basis point so three-hop routes don't round to zero
+    let kept = pool_types.into_iter().fold(100_000_000u64, |kept, pool_type| {
+        kept * (10_000 - slippage.for_pool_type(*pool_type).min(10_000) as u64) / 10_000
+    });
+    (100_000_000 - kept).div_ceil(10_000) as u32
+}
+
+/// Smallest final output to accept from a route simulated to return `amount_out`: `slippage_bps`
+/// below it, rounded down. A round trip (same token in and out) never accepts less than its
+/// `round_trip_input`: a fill that doesn't return the input is a loss the contract should revert.
+pub fn min_amount_out(amount_out: U256, slippage_bps: u32, round_trip_input: Option<U256>) -> U256 {
+    let min_out = amount_out * U256::from(10_000 - slippage_bps.min(10_000)) / U256::from(10_000u32);
+    min_out.max(round_trip_input.unwrap_or_default())
+}
+
+/// Unix timestamp `secs` from now, as passed to the executor's `deadline` argument.
+/// Typed (EIP-1559) transactions carry no expiry field, so this is the only validity window.
+pub fn deadline_from_now(secs: u64) -> U256 {
+    U256::from((chrono::Utc::now().timestamp().max(0) as u64).saturating_add(secs))
+}
+
+/// `buySellExecution` call for the given route data
+fn buy_sell_call<M: Middleware>(
+    contract: &DirectSwapExecutor<M>,
+    swap_data: &BuySellExecutionData,
+) -> ContractCall<M, ()> {
+    contract.buy_sell_execution(
+        swap_data.buy_tokens.clone(),
+        swap_data.buy_pools.clone(),
+        swap_data.buy_pool_types.clone(),
+        swap_data.buy_amounts.clone(),
+        swap_data.sell_tokens.clone(),
+        swap_data.sell_pools.clone(),
+        swap_data.sell_pool_types.clone(),
+        swap_data.sell_amounts_with_min_out(),
+        swap_data.deadline,
+    )
+}
+
+/// ABI-encoded `buySellExecution` calldata, for running the trade outside ethers (e.g. on a REVM fork)
+pub fn buy_sell_calldata(swap_data: &BuySellExecutionData) -> Bytes {
+    let (provider, _mock) = Provider::mocked();
+    let contract = DirectSwapExecutor::new(H160::zero(), Arc::new(provider));
+    buy_sell_call(&contract, swap_data).calldata().expect("buySellExecution has calldata")
+}
+
+/// Gas limit of a `buySellExecution` send; `to_tx_env` carries the same limit into REVM runs
+pub const BUY_SELL_GAS_LIMIT: u64 = 400_000;
+
+impl BuySellExecutionData {
+    /// The `buySellExecution` transaction from `from` to the executor. Calldata comes from the
+    /// same `buy_sell_call` the send path uses; gas price and nonce are left for the sender.
+    pub fn to_transaction(&self, contract: H160, from: H160) -> Transaction {
+        Transaction {
+            from,
+            to: Some(contract),
+            input: buy_sell_calldata(self),
+            gas: U256::from(BUY_SELL_GAS_LIMIT),
+            ..Default::default()
+        }
+    }
+
+    /// REVM `TxEnv` for the transaction `execute_arbitrage_onchain` would send (the fork tests execute this)
+    pub fn to_tx_env(&self, contract: H160, from: H160) -> TxEnv {
+        ethers_tx_to_revm_txenv(&self.to_transaction(contract, from))
+    }
+}
+
+/// Executor strategy from the route's pool types (0 = V2, 1 = V3, 2 = StableSwap). `Flashloan` is chosen
+/// by the caller, which knows whether the contract holds enough inventory.
+pub fn route_strategy(buy_pool_types: &[u8], sell_pool_types: &[u8]) -> ExecutorStrategy {
+    if buy_pool_types.iter().chain(sell_pool_types).any(|&t| t == 1) {
+        ExecutorStrategy::V3
+    } else {
+        ExecutorStrategy::V2
+    }
 }
 
 // Keep the old SwapExecutionData for backward compatibility
@@ -187,91 +339,439 @@ pub struct SwapExecutionData {
 //     }
 // }
 
+/// Blocks of history sampled by `GasStrategy::FeeHistoryPercentile`
+const FEE_HISTORY_BLOCKS: u64 = 20;
+
+/// Gas price to bid under `strategy`, queried from the node right before sending
+pub async fn resolve_gas_price<M: Middleware>(strategy: &GasStrategy, provider: &M) -> anyhow::Result<U256> {
+    match strategy {
+        GasStrategy::Fixed(price) => Ok(*price),
+        GasStrategy::OracleMultiple(multiple) => {
+            if !multiple.is_finite() || *multiple <= 0.0 {
+                anyhow::bail!("invalid gas price multiple: {}", multiple);
+            }
+            let oracle = provider.get_gas_price().await.map_err(|e| anyhow::anyhow!("eth_gasPrice failed: {}", e))?;
+            // Scale in integer math (multiple to 3 decimals)
+            let scaled = (*multiple * 1000.0).round() as u64;
+            Ok(oracle.saturating_mul(U256::from(scaled)) / U256::from(1000u64))
+        }
+        GasStrategy::FeeHistoryPercentile(percentile) => {
+            if *percentile > 100 {
+                anyhow::bail!("fee history percentile must be 0-100, got {}", percentile);
+            }
+            let history = provider
+                .fee_history(FEE_HISTORY_BLOCKS, BlockNumber::Latest, &[*percentile as f64])
+                .await
+                .map_err(|e| anyhow::anyhow!("eth_feeHistory failed: {}", e))?;
+            // Last entry is the next block's base fee
+            let base_fee = history.base_fee_per_gas.last().copied().unwrap_or_default();
+            let rewards: Vec<U256> = history.reward.iter().filter_map(|r| r.first().copied()).collect();
+            let priority_fee = if rewards.is_empty() {
+                U256::zero()
+            } else {
+                rewards.iter().fold(U256::zero(), |acc, r| acc.saturating_add(*r)) / U256::from(rewards.len())
+            };
+            Ok(base_fee.saturating_add(priority_fee))
+        }
+        GasStrategy::Eip1559 { priority_gwei, max_multiplier } => {
+            Ok(resolve_eip1559(*priority_gwei, *max_multiplier, provider).await?.max_gas_price())
+        }
+    }
+}
+
+/// Fee fields of one send
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasPricing {
+    /// Legacy transaction bidding one gas price
+    Legacy(U256),
+    /// EIP-1559 transaction with its fee caps
+    Eip1559 { max_fee_per_gas: U256, max_priority_fee_per_gas: U256 },
+}
+
+impl GasPricing {
+    /// Most the send can pay per gas
+    pub fn max_gas_price(&self) -> U256 {
+        match self {
+            GasPricing::Legacy(gas_price) => *gas_price,
+            GasPricing::Eip1559 { max_fee_per_gas, .. } => *max_fee_per_gas,
+        }
+    }
+}
+
+/// `GasStrategy::Eip1559` caps on top of `base_fee`
+fn eip1559_pricing(base_fee: U256, priority_gwei: f64, max_multiplier: f64) -> anyhow::Result<GasPricing> {
+    if !priority_gwei.is_finite() || priority_gwei < 0.0 {
+        anyhow::bail!("invalid priority fee: {} gwei", priority_gwei);
+    }
+    if !max_multiplier.is_finite() || max_multiplier < 1.0 {
+        anyhow::bail!("max fee multiplier must be at least 1, got {}", max_multiplier);
+    }
+    let priority = U256::from((priority_gwei * 1e9).round() as u64);
+    // Scale in integer math (multiplier to 3 decimals)
+    let scaled = (max_multiplier * 1000.0).round() as u64;
+    let max_fee = (base_fee.saturating_mul(U256::from(scaled)) / U256::from(1000u64)).saturating_add(priority);
+    Ok(GasPricing::Eip1559 { max_fee_per_gas: max_fee, max_priority_fee_per_gas: priority })
+}
+
+/// EIP-1559 caps from the latest block's base fee, or the node's legacy gas price if it has none
+async fn resolve_eip1559<M: Middleware>(priority_gwei: f64, max_multiplier: f64, provider: &M) -> anyhow::Result<GasPricing> {
+    let block = provider
+        .get_block(BlockNumber::Latest)
+        .await
+        .map_err(|e| anyhow::anyhow!("eth_getBlockByNumber failed: {}", e))?;
+    match block.and_then(|block| block.base_fee_per_gas) {
+        Some(base_fee) => eip1559_pricing(base_fee, priority_gwei, max_multiplier),
+        None => {
+            let gas_price = provider.get_gas_price().await.map_err(|e| anyhow::anyhow!("eth_gasPrice failed: {}", e))?;
+            Ok(GasPricing::Legacy(gas_price))
+        }
+    }
+}
+
+/// Fee fields to send with under `strategy`; every strategy but `Eip1559` bids a legacy gas price
+pub async fn resolve_gas_pricing<M: Middleware>(strategy: &GasStrategy, provider: &M) -> anyhow::Result<GasPricing> {
+    match strategy {
+        GasStrategy::Eip1559 { priority_gwei, max_multiplier } => resolve_eip1559(*priority_gwei, *max_multiplier, provider).await,
+        _ => resolve_gas_price(strategy, provider).await.map(GasPricing::Legacy),
+    }
+}
+
+/// Resolve the `strategy` bid into `oracle` now and then every `interval`, so every gas estimate
+/// is charged at what a send would bid (EIP-1559: the max fee). A failed query keeps the last
+/// price; a zero interval keeps the price the oracle was seeded with.
+pub fn spawn_gas_price_refresh<M: Middleware + 'static>(
+    oracle: Arc<PriceOracle>,
+    strategy: GasStrategy,
+    provider: Arc<M>,
+    interval: std::time::Duration,
+) {
+    if interval.is_zero() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match resolve_gas_price(&strategy, provider.as_ref()).await {
+                Ok(gas_price) => oracle.set_gas_price(gas_price),
+                Err(e) => eprintln!("❌ [GAS] Gas price refresh failed, keeping {}: {}", oracle.gas_price(), e),
+            }
+        }
+    });
+}
+
+/// Put `pricing` on `call`: a legacy transaction, or the EIP-1559 request abigen builds by default
+fn with_gas_pricing<M: Middleware, D: ethers::abi::Detokenize>(call: ContractCall<M, D>, pricing: GasPricing) -> ContractCall<M, D> {
+    match pricing {
+        GasPricing::Legacy(gas_price) => call.legacy().gas_price(gas_price),
+        GasPricing::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => {
+            let mut call = call;
+            match &mut call.tx {
+                TypedTransaction::Eip1559(tx) => {
+                    tx.max_fee_per_gas = Some(max_fee_per_gas);
+                    tx.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+                }
+                tx => {
+                    tx.set_gas_price(max_fee_per_gas);
+                }
+            }
+            call
+        }
+    }
+}
+
+/// Nonces for the bot wallet, shared by every send so concurrent legs don't collide. A nonce is
+/// taken only right before signing; one that never reached a submitter is handed back with
+/// `release`, and after a send that didn't land `reset` makes the next caller re-read the
+/// account's pending count so no gap is left behind.
+#[derive(Debug, Default)]
+pub struct NonceAllocator {
+    next: tokio::sync::Mutex<Option<U256>>, // None = fetch from the node on the next `next`
+}
+
+impl NonceAllocator {
+    /// The nonce to sign with; fetches the pending transaction count when none is cached
+    pub async fn next<M: Middleware>(&self, address: H160, provider: &M) -> anyhow::Result<U256> {
+        let mut next = self.next.lock().await;
+        let nonce = match *next {
+            Some(nonce) => nonce,
+            None => provider
+                .get_transaction_count(address, Some(BlockNumber::Pending.into()))
+                .await
+                .map_err(|e| anyhow::anyhow!("eth_getTransactionCount failed: {}", e))?,
+        };
+        *next = Some(nonce + 1);
+        Ok(nonce)
+    }
+
+    /// Give back a nonce that was never submitted. If later nonces were already handed out the
+    /// count is re-read instead, since those sends would otherwise wait behind a gap.
+    pub async fn release(&self, nonce: U256) {
+        let mut next = self.next.lock().await;
+        *next = (*next == Some(nonce + 1)).then_some(nonce);
+    }
+
+    /// Forget the cached nonce, e.g. after a send that got no receipt
+    pub async fn reset(&self) {
+        *self.next.lock().await = None;
+    }
+
+    /// Continue from `nonce`, e.g. the count read when a standby instance takes over
+    pub async fn set(&self, nonce: U256) {
+        *self.next.lock().await = Some(nonce);
+    }
+}
+
 pub async fn execute_arbitrage_onchain(
     contract_address: H160,
     swap_data: BuySellExecutionData,
     wallet: LocalWallet,
-    provider: Arc<Provider<Http>>,
-) -> Result<TxHash, Box<dyn std::error::Error>> {
+    provider: Arc<HttpProvider>,
+    gas_strategy: &GasStrategy,
+    nonces: &NonceAllocator,
+    submitter: &dyn Submitter,
+) -> Result<TransactionReceipt, Box<dyn std::error::Error>> {
     let client = SignerMiddleware::new(provider.clone(), wallet.clone());
     let client = Arc::new(client);
     let contract = DirectSwapExecutor::new(contract_address, client.clone());
+    let opp = swap_data.opportunity_id;
 
-    // --- Dynamic Gas (EIP-1559 preferred, fallback to legacy) ---
-    let block = provider.get_block(BlockNumber::Pending).await?.unwrap();
-    let base_fee = block.base_fee_per_gas.unwrap_or(U256::from(0));
-    let priority_fee = U256::from(100_000_000u64); // 2 gwei
-    let max_fee_per_gas = base_fee + priority_fee;
-    println!("[EXECUTOR] Using base_fee: {} priority_fee: {} max_fee_per_gas: {}", base_fee, priority_fee, max_fee_per_gas);
+    // --- Gas fees from the configured strategy ---
+    let gas_pricing = resolve_gas_pricing(gas_strategy, provider.as_ref()).await?;
+    println!("[EXECUTOR] [opp #{}] Using {:?} ({:?})", opp, gas_pricing, gas_strategy);
 
-    // --- Current Nonce ---
-    let nonce = provider.get_transaction_count(wallet.address(), None).await?;
-    println!("[EXECUTOR] Using nonce: {:?}", nonce);
+    if swap_data.is_expired() {
+        return Err(format!("Deadline {} passed before send", swap_data.deadline).into());
+    }
 
     // --- Simulate call (dry run) ---
-    let call = contract.buy_sell_execution(
-        swap_data.buy_tokens.clone(),
-        swap_data.buy_pools.clone(),
-        swap_data.buy_pool_types.clone(),
-        swap_data.buy_amounts.clone(),
-        swap_data.sell_tokens.clone(),
-        swap_data.sell_pools.clone(),
-        swap_data.sell_pool_types.clone(),
-        swap_data.sell_amounts.clone(),
-    );
+    let call = buy_sell_call(&contract, &swap_data);
     let simulation = call.clone().call().await;
     match simulation {
-        Ok(_) => println!("[EXECUTOR] Simulation succeeded!"),
+        Ok(_) => println!("[EXECUTOR] [opp #{}] Simulation succeeded!", opp),
         Err(e) => {
-            println!("[EXECUTOR] Simulation failed: {:?}", e);
+            println!("[EXECUTOR] [opp #{}] Simulation failed: {:?}", opp, e);
             return Err(format!("Simulation failed: {e:?}").into());
         }
     }
 
-    // --- Send TX with dynamic gas ---
-    let call_with_opts = call
-        .gas_price(max_fee_per_gas)
-        .gas(400_000u64)
-        .nonce(nonce);
-
-    let pending_tx = call_with_opts.send().await?;
+    // --- Nonce, taken only now that the trade is going out ---
+    let nonce = nonces.next(wallet.address(), provider.as_ref()).await?;
+    println!("[EXECUTOR] [opp #{}] Using nonce: {:?}", opp, nonce);
 
-    let tx_hash = pending_tx.tx_hash();
-    println!("[EXECUTOR] TX fired: https://bscscan.com/tx/{:?}", tx_hash);
+    // --- Sign with dynamic gas and hand to the configured submitter ---
+    let submitted = async {
+        let mut tx = with_gas_pricing(call, gas_pricing)
+            .gas(BUY_SELL_GAS_LIMIT)
+            .nonce(nonce)
+            .tx;
+        client.fill_transaction(&mut tx, None).await?;
+        let signature = client.signer().sign_transaction(&tx).await?;
+        let raw_tx = tx.rlp_signed(&signature);
+        let target_block = provider.get_block_number().await?.as_u64() + 1;
+        let tx_hash = submitter.submit(raw_tx, target_block).await?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>((tx_hash, target_block))
+    }
+    .await;
+    let (tx_hash, target_block) = match submitted {
+        Ok(sent) => sent,
+        Err(e) => {
+            nonces.release(nonce).await;
+            return Err(e);
+        }
+    };
+    println!("[EXECUTOR] [opp #{}] TX fired ({}): https://bscscan.com/tx/{:?}", opp, submitter.name(), tx_hash);
 
-    let receipt = pending_tx.await?;
-    if let Some(receipt) = &receipt {
+    // Mined receipts are returned even when reverted so the caller can record them in the ledger.
+    // Without one (a bundle that didn't land, a dropped tx) the nonce may still be free.
+    let receipt = match submitter.wait_for_receipt(tx_hash, target_block).await {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            nonces.reset().await;
+            return Err(e.into());
+        }
+    };
+    if let Some(receipt) = receipt {
         if receipt.status == Some(U64::from(1u64)) {
-            println!("[EXECUTOR] TX succeeded! Hash: {:?}", receipt.transaction_hash);
-            Ok(receipt.transaction_hash)
+            println!("[EXECUTOR] [opp #{}] TX succeeded! Hash: {:?}", opp, receipt.transaction_hash);
         } else {
-            println!("[EXECUTOR] TX failed! Hash: {:?}", receipt.transaction_hash);
-            Err("Transaction failed on-chain".into())
+            println!("[EXECUTOR] [opp #{}] TX failed! Hash: {:?}", opp, receipt.transaction_hash);
         }
+        Ok(receipt)
     } else {
-        println!("[EXECUTOR] No transaction receipt returned! Hash: {:?}", tx_hash);
+        println!("[EXECUTOR] [opp #{}] No transaction receipt returned! Hash: {:?}", opp, tx_hash);
+        nonces.reset().await;
         Err("No transaction receipt returned".into())
     }
 }
 
+/// Send the trade through `execute_arbitrage_onchain` and `submitter`, or with `dry_run` only
+/// record it in `executor.log` with its expected profit and return Ok(None). A dry run never
+/// touches `provider` or `submitter`: no gas lookup, nonce, simulation call or send.
+pub async fn dispatch_arbitrage(
+    contract_address: H160,
+    swap_data: BuySellExecutionData,
+    wallet: LocalWallet,
+    provider: Arc<HttpProvider>,
+    gas_strategy: &GasStrategy,
+    nonces: &NonceAllocator,
+    expected_profit: U256,
+    dry_run: bool,
+    submitter: &dyn Submitter,
+) -> Result<Option<TransactionReceipt>, Box<dyn std::error::Error>> {
+    if !dry_run {
+        return execute_arbitrage_onchain(contract_address, swap_data, wallet, provider, gas_strategy, nonces, submitter)
+            .await
+            .map(Some);
+    }
+    let opp = swap_data.opportunity_id;
+    println!("[EXECUTOR] [opp #{}] Dry run: not sending, expected profit {}, trade {:?}", opp, expected_profit, swap_data);
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(log_path("executor.log"))?;
+    writeln!(
+        file,
+        "[EXECUTOR RESULT] [opp #{}] Dry run: not sent, expected_profit={} strategy={:?} swap_data={:?}",
+        opp, expected_profit, gas_strategy, swap_data
+    )?;
+    Ok(None)
+}
+
+/// What every trade sent from the main loop shares; built once and cloned into each send task
+#[derive(Clone)]
+pub struct TradeContext {
+    pub wallet: LocalWallet,
+    pub provider: Arc<HttpProvider>,
+    pub gas_strategy: GasStrategy,
+    pub nonces: Arc<NonceAllocator>,
+    pub submitter: Arc<dyn Submitter>,
+    pub inventory: Arc<InventoryManager>,
+    pub token_index: Arc<TokenIndexMap>,
+    pub slippage: Arc<AdaptiveSlippage>,
+    pub adaptive_slippage: bool,
+    pub dry_run: bool,
+}
+
+/// One built trade as the main loop hands it off
+pub struct Trade {
+    pub contract_address: H160,
+    pub swap_data: BuySellExecutionData,
+    pub simulated_profit: U256,
+    pub base_token_idx: u32,
+    pub reserved_in: Option<H160>, // holder whose inventory was reserved for the buy input
+}
+
+/// Send one trade and book its outcome: the ledger row and divergence for mined trades, the
+/// InsufficientProfit feedback, metrics and `executor.log`. The buy input reservation is
+/// released once the send returns. A dry run only logs the trade.
+pub async fn run_trade(ctx: TradeContext, trade: Trade) {
+    let Trade { contract_address, swap_data, simulated_profit, base_token_idx, reserved_in } = trade;
+    let opp = swap_data.opportunity_id;
+    let (input_token, input_amount) = (swap_data.buy_tokens[0], swap_data.buy_amounts[0]);
+    if let Ok(mut file) = std::fs::OpenOptions::new().create(true).append(true).open(log_path("executor.log")) {
+        let _ = writeln!(file, "[EXECUTOR CALL] [opp #{}] contract_address={:?}, swap_data={:?}", opp, contract_address, swap_data);
+    }
+    if !ctx.dry_run {
+        crate::metrics::metrics().executions_attempted.inc();
+    }
+    let result = dispatch_arbitrage(
+        contract_address,
+        swap_data,
+        ctx.wallet.clone(),
+        ctx.provider.clone(),
+        &ctx.gas_strategy,
+        &ctx.nonces,
+        simulated_profit,
+        ctx.dry_run,
+        ctx.submitter.as_ref(),
+    )
+    .await;
+    if let Some(holder) = reserved_in {
+        ctx.inventory.release(holder, input_token, input_amount);
+    }
+    // Dry run: logged by `dispatch_arbitrage`, nothing mined to record
+    let result = match result {
+        Ok(Some(receipt)) => Ok(receipt),
+        Ok(None) => return,
+        Err(e) => Err(e.to_string()),
+    };
+    // --- Record mined trades with realized on-chain amounts ---
+    if let Ok(receipt) = &result {
+        // Unknown realized profit stays out of the divergence stats rather than counting as 0
+        let realized_profit = realized_profit_from_receipt(receipt, base_token_idx, &ctx.token_index);
+        if let Some(realized) = realized_profit {
+            if let Some(divergence) = profit_divergence_bps(simulated_profit, realized) {
+                println!("[DIVERGENCE] [opp #{}] simulated={} realized={} divergence={:.1} bps", opp, simulated_profit, realized, divergence);
+            }
+        }
+        let record = trade_ledger::TradeRecord::from_receipt(receipt, input_token, contract_address, realized_profit, simulated_profit);
+        println!("[LEDGER] {:?} status={} input={} realized_output={} net_profit={}", record.tx_hash, record.status, record.input, record.realized_output, record.net_profit.map_or("unknown".to_string(), |p| p.to_string()));
+        if let Err(e) = trade_ledger::append_trade(&log_path(trade_ledger::TRADE_LEDGER_FILE), &record) {
+            eprintln!("[LEDGER] Failed to write trade: {}", e);
+        }
+    }
+    // --- InsufficientProfit feedback into the slippage buffer ---
+    // A mined revert has no reason in its receipt: replay the transaction to classify it
+    let revert = match &result {
+        Ok(receipt) if receipt.status != Some(1u64.into()) => replay_mined_revert(receipt, ctx.provider.as_ref()).await,
+        Ok(_) => None,
+        Err(e) => classify_revert_in_message(e),
+    };
+    match (&result, &revert) {
+        (Ok(receipt), _) if receipt.status == Some(1u64.into()) => crate::metrics::metrics().executions_succeeded.inc(),
+        (Ok(_), _) | (Err(_), Some(_)) => crate::metrics::metrics().executions_reverted.inc(),
+        (Err(_), None) => {}
+    }
+    match (&result, &revert) {
+        (_, Some(RevertReason::InsufficientProfit)) => {
+            let count = INSUFFICIENT_PROFIT_REVERTS.fetch_add(1, Ordering::Relaxed) + 1;
+            if ctx.adaptive_slippage {
+                println!("[SLIPPAGE] InsufficientProfit revert #{}, buffer widened to {} bps", count, ctx.slippage.record_insufficient_profit());
+            } else {
+                println!("[SLIPPAGE] InsufficientProfit revert #{}", count);
+            }
+        }
+        (Ok(receipt), _) if ctx.adaptive_slippage && receipt.status == Some(1u64.into()) => {
+            ctx.slippage.record_success();
+        }
+        _ => {}
+    }
+    if let Ok(mut file) = std::fs::OpenOptions::new().create(true).append(true).open(log_path("executor.log")) {
+        match &result {
+            Ok(receipt) => {
+                let decoded = revert.as_ref().and_then(RevertReason::description).map_or(String::new(), |reason| format!(" | Decoded: {}", reason));
+                let _ = writeln!(file, "[EXECUTOR RESULT] [opp #{}] Mined: tx_hash={:?} status={:?} gas_price={:?} strategy={:?}{}", opp, receipt.transaction_hash, receipt.status, receipt.effective_gas_price, ctx.gas_strategy, decoded);
+            }
+            Err(msg) => {
+                let decoded = revert.as_ref().and_then(RevertReason::description);
+                if let Some(reason) = decoded {
+                    let _ = writeln!(file, "[EXECUTOR RESULT] [opp #{}] Error: {} | Decoded: {}", opp, msg, reason);
+                } else {
+                    let _ = writeln!(file, "[EXECUTOR RESULT] [opp #{}] Error: {}", opp, msg);
+                }
+            }
+        }
+    }
+    match result {
+        Ok(receipt) => println!("[ARBITRAGE EXECUTED] [opp #{}] Tx hash: {:?}", opp, receipt.transaction_hash),
+        Err(e) => eprintln!("[ARBITRAGE ERROR] [opp #{}] {e}", opp),
+    }
+}
+
 // Keep the old function for backward compatibility
 pub async fn execute_arbitrage_onchain_legacy(
     contract_address: H160,
     swap_data: SwapExecutionData,
     wallet: LocalWallet,
-    provider: Arc<Provider<Http>>,
+    provider: Arc<HttpProvider>,
+    gas_strategy: &GasStrategy,
 ) -> Result<TxHash, Box<dyn std::error::Error>> {
     let client = SignerMiddleware::new(provider.clone(), wallet.clone());
     let client = Arc::new(client);
     let contract = DirectSwapExecutor::new(contract_address, client.clone());
     let extra_data_bytes: Vec<ethers::types::Bytes> = swap_data.extra_data.into_iter().map(ethers::types::Bytes::from).collect();
 
-    // --- Dynamic Gas (EIP-1559 preferred, fallback to legacy) ---
-    let block = provider.get_block(BlockNumber::Pending).await?.unwrap();
-    let base_fee = block.base_fee_per_gas.unwrap_or(U256::from(0));
-    let priority_fee = U256::from(100_000_000u64); // 2 gwei
-    let max_fee_per_gas = base_fee + priority_fee;
-    println!("[EXECUTOR] Using base_fee: {} priority_fee: {} max_fee_per_gas: {}", base_fee, priority_fee, max_fee_per_gas);
+    // --- Gas price from the configured strategy ---
+    let gas_price = resolve_gas_price(gas_strategy, provider.as_ref()).await?;
+    println!("[EXECUTOR] Using gas_price: {} ({:?})", gas_price, gas_strategy);
 
     // --- Current Nonce ---
     let nonce = provider.get_transaction_count(wallet.address(), None).await?;
@@ -297,7 +797,7 @@ pub async fn execute_arbitrage_onchain_legacy(
 
     // --- Send TX with dynamic gas ---
     let call_with_opts = call
-        .gas_price(max_fee_per_gas)
+        .gas_price(gas_price)
         .gas(400_000u64)
         .nonce(nonce);
 
@@ -321,19 +821,847 @@ pub async fn execute_arbitrage_onchain_legacy(
     }
 }
 
-/// Decode a Solidity revert reason (Error(string)) from hex revert data
-pub fn decode_revert_reason(data: &str) -> Option<String> {
+/// Allowance believed to be left per (owner, token, spender). Trades draw it down as they are
+/// sent, so it only ever underestimates and an unneeded on-chain check is the worst case.
+static KNOWN_ALLOWANCES: Lazy<DashMap<(H160, H160, H160), U256>> = Lazy::new(DashMap::new);
+
+/// Draw `amount` from the cached allowance for `key`; false if the cache doesn't cover it
+fn take_known_allowance(key: (H160, H160, H160), amount: U256) -> bool {
+    match KNOWN_ALLOWANCES.get_mut(&key) {
+        Some(mut known) if *known >= amount => {
+            *known -= amount;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Make sure `spender` may pull at least `min_amount` of `token` from the wallet, sending an
+/// `approve` for `mode`'s amount only when the current allowance falls short. Waits for the
+/// approval to be mined so the trade that follows doesn't revert. Returns the approval tx hash
+/// if one was sent; its nonce comes from `nonces`, shared with the wallet's trades.
+pub async fn ensure_allowance(
+    token: H160,
+    spender: H160,
+    min_amount: U256,
+    wallet: &LocalWallet,
+    provider: Arc<HttpProvider>,
+    mode: ApprovalMode,
+    nonces: &NonceAllocator,
+) -> anyhow::Result<Option<TxHash>> {
+    let owner = wallet.address();
+    let key = (owner, token, spender);
+    if take_known_allowance(key, min_amount) {
+        return Ok(None);
+    }
+
+    let current = IERC20::new(token, provider.clone())
+        .allowance(owner, spender)
+        .call()
+        .await
+        .map_err(|e| anyhow::anyhow!("allowance({:?}, {:?}) on {:?} failed: {}", owner, spender, token, e))?;
+    if current >= min_amount {
+        KNOWN_ALLOWANCES.insert(key, current - min_amount);
+        return Ok(None);
+    }
+
+    let amount = mode.approval_amount(min_amount);
+    println!("[APPROVE] Allowance {} < {} for {:?}, approving {} ({:?})", current, min_amount, token, amount, mode);
+    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+    let nonce = nonces.next(owner, provider.as_ref()).await?;
+    let call = IERC20::new(token, client).approve(spender, amount).nonce(nonce);
+    let pending_tx = match call.send().await {
+        Ok(pending_tx) => pending_tx,
+        Err(e) => {
+            nonces.release(nonce).await;
+            anyhow::bail!("approve on {:?} failed: {}", token, e);
+        }
+    };
+    let tx_hash = pending_tx.tx_hash();
+    let Some(receipt) = pending_tx.await? else {
+        nonces.reset().await;
+        anyhow::bail!("no receipt for approval {:?}", tx_hash);
+    };
+    if receipt.status != Some(U64::from(1u64)) {
+        anyhow::bail!("approval {:?} for {:?} reverted", tx_hash, token);
+    }
+    KNOWN_ALLOWANCES.insert(key, amount - min_amount);
+    println!("[APPROVE] Approved {:?} for {:?}: {:?}", spender, token, tx_hash);
+    Ok(Some(tx_hash))
+}
+
+/// 4-byte selectors the executor contract's InsufficientProfit error may carry
+static INSUFFICIENT_PROFIT_SELECTORS: Lazy<[String; 2]> = Lazy::new(|| {
+    ["InsufficientProfit()", "InsufficientProfit(uint256,uint256)"]
+        .map(|sig| hex::encode(&ethers::utils::keccak256(sig.as_bytes())[..4]))
+});
+
+/// InsufficientProfit reverts seen since start
+pub static INSUFFICIENT_PROFIT_REVERTS: AtomicU64 = AtomicU64::new(0);
+
+/// What a revert payload says
+#[derive(Debug, Clone, PartialEq)]
+pub enum RevertReason {
+    /// On-chain profit came in below the encoded minimum (custom error or require message)
+    InsufficientProfit,
+    /// `Error(string)` message
+    Message(String),
+    /// Unrecognized custom error, by selector
+    Custom(String),
+}
+
+impl RevertReason {
+    /// Human-readable reason; None for unrecognized custom errors
+    pub fn description(&self) -> Option<String> {
+        match self {
+            RevertReason::InsufficientProfit => Some("InsufficientProfit".to_string()),
+            RevertReason::Message(reason) => Some(reason.clone()),
+            RevertReason::Custom(_) => None,
+        }
+    }
+}
+
+/// Classify hex revert data
+pub fn classify_revert(data: &str) -> Option<RevertReason> {
     let data = data.strip_prefix("0x").unwrap_or(data);
-    if data.starts_with("08c379a0") && data.len() > 8 + 64 {
-        let reason_start = 8 + 64 + 64;
-        let len_hex = &data[8+64..8+64+64];
-        let len = usize::from_str_radix(len_hex, 16).unwrap_or(0) * 2;
-        let reason_hex = &data[reason_start..reason_start+len.min(data.len()-reason_start)];
-        if let Ok(bytes) = hex::decode(reason_hex) {
-            if let Ok(reason) = String::from_utf8(bytes) {
-                return Some(reason);
+    let selector = data.get(..8)?;
+    if !selector.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    if INSUFFICIENT_PROFIT_SELECTORS.iter().any(|s| selector.eq_ignore_ascii_case(s)) {
+        return Some(RevertReason::InsufficientProfit);
+    }
+    if selector == "08c379a0" {
+        let len = data.get(8 + 64..8 + 128).and_then(|l| usize::from_str_radix(l, 16).ok())?;
+        let reason_hex = data.get(8 + 128..)?;
+        let reason_hex = reason_hex.get(..len.saturating_mul(2).min(reason_hex.len()))?;
+        let reason = String::from_utf8(hex::decode(reason_hex).ok()?).ok()?;
+        let normalized = reason.to_lowercase().replace([' ', '_'], "");
+        if normalized.contains("insufficientprofit") {
+            return Some(RevertReason::InsufficientProfit);
+        }
+        return Some(RevertReason::Message(reason));
+    }
+    Some(RevertReason::Custom(selector.to_string()))
+}
+
+/// Find and classify the first recognizable revert payload in an error message
+pub fn classify_revert_in_message(msg: &str) -> Option<RevertReason> {
+    msg.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| word.starts_with("0x"))
+        .filter_map(classify_revert)
+        .find(|reason| !matches!(reason, RevertReason::Custom(_)))
+}
+
+/// Why a mined trade reverted. The receipt carries no revert data, so the transaction is replayed
+/// with `eth_call` on the state it was mined on top of (the parent block) and the revert classified.
+/// None when the replay can't be run or succeeds there, i.e. a trade ahead of it in the block moved the pools.
+pub async fn replay_mined_revert<M: Middleware>(receipt: &TransactionReceipt, provider: &M) -> Option<RevertReason> {
+    let parent = receipt.block_number?.as_u64().checked_sub(1)?;
+    let tx = provider.get_transaction(receipt.transaction_hash).await.ok()??;
+    let call: TypedTransaction = TransactionRequest::new()
+        .from(tx.from)
+        .to(tx.to?)
+        .data(tx.input)
+        .value(tx.value)
+        .gas(tx.gas)
+        .into();
+    match provider.call(&call, Some(BlockNumber::Number(parent.into()).into())).await {
+        Ok(_) => None,
+        Err(e) => classify_revert_in_message(&e.to_string()),
+    }
+}
+
+/// Decode a Solidity revert reason (Error(string) or the InsufficientProfit custom error) from hex revert data
+pub fn decode_revert_reason(data: &str) -> Option<String> {
+    classify_revert(data)?.description()
+}
+
+/// Slippage buffer that widens by `step_bps` on every InsufficientProfit revert and narrows
+/// by one step after `SUCCESSES_PER_STEP` clean executions, always within [min_bps, max_bps]
+#[derive(Debug)]
+pub struct AdaptiveSlippage {
+    current_bps: AtomicU32,
+    successes: AtomicU32,
+    min_bps: u32,
+    max_bps: u32,
+    step_bps: u32,
+}
+
+impl AdaptiveSlippage {
+    /// Clean executions needed before the buffer narrows again
+    const SUCCESSES_PER_STEP: u32 = 5;
+
+    pub fn new(initial_bps: u32, min_bps: u32, max_bps: u32, step_bps: u32) -> Self {
+        let max_bps = max_bps.max(min_bps);
+        Self {
+            current_bps: AtomicU32::new(initial_bps.clamp(min_bps, max_bps)),
+            successes: AtomicU32::new(0),
+            min_bps,
+            max_bps,
+            step_bps,
+        }
+    }
+
+    pub fn current_bps(&self) -> u32 {
+        self.current_bps.load(Ordering::Relaxed)
+    }
+
+    /// Widen the buffer; returns the new value
+    pub fn record_insufficient_profit(&self) -> u32 {
+        self.successes.store(0, Ordering::Relaxed);
+        self.adjust(|bps| bps.saturating_add(self.step_bps))
+    }
+
+    /// Count a clean execution; returns the (possibly narrowed) value
+    pub fn record_success(&self) -> u32 {
+        if self.successes.fetch_add(1, Ordering::Relaxed) + 1 < Self::SUCCESSES_PER_STEP {
+            return self.current_bps();
+        }
+        self.successes.store(0, Ordering::Relaxed);
+        self.adjust(|bps| bps.saturating_sub(self.step_bps))
+    }
+
+    /// Profit left after giving up `current_bps` of the route's final output
+    pub fn worst_case_profit(&self, profit: U256, amount_out: U256) -> U256 {
+        profit.saturating_sub(amount_out * U256::from(self.current_bps()) / U256::from(10_000u32))
+    }
+
+    fn adjust(&self, f: impl Fn(u32) -> u32) -> u32 {
+        let (min, max) = (self.min_bps, self.max_bps);
+        let prev = self
+            .current_bps
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bps| Some(f(bps).clamp(min, max)))
+            .unwrap_or_else(|bps| bps);
+        f(prev).clamp(min, max)
+    }
+}
+
+/// Net base-token delta for our side of the trade (tx sender and executor contract)
+/// computed from the receipt's Transfer logs, so tax and slippage effects are included.
+/// None if no base-token transfer reaches or leaves our side: the profit is unknown, not zero.
+pub fn realized_profit_from_receipt(
+    receipt: &TransactionReceipt,
+    base_token: u32,
+    token_index: &crate::token_index::TokenIndexMap,
+) -> Option<I256> {
+    let base_token_address = *token_index.index_to_address.get(&base_token)?;
+    let transfer_topic = H256::from(ethers::utils::keccak256("Transfer(address,address,uint256)"));
+    let ours = |addr: &H160| *addr == receipt.from || Some(*addr) == receipt.to;
+    let mut delta = None;
+    for log in &receipt.logs {
+        if log.address != base_token_address || log.topics.len() != 3 || log.topics[0] != transfer_topic {
+            continue;
+        }
+        let from = H160::from(log.topics[1]);
+        let to = H160::from(log.topics[2]);
+        let amount = I256::try_from(U256::from_big_endian(&log.data)).ok()?;
+        let current = delta.unwrap_or_else(I256::zero);
+        match (ours(&from), ours(&to)) {
+            (true, false) => delta = Some(current.checked_sub(amount)?),
+            (false, true) => delta = Some(current.checked_add(amount)?),
+            _ => {} // internal moves between wallet and contract
+        }
+    }
+    delta
+}
+
+/// Realized vs simulated profit divergence in basis points of the simulated profit
+pub fn profit_divergence_bps(simulated: U256, realized: I256) -> Option<f64> {
+    if simulated.is_zero() {
+        return None;
+    }
+    let simulated_f = simulated.to_string().parse::<f64>().ok()?;
+    let realized_f = realized.to_string().parse::<f64>().ok()?;
+    Some((realized_f - simulated_f) / simulated_f * 10_000.0)
+}
+
+/// WBNB is the native gas token on BSC
+pub const WBNB_ADDRESS: &str = "0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c";
+
+/// Convert gas cost (gas_used * gas_price, paid in BNB) into the route's base token.
+/// `price_oracle` returns the USD price of a token. Base tokens are assumed to use 18 decimals.
+pub fn gas_cost_in_base_token(
+    gas_used: U256,
+    gas_price: U256,
+    base_token: H160,
+    price_oracle: impl Fn(&H160) -> Option<f64>,
+) -> Option<U256> {
+    let gas_cost_bnb = gas_used.saturating_mul(gas_price);
+    let wbnb: H160 = WBNB_ADDRESS.parse().ok()?;
+    if base_token == wbnb {
+        return Some(gas_cost_bnb);
+    }
+    let bnb_usd = price_oracle(&wbnb)?;
+    let base_usd = price_oracle(&base_token)?;
+    if bnb_usd <= 0.0 || base_usd <= 0.0 {
+        return None;
+    }
+    // Keep the conversion in integer math with a 1e9 scaled rate
+    let rate_scaled = ((bnb_usd / base_usd) * 1e9) as u128;
+    Some(gas_cost_bnb.saturating_mul(U256::from(rate_scaled)) / U256::from(1_000_000_000u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::FeeHistory;
+
+    #[test]
+    fn test_route_strategy() {
+        assert_eq!(route_strategy(&[0, 0], &[0]), ExecutorStrategy::V2);
+        assert_eq!(route_strategy(&[0], &[0, 1]), ExecutorStrategy::V3);
+        assert_eq!(route_strategy(&[1], &[]), ExecutorStrategy::V3);
+    }
+
+    #[test]
+    fn test_mixed_route_uses_v3_buffer() {
+        let slippage = PoolTypeSlippage { v2: 30, v3: 80, stable: 10 };
+        assert_eq!(route_slippage_bps(&[0, 0], &slippage), 60);
+        assert_eq!(route_slippage_bps(&[1], &slippage), 80);
+        // V2 buy, V3 sell: 1 - 0.997 * 0.992, rounded up
+        assert_eq!(route_slippage_bps(&[0, 1], &slippage), 110);
+        assert_eq!(route_slippage_bps(&[2, 2], &slippage), 20);
+        assert_eq!(route_slippage_bps(&[], &slippage), 0);
+
+        let data = BuySellExecutionData {
+            buy_tokens: vec![H160::from_low_u64_be(1), H160::from_low_u64_be(2)],
+            buy_pools: vec![H160::from_low_u64_be(3)],
+            buy_pool_types: vec![0],
+            buy_amounts: vec![U256::from(10_000u64), U256::from(20_000u64)],
+            sell_tokens: vec![H160::from_low_u64_be(2), H160::from_low_u64_be(1)],
+            sell_pools: vec![H160::from_low_u64_be(4)],
+            sell_pool_types: vec![1],
+            sell_amounts: vec![U256::from(20_000u64), U256::from(11_000u64)],
+            strategy: ExecutorStrategy::V3,
+            deadline: U256::from(1_700_000_000u64),
+            slippage_bps: route_slippage_bps(&[0, 1], &slippage),
+            min_amount_out: min_amount_out(U256::from(11_000u64), route_slippage_bps(&[0, 1], &slippage), Some(U256::from(10_000u64))),
+            opportunity_id: 0,
+        };
+        assert_eq!(data.min_amount_out, U256::from(10_879u64));
+        assert_eq!(data.sell_amounts_with_min_out(), vec![U256::from(20_000u64), U256::from(10_879u64)]);
+    }
+
+    #[test]
+    fn test_min_amount_out_applies_slippage_and_profit_floor() {
+        // 50 bps off a 1,000,000 simulated output
+        assert_eq!(min_amount_out(U256::from(1_000_000u64), 50, None), U256::from(995_000u64));
+        assert_eq!(min_amount_out(U256::from(1_000_000u64), 50, Some(U256::from(990_000u64))), U256::from(995_000u64));
+        // Rounded down
+        assert_eq!(min_amount_out(U256::from(1_001u64), 50, None), U256::from(995u64));
+        // Profit thinner than the buffer: the floor is the round trip's input, not a loss
+        assert_eq!(min_amount_out(U256::from(10_030u64), 50, Some(U256::from(10_000u64))), U256::from(10_000u64));
+        assert_eq!(min_amount_out(U256::from(10_030u64), 20_000, None), U256::zero());
+    }
+
+    /// WBNB -> X on a cheap V2 pair, X -> WBNB on a dear one, simulated for 5 X
+    fn v2_round_trip(config: Config) -> (crate::testkit::Market, SimulatedRoute, HashMap<H160, PoolMeta>) {
+        use crate::route_cache::DEXType;
+        use crate::testkit::{pool_address, pool_v2, token, units, MarketBuilder};
+
+        let (base, x) = (config.get_base_token_by_symbol("WBNB").unwrap().address, token(2));
+        let (cheap, dear) = (pool_address(1), pool_address(2));
+        let market = MarketBuilder::new()
+            .pool(cheap, pool_v2(base, x, units(1_000), units(1_100)))
+            .pool(dear, pool_v2(base, x, units(1_000), units(950)))
+            .route(&[base, x, base], &[cheap, dear])
+            .config(config)
+            .build();
+        let routes = crate::arbitrage_finder::simulate_all_paths_for_token_x(
+            market.index(x),
+            units(5),
+            cheap,
+            &market.route_cache,
+            &market.reserve_cache,
+            &market.token_index,
+            &market.token_tax_map,
+            &market.config,
+        );
+        let route = routes.into_iter().max_by_key(|r| r.profit).expect("no route simulated");
+        let pool_meta_map: HashMap<H160, PoolMeta> = [cheap, dear]
+            .into_iter()
+            .map(|pool| (pool, PoolMeta { token0: base, token1: x, address: pool, dex_type: DEXType::PancakeV2, factory: None, fee: None }))
+            .collect();
+        (market, route, pool_meta_map)
+    }
+
+    #[test]
+    fn test_max_input_cap_clamps_only_above_the_cap() {
+        let config = Config { max_input_per_base: HashMap::new(), ..Config::default() };
+        let (market, route, pool_meta_map) = v2_round_trip(config.clone());
+        let base = market.token_index.index_to_address[&route.buy_path.hops[0]];
+        let amount_in = route.buy_amounts[0];
+        let build = |cap: U256| {
+            let mut config = config.clone();
+            config.max_input_per_base.insert(base, cap);
+            BuySellExecutionData::from_simulated_route(
+                &route,
+                &pool_meta_map,
+                &market.token_index,
+                &market.reserve_cache,
+                &market.token_tax_map,
+                &config,
+            )
+            .expect("execution data")
+        };
+
+        // At the cap: the simulated trade is sent as is
+        let data = build(amount_in);
+        assert_eq!((data.buy_amounts.clone(), data.sell_amounts.clone()), (route.buy_amounts.clone(), route.sell_amounts.clone()));
+
+        // Above the cap: the input drops to at most the cap and both legs are re-simulated for it
+        let cap = amount_in / 2;
+        let data = build(cap);
+        assert!(data.buy_amounts[0] <= cap && data.buy_amounts[0] > cap * 9 / 10, "capped input {}", data.buy_amounts[0]);
+        let token_x_amount = *data.buy_amounts.last().unwrap();
+        assert_eq!(data.sell_amounts[0], token_x_amount);
+        let expected_sell = crate::simulate_swap_path::simulate_sell_path_amounts_array(
+            &route.sell_path,
+            token_x_amount,
+            &market.reserve_cache,
+            &market.token_index,
+            &market.token_tax_map,
+            &market.config,
+        )
+        .unwrap();
+        assert_eq!(data.sell_amounts, expected_sell);
+        assert!(data.sell_amounts.last() < route.sell_amounts.last());
+    }
+
+    #[test]
+    fn test_execution_data_carries_min_out_from_config() {
+        // Two V2 hops at 25 bps each compound to a 50 bps route tolerance
+        let config = Config { slippage_bps: PoolTypeSlippage { v2: 25, ..PoolTypeSlippage::default() }, ..Config::default() };
+        let (market, route, pool_meta_map) = v2_round_trip(config);
+
+        let data = BuySellExecutionData::from_simulated_route(
+            &route,
+            &pool_meta_map,
+            &market.token_index,
+            &market.reserve_cache,
+            &market.token_tax_map,
+            &market.config,
+        )
+        .expect("execution data");
+        assert_eq!(data.slippage_bps, 50);
+        let simulated_out = *data.sell_amounts.last().unwrap();
+        assert_eq!(data.min_amount_out, simulated_out * U256::from(9_950u64) / U256::from(10_000u64));
+        assert!(data.min_amount_out > data.buy_amounts[0], "the route is profitable even at the minimum");
+
+        // The contract sees the reduced minimum as the sell leg's final amount
+        let calldata = buy_sell_calldata(&data);
+        let last_word = &calldata[calldata.len() - 32..];
+        assert_eq!(U256::from_big_endian(last_word), data.min_amount_out);
+    }
+
+    #[test]
+    fn test_buy_sell_calldata_selector() {
+        let data = BuySellExecutionData {
+            buy_tokens: vec![H160::from_low_u64_be(1), H160::from_low_u64_be(2)],
+            buy_pools: vec![H160::from_low_u64_be(3)],
+            buy_pool_types: vec![0],
+            buy_amounts: vec![U256::from(10u64), U256::from(20u64)],
+            sell_tokens: vec![H160::from_low_u64_be(2), H160::from_low_u64_be(1)],
+            sell_pools: vec![H160::from_low_u64_be(4)],
+            sell_pool_types: vec![0],
+            sell_amounts: vec![U256::from(20u64), U256::from(11u64)],
+            strategy: ExecutorStrategy::V2,
+            deadline: U256::from(1_700_000_000u64),
+            slippage_bps: 0,
+            min_amount_out: U256::from(11u64),
+            opportunity_id: 0,
+        };
+        let calldata = buy_sell_calldata(&data);
+        let selector = ethers::utils::keccak256(
+            "buySellExecution(address[],address[],uint8[],uint256[],address[],address[],uint8[],uint256[],uint256)",
+        );
+        assert_eq!(&calldata[..4], &selector[..4]);
+    }
+
+    #[test]
+    fn test_tx_env_matches_sent_transaction() {
+        let data = BuySellExecutionData {
+            buy_tokens: vec![H160::from_low_u64_be(1), H160::from_low_u64_be(2)],
+            buy_pools: vec![H160::from_low_u64_be(3)],
+            buy_pool_types: vec![0],
+            buy_amounts: vec![U256::from(10u64), U256::from(20u64)],
+            sell_tokens: vec![H160::from_low_u64_be(2), H160::from_low_u64_be(1)],
+            sell_pools: vec![H160::from_low_u64_be(4)],
+            sell_pool_types: vec![1],
+            sell_amounts: vec![U256::from(20u64), U256::from(11u64)],
+            strategy: ExecutorStrategy::V3,
+            deadline: U256::from(1_700_000_000u64),
+            slippage_bps: 0,
+            min_amount_out: U256::from(11u64),
+            opportunity_id: 0,
+        };
+        let (contract, from) = (H160::from_low_u64_be(0xc0), H160::from_low_u64_be(0xf0));
+        let tx = data.to_transaction(contract, from);
+        let tx_env = data.to_tx_env(contract, from);
+        assert_eq!(tx_env.data.as_ref(), tx.input.as_ref());
+        assert_eq!(tx_env.caller.as_slice(), from.as_bytes());
+        assert_eq!(tx_env.kind.to().map(|to| to.as_slice()), Some(contract.as_bytes()));
+        assert_eq!(tx_env.gas_limit, BUY_SELL_GAS_LIMIT);
+
+        // The send path builds its call from the same data
+        let (provider, _mock) = Provider::mocked();
+        let sent = buy_sell_call(&DirectSwapExecutor::new(contract, Arc::new(provider)), &data);
+        assert_eq!(sent.tx.data().map(|d| d.as_ref()), Some(tx_env.data.as_ref()));
+    }
+
+    #[tokio::test]
+    async fn test_gas_strategy_fixed() {
+        let (provider, _mock) = Provider::mocked();
+        let price = U256::from(3_000_000_000u64);
+        assert_eq!(resolve_gas_price(&GasStrategy::Fixed(price), &provider).await.unwrap(), price);
+    }
+
+    #[tokio::test]
+    async fn test_nonce_allocator_leaves_no_gap() {
+        // The mock errors once its queue is empty, so every `next` below that succeeds without a
+        // pushed response was served from the cached nonce
+        let (provider, mock) = Provider::mocked();
+        let wallet = H160::from_low_u64_be(0xf0);
+        let nonces = NonceAllocator::default();
+        mock.push(U256::from(7u64)).unwrap();
+        assert_eq!(nonces.next(wallet, &provider).await.unwrap(), U256::from(7u64));
+        assert_eq!(nonces.next(wallet, &provider).await.unwrap(), U256::from(8u64));
+
+        // A leg that failed to submit hands its nonce to the next send
+        nonces.release(U256::from(8u64)).await;
+        assert_eq!(nonces.next(wallet, &provider).await.unwrap(), U256::from(8u64));
+
+        // Releasing behind a later nonce can't be patched locally: the count is re-read, and the
+        // node reports the free nonce again
+        assert_eq!(nonces.next(wallet, &provider).await.unwrap(), U256::from(9u64));
+        nonces.release(U256::from(8u64)).await;
+        mock.push(U256::from(8u64)).unwrap();
+        assert_eq!(nonces.next(wallet, &provider).await.unwrap(), U256::from(8u64));
+
+        // A send without a receipt resets, so a bundle that never landed doesn't leave a hole
+        nonces.reset().await;
+        mock.push(U256::from(9u64)).unwrap();
+        assert_eq!(nonces.next(wallet, &provider).await.unwrap(), U256::from(9u64));
+
+        nonces.set(U256::from(20u64)).await;
+        assert_eq!(nonces.next(wallet, &provider).await.unwrap(), U256::from(20u64));
+    }
+
+    #[tokio::test]
+    async fn test_gas_strategy_oracle_multiple() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::from(1_000_000_000u64)).unwrap();
+        let price = resolve_gas_price(&GasStrategy::OracleMultiple(1.5), &provider).await.unwrap();
+        assert_eq!(price, U256::from(1_500_000_000u64));
+        assert!(resolve_gas_price(&GasStrategy::OracleMultiple(0.0), &provider).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_gas_strategy_fee_history_percentile() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(FeeHistory {
+            base_fee_per_gas: vec![U256::from(100u64), U256::from(200u64)],
+            gas_used_ratio: vec![0.5],
+            oldest_block: U256::from(1u64),
+            reward: vec![vec![U256::from(10u64)], vec![U256::from(30u64)]],
+        })
+        .unwrap();
+        let price = resolve_gas_price(&GasStrategy::FeeHistoryPercentile(90), &provider).await.unwrap();
+        // Next base fee (200) + mean of the sampled priority fees (20)
+        assert_eq!(price, U256::from(220u64));
+        assert!(resolve_gas_price(&GasStrategy::FeeHistoryPercentile(101), &provider).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_gas_pricing_fills_transaction_fields() {
+        let gwei = |n: u64| U256::from(n) * U256::exp10(9);
+        let (contract_provider, _contract_mock) = Provider::mocked();
+        let contract = DirectSwapExecutor::new(H160::from_low_u64_be(0xc0), Arc::new(contract_provider));
+        let data = BuySellExecutionData {
+            buy_tokens: vec![H160::from_low_u64_be(1), H160::from_low_u64_be(2)],
+            buy_pools: vec![H160::from_low_u64_be(3)],
+            buy_pool_types: vec![0],
+            buy_amounts: vec![U256::from(10u64), U256::from(20u64)],
+            sell_tokens: vec![H160::from_low_u64_be(2), H160::from_low_u64_be(1)],
+            sell_pools: vec![H160::from_low_u64_be(4)],
+            sell_pool_types: vec![0],
+            sell_amounts: vec![U256::from(20u64), U256::from(11u64)],
+            strategy: ExecutorStrategy::V2,
+            deadline: U256::from(1_700_000_000u64),
+            slippage_bps: 0,
+            min_amount_out: U256::from(11u64),
+            opportunity_id: 0,
+        };
+        let (provider, mock) = Provider::mocked();
+
+        // Legacy strategies: a legacy transaction bidding one price
+        let pricing = resolve_gas_pricing(&GasStrategy::Fixed(gwei(3)), &provider).await.unwrap();
+        assert_eq!(pricing, GasPricing::Legacy(gwei(3)));
+        match with_gas_pricing(buy_sell_call(&contract, &data), pricing).tx {
+            TypedTransaction::Legacy(tx) => assert_eq!(tx.gas_price, Some(gwei(3))),
+            other => panic!("expected a legacy transaction, got {:?}", other),
+        }
+
+        // EIP-1559: base fee 1 gwei doubled plus a 1.5 gwei tip
+        let eip1559 = GasStrategy::Eip1559 { priority_gwei: 1.5, max_multiplier: 2.0 };
+        mock.push(Block::<TxHash> { base_fee_per_gas: Some(gwei(1)), ..Default::default() }).unwrap();
+        let pricing = resolve_gas_pricing(&eip1559, &provider).await.unwrap();
+        let (max_fee, tip) = (U256::from(3_500_000_000u64), U256::from(1_500_000_000u64));
+        assert_eq!(pricing, GasPricing::Eip1559 { max_fee_per_gas: max_fee, max_priority_fee_per_gas: tip });
+        match with_gas_pricing(buy_sell_call(&contract, &data), pricing).tx {
+            TypedTransaction::Eip1559(tx) => {
+                assert_eq!((tx.max_fee_per_gas, tx.max_priority_fee_per_gas), (Some(max_fee), Some(tip)));
             }
+            other => panic!("expected an EIP-1559 transaction, got {:?}", other),
         }
+
+        // No base fee: legacy eth_gasPrice (mock responses are served last-pushed first)
+        mock.push(gwei(5)).unwrap();
+        mock.push(Block::<TxHash>::default()).unwrap();
+        assert_eq!(resolve_gas_pricing(&eip1559, &provider).await.unwrap(), GasPricing::Legacy(gwei(5)));
+        assert_eq!(resolve_gas_price(&GasStrategy::Fixed(gwei(3)), &provider).await.unwrap(), gwei(3));
+
+        mock.push(Block::<TxHash> { base_fee_per_gas: Some(gwei(1)), ..Default::default() }).unwrap();
+        let below_base = GasStrategy::Eip1559 { priority_gwei: 1.0, max_multiplier: 0.5 };
+        assert!(resolve_gas_pricing(&below_base, &provider).await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gas_price_refresh_tracks_strategy_bid() {
+        let gwei = |n: u64| U256::from(n) * U256::exp10(9);
+        let (provider, mock) = Provider::mocked();
+        let oracle = Arc::new(PriceOracle::default());
+        oracle.set_gas_price(gwei(5));
+        // Twice the node's price, refreshed every 3s; the mock fails once it runs out
+        mock.push(gwei(3)).unwrap();
+        spawn_gas_price_refresh(oracle.clone(), GasStrategy::OracleMultiple(2.0), Arc::new(provider), std::time::Duration::from_secs(3));
+
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        assert_eq!(oracle.gas_price(), gwei(6));
+        tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+        assert_eq!(oracle.gas_price(), gwei(6), "a failed query keeps the last bid");
+        mock.push(gwei(4)).unwrap();
+        tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+        assert_eq!(oracle.gas_price(), gwei(8));
+    }
+
+    fn mock_oracle(token: &H160) -> Option<f64> {
+        let wbnb: H160 = WBNB_ADDRESS.parse().unwrap();
+        let usdt: H160 = "0x55d398326f99059fF775485246999027B3197955".parse().unwrap();
+        if *token == wbnb {
+            Some(600.0)
+        } else if *token == usdt {
+            Some(1.0)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_gas_cost_in_usdt_base() {
+        let usdt: H160 = "0x55d398326f99059fF775485246999027B3197955".parse().unwrap();
+        // 200k gas at 5 gwei = 0.001 BNB = 0.6 USDT
+        let cost = gas_cost_in_base_token(
+            U256::from(200_000u64),
+            U256::from(5_000_000_000u64),
+            usdt,
+            mock_oracle,
+        )
+        .unwrap();
+        assert_eq!(cost, U256::from(600_000_000_000_000_000u128));
+    }
+
+    #[test]
+    fn test_gas_cost_in_bnb_base() {
+        let wbnb: H160 = WBNB_ADDRESS.parse().unwrap();
+        let cost = gas_cost_in_base_token(
+            U256::from(200_000u64),
+            U256::from(5_000_000_000u64),
+            wbnb,
+            mock_oracle,
+        )
+        .unwrap();
+        assert_eq!(cost, U256::from(1_000_000_000_000_000u128));
+    }
+
+    #[test]
+    fn test_realized_profit_from_receipt() {
+        let base = H160::repeat_byte(0xaa);
+        let wallet = H160::repeat_byte(0x0e);
+        let contract = H160::repeat_byte(0xee);
+        let pool = H160::repeat_byte(0x01);
+        let transfer = |from: H160, to: H160, amount: u64| {
+            let mut data = [0u8; 32];
+            U256::from(amount).to_big_endian(&mut data);
+            Log {
+                address: base,
+                topics: vec![
+                    H256::from(ethers::utils::keccak256("Transfer(address,address,uint256)")),
+                    H256::from(from),
+                    H256::from(to),
+                ],
+                data: Bytes::from(data.to_vec()),
+                ..Default::default()
+            }
+        };
+        let receipt = TransactionReceipt {
+            from: wallet,
+            to: Some(contract),
+            logs: vec![
+                transfer(wallet, contract, 500),
+                transfer(contract, pool, 1_000),
+                transfer(pool, contract, 990),
+            ],
+            ..Default::default()
+        };
+        let mut token_index = crate::token_index::TokenIndexMap {
+            address_to_index: HashMap::new(),
+            index_to_address: HashMap::new(),
+        };
+        token_index.address_to_index.insert(base, 0);
+        token_index.index_to_address.insert(0, base);
+
+        // Lost 10 to slippage/tax; the wallet -> contract top-up is internal
+        let realized = realized_profit_from_receipt(&receipt, 0, &token_index).unwrap();
+        assert_eq!(realized, I256::from(-10));
+        assert!(realized_profit_from_receipt(&receipt, 1, &token_index).is_none());
+
+        // Only the internal top-up is left: nothing says what the trade made
+        let internal_only = TransactionReceipt { logs: receipt.logs[..1].to_vec(), ..receipt.clone() };
+        assert!(realized_profit_from_receipt(&internal_only, 0, &token_index).is_none());
+        assert!(realized_profit_from_receipt(&TransactionReceipt { logs: vec![], ..receipt.clone() }, 0, &token_index).is_none());
+
+        let divergence = profit_divergence_bps(U256::from(20u64), realized).unwrap();
+        assert_eq!(divergence, -15_000.0);
+    }
+
+    #[test]
+    fn test_gas_cost_unknown_base_token() {
+        let unknown = H160::repeat_byte(0x11);
+        assert!(gas_cost_in_base_token(U256::from(1u64), U256::from(1u64), unknown, mock_oracle).is_none());
+    }
+
+    #[test]
+    fn test_classify_revert() {
+        let selector = |sig: &str| hex::encode(&ethers::utils::keccak256(sig.as_bytes())[..4]);
+        assert_eq!(classify_revert(&selector("InsufficientProfit()")), Some(RevertReason::InsufficientProfit));
+        let with_args = format!("0x{}{:064x}{:064x}", selector("InsufficientProfit(uint256,uint256)"), 5, 10);
+        assert_eq!(classify_revert(&with_args), Some(RevertReason::InsufficientProfit));
+
+        let error_string = |reason: &str| {
+            let encoded = ethers::abi::encode(&[ethers::abi::Token::String(reason.to_string())]);
+            format!("0x08c379a0{}", hex::encode(encoded))
+        };
+        assert_eq!(classify_revert(&error_string("Insufficient profit")), Some(RevertReason::InsufficientProfit));
+        assert_eq!(classify_revert(&error_string("K")), Some(RevertReason::Message("K".to_string())));
+        assert_eq!(decode_revert_reason(&error_string("TRANSFER_FAILED")), Some("TRANSFER_FAILED".to_string()));
+        assert_eq!(classify_revert("0xdeadbeef"), Some(RevertReason::Custom("deadbeef".to_string())));
+        // Truncated payloads are rejected rather than sliced out of bounds
+        assert_eq!(classify_revert(&error_string("K")[..90]), None);
+        assert_eq!(classify_revert("0x08c3"), None);
+
+        let msg = format!("(code: 3, message: execution reverted, data: Some(String(\"{}\")))", error_string("insufficient_profit"));
+        assert_eq!(classify_revert_in_message(&msg), Some(RevertReason::InsufficientProfit));
+        assert_eq!(classify_revert_in_message("nonce too low"), None);
+    }
+
+    #[tokio::test]
+    async fn test_mined_revert_is_replayed_and_classified() {
+        let (provider, mock) = Provider::mocked();
+        let tx_hash = H256::repeat_byte(0xaa);
+        let receipt = TransactionReceipt { transaction_hash: tx_hash, block_number: Some(100u64.into()), status: Some(0u64.into()), ..Default::default() };
+        let tx = Transaction { hash: tx_hash, from: H160::from_low_u64_be(0xf0), to: Some(H160::from_low_u64_be(0xc0)), ..Default::default() };
+        let insufficient_profit = format!("0x{}", hex::encode(&ethers::utils::keccak256("InsufficientProfit()")[..4]));
+
+        // Served last-pushed first: eth_getTransactionByHash, then the eth_call revert
+        mock.push_response(ethers::providers::MockResponse::Error(ethers::providers::JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: Some(serde_json::Value::String(insufficient_profit)),
+        }));
+        mock.push(tx.clone()).unwrap();
+        assert_eq!(replay_mined_revert(&receipt, &provider).await, Some(RevertReason::InsufficientProfit));
+
+        // The replay passing on the parent state means something else in the block broke the trade
+        mock.push(Bytes::new()).unwrap();
+        mock.push(tx).unwrap();
+        assert_eq!(replay_mined_revert(&receipt, &provider).await, None);
+    }
+
+    #[test]
+    fn test_adaptive_slippage_bounds() {
+        let slippage = AdaptiveSlippage::new(100, 50, 150, 25);
+        assert_eq!(slippage.record_insufficient_profit(), 125);
+        assert_eq!(slippage.record_insufficient_profit(), 150);
+        assert_eq!(slippage.record_insufficient_profit(), 150);
+        assert_eq!(slippage.current_bps(), 150);
+
+        // Narrows one step per SUCCESSES_PER_STEP clean executions, never below min
+        for _ in 1..AdaptiveSlippage::SUCCESSES_PER_STEP {
+            assert_eq!(slippage.record_success(), 150);
+        }
+        assert_eq!(slippage.record_success(), 125);
+        for _ in 0..AdaptiveSlippage::SUCCESSES_PER_STEP * 10 {
+            slippage.record_success();
+        }
+        assert_eq!(slippage.current_bps(), 50);
+
+        // 1% of a 1000 output eats 10 of the profit
+        let slippage = AdaptiveSlippage::new(100, 10, 500, 25);
+        assert_eq!(slippage.worst_case_profit(U256::from(50u64), U256::from(1_000u64)), U256::from(40u64));
+        assert_eq!(slippage.worst_case_profit(U256::from(5u64), U256::from(1_000u64)), U256::zero());
+    }
+
+    #[test]
+    fn test_approval_amount_and_known_allowance() {
+        let min = U256::from(1_000u64);
+        assert_eq!(ApprovalMode::Max.approval_amount(min), U256::MAX);
+        assert_eq!(ApprovalMode::Exact.approval_amount(min), min);
+        assert_eq!(ApprovalMode::Amount(U256::from(5_000u64)).approval_amount(min), U256::from(5_000u64));
+        assert_eq!(ApprovalMode::Amount(U256::from(10u64)).approval_amount(min), min);
+
+        let key = (H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3));
+        assert!(!take_known_allowance(key, min));
+        KNOWN_ALLOWANCES.insert(key, U256::from(1_500u64));
+        assert!(take_known_allowance(key, min));
+        // 500 left: the next trade has to check on-chain again
+        assert!(!take_known_allowance(key, min));
+        assert_eq!(*KNOWN_ALLOWANCES.get(&key).unwrap(), U256::from(500u64));
+    }
+
+    #[test]
+    fn test_deadline_encoded_in_calldata() {
+        let config = Config { tx_deadline_secs: 45, ..Config::default() };
+        let now = chrono::Utc::now().timestamp() as u64;
+        let swap_data = BuySellExecutionData {
+            buy_tokens: vec![H160::from_low_u64_be(1), H160::from_low_u64_be(2)],
+            buy_pools: vec![H160::from_low_u64_be(10)],
+            buy_pool_types: vec![0],
+            buy_amounts: vec![U256::from(100u64), U256::from(50u64)],
+            sell_tokens: vec![H160::from_low_u64_be(2), H160::from_low_u64_be(1)],
+            sell_pools: vec![H160::from_low_u64_be(11)],
+            sell_pool_types: vec![0],
+            sell_amounts: vec![U256::from(50u64), U256::from(110u64)],
+            strategy: ExecutorStrategy::V2,
+            deadline: deadline_from_now(config.tx_deadline_secs),
+            slippage_bps: 0,
+            min_amount_out: U256::from(110u64),
+            opportunity_id: 0,
+        };
+        let deadline = swap_data.deadline.as_u64();
+        assert!(deadline > now);
+        assert!(deadline >= now + 45 && deadline <= now + 46);
+        assert!(!swap_data.is_expired());
+
+        // deadline is the 9th (static) argument: head word 8 after the selector
+        let (provider, _mock) = Provider::mocked();
+        let contract = DirectSwapExecutor::new(H160::zero(), Arc::new(provider));
+        let calldata = buy_sell_call(&contract, &swap_data).calldata().unwrap();
+        let word = &calldata[4 + 8 * 32..4 + 9 * 32];
+        assert_eq!(U256::from_big_endian(word), swap_data.deadline);
+
+        let expired = BuySellExecutionData { deadline: U256::from(now - 1), ..swap_data };
+        assert!(expired.is_expired());
     }
-    None
 }
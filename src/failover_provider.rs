@@ -0,0 +1,289 @@
+use async_trait::async_trait;
+use ethers::providers::{Http, HttpClientError, JsonRpcClient, JsonRpcError, Provider, ProviderError, RpcError};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Per-request timeout before moving on to the next endpoint
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Consecutive failures after which an endpoint is considered unhealthy
+const UNHEALTHY_AFTER: u32 = 3;
+/// How long an unhealthy endpoint is skipped before it's tried again
+const COOLDOWN_SECS: u64 = 30;
+
+/// HTTP provider over several endpoints
+pub type HttpProvider = Provider<FailoverProvider>;
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    client: Http,
+    consecutive_failures: AtomicU32,
+    last_failure: AtomicU64, // unix seconds
+}
+
+impl Endpoint {
+    fn is_healthy(&self, now: u64) -> bool {
+        self.consecutive_failures.load(Ordering::Relaxed) < UNHEALTHY_AFTER
+            || now.saturating_sub(self.last_failure.load(Ordering::Relaxed)) >= COOLDOWN_SECS
+    }
+
+    fn record_failure(&self, now: u64) {
+        self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+        self.last_failure.store(now, Ordering::Relaxed);
+    }
+}
+
+/// JSON-RPC transport that sends each request to the active HTTP endpoint and
+/// rotates to the next one on transport error or timeout. JSON-RPC error responses
+/// (e.g. reverts) come from a healthy node and are returned without failing over.
+#[derive(Debug, Clone)]
+pub struct FailoverProvider {
+    endpoints: Arc<Vec<Endpoint>>,
+    active: Arc<AtomicUsize>,
+}
+
+#[derive(Debug)]
+pub enum FailoverError {
+    /// The node answered with a JSON-RPC error
+    Rpc(HttpClientError),
+    /// The request params could not be serialized
+    Serde(serde_json::Error),
+    /// Every endpoint failed: (url, error)
+    AllFailed(Vec<(String, String)>),
+}
+
+impl fmt::Display for FailoverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailoverError::Rpc(e) => write!(f, "{}", e),
+            FailoverError::Serde(e) => write!(f, "failed to serialize params: {}", e),
+            FailoverError::AllFailed(errors) => {
+                write!(f, "all {} RPC endpoints failed:", errors.len())?;
+                for (url, e) in errors {
+                    write!(f, " [{}: {}]", url, e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for FailoverError {}
+
+impl RpcError for FailoverError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            FailoverError::Rpc(e) => e.as_error_response(),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            FailoverError::Rpc(e) => e.as_serde_error(),
+            FailoverError::Serde(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<FailoverError> for ProviderError {
+    fn from(e: FailoverError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(e))
+    }
+}
+
+impl FailoverProvider {
+    /// Build from a list of HTTP endpoint URLs (first one starts as active)
+    pub fn new(urls: &[String]) -> anyhow::Result<Self> {
+        if urls.is_empty() {
+            anyhow::bail!("no RPC endpoints configured");
+        }
+        let endpoints = urls
+            .iter()
+            .map(|url| {
+                Ok(Endpoint {
+                    url: url.clone(),
+                    client: url.parse::<Http>().map_err(|e| anyhow::anyhow!("invalid RPC url {}: {}", url, e))?,
+                    consecutive_failures: AtomicU32::new(0),
+                    last_failure: AtomicU64::new(0),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            endpoints: Arc::new(endpoints),
+            active: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Convenience: `Provider` over the given endpoints, usable anywhere a `Middleware` is
+    pub fn connect(urls: &[String]) -> anyhow::Result<HttpProvider> {
+        Ok(Provider::new(Self::new(urls)?))
+    }
+
+    /// URL of the endpoint currently receiving requests
+    pub fn active_url(&self) -> &str {
+        &self.endpoints[self.active.load(Ordering::Relaxed) % self.endpoints.len()].url
+    }
+
+    /// (url, healthy) for every endpoint
+    pub fn endpoint_health(&self) -> Vec<(String, bool)> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.endpoints.iter().map(|e| (e.url.clone(), e.is_healthy(now))).collect()
+    }
+
+    /// Endpoint indices to try, starting at the active one; unhealthy endpoints go last
+    fn attempt_order(&self, now: u64) -> Vec<usize> {
+        let n = self.endpoints.len();
+        let start = self.active.load(Ordering::Relaxed);
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..n).map(|i| (start + i) % n).partition(|&i| self.endpoints[i].is_healthy(now));
+        healthy.extend(unhealthy);
+        healthy
+    }
+}
+
+/// Rate-limit responses are treated like transport failures so the next endpoint is tried
+fn is_rate_limited(err: &JsonRpcError) -> bool {
+    err.code == 429 || err.code == -32005 || classify_provider_error(&err.message) == ErrorClass::RateLimited
+}
+
+/// How a caller should react to a provider or transport error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    Fatal,       // bad URL, rejected credentials, unsupported method: retrying can't help
+    RateLimited, // the endpoint is throttling us: back off longer before retrying
+    Retryable,   // timeouts, disconnects, dropped streams
+}
+
+const FATAL_PHRASES: &[&str] = &[
+    "unauthorized",
+    "forbidden",
+    "invalid api key",
+    "api key not found",
+    "authentication failed",
+    "relative url without a base",
+    "invalid url",
+    "url error",
+    "unsupported url scheme",
+    "invalid dns name",
+    "method not found",
+    "does not exist/is not available",
+    "no ws endpoints configured",
+];
+const RATE_LIMIT_PHRASES: &[&str] = &["rate limit", "too many requests", "limit exceeded", "capacity exceeded"];
+
+/// Classify an error by its message. ethers flattens WS and HTTP transport errors into
+/// strings, so the text (including HTTP status codes like 401 or 429) is what's left to go on.
+/// Anything unrecognized is assumed transient.
+pub fn classify_provider_error(message: &str) -> ErrorClass {
+    let lower = message.to_lowercase();
+    let has_code = |code: &str| lower.split(|c: char| !c.is_ascii_alphanumeric()).any(|word| word == code);
+    if has_code("401") || has_code("403") || FATAL_PHRASES.iter().any(|p| lower.contains(p)) {
+        ErrorClass::Fatal
+    } else if has_code("429") || has_code("32005") || RATE_LIMIT_PHRASES.iter().any(|p| lower.contains(p)) {
+        ErrorClass::RateLimited
+    } else {
+        ErrorClass::Retryable
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FailoverProvider {
+    type Error = FailoverError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        // Serialize once so the same params can be replayed against each endpoint
+        let params = serde_json::to_value(params).map_err(FailoverError::Serde)?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        let mut errors = Vec::new();
+
+        for idx in self.attempt_order(now) {
+            let endpoint = &self.endpoints[idx];
+            match tokio::time::timeout(REQUEST_TIMEOUT, endpoint.client.request::<_, R>(method, &params)).await {
+                Ok(Ok(res)) => {
+                    endpoint.consecutive_failures.store(0, Ordering::Relaxed);
+                    if self.active.swap(idx, Ordering::Relaxed) != idx {
+                        println!("[RPC] Failed over to {}", endpoint.url);
+                    }
+                    return Ok(res);
+                }
+                Ok(Err(e)) if e.as_error_response().map_or(false, |r| !is_rate_limited(r)) => {
+                    // The node is up; the request itself failed
+                    endpoint.consecutive_failures.store(0, Ordering::Relaxed);
+                    return Err(FailoverError::Rpc(e));
+                }
+                Ok(Err(e)) => {
+                    endpoint.record_failure(now);
+                    eprintln!("[RPC] {} failed on {}: {}", method, endpoint.url, e);
+                    errors.push((endpoint.url.clone(), e.to_string()));
+                }
+                Err(_) => {
+                    endpoint.record_failure(now);
+                    eprintln!("[RPC] {} timed out on {}", method, endpoint.url);
+                    errors.push((endpoint.url.clone(), "timeout".to_string()));
+                }
+            }
+        }
+        Err(FailoverError::AllFailed(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attempt_order_skips_unhealthy() {
+        let provider = FailoverProvider::new(&[
+            "http://127.0.0.1:1".to_string(),
+            "http://127.0.0.1:2".to_string(),
+            "http://127.0.0.1:3".to_string(),
+        ])
+        .unwrap();
+        let now = 1_000;
+        assert_eq!(provider.attempt_order(now), vec![0, 1, 2]);
+
+        for _ in 0..UNHEALTHY_AFTER {
+            provider.endpoints[0].record_failure(now);
+        }
+        assert_eq!(provider.attempt_order(now), vec![1, 2, 0]);
+
+        // Cooldown expired: the endpoint gets another chance in its normal slot
+        assert_eq!(provider.attempt_order(now + COOLDOWN_SECS), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_requires_endpoint() {
+        assert!(FailoverProvider::new(&[]).is_err());
+    }
+
+    #[test]
+    fn test_classify_provider_error() {
+        for fatal in [
+            "HTTP error 401 Unauthorized",
+            "URL error: URL scheme not supported: relative URL without a base",
+            "Invalid API key",
+            "(code: -32601, message: the method eth_subscribe does not exist/is not available, data: None)",
+        ] {
+            assert_eq!(classify_provider_error(fatal), ErrorClass::Fatal, "{}", fatal);
+        }
+        for limited in ["HTTP error 429 Too Many Requests", "(code: -32005, message: limit exceeded)"] {
+            assert_eq!(classify_provider_error(limited), ErrorClass::RateLimited, "{}", limited);
+        }
+        for transient in [
+            "V3 Swap subscription timeout",
+            "WebSocket protocol error: Connection reset without closing handshake",
+            "error in block 14015401", // a number that merely contains a status code
+        ] {
+            assert_eq!(classify_provider_error(transient), ErrorClass::Retryable, "{}", transient);
+        }
+    }
+}
@@ -7,7 +7,7 @@ use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use ethers::{
-    providers::{Http, Provider, Middleware},
+    providers::Middleware,
     types::{Address, BlockNumber, Filter, Log, H256},
     utils::hex,
 };
@@ -18,6 +18,7 @@ use std::str::FromStr;
 use ethers::utils::keccak256;
 
 use crate::config::{Config, DexConfig, DexVersion};
+use crate::failover_provider::FailoverProvider;
 
 /// Pair information structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +49,8 @@ pub struct PairInfo {
     pub reserve0: Option<String>,
     #[serde(default)]
     pub reserve1: Option<String>,
+    #[serde(default)]
+    pub fee: Option<u32>, // V3 fee tier in hundredths of a bip (500 = 0.05%), from PoolCreated; None for V2
 }
 
 /// Progress tracking for each factory
@@ -60,6 +63,24 @@ pub struct FactoryProgress {
     pub last_updated: u64, // timestamp
 }
 
+/// Fee tier of a V3 `PoolCreated` log: the indexed `uint24 fee`, the third topic after the
+/// signature. None if the log doesn't carry it.
+fn pool_created_fee(log: &Log) -> Option<u32> {
+    let topic = log.topics.get(3)?;
+    Some(u32::from_be_bytes(topic.as_bytes()[28..32].try_into().ok()?))
+}
+
+/// What a fetch does with the pair files and checkpoint of an earlier run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchStart {
+    /// Start over, but only if the pair files are empty or missing
+    New,
+    /// Continue from the saved checkpoint, appending to the pair files
+    Resume,
+    /// Start over, discarding the checkpoint and the pairs already fetched
+    Fresh,
+}
+
 /// Main pair fetcher
 pub struct PairFetcher {
     config: Config,
@@ -72,15 +93,20 @@ pub struct PairFetcher {
 
 impl PairFetcher {
     pub fn new(config: Config) -> Self {
-        let progress_file = "data/factory_progress.json".to_string();
-        let v2_pairs_file = "data/pairs_v2.jsonl".to_string();
-        let v3_pools_file = "data/pairs_v3.jsonl".to_string();
+        Self::with_data_dir(config, Path::new("data"))
+    }
+
+    /// Fetcher reading `safe_tokens.json` from, and writing pairs and progress to, `dir`
+    pub fn with_data_dir(config: Config, dir: &Path) -> Self {
+        let progress_file = dir.join("factory_progress.json").display().to_string();
+        let v2_pairs_file = dir.join("pairs_v2.jsonl").display().to_string();
+        let v3_pools_file = dir.join("pairs_v3.jsonl").display().to_string();
         
         // Create data directory if it doesn't exist
-        std::fs::create_dir_all("data").ok();
+        std::fs::create_dir_all(dir).ok();
         
         // Load safe tokens
-        let safe_tokens = load_safe_tokens("data/safe_tokens.json");
+        let safe_tokens = load_safe_tokens(&dir.join("safe_tokens.json").display().to_string());
         
         Self {
             config,
@@ -111,13 +137,46 @@ impl PairFetcher {
         Ok(())
     }
     
-    /// Save progress to file
+    /// Save progress to file. Written to a temp file and renamed, so a crash mid-write keeps the
+    /// previous checkpoint.
     pub fn save_progress(&self) -> Result<()> {
         let progress = self.progress.lock().unwrap();
-        let file = File::create(&self.progress_file)?;
-        serde_json::to_writer_pretty(file, &*progress)?;
+        let tmp = format!("{}.tmp", self.progress_file);
+        serde_json::to_writer_pretty(File::create(&tmp)?, &*progress)?;
+        std::fs::rename(&tmp, &self.progress_file)?;
         Ok(())
     }
+
+    /// Set up a run. Resuming loads the saved per-factory block cursors and returns the pairs
+    /// already in the output files, which are appended to; a pair written just before a crash,
+    /// and so scanned again, is not written twice. A fresh run discards the progress and
+    /// truncates the output files; without `--fresh` it refuses to when they hold pairs.
+    fn start_run(&self, start: FetchStart) -> Result<HashSet<Address>> {
+        if start == FetchStart::Resume {
+            self.load_progress()?;
+            let (written, _) = load_pairs_from_files(&[&self.v2_pairs_file, &self.v3_pools_file]);
+            let written: HashSet<Address> = written.into_iter().map(|pair| pair.pair_address).collect();
+            println!("Resuming: {} pairs already written", written.len());
+            return Ok(written);
+        }
+        let existing: Vec<&str> = [&self.v2_pairs_file, &self.v3_pools_file]
+            .into_iter()
+            .filter(|file| std::fs::metadata(file).is_ok_and(|m| m.len() > 0))
+            .map(String::as_str)
+            .collect();
+        if start == FetchStart::New && !existing.is_empty() {
+            return Err(anyhow!(
+                "{} already hold pairs; rerun with --resume to continue that fetch or --fresh to discard it",
+                existing.join(" and ")
+            ));
+        }
+        println!("Starting a fresh fetch: truncating {} and {}", self.v2_pairs_file, self.v3_pools_file);
+        self.progress.lock().unwrap().clear();
+        File::create(&self.v2_pairs_file)?;
+        File::create(&self.v3_pools_file)?;
+        self.save_progress()?;
+        Ok(HashSet::new())
+    }
     
     /// Get or create progress for a factory
     fn get_or_create_progress(&self, factory_address: Address, dex_name: &str) -> FactoryProgress {
@@ -172,17 +231,19 @@ impl PairFetcher {
         Ok(())
     }
     
-    /// Fetch pairs from a single factory
-    async fn fetch_factory_pairs(
+    /// Fetch pairs from a single factory, checkpointing the block cursor after every batch.
+    /// A failed batch stops the factory there, so the next `--resume` run starts from it.
+    async fn fetch_factory_pairs<M: Middleware>(
         &self,
         dex: &DexConfig,
-        provider: &Provider<Http>,
+        provider: &M,
+        written: &HashSet<Address>,
     ) -> Result<Vec<PairInfo>> {
         let mut pairs = Vec::new();
         let progress = self.get_or_create_progress(dex.factory_address, &dex.name);
         println!("Fetching pairs from {} (last block: {})", dex.name, progress.last_scanned_block);
         // Get current block number
-        let current_block = provider.get_block_number().await?.as_u64();
+        let current_block = provider.get_block_number().await.map_err(|e| anyhow!("{}: block number: {}", dex.name, e))?.as_u64();
         let from_block = if progress.last_scanned_block == 0 {
             // First time scanning - start from a reasonable block
             match dex.version {
@@ -233,6 +294,7 @@ impl PairFetcher {
                     }
                     let mut before_filter = 0;
                     let mut after_filter = 0;
+                    let mut batch_pairs = 0;
                     for log in logs {
                         let pair = match dex.version {
                             DexVersion::V2 => self.parse_pair_created_log(&log, dex).await?,
@@ -243,8 +305,11 @@ impl PairFetcher {
                             // Only save if token0 or token1 is in safe_tokens
                             if self.safe_tokens.contains(&pair.token0) || self.safe_tokens.contains(&pair.token1) {
                                 after_filter += 1;
-                                pairs.push(pair.clone());
-                                self.save_pair(&pair)?;
+                                if !written.contains(&pair.pair_address) {
+                                    self.save_pair(&pair)?;
+                                    batch_pairs += 1;
+                                }
+                                pairs.push(pair);
                             }
                         }
                     }
@@ -253,14 +318,17 @@ impl PairFetcher {
                         println!("[DEBUG] V3 pairs after safe token filter: {}", after_filter);
                     }
                     // Update progress after each batch
-                    self.update_progress(dex.factory_address, current_to, pairs.len() as u64);
+                    self.update_progress(dex.factory_address, current_to, batch_pairs);
                     self.save_progress()?;
                     println!("{}: Scanned blocks {}-{}, found {} pairs", 
                         dex.name, current_from, current_to, pairs.len());
                 }
                 Err(e) => {
-                    eprintln!("Error fetching logs for {}: {}", dex.name, e);
-                    // Continue with next batch
+                    // Skipping ahead would checkpoint past the missing range and lose its pairs
+                    return Err(anyhow!(
+                        "{}: logs for blocks {}-{} failed ({}); checkpoint kept at block {}, rerun with --resume",
+                        dex.name, current_from, current_to, e, current_from - 1
+                    ));
                 }
             }
             current_from = current_to + 1;
@@ -307,6 +375,7 @@ impl PairFetcher {
             liquidity_usd: None,
             reserve0: None,
             reserve1: None,
+            fee: None,
         };
         
         Ok(Some(pair_info))
@@ -348,22 +417,23 @@ impl PairFetcher {
             liquidity_usd: None,
             reserve0: None,
             reserve1: None,
+            fee: pool_created_fee(log),
         };
         
         Ok(Some(pair_info))
     }
     
-    /// Main function to fetch all pairs from all factories
-    pub async fn fetch_all_pairs(&self) -> Result<()> {
+    /// Main function to fetch all pairs from all factories; `start` says what to do with an earlier run
+    pub async fn fetch_all_pairs(&self, start: FetchStart) -> Result<()> {
         println!("Starting pair fetching for {} DEXes...", self.config.dexes.len());
         
-        // Load existing progress
-        self.load_progress()?;
+        let written = self.start_run(start)?;
         
         // Create HTTP provider
-        let provider = Provider::<Http>::try_from(&self.config.rpc_url)?;
+        let provider = FailoverProvider::connect(&self.config.http_endpoints())?;
         
         // Process all DEXes in parallel
+        let written = &written;
         let results: Vec<Result<Vec<PairInfo>>> = self.config.dexes
             .par_iter()
             .map(|dex| {
@@ -373,7 +443,7 @@ impl PairFetcher {
                 tokio::runtime::Runtime::new()
                     .unwrap()
                     .block_on(async move {
-                        fetcher.fetch_factory_pairs(dex, &provider).await
+                        fetcher.fetch_factory_pairs(dex, &provider, written).await
                     })
             })
             .collect();
@@ -417,6 +487,138 @@ impl Clone for PairFetcher {
     }
 }
 
+/// Stats from loading pair files
+#[derive(Debug, Clone, Default)]
+pub struct PairLoadStats {
+    pub files_found: usize,
+    pub files_missing: Vec<String>,
+    pub lines_read: usize,
+    pub parse_errors: usize,
+    pub v3_count: usize,
+    pub error_kinds: HashMap<String, usize>, // serde error category -> count
+    pub error_samples: Vec<(String, usize, String)>, // (file, line, error)
+    pub read_errors: Vec<(String, usize, String)>,   // (file, last line read, I/O error); the rest of that file was not loaded
+}
+
+const PAIR_PARSE_CHUNK_LINES: usize = 50_000;
+const MAX_ERROR_SAMPLES: usize = 10;
+
+impl PairLoadStats {
+    fn record_error(&mut self, file: &str, line_no: usize, err: &serde_json::Error) {
+        self.parse_errors += 1;
+        *self.error_kinds.entry(format!("{:?}", err.classify())).or_insert(0) += 1;
+        if self.error_samples.len() < MAX_ERROR_SAMPLES {
+            self.error_samples.push((file.to_string(), line_no, err.to_string()));
+        }
+    }
+
+    /// Print the parse-error summary
+    pub fn print_summary(&self) {
+        println!(
+            "📊 Pair load summary: {} files, {} lines, {} parse errors, {} V3 pairs",
+            self.files_found, self.lines_read, self.parse_errors, self.v3_count
+        );
+        for (kind, count) in &self.error_kinds {
+            println!("   {} errors: {}", kind, count);
+        }
+        for (file, line, err) in &self.error_samples {
+            println!("   {}:{}: {}", file, line, err);
+        }
+        for (file, line, err) in &self.read_errors {
+            eprintln!("   ❌ {}: read failed after line {}: {}", file, line, err);
+        }
+    }
+}
+
+/// Stream-parse pairs from JSONL files in chunks (parsed in parallel with rayon),
+/// printing progress with lines/sec and ETA. Missing files and unparsable lines are skipped;
+/// a read error stops that file and is reported in `read_errors`, never taken for the end of it.
+pub fn load_pairs_from_files<P: AsRef<Path>>(paths: &[P]) -> (Vec<PairInfo>, PairLoadStats) {
+    let mut pairs: Vec<PairInfo> = Vec::new();
+    let mut stats = PairLoadStats::default();
+    for file_path in paths {
+        let file_path = file_path.as_ref().display().to_string();
+        let file = match File::open(&file_path) {
+            Ok(file) => file,
+            Err(_) => {
+                println!("❌ Could not open file: {}", file_path);
+                stats.files_missing.push(file_path);
+                continue;
+            }
+        };
+        stats.files_found += 1;
+        let total_bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+        println!("📂 Loading pairs from: {} ({:.1} MB)", file_path, total_bytes as f64 / 1_048_576.0);
+        let start = std::time::Instant::now();
+        let mut reader = BufReader::with_capacity(1 << 20, file);
+        let mut bytes_read: u64 = 0;
+        let mut file_lines = 0usize;
+        let mut file_pairs = 0usize;
+        let mut chunk: Vec<(usize, String)> = Vec::with_capacity(PAIR_PARSE_CHUNK_LINES);
+        let mut eof = false;
+        while !eof {
+            // 1. Read a chunk of lines
+            chunk.clear();
+            while chunk.len() < PAIR_PARSE_CHUNK_LINES {
+                let mut line = String::new();
+                match reader.read_line(&mut line) {
+                    Ok(0) => {
+                        eof = true;
+                        break;
+                    }
+                    Err(e) => {
+                        eprintln!("❌ Failed to read {} after line {}: {}", file_path, file_lines, e);
+                        stats.read_errors.push((file_path.clone(), file_lines, e.to_string()));
+                        eof = true;
+                        break;
+                    }
+                    Ok(n) => {
+                        bytes_read += n as u64;
+                        file_lines += 1;
+                        if !line.trim().is_empty() {
+                            chunk.push((file_lines, line));
+                        }
+                    }
+                }
+            }
+            // 2. Parse the chunk in parallel (order preserved)
+            let parsed: Vec<(usize, std::result::Result<PairInfo, serde_json::Error>)> = chunk
+                .par_iter()
+                .map(|(line_no, line)| (*line_no, serde_json::from_str::<PairInfo>(line)))
+                .collect();
+            for (line_no, result) in parsed {
+                match result {
+                    Ok(pair) => {
+                        if pair.dex_version == DexVersion::V3 {
+                            stats.v3_count += 1;
+                        }
+                        file_pairs += 1;
+                        pairs.push(pair);
+                    }
+                    Err(e) => stats.record_error(&file_path, line_no, &e),
+                }
+            }
+            // 3. Progress
+            let elapsed = start.elapsed().as_secs_f64().max(1e-6);
+            let lines_per_sec = file_lines as f64 / elapsed;
+            let eta = if bytes_read > 0 && total_bytes > bytes_read {
+                elapsed * (total_bytes - bytes_read) as f64 / bytes_read as f64
+            } else {
+                0.0
+            };
+            let pct = if total_bytes > 0 { bytes_read as f64 / total_bytes as f64 * 100.0 } else { 100.0 };
+            println!(
+                "   ⏳ {:.1}% | {} lines | {:.0} lines/sec | ETA {:.1}s",
+                pct, file_lines, lines_per_sec, eta
+            );
+        }
+        stats.lines_read += file_lines;
+        println!("   Loaded {} pairs from {} in {:.2?}", file_pairs, file_path, start.elapsed());
+    }
+    stats.print_summary();
+    (pairs, stats)
+}
+
 fn load_safe_tokens(path: &str) -> HashSet<Address> {
     let mut set = HashSet::new();
     if let Ok(file) = File::open(path) {
@@ -457,6 +659,7 @@ mod tests {
             liquidity_usd: None,
             reserve0: None,
             reserve1: None,
+            fee: None,
         };
         
         let json = serde_json::to_string(&pair).unwrap();
@@ -465,4 +668,193 @@ mod tests {
         assert_eq!(pair.dex_name, deserialized.dex_name);
         assert_eq!(pair.block_number, deserialized.block_number);
     }
+    
+    #[test]
+    fn test_load_pairs_from_files_collects_errors() {
+        let pair = PairInfo {
+            pair_address: Address::random(),
+            token0: Address::random(),
+            token1: Address::random(),
+            dex_name: "TestDEX".to_string(),
+            dex_version: DexVersion::V3,
+            factory_address: Address::random(),
+            block_number: 1,
+            transaction_hash: String::new(),
+            token0_symbol: None,
+            token1_symbol: None,
+            token0_decimals: None,
+            token1_decimals: None,
+            liquidity_usd: None,
+            reserve0: None,
+            reserve1: None,
+            fee: None,
+        };
+        let line = serde_json::to_string(&pair).unwrap();
+        let path = std::env::temp_dir().join(format!("pairs_test_{}.jsonl", std::process::id()));
+        std::fs::write(&path, format!("{line}\nnot json\n\n{line}\n")).unwrap();
+        let missing = std::env::temp_dir().join("pairs_test_missing.jsonl");
+        
+        let (pairs, stats) = load_pairs_from_files(&[path.clone(), missing]);
+        std::fs::remove_file(&path).ok();
+        
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(stats.v3_count, 2);
+        assert_eq!(stats.files_found, 1);
+        assert_eq!(stats.files_missing.len(), 1);
+        assert_eq!(stats.parse_errors, 1);
+        assert_eq!(stats.error_samples[0].1, 2);
+        assert!(stats.read_errors.is_empty());
+    }
+
+    #[test]
+    fn test_load_pairs_reports_read_errors() {
+        let pair = PairInfo {
+            pair_address: Address::random(),
+            token0: Address::random(),
+            token1: Address::random(),
+            dex_name: "TestDEX".to_string(),
+            dex_version: DexVersion::V2,
+            factory_address: Address::random(),
+            block_number: 1,
+            transaction_hash: String::new(),
+            token0_symbol: None,
+            token1_symbol: None,
+            token0_decimals: None,
+            token1_decimals: None,
+            liquidity_usd: None,
+            reserve0: None,
+            reserve1: None,
+            fee: None,
+        };
+        let line = serde_json::to_string(&pair).unwrap();
+        let path = std::env::temp_dir().join(format!("pairs_read_error_test_{}.jsonl", std::process::id()));
+        // Invalid UTF-8 on line 2 fails the read itself, not the JSON parse
+        let mut bytes = format!("{line}\n").into_bytes();
+        bytes.extend_from_slice(&[0xff, 0xfe, b'\n']);
+        bytes.extend_from_slice(format!("{line}\n").as_bytes());
+        std::fs::write(&path, bytes).unwrap();
+
+        let (pairs, stats) = load_pairs_from_files(&[path.clone()]);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(stats.read_errors.len(), 1);
+        assert_eq!(stats.read_errors[0].1, 1);
+        assert_eq!(stats.parse_errors, 0);
+    }
+    
+    #[test]
+    fn test_v3_pool_simulates_with_its_own_fee_tier() {
+        use crate::route_cache::PoolMeta;
+        use crate::simulate_swap_path::quote_exact_in;
+        use crate::testkit::{pool_address, pool_v3, token, units, MarketBuilder};
+        let (a, b, pool) = (token(1), token(2), pool_address(1));
+        // PoolCreated(token0, token1, uint24 indexed fee = 500, ...)
+        let log = Log {
+            topics: vec![H256::random(), H256::from(a), H256::from(b), H256::from_low_u64_be(500)],
+            ..Default::default()
+        };
+        let pair = PairInfo {
+            pair_address: pool,
+            token0: a,
+            token1: b,
+            dex_name: "PancakeSwap V3".to_string(),
+            dex_version: DexVersion::V3,
+            factory_address: Address::random(),
+            block_number: 1,
+            transaction_hash: String::new(),
+            token0_symbol: None,
+            token1_symbol: None,
+            token0_decimals: None,
+            token1_decimals: None,
+            liquidity_usd: None,
+            reserve0: None,
+            reserve1: None,
+            fee: pool_created_fee(&log),
+        };
+        assert_eq!(pair.fee, Some(500));
+        assert_eq!(PoolMeta::from_pair(&pair, None).fee, Some(500));
+        assert_eq!(PoolMeta::from_pair(&pair, Some(100)).fee, Some(100), "the on-chain fee() wins");
+        assert_eq!(pool_created_fee(&Log { topics: vec![H256::random(); 3], ..Default::default() }), None);
+
+        let quote = |fee: u32| {
+            let market = MarketBuilder::new().pool(pool, pool_v3(a, b, 1.0, units(1_000_000), fee)).build();
+            let amounts = quote_exact_in(
+                &market.route(&[a, b], &[pool]),
+                units(1),
+                &market.reserve_cache,
+                &market.token_index,
+                &market.token_tax_map,
+                &market.config,
+            );
+            *amounts.unwrap().last().unwrap()
+        };
+        let (at_500, at_2500) = (quote(pair.fee.unwrap()), quote(2500));
+        // 0.05% fee keeps about 0.9995 of a unit; the old hardcoded 0.25% kept about 0.9975
+        assert!(at_500 > at_2500);
+        assert!(at_500 > units(1) * 9_990 / 10_000 && at_500 < units(1), "{}", at_500);
+        assert!(at_2500 < units(1) * 9_980 / 10_000, "{}", at_2500);
+    }
+    
+    #[tokio::test]
+    async fn test_interrupted_fetch_resumes_from_checkpoint() {
+        use ethers::providers::Provider;
+        use ethers::types::{Bytes, U64};
+
+        let dir = std::env::temp_dir().join(format!("pair_fetch_resume_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let base = Address::from_low_u64_be(0xba5e);
+        std::fs::write(dir.join("safe_tokens.json"), format!(r#"[{{"address": "{:?}"}}]"#, base)).unwrap();
+        let dex = DexConfig { name: "TestDEX".to_string(), factory_address: Address::from_low_u64_be(0xfac), fee: 25, version: DexVersion::V2 };
+        let config = Config { dexes: vec![dex.clone()], ..Config::default() };
+        // PairCreated(base, token, pair, allPairsLength) at `block`
+        let pair_created = |pair: u64, block: u64| {
+            let mut data = vec![0u8; 64];
+            data[12..32].copy_from_slice(Address::from_low_u64_be(pair).as_bytes());
+            Log {
+                topics: vec![H256::random(), H256::from(base), H256::from(Address::from_low_u64_be(pair + 1))],
+                data: Bytes::from(data),
+                block_number: Some(U64::from(block)),
+                transaction_hash: Some(H256::random()),
+                ..Default::default()
+            }
+        };
+        // V2 scans start at block 1_000_000 in 50_000-block batches: three batches up to this head
+        let head = U64::from(1_120_000u64);
+
+        // Run 1: the second batch fails (the mock has no response left for it)
+        let (provider, mock) = Provider::mocked();
+        mock.push(vec![pair_created(0xa, 1_000_100)]).unwrap();
+        mock.push(head).unwrap();
+        let fetcher = PairFetcher::with_data_dir(config.clone(), &dir);
+        let written = fetcher.start_run(FetchStart::New).unwrap();
+        assert!(fetcher.fetch_factory_pairs(&dex, &provider, &written).await.is_err());
+
+        // Run 2 in a new process: resumes at the second batch, which also repeats pair A, as
+        // after a crash between writing a pair and saving the checkpoint
+        let (provider, mock) = Provider::mocked();
+        mock.push(Vec::<Log>::new()).unwrap();
+        mock.push(vec![pair_created(0xa, 1_000_100), pair_created(0xb, 1_060_000)]).unwrap();
+        mock.push(head).unwrap();
+        let fetcher = PairFetcher::with_data_dir(config, &dir);
+        let written = fetcher.start_run(FetchStart::Resume).unwrap();
+        assert_eq!(written.len(), 1);
+        assert_eq!(fetcher.get_or_create_progress(dex.factory_address, &dex.name).last_scanned_block, 1_049_999);
+        fetcher.fetch_factory_pairs(&dex, &provider, &written).await.unwrap();
+
+        let (pairs, _) = load_pairs_from_files(&[dir.join("pairs_v2.jsonl")]);
+        let addresses: Vec<Address> = pairs.iter().map(|p| p.pair_address).collect();
+        assert_eq!(addresses, [Address::from_low_u64_be(0xa), Address::from_low_u64_be(0xb)]);
+        let progress = fetcher.get_or_create_progress(dex.factory_address, &dex.name);
+        assert_eq!((progress.last_scanned_block, progress.total_pairs), (1_120_000, 2));
+
+        // A plain rerun won't wipe those pairs; only --fresh does
+        let fetcher = PairFetcher::with_data_dir(Config { dexes: vec![dex.clone()], ..Config::default() }, &dir);
+        assert!(fetcher.start_run(FetchStart::New).is_err());
+        assert_eq!(load_pairs_from_files(&[dir.join("pairs_v2.jsonl")]).0.len(), 2);
+        assert!(fetcher.start_run(FetchStart::Fresh).unwrap().is_empty());
+        assert_eq!(load_pairs_from_files(&[dir.join("pairs_v2.jsonl")]).0.len(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
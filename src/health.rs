@@ -0,0 +1,141 @@
+use ethers::providers::{Http, Middleware, Provider, Ws};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{H160, U256};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::{self, ReserveCache};
+use crate::config::Config;
+use crate::failover_provider::FailoverProvider;
+use crate::fetch_pairs::load_pairs_from_files;
+
+/// Minimum share of pairs that must preload into the reserve cache
+const MIN_CACHE_LOAD_RATIO: f64 = 0.8;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct CheckResult {
+    name: &'static str,
+    passed: bool,
+    detail: String,
+}
+
+impl CheckResult {
+    fn new(name: &'static str, passed: bool, detail: impl Into<String>) -> Self {
+        Self { name, passed, detail: detail.into() }
+    }
+}
+
+/// Run all pre-flight checks, print a pass/fail table and return true if everything passed
+pub async fn run_health_check(config: &Config) -> bool {
+    let mut results = Vec::new();
+
+    // --- HTTP endpoints (each checked on its own) ---
+    for url in config.http_endpoints() {
+        match Provider::<Http>::try_from(url.as_str()) {
+            Ok(p) => match tokio::time::timeout(CONNECT_TIMEOUT, p.get_block_number()).await {
+                Ok(Ok(block)) => results.push(CheckResult::new("HTTP RPC", true, format!("{}: block {}", url, block))),
+                Ok(Err(e)) => results.push(CheckResult::new("HTTP RPC", false, format!("{}: {}", url, e))),
+                Err(_) => results.push(CheckResult::new("HTTP RPC", false, format!("{}: timeout", url))),
+            },
+            Err(e) => results.push(CheckResult::new("HTTP RPC", false, format!("{}: {}", url, e))),
+        }
+    }
+    // Remaining checks go through the failover provider, like the bot itself
+    let provider = FailoverProvider::connect(&config.http_endpoints()).ok().map(Arc::new);
+
+    // --- WS endpoints ---
+    for url in config.ws_endpoints() {
+        match tokio::time::timeout(CONNECT_TIMEOUT, Provider::<Ws>::connect(&url)).await {
+            Ok(Ok(ws)) => match ws.get_block_number().await {
+                Ok(block) => results.push(CheckResult::new("WS RPC", true, format!("{}: block {}", url, block))),
+                Err(e) => results.push(CheckResult::new("WS RPC", false, format!("{}: {}", url, e))),
+            },
+            Ok(Err(e)) => results.push(CheckResult::new("WS RPC", false, format!("{}: {}", url, e))),
+            Err(_) => results.push(CheckResult::new("WS RPC", false, format!("{}: timeout", url))),
+        }
+    }
+
+    // --- Executor contract code ---
+    match std::env::var("CONTRACT_ADDRESS").ok().and_then(|a| H160::from_str(&a).ok()) {
+        Some(contract) => match &provider {
+            Some(provider) => match provider.get_code(contract, None).await {
+                Ok(code) if !code.is_empty() => {
+                    results.push(CheckResult::new("Contract code", true, format!("{:?} ({} bytes)", contract, code.len())))
+                }
+                Ok(_) => results.push(CheckResult::new("Contract code", false, format!("no code at {:?}", contract))),
+                Err(e) => results.push(CheckResult::new("Contract code", false, e.to_string())),
+            },
+            None => results.push(CheckResult::new("Contract code", false, "no HTTP provider")),
+        },
+        None => results.push(CheckResult::new("Contract code", false, "CONTRACT_ADDRESS missing or invalid")),
+    }
+
+    // --- Wallet gas balance (enough for one tx at configured gas settings) ---
+    match std::env::var("PRIVATE_KEY").ok().and_then(|k| k.parse::<LocalWallet>().ok()) {
+        Some(wallet) => match &provider {
+            Some(provider) => match provider.get_balance(wallet.address(), None).await {
+                Ok(balance) => {
+                    let needed = U256::from(config.gas_limit) * U256::from(config.gas_price);
+                    results.push(CheckResult::new(
+                        "Wallet gas",
+                        balance >= needed,
+                        format!("{:?} balance {} (need {})", wallet.address(), balance, needed),
+                    ));
+                }
+                Err(e) => results.push(CheckResult::new("Wallet gas", false, e.to_string())),
+            },
+            None => results.push(CheckResult::new("Wallet gas", false, "no HTTP provider")),
+        },
+        None => results.push(CheckResult::new("Wallet gas", false, "PRIVATE_KEY missing or invalid")),
+    }
+
+    // --- Pair files ---
+    let (pairs, stats) = load_pairs_from_files(&config.pair_files);
+    results.push(CheckResult::new(
+        "Pair files",
+        !pairs.is_empty() && stats.files_missing.is_empty() && stats.read_errors.is_empty(),
+        format!(
+            "{} pairs, {} parse errors, missing: {:?}, unreadable: {:?}",
+            pairs.len(),
+            stats.parse_errors,
+            stats.files_missing,
+            stats.read_errors.iter().map(|(file, _, _)| file).collect::<Vec<_>>()
+        ),
+    ));
+
+    // --- Reserve cache preload ---
+    match &provider {
+        Some(provider) if !pairs.is_empty() => {
+            let reserve_cache = Arc::new(ReserveCache::default());
+            let summary = cache::preload_reserve_cache(&pairs, provider.clone(), &reserve_cache, &config.preload, config.token_order_check, 0).await;
+            let report = cache::health_report(&reserve_cache, u64::MAX);
+            let ratio = report.total_pools as f64 / pairs.len() as f64;
+            results.push(CheckResult::new(
+                "Reserve cache",
+                ratio >= MIN_CACHE_LOAD_RATIO,
+                format!(
+                    "{}/{} pools ({:.1}%), V2 {}, V3 {}, empty {}, failed {}, timed out {}",
+                    report.total_pools,
+                    pairs.len(),
+                    ratio * 100.0,
+                    report.v2_pools,
+                    report.v3_pools,
+                    report.empty_pools,
+                    summary.failed,
+                    summary.timed_out
+                ),
+            ));
+        }
+        _ => results.push(CheckResult::new("Reserve cache", false, "skipped (no provider or pairs)")),
+    }
+
+    // --- Report ---
+    println!("\n=== HEALTH CHECK ===");
+    for r in &results {
+        println!("  {} {:<15} {}", if r.passed { "✅ PASS" } else { "❌ FAIL" }, r.name, r.detail);
+    }
+    let all_passed = results.iter().all(|r| r.passed);
+    println!("{}", if all_passed { "✅ All checks passed" } else { "❌ Some checks failed" });
+    all_passed
+}
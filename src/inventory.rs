@@ -0,0 +1,149 @@
+use dashmap::DashMap;
+use crate::failover_provider::HttpProvider;
+use ethers::types::{H160, U256};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bindings::IERC20;
+
+/// Balance and in-flight reservations for a single base token
+#[derive(Debug, Clone, Default)]
+pub struct InventoryEntry {
+    pub balance: U256,
+    pub reserved: U256,
+}
+
+/// Tracks available base-token inventory per executor contract, so concurrent executions
+/// don't overcommit the capital any one contract holds
+#[derive(Debug, Default)]
+pub struct InventoryManager {
+    entries: DashMap<(H160, H160), InventoryEntry>, // (holder, token)
+}
+
+impl InventoryManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `holder`'s on-chain balance for a token (reservations are kept)
+    pub fn set_balance(&self, holder: H160, token: H160, balance: U256) {
+        self.entries.entry((holder, token)).or_default().balance = balance;
+    }
+
+    /// `holder`'s balance minus in-flight reservations
+    pub fn available(&self, holder: H160, token: &H160) -> U256 {
+        self.entries
+            .get(&(holder, *token))
+            .map(|e| e.balance.saturating_sub(e.reserved))
+            .unwrap_or_default()
+    }
+
+    /// (holder, token, balance and reservations) for every tracked token
+    pub fn snapshot(&self) -> Vec<(H160, H160, InventoryEntry)> {
+        self.entries.iter().map(|e| (e.key().0, e.key().1, e.value().clone())).collect()
+    }
+
+    /// Reserve `amount` of `holder`'s `token` if enough inventory is available. Returns false otherwise.
+    pub fn try_reserve(&self, holder: H160, token: H160, amount: U256) -> bool {
+        // The entry guard holds the shard lock, so check-and-reserve is atomic
+        let mut entry = self.entries.entry((holder, token)).or_default();
+        if entry.balance.saturating_sub(entry.reserved) < amount {
+            return false;
+        }
+        entry.reserved = entry.reserved.saturating_add(amount);
+        true
+    }
+
+    /// Release a reservation once the execution has completed (success or failure)
+    pub fn release(&self, holder: H160, token: H160, amount: U256) {
+        if let Some(mut entry) = self.entries.get_mut(&(holder, token)) {
+            entry.reserved = entry.reserved.saturating_sub(amount);
+        }
+    }
+
+    /// Fetch `balanceOf(holder)` for every token and holder and update balances
+    pub async fn reconcile(&self, tokens: &[H160], holders: &[H160], provider: Arc<HttpProvider>) {
+        for holder in holders {
+            for token in tokens {
+                let erc20 = IERC20::new(*token, provider.clone());
+                match erc20.balance_of(*holder).call().await {
+                    Ok(balance) => self.set_balance(*holder, *token, balance),
+                    Err(e) => eprintln!("[INVENTORY] Failed to fetch balance of {:?} for {:?}: {}", holder, token, e),
+                }
+            }
+        }
+    }
+
+    /// Periodically reconcile balances with the chain in the background
+    pub fn spawn_reconcile_task(
+        self: Arc<Self>,
+        tokens: Vec<H160>,
+        holders: Vec<H160>,
+        provider: Arc<HttpProvider>,
+        interval: Duration,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                self.reconcile(&tokens, &holders, provider.clone()).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_and_release() {
+        let inventory = InventoryManager::new();
+        let (holder, token) = (H160::repeat_byte(0xc0), H160::repeat_byte(0x01));
+        inventory.set_balance(holder, token, U256::from(100u64));
+
+        assert!(inventory.try_reserve(holder, token, U256::from(60u64)));
+        assert_eq!(inventory.available(holder, &token), U256::from(40u64));
+        assert!(!inventory.try_reserve(holder, token, U256::from(50u64)));
+
+        inventory.release(holder, token, U256::from(60u64));
+        assert_eq!(inventory.available(holder, &token), U256::from(100u64));
+        assert!(!inventory.try_reserve(holder, H160::repeat_byte(0x02), U256::one()));
+    }
+
+    #[test]
+    fn test_inventory_is_kept_per_executor() {
+        let inventory = InventoryManager::new();
+        let (v2_executor, v3_executor, token) = (H160::repeat_byte(0xc0), H160::repeat_byte(0xc1), H160::repeat_byte(0x01));
+        inventory.set_balance(v2_executor, token, U256::from(100u64));
+
+        // The V2 contract's balance can't fund a trade sent to the V3 contract
+        assert!(!inventory.try_reserve(v3_executor, token, U256::from(10u64)));
+        inventory.set_balance(v3_executor, token, U256::from(30u64));
+        assert!(inventory.try_reserve(v3_executor, token, U256::from(30u64)));
+        assert_eq!(inventory.available(v2_executor, &token), U256::from(100u64));
+        assert_eq!(inventory.available(v3_executor, &token), U256::zero());
+    }
+
+    #[test]
+    fn test_concurrent_reservations_never_overcommit() {
+        let inventory = Arc::new(InventoryManager::new());
+        let (holder, token) = (H160::repeat_byte(0xc0), H160::repeat_byte(0x01));
+        inventory.set_balance(holder, token, U256::from(1_000u64));
+
+        // 32 threads each try to reserve 100, only 10 can succeed
+        let handles: Vec<_> = (0..32)
+            .map(|_| {
+                let inventory = inventory.clone();
+                std::thread::spawn(move || inventory.try_reserve(holder, token, U256::from(100u64)))
+            })
+            .collect();
+        let successes = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .filter(|ok| *ok)
+            .count();
+
+        assert_eq!(successes, 10);
+        assert_eq!(inventory.available(holder, &token), U256::zero());
+    }
+}
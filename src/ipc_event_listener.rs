@@ -1,12 +1,13 @@
 use crate::cache::ReserveCache;
+use crate::arbitrage_finder::find_arbitrage_opportunity;
 use crate::config::Config;
-use crate::mempool_decoder::{ArbitrageOpportunity, DecodedSwap};
-// use crate::price_tracker::find_arbitrage_opportunity_from_price_tracker;
+use crate::opportunity::{ArbitrageOpportunity, DecodedSwap};
+use crate::price_oracle::PriceOracle;
 use crate::route_cache::RoutePath;
-use crate::simulate_swap_path::{simulate_buy_path_amounts_array, simulate_sell_path_amounts_array};
-use crate::split_route_path::split_route_around_token_x;
+use crate::log_maintenance::log_path;
 use crate::token_index::TokenIndexMap;
 use crate::token_tax::TokenTaxMap;
+use crate::v2_swap::{classify_sync, SyncChange};
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use ethers::types::H160;
@@ -21,15 +22,15 @@ use std::sync::Arc;
 use std::time::Instant;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::net::UnixStream;
-use rayon::prelude::*;
 use ethers::types::I256;
 /// Spawns a background task that listens for DEX events on /tmp/mempool_decoder.sock and updates ReserveCache for SyncV2 events.
 pub async  fn spawn_ipc_event_listener_with_cache(
     reserve_cache: Arc<ReserveCache>,
     token_index: Arc<TokenIndexMap>,
-    precomputed_route_cache: Arc<DashMap<u32, Vec<RoutePath>>>,
+    precomputed_route_cache: Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     token_tax_map: Arc<TokenTaxMap>,
-    config: Config,
+    config: Arc<Config>,
+    price_oracle: Arc<PriceOracle>,
     opportunity_tx: tokio::sync::mpsc::Sender<ArbitrageOpportunity>,
 ) {
     tokio::spawn(async move {
@@ -62,6 +63,7 @@ pub async  fn spawn_ipc_event_listener_with_cache(
                                                     &precomputed_route_cache,
                                                     &token_tax_map,
                                                     &config,
+                                                    &price_oracle,
                                                     &opportunity_tx,
                                                     event.clone(),
                                                 ).await {
@@ -97,6 +99,7 @@ pub async  fn spawn_ipc_event_listener_with_cache(
                                                     &precomputed_route_cache,
                                                     &token_tax_map,
                                                     &config,
+                                                    &price_oracle,
                                                     &opportunity_tx,
                                                     tx_hash,
                                                     event
@@ -143,9 +146,10 @@ async fn update_reserve_cache_sync_v2(
     new_reserve1: U256,
     tx_hash: H256,
     token_index: &Arc<TokenIndexMap>,
-    precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     token_tax_map: &Arc<TokenTaxMap>,
-    config: &Config,
+    config: &Arc<Config>,
+    price_oracle: &Arc<PriceOracle>,
     opportunity_tx: &tokio::sync::mpsc::Sender<ArbitrageOpportunity>,
     event: serde_json::Value
 )-> anyhow::Result<()>  {
@@ -170,22 +174,14 @@ async fn update_reserve_cache_sync_v2(
     //     state.last_updated = chrono::Utc::now().timestamp() as u64;
     // }
     // println!("      [hash====================================== UPDATE] : {:?}", tx_hash);
-    let (token_x, token_x_amount) = if new_reserve0 < old_reserve0 {
-        // token0 bought (reserve0 decreased)
-        if let Some(pool_data) = reserve_cache.get(&pool) {
-            (pool_data.token0, old_reserve0.saturating_sub(new_reserve0))
-        } else {
-            return Ok(());
-        }
-    } else if new_reserve1 < old_reserve1 {
-        // token1 bought (reserve1 decreased)
-        if let Some(pool_data) = reserve_cache.get(&pool) {
-            (pool_data.token1, old_reserve1.saturating_sub(new_reserve1))
-        } else {
-            return Ok(());
-        }
-    } else {
+    let Some((token0, token1)) = reserve_cache.get(&pool).map(|pool_data| (pool_data.token0, pool_data.token1)) else {
         return Ok(());
+    };
+    // Only one reserve down and the other up is a swap; mints, burns and skims aren't chased
+    let (token_x, token_x_amount) = match classify_sync(old_reserve0, old_reserve1, new_reserve0, new_reserve1) {
+        SyncChange::Swap { token0_out: true, amount_out } => (token0, amount_out),
+        SyncChange::Swap { token0_out: false, amount_out } => (token1, amount_out),
+        SyncChange::Liquidity => return Ok(()),
     };
         // Create decoded swap for arbitrage detection
     let decoded_swap = DecodedSwap {
@@ -193,7 +189,7 @@ async fn update_reserve_cache_sync_v2(
         pool_address: pool,
         token_x,
         token_x_amount,
-        block_number: 0,
+        block_number: crate::price_tracker::current_block(), // detection block, for expiry
         timestamp: chrono::Utc::now().timestamp() as u64,
     };
     // println!(
@@ -210,14 +206,14 @@ async fn update_reserve_cache_sync_v2(
     let after_tx;
     let mut tx_hash_str: Option<String> = None;
     // --- Drop the lock before spawning the async task ---
-   if let Some((opportunity, latency_ms)) = find_arbitrage_opportunity_from_price_tracker(
+   if let Some((opportunity, latency_ms)) = find_arbitrage_opportunity(
         &decoded_swap,
         reserve_cache,
         token_index,
         precomputed_route_cache,
         token_tax_map,
         &config,
-        tx_hash,
+        price_oracle,
     )
     .await
     {
@@ -246,10 +242,16 @@ async fn update_reserve_cache_sync_v2(
         // timings.insert("tx_hash".to_string(), serde_json::json!(tx_hash.to_string()));
 
         // Send opportunity for execution
+        let opportunity_id = opportunity.id;
+        println!(
+            "[opp #{}] Detected on pool {:?} (block {}): tokenX {:?}, est. profit {}",
+            opportunity_id, opportunity.decoded_swap.pool_address, opportunity.decoded_swap.block_number,
+            opportunity.decoded_swap.token_x, opportunity.estimated_profit
+        );
         if let Err(e) = opportunity_tx.send(opportunity).await {
             eprintln!(
-                "❌ [Price Tracker] Failed to send arbitrage opportunity: {}",
-                e
+                "❌ [Price Tracker] [opp #{}] Failed to send arbitrage opportunity: {}",
+                opportunity_id, e
             );
         }
         after_tx = t0.elapsed().as_micros();
@@ -266,7 +268,7 @@ async fn update_reserve_cache_sync_v2(
         if let Ok(mut file) = OpenOptions::new()
             .create(true)
             .append(true)
-            .open("latency_breakdown_price_tracker.log")
+            .open(log_path("latency_breakdown_price_tracker.log"))
         {
             if let Err(e) = writeln!(file, "{}", serde_json::to_string(&timings).unwrap()) {
                 eprintln!("❌ [Price Tracker] Failed to write latency log: {}", e);
@@ -304,10 +306,12 @@ fn parse_swap_v3_event(event: &serde_json::Value) -> Option<(H160, U256, U256, i
     let liquidity = U256::from_dec_str(liq_str)
         .or_else(|_| U256::from_str_radix(liq_str.trim_start_matches("0x"), 16))
         .ok()?;
-    let tick = tick_str
-        .parse::<i32>()
-        .or_else(|_| i32::from_str_radix(tick_str.trim_start_matches("0x"), 16))
-        .ok()?;
+    // Decimal, or the raw (sign-extended) hex word; either way it must fit an int24
+    let tick_raw = I256::from_dec_str(tick_str)
+        .map(I256::into_raw)
+        .ok()
+        .or_else(|| U256::from_str_radix(tick_str.trim_start_matches("0x"), 16).ok())?;
+    let tick = crate::v3_swap::decode_int24(&tick_raw).ok()?;
 
     // println!(
     //     "[DEBUG] parse_swap_v3_event: address={:?}, sqrt_price_x96={}, liquidity={}, tick={}",
@@ -329,9 +333,10 @@ fn update_reserve_cache_swap_v3(
     token0: Option<H160>,
     token1: Option<H160>,
     token_index: &Arc<TokenIndexMap>,
-    precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     token_tax_map: &Arc<TokenTaxMap>,
-    config: &Config,
+    config: &Arc<Config>,
+    price_oracle: &Arc<PriceOracle>,
     opportunity_tx: &tokio::sync::mpsc::Sender<ArbitrageOpportunity>,
     tx_hash: H256,
     event: serde_json::Value
@@ -377,12 +382,12 @@ fn update_reserve_cache_swap_v3(
     //             } else {
     //                 (token1, amount1.unsigned_abs().into())
     //             };
-        let decoded_swap = crate::mempool_decoder::DecodedSwap {
+        let decoded_swap = crate::opportunity::DecodedSwap {
             tx_hash: H160::zero(), // V3 events may not have tx_hash directly
             pool_address: pool,
             token_x,
             token_x_amount,
-            block_number: 0,
+            block_number: crate::price_tracker::current_block(), // detection block, for expiry
             timestamp: chrono::Utc::now().timestamp() as u64,
         };
 
@@ -392,6 +397,7 @@ fn update_reserve_cache_swap_v3(
     let precomputed_route_cache = precomputed_route_cache.clone();
     let token_tax_map = token_tax_map.clone();
     let config = config.clone();
+    let price_oracle = price_oracle.clone();
     let opportunity_tx = opportunity_tx.clone();
     let decoded_swap = decoded_swap.clone();
 
@@ -406,14 +412,14 @@ fn update_reserve_cache_swap_v3(
         let mut after_tx;
         let mut tx_hash_str: Option<String> = None;
 
-        if let Some((opportunity, _latency_ms)) = find_arbitrage_opportunity_from_price_tracker(
+        if let Some((opportunity, _latency_ms)) = find_arbitrage_opportunity(
             &decoded_swap,
             &reserve_cache,
             &token_index,
             &precomputed_route_cache,
             &token_tax_map,
             &config,
-            tx_hash,
+            &price_oracle,
         )
         .await
         {
@@ -442,10 +448,16 @@ fn update_reserve_cache_swap_v3(
             // timings.insert("tx_hash".to_string(), serde_json::json!(tx_hash.to_string()));
 
             // Send opportunity for execution
+            let opportunity_id = opportunity.id;
+            println!(
+                "[opp #{}] Detected on pool {:?} (block {}): tokenX {:?}, est. profit {}",
+                opportunity_id, opportunity.decoded_swap.pool_address, opportunity.decoded_swap.block_number,
+                opportunity.decoded_swap.token_x, opportunity.estimated_profit
+            );
             if let Err(e) = opportunity_tx.send(opportunity).await {
                 eprintln!(
-                    "❌ [Price Tracker] Failed to send arbitrage opportunity: {}",
-                    e
+                    "❌ [Price Tracker] [opp #{}] Failed to send arbitrage opportunity: {}",
+                    opportunity_id, e
                 );
             }
             after_tx = t0.elapsed().as_micros();
@@ -462,7 +474,7 @@ fn update_reserve_cache_swap_v3(
             if let Ok(mut file) = OpenOptions::new()
                 .create(true)
                 .append(true)
-                .open("latency_breakdown_price_tracker.log")
+                .open(log_path("latency_breakdown_price_tracker.log"))
             {
                 if let Err(e) = writeln!(file, "{}", serde_json::to_string(&timings).unwrap()) {
                     eprintln!("❌ [Price Tracker] Failed to write latency log: {}", e);
@@ -472,19 +484,75 @@ fn update_reserve_cache_swap_v3(
     });
     // }
 }
+
+/// Run detection on every swap from `swap_rx` (the price tracker's V2 Swap logs) the way the
+/// V3 swap events above are handled, sending what's found to `opportunity_tx`.
+pub fn spawn_swap_detection(
+    mut swap_rx: tokio::sync::mpsc::Receiver<DecodedSwap>,
+    reserve_cache: Arc<ReserveCache>,
+    token_index: Arc<TokenIndexMap>,
+    precomputed_route_cache: Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
+    token_tax_map: Arc<TokenTaxMap>,
+    config: Arc<Config>,
+    price_oracle: Arc<PriceOracle>,
+    opportunity_tx: tokio::sync::mpsc::Sender<ArbitrageOpportunity>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(decoded_swap) = swap_rx.recv().await {
+            let (reserve_cache, token_index, precomputed_route_cache, token_tax_map, config, price_oracle, opportunity_tx) = (
+                reserve_cache.clone(),
+                token_index.clone(),
+                precomputed_route_cache.clone(),
+                token_tax_map.clone(),
+                config.clone(),
+                price_oracle.clone(),
+                opportunity_tx.clone(),
+            );
+            tokio::spawn(async move {
+                let Some((opportunity, _latency_ms)) = find_arbitrage_opportunity(
+                    &decoded_swap,
+                    &reserve_cache,
+                    &token_index,
+                    &precomputed_route_cache,
+                    &token_tax_map,
+                    &config,
+                    &price_oracle,
+                )
+                .await
+                else {
+                    return;
+                };
+                let opportunity_id = opportunity.id;
+                println!(
+                    "[opp #{}] Detected on pool {:?} (block {}): tokenX {:?}, est. profit {}",
+                    opportunity_id, opportunity.decoded_swap.pool_address, opportunity.decoded_swap.block_number,
+                    opportunity.decoded_swap.token_x, opportunity.estimated_profit
+                );
+                if let Err(e) = opportunity_tx.send(opportunity).await {
+                    eprintln!(
+                        "❌ [Price Tracker] [opp #{}] Failed to send arbitrage opportunity: {}",
+                        opportunity_id, e
+                    );
+                }
+            });
+        }
+    })
+}
+
 pub async fn test_arb(
     reserve_cache: &Arc<ReserveCache>,
     token_index: &Arc<TokenIndexMap>,
-    precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     token_tax_map: &Arc<TokenTaxMap>,
-    config: &Config,
+    config: &Arc<Config>,
+    price_oracle: &Arc<PriceOracle>,
 ) {
     let decoded_swap = DecodedSwap {
         tx_hash: H160::zero(),
         pool_address: H160::from_slice(&hex::decode("172fcd41e0913e95784454622d1c3724f546f849").unwrap()),
         token_x: H160::from_slice(&hex::decode("55d398326f99059ff775485246999027b3197955").unwrap()),
         token_x_amount: U256::from_dec_str("63472535314412411159").unwrap(),
-        block_number: 0,
+        block_number: crate::price_tracker::current_block(), // detection block, for expiry
         timestamp: chrono::Utc::now().timestamp() as u64,
     };
       let t0 = Instant::now();
@@ -496,14 +564,14 @@ pub async fn test_arb(
         let mut before_tx;
         let mut after_tx;
         let mut tx_hash_str: Option<String> = None;
-    if let Some((opportunity, latency_ms)) = find_arbitrage_opportunity_from_price_tracker(
+    if let Some((opportunity, latency_ms)) = find_arbitrage_opportunity(
         &decoded_swap,
         reserve_cache,
         token_index,
         precomputed_route_cache,
         token_tax_map,
         config,
-        H256::zero(),
+        price_oracle,
     )
     .await
     {
@@ -553,7 +621,7 @@ pub async fn test_arb(
             if let Ok(mut file) = OpenOptions::new()
                 .create(true)
                 .append(true)
-                .open("latency_breakdown_price_tracker.log")
+                .open(log_path("latency_breakdown_price_tracker.log"))
             {
                 if let Err(e) = writeln!(file, "{}", serde_json::to_string(&timings).unwrap()) {
                     eprintln!("❌ [Price Tracker] Failed to write latency log: {}", e);
@@ -563,249 +631,6 @@ pub async fn test_arb(
         println!("No arbitrage opportunity found.");
     }
 }
-pub async fn find_arbitrage_opportunity_from_price_tracker(
-    decoded_swap: &DecodedSwap,
-    reserve_cache: &Arc<ReserveCache>,
-    token_index: &Arc<TokenIndexMap>,
-    precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
-    token_tax_map: &Arc<TokenTaxMap>,
-    config: &Config,
-    tx_hash: H256
-) -> Option<(ArbitrageOpportunity, u128)> {
-    // Start latency timer
-    let start_time = std::time::Instant::now();
-    // Get token index
-    let token_x_index = token_index.address_to_index.get(&decoded_swap.token_x)?;
-    let token_x_index_u32 = *token_x_index as u32;
-
-    // println!(
-    //     "🔍 [Price Tracker] Finding arbitrage for tokenX (idx {}): {:?}",
-    //     token_x_index, decoded_swap.token_x
-    // );
-
-    // Get all routes that contain this token and the affected pool
-    let candidate_routes = precomputed_route_cache
-        .get(&token_x_index_u32)
-        .map(|entry| entry.value().clone())
-        .unwrap_or_default();
-
-    // println!(
-    //     "📊 [Price Tracker] Found {} candidate routes for tokenX",
-    //     candidate_routes.len()
-    // );
-
-    // Filter routes that contain the affected pool
-    let filtered_routes: Vec<&RoutePath> = candidate_routes
-        .iter()
-        .filter(|route| route.pools.contains(&decoded_swap.pool_address))
-        .collect();
-
-    // println!(
-    //     "🎯 [Price Tracker] {} routes contain the affected pool {:?}",
-    //     filtered_routes.len(),
-    //     decoded_swap.pool_address
-    // );
-
-    if filtered_routes.is_empty() {
-        return None;
-    }
-
-    // Simulate all filtered routes in parallel
-    let simulation_results: Vec<Option<crate::arbitrage_finder::SimulatedRoute>> = filtered_routes
-        .par_iter()
-        .map(|route| {
-            let (buy_path, sell_path) = match split_route_around_token_x(route, token_x_index_u32) {
-                Some(val) => val,
-                None => {
-                    // println!("[DEBUG] split_route_around_token_x failed for route: {:?}", route);
-                    return None;
-                }
-            };
-            // println!(
-            //     "[DEBUG=========================================================] Simulating route: buy_path={:?}, sell_path={:?}",
-            //     buy_path, sell_path
-            // );
-            let buy_amounts = match simulate_buy_path_amounts_array(
-                &buy_path,
-                decoded_swap.token_x_amount,
-                reserve_cache,
-                token_index,
-                token_tax_map,
-                config,
-            ) {
-                Some(val) => val,
-                None => {
-                    // println!("[DEBUG] simulate_buy_path_amounts_array failed for buy_path: {:?}", buy_path);
-                    return None;
-                }
-            };
-            // println!(
-            //     "[DEBUG=========================================================] Buy amounts: {:?}",
-            //     buy_amounts
-            // );
-            let sell_amounts = match simulate_sell_path_amounts_array(
-                &sell_path,
-                decoded_swap.token_x_amount,
-                reserve_cache,
-                token_index,
-                token_tax_map,
-                config,
-            ) {
-                Some(val) => val,
-                None => {
-                    // println!("[DEBUG] simulate_sell_path_amounts_array failed for sell_path: {:?}", sell_path);
-                    return None;
-                }
-            };
-            // println!(
-            //     "[DEBUG=========================================================] Sell amounts: {:?}",
-            //     sell_amounts
-            // );
-            let mut merged_amounts = buy_amounts.clone();
-            merged_amounts.extend_from_slice(&sell_amounts[1..]);
-            // println!(
-            //     "[DEBUG=========================================================] Merged amounts: {:?}",
-            //     merged_amounts
-            // );
-            // Calculate profit and profit percentage
-            if merged_amounts.len() >= 2 {
-                let amount_in: U256 = merged_amounts[0];
-                let amount_out: U256 = *merged_amounts.last().unwrap();
-                let profit: U256 = amount_out.saturating_sub(amount_in);
-
-                // Only consider profitable trades
-                let sell_symbols: Vec<String> = sell_path
-                    .hops
-                    .iter()
-                    .map(|&idx| token_index_to_symbol_from_price_tracker(idx, token_index))
-                    .collect();
-                let price_usd = {
-                    let last_symbol = &sell_symbols[sell_symbols.len() - 1];
-                    if let Ok(addr) = last_symbol.parse::<H160>() {
-                        get_token_usd_value(&addr).unwrap_or(0.0)
-                    } else {
-                        0.0
-                    }
-                };
-                let amount = u256_to_f64_lossy(&profit) / 10_f64.powi(18 as i32);
-                let profit_usd = amount * price_usd;
-                if  profit_usd > 0.02 {
-                    // Calculate profit percentage (profit / amount_in * 100)
-                    let profit_percentage = if amount_in > U256::zero() {
-                        // Convert to f64 for percentage calculation
-                        let profit_f64 = profit.as_u128() as f64;
-                        let amount_in_f64 = amount_in.as_u128() as f64;
-                        (profit_f64 / amount_in_f64) * 100.0
-                    } else {
-                        0.0
-                    };
-                       let mut merged_pools = buy_path.pools.clone();
-                        merged_pools.extend_from_slice(&sell_path.pools);
-                    // println!(
-                    //     "💰 [Price Tracker] Found profitable route: profit={:.6} USD ({}%), amount_in={}, amount_out={}, price_usd={}",
-                    //     profit_usd, profit_percentage, amount_in, amount_out, price_usd
-                    // );
-                    return Some(crate::arbitrage_finder::SimulatedRoute {
-                        merged_amounts,
-                        buy_amounts,
-                        sell_amounts,
-                        buy_symbols: buy_path
-                            .hops
-                            .iter()
-                            .map(|&idx| token_index_to_symbol_from_price_tracker(idx, token_index))
-                            .collect(),
-                        sell_symbols,
-                        merged_pools: merged_pools.clone(),
-                        buy_pools: buy_path.pools.clone(),
-                        sell_pools: sell_path.pools.clone(),
-                        profit,
-                        profit_percentage,
-                        buy_path: buy_path.clone(),
-                        sell_path: sell_path.clone(),
-                    });
-                }
-            }
-            None
-        })
-        .collect();
-
-    // Filter out None results
-    let profitable_routes: Vec<crate::arbitrage_finder::SimulatedRoute> =
-        simulation_results.into_iter().filter_map(|r| r).collect();
-
-    // println!(
-    //     "💰 [Price Tracker] Found {} profitable routes",
-    //     profitable_routes.len()
-    // );
-
-    if profitable_routes.is_empty() {
-        return None;
-    }
-
-    // Find the most profitable route by percentage (better for multiple base tokens)
-    let best_route = profitable_routes
-        .iter()
-        .max_by(|a, b| {
-            a.profit_percentage
-                .partial_cmp(&b.profit_percentage)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        })
-        .cloned();
-
-    let estimated_profit = best_route
-        .as_ref()
-        .map(|r| r.profit)
-        .unwrap_or(U256::zero());
-
-    // End latency timer
-    let latency = start_time.elapsed().as_millis();
-
-    Some((
-        ArbitrageOpportunity {
-            decoded_swap: decoded_swap.clone(),
-            profitable_routes,
-            best_route,
-            estimated_profit,
-        },
-        latency,
-    ))
-}
-fn u256_to_f64_lossy(val: &U256) -> f64 {
-    if val.bits() <= 128 {
-        val.as_u128() as f64
-    } else {
-        val.to_string().parse::<f64>().unwrap_or(f64::MAX)
-    }
-}
-const KNOWN_TOKENS: &[(&str, &str, f64)] = &[
-    ("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c", "BNB", 689.93),
-    ("0x2170Ed0880ac9A755fd29B2688956BD959F933F8", "ETH", 2961.19),
-    (
-        "0x7130d2A12B9BCbFAe4f2634d864A1Ee1Ce3Ead9c",
-        "BTC",
-        117970.0,
-    ),
-    ("0x55d398326f99059fF775485246999027B3197955", "USDT", 1.00),
-    ("0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d", "USDC", 1.00), // Multichain bridge price
-    ("0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56", "BUSD", 1.00),
-    ("0x0E09FaBB73Bd3Ade0a17ECC321fD13a19e81cE82", "CAKE", 2.37),
-];
-
-fn get_token_usd_value(token_address: &H160) -> Option<f64> {
-    let addr_str = format!("0x{:x}", token_address);
-    KNOWN_TOKENS
-        .iter()
-        .find(|(addr, _, _)| addr.to_lowercase() == addr_str.to_lowercase())
-        .map(|(_, _, price)| *price)
-}
-/// Helper to map token index to symbol (price tracker version)
-fn token_index_to_symbol_from_price_tracker(idx: u32, token_index: &TokenIndexMap) -> String {
-    if let Some(addr) = token_index.index_to_address.get(&(idx as u32)) {
-        format!("0x{:x}", addr)
-    } else {
-        format!("token{}", idx)
-    }
-}
 fn log_opportunity_from_price_tracker(
     opportunity: &ArbitrageOpportunity,
     latency_ms: u128,
@@ -839,14 +664,15 @@ fn log_opportunity_from_price_tracker(
     };
 
     let now: DateTime<Utc> = Utc::now();
-    let log_file_path = format!(
-        "logs/arbitrage_opportunities_price_tracker_{}.log",
+    let log_file_path = log_path(&format!(
+        "arbitrage_opportunities_price_tracker_{}.log",
         now.format("%Y%m%d_%H%M%S")
-    );
+    ));
 
     // Create detailed log entry
     let mut log_entry = json!({
         "source": "price_tracker",
+        "opportunity_id": opportunity.id,
         "event":event,
         "timestamp": now.to_rfc3339(),
         "block_number": opportunity.decoded_swap.block_number,
@@ -876,8 +702,8 @@ fn log_opportunity_from_price_tracker(
             "profit_percentage": opportunity.best_route.as_ref().map(|r| r.profit_percentage),
             "buy_path_pools": opportunity.best_route.as_ref().map(|r| r.buy_path.pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
             "sell_path_pools": opportunity.best_route.as_ref().map(|r| r.sell_path.pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
-            "buy_symbols": opportunity.best_route.as_ref().map(|r| r.buy_symbols.clone()),
-            "sell_symbols": opportunity.best_route.as_ref().map(|r| r.sell_symbols.clone()),
+            "buy_symbols": opportunity.best_route.as_ref().map(|r| r.buy_symbols()),
+            "sell_symbols": opportunity.best_route.as_ref().map(|r| r.sell_symbols()),
             "buy_pools": opportunity.best_route.as_ref().map(|r| r.buy_pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
             "sell_pools": opportunity.best_route.as_ref().map(|r| r.sell_pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
             // "sell_test_amounts": opportunity.best_route.as_ref().map(|r| r.sell_test_amounts.iter().map(|a| a.to_string()).collect::<Vec<_>>()),
@@ -942,7 +768,7 @@ fn log_opportunity_from_price_tracker(
     } else {
         eprintln!(
             "❌ [Price Tracker] Failed to open log file: {}",
-            log_file_path
+            log_file_path.display()
         );
     }
 
@@ -985,14 +811,15 @@ fn log_opportunity_from_price_tracker_test(
     };
 
     let now: DateTime<Utc> = Utc::now();
-    let log_file_path = format!(
-        "logs/arbitrage_opportunities_price_tracker_{}.log",
+    let log_file_path = log_path(&format!(
+        "arbitrage_opportunities_price_tracker_{}.log",
         now.format("%Y%m%d_%H%M%S")
-    );
+    ));
 
     // Create detailed log entry
     let mut log_entry = json!({
         "source": "price_tracker",
+        "opportunity_id": opportunity.id,
         // "event":event,
         "timestamp": now.to_rfc3339(),
         "block_number": opportunity.decoded_swap.block_number,
@@ -1022,8 +849,8 @@ fn log_opportunity_from_price_tracker_test(
             "profit_percentage": opportunity.best_route.as_ref().map(|r| r.profit_percentage),
             "buy_path_pools": opportunity.best_route.as_ref().map(|r| r.buy_path.pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
             "sell_path_pools": opportunity.best_route.as_ref().map(|r| r.sell_path.pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
-            "buy_symbols": opportunity.best_route.as_ref().map(|r| r.buy_symbols.clone()),
-            "sell_symbols": opportunity.best_route.as_ref().map(|r| r.sell_symbols.clone()),
+            "buy_symbols": opportunity.best_route.as_ref().map(|r| r.buy_symbols()),
+            "sell_symbols": opportunity.best_route.as_ref().map(|r| r.sell_symbols()),
             "buy_pools": opportunity.best_route.as_ref().map(|r| r.buy_pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
             "sell_pools": opportunity.best_route.as_ref().map(|r| r.sell_pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
             // "sell_test_amounts": opportunity.best_route.as_ref().map(|r| r.sell_test_amounts.iter().map(|a| a.to_string()).collect::<Vec<_>>()),
@@ -1088,7 +915,7 @@ fn log_opportunity_from_price_tracker_test(
     } else {
         eprintln!(
             "❌ [Price Tracker] Failed to open log file: {}",
-            log_file_path
+            log_file_path.display()
         );
     }
 
@@ -1,6 +1,7 @@
 use crate::cache::ReserveCache;
 use crate::config::Config;
-use crate::mempool_decoder::ArbitrageOpportunity;
+use crate::log_maintenance::log_path;
+use crate::opportunity::ArbitrageOpportunity;
 use crate::revm_sim::{RevmSimulator, print_dex_events_from_trace, print_full_call_trace};
 use crate::route_cache::RoutePath;
 use crate::token_index::TokenIndexMap;
@@ -15,6 +16,7 @@ use ethers::types::TxHash;
 use ethers::types::{BlockId, BlockNumber};
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::fs::{File, OpenOptions};
@@ -27,7 +29,7 @@ use tokio_stream::StreamExt;
 const SIM_WORKERS: usize = 32; // Number of parallel simulation workers (tune as needed)
 
 // Helper: Load known routers from txt file (one address per line)
-pub async fn load_known_routers(path: &str) -> anyhow::Result<HashSet<String>> {
+pub async fn load_known_routers(path: &Path) -> anyhow::Result<HashSet<String>> {
     let mut set = HashSet::new();
     if let Ok(file) = File::open(path).await {
         let reader = BufReader::new(file);
@@ -44,7 +46,7 @@ pub async fn load_known_routers(path: &str) -> anyhow::Result<HashSet<String>> {
 
 // Helper: Append new router to txt file (if not already present)
 pub async fn append_known_router(
-    path: &str,
+    path: &Path,
     addr: &str,
     cache: &Mutex<HashSet<String>>,
 ) -> anyhow::Result<()> {
@@ -68,7 +70,7 @@ pub async fn listen_and_fetch_details(
     dbprovider: Arc<DynProvider>,
     reserve_cache: &Arc<ReserveCache>,
     token_index: &Arc<TokenIndexMap>,
-    precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     token_tax_map: &Arc<TokenTaxMap>,
     config: &Config,
     opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
@@ -79,8 +81,8 @@ pub async fn listen_and_fetch_details(
     let provider = Provider::new(ipc);
 
     // Known routers cache setup
-    let known_router_path = "data/known_routers.txt";
-    let known_router_cache = Arc::new(Mutex::new(load_known_routers(known_router_path).await?));
+    let known_router_path = log_path("known_routers.txt");
+    let known_router_cache = Arc::new(Mutex::new(load_known_routers(&known_router_path).await?));
 
     let (tx, mut rx) = mpsc::channel::<TxHash>(1024);
     let (sim_tx, sim_rx) = mpsc::channel::<(TxHash, ethers::types::Transaction, u64, u64)>(1024);
@@ -0,0 +1,157 @@
+use hdrhistogram::Histogram;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Slowest latency tracked (60s); longer samples are recorded as this
+const MAX_TRACKED_US: u64 = 60_000_000;
+
+/// Measured stage of opportunity detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Search,  // event received -> candidate routes filtered (includes the wait for a blocking thread)
+    Sim,     // route simulation and profit checks
+    Total,   // event received -> detection result
+    Handoff, // event received -> detection running on a blocking thread
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::Search, Stage::Sim, Stage::Total, Stage::Handoff];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Search => "search",
+            Stage::Sim => "sim",
+            Stage::Total => "total",
+            Stage::Handoff => "handoff",
+        }
+    }
+}
+
+/// Percentile summary of one stage, in microseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub count: u64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+    pub p999_us: u64,
+    pub max_us: u64,
+}
+
+/// One histogram per stage, 3 significant digits from 1µs to `MAX_TRACKED_US`
+pub struct LatencyHistograms {
+    stages: [Histogram<u64>; 4],
+}
+
+impl LatencyHistograms {
+    pub fn new() -> Self {
+        let histogram = || Histogram::new_with_bounds(1, MAX_TRACKED_US, 3).expect("valid histogram bounds");
+        Self { stages: [histogram(), histogram(), histogram(), histogram()] }
+    }
+
+    pub fn record(&mut self, stage: Stage, elapsed: Duration) {
+        let us = (elapsed.as_micros() as u64).clamp(1, MAX_TRACKED_US);
+        self.stages[stage as usize].saturating_record(us);
+    }
+
+    pub fn percentiles(&self, stage: Stage) -> Percentiles {
+        let h = &self.stages[stage as usize];
+        if h.is_empty() {
+            return Percentiles::default();
+        }
+        Percentiles {
+            count: h.len(),
+            p50_us: h.value_at_quantile(0.50),
+            p90_us: h.value_at_quantile(0.90),
+            p99_us: h.value_at_quantile(0.99),
+            p999_us: h.value_at_quantile(0.999),
+            max_us: h.max(),
+        }
+    }
+}
+
+impl Default for LatencyHistograms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Detection latency since startup
+static LATENCY: Lazy<Mutex<LatencyHistograms>> = Lazy::new(|| Mutex::new(LatencyHistograms::new()));
+
+/// Record one measurement of `stage`
+pub fn record(stage: Stage, elapsed: Duration) {
+    LATENCY.lock().unwrap_or_else(|e| e.into_inner()).record(stage, elapsed);
+}
+
+/// Percentiles of every stage, keyed by stage name (dashboard `latency` field)
+pub fn snapshot() -> serde_json::Value {
+    let latency = LATENCY.lock().unwrap_or_else(|e| e.into_inner());
+    let mut stages = serde_json::Map::new();
+    for stage in Stage::ALL {
+        stages.insert(stage.name().to_string(), json!(latency.percentiles(stage)));
+    }
+    serde_json::Value::Object(stages)
+}
+
+/// Log p50/p90/p99/p99.9 of every stage each `interval_secs` (0 = never)
+pub fn spawn_latency_reporter(interval_secs: u64) {
+    if interval_secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        interval.tick().await; // first tick fires immediately
+        loop {
+            interval.tick().await;
+            let summary: Vec<(Stage, Percentiles)> = {
+                let latency = LATENCY.lock().unwrap_or_else(|e| e.into_inner());
+                Stage::ALL.iter().map(|stage| (*stage, latency.percentiles(*stage))).collect()
+            };
+            for (stage, p) in summary {
+                if p.count == 0 {
+                    continue;
+                }
+                println!(
+                    "⏱️ [LATENCY] {:<6} n={} p50={}µs p90={}µs p99={}µs p99.9={}µs max={}µs",
+                    stage.name(),
+                    p.count,
+                    p.p50_us,
+                    p.p90_us,
+                    p.p99_us,
+                    p.p999_us,
+                    p.max_us
+                );
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut latency = LatencyHistograms::new();
+        assert_eq!(latency.percentiles(Stage::Sim), Percentiles::default());
+        // 1..=1000µs, one sample each
+        for us in 1..=1000u64 {
+            latency.record(Stage::Sim, Duration::from_micros(us));
+        }
+        latency.record(Stage::Total, Duration::from_secs(3600));
+
+        let sim = latency.percentiles(Stage::Sim);
+        assert_eq!(sim.count, 1000);
+        // 3 significant digits: within 0.1% of the exact rank
+        assert!((499..=501).contains(&sim.p50_us), "p50 {}", sim.p50_us);
+        assert!((989..=991).contains(&sim.p99_us), "p99 {}", sim.p99_us);
+        assert!(sim.p999_us >= sim.p99_us && sim.max_us >= 999);
+        assert_eq!(latency.percentiles(Stage::Search).count, 0);
+        // Out-of-range samples are clamped, not dropped
+        assert_eq!(latency.percentiles(Stage::Total).count, 1);
+    }
+}
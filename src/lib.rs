@@ -0,0 +1,43 @@
+//! Library surface for the simulation hot path so it can be exercised by `benches/`.
+//! The bot binary (`main.rs`) declares its own module tree; keep this list limited to
+//! modules with no dependencies outside it.
+
+pub mod arbitrage_finder;
+pub mod backtest;
+pub mod bindings;
+pub mod cache;
+pub mod config;
+pub mod executor;
+pub mod failover_provider;
+pub mod fetch_pairs;
+pub mod inventory;
+pub mod latency_metrics;
+pub mod log_maintenance;
+pub mod metrics;
+pub mod mirror_log;
+pub mod opportunity;
+pub mod opportunity_queue;
+pub mod price_feed;
+pub mod price_tracker;
+pub mod price_oracle;
+pub mod quote_cache;
+pub mod reorg;
+pub mod route_cache;
+pub mod route_cache_codec;
+pub mod routes_api;
+pub mod schema;
+pub mod sim_trace;
+pub mod simulate_swap_path;
+pub mod split_route_path;
+pub mod stable_math;
+pub mod submitter;
+pub mod token_activity;
+pub mod token_index;
+pub mod token_tax;
+pub mod trade_ledger;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+pub mod utils;
+pub mod v2_swap;
+pub mod v3_math;
+pub mod v3_swap;
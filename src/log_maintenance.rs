@@ -0,0 +1,252 @@
+use crate::config::LogPolicy;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use once_cell::sync::OnceCell;
+use std::fs::{self, File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Directory every log file is written to; unset until `init_log_dir`
+static LOG_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Send every `log_path` to `dir`, creating it. Call once at startup.
+pub fn init_log_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let _ = LOG_DIR.set(dir.to_path_buf());
+    Ok(())
+}
+
+/// `name` inside the configured log directory (`logs/` before `init_log_dir`); an absolute
+/// path is kept as given
+pub fn log_path(name: impl AsRef<Path>) -> PathBuf {
+    LOG_DIR.get().map(PathBuf::as_path).unwrap_or(Path::new("logs")).join(name)
+}
+
+/// What one maintenance pass did
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    pub rotated: usize,   // files compressed into archives
+    pub deleted: usize,   // archives removed to get under the cap
+    pub total_bytes: u64, // size of the directory afterwards
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+fn is_temp(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "tmp")
+}
+
+/// Moved aside by `rotate` but not compressed yet
+fn is_staged(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "staged")
+}
+
+/// Regular files directly inside `dir` (no recursion)
+fn files(dir: &Path) -> io::Result<Vec<(PathBuf, Metadata)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if meta.is_file() {
+            files.push((entry.path(), meta));
+        }
+    }
+    Ok(files)
+}
+
+/// Move `path` aside and gzip it to `<name>.<unix secs>.gz`, keeping its modification time so
+/// the cap deletes the oldest contents first. Writers open their file per write in append mode,
+/// so the next write simply starts a fresh one.
+fn rotate(path: &Path, modified: SystemTime, now: SystemTime) -> io::Result<PathBuf> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let stamp = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let staged = path.with_file_name(format!("{}.{}.staged", name, stamp));
+    fs::rename(path, &staged)?;
+    compress(&staged, modified)
+}
+
+/// Gzip a staged `<name>.<unix secs>.staged` file to `<name>.<unix secs>.gz` and remove it. On
+/// failure the partial archive is removed and the staged file kept, so the next pass retries it.
+fn compress(staged: &Path, modified: SystemTime) -> io::Result<PathBuf> {
+    let archive = staged.with_extension("gz");
+    let tmp = staged.with_extension("gz.tmp");
+    if let Err(e) = gzip_to(staged, &tmp, &archive, modified) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    fs::remove_file(staged)?;
+    Ok(archive)
+}
+
+/// Gzip `source` into `tmp`, then rename it to `archive`
+fn gzip_to(source: &Path, tmp: &Path, archive: &Path, modified: SystemTime) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(tmp)?, Compression::default());
+    io::copy(&mut File::open(source)?, &mut encoder)?;
+    let file = encoder.finish()?;
+    file.set_modified(modified)?;
+    fs::rename(tmp, archive)
+}
+
+/// One pass over `policy.dir`: compress live files that are too big or no longer written to,
+/// then delete the oldest archives until the directory fits under the cap. Live files are
+/// never deleted; a file that fails to rotate is logged and retried on the next pass.
+pub fn run_maintenance(policy: &LogPolicy, now: SystemTime) -> io::Result<MaintenanceReport> {
+    let mut report = MaintenanceReport::default();
+    for (path, meta) in files(&policy.dir)? {
+        if is_archive(&path) || is_temp(&path) || meta.len() == 0 {
+            continue;
+        }
+        let modified = meta.modified()?;
+        if is_staged(&path) {
+            match compress(&path, modified) {
+                Ok(_) => report.rotated += 1,
+                Err(e) => eprintln!("❌ [LOGS] Failed to compress {}: {}", path.display(), e),
+            }
+            continue;
+        }
+        let idle = policy.max_age_secs > 0
+            && now.duration_since(modified).unwrap_or_default() >= Duration::from_secs(policy.max_age_secs);
+        let oversized = policy.max_file_bytes > 0 && meta.len() >= policy.max_file_bytes;
+        if !idle && !oversized {
+            continue;
+        }
+        match rotate(&path, modified, now) {
+            Ok(_) => report.rotated += 1,
+            Err(e) => eprintln!("❌ [LOGS] Failed to rotate {}: {}", path.display(), e),
+        }
+    }
+
+    let files = files(&policy.dir)?;
+    report.total_bytes = files.iter().map(|(_, meta)| meta.len()).sum();
+    if policy.total_cap_bytes == 0 || report.total_bytes <= policy.total_cap_bytes {
+        return Ok(report);
+    }
+    let mut archives: Vec<(PathBuf, Metadata)> = files.into_iter().filter(|(path, _)| is_archive(path)).collect();
+    archives.sort_by_key(|(path, meta)| (meta.modified().ok(), path.clone()));
+    for (path, meta) in archives {
+        if report.total_bytes <= policy.total_cap_bytes {
+            break;
+        }
+        fs::remove_file(&path)?;
+        report.total_bytes -= meta.len();
+        report.deleted += 1;
+    }
+    if report.total_bytes > policy.total_cap_bytes {
+        println!(
+            "[LOGS] {} still holds {} MiB of live logs, above the {} MiB cap",
+            policy.dir.display(),
+            report.total_bytes >> 20,
+            policy.total_cap_bytes >> 20
+        );
+    }
+    Ok(report)
+}
+
+/// Run `run_maintenance` now and then every `maintenance_interval_secs` (0 = off)
+pub fn spawn_log_maintenance(policy: LogPolicy) {
+    if policy.maintenance_interval_secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        // The first tick fires immediately: what the last run left behind is compacted at startup
+        let mut interval = tokio::time::interval(Duration::from_secs(policy.maintenance_interval_secs));
+        loop {
+            interval.tick().await;
+            let pass = policy.clone();
+            match tokio::task::spawn_blocking(move || run_maintenance(&pass, SystemTime::now())).await {
+                Ok(Ok(report)) if report.rotated + report.deleted > 0 => println!(
+                    "[LOGS] Rotated {} files, deleted {} archives; {} is {} MiB",
+                    report.rotated,
+                    report.deleted,
+                    policy.dir.display(),
+                    report.total_bytes >> 20
+                ),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("❌ [LOGS] Maintenance of {} failed: {}", policy.dir.display(), e),
+                Err(e) => eprintln!("❌ [LOGS] Maintenance task panicked: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn temp_log_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("log_maintenance_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, bytes: usize, modified: SystemTime) {
+        fs::write(path, "x".repeat(bytes)).unwrap();
+        File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = files(dir).unwrap().into_iter().map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_rotates_big_and_idle_files() {
+        let dir = temp_log_dir("rotate");
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let policy = LogPolicy { dir: dir.clone(), max_file_bytes: 1_000, max_age_secs: 3_600, total_cap_bytes: 0, maintenance_interval_secs: 0 };
+        write(&dir.join("executor.log"), 2_000, now);
+        write(&dir.join("opportunities_1.log"), 10, now - Duration::from_secs(7_200));
+        write(&dir.join("latency.log"), 10, now);
+
+        let report = run_maintenance(&policy, now).unwrap();
+        assert_eq!(report.rotated, 2);
+        assert_eq!(names(&dir), ["executor.log.1700000000.gz", "latency.log", "opportunities_1.log.1700000000.gz"]);
+
+        let mut contents = String::new();
+        GzDecoder::new(File::open(dir.join("executor.log.1700000000.gz")).unwrap()).read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "x".repeat(2_000));
+        // Nothing left to do on the next pass
+        assert_eq!(run_maintenance(&policy, now).unwrap().rotated, 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_staged_file_is_compressed_on_the_next_pass() {
+        let dir = temp_log_dir("staged");
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let policy = LogPolicy { dir: dir.clone(), max_file_bytes: 0, max_age_secs: 0, total_cap_bytes: 0, maintenance_interval_secs: 0 };
+        // Left behind by a rotation whose gzip step failed, with its partial archive
+        write(&dir.join("executor.log.1699999000.staged"), 500, now);
+        write(&dir.join("executor.log.1699999000.gz.tmp"), 10, now);
+
+        assert_eq!(run_maintenance(&policy, now).unwrap().rotated, 1);
+        assert_eq!(names(&dir), ["executor.log.1699999000.gz"]);
+        let mut contents = String::new();
+        GzDecoder::new(File::open(dir.join("executor.log.1699999000.gz")).unwrap()).read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "x".repeat(500));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cap_deletes_oldest_archives_only() {
+        let dir = temp_log_dir("cap");
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let policy = LogPolicy { dir: dir.clone(), max_file_bytes: 0, max_age_secs: 0, total_cap_bytes: 2_500, maintenance_interval_secs: 0 };
+        for (i, age) in [300, 100, 200].into_iter().enumerate() {
+            write(&dir.join(format!("old_{}.log.1.gz", i)), 1_000, now - Duration::from_secs(age));
+        }
+        write(&dir.join("executor.log"), 1_000, now - Duration::from_secs(400));
+
+        let report = run_maintenance(&policy, now).unwrap();
+        assert_eq!((report.deleted, report.total_bytes), (2, 2_000));
+        assert_eq!(names(&dir), ["executor.log", "old_1.log.1.gz"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -11,73 +11,168 @@ mod utils;
 mod split_route_path;
 mod simulate_swap_path;
 mod v3_math;
+mod stable_math;
 mod arbitrage_finder;
 mod executor;
 mod token_tax;
 // mod ipc_feed;
 mod tx_decoder;
 // mod revm_sim;
+mod sim_trace;
 mod ipc_event_listener;
+mod inventory;
+mod trade_ledger;
+mod cli;
+mod backtest;
+mod health;
+mod reorg;
+mod failover_provider;
+mod price_feed;
+mod price_oracle;
+mod v2_swap;
+mod v3_swap;
+mod dashboard;
+mod schema;
+mod opportunity;
+mod opportunity_queue;
+mod latency_metrics;
+mod quote_cache;
+mod mirror_log;
+mod route_cache_codec;
+mod token_activity;
+mod standby;
+mod log_maintenance;
+mod submitter;
+mod metrics;
+mod routes_api;
+#[cfg(test)]
+mod testkit;
 use alloy_provider::{network::Ethereum, DynProvider, ProviderBuilder};
 use ethers::abi::token;
-use ethers::providers::{Provider, Http, Ws};
+use ethers::providers::{Middleware, Provider, Ws};
 use std::sync::Arc;
-use config::Config;
-use fetch_pairs::{PairFetcher, PairInfo};
-use cache::{ReserveCache};
+use config::{Config, ExecutorStrategy, RunMode};
+use failover_provider::FailoverProvider;
+use fetch_pairs::{FetchStart, PairFetcher, PairInfo};
+use cache::{PoolType, ReserveCache};
 // use ethers::providers::{ Http, Ws};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::time::Duration;
 use dashmap::DashMap;
 use std::collections::HashMap;
-use ethers::types::H160;
+use ethers::types::{BlockNumber, H160};
 use once_cell::sync::Lazy;
 use std::sync::RwLock;
 use primitive_types::U256;
 use std::str::FromStr;
-use route_cache::{build_route_cache, PoolMeta, DEXType, RoutePath};
+use route_cache::{add_cross_base_routes, build_route_cache, load_route_cache, route_cache_fingerprint, save_route_cache, PoolMeta, RoutePath};
 use split_route_path::split_route_around_token_x;
 use simulate_swap_path::{simulate_buy_path, simulate_sell_path, simulate_buy_path_amounts_vec, simulate_sell_path_amounts_vec};
 // use arbitrage_finder::{simulate_all_paths_for_token_x, print_simulated_route};
-use mempool_decoder::{start_mempool_monitoring, MempoolDecoder};
+use opportunity::{ArbitrageOpportunity, DecodedSwap};
 use rayon::prelude::*;
-use crate::executor::{BuySellExecutionData, SwapExecutionData, execute_arbitrage_onchain, execute_arbitrage_onchain_legacy, decode_revert_reason};
+use crate::executor::{BuySellExecutionData, SwapExecutionData, NonceAllocator, ensure_allowance, execute_arbitrage_onchain_legacy, AdaptiveSlippage, run_trade, Trade, TradeContext};
 use std::env;
 use ethers::signers::LocalWallet;
 use ethers::signers::Signer;
 use dotenv::dotenv;
-use std::fs::OpenOptions;
-use std::io::Write;
 use crate::token_tax::{load_token_tax_map, TokenTaxMap};
+use crate::inventory::InventoryManager;
+use crate::opportunity_queue::OpportunityQueue;
+use crate::log_maintenance::log_path;
+use clap::Parser;
+use crate::cli::{Command, LogLevel};
 use alloy_provider::Provider as AlloyProviderTrait;
 use tokio::net::UnixStream;
+
+// Per-event simulation allocates heavily; operators can swap the system allocator at build time
+#[cfg(all(feature = "mimalloc", feature = "jemalloc"))]
+compile_error!("features `mimalloc` and `jemalloc` are mutually exclusive");
+
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
     // Start background IPC event listener
     // ipc_event_listener::spawn_ipc_event_listener();
+    let cli = cli::Cli::parse();
+    let command = cli.command.unwrap_or(Command::Run);
+    let log_level = cli.log_level;
     println!("🚀 Starting Ultra-Low Latency Arbitrage Bot...");
-    let config = Config::default();
+    let config = match &cli.config {
+        Some(path) => match Config::from_file(path) {
+            Ok(config) => {
+                println!("⚙️ Loaded config from {}", path);
+                config
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to load config {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => Config::default(),
+    };
+    if let Err(errors) = config.validate() {
+        eprintln!("❌ Invalid configuration ({} problems):", errors.len());
+        for error in &errors {
+            eprintln!("   - {}", error);
+        }
+        std::process::exit(1);
+    }
 
-    // --- Add contract address and wallet initialization ---
-    let contract_address = H160::from_str(&env::var("CONTRACT_ADDRESS").expect("CONTRACT_ADDRESS env var not set")).expect("Invalid contract address");
-    let wallet: LocalWallet = env::var("PRIVATE_KEY")
-        .expect("PRIVATE_KEY env var not set")
-        .parse::<LocalWallet>()
-        .expect("Invalid private key")
-        .with_chain_id(56u64); // BSC mainnet
+    // Every log, the ledger and the mirror decisions live in one directory
+    if let Err(e) = log_maintenance::init_log_dir(&config.logs.dir) {
+        eprintln!("❌ Cannot create log directory {}: {}", config.logs.dir.display(), e);
+        std::process::exit(1);
+    }
+
+    match &command {
+        Command::Backtest { snapshot, decisions } => {
+            let Some(snapshot) = snapshot.as_ref().or(config.state_snapshot.as_ref()) else {
+                eprintln!("❌ backtest needs a state snapshot: pass --snapshot or set `state_snapshot` in the config");
+                std::process::exit(1);
+            };
+            let decisions = decisions.clone().unwrap_or_else(|| log_path(&config.mirror_log_path));
+            if let Err(e) = backtest::run_backtest_from_files(&config, snapshot, &decisions) {
+                eprintln!("❌ Backtest failed: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Command::Health => {
+            if !health::run_health_check(&config).await {
+                std::process::exit(1);
+            }
+            return;
+        }
+        _ => {}
+    }
+    let feed_mode = matches!(command, Command::Feed) || config.mode == RunMode::Feed;
+    let mirror_mode = matches!(command, Command::Mirror) || config.mode == RunMode::Mirror;
+    let standby_mode = matches!(command, Command::Standby) || config.mode == RunMode::Standby;
 
     // Check if we should fetch pairs from factories
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 && args[1] == "--fetch-pairs" {
+    if let Command::FetchPairs { resume, fresh } = command {
         println!("📡 Fetching pairs from DEX factories...");
         let fetcher = PairFetcher::new(config.clone());
-        if let Err(e) = fetcher.fetch_all_pairs().await {
+        let start = match (resume, fresh) {
+            (true, _) => FetchStart::Resume,
+            (_, true) => FetchStart::Fresh,
+            _ => FetchStart::New,
+        };
+        if let Err(e) = fetcher.fetch_all_pairs(start).await {
             eprintln!("❌ Error fetching pairs: {}", e);
             return;
         }
-        println!("✅ Pair fetching completed! You can now run the bot without --fetch-pairs flag.");
+        println!("✅ Pair fetching completed! You can now run the bot with the run subcommand.");
         return;
     }
 
@@ -95,71 +190,142 @@ async fn main() {
     // Test dynamic V2 fee implementation
     simulate_swap_path::test_dynamic_v2_fees();
 
+    // Validate data file paths before loading anything
+    match config.validate_paths() {
+        Ok(missing) if !missing.is_empty() => println!("⚠️ Missing pair files (will be skipped): {:?}", missing),
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("❌ Invalid data file config: {}", e);
+            eprintln!("   Fetch pairs with `cargo run -- fetch-pairs` or fix `pair_files` / `token_tax_file` in your --config file");
+            std::process::exit(1);
+        }
+    }
+
+    // --- Log directory: rotation, compression and the size cap ---
+    log_maintenance::spawn_log_maintenance(config.logs.clone());
+
     // Load pairs from files
-    let mut pairs: Vec<PairInfo> = Vec::new();
-    let mut v3_count = 0;
-    let mut files_found = 0;
-    // 
-    for file_path in ["data/liquid_pairs_v2_accurate_taxed.jsonl", "data/liquid_pairs_v3_new.jsonl"] {
-        if let Ok(file) = File::open(file_path) {
-            files_found += 1;
-            println!("�� Loading pairs from: {}", file_path);
-            let reader = BufReader::new(file);
-            let mut line_count = 0;
-            let mut parse_errors = 0;
-            for line in reader.lines() {
-                line_count += 1;
-                if let Ok(line) = line {
-                    match serde_json::from_str::<PairInfo>(&line) {
-                        Ok(pair) => {
-                            if pair.dex_version == config::DexVersion::V3 {
-                                v3_count += 1;
-                            }
-                            pairs.push(pair);
-                        }
-                        Err(e) => {
-                            parse_errors += 1;
-                            if parse_errors <= 3 {
-                                println!("❌ Parse error on line {}: {}", line_count, e);
-                                println!("   Line content: {}", &line[..std::cmp::min(100, line.len())]);
-                            }
-                        }
-                    }
-                }
-            }
-            println!("   Loaded {} pairs, {} parse errors from {}", pairs.len(), parse_errors, file_path);
+    let (pairs, pair_stats) = fetch_pairs::load_pairs_from_files(&config.pair_files);
+    let v3_count = pair_stats.v3_count;
+    
+    if pairs.is_empty() {
+        eprintln!("❌ No pairs loaded! Checked files: {:?}", config.pair_files);
+        if pair_stats.files_found == 0 {
+            eprintln!("   None of the pair files exist. Fetch pairs first:");
         } else {
-            println!("❌ Could not open file: {}", file_path);
+            eprintln!("   Pair files exist but contain no valid pairs ({} parse errors). Re-fetch them:", pair_stats.parse_errors);
         }
+        eprintln!("   cargo run -- fetch-pairs");
+        eprintln!("   or point `pair_files` in your --config file at an existing dataset");
+        std::process::exit(1);
+    }
+    if !pair_stats.read_errors.is_empty() {
+        eprintln!("❌ Pair files could not be read to the end, refusing to run on a partial pool set:");
+        for (file, line, err) in &pair_stats.read_errors {
+            eprintln!("   {} (after line {}): {}", file, line, err);
+        }
+        std::process::exit(1);
+    }
+    if !pair_stats.files_missing.is_empty() {
+        println!("⚠️ Some pair files are missing, continuing with the rest: {:?}", pair_stats.files_missing);
     }
-    
-    // if files_found == 0 {
-    //     println!("❌ No pair files found! Please fetch pairs first:");
-    //     println!("   cargo run -- --fetch-pairs");
-    //     return;
-    // }
     
     println!("Loaded {} pairs from files ({} V3 pairs).", pairs.len(), v3_count);
 
     // --- Preload token tax info ---
     println!("Preloading token tax info...");
-    let token_tax_map: Arc<TokenTaxMap> = Arc::new(load_token_tax_map("data/token_zero_transfer_tax.jsonl"));
+    let token_tax_map: Arc<TokenTaxMap> = Arc::new(load_token_tax_map(&config.token_tax_file));
     println!("Loaded {} tokens with tax info.", token_tax_map.len());
+    if config.tax_detection {
+        match config.rpc_url.parse() {
+            Ok(url) => {
+                let fork_provider: DynProvider = alloy_provider::Provider::erased(ProviderBuilder::new().connect_http(url));
+                let probe = Arc::new(token_tax::RevmTaxProbe::new(Arc::new(fork_provider), config.tax_probe_router, config.chain_id));
+                let bases = config.base_tokens.iter().map(|b| b.address).collect();
+                token_tax::init_tax_detector(Arc::new(token_tax::TaxDetector::new(probe, token_tax_map.clone(), bases)));
+            }
+            Err(e) => eprintln!("❌ [TAX] Tax detection disabled, bad rpc_url {}: {}", config.rpc_url, e),
+        }
+    }
 
     // Build providers and cache
-    let provider = Arc::new(Provider::<Http>::try_from(&config.rpc_url).expect("provider"));
-    let ws_provider = Arc::new(Provider::<Ws>::connect(&config.ws_url).await.expect("ws provider"));
+    let provider = Arc::new(FailoverProvider::connect(&config.http_endpoints()).expect("provider"));
     let reserve_cache = Arc::new(ReserveCache::default());
     // Preload reserves in parallel
-    println!("Preloading reserves for all pools...");
-    cache::preload_reserve_cache(&pairs, provider.clone(), &reserve_cache, 2000).await;
+    if let Some(snapshot) = &config.state_snapshot {
+        // Offline: the snapshot already holds every pool, stable pools included
+        println!("Preloading reserves from state snapshot {}...", snapshot.display());
+        if let Err(e) = cache::preload_from_snapshot(snapshot, &reserve_cache, config.pinned_block) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    } else {
+        println!("Preloading reserves for all pools...");
+        if config.preload.multicall_batch_size > 0 {
+            cache::preload_reserve_cache_multicall(&pairs, provider.clone(), &reserve_cache, &config.preload, config.token_order_check, config.v3_tick_words).await;
+        } else {
+            cache::preload_reserve_cache(&pairs, provider.clone(), &reserve_cache, &config.preload, config.token_order_check, config.v3_tick_words).await;
+        }
+        if !config.stable_pools.is_empty() {
+            cache::preload_stable_pools(&config.stable_pools, provider.clone(), &reserve_cache).await;
+        }
+    }
     println!("Reserve cache loaded: {} pools", reserve_cache.len());
-    price_tracker::start_price_tracker(
+    // A pinned run must keep the captured state, not have it "corrected" to the chain head
+    if config.pinned_block.is_none() {
+        cache::spawn_consistency_checker(
+            reserve_cache.clone(),
+            provider.clone(),
+            config.consistency_check_interval,
+            config.consistency_sample_size,
+            config.consistency_tolerance_bps,
+        );
+        if !config.stable_pools.is_empty() && config.stable_refresh_interval_secs > 0 {
+            cache::spawn_stable_pool_refresh(
+                reserve_cache.clone(),
+                config.stable_pools.clone(),
+                provider.clone(),
+                config.stable_refresh_interval_secs,
+            );
+        }
+    }
+    if feed_mode {
+        if let Err(e) = price_feed::start_price_feed_server(&config.feed_socket_path, config.event_buffer_size) {
+            eprintln!("❌ Failed to start price feed on {}: {}", config.feed_socket_path.display(), e);
+            std::process::exit(1);
+        }
+    }
+    // V2 Swap logs wait here for detection until the route cache is built; feed mode trades on nothing
+    let (v2_swap_tx, v2_swap_rx) = tokio::sync::mpsc::channel::<DecodedSwap>(1000);
+    let tracker = price_tracker::start_price_tracker(
             // provider.clone(),
-            ws_provider.clone(),
+            config.ws_endpoints(),
             reserve_cache.clone(),
+            config.confirmation_blocks,
+            config.timeouts.clone(),
+            config.retry_policy.clone(),
+            config.ws_reconnect.clone(),
+            (!feed_mode).then_some(v2_swap_tx),
             // token_tax_map.clone(),
         ).await.expect("Failed to start price tracker");
+    // Trading on a cache nothing keeps in sync is worse than stopping
+    tokio::spawn(async move {
+        let reason = match tracker.await {
+            Ok(Ok(())) => "exited".to_string(),
+            Ok(Err(e)) => format!("{:#}", e),
+            Err(e) => format!("panicked: {}", e),
+        };
+        eprintln!("🛑 Price tracker stopped ({}); shutting down", reason);
+        std::process::exit(1);
+    });
+
+    // Feed mode: keep the cache synced and publishing, skip route cache and execution
+    if feed_mode {
+        println!("📡 Running in price feed mode (no route cache, no execution). Ctrl+C to stop.");
+        let _ = tokio::signal::ctrl_c().await;
+        println!("👋 Price feed stopped");
+        return;
+    }
 
 
 
@@ -172,7 +338,7 @@ async fn main() {
     println!("Starting price tracker (WS event listener)...");
     
     // Create channel for arbitrage opportunities from price tracker
-    let (price_tracker_tx, mut price_tracker_rx) = tokio::sync::mpsc::channel::<mempool_decoder::ArbitrageOpportunity>(1000);
+    let (price_tracker_tx, mut price_tracker_rx) = tokio::sync::mpsc::channel::<ArbitrageOpportunity>(1000);
     
     // We'll start the price tracker after building the token index and route cache
     println!("Price tracker will be started after building caches...");
@@ -186,7 +352,7 @@ async fn main() {
 
     // Build token index and token graph
     let token_index_map = TokenIndexMap::build_from_reserve_cache(&reserve_cache);
-    // let token_graph = TokenGraph::build(&reserve_cache, &token_index_map);
+    // let token_graph = TokenGraph::build(&reserve_cache, &token_index_map, &config);
 
 
 
@@ -232,37 +398,17 @@ async fn main() {
     //     println!("No route found for USDT index {}", token_index);
     // }
 
-    // Build all_pools: Vec<PoolMeta> from pairs
-    let all_pools: Vec<PoolMeta> = pairs.iter().map(|pair| {
-        let dex_type = match (pair.dex_name.as_str(), pair.dex_version.clone()) {
-            ("PancakeSwap V2", config::DexVersion::V2) => DEXType::PancakeV2,
-            ("PancakeSwap V3", config::DexVersion::V3) => DEXType::PancakeV3,
-            ("dex V3", config::DexVersion::V3) => DEXType::Other("dex V3".to_string()),
-            ("BiSwap", config::DexVersion::V2) => DEXType::BiSwapV2,
-            ("Uniswap v3", config::DexVersion::V3) => DEXType::BiSwapV3,
-            ("ApeSwap", config::DexVersion::V2) => DEXType::ApeSwapV2,
-            ("ApeSwap", config::DexVersion::V3) => DEXType::ApeSwapV3,
-            ("BakerySwap", config::DexVersion::V2) => DEXType::BakeryV2,
-            ("BakerySwap", config::DexVersion::V3) => DEXType::BakeryV3,
-            ("MDEX", config::DexVersion::V2) => DEXType::Other("MDEX".to_string()),
-            ("SushiSwap BSC", config::DexVersion::V2) => DEXType::SushiV2,
-            ("SushiSwap BSC", config::DexVersion::V3) => DEXType::SushiV3,
-            (other, _) => DEXType::Other(other.to_string()),
-        };
-        let (factory, fee) = if pair.dex_version == config::DexVersion::V3 {
-            (Some(pair.factory_address), Some(2500u32)) // TODO: Use actual fee if available
-        } else {
-            (None, None)
-        };
-        PoolMeta {
-            token0: pair.token0,
-            token1: pair.token1,
-            address: pair.pair_address,
-            dex_type,
-            factory,
-            fee,
+    // Build all_pools: Vec<PoolMeta> from pairs (skipping pools marked inactive at preload)
+    let mut all_pools: Vec<PoolMeta> = pairs.iter()
+        .filter(|pair| reserve_cache.get(&pair.pair_address).map_or(true, |s| !s.inactive))
+        .map(|pair| PoolMeta::from_pair(pair, reserve_cache.get(&pair.pair_address).and_then(|s| s.fee)))
+        .collect();
+    // Stable pools aren't in the pair files; each coin pair routes like a separate pool
+    for entry in reserve_cache.iter().filter(|e| !e.value().inactive) {
+        if let PoolType::StableN { tokens, .. } = &entry.value().pool_type {
+            all_pools.extend(PoolMeta::for_stable_pool(*entry.key(), tokens));
         }
-    }).collect();
+    }
 
     // Debug print: Show all V3 pools with their factory and fee
     // for pool in &all_pools {
@@ -276,8 +422,12 @@ async fn main() {
     // Build all_tokens: H160 -> u32 (use token_index_map.address_to_index, but as u32)
     let all_tokens: std::collections::HashMap<H160, u32> = token_index_map.address_to_index.iter().map(|(k, v)| (*k, *v as u32)).collect();
 
-    // Build base_tokens as Vec<H160>
+    // Build base_tokens as Vec<H160>; only enabled bases get routes
     let base_tokens: Vec<H160> = config.base_tokens.iter().map(|bt| bt.address).collect();
+    let enabled_base_tokens: Vec<H160> = config.enabled_base_tokens().iter().map(|bt| bt.address).collect();
+    if enabled_base_tokens.len() < base_tokens.len() {
+        println!("[INFO] {} of {} base tokens enabled", enabled_base_tokens.len(), base_tokens.len());
+    }
 
     // // --- Precompute token-to-base-token pool mapping for ultra-fast lookup ---
     // use route_cache::build_token_to_base_token_pools;
@@ -302,9 +452,177 @@ async fn main() {
     // }
     // Build the route cache
     let token_tax_info: HashMap<H160, crate::token_tax::TokenTaxInfo> = token_tax_map.iter().map(|entry| (*entry.key(), entry.value().clone())).collect();
-    let precomputed_route_cache = build_route_cache(&all_tokens, &all_pools, &base_tokens, &token_tax_info);
+    let is_routable = |token: &H160| config.is_token_routable(token);
+    let route_fingerprint = route_cache_fingerprint(
+        &all_tokens,
+        &all_pools,
+        &enabled_base_tokens,
+        &token_tax_info,
+        &is_routable,
+        config.max_route_hops,
+        config.cross_base_routes,
+    );
+    let saved_route_cache = config.route_cache_file.as_deref().and_then(|path| match load_route_cache(path, route_fingerprint) {
+        Ok(Some(route_cache)) => {
+            println!("[ROUTES] Loaded route cache from {}", path.display());
+            Some(route_cache)
+        }
+        Ok(None) => {
+            println!("[ROUTES] {} is missing or stale, rebuilding", path.display());
+            None
+        }
+        Err(e) => {
+            eprintln!("⚠️ [ROUTES] Ignoring {}: {}", path.display(), e);
+            None
+        }
+    });
+    let precomputed_route_cache = match saved_route_cache {
+        Some(route_cache) => route_cache,
+        None => {
+            let route_cache = build_route_cache(&all_tokens, &all_pools, &enabled_base_tokens, &token_tax_info, &is_routable, config.max_route_hops);
+            if config.cross_base_routes {
+                add_cross_base_routes(&route_cache, &all_tokens, &all_pools, &enabled_base_tokens, &token_tax_info, &is_routable);
+            }
+            if let Some(path) = &config.route_cache_file {
+                match save_route_cache(&route_cache, path, route_fingerprint, config.route_cache_format) {
+                    Ok(()) => println!("[ROUTES] Saved route cache to {}", path.display()),
+                    Err(e) => eprintln!("⚠️ [ROUTES] Failed to save {}: {}", path.display(), e),
+                }
+            }
+            route_cache
+        }
+    };
     println!("Precomputed route cache built: {} tokens with paths", precomputed_route_cache.len());
 
+    // --- Ad-hoc simulation mode: simulate routes for one token and exit ---
+    if let Command::Simulate { token: token_address, amount, pool } = command {
+        let token_x_amount = amount.unwrap_or_else(|| U256::exp10(18));
+        let pools: Vec<H160> = match pool {
+            Some(pool) => vec![pool],
+            None => {
+                let mut pools: Vec<H160> = all_tokens.get(&token_address)
+                    .and_then(|idx| precomputed_route_cache.get(idx))
+                    .map(|paths| paths.iter().flat_map(|p| p.pools.clone()).collect())
+                    .unwrap_or_default();
+                pools.sort();
+                pools.dedup();
+                pools
+            }
+        };
+        println!("🧪 Simulating {} pools for token {:?} with amount {}", pools.len(), token_address, token_x_amount);
+        for pool in pools {
+            match simulate_swap_path::simulate_all_filtered_routes(
+                token_address,
+                pool,
+                token_x_amount,
+                &all_tokens,
+                &precomputed_route_cache,
+                &reserve_cache,
+                &token_index_map,
+                &token_tax_map,
+                &config,
+            ) {
+                Some(results) => simulate_swap_path::print_comprehensive_results(&results),
+                None => println!("❌ No routes for token {:?} through pool {:?}", token_address, pool),
+            }
+        }
+        return;
+    }
+
+    // --- Add contract address and wallet initialization ---
+    let contract_address = H160::from_str(&env::var("CONTRACT_ADDRESS").expect("CONTRACT_ADDRESS env var not set")).expect("Invalid contract address");
+    let wallet: LocalWallet = env::var("PRIVATE_KEY")
+        .expect("PRIVATE_KEY env var not set")
+        .parse::<LocalWallet>()
+        .expect("Invalid private key")
+        .with_chain_id(56u64); // BSC mainnet
+    let submitter = submitter::build_submitter(&config.submission, provider.clone()).expect("transaction submitter");
+    println!("[EXECUTOR] Submitting transactions via {}", submitter.name());
+
+    // --- Seed base-token inventory held by each executor contract ---
+    let inventory = Arc::new(InventoryManager::new());
+    let inventory_holders = config.inventory_holders(contract_address);
+    inventory.reconcile(&base_tokens, &inventory_holders, provider.clone()).await;
+    for holder in &inventory_holders {
+        for token in &base_tokens {
+            println!("[INVENTORY] {:?} {:?} available: {}", holder, token, inventory.available(*holder, token));
+        }
+    }
+    inventory.clone().spawn_reconcile_task(
+        base_tokens.clone(),
+        inventory_holders.clone(),
+        provider.clone(),
+        Duration::from_secs(config.inventory_reconcile_interval),
+    );
+
+    // --- USD prices for profit floors and gas costs, refreshed from the reserve cache ---
+    // Shared with detection and the execution gates, never a process global
+    let oracle = Arc::new(price_oracle::PriceOracle::new(price_oracle::seed_prices()));
+    let priced_tokens: Vec<H160> = price_oracle::seed_prices().into_keys().chain(base_tokens.iter().copied()).collect();
+    let stables: Vec<H160> = config.base_tokens.iter().filter(|b| b.is_stable).map(|b| b.address).collect();
+    price_oracle::spawn_price_refresh(
+        oracle.clone(),
+        Arc::new(price_oracle::ReserveCachePriceSource::new(reserve_cache.clone(), priced_tokens, stables)),
+        Duration::from_secs(config.price_refresh_interval_secs),
+    );
+    // Gas estimates are charged at the bid sends would make, starting from gas_price
+    oracle.set_gas_price(U256::from(config.gas_price));
+    executor::spawn_gas_price_refresh(
+        oracle.clone(),
+        config.gas_strategy.clone(),
+        provider.clone(),
+        Duration::from_secs(config.gas_refresh_interval_secs),
+    );
+
+    latency_metrics::spawn_latency_reporter(config.latency_report_interval_secs);
+    quote_cache::init_live_quotes(config.quote_cache_size);
+    if config.idle_token_window_secs > 0 {
+        match token_activity::TokenActivity::load(&config.token_activity_file) {
+            Ok(activity) => {
+                let (tracked, idle) = activity.summary(token_activity::unix_now(), config.idle_token_window_secs);
+                println!("[ACTIVITY] Loaded stats for {} tokens ({} idle) from {}", tracked, idle, config.token_activity_file.display());
+                token_activity::init_token_activity(activity);
+                token_activity::spawn_activity_saver(
+                    config.token_activity_file.clone(),
+                    config.token_activity_save_interval_secs,
+                    config.idle_token_window_secs,
+                );
+            }
+            Err(e) => eprintln!("❌ Failed to load {}: {} (idle-token demotion disabled)", config.token_activity_file.display(), e),
+        }
+    }
+
+    // --- Optional read-only dashboard ---
+    if let Some(addr) = config.dashboard_addr {
+        let ctx = dashboard::DashboardContext { reserve_cache: reserve_cache.clone(), inventory: inventory.clone() };
+        if let Err(e) = dashboard::start_dashboard(addr, ctx).await {
+            eprintln!("❌ Failed to start dashboard on {}: {}", addr, e);
+        }
+    }
+    // --- Optional Prometheus endpoint ---
+    if let Some(port) = config.metrics_port {
+        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+        if let Err(e) = metrics::start_metrics_server(addr, reserve_cache.clone()).await {
+            eprintln!("❌ Failed to start metrics endpoint on {}: {}", addr, e);
+        }
+    }
+
+
+    // --- Warm standby: everything runs except execution until promoted ---
+    let standby = Arc::new(standby::StandbyState::new(!standby_mode));
+    if standby_mode {
+        standby::spawn_promotion_signal(standby.clone());
+        if let Some(addr) = config.control_addr {
+            if let Err(e) = standby::start_control_endpoint(addr, standby.clone()).await {
+                eprintln!("❌ Failed to start control endpoint on {}: {}", addr, e);
+            }
+        }
+        println!("[STANDBY] Caches and price tracker run as usual; trades are held until promotion (SIGUSR1 or POST /promote)");
+    }
+    let mut held_in_standby = 0u64;
+    // Every send takes its nonce here, so split legs and overlapping trades never share one
+    let nonces = Arc::new(NonceAllocator::default());
+
     // Print sample for USDT
     // if let Some(usdt) = config.base_tokens.iter().find(|t| t.symbol == "USDT") {
     //     if let Some(usdt_idx) = all_tokens.get(&usdt.address) {
@@ -472,6 +790,20 @@ async fn main() {
     
     let token_index_arc = Arc::new(token_index_map);
     let precomputed_route_cache_arc = Arc::new(precomputed_route_cache);
+
+    // --- Optional route quote API, reading the same caches the bot trades on ---
+    if let Some(addr) = config.routes_api_addr {
+        let ctx = routes_api::RoutesApiContext {
+            reserve_cache: reserve_cache.clone(),
+            token_index: token_index_arc.clone(),
+            route_cache: precomputed_route_cache_arc.clone(),
+            token_tax_map: token_tax_map.clone(),
+            config: Arc::new(config.clone()),
+        };
+        if let Err(e) = routes_api::start_routes_api(addr, ctx).await {
+            eprintln!("❌ Failed to start routes API on {}: {}", addr, e);
+        }
+    }
     
     // Remove the old mempool listener and spawn the new IPC feed listener in the background
     // let http_url = "http://127.0.0.1:8545";
@@ -502,36 +834,82 @@ async fn main() {
     // });
 
     // Start price tracker now that we have all the required data structures
-    ipc_event_listener::test_arb(&reserve_cache, &token_index_arc, &precomputed_route_cache_arc, &token_tax_map, &config).await;
+    // Detection gets one shared copy of the config rather than a clone per event
+    let detection_config = Arc::new(config.clone());
+    ipc_event_listener::test_arb(&reserve_cache, &token_index_arc, &precomputed_route_cache_arc, &token_tax_map, &detection_config, &oracle).await;
     ipc_event_listener::spawn_ipc_event_listener_with_cache(
         reserve_cache.clone(),
         token_index_arc.clone(),
         precomputed_route_cache_arc.clone(),
         token_tax_map.clone(),
-        config.clone(),
+        detection_config.clone(),
+        oracle.clone(),
         price_tracker_tx.clone(),
     ).await;
+    ipc_event_listener::spawn_swap_detection(
+        v2_swap_rx,
+        reserve_cache.clone(),
+        token_index_arc.clone(),
+        precomputed_route_cache_arc.clone(),
+        token_tax_map.clone(),
+        detection_config.clone(),
+        oracle.clone(),
+        price_tracker_tx.clone(),
+    );
    
     
+    // Slippage buffer fed by InsufficientProfit reverts; only gates routes when adaptive_slippage is on
+    let slippage = Arc::new(AdaptiveSlippage::new(
+        config.slippage_bps.max(),
+        config.min_slippage_bps,
+        config.max_adaptive_slippage_bps,
+        config.slippage_step_bps,
+    ));
+
     // Process arbitrage opportunities from both mempool and price tracker
     let mut opportunity_count = 0;
     let mut total_profit = U256::zero();
     
     // Add timeout and heartbeat monitoring
     let mut last_heartbeat = std::time::Instant::now();
-    const HEARTBEAT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300); // 5 minutes
+    let heartbeat_timeout = std::time::Duration::from_secs(config.timeouts.heartbeat_timeout_secs);
+    
+    // Opportunities wait here so the most valuable one is sent first when several arrive together
+    let mut opportunity_queue = OpportunityQueue::new(
+        config.opportunity_queue_size,
+        Duration::from_millis(config.opportunity_max_age_ms),
+    );
+    let mut expired_by_block = 0u64;
+    let in_flight = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let execution_done = Arc::new(tokio::sync::Notify::new());
+    let trade_context = TradeContext {
+        wallet: wallet.clone(),
+        provider: provider.clone(),
+        gas_strategy: config.gas_strategy.clone(),
+        nonces: nonces.clone(),
+        submitter: submitter.clone(),
+        inventory: inventory.clone(),
+        token_index: token_index_arc.clone(),
+        slippage: slippage.clone(),
+        adaptive_slippage: config.adaptive_slippage,
+        dry_run: config.dry_run,
+    };
     
     println!("📡 Listening for arbitrage opportunities in real-time...");
     println!("💡 Press Ctrl+C to stop the bot");
-    println!("🔍 DEBUG: Starting main event loop...");
+    if log_level >= LogLevel::Debug {
+        println!("🔍 DEBUG: Starting main event loop...");
+    }
     
     // Process opportunities from both mempool and price tracker with proper error handling
     loop {
-        println!("🔍 DEBUG: Loop iteration start - checking heartbeat...");
+        if log_level >= LogLevel::Debug {
+            println!("🔍 DEBUG: Loop iteration start - checking heartbeat...");
+        }
         
         // Check for heartbeat timeout
-        if last_heartbeat.elapsed() > HEARTBEAT_TIMEOUT {
-            println!("⚠️ No activity for 5 minutes, checking system health...");
+        if last_heartbeat.elapsed() > heartbeat_timeout {
+            println!("⚠️ No activity for {:?}, checking system health...", heartbeat_timeout);
             last_heartbeat = std::time::Instant::now();
         }
         
@@ -540,7 +918,7 @@ async fn main() {
         tokio::select! {
             // Handle arbitrage opportunities with timeout
             result = tokio::time::timeout(
-                tokio::time::Duration::from_secs(30), 
+                tokio::time::Duration::from_secs(config.timeouts.opportunity_recv_timeout_secs),
                 price_tracker_rx.recv()
             ) => {
                 // handle result (merge logic from both previous arms here)
@@ -549,51 +927,39 @@ async fn main() {
                         last_heartbeat = std::time::Instant::now();
                         opportunity_count += 1;
                         total_profit = total_profit.saturating_add(opportunity.estimated_profit);
-                        if let Some(best_route) = &opportunity.best_route {
-                            println!("\n🏆 BEST ARBITRAGE ROUTE:");
-                            if let Some(swap_data) = BuySellExecutionData::from_simulated_route(
-                                best_route,
-                                &pool_meta_map,
-                                &token_index_arc,
-                            ) {
-                                let contract_address = contract_address;
-                                let wallet = wallet.clone();
-                                let provider = provider.clone();
-                                tokio::spawn(async move {
-                                    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("executor.log") {
-                                        let _ = writeln!(file, "[EXECUTOR CALL] contract_address={:?}, swap_data={:?}", contract_address, swap_data);
-                                    }
-                                    let result = execute_arbitrage_onchain(
-                                        contract_address,
-                                        swap_data,
-                                        wallet,
-                                        provider
-                                    ).await;
-                                    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("executor.log") {
-                                        match &result {
-                                            Ok(tx_hash) => { let _ = writeln!(file, "[EXECUTOR RESULT] Success: tx_hash={:?}", tx_hash); },
-                                            Err(e) => {
-                                                let msg = e.to_string();
-                                                let decoded = if let Some(idx) = msg.find("0x08c379a0") {
-                                                    let hex_data = &msg[idx..].split_whitespace().next().unwrap_or("");
-                                                    decode_revert_reason(hex_data)
-                                                } else { None };
-                                                if let Some(reason) = decoded {
-                                                    let _ = writeln!(file, "[EXECUTOR RESULT] Error: {} | Decoded: {}", msg, reason);
-                                                } else {
-                                                    let _ = writeln!(file, "[EXECUTOR RESULT] Error: {}", msg);
-                                                }
-                                            },
-                                        }
-                                    }
-                                    match result {
-                                        Ok(tx_hash) => println!("[ARBITRAGE EXECUTED] Tx hash: {tx_hash:?}"),
-                                        Err(e) => eprintln!("[ARBITRAGE ERROR] {e}"),
-                                    }
-                                });
-                            } else {
-                                eprintln!("Failed to build BuySellExecutionData for best route");
+                        metrics::record_opportunity(opportunity.profitable_routes.len());
+                        metrics::record_detection_latency(opportunity.detection_latency);
+                        if config.dashboard_addr.is_some() {
+                            dashboard::record_opportunity(&opportunity);
+                        }
+                        println!(
+                            "📨 [opp #{}] Received from detection (block {}), est. profit {}",
+                            opportunity.id, opportunity.decoded_swap.block_number, opportunity.estimated_profit
+                        );
+                        // Mirror mode: log the trade we'd send for comparison and never execute
+                        if mirror_mode {
+                            let record = opportunity.best_route.as_ref().and_then(|route| mirror_log::DecisionRecord::from_route(
+                                opportunity.decoded_swap.block_number,
+                                chrono::Utc::now().timestamp() as u64,
+                                opportunity.decoded_swap.token_x,
+                                route,
+                            ));
+                            if let Some(record) = record {
+                                let mirror_log_path = log_path(&config.mirror_log_path);
+                                if let Err(e) = mirror_log::append_decision(&mirror_log_path, &record) {
+                                    eprintln!("[MIRROR] Failed to write {}: {}", mirror_log_path.display(), e);
+                                }
                             }
+                            continue;
+                        }
+                        // Standby: detection stays warm, nothing is queued for execution
+                        if !standby.is_active() {
+                            held_in_standby += 1;
+                            continue;
+                        }
+                        let priority = opportunity_priority(&opportunity, &config, &token_index_arc, &oracle);
+                        if let Some(dropped) = opportunity_queue.push(opportunity, priority, std::time::Instant::now()) {
+                            println!("📥 [QUEUE] Full ({} queued), dropped the lowest-priority opportunity [opp #{}]", opportunity_queue.len(), dropped.id);
                         }
                     }
                     Ok(None) => {
@@ -605,8 +971,33 @@ async fn main() {
                     }
                 }
             }
+            // Standby promotion: resync what the primary may have changed, then start executing
+            _ = standby.promotion_requested() => {
+                println!("[STANDBY] Promotion requested after {} held opportunities, resyncing nonce and inventory...", held_in_standby);
+                inventory.reconcile(&base_tokens, &inventory_holders, provider.clone()).await;
+                // A dry run sends nothing, so there is no nonce to pick up
+                if config.dry_run {
+                    standby.mark_active();
+                    println!("[STANDBY] Promoted to active (dry run), logging trades from now on");
+                } else {
+                    match provider.get_transaction_count(wallet.address(), Some(BlockNumber::Pending.into())).await {
+                        Ok(nonce) => {
+                            // Sends continue from the primary's last pending nonce
+                            nonces.set(nonce).await;
+                            standby.mark_active();
+                            println!("[STANDBY] Promoted to active: nonce {}, executing from now on", nonce);
+                        }
+                        Err(e) => {
+                            standby.promotion_failed();
+                            eprintln!("[STANDBY] Promotion aborted, could not fetch nonce: {}", e);
+                        }
+                    }
+                }
+            }
+            // An execution finished: a slot may be free for queued opportunities
+            _ = execution_done.notified(), if !opportunity_queue.is_empty() => {}
             // Periodic heartbeat to show the bot is alive
-            _ = tokio::time::sleep(tokio::time::Duration::from_secs(60)) => {
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(config.timeouts.heartbeat_interval_secs)) => {
                 println!("💓 Bot heartbeat - {} opportunities found, {} total profit", opportunity_count, total_profit);
                 last_heartbeat = std::time::Instant::now();
             }
@@ -617,7 +1008,144 @@ async fn main() {
             }
         }
         
-        println!("🔍 DEBUG: Loop iteration end");
+        // Drain the queue highest-value first while execution slots are free
+        while in_flight.load(std::sync::atomic::Ordering::Relaxed) < config.max_concurrent_executions {
+            let Some(opportunity) = opportunity_queue.pop(std::time::Instant::now()) else {
+                break;
+            };
+            let (detected_block, head) = (opportunity.decoded_swap.block_number, price_tracker::current_block());
+            if opportunity_queue::is_stale_at_block(detected_block, head, config.max_opportunity_age_blocks) {
+                expired_by_block += 1;
+                println!(
+                    "⌛ [EXPIRED] [opp #{}] Opportunity from block {} dropped at block {} (max age {} blocks, {} expired so far)",
+                    opportunity.id, detected_block, head, config.max_opportunity_age_blocks, expired_by_block
+                );
+                continue;
+            }
+            if let Some(best_route) = &opportunity.best_route {
+                let opp = opportunity.id;
+                println!("\n🏆 [opp #{}] BEST ARBITRAGE ROUTE:", opp);
+                // --- Optional split of the input across non-overlapping routes ---
+                let routes_to_execute = if config.enable_split_execution {
+                    let plan = arbitrage_finder::plan_split_routes(
+                        &opportunity.profitable_routes,
+                        best_route.token_x_amount(),
+                        config.split_max_routes,
+                        config.split_steps,
+                        &reserve_cache,
+                        &token_index_arc,
+                        &token_tax_map,
+                        &config,
+                    );
+                    if plan.len() > 1 {
+                        let aggregate = plan.iter().fold(U256::zero(), |acc, r| acc.saturating_add(r.profit));
+                        println!("🔀 Splitting across {} routes: aggregate profit {} vs best single {}", plan.len(), aggregate, best_route.profit);
+                        plan
+                    } else {
+                        vec![best_route.clone()]
+                    }
+                } else {
+                    vec![best_route.clone()]
+                };
+                for best_route in &routes_to_execute {
+                    // --- Net profit gate: profit minus gas, both in base token ---
+                    let base_token = best_route.buy_path.hops.first()
+                        .and_then(|idx| token_index_arc.index_to_address.get(idx).copied());
+                    let gas_cost = base_token.and_then(|base| arbitrage_finder::route_gas_cost(
+                        best_route.buy_path.pools.len() + best_route.sell_path.pools.len(),
+                        base,
+                        oracle.gas_price(),
+                        &config,
+                        |t| oracle.get_token_usd_value(t),
+                    ));
+                    // Adaptive mode: only count the profit left after the current slippage buffer
+                    let gated_profit = if config.adaptive_slippage {
+                        let amount_out = best_route.sell_amounts.last().copied().unwrap_or_default();
+                        slippage.worst_case_profit(best_route.profit, amount_out)
+                    } else {
+                        best_route.profit
+                    };
+                    match gas_cost {
+                        Some(gas_cost) if gated_profit > gas_cost => {
+                            println!("⛽ [opp #{}] Net profit after gas: {} (gas cost {})", opp, gated_profit - gas_cost, gas_cost);
+                        }
+                        Some(gas_cost) => {
+                            println!("⛽ [opp #{}] Skipping: profit {} does not cover gas cost {}", opp, gated_profit, gas_cost);
+                            continue;
+                        }
+                        None => {
+                            println!("⛽ [opp #{}] Skipping: could not price gas in base token {:?}", opp, base_token);
+                            continue;
+                        }
+                    }
+                    if let Some(mut swap_data) = BuySellExecutionData::from_simulated_route(
+                        best_route,
+                        &pool_meta_map,
+                        &token_index_arc,
+                        &reserve_cache,
+                        &token_tax_map,
+                        &config,
+                    ) {
+                        swap_data.opportunity_id = opp;
+                        // --- Inventory gate: reserve the buy input in the contract that will trade it ---
+                        // Without enough inventory the route can still go to a flashloan executor
+                        let input_token = swap_data.buy_tokens[0];
+                        let input_amount = swap_data.buy_amounts[0];
+                        let holder = config.executor_contract(swap_data.strategy, contract_address);
+                        let reserved = inventory.try_reserve(holder, input_token, input_amount);
+                        if !reserved {
+                            if !config.executor_contracts.contains_key(&ExecutorStrategy::Flashloan) {
+                                println!("💼 [opp #{}] Skipping: input {} exceeds available inventory {} for {:?} in {:?}", opp, input_amount, inventory.available(holder, &input_token), input_token, holder);
+                                continue;
+                            }
+                            println!("💼 [opp #{}] Input {} exceeds available inventory {} for {:?} in {:?}, using flashloan executor", opp, input_amount, inventory.available(holder, &input_token), input_token, holder);
+                            swap_data.strategy = ExecutorStrategy::Flashloan;
+                        }
+                        let contract_address = config.executor_contract(swap_data.strategy, contract_address);
+                        // --- Allowance: approve the executor for the input token on first use ---
+                        // Awaited here so the approval is mined (and its nonce used) before the trade
+                        // Dry runs send nothing, approvals included
+                        if !config.dry_run {
+                            if let Err(e) = ensure_allowance(input_token, contract_address, input_amount, &wallet, provider.clone(), config.approval_mode, &nonces).await {
+                                eprintln!("[APPROVE] [opp #{}] Skipping trade, could not approve {:?}: {}", opp, input_token, e);
+                                if reserved {
+                                    inventory.release(holder, input_token, input_amount);
+                                }
+                                continue;
+                            }
+                        }
+                        let trade = Trade {
+                            contract_address,
+                            swap_data,
+                            simulated_profit: best_route.profit,
+                            base_token_idx: best_route.buy_path.hops[0],
+                            reserved_in: reserved.then_some(holder),
+                        };
+                        let trade_context = trade_context.clone();
+                        in_flight.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        let in_flight = in_flight.clone();
+                        let execution_done = execution_done.clone();
+                        tokio::spawn(async move {
+                            run_trade(trade_context, trade).await;
+                            in_flight.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                            execution_done.notify_one();
+                        });
+                    } else {
+                        eprintln!("[opp #{}] Failed to build BuySellExecutionData for route", opp);
+                    }
+                }
+            }
+        }
+        
+        if log_level >= LogLevel::Debug {
+            println!("🔍 DEBUG: Loop iteration end");
+        }
+    }
+
+    if let Some(activity) = token_activity::token_activity() {
+        if let Err(e) = activity.save(&config.token_activity_file) {
+            eprintln!("❌ [ACTIVITY] Failed to save {}: {}", config.token_activity_file.display(), e);
+        }
     }
 
     println!("📊 Final Summary:");
@@ -629,8 +1157,40 @@ async fn main() {
     
     // Helpful message for users
     println!("\n💡 TIP: To fetch fresh pairs from DEX factories, run:");
-    println!("   cargo run -- --fetch-pairs");
+    println!("   cargo run -- fetch-pairs");
+    println!("💡 TIP: To simulate routes for one token, run:");
+    println!("   cargo run -- simulate --token <addr> --amount <wei> [--pool <addr>]");
 
     // Example usage of token_basepools
  
 }
+
+/// Queue priority of an opportunity: its best route's profit net of gas, in micro-USD.
+/// Routes that don't cover gas or can't be priced rank at 0.
+fn opportunity_priority(
+    opportunity: &ArbitrageOpportunity,
+    config: &Config,
+    token_index: &token_index::TokenIndexMap,
+    oracle: &price_oracle::PriceOracle,
+) -> u64 {
+    let Some(route) = &opportunity.best_route else {
+        return 0;
+    };
+    let Some(base) = route.buy_path.hops.first().and_then(|idx| token_index.index_to_address.get(idx).copied()) else {
+        return 0;
+    };
+    let (Some(gas_cost), Some(price_usd)) = (
+        arbitrage_finder::route_gas_cost(
+            route.buy_path.pools.len() + route.sell_path.pools.len(),
+            base,
+            oracle.gas_price(),
+            config,
+            |t| oracle.get_token_usd_value(t),
+        ),
+        oracle.get_token_usd_value(&base),
+    ) else {
+        return 0;
+    };
+    let net = route.profit.saturating_sub(gas_cost).min(U256::from(u128::MAX)).as_u128();
+    (net as f64 / 1e18 * price_usd * 1e6) as u64
+}
@@ -0,0 +1,149 @@
+use crate::cache::ReserveCache;
+use axum::extract::State;
+use axum::routing::get;
+use axum::Router;
+use once_cell::sync::Lazy;
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntGauge, Registry, TextEncoder};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Detection latency buckets in seconds, 100µs to 1s
+const LATENCY_BUCKETS: &[f64] = &[0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// Everything the bot exports on `/metrics`
+pub struct Metrics {
+    registry: Registry,
+    pub opportunities_found: IntCounter,
+    pub profitable_routes: IntCounter,
+    pub executions_attempted: IntCounter,
+    pub executions_succeeded: IntCounter,
+    pub executions_reverted: IntCounter, // mined with status 0, or rejected with a revert reason
+    pub reserve_cache_size: IntGauge,    // refreshed on every scrape
+    pub detection_latency: Histogram,    // event received -> detection result (latency_metrics' total stage)
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let counter = |name: &str, help: &str| {
+            let counter = IntCounter::new(name, help).expect("valid counter");
+            registry.register(Box::new(counter.clone())).expect("unique metric name");
+            counter
+        };
+        let opportunities_found = counter("arb_opportunities_found_total", "Profitable opportunities detected");
+        let profitable_routes = counter("arb_profitable_routes_total", "Profitable routes across all detected opportunities");
+        let executions_attempted = counter("arb_executions_attempted_total", "Trades handed to the submitter");
+        let executions_succeeded = counter("arb_executions_succeeded_total", "Trades mined with status 1");
+        let executions_reverted = counter("arb_executions_reverted_total", "Trades that reverted on chain or in the pre-send simulation");
+        let reserve_cache_size = IntGauge::new("arb_reserve_cache_pools", "Pools in the reserve cache").expect("valid gauge");
+        registry.register(Box::new(reserve_cache_size.clone())).expect("unique metric name");
+        let detection_latency = Histogram::with_opts(
+            HistogramOpts::new("arb_detection_latency_seconds", "Swap event received to detection result")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+        )
+        .expect("valid histogram");
+        registry.register(Box::new(detection_latency.clone())).expect("unique metric name");
+        Self {
+            registry,
+            opportunities_found,
+            profitable_routes,
+            executions_attempted,
+            executions_succeeded,
+            executions_reverted,
+            reserve_cache_size,
+            detection_latency,
+        }
+    }
+
+    /// Current values in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            eprintln!("[METRICS] Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Count one detected opportunity and its profitable routes
+pub fn record_opportunity(profitable_routes: usize) {
+    METRICS.opportunities_found.inc();
+    METRICS.profitable_routes.inc_by(profitable_routes as u64);
+}
+
+pub fn record_detection_latency(elapsed: Duration) {
+    METRICS.detection_latency.observe(elapsed.as_secs_f64());
+}
+
+/// Serve `GET /metrics` on `addr`; returns the bound address (useful with port 0)
+pub async fn start_metrics_server(addr: SocketAddr, reserve_cache: Arc<ReserveCache>) -> anyhow::Result<SocketAddr> {
+    let app = Router::new().route("/metrics", get(metrics_handler)).with_state(reserve_cache);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let addr = listener.local_addr()?;
+    println!("📈 [METRICS] Serving Prometheus metrics on http://{}/metrics", addr);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("[METRICS] Server stopped: {}", e);
+        }
+    });
+    Ok(addr)
+}
+
+async fn metrics_handler(State(reserve_cache): State<Arc<ReserveCache>>) -> String {
+    METRICS.reserve_cache_size.set(reserve_cache.len() as i64);
+    METRICS.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::PoolState;
+    use ethers::types::H160;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn scrape(addr: SocketAddr) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        response
+    }
+
+    fn value(body: &str, name: &str) -> f64 {
+        body.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' ')?.trim().parse().ok())
+            .unwrap_or_else(|| panic!("{} missing from:\n{}", name, body))
+    }
+
+    #[tokio::test]
+    async fn test_scrape_reports_opportunities_and_cache_size() {
+        let reserve_cache = Arc::new(ReserveCache::default());
+        reserve_cache.insert(H160::from_low_u64_be(1), PoolState::default());
+        reserve_cache.insert(H160::from_low_u64_be(2), PoolState::default());
+        let addr = start_metrics_server("127.0.0.1:0".parse().unwrap(), reserve_cache).await.unwrap();
+
+        let before = scrape(addr).await;
+        record_opportunity(3);
+        record_detection_latency(Duration::from_micros(700));
+        let after = scrape(addr).await;
+
+        let found = "arb_opportunities_found_total";
+        assert_eq!(value(&after, found), value(&before, found) + 1.0);
+        let routes = "arb_profitable_routes_total";
+        assert_eq!(value(&after, routes), value(&before, routes) + 3.0);
+        assert_eq!(value(&after, "arb_reserve_cache_pools"), 2.0);
+        assert!(value(&after, "arb_detection_latency_seconds_count") >= 1.0);
+        assert!(after.contains("arb_detection_latency_seconds_bucket{le=\"0.001\"}"));
+    }
+}
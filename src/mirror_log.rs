@@ -0,0 +1,96 @@
+use crate::arbitrage_finder::SimulatedRoute;
+use ethers::types::{H160, U256};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// One trade the bot would have sent, normalized for diffing against another bot's output.
+/// Addresses are lowercase `0x…` and amounts `0x…` hex quantities, as in JSON-RPC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub block: u64,
+    pub timestamp: u64,
+    pub token: H160,           // tokenX of the opportunity
+    pub base_token: H160,      // token the route starts and ends in
+    pub pools: Vec<H160>,      // buy pools then sell pools, in swap order
+    pub input: U256,           // base token amount in
+    pub expected_profit: U256, // base token profit before gas
+}
+
+impl DecisionRecord {
+    /// Record for the route that would be executed; None if the route is empty
+    pub fn from_route(block: u64, timestamp: u64, token: H160, route: &SimulatedRoute) -> Option<Self> {
+        Some(Self {
+            block,
+            timestamp,
+            token,
+            base_token: *route.buy_tokens.first()?,
+            pools: route.merged_pools.clone(),
+            input: *route.buy_amounts.first()?,
+            expected_profit: route.profit,
+        })
+    }
+
+    /// Fields two bots should agree on for the same opportunity; the timestamp is left out
+    pub fn comparison_key(&self) -> (u64, H160, &[H160]) {
+        (self.block, self.token, &self.pools)
+    }
+}
+
+/// Append a decision to the JSON lines mirror log
+pub fn append_decision(path: &Path, record: &DecisionRecord) -> std::io::Result<()> {
+    let line = serde_json::to_string(record)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+/// Read all decisions back from a mirror log (malformed lines are skipped)
+pub fn read_decisions(path: &Path) -> std::io::Result<Vec<DecisionRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(reader
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{pool_address, pool_v2, token, units, MarketBuilder};
+
+    #[test]
+    fn test_decision_round_trip() {
+        let (base, x) = (token(1), token(2));
+        let (p1, p2) = (pool_address(1), pool_address(2));
+        let market = MarketBuilder::new()
+            .pool(p1, pool_v2(base, x, units(1_000), units(2_000)))
+            .pool(p2, pool_v2(x, base, units(2_000), units(1_100)))
+            .build();
+        let route = SimulatedRoute::simulate(
+            &market.route(&[base, x], &[p1]),
+            &market.route(&[x, base], &[p2]),
+            units(1),
+            &market.reserve_cache,
+            &market.token_index,
+            &market.token_tax_map,
+            &market.config,
+        )
+        .unwrap();
+        let record = DecisionRecord::from_route(42, 1_700_000_000, x, &route).unwrap();
+        assert_eq!((record.base_token, record.pools.clone()), (base, vec![p1, p2]));
+        assert_eq!(record.comparison_key(), (42, x, &[p1, p2][..]));
+
+        let line = serde_json::to_string(&record).unwrap();
+        assert!(line.contains(&format!("\"token\":\"{:?}\"", x)), "{}", line);
+
+        let path = std::env::temp_dir().join(format!("mirror_log_test_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        append_decision(&path, &record).unwrap();
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"not json\n").unwrap();
+        append_decision(&path, &record).unwrap();
+        assert_eq!(read_decisions(&path).unwrap(), vec![record.clone(), record]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
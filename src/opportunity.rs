@@ -0,0 +1,25 @@
+use crate::arbitrage_finder::SimulatedRoute;
+use ethers::types::{H160, U256};
+use std::time::Duration;
+
+/// A swap (or reserve change) that may have opened an arbitrage through `pool_address`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodedSwap {
+    pub tx_hash: H160,         // zero when the source event doesn't carry one
+    pub pool_address: H160,
+    pub token_x: H160,         // token that left the pool (the one bought)
+    pub token_x_amount: U256,  // how much of it, in wei
+    pub block_number: u64,     // block the swap was detected at, for expiry
+    pub timestamp: u64,
+}
+
+/// Profitable routes found for one `DecodedSwap`, as handed from detection to execution
+#[derive(Debug, Clone)]
+pub struct ArbitrageOpportunity {
+    pub id: u64, // `opportunity_queue::next_opportunity_id`, logged as `[opp #N]`
+    pub decoded_swap: DecodedSwap,
+    pub profitable_routes: Vec<SimulatedRoute>,
+    pub best_route: Option<SimulatedRoute>,
+    pub estimated_profit: U256, // best route's profit, in its base token
+    pub detection_latency: Duration, // swap event to this result, hand-off to the blocking pool included
+}
@@ -0,0 +1,138 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static NEXT_OPPORTUNITY_ID: AtomicU64 = AtomicU64::new(1);
+
+/// ID for a newly detected opportunity, unique within the process. Log lines about the
+/// opportunity (detection, queueing, execution, result) carry it as `[opp #N]`.
+pub fn next_opportunity_id() -> u64 {
+    NEXT_OPPORTUNITY_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Bounded queue handing out the highest-priority item first. Equal priorities leave in
+/// arrival order, items older than `max_age` are discarded instead of returned, and when
+/// the queue is full the lowest-priority item is dropped to make room.
+pub struct OpportunityQueue<T> {
+    entries: BTreeMap<(u64, Reverse<u64>), (Instant, T)>,
+    capacity: usize,
+    max_age: Duration,
+    next_seq: u64,
+    pub dropped: u64, // evicted (or rejected) because the queue was full
+    pub expired: u64, // discarded for exceeding max_age
+}
+
+impl<T> OpportunityQueue<T> {
+    pub fn new(capacity: usize, max_age: Duration) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            capacity: capacity.max(1),
+            max_age,
+            next_seq: 0,
+            dropped: 0,
+            expired: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Queue `item`. Returns the item that lost its place when the queue was full: the
+    /// lowest-priority entry, which is `item` itself if nothing queued ranks below it.
+    pub fn push(&mut self, item: T, priority: u64, now: Instant) -> Option<T> {
+        let key = (priority, Reverse(self.next_seq));
+        self.next_seq += 1;
+        self.entries.insert(key, (now, item));
+        if self.entries.len() <= self.capacity {
+            return None;
+        }
+        self.dropped += 1;
+        self.entries.pop_first().map(|(_, (_, item))| item)
+    }
+
+    /// Highest-priority item that isn't stale; stale items are discarded along the way
+    pub fn pop(&mut self, now: Instant) -> Option<T> {
+        while let Some((_, (enqueued, item))) = self.entries.pop_last() {
+            if now.saturating_duration_since(enqueued) <= self.max_age {
+                return Some(item);
+            }
+            self.expired += 1;
+        }
+        None
+    }
+}
+
+/// An opportunity detected at `detected_block` is stale once the chain is more than
+/// `max_age_blocks` past it: the reserves it was priced on have likely moved. Unknown blocks (0)
+/// are never stale, so a missing head subscription doesn't drop everything.
+pub fn is_stale_at_block(detected_block: u64, current_block: u64, max_age_blocks: u64) -> bool {
+    detected_block != 0 && current_block != 0 && current_block.saturating_sub(detected_block) > max_age_blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opportunity_ids_are_unique() {
+        let ids: Vec<u64> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4).map(|_| scope.spawn(|| (0..250).map(|_| next_opportunity_id()).collect::<Vec<_>>())).collect();
+            handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+        });
+        let unique: std::collections::HashSet<u64> = ids.iter().copied().collect();
+        assert_eq!(unique.len(), ids.len());
+        assert!(!unique.contains(&0), "0 means no opportunity");
+    }
+
+    #[test]
+    fn test_block_expiry() {
+        // Max age 1: fine at N and N+1, stale at N+2
+        assert!(!is_stale_at_block(100, 100, 1));
+        assert!(!is_stale_at_block(100, 101, 1));
+        assert!(is_stale_at_block(100, 102, 1));
+        // Head behind the detection block (another node) or unknown blocks never expire
+        assert!(!is_stale_at_block(100, 99, 0));
+        assert!(!is_stale_at_block(0, 500, 1));
+        assert!(!is_stale_at_block(100, 0, 1));
+    }
+
+    #[test]
+    fn test_pops_by_priority_then_arrival() {
+        let now = Instant::now();
+        let mut queue = OpportunityQueue::new(8, Duration::from_secs(1));
+        queue.push("marginal", 10, now);
+        queue.push("big", 500, now);
+        queue.push("medium-a", 100, now);
+        queue.push("medium-b", 100, now);
+        let order: Vec<_> = std::iter::from_fn(|| queue.pop(now)).collect();
+        assert_eq!(order, vec!["big", "medium-a", "medium-b", "marginal"]);
+    }
+
+    #[test]
+    fn test_full_queue_drops_lowest_and_stale_items_expire() {
+        let start = Instant::now();
+        let mut queue = OpportunityQueue::new(2, Duration::from_millis(100));
+        assert_eq!(queue.push("a", 50, start), None);
+        assert_eq!(queue.push("b", 20, start), None);
+        assert_eq!(queue.push("c", 30, start), Some("b"));
+        // A newcomer that ranks below everything queued is the one turned away
+        assert_eq!(queue.push("d", 1, start), Some("d"));
+        assert_eq!(queue.dropped, 2);
+
+        let later = start + Duration::from_millis(50);
+        assert_eq!(queue.pop(later), Some("a"));
+        queue.push("fresh", 5, later);
+        assert_eq!(queue.len(), 2);
+        // "c" is past max_age by now; only the late arrival survives
+        let much_later = start + Duration::from_millis(120);
+        assert_eq!(queue.pop(much_later), Some("fresh"));
+        assert_eq!(queue.pop(much_later), None);
+        assert_eq!(queue.expired, 1);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::cache::{PoolState, PoolType};
+use crate::schema::SCHEMA_VERSION;
+use ethers::types::H160;
+use once_cell::sync::OnceCell;
+use serde_json::json;
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+use tokio::net::UnixListener;
+use tokio::sync::broadcast;
+
+/// Set once the feed server is running; until then `publish` is a no-op
+static FEED_TX: OnceCell<broadcast::Sender<String>> = OnceCell::new();
+
+/// Publish a pool's post-update state to every connected feed client.
+/// Called by the cache writers (price tracker, reorg refresh) right after they apply a change.
+pub fn publish(pool: H160, state: &PoolState, block: Option<u64>) {
+    let Some(tx) = FEED_TX.get() else { return };
+    if tx.receiver_count() == 0 {
+        return;
+    }
+    let _ = tx.send(pool_update_message(pool, state, block).to_string());
+}
+
+/// One feed message: pool address, type and the fields the update changed
+pub fn pool_update_message(pool: H160, state: &PoolState, block: Option<u64>) -> serde_json::Value {
+    let fields = match &state.pool_type {
+        PoolType::V2 => json!({
+            "reserve0": state.reserve0.map(|r| r.to_string()),
+            "reserve1": state.reserve1.map(|r| r.to_string()),
+        }),
+        PoolType::V3 => json!({
+            "sqrt_price_x96": state.sqrt_price_x96.map(|p| p.to_string()),
+            "liquidity": state.liquidity.map(|l| l.to_string()),
+            "tick": state.tick,
+        }),
+        PoolType::StableN { tokens, balances, amp } => json!({
+            "tokens": tokens.iter().map(|t| format!("{:?}", t)).collect::<Vec<_>>(),
+            "balances": balances.iter().map(|b| b.to_string()).collect::<Vec<_>>(),
+            "amp": amp.to_string(),
+        }),
+    };
+    json!({
+        "schema_version": SCHEMA_VERSION,
+        "pool": format!("{:?}", pool),
+        "pool_type": match state.pool_type {
+            PoolType::V2 => "V2",
+            PoolType::V3 => "V3",
+            PoolType::StableN { .. } => "StableN",
+        },
+        "block": block,
+        "fields": fields,
+        "spot_price": state.spot_price(), // token0 in token1, whole tokens; null for empty and stable pools
+        "timestamp": state.last_updated,
+    })
+}
+
+/// Bind the feed socket and stream newline-delimited JSON pool updates to every client.
+/// Slow clients that fall more than `buffer_size` messages behind skip the missed updates.
+pub fn start_price_feed_server(path: &Path, buffer_size: usize) -> anyhow::Result<()> {
+    // A socket file left over from a previous run would make bind fail
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let (tx, _) = broadcast::channel::<String>(buffer_size.max(1));
+    FEED_TX
+        .set(tx.clone())
+        .map_err(|_| anyhow::anyhow!("price feed server already started"))?;
+    println!("📤 [FEED] Publishing pool updates on {}", path.display());
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    eprintln!("[FEED] Accept failed: {}", e);
+                    continue;
+                }
+            };
+            let mut rx = tx.subscribe();
+            println!("[FEED] Client connected ({} total)", tx.receiver_count());
+            tokio::spawn(async move {
+                loop {
+                    let msg = match rx.recv().await {
+                        Ok(msg) => msg,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            eprintln!("[FEED] Client lagging, skipped {} updates", skipped);
+                            continue;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    if stream.write_all(msg.as_bytes()).await.is_err()
+                        || stream.write_all(b"\n").await.is_err()
+                    {
+                        println!("[FEED] Client disconnected");
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U256;
+
+    #[test]
+    fn test_pool_update_message_fields() {
+        let pool = H160::from_low_u64_be(7);
+        let v2 = PoolState {
+            pool_type: PoolType::V2,
+            reserve0: Some(U256::from(100u64)),
+            reserve1: Some(U256::from(200u64)),
+            ..Default::default()
+        };
+        let msg = pool_update_message(pool, &v2, Some(42));
+        assert_eq!(msg["schema_version"], SCHEMA_VERSION);
+        assert_eq!(msg["pool"], format!("{:?}", pool));
+        assert_eq!(msg["pool_type"], "V2");
+        assert_eq!(msg["block"], 42);
+        assert_eq!(msg["fields"]["reserve0"], "100");
+        assert_eq!(msg["fields"]["reserve1"], "200");
+        assert_eq!(msg["spot_price"], 2.0);
+
+        let v3 = PoolState {
+            pool_type: PoolType::V3,
+            sqrt_price_x96: Some(U256::one() << 96),
+            liquidity: Some(U256::from(5u64)),
+            tick: Some(-10),
+            ..Default::default()
+        };
+        let msg = pool_update_message(pool, &v3, None);
+        assert_eq!(msg["pool_type"], "V3");
+        assert!(msg["block"].is_null());
+        assert_eq!(msg["fields"]["tick"], -10);
+        assert_eq!(msg["fields"]["liquidity"], "5");
+        assert_eq!(msg["spot_price"], 1.0);
+        assert!(msg["fields"].get("reserve0").is_none());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::cache::{PoolType, ReserveCache};
+use crate::simulate_swap_path::u256_to_f64_lossy;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use ethers::types::{H160, U256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Starting prices, used until the first refresh and for tokens no source prices
+const SEED_PRICES: &[(&str, &str, f64)] = &[
+    ("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c", "BNB", 689.93),
+    ("0x2170Ed0880ac9A755fd29B2688956BD959F933F8", "ETH", 2961.19),
+    ("0x7130d2A12B9BCbFAe4f2634d864A1Ee1Ce3Ead9c", "BTC", 117970.0),
+    ("0x55d398326f99059fF775485246999027B3197955", "USDT", 1.00),
+    ("0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d", "USDC", 1.00), // Multichain bridge price
+    ("0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56", "BUSD", 1.00),
+    ("0x0E09FaBB73Bd3Ade0a17ECC321fD13a19e81cE82", "CAKE", 2.37),
+];
+
+/// Stablecoin side a pool needs, in whole coins, before it is trusted to price a token
+const MIN_STABLE_DEPTH: f64 = 10_000.0;
+
+pub fn seed_prices() -> HashMap<H160, f64> {
+    SEED_PRICES.iter().map(|(addr, _, price)| (addr.parse().expect("seed price address"), *price)).collect()
+}
+
+/// Where refreshed USD prices come from. A fetch may price only some tokens; the rest keep
+/// their last-known value.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    async fn fetch(&self) -> anyhow::Result<HashMap<H160, f64>>;
+}
+
+/// Prices each token off its deepest V2 pair against a USD stablecoin in the live reserve
+/// cache, which the event listeners already keep current, so a refresh costs no RPC calls.
+/// Stablecoins themselves are pinned at $1.
+pub struct ReserveCachePriceSource {
+    reserve_cache: Arc<ReserveCache>,
+    tokens: Vec<H160>,
+    stables: Vec<H160>,
+}
+
+impl ReserveCachePriceSource {
+    pub fn new(reserve_cache: Arc<ReserveCache>, tokens: Vec<H160>, stables: Vec<H160>) -> Self {
+        let tokens = tokens.into_iter().filter(|t| !stables.contains(t)).collect();
+        Self { reserve_cache, tokens, stables }
+    }
+
+    /// USD price of every tracked token that has a deep enough stable pair, stables included
+    pub fn prices(&self) -> HashMap<H160, f64> {
+        // token -> (stable-side depth, price) of its deepest stable pair so far
+        let mut best: HashMap<H160, (f64, f64)> = HashMap::new();
+        for entry in self.reserve_cache.iter() {
+            let state = entry.value();
+            if !matches!(state.pool_type, PoolType::V2) || state.inactive {
+                continue;
+            }
+            let (Some(reserve0), Some(reserve1)) = (state.reserve0, state.reserve1) else { continue };
+            let Some(spot) = state.spot_price().filter(|p| p.is_finite() && *p > 0.0) else { continue };
+            let (token, price, stable_reserve, stable_decimals) =
+                if self.tokens.contains(&state.token0) && self.stables.contains(&state.token1) {
+                    (state.token0, spot, reserve1, state.decimals1)
+                } else if self.tokens.contains(&state.token1) && self.stables.contains(&state.token0) {
+                    (state.token1, 1.0 / spot, reserve0, state.decimals0)
+                } else {
+                    continue;
+                };
+            let depth = u256_to_f64_lossy(&stable_reserve) / 10f64.powi(stable_decimals.unwrap_or(18) as i32);
+            if depth < MIN_STABLE_DEPTH {
+                continue;
+            }
+            if best.get(&token).is_none_or(|(d, _)| depth > *d) {
+                best.insert(token, (depth, price));
+            }
+        }
+        let mut prices: HashMap<H160, f64> = best.into_iter().map(|(token, (_, price))| (token, price)).collect();
+        prices.extend(self.stables.iter().map(|s| (*s, 1.0)));
+        prices
+    }
+}
+
+#[async_trait]
+impl PriceSource for ReserveCachePriceSource {
+    async fn fetch(&self) -> anyhow::Result<HashMap<H160, f64>> {
+        Ok(self.prices())
+    }
+}
+
+/// Last-known USD price per token, plus the gas price sends currently bid. Created once at
+/// startup and handed to detection, the listeners and the execution gates as an `Arc`, so
+/// tests can pass their own.
+#[derive(Debug, Default)]
+pub struct PriceOracle {
+    prices: DashMap<H160, f64>,
+    gas_price: AtomicU64, // wei; the resolved `gas_strategy` bid (EIP-1559: the max fee)
+}
+
+impl PriceOracle {
+    pub fn new(seed: HashMap<H160, f64>) -> Self {
+        Self { prices: seed.into_iter().collect(), gas_price: AtomicU64::new(0) }
+    }
+
+    /// Gas price every gas estimate is charged at, in wei
+    pub fn gas_price(&self) -> U256 {
+        U256::from(self.gas_price.load(Ordering::Relaxed))
+    }
+
+    pub fn set_gas_price(&self, gas_price: U256) {
+        self.gas_price.store(gas_price.min(U256::from(u64::MAX)).as_u64(), Ordering::Relaxed);
+    }
+
+    pub fn get_token_usd_value(&self, token: &H160) -> Option<f64> {
+        self.prices.get(token).map(|p| *p)
+    }
+
+    /// Apply one fetch from `source`; returns how many prices were updated. On error every
+    /// price stays at its last-known value. Non-positive or non-finite quotes are ignored.
+    pub async fn refresh(&self, source: &dyn PriceSource) -> anyhow::Result<usize> {
+        let fetched = source.fetch().await?;
+        let mut updated = 0;
+        for (token, price) in fetched {
+            if price.is_finite() && price > 0.0 {
+                self.prices.insert(token, price);
+                updated += 1;
+            }
+        }
+        Ok(updated)
+    }
+}
+
+/// Refresh `oracle` from `source` now and then every `interval` (zero = seed prices only)
+pub fn spawn_price_refresh(oracle: Arc<PriceOracle>, source: Arc<dyn PriceSource>, interval: Duration) {
+    if interval.is_zero() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match oracle.refresh(source.as_ref()).await {
+                Ok(0) => println!("[PRICES] Refresh priced no tokens, keeping last-known prices"),
+                Ok(_) => {}
+                Err(e) => eprintln!("❌ [PRICES] Refresh failed, keeping last-known prices: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::PoolState;
+    use std::sync::Mutex;
+
+    /// Returns the queued responses in order, then keeps failing
+    struct MockSource {
+        responses: Mutex<Vec<anyhow::Result<HashMap<H160, f64>>>>,
+    }
+
+    #[async_trait]
+    impl PriceSource for MockSource {
+        async fn fetch(&self) -> anyhow::Result<HashMap<H160, f64>> {
+            let mut responses = self.responses.lock().unwrap();
+            if responses.is_empty() {
+                anyhow::bail!("source down");
+            }
+            responses.remove(0)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_prices_update_after_interval_and_survive_failures() {
+        let (wbnb, cake) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let oracle = Arc::new(PriceOracle::new(HashMap::from([(wbnb, 689.93), (cake, 2.37)])));
+        let source = Arc::new(MockSource {
+            responses: Mutex::new(vec![
+                Ok(HashMap::from([(wbnb, 600.0)])),
+                Err(anyhow::anyhow!("rpc timeout")),
+                Ok(HashMap::from([(wbnb, 610.0), (cake, f64::NAN)])),
+            ]),
+        });
+        spawn_price_refresh(oracle.clone(), source.clone(), Duration::from_millis(200));
+        // The clock is paused: each sleep runs every refresh due before it wakes, and nothing else
+        let advance = |ms| tokio::time::sleep(Duration::from_millis(ms));
+
+        // First refresh runs right away; the failed one at 200ms keeps it
+        advance(1).await;
+        assert_eq!(oracle.get_token_usd_value(&wbnb), Some(600.0));
+        advance(398).await;
+        assert_eq!(oracle.get_token_usd_value(&wbnb), Some(600.0), "failed fetch keeps the last price");
+        // The next successful one lands at 400ms
+        advance(2).await;
+        assert_eq!(oracle.get_token_usd_value(&wbnb), Some(610.0));
+
+        // Tokens the source skipped or mispriced keep their last value
+        assert_eq!(oracle.get_token_usd_value(&cake), Some(2.37));
+        advance(200).await;
+        assert_eq!(oracle.get_token_usd_value(&wbnb), Some(610.0), "a source that stays down keeps the last price");
+        assert_eq!(oracle.get_token_usd_value(&H160::from_low_u64_be(3)), None);
+    }
+
+    #[test]
+    fn test_reserve_cache_source_uses_deepest_stable_pair() {
+        let (wbnb, usdt, busd) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3));
+        let one = U256::exp10(18);
+        let pair = |token0, token1, reserve0: u64, reserve1: u64| PoolState {
+            pool_type: PoolType::V2,
+            token0,
+            token1,
+            reserve0: Some(one * reserve0),
+            reserve1: Some(one * reserve1),
+            ..PoolState::default()
+        };
+        let cache = ReserveCache::default();
+        cache.insert(H160::from_low_u64_be(10), pair(wbnb, usdt, 1_000, 600_000)); // $600
+        cache.insert(H160::from_low_u64_be(11), pair(busd, wbnb, 1_220_000, 2_000)); // $610, deeper
+        cache.insert(H160::from_low_u64_be(12), pair(wbnb, usdt, 1, 900)); // dust pool, ignored
+
+        let source = ReserveCachePriceSource::new(Arc::new(cache), vec![wbnb, usdt], vec![usdt, busd]);
+        let prices = source.prices();
+        assert!((prices[&wbnb] - 610.0).abs() < 1e-9);
+        assert_eq!((prices[&usdt], prices[&busd]), (1.0, 1.0));
+        assert_eq!(prices.len(), 3);
+    }
+}
@@ -1,57 +1,59 @@
 use crate::bindings::UniswapV3Pool;
-use crate::cache::{PoolType, ReserveCache};
-use crate::mempool_decoder::{ArbitrageOpportunity, DecodedSwap};
-use crate::route_cache::RoutePath;
-use crate::config::Config;
-use crate::simulate_swap_path::{
-    simulate_buy_path_amounts_array, simulate_sell_path_amounts_array,
-};
-use crate::split_route_path::split_route_around_token_x;
-use crate::token_index::TokenIndexMap;
-use crate::token_tax::TokenTaxMap;
+use crate::cache::{bump_reserve_version, PoolState, PoolType, ReserveCache, ReserveCacheExt};
+use crate::opportunity::DecodedSwap;
+use crate::config::{RetryPolicy, Timeouts};
+use crate::failover_provider::{classify_provider_error, ErrorClass};
+use crate::price_feed;
+use crate::reorg::{ReorgTracker, REORG_DEPTH};
+use crate::v2_swap::{decode_v2_swap, V2_SWAP_TOPIC};
+use crate::v3_swap::{decode_v3_swap, record_v3_volume, PANCAKE_V3_SWAP_TOPIC, UNISWAP_V3_SWAP_TOPIC};
 use chrono::{DateTime, Datelike, Timelike, Utc};
 use dashmap::DashMap;
-use ethers::abi::{ParamType, decode};
 use ethers::prelude::*;
-use ethers::types::{H160, H256, I256, Log, U256};
+use ethers::types::{H160, H256, Log, U256};
 use futures::StreamExt;
-use rayon::prelude::*;
 use serde_json::json;
+use std::collections::BTreeMap;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use std::time::Instant;
 
 /// Start the price tracker: subscribe to V2 Sync and V3 Swap events, update ReserveCache in real time.
+/// The returned task only finishes if the WS connection can't be rebuilt within `ws_reconnect`.
+/// V2 Swap logs are decoded and handed to `swap_tx` for arbitrage detection, when one is given.
 pub async fn start_price_tracker(
-    ws_provider: Arc<Provider<Ws>>,
+    ws_urls: Vec<String>,
     // http_provider: Arc<Provider<Http>>,
     reserve_cache: Arc<ReserveCache>,
+    confirmation_blocks: u64,
+    timeouts: Timeouts,
+    retry_policy: RetryPolicy,
+    ws_reconnect: RetryPolicy,
+    swap_tx: Option<mpsc::Sender<DecodedSwap>>,
     // token_index: Arc<TokenIndexMap>,
-    // precomputed_route_cache: Arc<DashMap<u32, Vec<RoutePath>>>,
+    // precomputed_route_cache: Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     // opportunity_tx: mpsc::Sender<ArbitrageOpportunity>,
     // token_tax_map: Arc<TokenTaxMap>,
     // config: Config,
-) -> anyhow::Result<()> {
-    // Collect all V2 and V3 pool addresses from the cache
+) -> anyhow::Result<tokio::task::JoinHandle<anyhow::Result<()>>> {
+    // Collect all V2 and V3 pool addresses from the cache (inactive V3 pools have nothing to track)
     let mut v2_addresses = vec![];
     let mut v3_addresses = vec![];
-    for entry in reserve_cache.iter() {
+    for entry in reserve_cache.active_pools() {
         match entry.value().pool_type {
             PoolType::V2 => v2_addresses.push(*entry.key()),
             PoolType::V3 => v3_addresses.push(*entry.key()),
+            // No event decoding for stable pools; cache::spawn_stable_pool_refresh polls their balances
+            PoolType::StableN { .. } => {}
         }
     }
 
     // Topics
     let v2_sync_topic = H256::from(ethers::utils::keccak256(b"Sync(uint112,uint112)"));
-    let uniswap_v3_swap_topic = H256::from(ethers::utils::keccak256(
-        b"Swap(address,address,int256,int256,uint160,uint128,int24)",
-    ));
-    let pancakeswap_v3_swap_topic = H256::from(ethers::utils::keccak256(
-        b"Swap(address,address,int256,int256,uint160,uint128,int24,uint128,uint128)",
-    ));
+    let uniswap_v3_swap_topic = *UNISWAP_V3_SWAP_TOPIC;
+    let pancakeswap_v3_swap_topic = *PANCAKE_V3_SWAP_TOPIC;
 
     // Deep debug: print topic hash and address info
     // println!("[DEBUG] v3_swap_topic = 0x{:x}", uniswap_v3_swap_topic);
@@ -60,58 +62,14 @@ pub async fn start_price_tracker(
     //     println!("[DEBUG] V3 pool address [{}]: {:?}", i, addr);
     // }
 
-    // V2 Sync subscription with arbitrage detection
+    // Reorg tracking and confirmation buffer outlive any single WS endpoint
+    let reorg_tracker = Arc::new(ReorgTracker::new());
+    let pending_updates = Arc::new(PendingUpdates::new(confirmation_blocks));
+
+    // V2 Sync (reserves) and Swap (trade direction) subscription with arbitrage detection
     let v2_filter = Filter::new()
-        .topic0(v2_sync_topic)
+        .topic0(vec![v2_sync_topic, *V2_SWAP_TOPIC])
         .address(v2_addresses.clone());
-    let reserve_cache_v2 = reserve_cache.clone();
-    // let token_index_v2 = token_index.clone();
-    // let precomputed_route_cache_v2 = precomputed_route_cache.clone();
-    // let opportunity_tx_v2 = opportunity_tx.clone();
-    let ws_provider_v2 = ws_provider.clone();
-    // let token_tax_map_v2 = token_tax_map.clone();
-
-    tokio::spawn(async move {
-        let mut retry_count = 0;
-        const MAX_RETRIES: u32 = 10;
-
-        loop {
-            match run_v2_monitoring_loop(
-                &ws_provider_v2,
-                &v2_filter,
-                &reserve_cache_v2,
-                // &token_index_v2,
-                // &precomputed_route_cache_v2,
-                // &opportunity_tx_v2,
-                // &token_tax_map_v2,
-                // &config,
-            )
-            .await
-            {
-                Ok(_) => {
-                    println!("✅ V2 monitoring completed successfully");
-                    break;
-                }
-                Err(e) => {
-                    retry_count += 1;
-                    eprintln!(
-                        "❌ V2 monitoring error (attempt {}/{}): {}",
-                        retry_count, MAX_RETRIES, e
-                    );
-
-                    if retry_count >= MAX_RETRIES {
-                        eprintln!("🚨 Max retries reached, stopping V2 monitoring");
-                        break;
-                    }
-
-                    // Wait before retrying with exponential backoff
-                    let wait_time = std::cmp::min(5 * retry_count, 30); // Max 30 seconds
-                    println!("⏳ Waiting {} seconds before V2 retry...", wait_time);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(wait_time as u64)).await;
-                }
-            }
-        }
-    });
 
     // V3 Swap subscription with arbitrage detection
     // println!(
@@ -122,57 +80,501 @@ pub async fn start_price_tracker(
     let v3_filter = Filter::new()
         .topic0(vec![uniswap_v3_swap_topic, pancakeswap_v3_swap_topic]);
 
-    let reserve_cache_v3 = reserve_cache.clone();
-    // let token_index_v3 = token_index.clone();
-    // let precomputed_route_cache_v3 = precomputed_route_cache.clone();
-    // let opportunity_tx_v3 = opportunity_tx.clone();
-    // let http_provider_v3 = http_provider.clone();
-    let ws_provider_v3 = ws_provider.clone();
-    // let token_tax_map_v3 = token_tax_map.clone();
+    let (idx, ws_provider) = connect_next_ws_endpoint(&ws_urls, 0, &timeouts)
+        .await
+        .map_err(|(_, e)| e)?;
+    Ok(tokio::spawn(run_price_tracker_supervisor(
+        ws_urls,
+        idx,
+        ws_provider,
+        v2_filter,
+        v3_filter,
+        reserve_cache,
+        reorg_tracker,
+        pending_updates,
+        timeouts,
+        retry_policy,
+        ws_reconnect,
+        swap_tx,
+    )))
+}
 
-    tokio::spawn(async move {
-        let mut retry_count = 0;
-        const MAX_RETRIES: u32 = 10;
+/// Index (into the configured `ws_urls`) of the WS endpoint the price tracker is using
+pub static ACTIVE_WS_ENDPOINT: AtomicUsize = AtomicUsize::new(0);
+/// Number of times the price tracker switched or re-established its WS endpoint
+pub static WS_FAILOVER_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// Highest block seen in a processed V2/V3 log; backfill starts here after a reconnect
+static LAST_EVENT_BLOCK: AtomicU64 = AtomicU64::new(0);
+/// Latest head from the new-heads subscription (0 until the first one arrives)
+static CURRENT_BLOCK: AtomicU64 = AtomicU64::new(0);
+/// Sync/Swap updates dropped because the pool was already updated from a higher block
+pub static STALE_UPDATES_IGNORED: AtomicU64 = AtomicU64::new(0);
+/// Backfill at most this many blocks after a reconnect; older gaps are left to the consistency check
+const MAX_BACKFILL_BLOCKS: u64 = 200;
+
+/// Keep calling `connect` until it succeeds, waiting a jittered exponential backoff between
+/// attempts. Gives up after `policy.max_retries` attempts, or straight away on a fatal error.
+async fn reconnect_with_backoff<T, C, Fut>(policy: &RetryPolicy, mut connect: C) -> anyhow::Result<T>
+where
+    C: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, (ErrorClass, anyhow::Error)>>,
+{
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let (class, e) = match connect().await {
+            Ok(conn) => return Ok(conn),
+            Err(err) => err,
+        };
+        let Some(delay) = policy.jittered_delay_for(class, attempt) else {
+            return Err(e.context("WS reconnect failed"));
+        };
+        if attempt >= policy.max_retries {
+            return Err(e.context(format!("WS reconnect gave up after {} attempts", attempt)));
+        }
+        eprintln!("🚨 [WS] {}; reconnecting in {:?} (attempt {}/{})", e, delay, attempt, policy.max_retries);
+        tokio::time::sleep(delay).await;
+    }
+}
 
-        loop {
-            match run_v3_monitoring_loop(
-                &ws_provider_v3,
-                &v3_filter,
-                &reserve_cache_v3,
-                // &http_provider_v3,
-                // &token_index_v3,
-                // &precomputed_route_cache_v3,
-                // &opportunity_tx_v3,
-                // &token_tax_map_v3,
-            )
-            .await
-            {
-                Ok(_) => {
-                    println!("✅ V3 monitoring completed successfully");
-                    break;
+/// Whether a WS provider still answers requests; a dead socket fails every call, including new subscriptions
+async fn ws_is_alive(ws_provider: &Provider<Ws>, timeouts: &Timeouts) -> bool {
+    matches!(
+        tokio::time::timeout(
+            tokio::time::Duration::from_secs(timeouts.subscribe_timeout_secs),
+            ws_provider.get_block_number(),
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
+
+/// Connect to the first reachable WS endpoint, trying them in order starting at `start`.
+/// The error is `Fatal` only when every endpoint failed for a reason retrying can't fix.
+async fn connect_next_ws_endpoint(
+    ws_urls: &[String],
+    start: usize,
+    timeouts: &Timeouts,
+) -> Result<(usize, Arc<Provider<Ws>>), (ErrorClass, anyhow::Error)> {
+    if ws_urls.is_empty() {
+        return Err((ErrorClass::Fatal, anyhow::anyhow!("no WS endpoints configured")));
+    }
+    let mut fatal = 0;
+    for i in 0..ws_urls.len() {
+        let idx = (start + i) % ws_urls.len();
+        match tokio::time::timeout(
+            tokio::time::Duration::from_secs(timeouts.ws_connect_timeout_secs),
+            Provider::<Ws>::connect(&ws_urls[idx]),
+        )
+        .await
+        {
+            Ok(Ok(provider)) => {
+                println!("✅ [WS] Connected to {}", ws_urls[idx]);
+                return Ok((idx, Arc::new(provider)));
+            }
+            Ok(Err(e)) => {
+                let class = classify_provider_error(&e.to_string());
+                if class == ErrorClass::Fatal {
+                    fatal += 1;
                 }
+                eprintln!("❌ [WS] Failed to connect to {} ({:?}): {}", ws_urls[idx], class, e);
+            }
+            Err(_) => eprintln!("❌ [WS] Connection timeout for {}", ws_urls[idx]),
+        }
+    }
+    if fatal == ws_urls.len() {
+        return Err((
+            ErrorClass::Fatal,
+            anyhow::anyhow!("every WS endpoint rejected the connection; check ws_url / ws_urls and API keys"),
+        ));
+    }
+    Err((ErrorClass::Retryable, anyhow::anyhow!("all {} WS endpoints are unreachable", ws_urls.len())))
+}
+
+/// Run V2/V3 monitoring and the reorg watcher on one WS endpoint. When a monitoring loop
+/// stops every task is torn down and the provider is rebuilt: on the same endpoint if its
+/// connection died, on the next one if the loop gave up on repeated failures. Missed events
+/// are backfilled and the subscriptions restart with the same filters. Returns an error once
+/// `ws_reconnect` runs out without a working connection.
+async fn run_price_tracker_supervisor(
+    ws_urls: Vec<String>,
+    mut idx: usize,
+    mut ws_provider: Arc<Provider<Ws>>,
+    v2_filter: Filter,
+    v3_filter: Filter,
+    reserve_cache: Arc<ReserveCache>,
+    reorg_tracker: Arc<ReorgTracker>,
+    pending_updates: Arc<PendingUpdates>,
+    timeouts: Timeouts,
+    retry_policy: RetryPolicy,
+    ws_reconnect: RetryPolicy,
+    swap_tx: Option<mpsc::Sender<DecodedSwap>>,
+) -> anyhow::Result<()> {
+    loop {
+        ACTIVE_WS_ENDPOINT.store(idx, Ordering::Relaxed);
+
+        let watcher = spawn_reorg_watcher(
+            ws_provider.clone(),
+            reserve_cache.clone(),
+            reorg_tracker.clone(),
+            pending_updates.clone(),
+        );
+        let mut v2_task = {
+            let (ws_provider, filter, reserve_cache, reorg_tracker, pending_updates) = (
+                ws_provider.clone(),
+                v2_filter.clone(),
+                reserve_cache.clone(),
+                reorg_tracker.clone(),
+                pending_updates.clone(),
+            );
+            let (timeouts, retry_policy, swap_tx) = (timeouts.clone(), retry_policy.clone(), swap_tx.clone());
+            tokio::spawn(async move {
+                run_v2_monitoring_loop(
+                    &ws_provider,
+                    &filter,
+                    &reserve_cache,
+                    &reorg_tracker,
+                    &pending_updates,
+                    &timeouts,
+                    &retry_policy,
+                    swap_tx.as_ref(),
+                )
+                .await
+            })
+        };
+        let mut v3_task = {
+            let (ws_provider, filter, reserve_cache, reorg_tracker, pending_updates) = (
+                ws_provider.clone(),
+                v3_filter.clone(),
+                reserve_cache.clone(),
+                reorg_tracker.clone(),
+                pending_updates.clone(),
+            );
+            let (timeouts, retry_policy) = (timeouts.clone(), retry_policy.clone());
+            tokio::spawn(async move {
+                run_v3_monitoring_loop(
+                    &ws_provider,
+                    &filter,
+                    &reserve_cache,
+                    &reorg_tracker,
+                    &pending_updates,
+                    &timeouts,
+                    &retry_policy,
+                )
+                .await
+            })
+        };
+
+        // Either loop exiting means this endpoint's subscriptions are gone
+        let failed = tokio::select! {
+            res = &mut v2_task => {
+                println!("⚠️ V2 monitoring stopped on {}", ws_urls[idx]);
+                !matches!(res, Ok(Ok(())))
+            }
+            res = &mut v3_task => {
+                println!("⚠️ V3 monitoring stopped on {}", ws_urls[idx]);
+                !matches!(res, Ok(Ok(())))
+            }
+        };
+        v2_task.abort();
+        v3_task.abort();
+        watcher.abort();
+        WS_FAILOVER_COUNT.fetch_add(1, Ordering::Relaxed);
+
+        // Repeated failures: move on to the next endpoint. Clean exit or dead connection: rebuild on the same one.
+        let start = if failed { idx + 1 } else { idx };
+        let (next_idx, provider) =
+            match reconnect_with_backoff(&ws_reconnect, || connect_next_ws_endpoint(&ws_urls, start, &timeouts)).await {
+                Ok(conn) => conn,
                 Err(e) => {
-                    retry_count += 1;
-                    eprintln!(
-                        "❌ V3 monitoring error (attempt {}/{}): {}",
-                        retry_count, MAX_RETRIES, e
-                    );
-
-                    if retry_count >= MAX_RETRIES {
-                        eprintln!("🚨 Max retries reached, stopping V3 monitoring");
-                        break;
+                    eprintln!("🛑 [WS] {:#}; price tracker stopped", e);
+                    return Err(e);
+                }
+            };
+        if next_idx != idx {
+            println!("🔀 [WS] Failing over from {} to {}", ws_urls[idx], ws_urls[next_idx]);
+        }
+        idx = next_idx;
+        ws_provider = provider;
+
+        match backfill_missed_events(
+            &ws_provider,
+            &v2_filter,
+            &v3_filter,
+            &reserve_cache,
+            &reorg_tracker,
+            &pending_updates,
+        )
+        .await
+        {
+            Ok(count) => println!("[WS] Backfilled {} events after reconnect", count),
+            Err(e) => eprintln!("❌ [WS] Backfill failed: {}", e),
+        }
+    }
+}
+
+/// Replay V2 Sync / V3 Swap logs from the last processed block to the current head
+async fn backfill_missed_events(
+    ws_provider: &Arc<Provider<Ws>>,
+    v2_filter: &Filter,
+    v3_filter: &Filter,
+    reserve_cache: &Arc<ReserveCache>,
+    reorg_tracker: &Arc<ReorgTracker>,
+    pending_updates: &Arc<PendingUpdates>,
+) -> anyhow::Result<usize> {
+    let last = LAST_EVENT_BLOCK.load(Ordering::Relaxed);
+    if last == 0 {
+        return Ok(0);
+    }
+    let head = ws_provider.get_block_number().await?.as_u64();
+    // Start at the last block itself: replaying its logs in order lands on the same final state
+    let from = last.max(head.saturating_sub(MAX_BACKFILL_BLOCKS));
+    if from > head {
+        return Ok(0);
+    }
+
+    let mut count = 0;
+    let v2_logs = ws_provider.get_logs(&v2_filter.clone().from_block(from).to_block(head)).await?;
+    for log in v2_logs {
+        if !reorg_tracker.record_log(&log) {
+            pending_updates.discard_removed(&log);
+            continue;
+        }
+        // Replayed swaps are blocks old; their reserves are applied but they aren't traded on
+        if handle_v2_log(log, reserve_cache, pending_updates, None).await.is_ok() {
+            count += 1;
+        }
+    }
+    let v3_logs = ws_provider.get_logs(&v3_filter.clone().from_block(from).to_block(head)).await?;
+    for log in v3_logs {
+        if !reorg_tracker.record_log(&log) {
+            pending_updates.discard_removed(&log);
+            continue;
+        }
+        if handle_v3_swap_event_with_arbitrage(log, reserve_cache, pending_updates).await.is_ok() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Decoded pool state change from a V2 Sync or V3 Swap log
+#[derive(Clone, Debug)]
+enum PoolUpdate {
+    V2 { reserve0: U256, reserve1: U256 },
+    V3 { sqrt_price_x96: U256, liquidity: U256, tick: i32 },
+}
+
+impl PoolUpdate {
+    /// Write the update into `state`, unless it comes from a lower block than the last one
+    /// applied: a late or orphaned event must not roll the pool back. Later logs from the same
+    /// block still apply, as they reflect the post-block state. Returns whether it was applied.
+    fn apply(&self, state: &mut PoolState, block: Option<u64>) -> bool {
+        if let Some(block) = block {
+            if block < state.last_updated_block {
+                STALE_UPDATES_IGNORED.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+            state.last_updated_block = block;
+        }
+        match *self {
+            PoolUpdate::V2 { reserve0, reserve1 } => {
+                state.reserve0 = Some(reserve0);
+                state.reserve1 = Some(reserve1);
+            }
+            PoolUpdate::V3 { sqrt_price_x96, liquidity, tick } => {
+                state.sqrt_price_x96 = Some(sqrt_price_x96);
+                state.liquidity = Some(liquidity);
+                state.tick = Some(tick);
+            }
+        }
+        state.last_updated = chrono::Utc::now().timestamp() as u64;
+        true
+    }
+}
+
+/// Per-pool buffer of updates keyed by block, held until they are `confirmation_blocks` deep.
+/// With `confirmation_blocks == 0` updates skip the buffer and hit the cache immediately.
+pub struct PendingUpdates {
+    confirmation_blocks: u64,
+    pending: DashMap<H160, BTreeMap<u64, (Option<H256>, PoolUpdate)>>,
+}
+
+impl PendingUpdates {
+    pub fn new(confirmation_blocks: u64) -> Self {
+        Self {
+            confirmation_blocks,
+            pending: DashMap::new(),
+        }
+    }
+
+    /// Highest block whose events may be applied for the given head (`None` = latest)
+    pub fn confirmed_height(&self, head: u64) -> Option<u64> {
+        if self.confirmation_blocks == 0 {
+            None
+        } else {
+            Some(head.saturating_sub(self.confirmation_blocks))
+        }
+    }
+
+    /// Apply an update now, or buffer it until confirmed.
+    /// A later log in the same block replaces the earlier one (it reflects the post-block state).
+    fn submit(&self, reserve_cache: &ReserveCache, log: &Log, update: PoolUpdate) {
+        match log.block_number {
+            Some(block) if self.confirmation_blocks > 0 => {
+                self.pending
+                    .entry(log.address)
+                    .or_default()
+                    .insert(block.as_u64(), (log.block_hash, update));
+            }
+            _ => {
+                let block = log.block_number.map(|n| n.as_u64());
+                if let Some(mut state) = reserve_cache.get_mut(&log.address) {
+                    if update.apply(&mut state, block) {
+                        price_feed::publish(log.address, &state, block);
+                        bump_reserve_version();
+                    } else {
+                        println!(
+                            "[REORG] Ignored update for {:?} from block {:?}, cache already at block {}",
+                            log.address, block, state.last_updated_block
+                        );
                     }
+                }
+            }
+        }
+    }
 
-                    // Wait before retrying with exponential backoff
-                    let wait_time = std::cmp::min(5 * retry_count, 30); // Max 30 seconds
-                    println!("⏳ Waiting {} seconds before V3 retry...", wait_time);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(wait_time as u64)).await;
+    /// Apply buffered updates that are at least `confirmation_blocks` deep at `head`.
+    /// Only the newest confirmed update per pool is written. Returns the number of pools updated.
+    pub fn flush_confirmed(&self, reserve_cache: &ReserveCache, head: u64) -> usize {
+        let Some(confirmed) = self.confirmed_height(head) else { return 0 };
+        let mut applied = 0;
+        self.pending.retain(|pool, updates| {
+            let unconfirmed = updates.split_off(&(confirmed + 1));
+            if let Some((block, (_, update))) = updates.iter().next_back() {
+                if let Some(mut state) = reserve_cache.get_mut(pool) {
+                    if update.apply(&mut state, Some(*block)) {
+                        price_feed::publish(*pool, &state, Some(*block));
+                        bump_reserve_version();
+                        applied += 1;
+                    }
                 }
             }
+            *updates = unconfirmed;
+            !updates.is_empty()
+        });
+        applied
+    }
+
+    /// Drop the buffered update coming from a removed (reorged-out) log
+    pub fn discard_removed(&self, log: &Log) {
+        let Some(block) = log.block_number.map(|n| n.as_u64()) else { return };
+        if let Some(mut updates) = self.pending.get_mut(&log.address) {
+            if matches!(updates.get(&block), Some((hash, _)) if *hash == log.block_hash) {
+                updates.remove(&block);
+            }
         }
-    });
+    }
+}
 
-    Ok(())
+/// Chain head as last seen on the new-heads subscription; 0 before the first head
+pub fn current_block() -> u64 {
+    CURRENT_BLOCK.load(Ordering::Relaxed)
+}
+
+/// Subscribe to new heads, track the current block, feed them to the reorg tracker and re-sync any pools it flags
+/// (touched within the last `REORG_DEPTH` blocks of a reorg, or referenced by a removed log).
+fn spawn_reorg_watcher(
+    ws_provider: Arc<Provider<Ws>>,
+    reserve_cache: Arc<ReserveCache>,
+    reorg_tracker: Arc<ReorgTracker>,
+    pending_updates: Arc<PendingUpdates>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let mut heads = match ws_provider.subscribe_blocks().await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("❌ [REORG] Failed to subscribe to new heads: {}", e);
+                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            println!("✅ [REORG] Watching new heads (depth {})", REORG_DEPTH);
+            while let Some(block) = heads.next().await {
+                let Some(head) = block.number.map(|n| n.as_u64()) else { continue };
+                CURRENT_BLOCK.store(head, Ordering::Relaxed);
+                if let Some(hash) = block.hash {
+                    reorg_tracker.on_new_head(head, hash, block.parent_hash);
+                }
+                pending_updates.flush_confirmed(&reserve_cache, head);
+                let dirty = reorg_tracker.take_dirty();
+                if !dirty.is_empty() {
+                    // Re-fetch at the confirmed height so unconfirmed state never reaches the cache
+                    let at_block = pending_updates.confirmed_height(head);
+                    let refreshed = crate::cache::refresh_pools(&reserve_cache, &dirty, ws_provider.clone(), at_block).await;
+                    println!("[REORG] Re-synced {}/{} pools from canonical chain", refreshed, dirty.len());
+                }
+            }
+            eprintln!("❌ [REORG] New heads stream ended, resubscribing...");
+        }
+    })
+}
+
+/// Re-run one monitoring session until it ends on its own (stream ended, idle timeout), retrying
+/// failures with jittered backoff. A failure on a connection that no longer answers returns at
+/// once: every retry on a dead socket would fail too, so the supervisor rebuilds the provider and
+/// resubscribes instead. `Err` means the retries ran out and the endpoint should be abandoned.
+async fn run_session_with_retries<S, SF, A, AF>(
+    label: &str,
+    retry_policy: &RetryPolicy,
+    mut session: S,
+    mut is_alive: A,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    S: FnMut() -> SF,
+    SF: std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+    A: FnMut() -> AF,
+    AF: std::future::Future<Output = bool>,
+{
+    let mut retry_count = 0;
+    loop {
+        println!(
+            "🔍 DEBUG: {} monitoring session attempt {}/{}",
+            label,
+            retry_count + 1,
+            retry_policy.max_retries
+        );
+        let e = match session().await {
+            Ok(()) => {
+                println!("✅ {} monitoring session completed successfully", label);
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        retry_count += 1;
+        let class = classify_provider_error(&e.to_string());
+        eprintln!(
+            "❌ {} monitoring error (attempt {}/{}, {:?}): {}",
+            label, retry_count, retry_policy.max_retries, class, e
+        );
+        if !is_alive().await {
+            eprintln!("🔌 [WS] Connection lost, {} monitoring hands over for a reconnect", label);
+            return Ok(());
+        }
+
+        // Exponential backoff, longer when rate-limited; fatal errors skip straight to failover
+        let Some(delay) = retry_policy.jittered_delay_for(class, retry_count) else {
+            eprintln!("🛑 Fatal {} monitoring error, not retrying on this endpoint: {}", label, e);
+            return Err(e);
+        };
+        if retry_count >= retry_policy.max_retries {
+            eprintln!("🚨 Max retries reached, stopping {} monitoring", label);
+            return Err(e);
+        }
+        println!("⏳ Retrying {} in {:?}...", label, delay);
+        tokio::time::sleep(delay).await;
+    }
 }
 
 /// V2 monitoring loop with error handling and reconnection
@@ -180,60 +582,41 @@ async fn run_v2_monitoring_loop(
     ws_provider: &Arc<Provider<Ws>>,
     filter: &Filter,
     reserve_cache: &Arc<ReserveCache>,
+    reorg_tracker: &Arc<ReorgTracker>,
+    pending_updates: &Arc<PendingUpdates>,
+    timeouts: &Timeouts,
+    retry_policy: &RetryPolicy,
+    swap_tx: Option<&mpsc::Sender<DecodedSwap>>,
     // token_index: &Arc<TokenIndexMap>,
-    // precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    // precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     // opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
     // token_tax_map: &Arc<TokenTaxMap>,
     // config: &Config,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut retry_count = 0;
-    const MAX_RETRIES: u32 = 10;
-
     println!("🔍 DEBUG: V2 monitoring loop starting...");
 
-    loop {
-        println!(
-            "🔍 DEBUG: V2 monitoring session attempt {}/{}",
-            retry_count + 1,
-            MAX_RETRIES
-        );
-        match run_single_v2_session(
-            ws_provider,
-            filter,
-            reserve_cache,
-            // token_index,
-            // precomputed_route_cache,
-            // opportunity_tx,
-            // token_tax_map,
-            // &config,
-        )
-        .await
-        {
-            Ok(_) => {
-                println!("✅ V2 monitoring session completed successfully");
-                break;
-            }
-            Err(e) => {
-                retry_count += 1;
-                eprintln!(
-                    "❌ V2 monitoring error (attempt {}/{}): {}",
-                    retry_count, MAX_RETRIES, e
-                );
-
-                if retry_count >= MAX_RETRIES {
-                    eprintln!("🚨 Max retries reached, stopping V2 monitoring");
-                    return Err(e);
-                }
-
-                // Exponential backoff
-                let delay = std::time::Duration::from_secs(2_u64.pow(retry_count.min(5)));
-                println!("⏳ Retrying in {:?}...", delay);
-                tokio::time::sleep(delay).await;
-            }
-        }
-    }
-
-    Ok(())
+    run_session_with_retries(
+        "V2",
+        retry_policy,
+        || {
+            run_single_v2_session(
+                ws_provider,
+                filter,
+                reserve_cache,
+                reorg_tracker,
+                pending_updates,
+                timeouts,
+                swap_tx,
+                // token_index,
+                // precomputed_route_cache,
+                // opportunity_tx,
+                // token_tax_map,
+                // &config,
+            )
+        },
+        || ws_is_alive(ws_provider, timeouts),
+    )
+    .await
 }
 
 /// Single V2 monitoring session with proper error handling
@@ -241,8 +624,12 @@ async fn run_single_v2_session(
     ws_provider: &Arc<Provider<Ws>>,
     filter: &Filter,
     reserve_cache: &Arc<ReserveCache>,
+    reorg_tracker: &Arc<ReorgTracker>,
+    pending_updates: &Arc<PendingUpdates>,
+    timeouts: &Timeouts,
+    swap_tx: Option<&mpsc::Sender<DecodedSwap>>,
     // token_index: &Arc<TokenIndexMap>,
-    // precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    // precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     // opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
     // token_tax_map: &Arc<TokenTaxMap>,
     // config: &Config,
@@ -252,7 +639,7 @@ async fn run_single_v2_session(
     // Subscribe to V2 Sync events
     println!("🔍 DEBUG: Subscribing to V2 Sync events...");
     let mut v2_stream = match tokio::time::timeout(
-        tokio::time::Duration::from_secs(10),
+        tokio::time::Duration::from_secs(timeouts.subscribe_timeout_secs),
         ws_provider.subscribe_logs(filter),
     )
     .await
@@ -272,15 +659,15 @@ async fn run_single_v2_session(
     };
 
     let mut last_activity = std::time::Instant::now();
-    const ACTIVITY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300); // 5 minutes
+    let activity_timeout = std::time::Duration::from_secs(timeouts.activity_timeout_secs);
 
     println!("🔍 DEBUG: Starting V2 Sync monitoring loop...");
 
     // Monitor V2 Sync events with timeout and error handling
     loop {
         // Check for activity timeout
-        if last_activity.elapsed() > ACTIVITY_TIMEOUT {
-            println!("⚠️ No V2 activity for 5 minutes, restarting session...");
+        if last_activity.elapsed() > activity_timeout {
+            println!("⚠️ No V2 activity for {:?}, restarting session...", activity_timeout);
             return Ok(()); // Restart the session
         }
 
@@ -289,7 +676,7 @@ async fn run_single_v2_session(
         tokio::select! {
             // Handle V2 Sync events with timeout
             result = tokio::time::timeout(
-                tokio::time::Duration::from_secs(10),
+                tokio::time::Duration::from_secs(timeouts.stream_poll_timeout_secs),
                 v2_stream.next()
             ) => {
                 // println!("🔍 DEBUG: V2 Sync timeout result received: {:?}", result.is_ok());
@@ -298,12 +685,23 @@ async fn run_single_v2_session(
                         // println!("🔍 DEBUG: Processing V2 Sync event: {:?}", log.address);
                         last_activity = std::time::Instant::now();
 
+                        // Removed (reorged-out) logs are not applied; the pool gets re-synced instead
+                        if !reorg_tracker.record_log(&log) {
+                            pending_updates.discard_removed(&log);
+                            continue;
+                        }
+                        if let Some(block) = log.block_number {
+                            LAST_EVENT_BLOCK.fetch_max(block.as_u64(), Ordering::Relaxed);
+                        }
+
                         // Add timeout for event processing
                         match tokio::time::timeout(
-                            tokio::time::Duration::from_secs(10),
-                            handle_v2_sync_event_with_arbitrage(
+                            tokio::time::Duration::from_secs(timeouts.event_processing_timeout_secs),
+                            handle_v2_log(
                                 log,
                                 reserve_cache,
+                                pending_updates,
+                                swap_tx,
                                 // token_index,
                                 // precomputed_route_cache,
                                 // opportunity_tx,
@@ -313,11 +711,11 @@ async fn run_single_v2_session(
                         ).await {
                             Ok(result) => {
                                 if let Err(e) = result {
-                                    eprintln!("❌ Error processing V2 Sync event: {}", e);
+                                    eprintln!("❌ Error processing V2 event: {}", e);
                                 }
                             }
                             Err(_) => {
-                                eprintln!("⚠️ V2 Sync event processing timeout, skipping...");
+                                eprintln!("⚠️ V2 event processing timeout, skipping...");
                             }
                         }
                     }
@@ -333,7 +731,7 @@ async fn run_single_v2_session(
             }
 
             // Periodic activity check
-            _ = tokio::time::sleep(tokio::time::Duration::from_secs(30)) => {
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(timeouts.tracker_heartbeat_interval_secs)) => {
                 println!("💓 V2 heartbeat - last activity: {:?} ago", last_activity.elapsed());
             }
         }
@@ -345,53 +743,37 @@ async fn run_v3_monitoring_loop(
     ws_provider: &Arc<Provider<Ws>>,
     filter: &Filter,
     reserve_cache: &Arc<ReserveCache>,
+    reorg_tracker: &Arc<ReorgTracker>,
+    pending_updates: &Arc<PendingUpdates>,
+    timeouts: &Timeouts,
+    retry_policy: &RetryPolicy,
     // http_provider: &Arc<Provider<Http>>,
     // token_index: &Arc<TokenIndexMap>,
-    // precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    // precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     // opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
     // token_tax_map: &Arc<TokenTaxMap>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut retry_count = 0;
-    const MAX_RETRIES: u32 = 10;
-
-    loop {
-        match run_single_v3_session(
-            ws_provider,
-            filter,
-            reserve_cache,
-            // http_provider,
-            // token_index,
-            // precomputed_route_cache,
-            // opportunity_tx,
-            // token_tax_map,
-        )
-        .await
-        {
-            Ok(_) => {
-                println!("✅ V3 monitoring session completed successfully");
-                break;
-            }
-            Err(e) => {
-                retry_count += 1;
-                eprintln!(
-                    "❌ V3 monitoring error (attempt {}/{}): {}",
-                    retry_count, MAX_RETRIES, e
-                );
-
-                if retry_count >= MAX_RETRIES {
-                    eprintln!("🚨 Max retries reached, stopping V3 monitoring");
-                    return Err(e);
-                }
-
-                // Wait before retrying with exponential backoff
-                let wait_time = std::cmp::min(5 * retry_count, 30); // Max 30 seconds
-                println!("⏳ Waiting {} seconds before V3 retry...", wait_time);
-                tokio::time::sleep(tokio::time::Duration::from_secs(wait_time as u64)).await;
-            }
-        }
-    }
-
-    Ok(())
+    run_session_with_retries(
+        "V3",
+        retry_policy,
+        || {
+            run_single_v3_session(
+                ws_provider,
+                filter,
+                reserve_cache,
+                reorg_tracker,
+                pending_updates,
+                timeouts,
+                // http_provider,
+                // token_index,
+                // precomputed_route_cache,
+                // opportunity_tx,
+                // token_tax_map,
+            )
+        },
+        || ws_is_alive(ws_provider, timeouts),
+    )
+    .await
 }
 
 /// Single V3 monitoring session
@@ -399,36 +781,62 @@ async fn run_single_v3_session(
     ws_provider: &Arc<Provider<Ws>>,
     filter: &Filter,
     reserve_cache: &Arc<ReserveCache>,
+    reorg_tracker: &Arc<ReorgTracker>,
+    pending_updates: &Arc<PendingUpdates>,
+    timeouts: &Timeouts,
     // http_provider: &Arc<Provider<Http>>,
     // token_index: &Arc<TokenIndexMap>,
-    // precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    // precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     // opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
     // token_tax_map: &Arc<TokenTaxMap>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut v3_stream = match ws_provider.subscribe_logs(filter).await {
-        Ok(stream) => {
+    let mut v3_stream = match tokio::time::timeout(
+        tokio::time::Duration::from_secs(timeouts.subscribe_timeout_secs),
+        ws_provider.subscribe_logs(filter),
+    )
+    .await
+    {
+        Ok(Ok(stream)) => {
             println!("✅ V3 stream initialized successfully");
             stream
         }
-        Err(e) => {
+        Ok(Err(e)) => {
             return Err(format!("Failed to subscribe to V3 logs: {}", e).into());
         }
+        Err(_) => {
+            return Err("V3 Swap subscription timeout".into());
+        }
     };
 
     let mut last_activity = std::time::Instant::now();
-    const ACTIVITY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300); // 5 minutes
+    let activity_timeout = std::time::Duration::from_secs(timeouts.activity_timeout_secs);
 
     loop {
-        match tokio::time::timeout(tokio::time::Duration::from_secs(10), v3_stream.next()).await {
+        match tokio::time::timeout(
+            tokio::time::Duration::from_secs(timeouts.stream_poll_timeout_secs),
+            v3_stream.next(),
+        )
+        .await
+        {
             Ok(Some(log)) => {
                 last_activity = std::time::Instant::now();
 
+                // Removed (reorged-out) logs are not applied; the pool gets re-synced instead
+                if !reorg_tracker.record_log(&log) {
+                    pending_updates.discard_removed(&log);
+                    continue;
+                }
+                if let Some(block) = log.block_number {
+                    LAST_EVENT_BLOCK.fetch_max(block.as_u64(), Ordering::Relaxed);
+                }
+
                 // Add timeout for event processing
                 match tokio::time::timeout(
-                    tokio::time::Duration::from_secs(10),
+                    tokio::time::Duration::from_secs(timeouts.event_processing_timeout_secs),
                     handle_v3_swap_event_with_arbitrage(
                         log,
                         reserve_cache,
+                        pending_updates,
                         // http_provider,
                         // token_index,
                         // precomputed_route_cache,
@@ -449,8 +857,8 @@ async fn run_single_v3_session(
                 }
 
                 // Check for activity timeout
-                if last_activity.elapsed() > ACTIVITY_TIMEOUT {
-                    println!("⚠️ No V3 activity for 5 minutes, restarting...");
+                if last_activity.elapsed() > activity_timeout {
+                    println!("⚠️ No V3 activity for {:?}, restarting...", activity_timeout);
                     return Err("V3 activity timeout".into());
                 }
             }
@@ -468,12 +876,53 @@ async fn run_single_v3_session(
     Err("V3 stream ended unexpectedly".into())
 }
 
+/// Route a log from the V2 subscription: Sync updates the reserves, Swap says who bought what
+/// and goes to `swap_tx` for detection. A swap is dropped rather than waited on when detection
+/// is behind; by the time it frees up the opportunity is gone.
+async fn handle_v2_log(
+    log: Log,
+    reserve_cache: &Arc<ReserveCache>,
+    pending_updates: &Arc<PendingUpdates>,
+    swap_tx: Option<&mpsc::Sender<DecodedSwap>>,
+) -> anyhow::Result<()> {
+    if log.topics.first() != Some(&*V2_SWAP_TOPIC) {
+        return handle_v2_sync_event_with_arbitrage(log, reserve_cache, pending_updates).await;
+    }
+    if let (Some(swap), Some(swap_tx)) = (decoded_swap_from_v2_log(&log, reserve_cache)?, swap_tx) {
+        let _ = swap_tx.try_send(swap);
+    }
+    Ok(())
+}
+
+/// The `DecodedSwap` of a V2 Swap log. The bought token and amount come straight from
+/// amount{0,1}{In,Out}, so a skim or donation in the same transaction doesn't blur the
+/// direction the way reserve deltas do. None for pools not in the cache and for logs with
+/// no net outflow of exactly one token (e.g. a fully repaid flash loan).
+fn decoded_swap_from_v2_log(log: &Log, reserve_cache: &ReserveCache) -> anyhow::Result<Option<DecodedSwap>> {
+    let swap = decode_v2_swap(&log.data.0)?;
+    let Some((token0, token1)) = reserve_cache.get(&log.address).map(|pool| (pool.token0, pool.token1)) else {
+        return Ok(None);
+    };
+    let Some((token_x, token_x_amount)) = swap.token_out(token0, token1) else {
+        return Ok(None);
+    };
+    Ok(Some(DecodedSwap {
+        tx_hash: H160::zero(), // DecodedSwap holds an H160; the log's transaction hash doesn't fit
+        pool_address: log.address,
+        token_x,
+        token_x_amount,
+        block_number: current_block(), // detection block, for expiry
+        timestamp: chrono::Utc::now().timestamp() as u64,
+    }))
+}
+
 /// Handle a V2 Sync event: decode reserves, update the cache, and detect arbitrage opportunities.
 async fn handle_v2_sync_event_with_arbitrage(
     log: Log,
     reserve_cache: &Arc<ReserveCache>,
+    pending_updates: &Arc<PendingUpdates>,
     // token_index: &Arc<TokenIndexMap>,
-    // precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    // precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     // opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
     // token_tax_map: &Arc<TokenTaxMap>,
     // config: &Config,
@@ -496,36 +945,15 @@ async fn handle_v2_sync_event_with_arbitrage(
         .and_then(|s| s.reserve1)
         .unwrap_or(U256::zero());
 
-    // Update cache
-    if let Some(mut state) = reserve_cache.get_mut(&pool) {
-        state.reserve0 = Some(new_reserve0);
-        state.reserve1 = Some(new_reserve1);
-        state.last_updated = chrono::Utc::now().timestamp() as u64;
-    }
+    // Update cache (or buffer until confirmed)
+    pending_updates.submit(
+        reserve_cache,
+        &log,
+        PoolUpdate::V2 { reserve0: new_reserve0, reserve1: new_reserve1 },
+    );
 println!("[DEBUG] Updated V2 pool cache for {:?}: reserve0 = {}, reserve1 = {}", pool, new_reserve0, new_reserve1);
-    // Calculate which token was bought/sold
-    // let token0_change = new_reserve0.saturating_sub(old_reserve0);
-    // let token1_change = new_reserve1.saturating_sub(old_reserve1);
-
-    // Determine swap direction and amount
-    // let (token_x, token_x_amount) = if new_reserve0 < old_reserve0 {
-    //     // token0 bought (reserve0 decreased)
-    //     if let Some(pool_data) = reserve_cache.get(&pool) {
-    //         (pool_data.token0, old_reserve0.saturating_sub(new_reserve0))
-    //     } else {
-    //         return Ok(());
-    //     }
-    // } else if new_reserve1 < old_reserve1 {
-    //     // token1 bought (reserve1 decreased)
-    //     if let Some(pool_data) = reserve_cache.get(&pool) {
-    //         (pool_data.token1, old_reserve1.saturating_sub(new_reserve1))
-    //     } else {
-    //         return Ok(());
-    //     }
-    // } else {
-    //     return Ok(());
-    // };
-    
+    // Swap direction isn't inferred from reserve deltas; it comes from the pool's Swap log
+    // (decoded_swap_from_v2_log), which the same subscription delivers right after this Sync
 
     // // Create decoded swap for arbitrage detection
     // let decoded_swap = DecodedSwap {
@@ -617,9 +1045,10 @@ println!("[DEBUG] Updated V2 pool cache for {:?}: reserve0 = {}, reserve1 = {}",
 async fn handle_v3_swap_event_with_arbitrage(
     log: Log,
     reserve_cache: &Arc<ReserveCache>,
+    pending_updates: &Arc<PendingUpdates>,
     // _http_provider: &Arc<Provider<Http>>,
     // _token_index: &Arc<TokenIndexMap>,
-    // _precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    // _precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     // _opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
     // _token_tax_map: &Arc<TokenTaxMap>,
 ) -> anyhow::Result<()> {
@@ -629,298 +1058,27 @@ async fn handle_v3_swap_event_with_arbitrage(
         anyhow::bail!("No topics in log");
     }
     let topic0 = log.topics[0];
-    let uniswap_v3_swap_topic = H256::from(ethers::utils::keccak256(
-        b"Swap(address,address,int256,int256,uint160,uint128,int24)",
-    ));
-    let pancakeswap_v3_swap_topic = H256::from(ethers::utils::keccak256(
-        b"Swap(address,address,int256,int256,uint160,uint128,int24,uint128,uint128)",
-    ));
-    let (sqrt_price_x96, liquidity, tick) = if topic0 == uniswap_v3_swap_topic {
-        if log.data.0.len() != 160 {
-            eprintln!("[UniswapV3 Swap] Unexpected log data size: {}", log.data.0.len());
-            anyhow::bail!("Invalid UniswapV3 Swap log size: {}", log.data.0.len());
-        }
-        let decoded = decode(
-            &[
-                ParamType::Int(256),  // amount0
-                ParamType::Int(256),  // amount1
-                ParamType::Uint(160), // sqrtPriceX96
-                ParamType::Uint(128), // liquidity
-                ParamType::Int(24),   // tick
-            ],
-            &log.data.0,
-        )?;
-        let sqrt_price_x96 = decoded[2].clone().into_uint().unwrap();
-        let liquidity = decoded[3].clone().into_uint().unwrap();
-        let tick_token = decoded[4].clone().into_int().unwrap();
-        let tick: i32 = I256::from_raw(tick_token).as_i32();
-        (sqrt_price_x96, liquidity, tick)
-    } else if topic0 == pancakeswap_v3_swap_topic {
-        if log.data.0.len() != 224 {
-            eprintln!("[PancakeV3 Swap] Unexpected log data size: {}", log.data.0.len());
-            anyhow::bail!("Invalid PancakeV3 Swap log size: {}", log.data.0.len());
-        }
-        let decoded = decode(
-            &[
-                ParamType::Int(256),  // amount0
-                ParamType::Int(256),  // amount1
-                ParamType::Uint(160), // sqrtPriceX96
-                ParamType::Uint(128), // liquidity
-                ParamType::Int(24),   // tick
-                ParamType::Uint(128), // protocolFeesToken0
-                ParamType::Uint(128), // protocolFeesToken1
-            ],
-            &log.data.0,
-        )?;
-        let sqrt_price_x96 = decoded[2].clone().into_uint().unwrap();
-        let liquidity = decoded[3].clone().into_uint().unwrap();
-        let tick_token = decoded[4].clone().into_int().unwrap();
-        let tick: i32 = I256::from_raw(tick_token).as_i32();
-        (sqrt_price_x96, liquidity, tick)
-    } else {
+    if topic0 != *UNISWAP_V3_SWAP_TOPIC && topic0 != *PANCAKE_V3_SWAP_TOPIC {
         eprintln!("[V3 Swap] Unknown topic0: {:?}", topic0);
         return Ok(());
-    };
-    let pool = log.address;
-    if let Some(mut state) = reserve_cache.get_mut(&pool) {
-        // println!("[DEBUG] Updating V3 pool cache for {:?}", pool);
-        state.sqrt_price_x96 = Some(sqrt_price_x96);
-        state.liquidity = Some(liquidity);
-        state.tick = Some(tick);
-        state.last_updated = chrono::Utc::now().timestamp() as u64;
     }
+    let swap = decode_v3_swap(topic0, &log.data.0).map_err(|e| {
+        eprintln!("[V3 Swap] Failed to decode log from {:?}: {}", log.address, e);
+        e
+    })?;
+    let (sqrt_price_x96, liquidity, tick) = (swap.sqrt_price_x96, swap.liquidity, swap.tick);
+    let pool = log.address;
+    record_v3_volume(pool, &swap);
+    // println!("[DEBUG] Updating V3 pool cache for {:?}", pool);
+    pending_updates.submit(
+        reserve_cache,
+        &log,
+        PoolUpdate::V3 { sqrt_price_x96, liquidity, tick },
+    );
     println!("[DEBUG] Updated V3 pool cache for {:?}: sqrt_price_x96 = {}, liquidity = {}, tick = {}", pool, sqrt_price_x96, liquidity, tick);
     Ok(())
 }
 
-/// Find arbitrage opportunities for a decoded swap (price tracker version)
-pub async fn find_arbitrage_opportunity_from_price_tracker(
-    decoded_swap: &DecodedSwap,
-    reserve_cache: &Arc<ReserveCache>,
-    token_index: &Arc<TokenIndexMap>,
-    precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
-    token_tax_map: &Arc<TokenTaxMap>,
-    config: &Config,
-) -> Option<(ArbitrageOpportunity, u128)> {
-    // Start latency timer
-    let start_time = std::time::Instant::now();
-    // Get token index
-    let token_x_index = token_index.address_to_index.get(&decoded_swap.token_x)?;
-    let token_x_index_u32 = *token_x_index as u32;
-
-    // println!(
-    //     "🔍 [Price Tracker] Finding arbitrage for tokenX (idx {}): {}",
-    //     token_x_index, decoded_swap.token_x
-    // );
-
-    // Get all routes that contain this token and the affected pool
-    let candidate_routes = precomputed_route_cache
-        .get(&token_x_index_u32)
-        .map(|entry| entry.value().clone())
-        .unwrap_or_default();
-
-    // println!(
-    //     "📊 [Price Tracker] Found {} candidate routes for tokenX",
-    //     candidate_routes.len()
-    // );
-
-    // Filter routes that contain the affected pool
-    let filtered_routes: Vec<&RoutePath> = candidate_routes
-        .iter()
-        .filter(|route| route.pools.contains(&decoded_swap.pool_address))
-        .collect();
-
-    // println!(
-    //     "🎯 [Price Tracker] {} routes contain the affected pool {}",
-    //     filtered_routes.len(),
-    //     decoded_swap.pool_address
-    // );
-
-    if filtered_routes.is_empty() {
-        return None;
-    }
-
-    // Simulate all filtered routes in parallel
-    let simulation_results: Vec<Option<crate::arbitrage_finder::SimulatedRoute>> = filtered_routes
-        .par_iter()
-        .map(|route| {
-            // Split route into buy/sell paths
-            let (buy_path, sell_path) = split_route_around_token_x(route, token_x_index_u32)?;
-
-            // Simulate buy path (base -> tokenX)
-            let buy_amounts = simulate_buy_path_amounts_array(
-                &buy_path,
-                decoded_swap.token_x_amount,
-                reserve_cache,
-                token_index,
-                token_tax_map,
-                config,
-            )?;
-
-            // Simulate sell path (tokenX -> base)
-            let sell_amounts = simulate_sell_path_amounts_array(
-                &sell_path,
-                decoded_swap.token_x_amount,
-                reserve_cache,
-                token_index,
-                token_tax_map,
-                config,
-            )?;
-
-            // Merge amounts: [buy_amounts..., sell_amounts[1..]]
-            let mut merged_amounts = buy_amounts.clone();
-            merged_amounts.extend_from_slice(&sell_amounts[1..]);
-            // let sell_test_amounts;
-            // simulate_sell_path_amounts_array(
-            //     route,
-            //     merged_amounts[0],
-            //     reserve_cache,
-            //     token_index,
-            // )?;
-            // Calculate profit and profit percentage
-            if merged_amounts.len() >= 2 {
-                let amount_in = merged_amounts[0];
-                let amount_out = merged_amounts.last().unwrap();
-                let profit = amount_out.saturating_sub(amount_in);
-
-                // Only consider profitable trades
-                let sell_symbols: Vec<String> = sell_path
-                    .hops
-                    .iter()
-                    .map(|&idx| token_index_to_symbol_from_price_tracker(idx, token_index))
-                    .collect();
-                let price_usd = {
-                    let last_symbol = &sell_symbols[sell_symbols.len()-1];
-                    if let Ok(addr) = last_symbol.parse::<H160>() {
-                        get_token_usd_value(&addr).unwrap_or(0.0)
-                    } else {
-                        0.0
-                    }
-                };
-                let amount = u256_to_f64_lossy(&profit) / 10_f64.powi(18 as i32);
-                let profit_usd = amount * price_usd;
-                if profit_usd > 0.02 {
-                    // Calculate profit percentage (profit / amount_in * 100)
-                    let profit_percentage = if amount_in > U256::zero() {
-                        // Convert to f64 for percentage calculation
-                        let profit_f64 = profit.as_u128() as f64;
-                        let amount_in_f64 = amount_in.as_u128() as f64;
-                        (profit_f64 / amount_in_f64) * 100.0
-                    } else {
-                        0.0
-                    };
-
-                    // Merge token indices
-                    // let mut merged_tokens = buy_path.hops.clone();
-                    // merged_tokens.extend_from_slice(&sell_path.hops[1..]);
-
-                    // Map to symbols
-                    // let merged_symbols = merged_tokens
-                    //     .iter()
-                    //     .map(|&idx| token_index_to_symbol_from_price_tracker(idx, token_index))
-                    //     .collect();
-
-                    // Merge pools
-                    let mut merged_pools = buy_path.pools.clone();
-                    merged_pools.extend_from_slice(&sell_path.pools);
-
-                    return Some(crate::arbitrage_finder::SimulatedRoute {
-                        merged_amounts,
-                        buy_amounts,
-                        sell_amounts,
-                        // merged_tokens,
-                        // merged_symbols,
-                        buy_symbols: buy_path
-                            .hops
-                            .iter()
-                            .map(|&idx| token_index_to_symbol_from_price_tracker(idx, token_index))
-                            .collect(),
-                        sell_symbols,
-                            buy_pools: buy_path.pools.clone(),
-                        sell_pools: sell_path.pools.clone(),
-                        merged_pools,
-                        profit,
-                        profit_percentage,
-                        buy_path: buy_path.clone(),
-                        sell_path: sell_path.clone(),
-                        // sell_test_amounts,
-                    });
-                }
-            }
-
-            None
-        })
-        .collect();
-
-    // Filter out None results
-    let profitable_routes: Vec<crate::arbitrage_finder::SimulatedRoute> =
-        simulation_results.into_iter().filter_map(|r| r).collect();
-
-    // println!(
-    //     "💰 [Price Tracker] Found {} profitable routes",
-    //     profitable_routes.len()
-    // );
-
-    if profitable_routes.is_empty() {
-        return None;
-    }
-
-    // Find the most profitable route by percentage (better for multiple base tokens)
-    let best_route = profitable_routes
-        .iter()
-        .max_by(|a, b| a.profit_percentage.partial_cmp(&b.profit_percentage).unwrap_or(std::cmp::Ordering::Equal))
-        .cloned();
-
-    let estimated_profit = best_route
-        .as_ref()
-        .map(|r| r.profit)
-        .unwrap_or(U256::zero());
-
-    // End latency timer
-    let latency = start_time.elapsed().as_millis();
-
-    Some((
-        ArbitrageOpportunity {
-            decoded_swap: decoded_swap.clone(),
-            profitable_routes,
-            best_route,
-            estimated_profit,
-        },
-        latency,
-    ))
-}
-fn u256_to_f64_lossy(val: &U256) -> f64 {
-    if val.bits() <= 128 {
-        val.as_u128() as f64
-    } else {
-        val.to_string().parse::<f64>().unwrap_or(f64::MAX)
-    }
-}
-const KNOWN_TOKENS: &[(&str, &str, f64)] = &[
-    ("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c", "BNB", 689.93),
-    ("0x2170Ed0880ac9A755fd29B2688956BD959F933F8", "ETH", 2961.19),
-    ("0x7130d2A12B9BCbFAe4f2634d864A1Ee1Ce3Ead9c", "BTC", 117970.0),
-    ("0x55d398326f99059fF775485246999027B3197955", "USDT", 1.00),
-    ("0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d", "USDC", 1.00), // Multichain bridge price
-    ("0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56", "BUSD", 1.00),
-    ("0x0E09FaBB73Bd3Ade0a17ECC321fD13a19e81cE82", "CAKE", 2.37),
-];
-
-fn get_token_usd_value(token_address: &H160) -> Option<f64> {
-    let addr_str = format!("0x{:x}", token_address);
-    KNOWN_TOKENS.iter()
-        .find(|(addr, _, _)| addr.to_lowercase() == addr_str.to_lowercase())
-        .map(|(_, _, price)| *price)
-}
-/// Helper to map token index to symbol (price tracker version)
-fn token_index_to_symbol_from_price_tracker(idx: u32, token_index: &TokenIndexMap) -> String {
-    if let Some(addr) = token_index.index_to_address.get(&(idx as u32)) {
-        format!("0x{:x}", addr)
-    } else {
-        format!("token{}", idx)
-    }
-}
-
 // fn log_opportunity_from_price_tracker(
 //     opportunity: &ArbitrageOpportunity,
 //     latency_ms: u128,
@@ -989,8 +1147,8 @@ fn token_index_to_symbol_from_price_tracker(idx: u32, token_index: &TokenIndexMa
 //             "profit_percentage": opportunity.best_route.as_ref().map(|r| r.profit_percentage),
 //             "buy_path_pools": opportunity.best_route.as_ref().map(|r| r.buy_path.pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
 //             "sell_path_pools": opportunity.best_route.as_ref().map(|r| r.sell_path.pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
-//             "buy_symbols": opportunity.best_route.as_ref().map(|r| r.buy_symbols.clone()),
-//             "sell_symbols": opportunity.best_route.as_ref().map(|r| r.sell_symbols.clone()),
+//             "buy_symbols": opportunity.best_route.as_ref().map(|r| r.buy_symbols()),
+//             "sell_symbols": opportunity.best_route.as_ref().map(|r| r.sell_symbols()),
 //             "buy_pools": opportunity.best_route.as_ref().map(|r| r.buy_pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
 //             "sell_pools": opportunity.best_route.as_ref().map(|r| r.sell_pools.iter().map(|p| format!("0x{:x}", p)).collect::<Vec<_>>()),
 //             // "sell_test_amounts": opportunity.best_route.as_ref().map(|r| r.sell_test_amounts.iter().map(|a| a.to_string()).collect::<Vec<_>>()),
@@ -1065,3 +1223,246 @@ fn token_index_to_symbol_from_price_tracker(idx: u32, token_index: &TokenIndexMa
 //     //     log_file_path, latency_ms
 //     // );
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sync_log(pool: H160, block: u64) -> Log {
+        Log {
+            address: pool,
+            block_number: Some(U64::from(block)),
+            block_hash: Some(H256::from_low_u64_be(block)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pending_updates_wait_for_confirmations() {
+        let pool = H160::from_low_u64_be(1);
+        let cache = ReserveCache::new();
+        cache.insert(pool, PoolState::default());
+        let pending = PendingUpdates::new(2);
+
+        let update = |r: u64| PoolUpdate::V2 { reserve0: U256::from(r), reserve1: U256::from(r) };
+        pending.submit(&cache, &sync_log(pool, 100), update(1));
+        pending.submit(&cache, &sync_log(pool, 101), update(2));
+        assert_eq!(cache.get(&pool).unwrap().reserve0, None);
+
+        // Head 101: block 100 has only 1 confirmation
+        assert_eq!(pending.flush_confirmed(&cache, 101), 0);
+        // Head 102: block 100 confirmed, 101 still pending
+        assert_eq!(pending.flush_confirmed(&cache, 102), 1);
+        assert_eq!(cache.get(&pool).unwrap().reserve0, Some(U256::from(1)));
+
+        // Removed log drops the buffered update for block 101
+        pending.discard_removed(&sync_log(pool, 101));
+        assert_eq!(pending.flush_confirmed(&cache, 110), 0);
+        assert_eq!(cache.get(&pool).unwrap().reserve0, Some(U256::from(1)));
+    }
+
+    #[test]
+    fn test_zero_confirmations_applies_immediately() {
+        let pool = H160::from_low_u64_be(1);
+        let cache = ReserveCache::new();
+        cache.insert(pool, PoolState::default());
+        let pending = PendingUpdates::new(0);
+        pending.submit(&cache, &sync_log(pool, 100), PoolUpdate::V2 { reserve0: U256::from(7), reserve1: U256::from(8) });
+        assert_eq!(cache.get(&pool).unwrap().reserve1, Some(U256::from(8)));
+    }
+
+    #[test]
+    fn test_lower_block_update_does_not_roll_back_cache() {
+        let pool = H160::from_low_u64_be(1);
+        let cache = ReserveCache::new();
+        cache.insert(pool, PoolState::default());
+        let pending = PendingUpdates::new(0);
+        let update = |r: u64| PoolUpdate::V2 { reserve0: U256::from(r), reserve1: U256::from(r * 2) };
+
+        pending.submit(&cache, &sync_log(pool, 101), update(5));
+        // A Sync from an earlier (e.g. orphaned, delivered late) block arrives afterwards
+        let ignored = STALE_UPDATES_IGNORED.load(Ordering::Relaxed);
+        pending.submit(&cache, &sync_log(pool, 100), update(1));
+        let state = cache.get(&pool).unwrap().clone();
+        assert_eq!((state.reserve0, state.reserve1), (Some(U256::from(5)), Some(U256::from(10))));
+        assert_eq!(state.last_updated_block, 101);
+        assert!(STALE_UPDATES_IGNORED.load(Ordering::Relaxed) > ignored);
+
+        // Later logs in the same block and newer blocks still apply
+        pending.submit(&cache, &sync_log(pool, 101), update(6));
+        assert_eq!(cache.get(&pool).unwrap().reserve0, Some(U256::from(6)));
+        pending.submit(&cache, &sync_log(pool, 102), update(7));
+        assert_eq!(cache.get(&pool).unwrap().last_updated_block, 102);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_tick_is_skipped_without_panic() {
+        use ethers::abi::{encode, Token};
+        let pool = H160::from_low_u64_be(1);
+        let cache = Arc::new(ReserveCache::new());
+        cache.insert(pool, PoolState { pool_type: PoolType::V3, tick: Some(5), ..Default::default() });
+        let pending = Arc::new(PendingUpdates::new(0));
+
+        // int24 tick field carrying a value far outside the int24 range
+        let data = encode(&[
+            Token::Int(U256::zero()),
+            Token::Int(U256::zero()),
+            Token::Uint(U256::one() << 96),
+            Token::Uint(U256::from(1_000u64)),
+            Token::Int(U256::from(u64::MAX)),
+        ]);
+        let log = Log {
+            address: pool,
+            topics: vec![*UNISWAP_V3_SWAP_TOPIC],
+            data: data.into(),
+            ..sync_log(pool, 100)
+        };
+        assert!(handle_v3_swap_event_with_arbitrage(log, &cache, &pending).await.is_err());
+        assert_eq!(cache.get(&pool).unwrap().tick, Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_random_log_payloads_never_panic() {
+        use rand::{Rng, RngCore};
+        let mut rng = rand::thread_rng();
+        let pool = H160::from_low_u64_be(1);
+        let cache = Arc::new(ReserveCache::new());
+        cache.insert(pool, PoolState::default());
+        let pending = Arc::new(PendingUpdates::new(0));
+
+        for _ in 0..500 {
+            let mut data = vec![0u8; rng.gen_range(0..256)];
+            rng.fill_bytes(&mut data);
+            let topics = match rng.gen_range(0..3) {
+                0 => vec![],
+                1 => vec![*UNISWAP_V3_SWAP_TOPIC],
+                _ => vec![*PANCAKE_V3_SWAP_TOPIC],
+            };
+            let log = Log { topics, data: data.into(), ..sync_log(pool, 100) };
+            let v2_ok = handle_v2_sync_event_with_arbitrage(log.clone(), &cache, &pending).await.is_ok();
+            assert_eq!(v2_ok, log.data.0.len() >= 64);
+            let _ = handle_v3_swap_event_with_arbitrage(log, &cache, &pending).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_v2_swap_log_gives_bought_token_and_amount() {
+        // PancakeSwap V2 WBNB/BUSD: a router swap of 1.5 WBNB in for 862.4 BUSD out
+        let pool: H160 = "0x58F876857a02D6762E0101bb5C46A8c1ED44Dc16".parse().unwrap();
+        let wbnb: H160 = "0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c".parse().unwrap();
+        let busd: H160 = "0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56".parse().unwrap();
+        let router: H160 = "0x10ED43C718714eb63d5aA57B78B54704E256024E".parse().unwrap();
+        let data = hex::decode(concat!(
+            "00000000000000000000000000000000000000000000000014d1120d7b160000", // amount0In
+            "0000000000000000000000000000000000000000000000000000000000000000", // amount1In
+            "0000000000000000000000000000000000000000000000000000000000000000", // amount0Out
+            "00000000000000000000000000000000000000000000002ec03446cf18e00000", // amount1Out
+        ))
+        .unwrap();
+        let log = Log {
+            topics: vec![*V2_SWAP_TOPIC, H256::from(router), H256::from(H160::from_low_u64_be(0xbeef))],
+            data: data.into(),
+            ..sync_log(pool, 100)
+        };
+
+        let cache = Arc::new(ReserveCache::new());
+        assert!(decoded_swap_from_v2_log(&log, &cache).unwrap().is_none(), "pool not cached yet");
+        let reserves = (U256::from(10u64).pow(24.into()), U256::from(10u64).pow(27.into()));
+        cache.insert(pool, PoolState {
+            token0: wbnb,
+            token1: busd,
+            reserve0: Some(reserves.0),
+            reserve1: Some(reserves.1),
+            ..PoolState::default()
+        });
+
+        let swap = decoded_swap_from_v2_log(&log, &cache).unwrap().unwrap();
+        assert_eq!(swap.pool_address, pool);
+        assert_eq!(swap.token_x, busd);
+        assert_eq!(swap.token_x_amount, U256::from_dec_str("862400000000000000000").unwrap());
+
+        // A Swap log only reports the trade; the reserves are left to the Sync that precedes it
+        // It is handed to detection as is
+        let pending = Arc::new(PendingUpdates::new(0));
+        let (swap_tx, mut swap_rx) = mpsc::channel(1);
+        handle_v2_log(log.clone(), &cache, &pending, Some(&swap_tx)).await.unwrap();
+        assert_eq!(cache.get(&pool).unwrap().reserve0, Some(reserves.0));
+        let sent = swap_rx.try_recv().unwrap();
+        assert_eq!((sent.pool_address, sent.token_x, sent.token_x_amount), (pool, busd, swap.token_x_amount));
+        assert!(decoded_swap_from_v2_log(&Log { data: vec![0u8; 96].into(), ..log }, &cache).is_err());
+    }
+
+    /// Stands in for a WS provider whose first `failures` subscribe attempts fail
+    struct FlakyWs {
+        failures: usize,
+        subscribes: AtomicUsize,
+    }
+
+    impl FlakyWs {
+        fn new(failures: usize) -> Self {
+            Self { failures, subscribes: AtomicUsize::new(0) }
+        }
+
+        async fn session(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            if self.subscribes.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err("Failed to subscribe to V2 Sync events: connection reset".into());
+            }
+            Ok(())
+        }
+
+        async fn connect(&self) -> Result<usize, (ErrorClass, anyhow::Error)> {
+            let attempt = self.subscribes.fetch_add(1, Ordering::SeqCst);
+            if attempt < self.failures {
+                return Err((ErrorClass::Retryable, anyhow::anyhow!("connection refused")));
+            }
+            Ok(attempt)
+        }
+    }
+
+    fn instant_retries(max_retries: u32) -> RetryPolicy {
+        RetryPolicy { max_retries, initial_backoff_secs: 0, max_backoff_secs: 0, rate_limit_backoff_factor: 1 }
+    }
+
+    #[tokio::test]
+    async fn test_session_resubscribes_until_subscribe_succeeds() {
+        let ws = FlakyWs::new(3);
+        let result = run_session_with_retries("V2", &instant_retries(5), || ws.session(), || async { true }).await;
+        assert!(result.is_ok());
+        assert_eq!(ws.subscribes.load(Ordering::SeqCst), 4);
+
+        // Out of retries: the loop gives up on the endpoint
+        let ws = FlakyWs::new(10);
+        let result = run_session_with_retries("V2", &instant_retries(5), || ws.session(), || async { true }).await;
+        assert!(result.is_err());
+        assert_eq!(ws.subscribes.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_dead_connection_is_handed_back_without_retrying() {
+        let ws = FlakyWs::new(3);
+        let result = run_session_with_retries("V3", &instant_retries(5), || ws.session(), || async { false }).await;
+        assert!(result.is_ok(), "a dead socket means reconnect, not failover");
+        assert_eq!(ws.subscribes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_backs_off_then_gives_up() {
+        let ws = FlakyWs::new(2);
+        assert_eq!(reconnect_with_backoff(&instant_retries(4), || ws.connect()).await.unwrap(), 2);
+
+        let ws = FlakyWs::new(usize::MAX);
+        let err = reconnect_with_backoff(&instant_retries(4), || ws.connect()).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("gave up after 4 attempts"), "{:#}", err);
+        assert_eq!(ws.subscribes.load(Ordering::SeqCst), 4);
+
+        // Fatal errors (bad key, wrong URL) aren't retried
+        let attempts = &AtomicUsize::new(0);
+        let err = reconnect_with_backoff(&instant_retries(4), || async move {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>((ErrorClass::Fatal, anyhow::anyhow!("401 unauthorized")))
+        })
+        .await;
+        assert!(err.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::cache::reserve_version;
+use crate::route_cache::RoutePath;
+use ethers::types::U256;
+use lru::LruCache;
+use once_cell::sync::OnceCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Significant bits kept when bucketing a quote amount (rounds down by less than 2^-19)
+const BUCKET_BITS: usize = 20;
+/// Independently locked LRUs, so parallel route simulation rarely contends
+const SHARDS: usize = 16;
+
+/// Which quoting function a cached result came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuoteKind {
+    ExactIn,  // `quote_exact_in` / sell leg
+    ExactOut, // `quote_exact_out` / buy leg
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuoteKey {
+    pub route_id: u64,
+    pub kind: QuoteKind,
+    pub amount_bucket: U256,
+    pub reserve_version: u64,
+}
+
+/// Round `amount` down to its top `BUCKET_BITS` significant bits so nearby amounts share a quote
+pub fn bucket_amount(amount: U256) -> U256 {
+    let bits = amount.bits();
+    if bits <= BUCKET_BITS {
+        return amount;
+    }
+    let shift = bits - BUCKET_BITS;
+    (amount >> shift) << shift
+}
+
+/// Id of a route (or leg) from its hops and pools
+pub fn route_id(route: &RoutePath) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    route.hops.hash(&mut hasher);
+    route.pools.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// LRU of route quotes keyed by `(route, kind, amount bucket, reserve version)`. Any reserve
+/// update bumps the version, which retires every older entry without touching the cache.
+pub struct QuoteCache {
+    shards: Vec<Mutex<LruCache<QuoteKey, Option<Vec<U256>>>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl QuoteCache {
+    /// `capacity` entries in total, spread over the shards
+    pub fn new(capacity: usize) -> Self {
+        let per_shard = NonZeroUsize::new(capacity.div_ceil(SHARDS).max(1)).expect("non-zero shard size");
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::new(LruCache::new(per_shard))).collect(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Quote `amount` along `route` with `quote(amount, amounts)`, or serve the result cached at
+    /// `reserve_version`. The amount is bucketed first, so the amounts written are those of the
+    /// bucketed amount. Failed quotes (None) are cached too.
+    pub fn quote_into(
+        &self,
+        route: &RoutePath,
+        kind: QuoteKind,
+        amount: U256,
+        reserve_version: u64,
+        amounts: &mut Vec<U256>,
+        quote: impl FnOnce(U256, &mut Vec<U256>) -> Option<()>,
+    ) -> Option<()> {
+        let key = QuoteKey { route_id: route_id(route), kind, amount_bucket: bucket_amount(amount), reserve_version };
+        let shard = &self.shards[key.route_id as usize % SHARDS];
+        if let Some(cached) = shard.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            amounts.clear();
+            return cached.as_ref().map(|cached| amounts.extend_from_slice(cached));
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let result = quote(key.amount_bucket, amounts);
+        let entry = result.map(|_| amounts.clone());
+        shard.lock().unwrap_or_else(|e| e.into_inner()).put(key, entry);
+        result
+    }
+
+    /// (hits, misses) since startup
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+
+    /// Fraction of lookups served from the cache
+    pub fn hit_rate(&self) -> f64 {
+        let (hits, misses) = self.stats();
+        if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 }
+    }
+}
+
+/// Quote cache in front of the live reserve cache; unset when disabled
+static LIVE_QUOTES: OnceCell<QuoteCache> = OnceCell::new();
+
+/// Enable quote caching against the live reserve cache (0 = disabled). Call once at startup.
+pub fn init_live_quotes(capacity: usize) {
+    if capacity > 0 {
+        let _ = LIVE_QUOTES.set(QuoteCache::new(capacity));
+    }
+}
+
+pub fn live_quotes() -> Option<&'static QuoteCache> {
+    LIVE_QUOTES.get()
+}
+
+/// Quote against the live reserve cache through `LIVE_QUOTES`, or directly when it's disabled.
+/// Only for quotes read from the live cache: snapshots don't bump the reserve version.
+pub fn quote_live(
+    route: &RoutePath,
+    kind: QuoteKind,
+    amount: U256,
+    amounts: &mut Vec<U256>,
+    quote: impl FnOnce(U256, &mut Vec<U256>) -> Option<()>,
+) -> Option<()> {
+    match live_quotes() {
+        Some(cache) => cache.quote_into(route, kind, amount, reserve_version(), amounts, quote),
+        None => quote(amount, amounts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::bump_reserve_version;
+    use crate::simulate_swap_path::simulate_sell_path_amounts_into;
+    use crate::testkit::{pool_address, pool_v2, token, units, MarketBuilder};
+
+    #[test]
+    fn test_bucket_amount() {
+        assert_eq!(bucket_amount(U256::from(1_000u64)), U256::from(1_000u64));
+        let amount = units(1) + U256::from(12_345u64);
+        let bucket = bucket_amount(amount);
+        assert!(bucket <= amount && amount - bucket < amount >> (BUCKET_BITS - 1));
+        // Amounts that only differ below the kept bits share a bucket
+        assert_eq!(bucket_amount(units(1)), bucket);
+        assert_ne!(bucket_amount(units(2)), bucket);
+    }
+
+    #[test]
+    fn test_cached_quote_is_never_served_after_a_reserve_update() {
+        let (base, x) = (token(1), token(2));
+        let (p1, p2) = (pool_address(1), pool_address(2));
+        let market = MarketBuilder::new()
+            .pool(p1, pool_v2(base, x, units(1_000), units(2_000)))
+            .pool(p2, pool_v2(x, base, units(2_000), units(1_100)))
+            .build();
+        let route = market.route(&[base, x, base], &[p1, p2]);
+        let quotes = QuoteCache::new(64);
+        let mut computed = 0;
+        let mut quote = |version: u64, amounts: &mut Vec<U256>| {
+            quotes.quote_into(&route, QuoteKind::ExactIn, units(1), version, amounts, |amount, out| {
+                computed += 1;
+                simulate_sell_path_amounts_into(
+                    &route,
+                    amount,
+                    &market.reserve_cache,
+                    &market.token_index,
+                    &market.token_tax_map,
+                    &market.config,
+                    out,
+                )
+            })
+        };
+
+        let mut first = Vec::new();
+        let version = reserve_version();
+        quote(version, &mut first).unwrap();
+        let mut again = Vec::new();
+        quote(version, &mut again).unwrap();
+        assert_eq!(first, again);
+
+        // The second pool's reserves move; the next lookup must see them
+        market.reserve_cache.get_mut(&p2).unwrap().reserve1 = Some(units(900));
+        bump_reserve_version();
+        let mut updated = Vec::new();
+        quote(reserve_version(), &mut updated).unwrap();
+        assert!(updated.last() < first.last());
+        drop(quote);
+        assert_eq!(computed, 2);
+        assert_eq!(quotes.stats(), (1, 2));
+    }
+
+    #[test]
+    fn test_failed_quotes_are_cached() {
+        let quotes = QuoteCache::new(1);
+        let route = RoutePath::default();
+        let mut amounts = Vec::new();
+        assert!(quotes.quote_into(&route, QuoteKind::ExactOut, units(1), 7, &mut amounts, |_, _| None).is_none());
+        let served = quotes.quote_into(&route, QuoteKind::ExactOut, units(1), 7, &mut amounts, |_, _| {
+            panic!("cached failure should be served")
+        });
+        assert!(served.is_none());
+        assert_eq!(quotes.hit_rate(), 0.5);
+    }
+}
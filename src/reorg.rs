@@ -0,0 +1,153 @@
+use ethers::types::{H160, H256, Log};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+
+/// Number of recent blocks tracked for reorg detection.
+///
+/// Assumption: BSC (Parlia + fast finality) rarely reorgs more than a couple of blocks,
+/// so a reorg deeper than this window is treated as out of scope here; the periodic
+/// cache consistency check is the backstop for anything older.
+pub const REORG_DEPTH: u64 = 15;
+
+#[derive(Default)]
+struct ReorgState {
+    /// block number -> (block hash, pools updated from logs in that block)
+    blocks: BTreeMap<u64, (Option<H256>, HashSet<H160>)>,
+    /// Pools waiting to be re-synced from the canonical chain
+    dirty: HashSet<H160>,
+}
+
+/// Tracks recent block hashes and the pools touched in each block so that
+/// cache updates from orphaned blocks can be undone by re-fetching state.
+#[derive(Default)]
+pub struct ReorgTracker {
+    state: Mutex<ReorgState>,
+}
+
+impl ReorgTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a log before applying it to the cache.
+    /// Returns `false` if the log was removed (reorged out) and must not be applied;
+    /// the pool is then queued for re-sync instead.
+    pub fn record_log(&self, log: &Log) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if log.removed == Some(true) {
+            println!("[REORG] Removed log for pool {:?} (block {:?})", log.address, log.block_number);
+            state.dirty.insert(log.address);
+            return false;
+        }
+        let Some(number) = log.block_number.map(|n| n.as_u64()) else {
+            return true;
+        };
+        let entry = state.blocks.entry(number).or_insert_with(|| (log.block_hash, HashSet::new()));
+        if log.block_hash.is_some() && entry.0.is_some() && entry.0 != log.block_hash {
+            // Same height, different hash: the earlier logs at this height came from an orphaned block
+            let orphaned: Vec<H160> = entry.1.drain().collect();
+            entry.0 = log.block_hash;
+            entry.1.insert(log.address);
+            println!("[REORG] Block {} hash changed, re-syncing {} pools", number, orphaned.len());
+            state.dirty.extend(orphaned);
+        } else {
+            if entry.0.is_none() {
+                entry.0 = log.block_hash;
+            }
+            entry.1.insert(log.address);
+        }
+        true
+    }
+
+    /// Feed a new head. If its parent (or the head itself) doesn't match the hash we
+    /// recorded, every pool touched within the last `REORG_DEPTH` blocks is queued for re-sync.
+    pub fn on_new_head(&self, number: u64, hash: H256, parent_hash: H256) {
+        let mut state = self.state.lock().unwrap();
+        let parent_mismatch = number > 0
+            && matches!(state.blocks.get(&(number - 1)), Some((Some(h), _)) if *h != parent_hash);
+        let head_mismatch = matches!(state.blocks.get(&number), Some((Some(h), _)) if *h != hash);
+
+        if parent_mismatch || head_mismatch {
+            let touched: HashSet<H160> = state
+                .blocks
+                .range(number.saturating_sub(REORG_DEPTH)..)
+                .flat_map(|(_, (_, pools))| pools.iter().copied())
+                .collect();
+            println!("[REORG] Reorg detected at block {}, re-syncing {} pools", number, touched.len());
+            state.dirty.extend(touched);
+            // Anything at or above the new head's parent is no longer trustworthy
+            state.blocks.retain(|n, _| *n + 1 < number);
+        }
+
+        let entry = state.blocks.entry(number).or_insert_with(|| (None, HashSet::new()));
+        entry.0 = Some(hash);
+        if number > 0 {
+            state.blocks.entry(number - 1).or_insert_with(|| (None, HashSet::new())).0 = Some(parent_hash);
+        }
+
+        // Prune blocks past the finality window
+        let cutoff = number.saturating_sub(REORG_DEPTH);
+        state.blocks.retain(|n, _| *n >= cutoff);
+    }
+
+    /// Take the set of pools that need re-syncing from chain
+    pub fn take_dirty(&self) -> Vec<H160> {
+        self.state.lock().unwrap().dirty.drain().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U64;
+
+    fn log_at(pool: H160, number: u64, hash: H256) -> Log {
+        Log {
+            address: pool,
+            block_number: Some(U64::from(number)),
+            block_hash: Some(hash),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_removed_log_marks_pool_dirty() {
+        let tracker = ReorgTracker::new();
+        let pool = H160::from_low_u64_be(1);
+        let mut log = log_at(pool, 100, H256::from_low_u64_be(100));
+        log.removed = Some(true);
+        assert!(!tracker.record_log(&log));
+        assert_eq!(tracker.take_dirty(), vec![pool]);
+        assert!(tracker.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn test_parent_hash_mismatch_resyncs_touched_pools() {
+        let tracker = ReorgTracker::new();
+        let pool_a = H160::from_low_u64_be(1);
+        let pool_b = H160::from_low_u64_be(2);
+        tracker.on_new_head(100, H256::from_low_u64_be(100), H256::from_low_u64_be(99));
+        assert!(tracker.record_log(&log_at(pool_a, 100, H256::from_low_u64_be(100))));
+        assert!(tracker.record_log(&log_at(pool_b, 100, H256::from_low_u64_be(100))));
+
+        // Canonical head continues normally: nothing to re-sync
+        tracker.on_new_head(101, H256::from_low_u64_be(101), H256::from_low_u64_be(100));
+        assert!(tracker.take_dirty().is_empty());
+
+        // New 102 whose parent isn't the 101 we saw
+        tracker.on_new_head(102, H256::from_low_u64_be(1102), H256::from_low_u64_be(1101));
+        let mut dirty = tracker.take_dirty();
+        dirty.sort();
+        assert_eq!(dirty, vec![pool_a, pool_b]);
+    }
+
+    #[test]
+    fn test_same_height_different_hash_resyncs() {
+        let tracker = ReorgTracker::new();
+        let pool_a = H160::from_low_u64_be(1);
+        let pool_b = H160::from_low_u64_be(2);
+        assert!(tracker.record_log(&log_at(pool_a, 100, H256::from_low_u64_be(100))));
+        assert!(tracker.record_log(&log_at(pool_b, 100, H256::from_low_u64_be(999))));
+        assert_eq!(tracker.take_dirty(), vec![pool_a]);
+    }
+}
@@ -20,15 +20,18 @@ use serde::Serialize;
 use std::fs::OpenOptions;
 use std::{marker::PhantomData, time::Instant};
 // use revm::inspector::InspectorHandler;
-use crate::mempool_decoder::{ArbitrageOpportunity, DecodedSwap};
+use crate::opportunity::{ArbitrageOpportunity, DecodedSwap};
+use crate::log_maintenance::log_path;
 use crate::route_cache::RoutePath;
+use crate::sim_trace::{fork_db_at, trace_has_dex_event, CallTraceNode, TraceLog, DEX_EVENT_TOPICS, PANCAKE_V3_TOPIC, SWAP_V2_FORK_TOPIC, SWAP_V2_TOPIC, SWAP_V3_TOPIC, SYNC_V2_TOPIC};
 use crate::token_index::TokenIndexMap;
 use crate::token_tax::TokenTaxMap;
+use crate::v2_swap::{classify_sync, SyncChange};
+use crate::v3_swap::{decode_v3_swap, record_v3_volume};
 use crate::{
-    cache::ReserveCache,
+    cache::{bump_reserve_version, ReserveCache},
+    arbitrage_finder::find_arbitrage_opportunity,
     config::Config,
-    simulate_swap_path::{simulate_buy_path_amounts_array, simulate_sell_path_amounts_array},
-    split_route_path::split_route_around_token_x,
 };
 use alloy_eips::BlockId;
 use alloy_primitives::keccak256;
@@ -36,9 +39,8 @@ use alloy_provider::{DynProvider, Provider, ProviderBuilder, network::Ethereum};
 use dashmap::DashMap;
 use ethers::abi::{ParamType, Token};
 use ethers::types::Transaction;
-use ethers::types::{Bytes as eBytes, H160, U256 as eU256};
+use ethers::types::{Bytes as eBytes, H160, H256, I256, U256 as eU256};
 use once_cell::sync::Lazy;
-use rayon::prelude::*;
 use revm::bytecode::Bytecode;
 use revm::database::{AlloyDB, WrapDatabaseAsync};
 use revm::primitives::B256;
@@ -55,7 +57,7 @@ use tokio::sync::mpsc;
 #[derive(Debug, Clone, Serialize)]
 pub struct SimLog {
     pub address: Vec<u8>,
-    pub topics: Vec<Vec<u8>>, // TODO: topics extraction not possible due to private field
+    pub topics: Vec<Vec<u8>>,
     pub data: Vec<u8>,
 }
 
@@ -66,62 +68,6 @@ pub struct SimResult {
     pub output: Option<Vec<u8>>,
     pub logs: Vec<SimLog>,
 }
-pub fn parse_logdata_string2(logdata_bytes: &[u8]) -> (Vec<String>, String) {
-    let logdata = String::from_utf8_lossy(logdata_bytes);
-
-    // Find the "LogData {" substring
-    let logdata_start = match logdata.find("LogData {") {
-        Some(idx) => idx,
-        None => return (vec![], String::new()),
-    };
-
-    // Find the closing '}' for LogData { ... }
-    let mut brace_count = 0;
-    let mut end_idx = None;
-    for (i, c) in logdata[logdata_start..].char_indices() {
-        if c == '{' {
-            brace_count += 1;
-        } else if c == '}' {
-            brace_count -= 1;
-            if brace_count == 0 {
-                end_idx = Some(logdata_start + i + 1);
-                break;
-            }
-        }
-    }
-    let logdata_sub = match end_idx {
-        Some(end) => &logdata[logdata_start..end],
-        None => &logdata[logdata_start..],
-    };
-
-    // Now parse topics and data as before, but only in logdata_sub
-    let topics_start = match logdata_sub.find("topics: [") {
-        Some(idx) => idx + 9,
-        None => return (vec![], String::new()),
-    };
-    let topics_end = match logdata_sub[topics_start..].find("]") {
-        Some(rel_idx) => topics_start + rel_idx,
-        None => return (vec![], String::new()),
-    };
-    let topics_str = &logdata_sub[topics_start..topics_end];
-    let topics: Vec<String> = topics_str
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| s.starts_with("0x"))
-        .collect();
-
-    let data_start = match logdata_sub.find("data: ") {
-        Some(idx) => idx + 6,
-        None => return (topics, String::new()),
-    };
-    let data_end = logdata_sub[data_start..]
-        .find('}')
-        .map(|i| data_start + i)
-        .unwrap_or(logdata_sub.len());
-    let data_hex = logdata_sub[data_start..data_end].trim().to_string();
-
-    (topics, data_hex)
-}
 /// Helper to parse stringified LogData from SimLog.data and extract topics/data as hex strings.
 pub fn parse_logdata_string(logdata_bytes: &[u8]) -> (Vec<String>, String) {
     let logdata = String::from_utf8_lossy(logdata_bytes);
@@ -160,10 +106,18 @@ pub fn parse_logdata_string(logdata_bytes: &[u8]) -> (Vec<String>, String) {
     (topics, data_hex)
 }
 
-/// Pretty-print all logs in a SimResult, extracting topics/data from stringified LogData.
+/// Pretty-print all logs in a SimResult. Logs without topics are treated as legacy
+/// stringified LogData and parsed back.
 pub fn print_simresult_logs(sim_result: &SimResult) {
     for (i, sim_log) in sim_result.logs.iter().enumerate() {
-        let (topics, data_hex) = parse_logdata_string(&sim_log.data);
+        let (topics, data_hex) = if sim_log.topics.is_empty() {
+            parse_logdata_string(&sim_log.data)
+        } else {
+            (
+                sim_log.topics.iter().map(|t| format!("0x{}", hex::encode(t))).collect(),
+                format!("0x{}", hex::encode(&sim_log.data)),
+            )
+        };
         println!("Log #{}", i);
         println!("  Address: 0x{}", hex::encode(&sim_log.address));
         for (j, topic) in topics.iter().enumerate() {
@@ -465,8 +419,8 @@ impl RevmSimulator {
                 for log in ev_logs {
                     logs.push(SimLog {
                         address: log.address.0.to_vec(),
-                        topics: vec![], // TODO: REVM log.topics is private; cannot extract topics until REVM exposes them
-                        data: format!("{:?}", log.data).into_bytes(), // TODO: log.data is private; cannot extract raw bytes until REVM exposes them
+                        topics: log.data.topics().iter().map(|t| t.to_vec()).collect(),
+                        data: log.data.data.to_vec(),
                     });
                 }
             }
@@ -565,24 +519,39 @@ impl RevmSimulator {
     /// All contract code, storage, balances, etc. are fetched live from the node.
     /// provider_url: HTTP/WS endpoint of your BSC node (e.g. http://localhost:8545)
     /// This is the recommended forking pattern as per REVM examples.
-    ///
+    /// Forks the latest block; use `simulate_with_forked_state_at` to pin one.
     pub async fn simulate_with_forked_state(
         &self,
         tx_env: TxEnv,
         provider: Arc<DynProvider>,
     ) -> anyhow::Result<Option<CallTraceNode>> {
-        // 1. Setup alloy provider
-        // let provider: DynProvider = ProviderBuilder::new().connect(provider_url).await?.erased();
-        // 1.1 Fetch block number from provider
-        // let block_number = provider.get_block_number().await?;
-        // println!("[DEBUG] Forked state at block number: {}", block_number);
-        // 2. Setup AlloyDB (forking DB) at this block
-        // let block_id = BlockId::Number(block_number.into());
-        // println!("[DEBUG] Using BlockId for fork: {:?}", block_id);
-        let alloy_db =
-            WrapDatabaseAsync::new(AlloyDB::new((provider).as_ref().clone(), BlockId::latest()))
-                .unwrap();
-        let mut cache_db = CacheDB::new(alloy_db);
+        self.simulate_with_forked_state_at(tx_env, provider, BlockId::latest())
+            .await
+    }
+
+    /// Same as `simulate_with_forked_state`, but all state is read at `block` and the block
+    /// env (number, timestamp, basefee) is taken from its header, so a past opportunity can
+    /// be replayed exactly ("why did this revert?") and backtests are reproducible.
+    /// Pinning a historical block needs an archive node once it's outside the node's state window.
+    pub async fn simulate_with_forked_state_at(
+        &self,
+        tx_env: TxEnv,
+        provider: Arc<DynProvider>,
+        block: BlockId,
+    ) -> anyhow::Result<Option<CallTraceNode>> {
+        // 1. Resolve the pinned block's header (latest keeps the default block env)
+        let header = if block == BlockId::latest() {
+            None
+        } else {
+            let fork_block = provider
+                .get_block(block)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("fork block {:?} not found", block))?;
+            Some(fork_block.header)
+        };
+        // 2. Setup AlloyDB (forking DB) at this block; every basic/storage/code lookup
+        //    is answered as of `block`, not the node's current head
+        let mut cache_db = fork_db_at(provider.as_ref().clone(), block)?;
         // --- Debug: Print contract code length for 'to' address ---
         if let Some(to_addr) = match &tx_env.kind {
             revm::primitives::TxKind::Call(addr) => Some(*addr),
@@ -605,6 +574,11 @@ impl RevmSimulator {
         // 3. Setup REVM context with CacheDB
         let mut ctx = Context::mainnet().with_db(cache_db);
         ctx.cfg.disable_nonce_check = true;
+        if let Some(header) = header {
+            ctx.block.number = header.number;
+            ctx.block.timestamp = header.timestamp;
+            ctx.block.basefee = header.base_fee_per_gas.unwrap_or_default();
+        }
         // Print current block number for debug
         // println!("[DEBUG] Simulating at block number: {}", ctx.block.number);
         // 4. Setup EVM (MyEvm or direct)
@@ -686,6 +660,8 @@ impl RevmSimulator {
     let tx_env = ...; // Build TxEnv from ethers tx
     let sim = RevmSimulator::new();
     let sim_result = sim.simulate_with_forked_state(tx_env, "http://localhost:8545")?;
+    // Replay against the block an opportunity was detected at
+    let trace = sim.simulate_with_forked_state_at(tx_env, provider, BlockId::number(detected_block)).await?;
     print_simresult_logs(&sim_result);
     */
 }
@@ -706,8 +682,7 @@ pub fn print_full_call_trace(node: &CallTraceNode, indent: usize) {
         println!("{}  Output: {}", pad, hex::encode(output));
     }
     for (i, log) in node.logs.iter().enumerate() {
-        // Try to parse topics/data from debug string if possible
-        let (topics, data_hex) = parse_logdata_string(&log.data);
+        let (topics, data_hex) = log.topics_and_data_hex();
         println!(
             "{}  Log #{}: address 0x{}",
             pad,
@@ -727,27 +702,7 @@ pub fn print_full_call_trace(node: &CallTraceNode, indent: usize) {
 // --- Internal Call Trace Tracer ---
 // use revm::inspector::Inspector;
 use revm::interpreter::Interpreter;
-use revm::primitives::{Address, Bytes, Log};
-
-#[derive(Debug, Clone)]
-pub struct CallTraceNode {
-    pub call_type: String,
-    pub from: Address,
-    pub to: Address,
-    pub value: B256,
-    pub input: Bytes,
-    pub output: Option<Bytes>,
-    pub depth: usize,
-    pub children: Vec<CallTraceNode>,
-    pub logs: Vec<TraceLog>,
-}
-
-#[derive(Debug, Clone)]
-pub struct TraceLog {
-    pub address: Address,
-    pub topics: Vec<B256>,
-    pub data: Bytes,
-}
+use revm::primitives::Log;
 
 #[derive(Debug, Default)]
 pub struct MyTracer {
@@ -808,13 +763,8 @@ where
         _ctx: &mut revm::Context<BlockEnv, TxEnv, CfgEnv, DB>,
         log: Log,
     ) {
-        // Store actual log data bytes for decoding
         if let Some(node) = self.current_stack.last_mut() {
-            node.logs.push(TraceLog {
-                address: log.address,
-                topics: vec![], // not used
-                data: Bytes::from(format!("{:?}", log).into_bytes()),
-            });
+            node.logs.push(TraceLog::from(log));
         }
     }
 }
@@ -852,27 +802,6 @@ pub fn print_call_trace(node: &CallTraceNode, indent: usize) {
     }
 }
 
-pub static DEX_EVENT_TOPICS: Lazy<HashSet<B256>> = Lazy::new(|| {
-    let mut set = HashSet::new();
-    set.insert(B256::from_slice(
-        keccak256("Swap(address,address,uint256,uint256,uint256,uint256,address)").as_slice(),
-    ));
-    set.insert(B256::from_slice(
-        keccak256("Swap(address,uint256,uint256,uint256,uint256,address)").as_slice(),
-    ));
-    set.insert(B256::from_slice(
-        keccak256("Sync(uint112,uint112)").as_slice(),
-    ));
-    set.insert(B256::from_slice(
-        keccak256("Swap(address,address,int256,int256,uint160,uint128,int24)").as_slice(),
-    ));
-    set.insert(B256::from_slice(
-        keccak256("Swap(address,address,int256,int256,uint160,uint128,int24,uint128,uint128)")
-            .as_slice(),
-    ));
-    set
-});
-
 static SWAP_V2_BROADCAST: Lazy<broadcast::Sender<String>> = Lazy::new(|| {
     // 1024 message buffer
     let (tx, _rx) = broadcast::channel(1024);
@@ -903,6 +832,15 @@ pub async fn start_ipc_broadcast(path: &str) {
     });
 }
 
+/// First `N` decoded tokens as uints, or `None` if there are fewer or any isn't a uint
+fn uint_tokens<const N: usize>(tokens: &[ethers::abi::Token]) -> Option<[eU256; N]> {
+    let mut out = [eU256::zero(); N];
+    for (slot, token) in out.iter_mut().zip(tokens.get(..N)?) {
+        *slot = token.clone().into_uint()?;
+    }
+    Some(out)
+}
+
 fn decode_and_print_swap_v2(data_hex: &str, pool: H160, reserve_cache: &Arc<ReserveCache>) {
     if let Ok(data_bytes) = hex::decode(data_hex.trim_start_matches("0x")) {
         let param_types = vec![
@@ -912,10 +850,13 @@ fn decode_and_print_swap_v2(data_hex: &str, pool: H160, reserve_cache: &Arc<Rese
             ParamType::Uint(256), // amount1Out
         ];
         if let Ok(tokens) = ethers::abi::decode(&param_types, &data_bytes) {
-            let amount0_in = tokens[0].clone().into_uint().unwrap();
-            let amount1_in = tokens[1].clone().into_uint().unwrap();
-            let amount0_out = tokens[2].clone().into_uint().unwrap();
-            let amount1_out = tokens[3].clone().into_uint().unwrap();
+            let [amount0_in, amount1_in, amount0_out, amount1_out] = match uint_tokens::<4>(&tokens) {
+                Some(amounts) => amounts,
+                None => {
+                    eprintln!("[V2 DECODE] ❌ Skipping malformed Swap log from {:?}", pool);
+                    return;
+                }
+            };
             println!("      amount0In:   {}", amount0_in);
             println!("      amount1In:  {}", amount1_in);
             println!("      amount0Out: {}", amount0_out);
@@ -931,9 +872,9 @@ async fn decode_and_print_sync_v2(
     reserve_cache: &Arc<ReserveCache>,
     block_number: u64,
     token_index: &Arc<TokenIndexMap>,
-    precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     token_tax_map: &Arc<TokenTaxMap>,
-    config: &Config,
+    config: &Arc<Config>,
     opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
 ) {
     if let Ok(data_bytes) = hex::decode(data_hex.trim_start_matches("0x")) {
@@ -942,20 +883,24 @@ async fn decode_and_print_sync_v2(
             ParamType::Uint(112), // reserve1
         ];
         if let Ok(tokens) = ethers::abi::decode(&param_types, &data_bytes) {
-            let new_reserve0 = tokens[0].clone().into_uint().unwrap();
-            let new_reserve1 = tokens[1].clone().into_uint().unwrap();
+            let [new_reserve0, new_reserve1] = match uint_tokens::<2>(&tokens) {
+                Some(reserves) => reserves,
+                None => {
+                    eprintln!("[V2 DECODE] ❌ Skipping malformed Sync log from {:?}", pool);
+                    return;
+                }
+            };
             println!("      reserve0:   {}", new_reserve0);
             println!("      reserve1:   {}", new_reserve1);
             // --- CACHE UPDATE ---
-            // Get old reserves before updating
-            let old_reserve0 = reserve_cache
-                .get(&pool)
-                .and_then(|s| s.reserve0)
-                .unwrap_or(eU256::zero());
-            let old_reserve1 = reserve_cache
+            // Get old reserves before updating; without them there's no direction to infer
+            let Some((old_reserve0, old_reserve1, token0, token1)) = reserve_cache
                 .get(&pool)
-                .and_then(|s| s.reserve1)
-                .unwrap_or(eU256::zero());
+                .and_then(|s| Some((s.reserve0?, s.reserve1?, s.token0, s.token1)))
+            else {
+                println!("      [CACHE UPDATE] ❌ FAILED - Pool not found in cache: {:?}", pool);
+                return;
+            };
             
             // Print cache state BEFORE update
             println!("      [CACHE BEFORE] Pool: {:?}", pool);
@@ -968,6 +913,7 @@ async fn decode_and_print_sync_v2(
                 state.reserve0 = Some(new_reserve0);
                 state.reserve1 = Some(new_reserve1);
                 state.last_updated = chrono::Utc::now().timestamp() as u64;
+                bump_reserve_version();
                 
                 // Print cache state AFTER update
                 println!("      [CACHE AFTER] Pool: {:?}", pool);
@@ -978,24 +924,16 @@ async fn decode_and_print_sync_v2(
             } else {
                 println!("      [CACHE UPDATE] ❌ FAILED - Pool not found in cache: {:?}", pool);
             }
-            // Create decoded swap for arbitrage detection
-            let (token_x, token_x_amount) = if new_reserve0 < old_reserve0 {
-                // token0 bought (reserve0 decreased)
-                if let Some(pool_data) = reserve_cache.get(&pool) {
-                    (pool_data.token0, old_reserve0.saturating_sub(new_reserve0))
-                } else {
-                    return;
-                }
-            } else if new_reserve1 < old_reserve1 {
-                // token1 bought (reserve1 decreased)
-                if let Some(pool_data) = reserve_cache.get(&pool) {
-                    (pool_data.token1, old_reserve1.saturating_sub(new_reserve1))
-                } else {
-                    return;
-                }
-            } else {
-                return;
-            };
+            // Create decoded swap for arbitrage detection; liquidity changes only update the cache
+            let (token_x, token_x_amount) =
+                match classify_sync(old_reserve0, old_reserve1, new_reserve0, new_reserve1) {
+                    SyncChange::Swap { token0_out: true, amount_out } => (token0, amount_out),
+                    SyncChange::Swap { token0_out: false, amount_out } => (token1, amount_out),
+                    SyncChange::Liquidity => {
+                        println!("      [SYNC] Liquidity change on {:?}, no swap to chase", pool);
+                        return;
+                    }
+                };
 
             let decoded_swap = DecodedSwap {
                 tx_hash: H160::zero(), // Sync events don't have direct tx hash
@@ -1006,229 +944,199 @@ async fn decode_and_print_sync_v2(
                 timestamp: chrono::Utc::now().timestamp() as u64,
             };
             println!("[DecodedSwap] {:?}", decoded_swap);
-
-            // --- Start latency monitoring ---
-            let t0 = Instant::now();
-            let mut timings = serde_json::Map::new();
-            timings.insert("search_start_us".to_string(), serde_json::json!(0));
-
-            // --- Opportunity search (simulation/filtering) ---
-            let after_sim;
-            let before_tx;
-            let after_tx;
-            let mut tx_hash_str: Option<String> = None;
-            if let Some((opportunity, latency_ms)) = find_arbitrage_opportunity_from_price_tracker(
+            detect_and_dispatch_opportunity(
                 &decoded_swap,
+                old_reserve0,
+                old_reserve1,
                 reserve_cache,
                 token_index,
                 precomputed_route_cache,
                 token_tax_map,
-                &config,
+                config,
+                opportunity_tx,
             )
-            .await
-            {
-                after_sim = t0.elapsed().as_micros();
-                timings.insert("after_sim_us".to_string(), serde_json::json!(after_sim));
-
-                // Log the opportunity
-                log_opportunity_from_price_tracker(
-                    &opportunity,
-                    latency_ms,
-                    reserve_cache,
-                    old_reserve0,
-                    old_reserve1,
-                );
-
-                // --- Before TX fire ---
-                before_tx = t0.elapsed().as_micros();
-                timings.insert("before_tx_us".to_string(), serde_json::json!(before_tx));
-
-                // --- Simulate TX fire (mock, replace with actual call if needed) ---
-                // let tx_hash = execute_arbitrage_onchain(...).await?;
-                // For now, just simulate delay
-                // tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
-                // after_tx = t0.elapsed().as_micros();
-                // timings.insert("after_tx_us".to_string(), serde_json::json!(after_tx));
-                // timings.insert("tx_hash".to_string(), serde_json::json!(tx_hash.to_string()));
-
-                // Send opportunity for execution
-                if let Err(e) = opportunity_tx.send(opportunity).await {
-                    eprintln!(
-                        "❌ [Price Tracker] Failed to send arbitrage opportunity: {}",
-                        e
-                    );
-                }
-                after_tx = t0.elapsed().as_micros();
-                timings.insert("after_tx_us".to_string(), serde_json::json!(after_tx));
-                timings.insert("tx_hash".to_string(), serde_json::json!(tx_hash_str));
-
-                // --- Total ---
-                let total = t0.elapsed().as_millis();
-                timings.insert("total_ms".to_string(), serde_json::json!(total));
-
-                // Print and log timings
-                // println!("[LATENCY] Step timings: {}", serde_json::to_string_pretty(&timings).unwrap());
-                // Optionally, append to a timings log file
-                if let Ok(mut file) = OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("latency_breakdown_price_tracker.log")
-                {
-                    if let Err(e) = writeln!(file, "{}", serde_json::to_string(&timings).unwrap()) {
-                        eprintln!("❌ [Price Tracker] Failed to write latency log: {}", e);
-                    }
-                }
-            }
+            .await;
         }
     }
 }
-use num_traits::ToPrimitive;
-fn decode_and_print_swap_v3(data_hex: &str, pool: H160, reserve_cache: &Arc<ReserveCache>) {
-    use ethers::abi::Token;
-    use num_bigint::BigInt;
-    if let Ok(data_bytes) = hex::decode(data_hex.trim_start_matches("0x")) {
-        let param_types = vec![
-            ParamType::Int(256),  // amount0 (signed!)
-            ParamType::Int(256),  // amount1 (signed!)
-            ParamType::Uint(160), // sqrtPriceX96
-            ParamType::Uint(128), // liquidity
-            ParamType::Int(24),   // tick (signed!)
-        ];
-        if let Ok(tokens) = ethers::abi::decode(&param_types, &data_bytes) {
-            let sqrt_price_x96 = tokens[2].clone().into_uint().unwrap();
-            let liquidity = tokens[3].clone().into_uint().unwrap();
-            let tick = match &tokens[4] {
-                Token::Int(i) => {
-                    let mut buf = [0u8; 32];
-                    i.to_big_endian(&mut buf);
-                    BigInt::from_signed_bytes_be(&buf)
-                }
-                _ => panic!("not int"),
-            };
-            let tick_i32: i32 = tick.to_i32().expect("tick value out of range for i32");
-            println!("      sqrtPriceX96: {}", sqrt_price_x96);
-            println!("      liquidity:    {}", liquidity);
-            println!("      tick:         {}", tick);
-            // --- CACHE UPDATE ---
-            // Get old values before updating
-            let old_sqrt_price_x96 = reserve_cache
-                .get(&pool)
-                .and_then(|s| s.sqrt_price_x96)
-                .unwrap_or(eU256::zero());
-            let old_liquidity = reserve_cache
-                .get(&pool)
-                .and_then(|s| s.liquidity)
-                .unwrap_or(eU256::zero());
-            let old_tick = reserve_cache
-                .get(&pool)
-                .and_then(|s| s.tick)
-                .unwrap_or(0i32);
-            
-            // Print cache state BEFORE update
-            println!("      [CACHE BEFORE] Pool: {:?}", pool);
-            println!("      [CACHE BEFORE] Old sqrtPriceX96: {}", old_sqrt_price_x96);
-            println!("      [CACHE BEFORE] Old liquidity: {}", old_liquidity);
-            println!("      [CACHE BEFORE] Old tick: {}", old_tick);
-            println!("      [CACHE BEFORE] New sqrtPriceX96: {}", sqrt_price_x96);
-            println!("      [CACHE BEFORE] New liquidity: {}", liquidity);
-            println!("      [CACHE BEFORE] New tick: {}", tick_i32);
-            
-            if let Some(mut state) = reserve_cache.get_mut(&pool) {
-                state.sqrt_price_x96 = Some(sqrt_price_x96);
-                state.liquidity = Some(liquidity);
-                state.tick = Some(tick_i32);
-                state.last_updated = chrono::Utc::now().timestamp() as u64;
-                
-                // Print cache state AFTER update
-                println!("      [CACHE AFTER] Pool: {:?}", pool);
-                println!("      [CACHE AFTER] Updated sqrtPriceX96: {:?}", state.sqrt_price_x96);
-                println!("      [CACHE AFTER] Updated liquidity: {:?}", state.liquidity);
-                println!("      [CACHE AFTER] Updated tick: {:?}", state.tick);
-                println!("      [CACHE AFTER] Last updated: {}", state.last_updated);
-                println!("      [CACHE UPDATE] ✅ SUCCESS - V3 state updated in cache!");
-            } else {
-                println!("      [CACHE UPDATE] ❌ FAILED - V3 Pool not found in cache: {:?}", pool);
+
+/// Run route search for a decoded swap, log any opportunity found and hand it to the executor.
+/// `old_reserve0`/`old_reserve1` are the pre-swap V2 reserves (zero for V3 pools).
+async fn detect_and_dispatch_opportunity(
+    decoded_swap: &DecodedSwap,
+    old_reserve0: eU256,
+    old_reserve1: eU256,
+    reserve_cache: &Arc<ReserveCache>,
+    token_index: &Arc<TokenIndexMap>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Arc<Config>,
+    opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
+) {
+    // --- Start latency monitoring ---
+    let t0 = Instant::now();
+    let mut timings = serde_json::Map::new();
+    timings.insert("search_start_us".to_string(), serde_json::json!(0));
+
+    // --- Opportunity search (simulation/filtering) ---
+    let after_sim;
+    let before_tx;
+    let after_tx;
+    let mut tx_hash_str: Option<String> = None;
+    if let Some((opportunity, latency_ms)) = find_arbitrage_opportunity(
+        decoded_swap,
+        reserve_cache,
+        token_index,
+        precomputed_route_cache,
+        token_tax_map,
+        config,
+    )
+    .await
+    {
+        after_sim = t0.elapsed().as_micros();
+        timings.insert("after_sim_us".to_string(), serde_json::json!(after_sim));
+
+        // Log the opportunity
+        log_opportunity_from_price_tracker(
+            &opportunity,
+            latency_ms,
+            reserve_cache,
+            old_reserve0,
+            old_reserve1,
+        );
+
+        // --- Before TX fire ---
+        before_tx = t0.elapsed().as_micros();
+        timings.insert("before_tx_us".to_string(), serde_json::json!(before_tx));
+
+        // --- Simulate TX fire (mock, replace with actual call if needed) ---
+        // let tx_hash = execute_arbitrage_onchain(...).await?;
+        // For now, just simulate delay
+        // tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        // after_tx = t0.elapsed().as_micros();
+        // timings.insert("after_tx_us".to_string(), serde_json::json!(after_tx));
+        // timings.insert("tx_hash".to_string(), serde_json::json!(tx_hash.to_string()));
+
+        // Send opportunity for execution
+        if let Err(e) = opportunity_tx.send(opportunity).await {
+            eprintln!(
+                "❌ [Price Tracker] Failed to send arbitrage opportunity: {}",
+                e
+            );
+        }
+        after_tx = t0.elapsed().as_micros();
+        timings.insert("after_tx_us".to_string(), serde_json::json!(after_tx));
+        timings.insert("tx_hash".to_string(), serde_json::json!(tx_hash_str));
+
+        // --- Total ---
+        let total = t0.elapsed().as_millis();
+        timings.insert("total_ms".to_string(), serde_json::json!(total));
+
+        // Print and log timings
+        // println!("[LATENCY] Step timings: {}", serde_json::to_string_pretty(&timings).unwrap());
+        // Optionally, append to a timings log file
+        if let Ok(mut file) = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path("latency_breakdown_price_tracker.log"))
+        {
+            if let Err(e) = writeln!(file, "{}", serde_json::to_string(&timings).unwrap()) {
+                eprintln!("❌ [Price Tracker] Failed to write latency log: {}", e);
             }
-            
         }
     }
 }
 
-fn decode_and_print_pancake_swap_v3(
+/// Decode a Uniswap V3 or PancakeSwap V3 Swap log, update the cache and volume, and run detection
+async fn decode_and_print_v3_swap(
     data_hex: &str,
-    topics: &[String],
+    topic0: H256,
     pool: H160,
     reserve_cache: &Arc<ReserveCache>,
-) {
-    use ethers::abi::{ParamType, Token};
-    use num_bigint::BigInt;
-    if let Ok(data_bytes) = hex::decode(data_hex.trim_start_matches("0x")) {
-        let param_types = vec![
-            ParamType::Int(256),  // amount0
-            ParamType::Int(256),  // amount1
-            ParamType::Uint(160), // sqrtPriceX96
-            ParamType::Uint(128), // liquidity
-            ParamType::Int(24),   // tick
-            ParamType::Uint(128), // protocolFeesToken0
-            ParamType::Uint(128), // protocolFeesToken1
-        ];
-        if let Ok(tokens) = ethers::abi::decode(&param_types, &data_bytes) {
-            let sqrt_price_x96 = tokens[2].clone().into_uint().unwrap();
-            let liquidity = tokens[3].clone().into_uint().unwrap();
-            let tick = match &tokens[4] {
-                Token::Int(i) => {
-                    let mut buf = [0u8; 32];
-                    i.to_big_endian(&mut buf);
-                    BigInt::from_signed_bytes_be(&buf)
-                }
-                _ => panic!("not int"),
-            };
-            println!("      sqrtPriceX96: {}", sqrt_price_x96);
-            println!("      liquidity:   {}", liquidity);
-            println!("      tick:        {}", tick);
-            let tick_i32: i32 = tick.to_i32().expect("tick value out of range for i32");
-            // --- CACHE UPDATE ---
-            // Get old values before updating
-            let old_sqrt_price_x96 = reserve_cache
-                .get(&pool)
-                .and_then(|s| s.sqrt_price_x96)
-                .unwrap_or(eU256::zero());
-            let old_liquidity = reserve_cache
-                .get(&pool)
-                .and_then(|s| s.liquidity)
-                .unwrap_or(eU256::zero());
-            let old_tick = reserve_cache
-                .get(&pool)
-                .and_then(|s| s.tick)
-                .unwrap_or(0i32);
-            
-            // Print cache state BEFORE update
-            println!("      [CACHE BEFORE] Pool: {:?}", pool);
-            println!("      [CACHE BEFORE] Old sqrtPriceX96: {}", old_sqrt_price_x96);
-            println!("      [CACHE BEFORE] Old liquidity: {}", old_liquidity);
-            println!("      [CACHE BEFORE] Old tick: {}", old_tick);
-            println!("      [CACHE BEFORE] New sqrtPriceX96: {}", sqrt_price_x96);
-            println!("      [CACHE BEFORE] New liquidity: {}", liquidity);
-            println!("      [CACHE BEFORE] New tick: {}", tick_i32);
-            
-            if let Some(mut state) = reserve_cache.get_mut(&pool) {
-                state.sqrt_price_x96 = Some(sqrt_price_x96);
-                state.liquidity = Some(liquidity);
-                state.tick = Some(tick_i32);
-                state.last_updated = chrono::Utc::now().timestamp() as u64;
-                
-                // Print cache state AFTER update
-                println!("      [CACHE AFTER] Pool: {:?}", pool);
-                println!("      [CACHE AFTER] Updated sqrtPriceX96: {:?}", state.sqrt_price_x96);
-                println!("      [CACHE AFTER] Updated liquidity: {:?}", state.liquidity);
-                println!("      [CACHE AFTER] Updated tick: {:?}", state.tick);
-                println!("      [CACHE AFTER] Last updated: {}", state.last_updated);
-                println!("      [CACHE UPDATE] ✅ SUCCESS - V3 state updated in cache!");
-            } else {
-                println!("      [CACHE UPDATE] ❌ FAILED - V3 Pool not found in cache: {:?}", pool);
-            }
-        }
-    }
+    block_number: u64,
+    token_index: &Arc<TokenIndexMap>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Arc<Config>,
+    opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
+) -> anyhow::Result<()> {
+    let data_bytes = hex::decode(data_hex.trim_start_matches("0x"))?;
+    let swap = decode_v3_swap(topic0, &data_bytes)?;
+    println!("      amount0:      {}", swap.amount0);
+    println!("      amount1:      {}", swap.amount1);
+    println!("      sqrtPriceX96: {}", swap.sqrt_price_x96);
+    println!("      liquidity:   {}", swap.liquidity);
+    println!("      tick:        {}", swap.tick);
+    println!("      protocolFees: ({}, {})", swap.protocol_fees_token0, swap.protocol_fees_token1);
+    // --- CACHE UPDATE ---
+    // Get old values before updating
+    let old_sqrt_price_x96 = reserve_cache
+        .get(&pool)
+        .and_then(|s| s.sqrt_price_x96)
+        .unwrap_or(eU256::zero());
+    let old_liquidity = reserve_cache
+        .get(&pool)
+        .and_then(|s| s.liquidity)
+        .unwrap_or(eU256::zero());
+    let old_tick = reserve_cache
+        .get(&pool)
+        .and_then(|s| s.tick)
+        .unwrap_or(0i32);
+
+    // Print cache state BEFORE update
+    println!("      [CACHE BEFORE] Pool: {:?}", pool);
+    println!("      [CACHE BEFORE] Old sqrtPriceX96: {}", old_sqrt_price_x96);
+    println!("      [CACHE BEFORE] Old liquidity: {}", old_liquidity);
+    println!("      [CACHE BEFORE] Old tick: {}", old_tick);
+    println!("      [CACHE BEFORE] New sqrtPriceX96: {}", swap.sqrt_price_x96);
+    println!("      [CACHE BEFORE] New liquidity: {}", swap.liquidity);
+    println!("      [CACHE BEFORE] New tick: {}", swap.tick);
+
+    let (token0, token1) = if let Some(mut state) = reserve_cache.get_mut(&pool) {
+        state.sqrt_price_x96 = Some(swap.sqrt_price_x96);
+        state.liquidity = Some(swap.liquidity);
+        state.tick = Some(swap.tick);
+        state.last_updated = chrono::Utc::now().timestamp() as u64;
+        bump_reserve_version();
+
+        // Print cache state AFTER update
+        println!("      [CACHE AFTER] Pool: {:?}", pool);
+        println!("      [CACHE AFTER] Updated sqrtPriceX96: {:?}", state.sqrt_price_x96);
+        println!("      [CACHE AFTER] Updated liquidity: {:?}", state.liquidity);
+        println!("      [CACHE AFTER] Updated tick: {:?}", state.tick);
+        println!("      [CACHE AFTER] Last updated: {}", state.last_updated);
+        println!("      [CACHE UPDATE] ✅ SUCCESS - V3 state updated in cache!");
+        (state.token0, state.token1)
+    } else {
+        println!("      [CACHE UPDATE] ❌ FAILED - V3 Pool not found in cache: {:?}", pool);
+        return Ok(());
+    };
+    record_v3_volume(pool, &swap);
+
+    // Create decoded swap for arbitrage detection from the actual traded amounts
+    let Some((token_x, token_x_amount)) = swap.token_out(token0, token1) else { return Ok(()) };
+    let decoded_swap = DecodedSwap {
+        tx_hash: H160::zero(),
+        pool_address: pool,
+        token_x,
+        token_x_amount,
+        block_number,
+        timestamp: chrono::Utc::now().timestamp() as u64,
+    };
+    println!("[DecodedSwap] {:?}", decoded_swap);
+    detect_and_dispatch_opportunity(
+        &decoded_swap,
+        eU256::zero(),
+        eU256::zero(),
+        reserve_cache,
+        token_index,
+        precomputed_route_cache,
+        token_tax_map,
+        config,
+        opportunity_tx,
+    )
+    .await;
+    Ok(())
 }
 
 use std::future::Future;
@@ -1239,101 +1147,67 @@ pub fn print_dex_events_from_trace<'a>(
     tx_hash: &'a str,
     reserve_cache: &'a Arc<ReserveCache>,
     token_index: &'a Arc<TokenIndexMap>,
-    precomputed_route_cache: &'a Arc<DashMap<u32, Vec<RoutePath>>>,
+    precomputed_route_cache: &'a Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
     token_tax_map: &'a Arc<TokenTaxMap>,
-    config: &'a Config,
+    config: &'a Arc<Config>,
     opportunity_tx: &'a mpsc::Sender<ArbitrageOpportunity>,
 ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
     Box::pin(async move {
         for log in &node.logs {
-            let (topics, data_hex) = parse_logdata_string2(&log.data);
+            let (topics, data_hex) = log.topics_and_data_hex();
             let pool = H160::from_slice(log.address.0.as_slice());
-            if let Some(topic0) = topics.get(0) {
-                if let Ok(topic0_bytes) = hex::decode(topic0.trim_start_matches("0x")) {
-                    if DEX_EVENT_TOPICS.contains(&B256::from_slice(&topic0_bytes)) {
-                        let event_name = match topic0.as_str() {
-                            t if t
-                                == format!(
-                                    "0x{:x}",
-                                    keccak256(
-                                        "Swap(address,address,uint256,uint256,uint256,uint256,address)"
-                                    )
-                                ) =>
-                            {
-                                "SwapV2"
-                            }
-                            t if t
-                                == format!(
-                                    "0x{:x}",
-                                    keccak256("Swap(address,uint256,uint256,uint256,uint256,address)")
-                                ) =>
-                            {
-                                "SwapV2"
-                            }
-                            t if t == format!("0x{:x}", keccak256("Sync(uint112,uint112)")) => "SyncV2",
-                            t if t
-                                == format!(
-                                    "0x{:x}",
-                                    keccak256(
-                                        "Swap(address,address,int256,int256,uint160,uint128,int24)"
-                                    )
-                                ) =>
-                            {
-                                "SwapV3"
-                            }
-                            t if t
-                                == format!(
-                                    "0x{:x}",
-                                    keccak256(
-                                        "Swap(address,address,int256,int256,uint160,uint128,int24,uint128,uint128)"
-                                    )
-                                ) =>
-                            {
-                                "PanCakeSwapV3"
-                            }
-                            _ => "UnknownDEXEvent",
-                        };
-                        println!(
-                            "[DEX EVENT] {} at 0x{} (tx: {})",
-                            event_name,
-                            hex::encode(&log.address),
-                            tx_hash
-                        );
-                        match event_name {
-                            "SwapV2" => {
-                                println!("      [DEBUG] log struct: {:?}", log);
-                                decode_and_print_swap_v2(&data_hex, pool, reserve_cache);
-                            }
-                            "SyncV2" => {
-                                println!("      [DEBUG] topocs  : {:?}", topics);
-                                decode_and_print_sync_v2(
-                                    &data_hex,
-                                    pool,
-                                    reserve_cache,
-                                    0, // You may need to provide the correct block_number value here
-                                    token_index,
-                                    precomputed_route_cache,
-                                    token_tax_map,
-                                    config,
-                                    opportunity_tx,
-                                ).await;
-                            }
-                            "SwapV3" => {
-                                println!("      [DEBUG] topocs  : {:?}", topics);
-                                decode_and_print_swap_v3(&data_hex, pool, reserve_cache);
-                            }
-                            "PanCakeSwapV3" => {
-                                println!("      [DEBUG] topics  : {:?}", topics);
-                                decode_and_print_pancake_swap_v3(
-                                    &data_hex,
-                                    &topics,
-                                    pool,
-                                    reserve_cache,
-                                );
-                            }
-                            _ => println!("      raw data: {}", data_hex),
+            if let Some(&topic0) = topics.first().filter(|t| DEX_EVENT_TOPICS.contains(*t)) {
+                let event_name = match topic0 {
+                    t if t == *SWAP_V2_TOPIC || t == *SWAP_V2_FORK_TOPIC => "SwapV2",
+                    t if t == *SYNC_V2_TOPIC => "SyncV2",
+                    t if t == *SWAP_V3_TOPIC => "SwapV3",
+                    t if t == *PANCAKE_V3_TOPIC => "PanCakeSwapV3",
+                    _ => "UnknownDEXEvent",
+                };
+                println!(
+                    "[DEX EVENT] {} at 0x{} (tx: {})",
+                    event_name,
+                    hex::encode(&log.address),
+                    tx_hash
+                );
+                match event_name {
+                    "SwapV2" => {
+                        println!("      [DEBUG] log struct: {:?}", log);
+                        decode_and_print_swap_v2(&data_hex, pool, reserve_cache);
+                    }
+                    "SyncV2" => {
+                        println!("      [DEBUG] topocs  : {:?}", topics);
+                        decode_and_print_sync_v2(
+                            &data_hex,
+                            pool,
+                            reserve_cache,
+                            0, // You may need to provide the correct block_number value here
+                            token_index,
+                            precomputed_route_cache,
+                            token_tax_map,
+                            config,
+                            opportunity_tx,
+                        ).await;
+                    }
+                    "SwapV3" | "PanCakeSwapV3" => {
+                        println!("      [DEBUG] topics  : {:?}", topics);
+                        // A malformed log is logged and skipped; it must not take down the trace walk
+                        if let Err(e) = decode_and_print_v3_swap(
+                            &data_hex,
+                            H256::from(topic0.0),
+                            pool,
+                            reserve_cache,
+                            0, // You may need to provide the correct block_number value here
+                            token_index,
+                            precomputed_route_cache,
+                            token_tax_map,
+                            config,
+                            opportunity_tx,
+                        ).await {
+                            println!("      [V3 DECODE] ❌ Skipping event: {}", e);
                         }
                     }
+                    _ => println!("      raw data: {}", data_hex),
                 }
             }
         }
@@ -1351,243 +1225,6 @@ pub fn print_dex_events_from_trace<'a>(
         }
     })
 }
-/// Find arbitrage opportunities for a decoded swap (price tracker version)
-pub async fn find_arbitrage_opportunity_from_price_tracker(
-    decoded_swap: &DecodedSwap,
-    reserve_cache: &Arc<ReserveCache>,
-    token_index: &Arc<TokenIndexMap>,
-    precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
-    token_tax_map: &Arc<TokenTaxMap>,
-    config: &Config,
-) -> Option<(ArbitrageOpportunity, u128)> {
-    // Start latency timer
-    let start_time = std::time::Instant::now();
-    // Get token index
-    let token_x_index = token_index.address_to_index.get(&decoded_swap.token_x)?;
-    let token_x_index_u32 = *token_x_index as u32;
-
-    println!(
-        "🔍 [Price Tracker] Finding arbitrage for tokenX (idx {}): {:?}",
-        token_x_index, decoded_swap.token_x
-    );
-
-    // Get all routes that contain this token and the affected pool
-    let candidate_routes = precomputed_route_cache
-        .get(&token_x_index_u32)
-        .map(|entry| entry.value().clone())
-        .unwrap_or_default();
-
-    // println!(
-    //     "📊 [Price Tracker] Found {} candidate routes for tokenX",
-    //     candidate_routes.len()
-    // );
-
-    // Filter routes that contain the affected pool
-    let filtered_routes: Vec<&RoutePath> = candidate_routes
-        .iter()
-        .filter(|route| route.pools.contains(&decoded_swap.pool_address))
-        .collect();
-
-    println!(
-        "🎯 [Price Tracker] {} routes contain the affected pool {:?}",
-        filtered_routes.len(),
-        decoded_swap.pool_address
-    );
-
-    if filtered_routes.is_empty() {
-        return None;
-    }
-
-    // Simulate all filtered routes in parallel
-    let simulation_results: Vec<Option<crate::arbitrage_finder::SimulatedRoute>> = filtered_routes
-        .par_iter()
-        .map(|route| {
-            // Split route into buy/sell paths
-            let (buy_path, sell_path) = split_route_around_token_x(route, token_x_index_u32)?;
-
-            // Simulate buy path (base -> tokenX)
-            let buy_amounts = simulate_buy_path_amounts_array(
-                &buy_path,
-                decoded_swap.token_x_amount,
-                reserve_cache,
-                token_index,
-                token_tax_map,
-                config,
-            )?;
-
-            // Simulate sell path (tokenX -> base)
-            let sell_amounts = simulate_sell_path_amounts_array(
-                &sell_path,
-                decoded_swap.token_x_amount,
-                reserve_cache,
-                token_index,
-                token_tax_map,
-                config,
-            )?;
-
-            // Merge amounts: [buy_amounts..., sell_amounts[1..]]
-            let mut merged_amounts = buy_amounts.clone();
-            merged_amounts.extend_from_slice(&sell_amounts[1..]);
-            // let sell_test_amounts;
-            // simulate_sell_path_amounts_array(
-            //     route,
-            //     merged_amounts[0],
-            //     reserve_cache,
-            //     token_index,
-            // )?;
-            // Calculate profit and profit percentage
-            if merged_amounts.len() >= 2 {
-                let amount_in: eU256 = merged_amounts[0];
-                let amount_out: eU256 = *merged_amounts.last().unwrap();
-                let profit: eU256 = amount_out.saturating_sub(amount_in);
-
-                // Only consider profitable trades
-                let sell_symbols: Vec<String> = sell_path
-                    .hops
-                    .iter()
-                    .map(|&idx| token_index_to_symbol_from_price_tracker(idx, token_index))
-                    .collect();
-                let price_usd = {
-                    let last_symbol = &sell_symbols[sell_symbols.len() - 1];
-                    if let Ok(addr) = last_symbol.parse::<H160>() {
-                        get_token_usd_value(&addr).unwrap_or(0.0)
-                    } else {
-                        0.0
-                    }
-                };
-                let amount = u256_to_f64_lossy(&profit) / 10_f64.powi(18 as i32);
-                let profit_usd = amount * price_usd;
-                if amount_in < amount_out {
-                    // Calculate profit percentage (profit / amount_in * 100)
-                    let profit_percentage = if amount_in > eU256::zero() {
-                        // Convert to f64 for percentage calculation
-                        let profit_f64 = profit.as_u128() as f64;
-                        let amount_in_f64 = amount_in.as_u128() as f64;
-                        (profit_f64 / amount_in_f64) * 100.0
-                    } else {
-                        0.0
-                    };
-
-                    // Merge token indices
-                    // let mut merged_tokens = buy_path.hops.clone();
-                    // merged_tokens.extend_from_slice(&sell_path.hops[1..]);
-
-                    // Map to symbols
-                    // let merged_symbols = merged_tokens
-                    //     .iter()
-                    //     .map(|&idx| token_index_to_symbol_from_price_tracker(idx, token_index))
-                    //     .collect();
-
-                    // Merge pools
-                    let mut merged_pools = buy_path.pools.clone();
-                    merged_pools.extend_from_slice(&sell_path.pools);
-
-                    return Some(crate::arbitrage_finder::SimulatedRoute {
-                        merged_amounts,
-                        buy_amounts,
-                        sell_amounts,
-                        // merged_tokens,
-                        // merged_symbols,
-                        buy_symbols: buy_path
-                            .hops
-                            .iter()
-                            .map(|&idx| token_index_to_symbol_from_price_tracker(idx, token_index))
-                            .collect(),
-                        sell_symbols,
-                        buy_pools: buy_path.pools.clone(),
-                        sell_pools: sell_path.pools.clone(),
-                        merged_pools,
-                        profit,
-                        profit_percentage,
-                        buy_path: buy_path.clone(),
-                        sell_path: sell_path.clone(),
-                        // sell_test_amounts,
-                    });
-                }
-            }
-
-            None
-        })
-        .collect();
-
-    // Filter out None results
-    let profitable_routes: Vec<crate::arbitrage_finder::SimulatedRoute> =
-        simulation_results.into_iter().filter_map(|r| r).collect();
-
-    println!(
-        "💰 [Price Tracker] Found {} profitable routes",
-        profitable_routes.len()
-    );
-
-    if profitable_routes.is_empty() {
-        return None;
-    }
-
-    // Find the most profitable route by percentage (better for multiple base tokens)
-    let best_route = profitable_routes
-        .iter()
-        .max_by(|a, b| {
-            a.profit_percentage
-                .partial_cmp(&b.profit_percentage)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        })
-        .cloned();
-
-    let estimated_profit = best_route
-        .as_ref()
-        .map(|r| r.profit)
-        .unwrap_or(eU256::zero());
-
-    // End latency timer
-    let latency = start_time.elapsed().as_millis();
-
-    Some((
-        ArbitrageOpportunity {
-            decoded_swap: decoded_swap.clone(),
-            profitable_routes,
-            best_route,
-            estimated_profit,
-        },
-        latency,
-    ))
-}
-fn u256_to_f64_lossy(val: &eU256) -> f64 {
-    if val.bits() <= 128 {
-        val.as_u128() as f64
-    } else {
-        val.to_string().parse::<f64>().unwrap_or(f64::MAX)
-    }
-}
-const KNOWN_TOKENS: &[(&str, &str, f64)] = &[
-    ("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c", "BNB", 689.93),
-    ("0x2170Ed0880ac9A755fd29B2688956BD959F933F8", "ETH", 2961.19),
-    (
-        "0x7130d2A12B9BCbFAe4f2634d864A1Ee1Ce3Ead9c",
-        "BTC",
-        117970.0,
-    ),
-    ("0x55d398326f99059fF775485246999027B3197955", "USDT", 1.00),
-    ("0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d", "USDC", 1.00), // Multichain bridge price
-    ("0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56", "BUSD", 1.00),
-    ("0x0E09FaBB73Bd3Ade0a17ECC321fD13a19e81cE82", "CAKE", 2.37),
-];
-
-fn get_token_usd_value(token_address: &H160) -> Option<f64> {
-    let addr_str = format!("0x{:x}", token_address);
-    KNOWN_TOKENS
-        .iter()
-        .find(|(addr, _, _)| addr.to_lowercase() == addr_str.to_lowercase())
-        .map(|(_, _, price)| *price)
-}
-/// Helper to map token index to symbol (price tracker version)
-fn token_index_to_symbol_from_price_tracker(idx: u32, token_index: &TokenIndexMap) -> String {
-    if let Some(addr) = token_index.index_to_address.get(&(idx as u32)) {
-        format!("0x{:x}", addr)
-    } else {
-        format!("token{}", idx)
-    }
-}
-
 fn log_opportunity_from_price_tracker(
     opportunity: &ArbitrageOpportunity,
     latency_ms: u128,
@@ -1621,10 +1258,10 @@ fn log_opportunity_from_price_tracker(
     };
 
     let now: DateTime<Utc> = Utc::now();
-    let log_file_path = format!(
-        "logs/arbitrage_opportunities_price_tracker_{}.log",
+    let log_file_path = log_path(&format!(
+        "arbitrage_opportunities_price_tracker_{}.log",
         now.format("%Y%m%d_%H%M%S")
-    );
+    ));
 
     // Create detailed log entry
     let mut log_entry = json!({
@@ -1722,7 +1359,7 @@ fn log_opportunity_from_price_tracker(
     } else {
         eprintln!(
             "❌ [Price Tracker] Failed to open log file: {}",
-            log_file_path
+            log_file_path.display()
         );
     }
 
@@ -1817,231 +1454,6 @@ mod tests {
     //         root.children.push(child);
     //         print_call_trace(&root, 0);
     //     }
-
-    //     #[test]
-    //     fn test_swap_v3_decode_manual() {
-    //         use ethers::abi::{ParamType, decode, Token};
-    //         use num_bigint::BigInt;
-    //         let data_hex = "0xfffffffffffffffffffffffffffffffffffffffffffffff69dbe8ebd26a0f47500000000000000000000000000000000000000000000001b1ae4d6e2ef5000000000000000000000000000000000000000000001b167c1883204379306f55c2f000000000000000000000000000000000000000000001adbe22ae2898083e1360000000000000000000000000000000000000000000000000000000000002922";
-    //         let data_bytes = hex::decode(data_hex.trim_start_matches("0x")).unwrap();
-    //         println!("data_bytes.len(): {}", data_bytes.len());
-    //         let param_types = vec![
-    //             ParamType::Int(256),
-    //             ParamType::Int(256),
-    //             ParamType::Uint(160),
-    //             ParamType::Uint(128),
-    //             ParamType::Int(24),
-    //         ];
-    //         let tokens = decode(&param_types, &data_bytes).unwrap();
-    //         // Print amount0 as signed
-    //         let amount0 = match &tokens[0] {
-    //             Token::Int(i) => {
-    //                 let mut buf = [0u8; 32];
-    //                 i.to_big_endian(&mut buf);
-    //                 BigInt::from_signed_bytes_be(&buf)
-    //             },
-    //             _ => panic!("not int"),
-    //         };
-    //         let amount1 = match &tokens[1] {
-    //             Token::Int(i) => {
-    //                 let mut buf = [0u8; 32];
-    //                 i.to_big_endian(&mut buf);
-    //                 BigInt::from_signed_bytes_be(&buf)
-    //             },
-    //             _ => panic!("not int"),
-    //         };
-    //         println!("amount0: {}", amount0);
-    //         println!("amount1: {}", amount1);
-    //         println!("sqrtPriceX96: {}", tokens[2].clone().into_uint().unwrap());
-    //         println!("liquidity: {}", tokens[3].clone().into_uint().unwrap());
-    //         let tick = match &tokens[4] {
-    //             Token::Int(i) => {
-    //                 let mut buf = [0u8; 32];
-    //                 i.to_big_endian(&mut buf);
-    //                 BigInt::from_signed_bytes_be(&buf)
-    //             },
-    //             _ => panic!("not int"),
-    //         };
-    //         println!("tick: {}", tick);
-    //     }
-}
-
-/// Walks the call trace, updates the reserve cache for any Sync/Swap events, and checks for arbitrage opportunities.
-pub async fn process_simulation_events_and_arbitrage(
-    trace: &CallTraceNode,
-    reserve_cache: &Arc<ReserveCache>,
-    token_index: &Arc<TokenIndexMap>,
-    precomputed_route_cache: &Arc<DashMap<u32, Vec<RoutePath>>>,
-    opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
-    token_tax_map: &Arc<TokenTaxMap>,
-    config: &crate::config::Config,
-) {
-    // Helper to recursively walk the call trace
-    fn walk_trace<'a>(node: &'a CallTraceNode, out: &mut Vec<(&'a TraceLog, &'a CallTraceNode)>) {
-        for log in &node.logs {
-            out.push((log, node));
-        }
-        for child in &node.children {
-            walk_trace(child, out);
-        }
-    }
-    let mut logs_with_nodes = Vec::new();
-    walk_trace(trace, &mut logs_with_nodes);
-
-    for (log, node) in logs_with_nodes {
-        let (topics, data_hex) = crate::revm_sim::parse_logdata_string(&log.data);
-        // Sync V2 event
-        let sync_topic = format!(
-            "0x{:x}",
-            alloy_primitives::keccak256("Sync(uint112,uint112)")
-        );
-        let swap_v2_topic = format!(
-            "0x{:x}",
-            alloy_primitives::keccak256(
-                "Swap(address,address,uint256,uint256,uint256,uint256,address)"
-            )
-        );
-        let swap_v3_topic = format!(
-            "0x{:x}",
-            alloy_primitives::keccak256(
-                "Swap(address,address,int256,int256,uint160,uint128,int24)"
-            )
-        );
-        let pancake_v3_topic = format!(
-            "0x{:x}",
-            alloy_primitives::keccak256(
-                "Swap(address,address,int256,int256,uint160,uint128,int24,uint128,uint128)"
-            )
-        );
-        if let Some(topic0) = topics.get(0) {
-            // --- V2 Sync ---
-            if topic0 == &sync_topic && data_hex.len() >= 2 + 64 {
-                if let Ok(data_bytes) = hex::decode(data_hex.trim_start_matches("0x")) {
-                    if data_bytes.len() >= 64 {
-                        let new_reserve0 = eU256::from_big_endian(&data_bytes[0..32]);
-                        let new_reserve1 = eU256::from_big_endian(&data_bytes[32..64]);
-                        let pool = H160::from_slice(log.address.0.as_slice());
-                        // Update cache
-                        if let Some(mut state) = reserve_cache.get_mut(&pool) {
-                            state.reserve0 = Some(new_reserve0);
-                            state.reserve1 = Some(new_reserve1);
-                            state.last_updated = chrono::Utc::now().timestamp() as u64;
-                        }
-                        // Arbitrage check (like price_tracker)
-                        let decoded_swap = DecodedSwap {
-                            tx_hash: H160::zero(), // Mempool sim, so no real tx hash
-                            pool_address: pool,
-                            token_x: H160::zero(),         // Not used for now
-                            token_x_amount: eU256::zero(), // Not used for now
-                            block_number: 0,
-                            timestamp: chrono::Utc::now().timestamp() as u64,
-                        };
-                        if let Some((opportunity, _latency)) =
-                            crate::price_tracker::find_arbitrage_opportunity_from_price_tracker(
-                                &decoded_swap,
-                                reserve_cache,
-                                token_index,
-                                precomputed_route_cache,
-                                token_tax_map,
-                                config,
-                            )
-                            .await
-                        {
-                            let _ = opportunity_tx.send(opportunity).await;
-                        }
-                    }
-                }
-            }
-            // --- V3 Swap ---
-            if (topic0 == &swap_v3_topic || topic0 == &pancake_v3_topic)
-                && data_hex.len() >= 2 + 160
-            {
-                if let Ok(data_bytes) = hex::decode(data_hex.trim_start_matches("0x")) {
-                    // Uniswap V3: 160 bytes, Pancake V3: 224 bytes
-                    let (sqrt_price_x96, liquidity, tick) = if data_bytes.len() == 160 {
-                        // Uniswap V3
-                        let sqrt_price_x96 = eU256::from_big_endian(&data_bytes[64..84]);
-                        let liquidity = eU256::from_big_endian(&data_bytes[84..100]);
-                        let tick = {
-                            let mut buf = [0u8; 32];
-                            buf[8..32].copy_from_slice(&data_bytes[100..124]);
-                            eU256::from_big_endian(&buf)
-                        };
-                        (sqrt_price_x96, liquidity, tick)
-                    } else if data_bytes.len() == 224 {
-                        // Pancake V3
-                        let sqrt_price_x96 = eU256::from_big_endian(&data_bytes[64..84]);
-                        let liquidity = eU256::from_big_endian(&data_bytes[84..100]);
-                        let tick = {
-                            let mut buf = [0u8; 32];
-                            buf[8..32].copy_from_slice(&data_bytes[100..124]);
-                            eU256::from_big_endian(&buf)
-                        };
-                        (sqrt_price_x96, liquidity, tick)
-                    } else {
-                        (eU256::zero(), eU256::zero(), eU256::zero())
-                    };
-                    let pool = H160::from_slice(log.address.0.as_slice());
-                    if let Some(mut state) = reserve_cache.get_mut(&pool) {
-                        state.sqrt_price_x96 = Some(sqrt_price_x96);
-                        state.liquidity = Some(liquidity);
-                        state.tick = Some(tick.as_u32() as i32);
-                        state.last_updated = chrono::Utc::now().timestamp() as u64;
-                    }
-                    // Arbitrage check (like price_tracker)
-                    let decoded_swap = DecodedSwap {
-                        tx_hash: H160::zero(),
-                        pool_address: pool,
-                        token_x: H160::zero(),
-                        token_x_amount: eU256::zero(),
-                        block_number: 0,
-                        timestamp: chrono::Utc::now().timestamp() as u64,
-                    };
-                    if let Some((opportunity, _latency)) =
-                        crate::price_tracker::find_arbitrage_opportunity_from_price_tracker(
-                            &decoded_swap,
-                            reserve_cache,
-                            token_index,
-                            precomputed_route_cache,
-                            token_tax_map,
-                            config,
-                        )
-                        .await
-                    {
-                        let _ = opportunity_tx.send(opportunity).await;
-                    }
-                }
-            }
-        }
-    }
-}
-
-/// Walks the call trace tree and returns true if any log emits a SwapV2, SwapV3, or SyncV2 event
-fn trace_has_dex_event(node: &CallTraceNode) -> bool {
-    // DEX event topics
-    let swap_v2 = format!(
-        "0x{:x}",
-        keccak256("Swap(address,address,uint256,uint256,uint256,uint256,address)")
-    );
-    let swap_v3 = format!(
-        "0x{:x}",
-        keccak256("Swap(address,address,int256,int256,uint160,uint128,int24)")
-    );
-    let sync_v2 = format!("0x{:x}", keccak256("Sync(uint112,uint112)"));
-    for log in &node.logs {
-        let (topics, _) = parse_logdata_string2(&log.data);
-        if let Some(topic0) = topics.get(0) {
-            if topic0 == &swap_v2 || topic0 == &swap_v3 || topic0 == &sync_v2 {
-                return true;
-            }
-        }
-    }
-    for child in &node.children {
-        if trace_has_dex_event(child) {
-            return true;
-        }
-    }
-    false
 }
 
 /// Shallow trace: simulate call trace and check for DEX event logs
@@ -1,10 +1,22 @@
 use crate::token_tax::{TokenTaxInfo};
-use ethers::types::H160;
+use crate::config::DexVersion;
+use crate::fetch_pairs::PairInfo;
+use crate::token_index::TokenIndexMap;
+use crate::route_cache_codec::RouteCacheFormat;
+use crate::split_route_path::{precompute_route_legs, RouteLegs};
+use anyhow::bail;
+use ethers::types::{H160, H256};
+use ethers::utils::keccak256;
+use serde::{Deserialize, Serialize};
 use dashmap::DashMap;
 use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use rayon::prelude::*;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DEXType {
     PancakeV2,
     BiSwapV2,
@@ -16,10 +28,33 @@ pub enum DEXType {
     ApeSwapV3,
     BakeryV3,
     SushiV3,
+    StableSwap, // Curve-style StableN pool
     Other(String),
 }
 
-#[derive(Clone, Debug)]
+impl DEXType {
+    /// Map a DEX name and version from the pair files to its DEXType
+    pub fn from_dex_name(dex_name: &str, dex_version: &DexVersion) -> Self {
+        match (dex_name, dex_version) {
+            ("PancakeSwap V2", DexVersion::V2) => DEXType::PancakeV2,
+            ("PancakeSwap V3", DexVersion::V3) => DEXType::PancakeV3,
+            ("dex V3", DexVersion::V3) => DEXType::Other("dex V3".to_string()),
+            ("BiSwap", DexVersion::V2) => DEXType::BiSwapV2,
+            ("Uniswap v3", DexVersion::V3) => DEXType::BiSwapV3,
+            ("ApeSwap", DexVersion::V2) => DEXType::ApeSwapV2,
+            ("ApeSwap", DexVersion::V3) => DEXType::ApeSwapV3,
+            ("BakerySwap", DexVersion::V2) => DEXType::BakeryV2,
+            ("BakerySwap", DexVersion::V3) => DEXType::BakeryV3,
+            ("MDEX", DexVersion::V2) => DEXType::Other("MDEX".to_string()),
+            ("SushiSwap BSC", DexVersion::V2) => DEXType::SushiV2,
+            ("SushiSwap BSC", DexVersion::V3) => DEXType::SushiV3,
+            ("StableSwap", _) => DEXType::StableSwap,
+            (other, _) => DEXType::Other(other.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PoolMeta {
     pub token0: H160,
     pub token1: H160,
@@ -29,36 +64,138 @@ pub struct PoolMeta {
     pub fee: Option<u32>,      // V3 only
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+impl PoolMeta {
+    /// Route-building entry for a pair-file pool. A V3 pool keeps its own fee tier: the one the
+    /// preload read from `fee()` (`cached_fee`) when known, otherwise the PoolCreated tier.
+    pub fn from_pair(pair: &PairInfo, cached_fee: Option<u32>) -> Self {
+        let (factory, fee) = if pair.dex_version == DexVersion::V3 {
+            (Some(pair.factory_address), cached_fee.or(pair.fee))
+        } else {
+            (None, None)
+        };
+        PoolMeta {
+            token0: pair.token0,
+            token1: pair.token1,
+            address: pair.pair_address,
+            dex_type: DEXType::from_dex_name(&pair.dex_name, &pair.dex_version),
+            factory,
+            fee,
+        }
+    }
+
+    /// One entry per coin pair of a StableN pool, so route building sees it as a set of pairs
+    pub fn for_stable_pool(address: H160, tokens: &[H160]) -> Vec<Self> {
+        let mut metas = Vec::new();
+        for (i, token0) in tokens.iter().enumerate() {
+            for token1 in &tokens[i + 1..] {
+                metas.push(PoolMeta {
+                    token0: *token0,
+                    token1: *token1,
+                    address,
+                    dex_type: DEXType::StableSwap,
+                    factory: None,
+                    fee: None,
+                });
+            }
+        }
+        metas
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RoutePath {
     pub hops: Vec<u32>,      // token indices
     pub pools: Vec<H160>,   // pool addresses
     pub dex_types: Vec<DEXType>,
+    #[serde(skip)]
+    pub legs: Option<Arc<RouteLegs>>, // buy/sell split around the token the route is cached under
+}
+
+// Identity is the path itself; `legs` is a derived cache and doesn't take part
+impl PartialEq for RoutePath {
+    fn eq(&self, other: &Self) -> bool {
+        self.hops == other.hops && self.pools == other.pools && self.dex_types == other.dex_types
+    }
+}
+
+impl Eq for RoutePath {}
+
+impl RoutePath {
+    /// Whether the route ends in the base token it starts from; cross-base routes
+    /// (`base_a -> X -> base_b`) don't
+    pub fn is_cycle(&self) -> bool {
+        self.hops.first() == self.hops.last()
+    }
+}
+
+impl Hash for RoutePath {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hops.hash(state);
+        self.pools.hash(state);
+        self.dex_types.hash(state);
+    }
+}
+
+/// Operator constraints on which candidate routes are considered during detection.
+/// Hop counts are swaps (pools) in the full cycle. The default allows every route.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteFilter {
+    pub exclude_pools: HashSet<H160>, // known-bad / blacklisted pools
+    pub min_hops: Option<usize>,
+    pub max_hops: Option<usize>,
+    pub required_base: Option<H160>, // only routes starting and ending in this base token (excludes cross-base routes)
 }
 
-/// Build a cache of all 2-hop and 3-hop arbitrage cycles for each base token using parallel processing.
+impl RouteFilter {
+    /// Whether `route` passes every constraint
+    pub fn allows(&self, route: &RoutePath, token_index: &TokenIndexMap) -> bool {
+        let hops = route.pools.len();
+        if self.min_hops.is_some_and(|min| hops < min) || self.max_hops.is_some_and(|max| hops > max) {
+            return false;
+        }
+        if route.pools.iter().any(|pool| self.exclude_pools.contains(pool)) {
+            return false;
+        }
+        match self.required_base {
+            Some(base) => [route.hops.first(), route.hops.last()]
+                .into_iter()
+                .all(|end| end.and_then(|idx| token_index.index_to_address.get(idx)).is_some_and(|addr| *addr == base)),
+            None => true,
+        }
+    }
+}
+
+/// Build a cache of every arbitrage cycle of up to `max_hops` pools for each base token using
+/// parallel processing: 2-hop and 3-hop cycles directly, longer ones by a depth-first walk of
+/// the pool graph that never revisits a token. Each route is cached under every intermediate.
+/// Routes through a token `is_routable` rejects (the config's allow/deny lists) are left out.
 pub fn build_route_cache(
     all_tokens: &HashMap<H160, u32>,
     all_pools: &[PoolMeta],
     base_tokens: &[H160],
     token_tax_info: &HashMap<H160, TokenTaxInfo>, // <-- add this argument
-) -> DashMap<u32, Vec<RoutePath>> {
-    println!("Building route cache for {} tokens and {} pools", all_tokens.len(), all_pools.len());
+    is_routable: &(dyn Fn(&H160) -> bool + Sync),
+    max_hops: usize,
+) -> DashMap<u32, Arc<Vec<RoutePath>>> {
+    println!("Building route cache for {} tokens and {} pools (max {} hops)", all_tokens.len(), all_pools.len(), max_hops);
     
-    // Build a quick lookup: (tokenA, tokenB) -> (pool, dex_type)
-    let mut pool_lookup: HashMap<(u32, u32), (&PoolMeta, bool)> = HashMap::new();
-    for pool in all_pools {
-        if let (Some(&idx0), Some(&idx1)) = (all_tokens.get(&pool.token0), all_tokens.get(&pool.token1)) {
-            pool_lookup.insert((idx0, idx1), (pool, true));
-            pool_lookup.insert((idx1, idx0), (pool, false));
-        }
-    }
+    let pool_lookup = build_pool_lookup(all_tokens, all_pools);
+    let pair_pools = build_pair_pools(all_tokens, all_pools);
     
     // Convert all_tokens to Vec for parallel processing
     let all_tokens_vec: Vec<(H160, u32)> = all_tokens.iter().map(|(k, v)| (*k, *v)).collect();
+    let index_to_token: HashMap<u32, H160> = all_tokens.iter().map(|(k, v)| (*v, *k)).collect();
+    // Tokens whose tax simulation failed never appear in a route
+    let tax_ok = |token: &H160| token_tax_info.get(token).is_none_or(|tax| tax.simulation_success);
+    // Token -> tokens it shares a pool with, for the 4+ hop walk
+    let adjacency = if max_hops >= 4 { build_adjacency(&pool_lookup) } else { HashMap::new() };
     
     // Use DashMap for thread-safe concurrent insertion
-    let result = DashMap::new();
+    let result: DashMap<u32, Arc<Vec<RoutePath>>> = DashMap::new();
+    // Routes that had pools but touched a denied (or not allowed) token
+    let excluded = AtomicUsize::new(0);
     
     // Process each base token in parallel
     base_tokens.par_iter().for_each(|&base| {
@@ -66,82 +203,333 @@ pub fn build_route_cache(
             Some(idx) => *idx,
             None => return,
         };
+        if !is_routable(&base) {
+            return;
+        }
         
         let mut token_to_paths: HashMap<u32, HashSet<RoutePath>> = HashMap::new();
         
-        // 2-hop: base -> X -> base
-        let two_hop_paths: Vec<(u32, RoutePath)> = all_tokens_vec.par_iter()
-            .filter_map(|&(token_addr, x_idx)| {
-                if x_idx == base_idx { return None; }
-                // --- Skip tokens with simulationSuccess == false ---
-                if let Some(tax) = token_tax_info.get(&token_addr) {
-                    if !tax.simulation_success { return None; }
-                }
-                if let Some(&(pool1, _)) = pool_lookup.get(&(base_idx, x_idx)) {
-                    if let Some(&(pool2, _)) = pool_lookup.get(&(x_idx, base_idx)) {
-                        let path = RoutePath {
-                            hops: vec![base_idx, x_idx, base_idx],
-                            pools: vec![pool1.address, pool2.address],
-                            dex_types: vec![pool1.dex_type.clone(), pool2.dex_type.clone()],
-                        };
-                        return Some((x_idx, path));
+        // 2-hop: base -> X -> base, through two different pools of the same pair
+        if max_hops >= 2 {
+            let two_hop_paths: Vec<(u32, RoutePath)> = all_tokens_vec.par_iter()
+                .flat_map_iter(|&(token_addr, x_idx)| {
+                    let mut paths = Vec::new();
+                    if x_idx == base_idx || !tax_ok(&token_addr) { return paths; }
+                    let Some(pools) = pair_pools.get(&pair_key(base_idx, x_idx)) else { return paths; };
+                    for pool1 in pools {
+                        for pool2 in pools {
+                            if pool1.address == pool2.address { continue; }
+                            if !is_routable(&token_addr) {
+                                excluded.fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
+                            paths.push((x_idx, RoutePath {
+                                hops: vec![base_idx, x_idx, base_idx],
+                                pools: vec![pool1.address, pool2.address],
+                                dex_types: vec![pool1.dex_type.clone(), pool2.dex_type.clone()],
+                                ..Default::default()
+                            }));
+                        }
                     }
-                }
-                None
-            })
-            .collect();
-        for (x_idx, path) in two_hop_paths {
-            token_to_paths.entry(x_idx).or_default().insert(path);
+                    paths
+                })
+                .collect();
+            for (x_idx, path) in two_hop_paths {
+                token_to_paths.entry(x_idx).or_default().insert(path);
+            }
         }
         
         // 3-hop: base -> X -> Y -> base
-        let three_hop_paths: Vec<((u32, u32), RoutePath)> = all_tokens_vec.par_iter()
-            .flat_map_iter(|&(token_addr, x_idx)| {
-                if x_idx == base_idx { return Vec::new().into_iter(); }
-                // --- Skip tokens with simulationSuccess == false ---
-                if let Some(tax) = token_tax_info.get(&token_addr) {
-                    if !tax.simulation_success { return Vec::new().into_iter(); }
-                }
-                all_tokens_vec.par_iter()
-                    .filter_map(|&(token_addr_y, y_idx)| {
-                        if y_idx == base_idx || y_idx == x_idx { return None; }
-                        // --- Skip tokens with simulationSuccess == false ---
-                        if let Some(tax) = token_tax_info.get(&token_addr_y) {
-                            if !tax.simulation_success { return None; }
-                        }
-                        if let Some(&(pool1, _)) = pool_lookup.get(&(base_idx, x_idx)) {
-                            if let Some(&(pool2, _)) = pool_lookup.get(&(x_idx, y_idx)) {
-                                if let Some(&(pool3, _)) = pool_lookup.get(&(y_idx, base_idx)) {
-                                    let path = RoutePath {
-                                        hops: vec![base_idx, x_idx, y_idx, base_idx],
-                                        pools: vec![pool1.address, pool2.address, pool3.address],
-                                        dex_types: vec![pool1.dex_type.clone(), pool2.dex_type.clone(), pool3.dex_type.clone()],
-                                    };
-                                    return Some(((x_idx, y_idx), path));
+        if max_hops >= 3 {
+            let three_hop_paths: Vec<((u32, u32), RoutePath)> = all_tokens_vec.par_iter()
+                .flat_map_iter(|&(token_addr, x_idx)| {
+                    if x_idx == base_idx { return Vec::new().into_iter(); }
+                    // --- Skip tokens with simulationSuccess == false ---
+                    if !tax_ok(&token_addr) { return Vec::new().into_iter(); }
+                    all_tokens_vec.par_iter()
+                        .filter_map(|&(token_addr_y, y_idx)| {
+                            if y_idx == base_idx || y_idx == x_idx { return None; }
+                            // --- Skip tokens with simulationSuccess == false ---
+                            if !tax_ok(&token_addr_y) { return None; }
+                            if let Some(&(pool1, _)) = pool_lookup.get(&(base_idx, x_idx)) {
+                                if let Some(&(pool2, _)) = pool_lookup.get(&(x_idx, y_idx)) {
+                                    if let Some(&(pool3, _)) = pool_lookup.get(&(y_idx, base_idx)) {
+                                        if !is_routable(&token_addr) || !is_routable(&token_addr_y) {
+                                            excluded.fetch_add(1, Ordering::Relaxed);
+                                            return None;
+                                        }
+                                        let path = RoutePath {
+                                            hops: vec![base_idx, x_idx, y_idx, base_idx],
+                                            pools: vec![pool1.address, pool2.address, pool3.address],
+                                            dex_types: vec![pool1.dex_type.clone(), pool2.dex_type.clone(), pool3.dex_type.clone()],
+                                            ..Default::default()
+                                        };
+                                        return Some(((x_idx, y_idx), path));
+                                    }
                                 }
                             }
+                            None
+                        })
+                        .collect::<Vec<_>>()
+                        .into_iter()
+                })
+                .collect();
+            for ((x_idx, y_idx), path) in three_hop_paths {
+                token_to_paths.entry(x_idx).or_default().insert(path.clone());
+                token_to_paths.entry(y_idx).or_default().insert(path);
+            }
+        }
+        
+        // 4+ hops: base -> X -> ... -> base, one branch per first hop
+        if max_hops >= 4 {
+            let neighbours = adjacency.get(&base_idx).map(Vec::as_slice).unwrap_or_default();
+            let long_paths: Vec<RoutePath> = neighbours.par_iter()
+                .flat_map_iter(|&x_idx| {
+                    let mut cycles = Vec::new();
+                    let skip = |idx: u32| index_to_token.get(&idx).is_none_or(|token| !tax_ok(token));
+                    if !skip(x_idx) {
+                        extend_cycles(&mut vec![base_idx, x_idx], max_hops, &adjacency, &skip, &mut cycles);
+                    }
+                    cycles.into_iter().filter_map(|hops| {
+                        if !hops[1..hops.len() - 1].iter().all(|idx| is_routable(&index_to_token[idx])) {
+                            excluded.fetch_add(1, Ordering::Relaxed);
+                            return None;
                         }
-                        None
+                        let pools: Vec<&PoolMeta> = hops.windows(2).map(|w| pool_lookup[&(w[0], w[1])].0).collect();
+                        Some(RoutePath {
+                            pools: pools.iter().map(|p| p.address).collect(),
+                            dex_types: pools.iter().map(|p| p.dex_type.clone()).collect(),
+                            hops,
+                            ..Default::default()
+                        })
                     })
                     .collect::<Vec<_>>()
-                    .into_iter()
-            })
-            .collect();
-        for ((x_idx, y_idx), path) in three_hop_paths {
-            token_to_paths.entry(x_idx).or_default().insert(path.clone());
-            token_to_paths.entry(y_idx).or_default().insert(path);
+                })
+                .collect();
+            for path in long_paths {
+                for &idx in &path.hops[1..path.hops.len() - 1] {
+                    token_to_paths.entry(idx).or_default().insert(path.clone());
+                }
+            }
         }
         
         // Insert results into the shared DashMap
         for (token_idx, paths) in token_to_paths {
-            result.entry(token_idx).or_insert_with(Vec::new).extend(paths.into_iter());
+            let paths: Vec<RoutePath> = paths.into_iter().filter(|path| path.pools.len() <= max_hops).collect();
+            if !paths.is_empty() {
+                Arc::make_mut(&mut result.entry(token_idx).or_default()).extend(paths);
+            }
         }
     });
     
+    precompute_route_legs(&result);
+    let excluded = excluded.into_inner();
+    if excluded > 0 {
+        println!("Token allow/deny lists excluded {} routes", excluded);
+    }
     println!("Route cache built. Unique tokens with paths: {}", result.len());
     result
 }
 
+/// Depth-first extension of `path` (starting at the base token) to cycles of 4..=`max_hops`
+/// pools back to that base. Intermediates are distinct and none of them is `skip`ped.
+fn extend_cycles(
+    path: &mut Vec<u32>,
+    max_hops: usize,
+    adjacency: &HashMap<u32, Vec<u32>>,
+    skip: &dyn Fn(u32) -> bool,
+    cycles: &mut Vec<Vec<u32>>,
+) {
+    let last = path[path.len() - 1];
+    let used = path.len() - 1; // pools so far
+    for &next in adjacency.get(&last).into_iter().flatten() {
+        if next == path[0] {
+            if used + 1 >= 4 {
+                let mut cycle = path.clone();
+                cycle.push(next);
+                cycles.push(cycle);
+            }
+        } else if used + 2 <= max_hops && !path.contains(&next) && !skip(next) {
+            path.push(next);
+            extend_cycles(path, max_hops, adjacency, skip, cycles);
+            path.pop();
+        }
+    }
+}
+
+/// Quick lookup: (tokenA, tokenB) -> (pool, tokenA is token0)
+fn build_pool_lookup<'a>(
+    all_tokens: &HashMap<H160, u32>,
+    all_pools: &'a [PoolMeta],
+) -> HashMap<(u32, u32), (&'a PoolMeta, bool)> {
+    let mut pool_lookup = HashMap::new();
+    for pool in all_pools {
+        if let (Some(&idx0), Some(&idx1)) = (all_tokens.get(&pool.token0), all_tokens.get(&pool.token1)) {
+            pool_lookup.insert((idx0, idx1), (pool, true));
+            pool_lookup.insert((idx1, idx0), (pool, false));
+        }
+    }
+    pool_lookup
+}
+
+/// Every pool of each token pair, keyed by `pair_key`; `build_pool_lookup` keeps only the last
+fn build_pair_pools<'a>(all_tokens: &HashMap<H160, u32>, all_pools: &'a [PoolMeta]) -> HashMap<(u32, u32), Vec<&'a PoolMeta>> {
+    let mut pair_pools: HashMap<(u32, u32), Vec<&PoolMeta>> = HashMap::new();
+    for pool in all_pools {
+        if let (Some(&idx0), Some(&idx1)) = (all_tokens.get(&pool.token0), all_tokens.get(&pool.token1)) {
+            pair_pools.entry(pair_key(idx0, idx1)).or_default().push(pool);
+        }
+    }
+    pair_pools
+}
+
+fn pair_key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+/// Sorted neighbour lists, so the cycle walk is deterministic
+fn build_adjacency(pool_lookup: &HashMap<(u32, u32), (&PoolMeta, bool)>) -> HashMap<u32, Vec<u32>> {
+    let mut adjacency: HashMap<u32, Vec<u32>> = HashMap::new();
+    for &(a, b) in pool_lookup.keys() {
+        adjacency.entry(a).or_default().push(b);
+    }
+    for neighbours in adjacency.values_mut() {
+        neighbours.sort_unstable();
+    }
+    adjacency
+}
+
+/// Add 2-hop cross-base routes `base_a -> X -> base_b` (one per ordered pair of distinct
+/// base tokens) to a cache built by `build_route_cache`, keyed by X. Such a route ends in
+/// a different token than it starts with, so its profit is only meaningful once the output
+/// is valued in the input base token; see `arbitrage_finder::normalized_profit`.
+pub fn add_cross_base_routes(
+    route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>,
+    all_tokens: &HashMap<H160, u32>,
+    all_pools: &[PoolMeta],
+    base_tokens: &[H160],
+    token_tax_info: &HashMap<H160, TokenTaxInfo>,
+    is_routable: &(dyn Fn(&H160) -> bool + Sync),
+) -> usize {
+    let pool_lookup = build_pool_lookup(all_tokens, all_pools);
+    let base_indices: Vec<u32> = base_tokens
+        .iter()
+        .filter(|base| is_routable(base))
+        .filter_map(|base| all_tokens.get(base).copied())
+        .collect();
+
+    let cross_routes: Vec<(u32, RoutePath)> = all_tokens
+        .par_iter()
+        .filter(|(token_addr, x_idx)| {
+            !base_indices.contains(x_idx)
+                && is_routable(token_addr)
+                && token_tax_info.get(*token_addr).is_none_or(|tax| tax.simulation_success)
+        })
+        .flat_map_iter(|(_, &x_idx)| {
+            let mut routes = Vec::new();
+            for &base_a in &base_indices {
+                for &base_b in &base_indices {
+                    if base_a == base_b {
+                        continue;
+                    }
+                    let (Some(&(pool1, _)), Some(&(pool2, _))) =
+                        (pool_lookup.get(&(base_a, x_idx)), pool_lookup.get(&(x_idx, base_b)))
+                    else {
+                        continue;
+                    };
+                    routes.push((x_idx, RoutePath {
+                        hops: vec![base_a, x_idx, base_b],
+                        pools: vec![pool1.address, pool2.address],
+                        dex_types: vec![pool1.dex_type.clone(), pool2.dex_type.clone()],
+                        ..Default::default()
+                    }));
+                }
+            }
+            routes
+        })
+        .collect();
+
+    let added = cross_routes.len();
+    for (x_idx, path) in cross_routes {
+        let mut entry = route_cache.entry(x_idx).or_default();
+        if !entry.contains(&path) {
+            Arc::make_mut(&mut entry).push(path);
+        }
+    }
+    precompute_route_legs(route_cache);
+    println!("Added {} cross-base routes", added);
+    added
+}
+
+/// Hash of everything the route cache is built from: token indices, the pools in load order (the
+/// pair lookup keeps the last pool per pair), the bases, which tokens the tax filter and the
+/// allow/deny lists leave out, the hop limit and whether cross-base routes are added. A saved
+/// cache is only reused while this matches.
+pub fn route_cache_fingerprint(
+    all_tokens: &HashMap<H160, u32>,
+    all_pools: &[PoolMeta],
+    base_tokens: &[H160],
+    token_tax_info: &HashMap<H160, TokenTaxInfo>,
+    is_routable: &(dyn Fn(&H160) -> bool + Sync),
+    max_hops: usize,
+    cross_base_routes: bool,
+) -> H256 {
+    let mut tokens: Vec<(u32, H160)> = all_tokens.iter().map(|(token, idx)| (*idx, *token)).collect();
+    tokens.sort();
+    let mut bytes = Vec::with_capacity(tokens.len() * 25 + all_pools.len() * 64);
+    for (idx, token) in &tokens {
+        let excluded = !is_routable(token) || token_tax_info.get(token).is_some_and(|tax| !tax.simulation_success);
+        bytes.extend_from_slice(&idx.to_le_bytes());
+        bytes.extend_from_slice(token.as_bytes());
+        bytes.push(excluded as u8);
+    }
+    for pool in all_pools {
+        bytes.extend_from_slice(pool.address.as_bytes());
+        bytes.extend_from_slice(pool.token0.as_bytes());
+        bytes.extend_from_slice(pool.token1.as_bytes());
+        bytes.extend_from_slice(format!("{:?}", pool.dex_type).as_bytes());
+    }
+    for base in base_tokens {
+        bytes.extend_from_slice(base.as_bytes());
+    }
+    bytes.extend_from_slice(&(max_hops as u64).to_le_bytes());
+    bytes.push(cross_base_routes as u8);
+    H256(keccak256(bytes))
+}
+
+/// Write `route_cache` to `path` as the 32-byte fingerprint followed by `format`'s encoding.
+/// Written to a temp file and renamed, so a crash mid-write keeps the previous file.
+pub fn save_route_cache(
+    route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>,
+    path: &Path,
+    fingerprint: H256,
+    format: RouteCacheFormat,
+) -> anyhow::Result<()> {
+    let mut bytes = fingerprint.as_bytes().to_vec();
+    bytes.extend(format.encode(route_cache)?);
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// The cache saved at `path`, with its legs precomputed. `None` if the file doesn't exist or
+/// was built from different inputs than `fingerprint` describes; either way it must be rebuilt.
+pub fn load_route_cache(path: &Path, fingerprint: H256) -> anyhow::Result<Option<DashMap<u32, Arc<Vec<RoutePath>>>>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if bytes.len() < 32 {
+        bail!("route cache file is {} bytes, too short for its fingerprint", bytes.len());
+    }
+    if &bytes[..32] != fingerprint.as_bytes() {
+        return Ok(None);
+    }
+    RouteCacheFormat::decode(&bytes[32..]).map(Some)
+}
+
 /// Build a mapping: tokenX -> baseToken -> [pools...]
 pub fn build_token_to_base_token_pools(
     all_pools: &[PoolMeta],
@@ -200,5 +588,171 @@ mod tests {
             assert_eq!(cake_usdt_pools, &vec![H160::from_low_u64_be(1001)]);
         }
     }
-}
 
+    #[test]
+    fn test_route_filter() {
+        let usdt = H160::from_low_u64_be(1);
+        let wbnb = H160::from_low_u64_be(2);
+        let token_index = TokenIndexMap {
+            address_to_index: HashMap::from([(usdt, 0), (wbnb, 1)]),
+            index_to_address: HashMap::from([(0, usdt), (1, wbnb)]),
+        };
+        let (pool_a, pool_b, pool_c) = (H160::from_low_u64_be(1001), H160::from_low_u64_be(1002), H160::from_low_u64_be(1003));
+        let two_hop = RoutePath { hops: vec![0, 5, 0], pools: vec![pool_a, pool_b], dex_types: vec![DEXType::PancakeV2; 2], ..Default::default() };
+        let three_hop = RoutePath { hops: vec![1, 5, 6, 1], pools: vec![pool_a, pool_b, pool_c], dex_types: vec![DEXType::PancakeV2; 3], ..Default::default() };
+
+        let allow_all = RouteFilter::default();
+        assert!(allow_all.allows(&two_hop, &token_index) && allow_all.allows(&three_hop, &token_index));
+
+        let hop_bounds = RouteFilter { min_hops: Some(3), max_hops: Some(3), ..Default::default() };
+        assert!(!hop_bounds.allows(&two_hop, &token_index));
+        assert!(hop_bounds.allows(&three_hop, &token_index));
+
+        let excluded = RouteFilter { exclude_pools: HashSet::from([pool_c]), ..Default::default() };
+        assert!(excluded.allows(&two_hop, &token_index));
+        assert!(!excluded.allows(&three_hop, &token_index));
+
+        let usdt_only = RouteFilter { required_base: Some(usdt), ..Default::default() };
+        assert!(usdt_only.allows(&two_hop, &token_index));
+        assert!(!usdt_only.allows(&three_hop, &token_index));
+        let cross_base = RoutePath { hops: vec![0, 5, 1], ..two_hop.clone() };
+        assert!(allow_all.allows(&cross_base, &token_index));
+        assert!(!usdt_only.allows(&cross_base, &token_index));
+    }
+
+    #[test]
+    fn test_token_lists_limit_routes() {
+        let (usdt, cake, scam) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3));
+        let all_tokens = HashMap::from([(usdt, 0), (cake, 1), (scam, 2)]);
+        let meta = |token0, token1, n| PoolMeta { token0, token1, address: H160::from_low_u64_be(n), dex_type: DEXType::PancakeV2, factory: None, fee: None };
+        let all_pools = vec![meta(usdt, cake, 1001), meta(cake, scam, 1002), meta(scam, usdt, 1003)];
+        let build = |is_routable: &(dyn Fn(&H160) -> bool + Sync)| {
+            build_route_cache(&all_tokens, &all_pools, &[usdt], &HashMap::new(), is_routable, 3)
+        };
+
+        // usdt -> cake -> scam -> usdt and back, cached under both intermediates
+        let all = build(&|_| true);
+        assert_eq!(all.get(&1).unwrap().len(), 2);
+        // Denying scam, or allowing only cake, removes the only cycle
+        assert!(build(&|t| *t != scam).is_empty());
+        assert!(build(&|t| [usdt, cake].contains(t)).is_empty());
+        // A denied base builds nothing at all
+        assert!(build(&|t| *t != usdt).is_empty());
+    }
+
+    #[test]
+    fn test_add_cross_base_routes() {
+        let (usdt, wbnb, cake, busd) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3), H160::from_low_u64_be(4));
+        let all_tokens = HashMap::from([(usdt, 0), (wbnb, 1), (cake, 2), (busd, 3)]);
+        let meta = |token0, token1, n| PoolMeta { token0, token1, address: H160::from_low_u64_be(n), dex_type: DEXType::PancakeV2, factory: None, fee: None };
+        let all_pools = vec![meta(usdt, cake, 1001), meta(wbnb, cake, 1002)];
+        let base_tokens = vec![usdt, wbnb, busd];
+        let route_cache = build_route_cache(&all_tokens, &all_pools, &base_tokens, &HashMap::new(), &|_| true, 3);
+        // One CAKE pool per base: no same-base cycle
+        assert!(route_cache.get(&2).is_none());
+
+        assert_eq!(add_cross_base_routes(&route_cache, &all_tokens, &all_pools, &base_tokens, &HashMap::new(), &|_| true), 2);
+        let routes = route_cache.get(&2).unwrap();
+        let mut hops: Vec<_> = routes.iter().map(|r| r.hops.clone()).collect();
+        hops.sort();
+        assert_eq!(hops, vec![vec![0, 2, 1], vec![1, 2, 0]]);
+        assert!(routes.iter().all(|r| !r.is_cycle() && r.legs.is_some()));
+        drop(routes);
+
+        // Re-running doesn't duplicate routes
+        add_cross_base_routes(&route_cache, &all_tokens, &all_pools, &base_tokens, &HashMap::new(), &|_| true);
+        assert_eq!(route_cache.get(&2).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_max_hops_limits_stored_routes() {
+        let (usdt, cake, wbnb, busd, doge) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3), H160::from_low_u64_be(4), H160::from_low_u64_be(5));
+        let all_tokens = HashMap::from([(usdt, 0), (cake, 1), (wbnb, 2), (busd, 3), (doge, 4)]);
+        let meta = |token0, token1, n| PoolMeta { token0, token1, address: H160::from_low_u64_be(n), dex_type: DEXType::PancakeV2, factory: None, fee: None };
+        // Two USDT-CAKE pools (2-hop), a CAKE-WBNB-USDT triangle (3-hop) and a
+        // USDT-CAKE-BUSD-DOGE square (4-hop)
+        let all_pools = vec![
+            meta(usdt, cake, 1001), meta(cake, usdt, 1002),
+            meta(cake, wbnb, 1003), meta(wbnb, usdt, 1004),
+            meta(cake, busd, 1005), meta(busd, doge, 1006), meta(doge, usdt, 1007),
+        ];
+        let build = |max_hops| build_route_cache(&all_tokens, &all_pools, &[usdt], &HashMap::new(), &|_| true, max_hops);
+        let hop_counts = |cache: &DashMap<u32, Arc<Vec<RoutePath>>>| {
+            let mut counts: Vec<usize> = cache.iter().flat_map(|e| e.value().iter().map(|r| r.pools.len()).collect::<Vec<_>>()).collect();
+            counts.sort();
+            counts.dedup();
+            counts
+        };
+
+        let two = build(2);
+        assert_eq!(hop_counts(&two), vec![2]);
+        // Both orders of the CAKE pools, only under CAKE
+        assert_eq!(two.get(&1).unwrap().len(), 2);
+        assert!(two.get(&2).is_none() && two.get(&3).is_none());
+
+        assert_eq!(hop_counts(&build(3)), vec![2, 3]);
+
+        let four = build(4);
+        assert_eq!(hop_counts(&four), vec![2, 3, 4]);
+        let doge_routes = four.get(&4).unwrap();
+        let mut hops: Vec<_> = doge_routes.iter().map(|r| r.hops.clone()).collect();
+        hops.sort();
+        assert_eq!(hops, vec![vec![0, 1, 3, 4, 0], vec![0, 4, 3, 1, 0]]);
+        assert!(doge_routes.iter().all(|r| r.pools.len() == 4 && r.legs.is_some()));
+    }
+
+    #[test]
+    fn test_two_hop_routes_never_reuse_a_pool() {
+        let (usdt, cake) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let all_tokens = HashMap::from([(usdt, 0), (cake, 1)]);
+        let meta = |token0, token1, n| PoolMeta { token0, token1, address: H160::from_low_u64_be(n), dex_type: DEXType::PancakeV2, factory: None, fee: None };
+        let build = |all_pools: &[PoolMeta]| build_route_cache(&all_tokens, all_pools, &[usdt], &HashMap::new(), &|_| true, 2);
+
+        // A single pool can't be bought and sold through in the same cycle
+        assert!(build(&[meta(usdt, cake, 1001)]).is_empty());
+
+        // Three pools of the pair, listed in either token order: every ordered pair of distinct pools
+        let pools = [meta(usdt, cake, 1001), meta(cake, usdt, 1002), meta(usdt, cake, 1003)];
+        let cache = build(&pools);
+        let routes = cache.get(&1).unwrap();
+        assert_eq!(routes.len(), 6);
+        assert!(routes.iter().all(|r| r.hops == vec![0, 1, 0] && r.pools[0] != r.pools[1]));
+    }
+
+    #[test]
+    fn test_saved_route_cache_reloads_until_inputs_change() {
+        let (usdt, cake, wbnb) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3));
+        let all_tokens = HashMap::from([(usdt, 0), (cake, 1), (wbnb, 2)]);
+        let meta = |token0, token1, n| PoolMeta { token0, token1, address: H160::from_low_u64_be(n), dex_type: DEXType::PancakeV2, factory: None, fee: None };
+        let mut all_pools = vec![meta(usdt, cake, 1001), meta(cake, wbnb, 1002), meta(wbnb, usdt, 1003)];
+        all_pools[1].dex_type = DEXType::Other("dex V3".to_string());
+        let fingerprint = |pools: &[PoolMeta], max_hops| route_cache_fingerprint(&all_tokens, pools, &[usdt], &HashMap::new(), &|_| true, max_hops, false);
+        let built = build_route_cache(&all_tokens, &all_pools, &[usdt], &HashMap::new(), &|_| true, 3);
+
+        let path = std::env::temp_dir().join(format!("route_cache_{}.bin", std::process::id()));
+        for format in [RouteCacheFormat::Binary, RouteCacheFormat::Json] {
+            save_route_cache(&built, &path, fingerprint(&all_pools, 3), format).unwrap();
+            let loaded = load_route_cache(&path, fingerprint(&all_pools, 3)).unwrap().unwrap();
+            assert_eq!(loaded.len(), built.len());
+            for entry in built.iter() {
+                let routes = loaded.get(entry.key()).unwrap();
+                assert_eq!(routes.len(), entry.value().len());
+                assert!(entry.value().iter().all(|route| routes.contains(route)));
+                assert!(routes.iter().all(|route| route.legs.is_some()));
+            }
+        }
+
+        // A new pool or another hop limit invalidates the file
+        let mut more_pools = all_pools.clone();
+        more_pools.push(meta(usdt, wbnb, 1004));
+        assert!(load_route_cache(&path, fingerprint(&more_pools, 3)).unwrap().is_none());
+        assert!(load_route_cache(&path, fingerprint(&all_pools, 4)).unwrap().is_none());
+        let _ = std::fs::remove_file(&path);
+        assert!(load_route_cache(&path, fingerprint(&all_pools, 3)).unwrap().is_none());
+
+        // Pool metadata round-trips through serde, addresses included
+        let json = serde_json::to_string(&all_pools[1]).unwrap();
+        let back: PoolMeta = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.token0, back.token1, back.address, back.dex_type), (cake, wbnb, H160::from_low_u64_be(1002), DEXType::Other("dex V3".to_string())));
+    }
+}
@@ -0,0 +1,276 @@
+use crate::route_cache::{DEXType, RoutePath};
+use crate::schema::{from_versioned_json, to_versioned_json, SCHEMA_VERSION};
+use crate::split_route_path::precompute_route_legs;
+use anyhow::{bail, Context};
+use dashmap::DashMap;
+use ethers::types::H160;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// First bytes of a binary route cache
+const MAGIC: &[u8; 4] = b"ARRC";
+/// Tag of `DEXType::Other`, followed by the name
+const OTHER_TAG: u8 = u8::MAX;
+/// Smallest encoded route (no hops, no pools): bounds how many routes a count can promise
+const MIN_ROUTE_BYTES: usize = 2;
+
+/// On-disk form of a route cache. Binary is several times smaller and faster to load;
+/// JSON is readable and survives schema migrations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RouteCacheFormat {
+    Json,
+    #[default]
+    Binary,
+}
+
+impl RouteCacheFormat {
+    pub fn encode(&self, route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>) -> anyhow::Result<Vec<u8>> {
+        match self {
+            RouteCacheFormat::Json => Ok(encode_json(route_cache)?.into_bytes()),
+            RouteCacheFormat::Binary => encode_binary(route_cache),
+        }
+    }
+
+    /// Decode either format (told apart by the magic) and precompute the route legs
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<DashMap<u32, Arc<Vec<RoutePath>>>> {
+        let route_cache = if bytes.starts_with(MAGIC) {
+            decode_binary(bytes)?
+        } else {
+            decode_json(std::str::from_utf8(bytes).context("route cache is neither binary nor UTF-8 JSON")?)?
+        };
+        precompute_route_legs(&route_cache);
+        Ok(route_cache)
+    }
+}
+
+fn dex_tag(dex_type: &DEXType) -> u8 {
+    match dex_type {
+        DEXType::PancakeV2 => 0,
+        DEXType::BiSwapV2 => 1,
+        DEXType::ApeSwapV2 => 2,
+        DEXType::BakeryV2 => 3,
+        DEXType::SushiV2 => 4,
+        DEXType::PancakeV3 => 5,
+        DEXType::BiSwapV3 => 6,
+        DEXType::ApeSwapV3 => 7,
+        DEXType::BakeryV3 => 8,
+        DEXType::SushiV3 => 9,
+        DEXType::StableSwap => 10,
+        DEXType::Other(_) => OTHER_TAG,
+    }
+}
+
+fn dex_from_tag(tag: u8) -> Option<DEXType> {
+    Some(match tag {
+        0 => DEXType::PancakeV2,
+        1 => DEXType::BiSwapV2,
+        2 => DEXType::ApeSwapV2,
+        3 => DEXType::BakeryV2,
+        4 => DEXType::SushiV2,
+        5 => DEXType::PancakeV3,
+        6 => DEXType::BiSwapV3,
+        7 => DEXType::ApeSwapV3,
+        8 => DEXType::BakeryV3,
+        9 => DEXType::SushiV3,
+        10 => DEXType::StableSwap,
+        _ => return None,
+    })
+}
+
+/// Versioned JSON: `{"schema_version": N, "data": {"<token index>": [RoutePath, ...]}}`
+pub fn encode_json(route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>) -> serde_json::Result<String> {
+    let sorted: BTreeMap<u32, Vec<RoutePath>> =
+        route_cache.iter().map(|entry| (*entry.key(), Vec::clone(entry.value()))).collect();
+    to_versioned_json(&sorted)
+}
+
+pub fn decode_json(json: &str) -> anyhow::Result<DashMap<u32, Arc<Vec<RoutePath>>>> {
+    let routes: HashMap<u32, Vec<RoutePath>> = from_versioned_json(json)?;
+    Ok(routes.into_iter().map(|(token, routes)| (token, Arc::new(routes))).collect())
+}
+
+/// Little-endian binary layout:
+/// `"ARRC" | schema_version: u32 | entries: u32`, then per entry
+/// `token: u32 | routes: u32`, then per route
+/// `hops: u8 | hop: u32 ... | pools: u8 | (address: [u8; 20] | dex tag: u8 [| name len: u16 | name]) ...`.
+/// Entries are written in token order so equal caches encode to equal bytes. Routes with more
+/// than 255 hops or pools, or DEX names over 65535 bytes, don't fit and are an error.
+pub fn encode_binary(route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>) -> anyhow::Result<Vec<u8>> {
+    let mut entries: Vec<(u32, Arc<Vec<RoutePath>>)> =
+        route_cache.iter().map(|entry| (*entry.key(), entry.value().clone())).collect();
+    entries.sort_by_key(|(token, _)| *token);
+
+    let mut out = Vec::with_capacity(12 + entries.iter().map(|(_, routes)| 8 + routes.len() * 60).sum::<usize>());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (token, routes) in &entries {
+        out.extend_from_slice(&token.to_le_bytes());
+        out.extend_from_slice(&(routes.len() as u32).to_le_bytes());
+        for route in routes.iter() {
+            let (Ok(hop_count), Ok(pool_count)) = (u8::try_from(route.hops.len()), u8::try_from(route.pools.len())) else {
+                bail!("route under token {} has {} hops and {} pools; at most 255 of each fit", token, route.hops.len(), route.pools.len());
+            };
+            out.push(hop_count);
+            for hop in &route.hops {
+                out.extend_from_slice(&hop.to_le_bytes());
+            }
+            out.push(pool_count);
+            for (i, pool) in route.pools.iter().enumerate() {
+                out.extend_from_slice(pool.as_bytes());
+                let dex_type = route.dex_types.get(i).cloned().unwrap_or(DEXType::Other(String::new()));
+                out.push(dex_tag(&dex_type));
+                if let DEXType::Other(name) = &dex_type {
+                    let len = u16::try_from(name.len()).with_context(|| format!("DEX name of {} bytes is too long", name.len()))?;
+                    out.extend_from_slice(&len.to_le_bytes());
+                    out.extend_from_slice(name.as_bytes());
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Bounds-checked reader over the binary form
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        let Some(slice) = self.bytes.get(self.pos..self.pos + n) else {
+            bail!("route cache truncated at byte {}", self.pos);
+        };
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+}
+
+pub fn decode_binary(bytes: &[u8]) -> anyhow::Result<DashMap<u32, Arc<Vec<RoutePath>>>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(4)? != MAGIC {
+        bail!("not a binary route cache");
+    }
+    // No field-level migrations for the binary form: older files are rebuilt, not upgraded
+    let version = reader.u32()?;
+    if version != SCHEMA_VERSION {
+        bail!("binary route cache has schema version {} (this build reads {}); rebuild it", version, SCHEMA_VERSION);
+    }
+    let route_cache = DashMap::new();
+    for _ in 0..reader.u32()? {
+        let token = reader.u32()?;
+        let count = reader.u32()? as usize;
+        // A corrupt count can't reserve more routes than the remaining bytes could hold
+        let mut routes = Vec::with_capacity(count.min((bytes.len() - reader.pos) / MIN_ROUTE_BYTES));
+        for _ in 0..count {
+            let hops = (0..reader.u8()?).map(|_| reader.u32()).collect::<anyhow::Result<Vec<u32>>>()?;
+            let pool_count = reader.u8()? as usize;
+            let mut pools = Vec::with_capacity(pool_count);
+            let mut dex_types = Vec::with_capacity(pool_count);
+            for _ in 0..pool_count {
+                pools.push(H160::from_slice(reader.take(20)?));
+                let tag = reader.u8()?;
+                let dex_type = if tag == OTHER_TAG {
+                    let len = reader.u16()? as usize;
+                    DEXType::Other(String::from_utf8(reader.take(len)?.to_vec())?)
+                } else {
+                    dex_from_tag(tag).with_context(|| format!("unknown DEX tag {} at byte {}", tag, reader.pos - 1))?
+                };
+                dex_types.push(dex_type);
+            }
+            routes.push(RoutePath { hops, pools, dex_types, ..Default::default() });
+        }
+        route_cache.insert(token, Arc::new(routes));
+    }
+    if reader.pos != bytes.len() {
+        bail!("{} trailing bytes after the route cache", bytes.len() - reader.pos);
+    }
+    Ok(route_cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_cache() -> DashMap<u32, Arc<Vec<RoutePath>>> {
+        let pool = H160::from_low_u64_be;
+        let cache = DashMap::new();
+        cache.insert(
+            1,
+            Arc::new(vec![
+                RoutePath { hops: vec![0, 1, 0], pools: vec![pool(10), pool(11)], dex_types: vec![DEXType::PancakeV2, DEXType::PancakeV3], ..Default::default() },
+                RoutePath {
+                    hops: vec![0, 1, 2, 0],
+                    pools: vec![pool(10), pool(12), pool(13)],
+                    dex_types: vec![DEXType::Other("MDEX".to_string()), DEXType::StableSwap, DEXType::SushiV3],
+                    ..Default::default()
+                },
+            ]),
+        );
+        cache.insert(2, Arc::new(vec![RoutePath { hops: vec![0, 2, 0], pools: vec![pool(12), pool(14)], dex_types: vec![DEXType::StableSwap, DEXType::BiSwapV2], ..Default::default() }]));
+        cache
+    }
+
+    fn sorted(cache: &DashMap<u32, Arc<Vec<RoutePath>>>) -> BTreeMap<u32, Vec<RoutePath>> {
+        cache.iter().map(|entry| (*entry.key(), Vec::clone(entry.value()))).collect()
+    }
+
+    #[test]
+    fn test_binary_and_json_round_trip() {
+        let cache = sample_cache();
+        for format in [RouteCacheFormat::Binary, RouteCacheFormat::Json] {
+            let bytes = format.encode(&cache).unwrap();
+            let back = RouteCacheFormat::decode(&bytes).unwrap();
+            assert_eq!(sorted(&back), sorted(&cache), "{:?}", format);
+            // Legs are rebuilt on load, so the cache is ready for detection
+            assert!(back.get(&1).unwrap().iter().all(|route| route.legs.is_some()));
+        }
+        let binary = RouteCacheFormat::Binary.encode(&cache).unwrap();
+        assert!(binary.len() * 3 < RouteCacheFormat::Json.encode(&cache).unwrap().len());
+    }
+
+    #[test]
+    fn test_binary_rejects_bad_input() {
+        let bytes = encode_binary(&sample_cache()).unwrap();
+        assert!(decode_binary(&bytes[..bytes.len() - 1]).is_err());
+        let mut future = bytes.clone();
+        future[4..8].copy_from_slice(&(SCHEMA_VERSION + 1).to_le_bytes());
+        assert!(decode_binary(&future).unwrap_err().to_string().contains("schema version"));
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(decode_binary(&trailing).is_err());
+
+        // A huge route count in a tiny file fails on the missing bytes, without a huge allocation
+        let mut huge = Vec::from(&MAGIC[..]);
+        huge.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+        huge.extend_from_slice(&1u32.to_le_bytes());
+        huge.extend_from_slice(&1u32.to_le_bytes());
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode_binary(&huge).unwrap_err().to_string().contains("truncated"));
+    }
+
+    #[test]
+    fn test_binary_rejects_routes_too_long_to_encode() {
+        let cache = DashMap::new();
+        cache.insert(1, Arc::new(vec![RoutePath { hops: vec![0; 256], ..Default::default() }]));
+        assert!(encode_binary(&cache).unwrap_err().to_string().contains("255"));
+        // 255 still fits
+        cache.insert(1, Arc::new(vec![RoutePath { hops: vec![0; 255], ..Default::default() }]));
+        let back = decode_binary(&encode_binary(&cache).unwrap()).unwrap();
+        assert_eq!(back.get(&1).unwrap()[0].hops.len(), 255);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::arbitrage_finder::{simulate_all_routes_for_token_x, SimulatedRoute};
+use crate::cache::ReserveCache;
+use crate::config::Config;
+use crate::route_cache::RoutePath;
+use crate::token_index::TokenIndexMap;
+use crate::token_tax::TokenTaxMap;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use dashmap::DashMap;
+use ethers::types::{H160, U256};
+use serde::Deserialize;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Routes returned when the request has no `limit`
+const DEFAULT_ROUTE_LIMIT: usize = 10;
+/// Largest `limit` honoured; the whole simulation runs either way, this only caps the response
+const MAX_ROUTE_LIMIT: usize = 100;
+
+/// The bot's live caches; the API reads them as the detection path does, so answers track the chain
+#[derive(Clone)]
+pub struct RoutesApiContext {
+    pub reserve_cache: Arc<ReserveCache>,
+    pub token_index: Arc<TokenIndexMap>,
+    pub route_cache: Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
+    pub token_tax_map: Arc<TokenTaxMap>,
+    pub config: Arc<Config>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoutesQuery {
+    amount: Option<String>, // tokenX wei; defaults to 10^18
+    limit: Option<usize>,
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn api_error(status: StatusCode, message: impl Into<String>) -> ApiError {
+    (status, Json(json!({ "error": message.into() })))
+}
+
+fn route_json(route: &SimulatedRoute) -> serde_json::Value {
+    let hex = |addresses: &[H160]| addresses.iter().map(|a| format!("{:?}", a)).collect::<Vec<_>>();
+    json!({
+        "base_token": route.buy_tokens.first().map(|t| format!("{:?}", t)),
+        "profit": route.profit.to_string(), // wei of the base token
+        "profit_percentage": route.profit_percentage,
+        "buy_tokens": hex(&route.buy_tokens),
+        "buy_pools": hex(&route.buy_pools),
+        "sell_tokens": hex(&route.sell_tokens),
+        "sell_pools": hex(&route.sell_pools),
+        "amounts": route.merged_amounts.iter().map(|a| a.to_string()).collect::<Vec<_>>(),
+    })
+}
+
+/// Serve `GET /routes/{token}?amount=&limit=`: every cached route of the token simulated
+/// against the current reserves, best `profit_percentage` first. Returns the bound address.
+pub async fn start_routes_api(addr: SocketAddr, ctx: RoutesApiContext) -> anyhow::Result<SocketAddr> {
+    let app = Router::new().route("/routes/:token", get(routes_handler)).with_state(Arc::new(ctx));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let addr = listener.local_addr()?;
+    println!("🧭 [ROUTES API] Serving route quotes on http://{}/routes/{{token}}", addr);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("[ROUTES API] Server stopped: {}", e);
+        }
+    });
+    Ok(addr)
+}
+
+async fn routes_handler(
+    State(ctx): State<Arc<RoutesApiContext>>,
+    Path(token): Path<String>,
+    Query(query): Query<RoutesQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let token: H160 = token
+        .parse()
+        .map_err(|_| api_error(StatusCode::BAD_REQUEST, format!("invalid token address: {}", token)))?;
+    let amount = match &query.amount {
+        Some(amount) => U256::from_dec_str(amount)
+            .map_err(|_| api_error(StatusCode::BAD_REQUEST, format!("invalid amount: {}", amount)))?,
+        None => U256::exp10(18),
+    };
+    let Some(&token_x_index) = ctx.token_index.address_to_index.get(&token) else {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("token {:?} is not in the token index", token)));
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_ROUTE_LIMIT).min(MAX_ROUTE_LIMIT);
+
+    // The simulation is rayon-parallel; keep it off the async workers like the detection path does
+    let sim_ctx = ctx.clone();
+    let mut routes = tokio::task::spawn_blocking(move || {
+        simulate_all_routes_for_token_x(
+            token_x_index,
+            amount,
+            &sim_ctx.route_cache,
+            &sim_ctx.reserve_cache,
+            &sim_ctx.token_index,
+            &sim_ctx.token_tax_map,
+            &sim_ctx.config,
+        )
+    })
+    .await
+    .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("simulation failed: {}", e)))?;
+
+    // Profit is in each route's own base token, so routes are ranked by percentage
+    routes.sort_by(|a, b| b.profit_percentage.total_cmp(&a.profit_percentage));
+    Ok(Json(json!({
+        "token": format!("{:?}", token),
+        "amount": amount.to_string(),
+        "routes_simulated": routes.len(),
+        "routes": routes.iter().take(limit).map(route_json).collect::<Vec<_>>(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{pool_address, pool_v2, pool_v3, token, units, MarketBuilder};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn get_json(addr: SocketAddr, path: &str) -> (u16, serde_json::Value) {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap_or_else(|e| panic!("{}: {}", e, body)))
+    }
+
+    #[tokio::test]
+    async fn test_routes_endpoint_ranks_live_routes() {
+        // Only routes starting at an enabled base token are simulated
+        let base = Config::default().get_base_token_by_symbol("WBNB").unwrap().address;
+        let x = token(2);
+        let (v3, v2) = (pool_address(1), pool_address(2));
+        // X is ~10% cheaper on the V3 pool than on the V2 pair
+        let market = MarketBuilder::new()
+            .pool(v3, pool_v3(base, x, 1.1, units(1_000_000), 2500))
+            .pool(v2, pool_v2(base, x, units(100_000), units(100_000)))
+            .route(&[base, x, base], &[v3, v2])
+            .route(&[base, x, base], &[v2, v3])
+            .build();
+        let ctx = RoutesApiContext {
+            reserve_cache: market.reserve_cache.clone(),
+            token_index: market.token_index.clone(),
+            route_cache: market.route_cache.clone(),
+            token_tax_map: market.token_tax_map.clone(),
+            config: Arc::new(market.config.clone()),
+        };
+        let addr = start_routes_api("127.0.0.1:0".parse().unwrap(), ctx).await.unwrap();
+
+        let (status, body) = get_json(addr, &format!("/routes/{:?}?amount={}&limit=1", x, units(1))).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["routes_simulated"], 2);
+        let routes = body["routes"].as_array().unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0]["buy_pools"], json!([format!("{:?}", v3)]));
+        assert_eq!(routes[0]["sell_pools"], json!([format!("{:?}", v2)]));
+        let profit = U256::from_dec_str(routes[0]["profit"].as_str().unwrap()).unwrap();
+        assert!(profit > U256::exp10(16), "profit {}", profit);
+
+        // Shared caches: flooding the V2 pair with X makes it the cheap side, and the answer flips
+        market.reserve_cache.get_mut(&v2).unwrap().reserve1 = Some(units(1_000_000));
+        crate::cache::bump_reserve_version();
+        let (_, body) = get_json(addr, &format!("/routes/{:?}?amount={}", x, units(1))).await;
+        assert_eq!(body["routes"][0]["buy_pools"], json!([format!("{:?}", v2)]), "{}", body);
+        assert_eq!(body["routes"][1]["profit"], "0");
+
+        assert_eq!(get_json(addr, &format!("/routes/{:?}", token(99))).await.0, 404);
+        assert_eq!(get_json(addr, "/routes/not-an-address").await.0, 400);
+        assert_eq!(get_json(addr, &format!("/routes/{:?}?amount=lots", x)).await.0, 400);
+    }
+}
@@ -0,0 +1,107 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Version of the serialized forms of `PoolState`, `RoutePath`, `SimulatedRoute` and the
+/// price feed messages. Bump it when a change can't be absorbed by `#[serde(default)]`
+/// (renamed/removed field, changed meaning) and add the upgrade step to `migrate`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// On-disk / over-the-wire envelope: `{"schema_version": N, "data": ...}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub schema_version: u32,
+    pub data: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(data: T) -> Self {
+        Self { schema_version: SCHEMA_VERSION, data }
+    }
+}
+
+/// Serialize `data` wrapped in the current schema version
+pub fn to_versioned_json<T: Serialize>(data: &T) -> serde_json::Result<String> {
+    serde_json::to_string(&Versioned::new(data))
+}
+
+/// Deserialize a versioned blob, upgrading older versions first.
+/// Blobs written by a newer build are rejected instead of being half-read.
+pub fn from_versioned_json<T: DeserializeOwned>(json: &str) -> anyhow::Result<T> {
+    let envelope: Versioned<Value> = serde_json::from_str(json)?;
+    let data = migrate(envelope.schema_version, envelope.data)?;
+    Ok(serde_json::from_value(data)?)
+}
+
+/// Upgrade `data` from `version` to `SCHEMA_VERSION`, one step at a time
+pub fn migrate(version: u32, data: Value) -> anyhow::Result<Value> {
+    if version == 0 || version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "unsupported schema version {} (this build reads 1..={})",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    // Each future version adds an arm here, e.g. `1 => rename_field(&mut data, "old", "new")`.
+    // Purely additive fields need no step: structs deserialize with `#[serde(default)]`.
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage_finder::SimulatedRoute;
+    use crate::cache::{PoolState, PoolType};
+    use crate::route_cache::{DEXType, RoutePath};
+    use ethers::types::{H160, U256};
+
+    #[test]
+    fn test_v1_pool_state_missing_newer_fields() {
+        // Written before `tick_spacing`, `dex_name` and `inactive` existed
+        let blob = r#"{"schema_version":1,"data":{
+            "pool_type":"V3",
+            "token0":"0x0000000000000000000000000000000000000001",
+            "token1":"0x0000000000000000000000000000000000000002",
+            "sqrt_price_x96":"0x1000000000000000000000000",
+            "liquidity":"0x3e8",
+            "tick":-5,
+            "fee":2500,
+            "last_updated":1720000000
+        }}"#;
+        let state: PoolState = from_versioned_json(blob).unwrap();
+        assert_eq!(state.pool_type, PoolType::V3);
+        assert_eq!(state.sqrt_price_x96, Some(U256::one() << 96));
+        assert_eq!(state.liquidity, Some(U256::from(1_000u64)));
+        assert_eq!(state.tick, Some(-5));
+        assert_eq!(state.tick_spacing, None);
+        assert_eq!(state.dex_name, None);
+        assert!(!state.inactive);
+    }
+
+    #[test]
+    fn test_round_trip_and_version_checks() {
+        let path = RoutePath {
+            hops: vec![0, 1, 0],
+            pools: vec![H160::from_low_u64_be(10), H160::from_low_u64_be(11)],
+            dex_types: vec![DEXType::PancakeV2, DEXType::Other("MDEX".to_string())],
+            ..Default::default()
+        };
+        let json = to_versioned_json(&path).unwrap();
+        assert!(json.starts_with(r#"{"schema_version":1,"#));
+        assert_eq!(from_versioned_json::<RoutePath>(&json).unwrap(), path);
+
+        let route = SimulatedRoute {
+            profit: U256::from(7u64),
+            buy_path: path.clone(),
+            ..Default::default()
+        };
+        let back: SimulatedRoute = from_versioned_json(&to_versioned_json(&route).unwrap()).unwrap();
+        assert_eq!(back.profit, route.profit);
+        assert_eq!(back.buy_path, path);
+
+        let future = json.replacen(r#""schema_version":1"#, r#""schema_version":99"#, 1);
+        assert!(from_versioned_json::<RoutePath>(&future).is_err());
+        // Unversioned blobs are rejected rather than guessed at
+        assert!(from_versioned_json::<RoutePath>(r#"{"hops":[],"pools":[],"dex_types":[]}"#).is_err());
+    }
+}
@@ -0,0 +1,441 @@
+//! Forked state and decoding of simulated REVM call traces. Kept apart from `revm_sim.rs` so
+//! it builds and is tested without the custom EVM there.
+
+use crate::arbitrage_finder::find_arbitrage_opportunity;
+use crate::cache::{bump_reserve_version, ReserveCache};
+use crate::config::Config;
+use crate::opportunity::{ArbitrageOpportunity, DecodedSwap};
+use crate::price_oracle::PriceOracle;
+use crate::route_cache::RoutePath;
+use crate::token_index::TokenIndexMap;
+use crate::token_tax::TokenTaxMap;
+use crate::v3_swap::{decode_v3_swap, PANCAKE_V3_SWAP_TOPIC, UNISWAP_V3_SWAP_TOPIC};
+use alloy_eips::BlockId;
+use alloy_primitives::keccak256;
+use alloy_provider::{network::Ethereum, DynProvider};
+use dashmap::DashMap;
+use ethers::types::{H160, H256, U256};
+use once_cell::sync::Lazy;
+use revm::database::{AlloyDB, CacheDB, WrapDatabaseAsync};
+use revm::primitives::{Address, Bytes, Log, B256};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Node state as of one block, cached in memory as the simulation reads it
+pub type ForkDb = CacheDB<WrapDatabaseAsync<AlloyDB<Ethereum, DynProvider>>>;
+
+/// Fork the node's state at `block`: every account, storage and code lookup is answered as of
+/// that block, not the node's head. Needs a tokio runtime; blocks outside the node's state
+/// window need an archive node.
+pub fn fork_db_at(provider: DynProvider, block: BlockId) -> anyhow::Result<ForkDb> {
+    let alloy_db = WrapDatabaseAsync::new(AlloyDB::new(provider, block))
+        .ok_or_else(|| anyhow::anyhow!("forked simulation needs a tokio runtime"))?;
+    Ok(CacheDB::new(alloy_db))
+}
+
+/// Uniswap V2 / PancakeSwap `Swap(sender, amount0In, amount1In, amount0Out, amount1Out, to)`
+pub static SWAP_V2_TOPIC: Lazy<B256> =
+    Lazy::new(|| keccak256("Swap(address,uint256,uint256,uint256,uint256,address)"));
+/// V2 fork `Swap` with an extra leading address
+pub static SWAP_V2_FORK_TOPIC: Lazy<B256> =
+    Lazy::new(|| keccak256("Swap(address,address,uint256,uint256,uint256,uint256,address)"));
+pub static SYNC_V2_TOPIC: Lazy<B256> = Lazy::new(|| keccak256("Sync(uint112,uint112)"));
+pub static SWAP_V3_TOPIC: Lazy<B256> = Lazy::new(|| B256::from(UNISWAP_V3_SWAP_TOPIC.0));
+pub static PANCAKE_V3_TOPIC: Lazy<B256> = Lazy::new(|| B256::from(PANCAKE_V3_SWAP_TOPIC.0));
+
+pub static DEX_EVENT_TOPICS: Lazy<HashSet<B256>> = Lazy::new(|| {
+    [
+        *SWAP_V2_TOPIC,
+        *SWAP_V2_FORK_TOPIC,
+        *SYNC_V2_TOPIC,
+        *SWAP_V3_TOPIC,
+        *PANCAKE_V3_TOPIC,
+    ]
+    .into_iter()
+    .collect()
+});
+
+#[derive(Debug, Clone)]
+pub struct CallTraceNode {
+    pub call_type: String,
+    pub from: Address,
+    pub to: Address,
+    pub value: B256,
+    pub input: Bytes,
+    pub output: Option<Bytes>,
+    pub depth: usize,
+    pub children: Vec<CallTraceNode>,
+    pub logs: Vec<TraceLog>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TraceLog {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+}
+
+impl From<Log> for TraceLog {
+    fn from(log: Log) -> Self {
+        TraceLog { address: log.address, topics: log.data.topics().to_vec(), data: log.data.data.clone() }
+    }
+}
+
+impl TraceLog {
+    /// Topics and `0x…` data. A log without topics may be a legacy one whose `data` holds the
+    /// `Debug` string of the whole log; that is parsed back with `parse_logdata_string2`.
+    pub fn topics_and_data_hex(&self) -> (Vec<B256>, String) {
+        if self.topics.is_empty() {
+            let (topics, data_hex) = parse_logdata_string2(&self.data);
+            if !topics.is_empty() {
+                return (topics.iter().filter_map(|t| t.parse::<B256>().ok()).collect(), data_hex);
+            }
+        }
+        (self.topics.clone(), format!("0x{}", hex::encode(&self.data)))
+    }
+}
+
+/// Topics and `0x…` data parsed back out of a log's `Debug` string (`LogData { topics: [..], data: 0x.. }`)
+pub fn parse_logdata_string2(logdata_bytes: &[u8]) -> (Vec<String>, String) {
+    let logdata = String::from_utf8_lossy(logdata_bytes);
+
+    // Find the "LogData {" substring
+    let logdata_start = match logdata.find("LogData {") {
+        Some(idx) => idx,
+        None => return (vec![], String::new()),
+    };
+
+    // Find the closing '}' for LogData { ... }
+    let mut brace_count = 0;
+    let mut end_idx = None;
+    for (i, c) in logdata[logdata_start..].char_indices() {
+        if c == '{' {
+            brace_count += 1;
+        } else if c == '}' {
+            brace_count -= 1;
+            if brace_count == 0 {
+                end_idx = Some(logdata_start + i + 1);
+                break;
+            }
+        }
+    }
+    let logdata_sub = match end_idx {
+        Some(end) => &logdata[logdata_start..end],
+        None => &logdata[logdata_start..],
+    };
+
+    // Now parse topics and data as before, but only in logdata_sub
+    let topics_start = match logdata_sub.find("topics: [") {
+        Some(idx) => idx + 9,
+        None => return (vec![], String::new()),
+    };
+    let topics_end = match logdata_sub[topics_start..].find("]") {
+        Some(rel_idx) => topics_start + rel_idx,
+        None => return (vec![], String::new()),
+    };
+    let topics_str = &logdata_sub[topics_start..topics_end];
+    let topics: Vec<String> = topics_str
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| s.starts_with("0x"))
+        .collect();
+
+    let data_start = match logdata_sub.find("data: ") {
+        Some(idx) => idx + 6,
+        None => return (topics, String::new()),
+    };
+    let data_end = logdata_sub[data_start..]
+        .find('}')
+        .map(|i| data_start + i)
+        .unwrap_or(logdata_sub.len());
+    let data_hex = logdata_sub[data_start..data_end].trim().to_string();
+
+    (topics, data_hex)
+}
+
+/// Pool state change carried by one Sync/Swap log of a simulated trace
+#[derive(Debug, Clone, PartialEq)]
+enum PoolDelta {
+    V2Reserves { pool: H160, reserve0: U256, reserve1: U256 },
+    V3Price { pool: H160, sqrt_price_x96: U256, liquidity: U256, tick: i32 },
+}
+
+impl PoolDelta {
+    fn pool(&self) -> H160 {
+        match self {
+            PoolDelta::V2Reserves { pool, .. } | PoolDelta::V3Price { pool, .. } => *pool,
+        }
+    }
+}
+
+/// Decodes every V2 Sync / V3 Swap log of the trace, in emission order, without touching the cache
+fn collect_pool_deltas(trace: &CallTraceNode) -> Vec<PoolDelta> {
+    // Helper to recursively walk the call trace
+    fn walk_trace<'a>(node: &'a CallTraceNode, out: &mut Vec<&'a TraceLog>) {
+        for log in &node.logs {
+            out.push(log);
+        }
+        for child in &node.children {
+            walk_trace(child, out);
+        }
+    }
+    let mut logs = Vec::new();
+    walk_trace(trace, &mut logs);
+
+    let mut deltas = Vec::new();
+    for log in logs {
+        let (topics, data_hex) = log.topics_and_data_hex();
+        let Some(&topic0) = topics.first() else { continue };
+        let pool = H160::from_slice(log.address.0.as_slice());
+        // --- V2 Sync ---
+        if topic0 == *SYNC_V2_TOPIC && data_hex.len() >= 2 + 64 {
+            if let Ok(data_bytes) = hex::decode(data_hex.trim_start_matches("0x")) {
+                if data_bytes.len() >= 64 {
+                    deltas.push(PoolDelta::V2Reserves {
+                        pool,
+                        reserve0: U256::from_big_endian(&data_bytes[0..32]),
+                        reserve1: U256::from_big_endian(&data_bytes[32..64]),
+                    });
+                }
+            }
+        }
+        // --- V3 Swap ---
+        if topic0 == *SWAP_V3_TOPIC || topic0 == *PANCAKE_V3_TOPIC {
+            if let Ok(data_bytes) = hex::decode(data_hex.trim_start_matches("0x")) {
+                // Same ABI decoder as the price tracker (Uniswap V3: 160 bytes, Pancake V3: 224 bytes)
+                match decode_v3_swap(H256::from(topic0.0), &data_bytes) {
+                    Ok(swap) => deltas.push(PoolDelta::V3Price {
+                        pool,
+                        sqrt_price_x96: swap.sqrt_price_x96,
+                        liquidity: swap.liquidity,
+                        tick: swap.tick,
+                    }),
+                    Err(e) => eprintln!("[SIM] Skipping V3 Swap log: {}", e),
+                }
+            }
+        }
+    }
+    deltas
+}
+
+/// Writes all deltas to the cache in one synchronous pass (no await point, so a cancelled
+/// task either applied the whole trace or none of it). Later deltas for a pool win.
+/// Returns the touched pools, in first-touched order.
+fn apply_pool_deltas(reserve_cache: &ReserveCache, deltas: &[PoolDelta], now: u64) -> Vec<H160> {
+    let mut touched: Vec<H160> = Vec::new();
+    for delta in deltas {
+        let pool = delta.pool();
+        if let Some(mut state) = reserve_cache.get_mut(&pool) {
+            match delta {
+                PoolDelta::V2Reserves { reserve0, reserve1, .. } => {
+                    state.reserve0 = Some(*reserve0);
+                    state.reserve1 = Some(*reserve1);
+                }
+                PoolDelta::V3Price { sqrt_price_x96, liquidity, tick, .. } => {
+                    state.sqrt_price_x96 = Some(*sqrt_price_x96);
+                    state.liquidity = Some(*liquidity);
+                    state.tick = Some(*tick);
+                }
+            }
+            state.last_updated = now;
+            bump_reserve_version();
+        }
+        if !touched.contains(&pool) {
+            touched.push(pool);
+        }
+    }
+    touched
+}
+
+/// Walks the call trace, updates the reserve cache for any Sync/Swap events, and checks for arbitrage opportunities.
+/// All deltas are collected first and written back together before any opportunity check
+/// awaits, so the cache never holds a half-applied trace.
+pub async fn process_simulation_events_and_arbitrage(
+    trace: &CallTraceNode,
+    reserve_cache: &Arc<ReserveCache>,
+    token_index: &Arc<TokenIndexMap>,
+    precomputed_route_cache: &Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
+    opportunity_tx: &mpsc::Sender<ArbitrageOpportunity>,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Arc<Config>,
+    price_oracle: &Arc<PriceOracle>,
+) {
+    let deltas = collect_pool_deltas(trace);
+    let touched = apply_pool_deltas(reserve_cache, &deltas, chrono::Utc::now().timestamp() as u64);
+
+    for pool in touched {
+        // Arbitrage check (like price_tracker)
+        let decoded_swap = DecodedSwap {
+            tx_hash: H160::zero(), // Mempool sim, so no real tx hash
+            pool_address: pool,
+            token_x: H160::zero(),         // Not used for now
+            token_x_amount: U256::zero(), // Not used for now
+            block_number: 0,
+            timestamp: chrono::Utc::now().timestamp() as u64,
+        };
+        if let Some((opportunity, _latency)) = find_arbitrage_opportunity(
+            &decoded_swap,
+            reserve_cache,
+            token_index,
+            precomputed_route_cache,
+            token_tax_map,
+            config,
+            price_oracle,
+        )
+        .await
+        {
+            let _ = opportunity_tx.send(opportunity).await;
+        }
+    }
+}
+
+/// Walks the call trace tree and returns true if any log emits a SwapV2, SwapV3, or SyncV2 event
+pub fn trace_has_dex_event(node: &CallTraceNode) -> bool {
+    for log in &node.logs {
+        let (topics, _) = log.topics_and_data_hex();
+        if let Some(&topic0) = topics.first() {
+            if topic0 == *SWAP_V2_FORK_TOPIC || topic0 == *SWAP_V3_TOPIC || topic0 == *SYNC_V2_TOPIC {
+                return true;
+            }
+        }
+    }
+    for child in &node.children {
+        if trace_has_dex_event(child) {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_oracle::seed_prices;
+
+    #[test]
+    fn test_dex_topic_hex_forms() {
+        assert_eq!(
+            format!("0x{:x}", *SYNC_V2_TOPIC),
+            "0x1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1"
+        );
+        assert_eq!(
+            format!("0x{:x}", *SWAP_V2_TOPIC),
+            "0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822"
+        );
+        assert_eq!(
+            format!("0x{:x}", *SWAP_V3_TOPIC),
+            "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67"
+        );
+        assert_eq!(DEX_EVENT_TOPICS.len(), 5);
+        assert!(DEX_EVENT_TOPICS.contains(&*PANCAKE_V3_TOPIC));
+    }
+
+    fn sync_log(pool: H160, reserve0: u64, reserve1: u64) -> TraceLog {
+        let word = |n: u64| format!("{:064x}", n);
+        let data = format!(
+            "LogData {{ topics: [0x{:x}], data: 0x{}{} }}",
+            *SYNC_V2_TOPIC,
+            word(reserve0),
+            word(reserve1)
+        );
+        TraceLog { address: Address::from_slice(pool.as_bytes()), topics: vec![], data: Bytes::from(data.into_bytes()) }
+    }
+
+    #[test]
+    fn test_traced_swap_log_keeps_raw_topics() {
+        // Uniswap V3 Swap(sender, recipient, amount0, amount1, sqrtPriceX96, liquidity, tick)
+        let mut data = Vec::new();
+        for word in [U256::from(1_000u64), U256::MAX - U256::from(1_994u64), U256::from(2u64).pow(96.into()), U256::from(5_000_000u64)] {
+            let mut bytes = [0u8; 32];
+            word.to_big_endian(&mut bytes);
+            data.extend_from_slice(&bytes);
+        }
+        data.extend_from_slice(&[0xff; 32]); // tick -1
+        let sender = B256::left_padding_from(&[0x11; 20]);
+        let pool = Address::from_slice(&[0xaa; 20]);
+        let log = Log::new_unchecked(pool, vec![*SWAP_V3_TOPIC, sender, sender], Bytes::from(data.clone()));
+
+        let trace_log = TraceLog::from(log);
+        let (topics, data_hex) = trace_log.topics_and_data_hex();
+        assert_eq!(topics, vec![*SWAP_V3_TOPIC, sender, sender]);
+        assert_eq!(data_hex, format!("0x{}", hex::encode(&data)));
+        assert_eq!(
+            collect_pool_deltas(&call_node(vec![trace_log], vec![])),
+            vec![PoolDelta::V3Price {
+                pool: H160::from_slice(pool.as_slice()),
+                sqrt_price_x96: U256::from(2u64).pow(96.into()),
+                liquidity: U256::from(5_000_000u64),
+                tick: -1,
+            }]
+        );
+
+        // Legacy stringified logs still decode through the fallback parser
+        let legacy = sync_log(H160::from_low_u64_be(0xb), 7, 9);
+        assert_eq!(legacy.topics_and_data_hex().0, vec![*SYNC_V2_TOPIC]);
+    }
+
+    fn call_node(logs: Vec<TraceLog>, children: Vec<CallTraceNode>) -> CallTraceNode {
+        CallTraceNode {
+            call_type: "CALL".to_string(),
+            from: Address::ZERO,
+            to: Address::ZERO,
+            value: B256::ZERO,
+            input: Bytes::new(),
+            output: None,
+            depth: 0,
+            children,
+            logs,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trace_write_back_is_all_or_nothing() {
+        use crate::cache::{PoolState, PoolType};
+        let (pool_a, pool_b) = (H160::from_low_u64_be(0xa), H160::from_low_u64_be(0xb));
+        let reserve_cache = Arc::new(ReserveCache::new());
+        for pool in [pool_a, pool_b] {
+            reserve_cache.insert(
+                pool,
+                PoolState {
+                    pool_type: PoolType::V2,
+                    reserve0: Some(U256::from(1_000)),
+                    reserve1: Some(U256::from(1_000)),
+                    ..Default::default()
+                },
+            );
+        }
+        let reserves = |pool: H160| {
+            let state = reserve_cache.get(&pool).unwrap();
+            (state.reserve0.unwrap().as_u64(), state.reserve1.unwrap().as_u64())
+        };
+        // A's Sync, then a nested call syncing B and A again
+        let trace = call_node(
+            vec![sync_log(pool_a, 900, 1_100)],
+            vec![call_node(vec![sync_log(pool_b, 2_000, 500), sync_log(pool_a, 800, 1_250)], vec![])],
+        );
+
+        // Decoding the trace never writes to the cache
+        let deltas = collect_pool_deltas(&trace);
+        assert_eq!(deltas.len(), 3);
+        assert_eq!((reserves(pool_a), reserves(pool_b)), ((1_000, 1_000), (1_000, 1_000)));
+
+        // Cancel the task at its first await (the opportunity check after the write-back)
+        let (opportunity_tx, _opportunity_rx) = mpsc::channel(1);
+        let config = Arc::new(Config::default());
+        let task = process_simulation_events_and_arbitrage(
+            &trace,
+            &reserve_cache,
+            &Arc::new(TokenIndexMap { address_to_index: Default::default(), index_to_address: Default::default() }),
+            &Arc::new(DashMap::new()),
+            &opportunity_tx,
+            &Arc::new(TokenTaxMap::new()),
+            &config,
+            &Arc::new(PriceOracle::new(seed_prices())),
+        );
+        let _ = futures::FutureExt::now_or_never(task);
+        // Both pools carry the whole trace, A at its last Sync
+        assert_eq!(reserves(pool_a), (800, 1_250));
+        assert_eq!(reserves(pool_b), (2_000, 500));
+    }
+}
@@ -1,14 +1,31 @@
 use ethers::types::{H160, U256};
 use crate::route_cache::{RoutePath, DEXType};
-use crate::cache::{ReserveCache};
+use crate::cache::{PoolState, PoolType, ReserveCache};
 use crate::token_index::TokenIndexMap;
-use crate::v3_math::{Q96, mul_div, simulate_v3_swap, calculate_v3_buy_amount, sqrt_price_x96_to_price};
+use crate::v3_math::{Q96, mul_div, simulate_v3_swap, simulate_v3_swap_ticks, calculate_v3_buy_amount, sqrt_price_x96_to_price};
 use crate::split_route_path::split_route_around_token_x;
+use crate::stable_math::{get_dx, get_dy};
 use std::collections::HashMap;
 use dashmap::DashMap;
-use crate::token_tax::TokenTaxMap;
+use crate::token_tax::{apply_tax, gross_up_for_tax, TokenTaxMap};
 use crate::config::Config;
 use std::sync::Arc;
+use std::cell::RefCell;
+
+thread_local! {
+    /// Per-thread (buy, sell) amounts buffers reused across routes and events
+    static ROUTE_SIM_BUFFERS: RefCell<(Vec<U256>, Vec<U256>)> =
+        RefCell::new((Vec::with_capacity(8), Vec::with_capacity(8)));
+}
+
+/// Run `f` with this thread's reusable (buy, sell) amounts buffers.
+/// Use with the `_into` simulators to avoid allocating per simulated route.
+pub fn with_route_sim_buffers<R>(f: impl FnOnce(&mut Vec<U256>, &mut Vec<U256>) -> R) -> R {
+    ROUTE_SIM_BUFFERS.with(|buffers| {
+        let (buy, sell) = &mut *buffers.borrow_mut();
+        f(buy, sell)
+    })
+}
 
 /// Detailed hop information with amounts
 #[derive(Debug, Clone)]
@@ -22,6 +39,110 @@ pub struct HopDetail {
     pub reserve_out: U256,
     pub pool_type: crate::cache::PoolType,
     pub fee: u32,
+    pub zero_for_one: bool,          // true if token_in is the pool's token0
+    pub sqrt_price_x96_before: U256, // V3 only (zero for V2)
+    pub sqrt_price_x96_after: U256,  // V3 only (zero for V2)
+}
+
+impl HopDetail {
+    /// Output received per unit of input on this hop (raw token units, after fee and tax)
+    pub fn effective_price(&self) -> f64 {
+        if self.amount_in.is_zero() {
+            return 0.0;
+        }
+        u256_to_f64_lossy(&self.amount_out) / u256_to_f64_lossy(&self.amount_in)
+    }
+
+    /// Pre-swap spot price as output per unit of input
+    pub fn spot_price(&self) -> Option<f64> {
+        match &self.pool_type {
+            crate::cache::PoolType::V2 => {
+                if self.reserve_in.is_zero() {
+                    return None;
+                }
+                Some(u256_to_f64_lossy(&self.reserve_out) / u256_to_f64_lossy(&self.reserve_in))
+            }
+            crate::cache::PoolType::V3 => {
+                if self.sqrt_price_x96_before.is_zero() {
+                    return None;
+                }
+                let price = sqrt_price_x96_to_price(self.sqrt_price_x96_before); // token1 per token0
+                Some(if self.zero_for_one { price } else { 1.0 / price })
+            }
+            crate::cache::PoolType::StableN { balances, amp, .. } => {
+                stable_marginal_price(balances, *amp, self.reserve_in, self.reserve_out)
+            }
+        }
+    }
+
+    /// How far this hop moves the pool price, in bps of the pre-swap spot price.
+    /// V2 uses the post-swap reserves, V3 the sqrtPrice before/after the swap.
+    pub fn price_impact_bps(&self) -> Option<f64> {
+        let spot = self.spot_price()?;
+        let post = match &self.pool_type {
+            crate::cache::PoolType::V2 => {
+                let reserve_out_after = self.reserve_out.checked_sub(self.amount_out)?;
+                let reserve_in_after = self.reserve_in.checked_add(self.amount_in)?;
+                u256_to_f64_lossy(&reserve_out_after) / u256_to_f64_lossy(&reserve_in_after)
+            }
+            crate::cache::PoolType::V3 => {
+                if self.sqrt_price_x96_after.is_zero() {
+                    return None;
+                }
+                let price = sqrt_price_x96_to_price(self.sqrt_price_x96_after);
+                if self.zero_for_one { price } else { 1.0 / price }
+            }
+            crate::cache::PoolType::StableN { balances, amp, .. } => {
+                let (i, j) = stable_coin_positions(balances, self.reserve_in, self.reserve_out)?;
+                let mut after = balances.clone();
+                after[i] = after[i].checked_add(self.amount_in)?;
+                after[j] = after[j].checked_sub(self.amount_out)?;
+                stable_marginal_price(&after, *amp, after[i], after[j])?
+            }
+        };
+        Some(((spot - post) / spot * 10_000.0).abs())
+    }
+}
+
+/// Positions (i, j) of the hop's coins in a stable pool, recovered from the pre-swap balances
+/// stored as `reserve_in`/`reserve_out`. Coins with equal balances are interchangeable in the
+/// invariant, so picking either gives the same price.
+fn stable_coin_positions(balances: &[U256], reserve_in: U256, reserve_out: U256) -> Option<(usize, usize)> {
+    let i = balances.iter().position(|b| *b == reserve_in)?;
+    let j = balances.iter().enumerate().position(|(k, b)| k != i && *b == reserve_out)?;
+    Some((i, j))
+}
+
+/// Fee-less output per unit of input for a tiny swap from the coin holding `reserve_in`
+/// to the one holding `reserve_out`
+fn stable_marginal_price(balances: &[U256], amp: U256, reserve_in: U256, reserve_out: U256) -> Option<f64> {
+    let (i, j) = stable_coin_positions(balances, reserve_in, reserve_out)?;
+    let probe = (reserve_in / U256::from(1_000_000u32)).max(U256::one());
+    let out = get_dy(i, j, probe, balances, amp, 0)?;
+    Some(u256_to_f64_lossy(&out) / u256_to_f64_lossy(&probe))
+}
+
+/// Coin indices, balances, amp and fee for a hop through a StableN pool; `input_token` and
+/// `output_token` are the hop's token indices, which pick two of the pool's N coins
+fn stable_hop<'a>(
+    entry: &'a PoolState,
+    input_token: u32,
+    output_token: u32,
+    token_index_map: &TokenIndexMap,
+) -> Option<(usize, usize, &'a [U256], U256, u32)> {
+    let PoolType::StableN { balances, amp, .. } = &entry.pool_type else { return None };
+    let token_in = *token_index_map.index_to_address.get(&input_token)?;
+    let token_out = *token_index_map.index_to_address.get(&output_token)?;
+    let (i, j) = entry.pool_type.stable_indices(token_in, token_out)?;
+    Some((i, j, balances, *amp, entry.fee.unwrap_or(0)))
+}
+
+pub(crate) fn u256_to_f64_lossy(val: &U256) -> f64 {
+    if val.bits() <= 128 {
+        val.as_u128() as f64
+    } else {
+        val.to_string().parse::<f64>().unwrap_or(f64::MAX)
+    }
 }
 
 /// Complete path simulation result with all hop details
@@ -74,6 +195,18 @@ fn simulate_v3_swap_single(
     simulate_v3_swap(amount_in, sqrt_price_x96, liquidity, fee, zero_for_one)
 }
 
+/// Exact-input V3 hop on a cached pool: walks the pool's ticks when they were loaded (None once
+/// the swap would leave the loaded words), otherwise the single-range fast path
+fn v3_amount_out(entry: &PoolState, amount_in: U256, zero_for_one: bool) -> Option<U256> {
+    let (sqrt_price_x96, liquidity, fee) = (entry.sqrt_price_x96?, entry.liquidity?, entry.fee?);
+    match (&entry.ticks, entry.tick_range, entry.tick) {
+        (Some(ticks), Some(range), Some(tick)) => {
+            simulate_v3_swap_ticks(amount_in, sqrt_price_x96, liquidity, tick, ticks, range, fee, zero_for_one)
+        }
+        _ => simulate_v3_swap_single(amount_in, sqrt_price_x96, liquidity, fee, zero_for_one),
+    }
+}
+
 /// Simulate how many base tokens are needed to buy `amount_out` of tokenX
 /// Returns detailed information for each hop including amounts in/out
 pub fn simulate_buy_path(
@@ -141,9 +274,7 @@ pub fn simulate_buy_path(
                 if let Some(tax_info) = token_tax_map.get(&input_token_address) {
                     let buy_tax = tax_info.buy_tax / 100.0;
                     if buy_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - buy_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, buy_tax)?;
                     }
                 }
                 
@@ -156,9 +287,7 @@ pub fn simulate_buy_path(
                 if let Some(tax_info) = token_tax_map.get(&input_token_address) {
                     let sell_tax = tax_info.sell_tax / 100.0;
                     if sell_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - sell_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, sell_tax)?;
                     }
                 }
                 // --- Apply buy tax on output_token (pool withdrawal) ---
@@ -173,9 +302,7 @@ pub fn simulate_buy_path(
                         println!("[TAX WARNING] Buy tax >= 100% for token {:?}, setting amount_out to zero", output_token_address);
                         amount_out = U256::zero();
                     } else if buy_tax > 0.0 {
-                        let amount_out_f = amount_out.as_u128() as f64;
-                        let taxed = amount_out_f * (1.0 - buy_tax);
-                        amount_out = U256::from(taxed as u128);
+                        amount_out = apply_tax(amount_out, buy_tax);
                     }
                 }
                 
@@ -190,6 +317,9 @@ pub fn simulate_buy_path(
                     reserve_out,
                     pool_type: crate::cache::PoolType::V2,
                     fee,
+                    zero_for_one: input_token == token0_idx,
+                    sqrt_price_x96_before: U256::zero(),
+                    sqrt_price_x96_after: U256::zero(),
                 });
                 
                 println!("[V2 BUY] Pool {}: reserve_in={}, reserve_out={}, amount_out={}, calculated_input={}", 
@@ -200,7 +330,7 @@ pub fn simulate_buy_path(
             crate::cache::PoolType::V3 => {
                 let sqrt_price_x96 = entry.sqrt_price_x96?;
                 let liquidity = entry.liquidity?;
-                let fee = entry.fee.unwrap_or(3000);
+                let fee = entry.fee?;
                 let zero_for_one = input_token == token0_idx;
                 
                 if liquidity.is_zero() || sqrt_price_x96.is_zero() {
@@ -211,6 +341,8 @@ pub fn simulate_buy_path(
                 
                 // Use the new V3 buy calculation from v3_math
                 let mut amount_in = crate::v3_math::calculate_v3_buy_amount(amount_out, sqrt_price_x96, liquidity, fee, zero_for_one)?;
+                let sqrt_price_x96_after = crate::v3_math::get_next_sqrt_price_from_output(sqrt_price_x96, liquidity, amount_out, zero_for_one)
+                    .unwrap_or_default();
                 
                 // --- Apply buy tax if exists ---
                 let input_token_address = if input_token == token0_idx {
@@ -221,9 +353,7 @@ pub fn simulate_buy_path(
                 if let Some(tax_info) = token_tax_map.get(&input_token_address) {
                     let buy_tax = tax_info.buy_tax / 100.0;
                     if buy_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - buy_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, buy_tax)?;
                     }
                 }
                 
@@ -236,9 +366,7 @@ pub fn simulate_buy_path(
                 if let Some(tax_info) = token_tax_map.get(&input_token_address) {
                     let sell_tax = tax_info.sell_tax / 100.0;
                     if sell_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - sell_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, sell_tax)?;
                     }
                 }
                 // --- Apply buy tax on output_token (pool withdrawal) ---
@@ -253,9 +381,7 @@ pub fn simulate_buy_path(
                         println!("[TAX WARNING] Buy tax >= 100% for token {:?}, setting amount_out to zero", output_token_address);
                         amount_out = U256::zero();
                     } else if buy_tax > 0.0 {
-                        let amount_out_f = amount_out.as_u128() as f64;
-                        let taxed = amount_out_f * (1.0 - buy_tax);
-                        amount_out = U256::from(taxed as u128);
+                        amount_out = apply_tax(amount_out, buy_tax);
                     }
                 }
                 
@@ -270,6 +396,9 @@ pub fn simulate_buy_path(
                     reserve_out: U256::zero(),
                     pool_type: crate::cache::PoolType::V3,
                     fee,
+                    zero_for_one,
+                    sqrt_price_x96_before: sqrt_price_x96,
+                    sqrt_price_x96_after,
                 });
                 
                 println!("[V3 BUY] Pool {}: sqrtPrice={}, liquidity={}, amount_out={}, calculated_input={}, fee={}", 
@@ -277,6 +406,28 @@ pub fn simulate_buy_path(
                 
                 amount_out = amount_in;
             }
+            crate::cache::PoolType::StableN { .. } => {
+                let (i_coin, j_coin, balances, amp, fee) = stable_hop(entry, input_token, output_token, token_index_map)?;
+                // StableSwap pools assume plain (untaxed) coins, so no tax adjustment here
+                let amount_in = get_dx(i_coin, j_coin, amount_out, balances, amp, fee)?;
+                hops.push(HopDetail {
+                    pool_address: *pool,
+                    token_in: input_token,
+                    token_out: output_token,
+                    amount_in,
+                    amount_out,
+                    reserve_in: balances[i_coin],
+                    reserve_out: balances[j_coin],
+                    pool_type: entry.pool_type.clone(),
+                    fee,
+                    zero_for_one: i_coin < j_coin,
+                    sqrt_price_x96_before: U256::zero(),
+                    sqrt_price_x96_after: U256::zero(),
+                });
+                println!("[STABLE BUY] Pool {}: coins {}->{}, amount_out={}, calculated_input={}",
+                    pool, i_coin, j_coin, amount_out, amount_in);
+                amount_out = amount_in;
+            }
         }
     }
     
@@ -390,6 +541,9 @@ pub fn simulate_sell_path(
                     reserve_out,
                     pool_type: crate::cache::PoolType::V2,
                     fee,
+                    zero_for_one: input_token == token0_idx,
+                    sqrt_price_x96_before: U256::zero(),
+                    sqrt_price_x96_after: U256::zero(),
                 });
                 
                 println!("[V2 SELL] Pool {}: reserve_in={}, reserve_out={}, amount_in={}, calculated_output={}", 
@@ -400,7 +554,7 @@ pub fn simulate_sell_path(
             crate::cache::PoolType::V3 => {
                 let sqrt_price_x96 = entry.sqrt_price_x96?;
                 let liquidity = entry.liquidity?;
-                let fee = entry.fee.unwrap_or(3000);
+                let fee = entry.fee?;
                 let zero_for_one = input_token == token0_idx;
                 
                 if liquidity.is_zero() || sqrt_price_x96.is_zero() {
@@ -409,14 +563,11 @@ pub fn simulate_sell_path(
                     return None;
                 }
                 
-                // Use new V3 math function with overflow protection
-                let mut amount_out = crate::v3_math::simulate_v3_swap(
-                    amount_in,
-                    sqrt_price_x96,
-                    liquidity,
-                    fee,
-                    zero_for_one,
-                )?;
+                // Crosses initialized ticks when the pool has them loaded
+                let mut amount_out = v3_amount_out(entry, amount_in, zero_for_one)?;
+                let amount_in_less_fee = amount_in * U256::from(1_000_000 - fee) / U256::from(1_000_000u32);
+                let sqrt_price_x96_after = crate::v3_math::get_next_sqrt_price_from_input(sqrt_price_x96, liquidity, amount_in_less_fee, zero_for_one)
+                    .unwrap_or_default();
                 
                 // --- Apply sell tax if exists ---
                 let output_token_address = if output_token == token0_idx {
@@ -462,6 +613,9 @@ pub fn simulate_sell_path(
                     reserve_out: U256::zero(),
                     pool_type: crate::cache::PoolType::V3,
                     fee,
+                    zero_for_one,
+                    sqrt_price_x96_before: sqrt_price_x96,
+                    sqrt_price_x96_after,
                 });
                 
                 println!("[V3 SELL] Pool {}: sqrtPrice={}, liquidity={}, amount_in={}, calculated_output={}, fee={}", 
@@ -469,6 +623,28 @@ pub fn simulate_sell_path(
                 
                 amount_in = amount_out;
             }
+            crate::cache::PoolType::StableN { .. } => {
+                let (i_coin, j_coin, balances, amp, fee) = stable_hop(entry, input_token, output_token, token_index_map)?;
+                // StableSwap pools assume plain (untaxed) coins, so no tax adjustment here
+                let amount_out = get_dy(i_coin, j_coin, amount_in, balances, amp, fee)?;
+                hops.push(HopDetail {
+                    pool_address: *pool,
+                    token_in: input_token,
+                    token_out: output_token,
+                    amount_in,
+                    amount_out,
+                    reserve_in: balances[i_coin],
+                    reserve_out: balances[j_coin],
+                    pool_type: entry.pool_type.clone(),
+                    fee,
+                    zero_for_one: i_coin < j_coin,
+                    sqrt_price_x96_before: U256::zero(),
+                    sqrt_price_x96_after: U256::zero(),
+                });
+                println!("[STABLE SELL] Pool {}: coins {}->{}, amount_in={}, calculated_output={}",
+                    pool, i_coin, j_coin, amount_in, amount_out);
+                amount_in = amount_out;
+            }
         }
     }
     
@@ -615,9 +791,21 @@ pub fn print_path_simulation_details(result: &PathSimulationResult, path_name: &
             crate::cache::PoolType::V3 => {
                 println!("    V3 Pool (no reserves)");
             }
+            crate::cache::PoolType::StableN { .. } => {
+                println!("    Balance in:  {}", hop.reserve_in);
+                println!("    Balance out: {}", hop.reserve_out);
+            }
         }
         println!("    Pool type:  {:?}", hop.pool_type);
         println!("    Fee:        {} bps", hop.fee);
+        println!("    Effective price: {:.8}", hop.effective_price());
+        match (hop.spot_price(), hop.price_impact_bps()) {
+            (Some(spot), Some(impact)) => {
+                println!("    Spot price:      {:.8}", spot);
+                println!("    Price impact:    {:.2} bps", impact);
+            }
+            _ => println!("    Price impact:    n/a"),
+        }
         println!();
     }
     
@@ -702,9 +890,7 @@ pub fn simulate_buy_path_amounts_vec(
                         println!("[TAX WARNING] Buy tax >= 100% for token {:?}, setting amount_in to zero", input_token_address);
                         amount_in = U256::zero();
                     } else if buy_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - buy_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, buy_tax)?;
                     }
                 }
                 
@@ -717,9 +903,7 @@ pub fn simulate_buy_path_amounts_vec(
                 if let Some(tax_info) = token_tax_map.get(&input_token_address) {
                     let sell_tax = tax_info.sell_tax / 100.0;
                     if sell_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - sell_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, sell_tax)?;
                     }
                 }
                 // --- Apply buy tax on output_token (pool withdrawal) ---
@@ -734,9 +918,7 @@ pub fn simulate_buy_path_amounts_vec(
                         println!("[TAX WARNING] Buy tax >= 100% for token {:?}, setting amount_out to zero", output_token_address);
                         amount_out = U256::zero();
                     } else if buy_tax > 0.0 {
-                        let amount_out_f = amount_out.as_u128() as f64;
-                        let taxed = amount_out_f * (1.0 - buy_tax);
-                        amount_out = U256::from(taxed as u128);
+                        amount_out = apply_tax(amount_out, buy_tax);
                     }
                 }
                 
@@ -747,7 +929,7 @@ pub fn simulate_buy_path_amounts_vec(
             crate::cache::PoolType::V3 => {
                 let sqrt_price_x96 = entry.sqrt_price_x96?;
                 let liquidity = entry.liquidity?;
-                let fee = entry.fee.unwrap_or(3000);
+                let fee = entry.fee?;
                 let zero_for_one = input_token == token0_idx;
                 
                 // Use the proper V3 buy calculation function
@@ -765,9 +947,7 @@ pub fn simulate_buy_path_amounts_vec(
                         println!("[TAX WARNING] Buy tax >= 100% for token {:?}, setting amount_in to zero", input_token_address);
                         amount_in = U256::zero();
                     } else if buy_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - buy_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, buy_tax)?;
                     }
                 }
                 
@@ -780,9 +960,7 @@ pub fn simulate_buy_path_amounts_vec(
                 if let Some(tax_info) = token_tax_map.get(&input_token_address) {
                     let sell_tax = tax_info.sell_tax / 100.0;
                     if sell_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - sell_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, sell_tax)?;
                     }
                 }
                 // --- Apply buy tax on output_token (pool withdrawal) ---
@@ -797,9 +975,7 @@ pub fn simulate_buy_path_amounts_vec(
                         println!("[TAX WARNING] Buy tax >= 100% for token {:?}, setting amount_out to zero", output_token_address);
                         amount_out = U256::zero();
                     } else if buy_tax > 0.0 {
-                        let amount_out_f = amount_out.as_u128() as f64;
-                        let taxed = amount_out_f * (1.0 - buy_tax);
-                        amount_out = U256::from(taxed as u128);
+                        amount_out = apply_tax(amount_out, buy_tax);
                     }
                 }
                 
@@ -807,6 +983,13 @@ pub fn simulate_buy_path_amounts_vec(
                 amounts_out.push(amount_out);
                 amount_out = amount_in;
             }
+            crate::cache::PoolType::StableN { .. } => {
+                let (i_coin, j_coin, balances, amp, fee) = stable_hop(entry, input_token, output_token, token_index_map)?;
+                let amount_in = get_dx(i_coin, j_coin, amount_out, balances, amp, fee)?;
+                amounts_in.push(amount_in);
+                amounts_out.push(amount_out);
+                amount_out = amount_in;
+            }
         }
     }
     // Reverse to get hop order (base -> tokenX)
@@ -898,15 +1081,8 @@ pub fn simulate_sell_path_amounts_vec(
                 amount_in = amount_out;
             }
             crate::cache::PoolType::V3 => {
-                let sqrt_price_x96 = entry.sqrt_price_x96?;
-                let liquidity = entry.liquidity?;
-                let fee = entry.fee.unwrap_or(3000);
                 let zero_for_one = input_token == token0_idx;
-                let mut amount_out = if zero_for_one {
-                    simulate_v3_swap_single(amount_in, sqrt_price_x96, liquidity, fee, true)?
-                } else {
-                    simulate_v3_swap_single(amount_in, sqrt_price_x96, liquidity, fee, false)?
-                };
+                let mut amount_out = v3_amount_out(entry, amount_in, zero_for_one)?;
                 
                 // --- Apply sell tax if exists ---
                 let output_token_address = if output_token == token0_idx {
@@ -945,13 +1121,88 @@ pub fn simulate_sell_path_amounts_vec(
                 amounts_out.push(amount_out);
                 amount_in = amount_out;
             }
+            crate::cache::PoolType::StableN { .. } => {
+                let (i_coin, j_coin, balances, amp, fee) = stable_hop(entry, input_token, output_token, token_index_map)?;
+                let amount_out = get_dy(i_coin, j_coin, amount_in, balances, amp, fee)?;
+                amounts_in.push(amount_in);
+                amounts_out.push(amount_out);
+                amount_in = amount_out;
+            }
         }
     }
     Some((amounts_in, amounts_out))
 }
 
-/// Returns amounts array exactly like PancakeSwap Router getAmountsOut
-/// [amountIn, hop1_out, hop2_out, ..., final_out]
+/// Quote an exact-input swap along `path`, like a router's `getAmountsOut`:
+/// `[amount_in, hop1_out, ..., final_out]` in raw token units (no decimals scaling).
+/// Each hop's output is after that token's transfer taxes, so it is what the next hop receives.
+/// Returns None if any pool is missing from the cache, can't fill the swap, or is a V3 pool
+/// with an unknown fee (never assumed to be 3000).
+pub fn quote_exact_in(
+    path: &RoutePath,
+    amount_in: U256,
+    cache: &ReserveCache,
+    token_index_map: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+) -> Option<Vec<U256>> {
+    let mut amounts = Vec::with_capacity(path.hops.len());
+    simulate_sell_path_amounts_into(path, amount_in, cache, token_index_map, token_tax_map, config, &mut amounts)?;
+    Some(amounts)
+}
+
+/// Quote an exact-output swap along `path`, like a router's `getAmountsIn`:
+/// `[required_in, hop1_out, ..., amount_out]` in raw token units (no decimals scaling).
+/// Inputs are grossed up for transfer taxes so `amount_out` arrives after tax.
+/// Returns None if any pool is missing from the cache, lacks the liquidity, or is a V3 pool
+/// with an unknown fee.
+pub fn quote_exact_out(
+    path: &RoutePath,
+    amount_out: U256,
+    cache: &ReserveCache,
+    token_index_map: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+) -> Option<Vec<U256>> {
+    let mut amounts = Vec::with_capacity(path.hops.len());
+    simulate_buy_path_amounts_into(path, amount_out, cache, token_index_map, token_tax_map, config, &mut amounts)?;
+    Some(amounts)
+}
+
+/// Exact-output arbitrage quote: what a full cycle needs as input to return `target_out`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetOutputQuote {
+    pub amounts: Vec<U256>, // [required_in, hop1_out, ..., target_out]
+    pub required_in: U256,
+    pub profit: U256, // target_out - required_in, zero when the cycle loses money
+}
+
+/// Solve for the base input of a cycle (first hop token == last) that returns exactly
+/// `target_out`, e.g. a fixed flashloan repayment, using the `getAmountsIn` math on every hop
+pub fn simulate_for_target_output(
+    route: &RoutePath,
+    target_out: U256,
+    cache: &ReserveCache,
+    token_index_map: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+) -> Option<TargetOutputQuote> {
+    if route.hops.len() < 2 || route.hops.first() != route.hops.last() {
+        return None;
+    }
+    let amounts = quote_exact_out(route, target_out, cache, token_index_map, token_tax_map, config)?;
+    let required_in = *amounts.first()?;
+    if required_in.is_zero() {
+        return None;
+    }
+    Some(TargetOutputQuote {
+        profit: target_out.saturating_sub(required_in),
+        required_in,
+        amounts,
+    })
+}
+
+/// Sell leg of an arbitrage (tokenX → base); see `quote_exact_in`
 pub fn simulate_sell_path_amounts_array(
     route: &RoutePath,
     token_x_amount: U256,
@@ -960,7 +1211,21 @@ pub fn simulate_sell_path_amounts_array(
     token_tax_map: &Arc<TokenTaxMap>,
     config: &Config,
 ) -> Option<Vec<U256>> {
-    let mut amounts = Vec::with_capacity(route.hops.len());
+    quote_exact_in(route, token_x_amount, cache, token_index_map, token_tax_map, config)
+}
+
+/// Same as `quote_exact_in` but writes into a caller-provided buffer
+/// (cleared first) so hot paths can reuse allocations
+pub fn simulate_sell_path_amounts_into(
+    route: &RoutePath,
+    token_x_amount: U256,
+    cache: &ReserveCache,
+    token_index_map: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+    amounts: &mut Vec<U256>,
+) -> Option<()> {
+    amounts.clear();
     // Start with input amount (before any tax)
     let mut amount_in = token_x_amount;
     amounts.push(amount_in);
@@ -1016,15 +1281,12 @@ pub fn simulate_sell_path_amounts_array(
                 numerator.checked_div(denominator)?
             }
             crate::cache::PoolType::V3 => {
-                let sqrt_price_x96 = entry.sqrt_price_x96?;
-                let liquidity = entry.liquidity?;
-                let fee = entry.fee.unwrap_or(3000);
                 let zero_for_one = input_token == token0_idx;
-                if zero_for_one {
-                    simulate_v3_swap_single(amount_in, sqrt_price_x96, liquidity, fee, true)?
-                } else {
-                    simulate_v3_swap_single(amount_in, sqrt_price_x96, liquidity, fee, false)?
-                }
+                v3_amount_out(entry, amount_in, zero_for_one)?
+            }
+            crate::cache::PoolType::StableN { .. } => {
+                let (i_coin, j_coin, balances, amp, fee) = stable_hop(entry, input_token, output_token, token_index_map)?;
+                get_dy(i_coin, j_coin, amount_in, balances, amp, fee)?
             }
         };
 
@@ -1053,11 +1315,78 @@ pub fn simulate_sell_path_amounts_array(
         // The after-tax output becomes the input for the next hop
         amount_in = amount_out;
     }
-    Some(amounts)
+    Some(())
+}
+
+/// Copy the pools on `paths` out of `cache` into a standalone per-opportunity snapshot,
+/// so swaps can be applied to it without touching the shared cache
+pub fn snapshot_pools(paths: &[&RoutePath], cache: &ReserveCache) -> ReserveCache {
+    let snapshot = ReserveCache::new();
+    for pool in paths.iter().flat_map(|path| path.pools.iter()) {
+        if let Some(state) = cache.get(pool) {
+            snapshot.insert(*pool, state.value().clone());
+        }
+    }
+    snapshot
 }
 
-/// Returns amounts array exactly like PancakeSwap Router getAmountsIn
-/// [amountIn, hop1_out, hop2_out, ..., amountOut]
+/// Apply the reserve changes of swapping along `path` to `snapshot`, where `amounts` is the
+/// router-style array the quote functions return for it. V2 and stable pools get the gross input
+/// added and output removed; V3 pools move their sqrt price within the current liquidity.
+pub fn apply_path_to_snapshot(
+    path: &RoutePath,
+    amounts: &[U256],
+    snapshot: &ReserveCache,
+    token_index_map: &TokenIndexMap,
+) -> Option<()> {
+    for (i, pool) in path.pools.iter().enumerate() {
+        let (amount_in, amount_out) = (*amounts.get(i)?, *amounts.get(i + 1)?);
+        let input_token = token_index_map.index_to_address.get(path.hops.get(i)?)?;
+        let mut state = snapshot.get_mut(pool)?;
+        let zero_for_one = *input_token == state.token0;
+        match state.pool_type {
+            crate::cache::PoolType::V2 => {
+                let (reserve_in, reserve_out) = if zero_for_one {
+                    (state.reserve0?, state.reserve1?)
+                } else {
+                    (state.reserve1?, state.reserve0?)
+                };
+                let reserve_in = reserve_in.checked_add(amount_in)?;
+                let reserve_out = reserve_out.checked_sub(amount_out)?;
+                if zero_for_one {
+                    state.reserve0 = Some(reserve_in);
+                    state.reserve1 = Some(reserve_out);
+                } else {
+                    state.reserve0 = Some(reserve_out);
+                    state.reserve1 = Some(reserve_in);
+                }
+            }
+            crate::cache::PoolType::V3 => {
+                let fee = state.fee?;
+                let amount_in_less_fee = amount_in * U256::from(1_000_000 - fee) / U256::from(1_000_000u32);
+                state.sqrt_price_x96 = Some(crate::v3_math::get_next_sqrt_price_from_input(
+                    state.sqrt_price_x96?,
+                    state.liquidity?,
+                    amount_in_less_fee,
+                    zero_for_one,
+                )?);
+                // The single-range move doesn't track the tick, so the tick walk no longer applies
+                state.ticks = None;
+                state.tick_range = None;
+            }
+            crate::cache::PoolType::StableN { .. } => {
+                let output_token = token_index_map.index_to_address.get(path.hops.get(i + 1)?)?;
+                let (i_coin, j_coin) = state.pool_type.stable_indices(*input_token, *output_token)?;
+                let PoolType::StableN { balances, .. } = &mut state.pool_type else { return None };
+                balances[i_coin] = balances[i_coin].checked_add(amount_in)?;
+                balances[j_coin] = balances[j_coin].checked_sub(amount_out)?;
+            }
+        }
+    }
+    Some(())
+}
+
+/// Buy leg of an arbitrage (base → tokenX) for a target tokenX amount; see `quote_exact_out`
 pub fn simulate_buy_path_amounts_array(
     route: &RoutePath,
     token_x_amount: U256,
@@ -1066,11 +1395,24 @@ pub fn simulate_buy_path_amounts_array(
     token_tax_map: &Arc<TokenTaxMap>,
     config: &Config,
 ) -> Option<Vec<U256>> {
-    let mut amounts: Vec<U256> = Vec::with_capacity(route.hops.len());
+    quote_exact_out(route, token_x_amount, cache, token_index_map, token_tax_map, config)
+}
+
+/// Same as `quote_exact_out` but writes into a caller-provided buffer
+/// (cleared first) so hot paths can reuse allocations
+pub fn simulate_buy_path_amounts_into(
+    route: &RoutePath,
+    token_x_amount: U256,
+    cache: &ReserveCache,
+    token_index_map: &TokenIndexMap,
+    token_tax_map: &Arc<TokenTaxMap>,
+    config: &Config,
+    reverse_amounts: &mut Vec<U256>,
+) -> Option<()> {
     let mut amount_out = token_x_amount;
     
     // Calculate amounts in reverse order (from tokenX back to base)
-    let mut reverse_amounts = Vec::with_capacity(route.hops.len());
+    reverse_amounts.clear();
     reverse_amounts.push(token_x_amount); // Start with desired output
     
     for (i, pool) in route.pools.iter().enumerate().rev() {
@@ -1121,9 +1463,7 @@ pub fn simulate_buy_path_amounts_array(
                         println!("[TAX WARNING] Buy tax >= 100% for token {:?}, setting amount_in to zero", input_token_address);
                         amount_in = U256::zero();
                     } else if buy_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - buy_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, buy_tax)?;
                     }
                 }
                 
@@ -1136,9 +1476,7 @@ pub fn simulate_buy_path_amounts_array(
                 if let Some(tax_info) = token_tax_map.get(&input_token_address) {
                     let sell_tax = tax_info.sell_tax / 100.0;
                     if sell_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - sell_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, sell_tax)?;
                     }
                 }
                 
@@ -1147,7 +1485,7 @@ pub fn simulate_buy_path_amounts_array(
             crate::cache::PoolType::V3 => {
                 let sqrt_price_x96 = entry.sqrt_price_x96?;
                 let liquidity = entry.liquidity?;
-                let fee = entry.fee.unwrap_or(3000);
+                let fee = entry.fee?;
                 let zero_for_one = input_token == token0_idx;
                 
                 // Use the proper V3 buy calculation function
@@ -1165,9 +1503,7 @@ pub fn simulate_buy_path_amounts_array(
                         println!("[TAX WARNING] Buy tax >= 100% for token {:?}, setting amount_in to zero", input_token_address);
                         amount_in = U256::zero();
                     } else if buy_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - buy_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, buy_tax)?;
                     }
                 }
                 
@@ -1180,14 +1516,17 @@ pub fn simulate_buy_path_amounts_array(
                 if let Some(tax_info) = token_tax_map.get(&input_token_address) {
                     let sell_tax = tax_info.sell_tax / 100.0;
                     if sell_tax > 0.0 {
-                        let amount_in_f = amount_in.as_u128() as f64;
-                        let taxed = amount_in_f / (1.0 - sell_tax);
-                        amount_in = U256::from(taxed as u128);
+                        amount_in = gross_up_for_tax(amount_in, sell_tax)?;
                     }
                 }
                 
                 amount_in
             }
+            crate::cache::PoolType::StableN { .. } => {
+                // StableSwap pools assume plain (untaxed) coins, so no tax gross-up here
+                let (i_coin, j_coin, balances, amp, fee) = stable_hop(entry, input_token, output_token, token_index_map)?;
+                get_dx(i_coin, j_coin, amount_out, balances, amp, fee)?
+            }
         };
         
         reverse_amounts.push(amount_in);
@@ -1196,7 +1535,7 @@ pub fn simulate_buy_path_amounts_array(
     
     // Reverse to get correct order (base -> tokenX)
     reverse_amounts.reverse();
-    Some(reverse_amounts)
+    Some(())
 }
 
 /// Test function to verify dynamic V2 fee implementation
@@ -1266,7 +1605,7 @@ pub fn simulate_all_filtered_routes(
     pool_address: H160,
     token_x_amount: U256,
     all_tokens: &HashMap<H160, u32>,
-    precomputed_route_cache: &DashMap<u32, Vec<RoutePath>>,
+    precomputed_route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>,
     reserve_cache: &ReserveCache,
     token_index_map: &TokenIndexMap,
     token_tax_map: &Arc<TokenTaxMap>,
@@ -1445,3 +1784,305 @@ pub fn print_comprehensive_results(results: &ComprehensiveSimulationResults) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v3_quote_uses_pool_fee() {
+        use crate::testkit::{pool_address, pool_v3, token, units, MarketBuilder};
+        let (a, b) = (token(1), token(2));
+        let (low_fee, no_fee) = (pool_address(1), pool_address(2));
+        let mut unknown = pool_v3(a, b, 1.0, units(1_000_000), 500);
+        unknown.fee = None;
+        let market = MarketBuilder::new()
+            .pool(low_fee, pool_v3(a, b, 1.0, units(1_000_000), 500))
+            .pool(no_fee, unknown)
+            .build();
+        let quote = |pool: H160| {
+            quote_exact_in(
+                &market.route(&[a, b], &[pool]),
+                units(1),
+                &market.reserve_cache,
+                &market.token_index,
+                &market.token_tax_map,
+                &market.config,
+            )
+        };
+
+        let state = market.reserve_cache.get(&low_fee).unwrap().clone();
+        let (sqrt_price, liquidity) = (state.sqrt_price_x96.unwrap(), state.liquidity.unwrap());
+        let at_500 = simulate_v3_swap(units(1), sqrt_price, liquidity, 500, true).unwrap();
+        let at_3000 = simulate_v3_swap(units(1), sqrt_price, liquidity, 3000, true).unwrap();
+        let out = quote(low_fee).unwrap();
+        assert_eq!(out[1], at_500);
+        assert!(out[1] > at_3000);
+        // No fee known: the pool is skipped, not quoted at a guessed tier
+        assert_eq!(quote(no_fee), None);
+    }
+
+    #[test]
+    fn test_taxed_buy_matches_router_get_amounts_in() {
+        use crate::testkit::{pool_address, pool_v2, token, units, MarketBuilder};
+        use crate::token_tax::TokenTaxInfo;
+        let (taxed, x) = (token(1), token(2));
+        let pool = pool_address(1);
+        let (reserve_in, reserve_out) = (units(1_000) + U256::from(7u64), units(3_000) - U256::from(13u64));
+        let market = MarketBuilder::new().pool(pool, pool_v2(taxed, x, reserve_in, reserve_out)).build();
+        let route = market.route(&[taxed, x], &[pool]);
+        let quote = |amount_out| {
+            simulate_buy_path_amounts_array(&route, amount_out, &market.reserve_cache, &market.token_index, &market.token_tax_map, &market.config).unwrap()[0]
+        };
+        // PancakeRouter.getAmountsIn at the 0.25% fee
+        let router = |amount_out: U256| reserve_in * amount_out * U256::from(10_000u64) / ((reserve_out - amount_out) * U256::from(9_975u64)) + U256::one();
+
+        let amounts_out = [U256::from(1u64), U256::from(999_999_999u64), units(1) + U256::from(1u64), units(250) / U256::from(3u64)];
+        for amount_out in amounts_out {
+            assert_eq!(quote(amount_out), router(amount_out), "untaxed {}", amount_out);
+        }
+
+        // 3.3% on transfer into the pool: the token keeps floor(amount * tax), the pool gets the rest
+        market.token_tax_map.insert(taxed, TokenTaxInfo { buy_tax: 0.0, sell_tax: 3.3, transfer_tax: 0.0, simulation_success: true });
+        let arrives = |sent: U256| sent - sent * U256::from(33u64) / U256::from(1_000u64);
+        for amount_out in amounts_out {
+            let (needed, sent) = (router(amount_out), quote(amount_out));
+            assert!(arrives(sent) >= needed, "{} sent delivers {} < {}", sent, arrives(sent), needed);
+            // ...and within 2 wei of the least that does
+            assert!(arrives(sent.saturating_sub(U256::from(3u64))) < needed, "over-estimated {} for {}", sent, needed);
+        }
+
+        // A 100% tax can't be bought through
+        market.token_tax_map.insert(taxed, TokenTaxInfo { buy_tax: 0.0, sell_tax: 100.0, transfer_tax: 0.0, simulation_success: true });
+        assert!(simulate_buy_path_amounts_array(&route, units(1), &market.reserve_cache, &market.token_index, &market.token_tax_map, &market.config).is_none());
+    }
+
+    fn hop(pool_type: PoolType) -> HopDetail {
+        HopDetail {
+            pool_address: H160::zero(),
+            token_in: 0,
+            token_out: 1,
+            amount_in: U256::zero(),
+            amount_out: U256::zero(),
+            reserve_in: U256::zero(),
+            reserve_out: U256::zero(),
+            pool_type,
+            fee: 0,
+            zero_for_one: true,
+            sqrt_price_x96_before: U256::zero(),
+            sqrt_price_x96_after: U256::zero(),
+        }
+    }
+
+    #[test]
+    fn test_quote_exact_in_out_round_trip() {
+        use crate::cache::PoolState;
+        let a = H160::from_low_u64_be(1);
+        let b = H160::from_low_u64_be(2);
+        let c = H160::from_low_u64_be(3);
+        let pool_ab = H160::from_low_u64_be(100);
+        let pool_bc = H160::from_low_u64_be(101);
+        let v2 = |token0, token1, r0: u64, r1: u64| PoolState {
+            pool_type: PoolType::V2,
+            token0,
+            token1,
+            reserve0: Some(U256::from(r0) * U256::exp10(18)),
+            reserve1: Some(U256::from(r1) * U256::exp10(18)),
+            dex_name: Some("PancakeSwap V2".to_string()),
+            ..Default::default()
+        };
+        let cache = ReserveCache::new();
+        cache.insert(pool_ab, v2(a, b, 1_000, 2_000));
+        cache.insert(pool_bc, v2(b, c, 5_000, 5_000));
+        let index = TokenIndexMap::build_from_reserve_cache(&cache);
+        let idx = |t: &H160| index.address_to_index[t];
+        let path = RoutePath {
+            hops: vec![idx(&a), idx(&b), idx(&c)],
+            pools: vec![pool_ab, pool_bc],
+            dex_types: vec![DEXType::PancakeV2, DEXType::PancakeV2],
+            ..Default::default()
+        };
+        let taxes = Arc::new(TokenTaxMap::new());
+        let config = Config::default();
+
+        let amount_in = U256::exp10(18);
+        let out = quote_exact_in(&path, amount_in, &cache, &index, &taxes, &config).unwrap();
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0], amount_in);
+        assert!(out[1] > out[2] && out[2] > U256::zero());
+
+        // Asking for exactly that output needs the same input, up to per-hop rounding
+        let back = quote_exact_out(&path, out[2], &cache, &index, &taxes, &config).unwrap();
+        assert_eq!(back[2], out[2]);
+        let diff = if back[0] > amount_in { back[0] - amount_in } else { amount_in - back[0] };
+        assert!(diff < U256::from(10u64), "round trip off by {}", diff);
+
+        // The arbitrage wrappers are the same quotes
+        assert_eq!(simulate_sell_path_amounts_array(&path, amount_in, &cache, &index, &taxes, &config), Some(out));
+    }
+
+    #[test]
+    fn test_target_output_round_trips_with_forward_quote() {
+        use crate::testkit::{pool_address, pool_v2, token, units, MarketBuilder};
+        let (base, x, y) = (token(1), token(2), token(3));
+        let pools = [pool_address(1), pool_address(2), pool_address(3)];
+        // X is cheap against base, Y is dear: the cycle base -> X -> Y -> base pays
+        let market = MarketBuilder::new()
+            .pool(pools[0], pool_v2(base, x, units(1_000), units(2_000)))
+            .pool(pools[1], pool_v2(x, y, units(1_000), units(1_000)))
+            .pool(pools[2], pool_v2(y, base, units(1_000), units(800)))
+            .build();
+        let quote_out = |route: &RoutePath, target| {
+            simulate_for_target_output(route, target, &market.reserve_cache, &market.token_index, &market.token_tax_map, &market.config)
+        };
+        let quote_in = |route: &RoutePath, amount| {
+            quote_exact_in(route, amount, &market.reserve_cache, &market.token_index, &market.token_tax_map, &market.config).unwrap()
+        };
+
+        let cycle = market.route(&[base, x, y, base], &pools);
+        let target = units(5);
+        let quote = quote_out(&cycle, target).unwrap();
+        assert_eq!(quote.amounts.len(), 4);
+        assert_eq!(*quote.amounts.last().unwrap(), target);
+        assert_eq!(quote.profit, target - quote.required_in);
+        assert!(quote.profit > U256::zero());
+        // Feeding the solved input forward delivers the target, up to per-hop rounding
+        let out = *quote_in(&cycle, quote.required_in).last().unwrap();
+        assert!(out >= target && out - target < U256::from(100u64), "forward {} vs target {}", out, target);
+        let short = *quote_in(&cycle, quote.required_in - U256::from(100u64)).last().unwrap();
+        assert!(short < target);
+
+        // The reverse cycle loses money: input exceeds the target, profit floors at zero
+        let reverse = market.route(&[base, y, x, base], &[pools[2], pools[1], pools[0]]);
+        let quote = quote_out(&reverse, target).unwrap();
+        assert!(quote.required_in > target);
+        assert!(quote.profit.is_zero());
+
+        // Not a cycle
+        let one_way = market.route(&[base, x], &pools[..1]);
+        assert!(quote_out(&one_way, target).is_none());
+    }
+
+    #[test]
+    fn test_stable_hop_uses_coin_indices() {
+        let coins = [H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3)];
+        let balances = vec![
+            U256::from(1_000_000u64) * U256::exp10(18),
+            U256::from(1_200_000u64) * U256::exp10(18),
+            U256::from(800_000u64) * U256::exp10(18),
+        ];
+        let amp = U256::from(200u32);
+        let pool = H160::from_low_u64_be(100);
+        let cache = ReserveCache::new();
+        cache.insert(pool, PoolState {
+            pool_type: PoolType::StableN { tokens: coins.to_vec(), balances: balances.clone(), amp },
+            token0: coins[0],
+            token1: coins[1],
+            fee: Some(4_000_000),
+            ..Default::default()
+        });
+        let index = TokenIndexMap::build_from_reserve_cache(&cache);
+        // The third coin is indexed even though it isn't token0/token1
+        let (a, c) = (index.address_to_index[&coins[0]], index.address_to_index[&coins[2]]);
+        let path = RoutePath { hops: vec![a, c], pools: vec![pool], dex_types: vec![DEXType::StableSwap], ..Default::default() };
+        let taxes = Arc::new(TokenTaxMap::new());
+        let config = Config::default();
+        let amount_in = U256::from(1_000u64) * U256::exp10(18);
+
+        let out = quote_exact_in(&path, amount_in, &cache, &index, &taxes, &config).unwrap();
+        assert_eq!(out[1], get_dy(0, 2, amount_in, &balances, amp, 4_000_000).unwrap());
+        let back = quote_exact_out(&path, out[1], &cache, &index, &taxes, &config).unwrap();
+        let diff = if back[0] > amount_in { back[0] - amount_in } else { amount_in - back[0] };
+        assert!(diff < U256::from(10u64), "round trip off by {}", diff);
+
+        let detail = simulate_sell_path(&path, amount_in, &cache, &index, &taxes, &config).unwrap();
+        let hop = &detail.hops[0];
+        assert_eq!(detail.total_amount_out, out[1]);
+        assert_eq!((hop.reserve_in, hop.reserve_out), (balances[0], balances[2]));
+        // Coin 2 is the scarce one, so each coin 0 buys slightly less than one of it
+        let spot = hop.spot_price().unwrap();
+        assert!(spot < 1.0 && spot > 0.99, "spot {}", spot);
+        assert!(hop.price_impact_bps().unwrap() > 0.0);
+
+        let snapshot = snapshot_pools(&[&path], &cache);
+        apply_path_to_snapshot(&path, &out, &snapshot, &index).unwrap();
+        let PoolType::StableN { balances: after, .. } = snapshot.get(&pool).unwrap().pool_type.clone() else { panic!() };
+        assert_eq!(after, vec![balances[0] + amount_in, balances[1], balances[2] - out[1]]);
+    }
+
+    #[test]
+    fn test_sell_leg_sees_post_buy_state() {
+        use crate::cache::PoolState;
+        let base = H160::from_low_u64_be(1);
+        let token_x = H160::from_low_u64_be(2);
+        let pool = H160::from_low_u64_be(100);
+        let cache = ReserveCache::new();
+        cache.insert(pool, PoolState {
+            pool_type: PoolType::V2,
+            token0: base,
+            token1: token_x,
+            reserve0: Some(U256::from(1_000u64) * U256::exp10(18)),
+            reserve1: Some(U256::from(1_000u64) * U256::exp10(18)),
+            dex_name: Some("PancakeSwap V2".to_string()),
+            ..Default::default()
+        });
+        let index = TokenIndexMap::build_from_reserve_cache(&cache);
+        let (b, x) = (index.address_to_index[&base], index.address_to_index[&token_x]);
+        let buy = RoutePath { hops: vec![b, x], pools: vec![pool], dex_types: vec![DEXType::PancakeV2], ..Default::default() };
+        let sell = RoutePath { hops: vec![x, b], pools: vec![pool], dex_types: vec![DEXType::PancakeV2], ..Default::default() };
+        let taxes = Arc::new(TokenTaxMap::new());
+        let config = Config::default();
+        let token_x_amount = U256::from(100u64) * U256::exp10(18);
+
+        let buy_amounts = simulate_buy_path_amounts_array(&buy, token_x_amount, &cache, &index, &taxes, &config).unwrap();
+        let independent = simulate_sell_path_amounts_array(&sell, token_x_amount, &cache, &index, &taxes, &config).unwrap();
+
+        let snapshot = snapshot_pools(&[&buy, &sell], &cache);
+        apply_path_to_snapshot(&buy, &buy_amounts, &snapshot, &index).unwrap();
+        let state = snapshot.get(&pool).unwrap();
+        assert_eq!(state.reserve0, Some(cache.get(&pool).unwrap().reserve0.unwrap() + buy_amounts[0]));
+        assert_eq!(state.reserve1, Some(cache.get(&pool).unwrap().reserve1.unwrap() - token_x_amount));
+        drop(state);
+        let sequential = simulate_sell_path_amounts_array(&sell, token_x_amount, &snapshot, &index, &taxes, &config).unwrap();
+
+        // Selling into the pool the buy just drained of X fetches more base than the static
+        // reserves suggest, but the round trip still loses to fees
+        assert!(sequential[1] > independent[1]);
+        assert!(sequential[1] < buy_amounts[0]);
+        // The shared cache is untouched
+        assert_eq!(cache.get(&pool).unwrap().reserve1, Some(U256::from(1_000u64) * U256::exp10(18)));
+    }
+
+    #[test]
+    fn test_v2_hop_price_impact() {
+        let mut h = hop(PoolType::V2);
+        h.reserve_in = U256::from(1_000_000u64);
+        h.reserve_out = U256::from(2_000_000u64);
+        h.amount_in = U256::from(10_000u64);
+        h.amount_out = U256::from(19_743u64);
+        assert!((h.spot_price().unwrap() - 2.0).abs() < 1e-12);
+        assert!((h.effective_price() - 1.9743).abs() < 1e-12);
+        // Post-swap price (1_980_257 / 1_010_000) is ~1.96% below spot
+        let impact = h.price_impact_bps().unwrap();
+        assert!((impact - 196.75).abs() < 0.1, "impact {}", impact);
+    }
+
+    #[test]
+    fn test_v3_hop_price_impact_direction() {
+        let mut h = hop(PoolType::V3);
+        h.sqrt_price_x96_before = U256::from(Q96);
+        // sqrtPrice down 1% → price (token1 per token0) down ~1.99%
+        h.sqrt_price_x96_after = U256::from(Q96 / 100 * 99);
+        let impact = h.price_impact_bps().unwrap();
+        assert!((impact - 199.0).abs() < 0.5, "impact {}", impact);
+
+        // Selling token1 moves price up; impact is still reported as a positive bps move
+        h.zero_for_one = false;
+        h.sqrt_price_x96_after = U256::from(Q96 / 100 * 101);
+        let impact = h.price_impact_bps().unwrap();
+        assert!((impact - 197.0).abs() < 0.5, "impact {}", impact);
+
+        h.sqrt_price_x96_before = U256::zero();
+        assert!(h.price_impact_bps().is_none());
+    }
+}
@@ -1,9 +1,75 @@
 use crate::route_cache::RoutePath;
+use dashmap::DashMap;
+use std::borrow::Cow;
+use std::sync::Arc;
 
+/// Buy/sell legs of a route around one tokenX, as returned by `split_route_legs`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteLegs {
+    pub token_x: u32,
+    pub buy: RoutePath,
+    pub sell: RoutePath,
+    pub shares_pool: bool, // a pool is in both legs; `split_route_around_token_x` rejects these
+}
+
+impl RouteLegs {
+    fn compute(route: &RoutePath, token_x_idx: u32) -> Option<Self> {
+        let (buy, sell) = split_route_legs(route, token_x_idx)?;
+        Some(Self {
+            token_x: token_x_idx,
+            shares_pool: legs_share_pool(&buy, &sell),
+            buy,
+            sell,
+        })
+    }
+}
+
+/// Store each cached route's legs around the token it is cached under, so detection
+/// reads them instead of re-splitting on every event. Run again for any token whose
+/// entry is rebuilt or extended; routes without legs are split on the fly. An entry still
+/// held by a running detection is copied, so that detection keeps the routes it started with.
+pub fn precompute_route_legs(route_cache: &DashMap<u32, Arc<Vec<RoutePath>>>) {
+    for mut entry in route_cache.iter_mut() {
+        let token_x_idx = *entry.key();
+        for route in Arc::make_mut(entry.value_mut()).iter_mut() {
+            route.legs = RouteLegs::compute(route, token_x_idx).map(Arc::new);
+        }
+    }
+}
+
+/// Legs of `route` around `token_x_idx`: the precomputed ones when they were built for
+/// that token, otherwise split now
+#[inline]
+pub fn route_legs(route: &RoutePath, token_x_idx: u32) -> Option<Cow<'_, RouteLegs>> {
+    match &route.legs {
+        Some(legs) if legs.token_x == token_x_idx => Some(Cow::Borrowed(legs.as_ref())),
+        _ => RouteLegs::compute(route, token_x_idx).map(Cow::Owned),
+    }
+}
+
+/// Split a route at tokenX into (buy, sell) legs, rejecting routes that use a pool in both
 #[inline]
 pub fn split_route_around_token_x(
     route: &RoutePath,
     token_x_idx: u32,
+) -> Option<(RoutePath, RoutePath)> {
+    let (buy_path, sell_path) = split_route_legs(route, token_x_idx)?;
+
+    // A pool in both legs would be simulated twice against the same reserves (phantom profit)
+    if legs_share_pool(&buy_path, &sell_path) {
+        return None;
+    }
+
+    Some((buy_path, sell_path))
+}
+
+/// Split without the shared-pool check, for callers that simulate the sell leg
+/// against post-buy state. The sell leg of a cross-base route ends in a different
+/// base token than the buy leg starts from.
+#[inline]
+pub fn split_route_legs(
+    route: &RoutePath,
+    token_x_idx: u32,
 ) -> Option<(RoutePath, RoutePath)> {
     let token_pos = route.hops.iter().position(|&t| t == token_x_idx)?;
 
@@ -19,17 +85,25 @@ pub fn split_route_around_token_x(
         hops: buy_hops,
         pools: route.pools[0..buy_pool_len].to_vec(),
         dex_types: route.dex_types[0..buy_pool_len].to_vec(),
+        ..Default::default()
     };
 
     let sell_path = RoutePath {
         hops: sell_hops,
         pools: route.pools[route.pools.len() - sell_pool_len..].to_vec(),
         dex_types: route.dex_types[route.dex_types.len() - sell_pool_len..].to_vec(),
+        ..Default::default()
     };
 
     Some((buy_path, sell_path))
 }
 
+/// True if any pool is traversed by both the buy and the sell leg
+#[inline]
+pub fn legs_share_pool(buy_path: &RoutePath, sell_path: &RoutePath) -> bool {
+    buy_path.pools.iter().any(|pool| sell_path.pools.contains(pool))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -40,12 +114,84 @@ mod tests {
     fn test_split() {
         let route = RoutePath {
             hops: vec![1, 2, 3, 4],
-            pools: vec![H160::zero(), H160::zero(), H160::zero()],
+            pools: vec![H160::from_low_u64_be(1), H160::from_low_u64_be(2), H160::from_low_u64_be(3)],
             dex_types: vec![DEXType::PancakeV2, DEXType::BiSwapV2, DEXType::ApeSwapV2],
+            ..Default::default()
         };
 
         let (buy, sell) = split_route_around_token_x(&route, 3).unwrap();
         assert_eq!(buy.hops, vec![1, 2, 3]);
         assert_eq!(sell.hops, vec![3, 4]);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_split_rejects_pool_in_both_legs() {
+        let pool = H160::from_low_u64_be(1);
+        // base -> X -> base through the same pair both ways
+        let route = RoutePath {
+            hops: vec![1, 2, 1],
+            pools: vec![pool, pool],
+            dex_types: vec![DEXType::PancakeV2, DEXType::PancakeV2],
+            ..Default::default()
+        };
+        assert!(split_route_around_token_x(&route, 2).is_none());
+
+        // Pool reused by a later hop on the other side of tokenX
+        let route = RoutePath {
+            hops: vec![1, 2, 3, 1],
+            pools: vec![pool, H160::from_low_u64_be(2), pool],
+            dex_types: vec![DEXType::PancakeV2; 3],
+            ..Default::default()
+        };
+        assert!(split_route_around_token_x(&route, 2).is_none());
+        assert!(split_route_around_token_x(&route, 3).is_none());
+    }
+
+    #[test]
+    fn test_precomputed_legs_match_on_the_fly_split() {
+        let pool = |n| H160::from_low_u64_be(n);
+        let three_hop = RoutePath {
+            hops: vec![1, 2, 3, 1],
+            pools: vec![pool(1), pool(2), pool(3)],
+            dex_types: vec![DEXType::PancakeV2, DEXType::PancakeV3, DEXType::BiSwapV2],
+            ..Default::default()
+        };
+        let two_hop = RoutePath {
+            hops: vec![1, 2, 1],
+            pools: vec![pool(4), pool(1)],
+            dex_types: vec![DEXType::BiSwapV2, DEXType::PancakeV2],
+            ..Default::default()
+        };
+        let shared = RoutePath {
+            hops: vec![1, 2, 3, 1],
+            pools: vec![pool(5), pool(2), pool(5)],
+            dex_types: vec![DEXType::PancakeV2; 3],
+            ..Default::default()
+        };
+        let route_cache: DashMap<u32, Arc<Vec<RoutePath>>> = DashMap::new();
+        route_cache.insert(2, Arc::new(vec![three_hop.clone(), two_hop.clone(), shared.clone()]));
+        route_cache.insert(3, Arc::new(vec![three_hop.clone(), shared.clone()]));
+        precompute_route_legs(&route_cache);
+
+        for entry in route_cache.iter() {
+            let token_x = *entry.key();
+            for route in entry.value().iter() {
+                let legs = route_legs(route, token_x).unwrap();
+                assert!(matches!(legs, Cow::Borrowed(_)), "legs for {:?} not precomputed", route.hops);
+                let (buy, sell) = split_route_legs(route, token_x).unwrap();
+                assert_eq!((&legs.buy, &legs.sell), (&buy, &sell));
+                assert_eq!(legs.shares_pool, split_route_around_token_x(route, token_x).is_none());
+            }
+        }
+        // The shared-pool route keeps its legs for sequential simulation
+        assert!(route_cache.get(&3).unwrap()[1].legs.as_ref().unwrap().shares_pool);
+
+        // Legs cached for another token are not reused
+        let cached = route_cache.get(&2).unwrap()[0].clone();
+        let legs = route_legs(&cached, 3).unwrap();
+        assert!(matches!(legs, Cow::Owned(_)));
+        assert_eq!((legs.buy.hops.as_slice(), legs.sell.hops.as_slice()), (&[1, 2, 3][..], &[3, 1][..]));
+        // Extra fields don't change route identity
+        assert_eq!(cached, three_hop);
+    }
+}
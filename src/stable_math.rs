@@ -0,0 +1,170 @@
+use ethers::types::U256;
+
+/// Curve fee denominator: a pool `fee()` of 4_000_000 is 0.04%
+pub const FEE_DENOMINATOR: u64 = 10_000_000_000;
+
+/// Newton iterations before giving up, as in the Vyper implementation
+const MAX_ITERATIONS: usize = 255;
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b { a - b } else { b - a }
+}
+
+/// StableSwap invariant D for `balances` and amplification `amp` (the pool's `A()`).
+/// Balances must share one precision; BSC stable pools are all 18-decimal coins.
+pub fn get_d(balances: &[U256], amp: U256) -> Option<U256> {
+    let n = U256::from(balances.len());
+    let sum = balances.iter().try_fold(U256::zero(), |acc, b| acc.checked_add(*b))?;
+    if sum.is_zero() {
+        return Some(U256::zero());
+    }
+    let ann = amp.checked_mul(n)?;
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        let mut d_p = d;
+        for balance in balances {
+            d_p = d_p.checked_mul(d)?.checked_div(balance.checked_mul(n)?)?;
+        }
+        let d_prev = d;
+        let numerator = ann.checked_mul(sum)?.checked_add(d_p.checked_mul(n)?)?.checked_mul(d)?;
+        let denominator = ann
+            .checked_sub(U256::one())?
+            .checked_mul(d)?
+            .checked_add((n + 1).checked_mul(d_p)?)?;
+        d = numerator.checked_div(denominator)?;
+        if abs_diff(d, d_prev) <= U256::one() {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// New balance of coin `j` that keeps D constant when coin `i`'s balance becomes `x`
+pub fn get_y(i: usize, j: usize, x: U256, balances: &[U256], amp: U256) -> Option<U256> {
+    let n_coins = balances.len();
+    if i == j || i >= n_coins || j >= n_coins {
+        return None;
+    }
+    let n = U256::from(n_coins);
+    let d = get_d(balances, amp)?;
+    let ann = amp.checked_mul(n)?;
+    let mut c = d;
+    let mut sum = U256::zero();
+    for (k, balance) in balances.iter().enumerate() {
+        let x_k = if k == i {
+            x
+        } else if k != j {
+            *balance
+        } else {
+            continue;
+        };
+        sum = sum.checked_add(x_k)?;
+        c = c.checked_mul(d)?.checked_div(x_k.checked_mul(n)?)?;
+    }
+    c = c.checked_mul(d)?.checked_div(ann.checked_mul(n)?)?;
+    let b = sum.checked_add(d.checked_div(ann)?)?;
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        let denominator = y.checked_mul(U256::from(2u8))?.checked_add(b)?.checked_sub(d)?;
+        y = y.checked_mul(y)?.checked_add(c)?.checked_div(denominator)?;
+        if abs_diff(y, y_prev) <= U256::one() {
+            return Some(y);
+        }
+    }
+    None
+}
+
+/// Output of coin `j` for `dx` of coin `i`, after the pool fee (Curve `get_dy`)
+pub fn get_dy(i: usize, j: usize, dx: U256, balances: &[U256], amp: U256, fee: u32) -> Option<U256> {
+    let x = balances.get(i)?.checked_add(dx)?;
+    let y = get_y(i, j, x, balances, amp)?;
+    let dy = balances.get(j)?.checked_sub(y)?.checked_sub(U256::one())?;
+    let fee_amount = dy.checked_mul(U256::from(fee))? / U256::from(FEE_DENOMINATOR);
+    dy.checked_sub(fee_amount)
+}
+
+/// Input of coin `i` needed to receive `dy` of coin `j` after the pool fee (rounded up,
+/// so `get_dy(i, j, get_dx(..))` is at least `dy`)
+pub fn get_dx(i: usize, j: usize, dy: U256, balances: &[U256], amp: U256, fee: u32) -> Option<U256> {
+    let fee_numerator = FEE_DENOMINATOR.checked_sub(u64::from(fee)).filter(|f| *f > 0)?;
+    let dy_before_fee = dy
+        .checked_mul(U256::from(FEE_DENOMINATOR))?
+        .checked_add(U256::from(fee_numerator - 1))?
+        / U256::from(fee_numerator);
+    let y = balances.get(j)?.checked_sub(dy_before_fee)?.checked_sub(U256::one())?;
+    if y.is_zero() {
+        return None;
+    }
+    let x = get_y(j, i, y, balances, amp)?;
+    Some(x.checked_sub(*balances.get(i)?)? + U256::one())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn e18(n: u64) -> U256 {
+        U256::from(n) * U256::exp10(18)
+    }
+
+    /// Reference values from an integer port of the 3pool Vyper `get_D` / `get_y` / `get_dy`
+    #[test]
+    fn test_matches_reference_implementation() {
+        let balances = [e18(1_000_000), e18(1_200_000), e18(800_000)];
+        let amp = U256::from(200u32);
+        assert_eq!(
+            get_d(&balances, amp).unwrap(),
+            U256::from_dec_str("2999792760282722013878415").unwrap()
+        );
+        assert_eq!(
+            get_dy(0, 1, e18(1_000), &balances, amp, 4_000_000).unwrap(),
+            U256::from_dec_str("1000459257259834444044").unwrap()
+        );
+        assert_eq!(
+            get_dy(2, 0, e18(50_000), &balances, amp, 4_000_000).unwrap(),
+            U256::from_dec_str("50028080809346182669203").unwrap()
+        );
+        // Balanced 2-coin pool, no fee: close to 1:1
+        let balanced = [e18(5_000_000), e18(5_000_000)];
+        assert_eq!(
+            get_dy(0, 1, e18(1_000), &balanced, U256::from(100u32), 0).unwrap(),
+            U256::from_dec_str("999998019805823727699").unwrap()
+        );
+    }
+
+    /// Imbalanced 2-coin pool with Ellipsis-style parameters (A = 400, 0.04% fee); reference
+    /// values from the same Vyper port
+    #[test]
+    fn test_two_coin_quote_matches_reference() {
+        let balances = [e18(3_250_000) + U256::from(123_456_789u64), e18(2_100_000) + U256::from(987_654_321u64)];
+        let amp = U256::from(400u32);
+        assert_eq!(get_d(&balances, amp).unwrap(), U256::from_dec_str("5349676902305347806092539").unwrap());
+        for (i, j, dx, expected) in [
+            (0, 1, e18(10_000), "9984109676864997271990"),
+            (1, 0, e18(250_000), "250123135294119128404846"),
+        ] {
+            let dy = get_dy(i, j, dx, &balances, amp, 4_000_000).unwrap();
+            let expected = U256::from_dec_str(expected).unwrap();
+            assert!(abs_diff(dy, expected) <= U256::one(), "get_dy({}, {}) = {}, Curve quotes {}", i, j, dy, expected);
+        }
+    }
+
+    #[test]
+    fn test_get_dx_inverts_get_dy() {
+        let balances = [e18(1_000_000), e18(1_200_000), e18(800_000)];
+        let amp = U256::from(200u32);
+        for (i, j, dy) in [(0, 1, e18(1_000)), (1, 2, e18(25_000)), (2, 0, U256::from(12_345u64))] {
+            let dx = get_dx(i, j, dy, &balances, amp, 4_000_000).unwrap();
+            let out = get_dy(i, j, dx, &balances, amp, 4_000_000).unwrap();
+            assert!(out >= dy, "get_dy({}) = {} < {}", dx, out, dy);
+            // Rounding slack stays within a few wei
+            let less = get_dy(i, j, dx - U256::from(3u8), &balances, amp, 4_000_000).unwrap();
+            assert!(less < dy);
+        }
+        // Can't drain the output coin
+        assert!(get_dx(0, 2, e18(800_000), &balances, amp, 4_000_000).is_none());
+        assert!(get_dy(0, 0, e18(1), &balances, amp, 0).is_none());
+        assert!(get_dy(0, 3, e18(1), &balances, amp, 0).is_none());
+    }
+}
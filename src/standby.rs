@@ -0,0 +1,141 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Whether a bot started in standby executes trades yet. Standby keeps the reserve cache, route
+/// cache and price tracker running as usual; only the execution arm waits for promotion.
+#[derive(Debug)]
+pub struct StandbyState {
+    active: AtomicBool,
+    promotion_requested: AtomicBool,
+    promote: Notify,
+}
+
+impl StandbyState {
+    pub fn new(active: bool) -> Self {
+        Self { active: AtomicBool::new(active), promotion_requested: AtomicBool::new(false), promote: Notify::new() }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Ask the main loop to promote. Returns false if already active or already requested.
+    pub fn request_promotion(&self) -> bool {
+        if self.is_active() || self.promotion_requested.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        self.promote.notify_one();
+        true
+    }
+
+    /// Resolves once a promotion has been requested; never resolves for an active instance
+    pub async fn promotion_requested(&self) {
+        if self.is_active() {
+            std::future::pending::<()>().await;
+        }
+        self.promote.notified().await;
+    }
+
+    /// Called by the main loop once nonce and inventory are resynced
+    pub fn mark_active(&self) {
+        self.active.store(true, Ordering::Release);
+    }
+
+    /// Resync failed: stay in standby and accept the next request
+    pub fn promotion_failed(&self) {
+        self.promotion_requested.store(false, Ordering::Release);
+    }
+
+    pub fn status(&self) -> &'static str {
+        match (self.is_active(), self.promotion_requested.load(Ordering::Acquire)) {
+            (true, _) => "active",
+            (false, true) => "promoting",
+            (false, false) => "standby",
+        }
+    }
+}
+
+/// Promote on SIGUSR1 (`kill -USR1 <pid>`)
+#[cfg(unix)]
+pub fn spawn_promotion_signal(state: Arc<StandbyState>) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut usr1 = match signal(SignalKind::user_defined1()) {
+        Ok(usr1) => usr1,
+        Err(e) => {
+            eprintln!("[STANDBY] Cannot listen for SIGUSR1: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while usr1.recv().await.is_some() {
+            println!("[STANDBY] SIGUSR1 received, status {}", state.status());
+            state.request_promotion();
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn spawn_promotion_signal(_state: Arc<StandbyState>) {}
+
+/// Serve `GET /status` and `POST /promote` on `addr`
+pub async fn start_control_endpoint(addr: SocketAddr, state: Arc<StandbyState>) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/status", get(status_handler))
+        .route("/promote", post(promote_handler))
+        .with_state(state);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!("[STANDBY] Control endpoint on http://{}/status and POST http://{}/promote", addr, addr);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("[STANDBY] Control endpoint stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+async fn status_handler(State(state): State<Arc<StandbyState>>) -> Json<serde_json::Value> {
+    Json(json!({ "status": state.status() }))
+}
+
+/// 202 while the resync runs; poll `/status` until it reports `active`
+async fn promote_handler(State(state): State<Arc<StandbyState>>) -> (StatusCode, Json<serde_json::Value>) {
+    let code = if state.request_promotion() { StatusCode::ACCEPTED } else { StatusCode::OK };
+    (code, Json(json!({ "status": state.status() })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_promotion_handshake() {
+        let state = Arc::new(StandbyState::new(false));
+        assert_eq!(state.status(), "standby");
+
+        let waiter = tokio::spawn({
+            let state = state.clone();
+            async move { state.promotion_requested().await }
+        });
+        assert!(state.request_promotion());
+        assert!(!state.request_promotion(), "second request is a no-op");
+        waiter.await.unwrap();
+        assert_eq!(state.status(), "promoting");
+        assert!(!state.is_active());
+
+        state.promotion_failed();
+        assert_eq!(state.status(), "standby");
+        assert!(state.request_promotion(), "a failed resync can be retried");
+
+        state.mark_active();
+        assert_eq!(state.status(), "active");
+        assert!(!state.request_promotion());
+        assert!(StandbyState::new(true).is_active());
+    }
+}
@@ -0,0 +1,270 @@
+//! Deterministic fixtures for tests and benches (`--features testkit` outside `cargo test`).
+//! Pools, the token index, routes and the precomputed route cache are all built from one
+//! pool list, so they agree with each other and token indices follow insertion order.
+
+use crate::cache::{PoolState, PoolType, ReserveCache};
+use crate::config::{BaseToken, Config, DexVersion};
+use crate::price_oracle::{seed_prices, PriceOracle};
+use crate::route_cache::{DEXType, RoutePath};
+use crate::split_route_path::precompute_route_legs;
+use crate::token_index::TokenIndexMap;
+use crate::token_tax::TokenTaxMap;
+use crate::v3_math::price_to_sqrt_price_x96;
+use dashmap::DashMap;
+use ethers::types::{H160, U256};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Address of test token `n`
+pub fn token(n: u64) -> H160 {
+    H160::from_low_u64_be(n)
+}
+
+/// Address of test pool `n`; offset so it never collides with `token(n)`
+pub fn pool_address(n: u64) -> H160 {
+    H160::from_low_u64_be(0x1_0000 + n)
+}
+
+/// `n` whole units of an 18-decimal token
+pub fn units(n: u64) -> U256 {
+    U256::from(n) * U256::exp10(18)
+}
+
+/// PancakeSwap V2 pair holding `reserve0` / `reserve1`
+pub fn pool_v2(token0: H160, token1: H160, reserve0: U256, reserve1: U256) -> PoolState {
+    PoolState {
+        pool_type: PoolType::V2,
+        token0,
+        token1,
+        reserve0: Some(reserve0),
+        reserve1: Some(reserve1),
+        dex_name: Some("PancakeSwap V2".to_string()),
+        ..Default::default()
+    }
+}
+
+/// PancakeSwap V3 pool at `price` (token1 per token0) with `liquidity` in range.
+/// The tick is derived from the price and the spacing from the fee tier.
+pub fn pool_v3(token0: H160, token1: H160, price: f64, liquidity: U256, fee: u32) -> PoolState {
+    let tick_spacing = match fee {
+        100 => 1,
+        500 => 10,
+        2500 => 50,
+        10_000 => 200,
+        _ => 60,
+    };
+    PoolState {
+        pool_type: PoolType::V3,
+        token0,
+        token1,
+        sqrt_price_x96: Some(price_to_sqrt_price_x96(price)),
+        liquidity: Some(liquidity),
+        tick: Some((price.ln() / 1.0001f64.ln()).floor() as i32),
+        fee: Some(fee),
+        tick_spacing: Some(tick_spacing),
+        dex_name: Some("PancakeSwap V3".to_string()),
+        ..Default::default()
+    }
+}
+
+fn dex_type_for(state: &PoolState) -> DEXType {
+    let version = match state.pool_type {
+        PoolType::V2 => DexVersion::V2,
+        PoolType::V3 => DexVersion::V3,
+        PoolType::StableN { .. } => return DEXType::StableSwap,
+    };
+    DEXType::from_dex_name(state.dex_name.as_deref().unwrap_or(""), &version)
+}
+
+/// Route visiting `tokens` in order through `pools` (`tokens.len() == pools.len() + 1`)
+pub fn route(tokens: &[H160], pools: &[H160], cache: &ReserveCache, index: &TokenIndexMap) -> RoutePath {
+    assert_eq!(tokens.len(), pools.len() + 1, "a route needs one more token than pools");
+    RoutePath {
+        hops: tokens.iter().map(|t| index.address_to_index[t]).collect(),
+        pools: pools.to_vec(),
+        dex_types: pools
+            .iter()
+            .map(|p| dex_type_for(cache.get(p).expect("route pool not in the market").value()))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Everything detection and simulation take, built by `MarketBuilder`
+pub struct Market {
+    pub reserve_cache: Arc<ReserveCache>,
+    pub token_index: Arc<TokenIndexMap>,
+    pub route_cache: Arc<DashMap<u32, Arc<Vec<RoutePath>>>>,
+    pub token_tax_map: Arc<TokenTaxMap>,
+    pub config: Config,
+    pub price_oracle: Arc<PriceOracle>, // seed prices, gas at config.gas_price
+}
+
+impl Market {
+    /// Token index of `token`; panics for tokens outside the market
+    pub fn index(&self, token: H160) -> u32 {
+        self.token_index.address_to_index[&token]
+    }
+
+    pub fn route(&self, tokens: &[H160], pools: &[H160]) -> RoutePath {
+        route(tokens, pools, &self.reserve_cache, &self.token_index)
+    }
+}
+
+#[derive(Default)]
+pub struct MarketBuilder {
+    pools: Vec<(H160, PoolState)>,
+    routes: Vec<(Vec<H160>, Vec<H160>)>,
+    bases: Vec<H160>,
+    config: Config,
+}
+
+impl MarketBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pool(mut self, address: H160, state: PoolState) -> Self {
+        self.pools.push((address, state));
+        self
+    }
+
+    /// Register a cycle in the route cache under each of its intermediate tokens,
+    /// the way `build_route_cache` does
+    pub fn route(mut self, tokens: &[H160], pools: &[H160]) -> Self {
+        self.routes.push((tokens.to_vec(), pools.to_vec()));
+        self
+    }
+
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Add `token` to the config's base tokens, enabled, so detection simulates routes from it
+    pub fn base(mut self, token: H160) -> Self {
+        self.bases.push(token);
+        self
+    }
+
+    pub fn build(mut self) -> Market {
+        for address in self.bases {
+            if !self.config.base_tokens.iter().any(|b| b.address == address) {
+                let symbol = format!("BASE{}", self.config.base_tokens.len());
+                self.config.base_tokens.push(BaseToken { symbol, address, decimals: 18, is_stable: false, enabled: true });
+            }
+        }
+        let reserve_cache = ReserveCache::new();
+        let mut address_to_index = HashMap::new();
+        let mut index_to_address = HashMap::new();
+        for (address, state) in self.pools {
+            let extra: Vec<H160> = match &state.pool_type {
+                PoolType::StableN { tokens, .. } => tokens.clone(),
+                _ => Vec::new(),
+            };
+            for token in [state.token0, state.token1].into_iter().chain(extra) {
+                let next = address_to_index.len() as u32;
+                address_to_index.entry(token).or_insert_with(|| {
+                    index_to_address.insert(next, token);
+                    next
+                });
+            }
+            reserve_cache.insert(address, state);
+        }
+        let token_index = TokenIndexMap { address_to_index, index_to_address };
+
+        let route_cache: DashMap<u32, Arc<Vec<RoutePath>>> = DashMap::new();
+        for (tokens, pools) in &self.routes {
+            let path = route(tokens, pools, &reserve_cache, &token_index);
+            let mut intermediates = path.hops[1..path.hops.len() - 1].to_vec();
+            intermediates.dedup();
+            for idx in intermediates {
+                Arc::make_mut(&mut route_cache.entry(idx).or_default()).push(path.clone());
+            }
+        }
+        precompute_route_legs(&route_cache);
+        let price_oracle = PriceOracle::new(seed_prices());
+        price_oracle.set_gas_price(U256::from(self.config.gas_price));
+
+        Market {
+            reserve_cache: Arc::new(reserve_cache),
+            token_index: Arc::new(token_index),
+            route_cache: Arc::new(route_cache),
+            token_tax_map: Arc::new(TokenTaxMap::new()),
+            config: self.config,
+            price_oracle: Arc::new(price_oracle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage_finder::simulate_all_paths_for_token_x;
+    use crate::simulate_swap_path::quote_exact_in;
+
+    #[test]
+    fn test_market_is_consistent() {
+        let (base, x, y) = (token(1), token(2), token(3));
+        let pools = [pool_address(1), pool_address(2), pool_address(3)];
+        let market = MarketBuilder::new()
+            .pool(pools[0], pool_v2(base, x, units(1_000), units(2_000)))
+            .pool(pools[1], pool_v3(x, y, 0.5, units(1_000_000), 500))
+            .pool(pools[2], pool_v2(y, base, units(1_000), units(1_000)))
+            .route(&[base, x, y, base], &pools)
+            .build();
+
+        // Indices follow pool insertion order, not DashMap iteration order
+        assert_eq!((market.index(base), market.index(x), market.index(y)), (0, 1, 2));
+        let cycle = market.route(&[base, x, y, base], &pools);
+        assert_eq!(cycle.dex_types, vec![DEXType::PancakeV2, DEXType::PancakeV3, DEXType::PancakeV2]);
+        for token in [x, y] {
+            assert_eq!(**market.route_cache.get(&market.index(token)).unwrap().value(), vec![cycle.clone()]);
+        }
+        assert!(market.route_cache.get(&market.index(base)).is_none());
+
+        let amounts = quote_exact_in(
+            &cycle,
+            units(1),
+            &market.reserve_cache,
+            &market.token_index,
+            &market.token_tax_map,
+            &market.config,
+        )
+        .unwrap();
+        assert_eq!(amounts.len(), 4);
+        assert!(amounts.iter().all(|a| !a.is_zero()));
+    }
+
+    #[test]
+    fn test_v2_v3_price_gap_is_found() {
+        let (base, x) = (token(1), token(2));
+        let (v3, v2) = (pool_address(1), pool_address(2));
+        // X is ~10% cheaper on the V3 pool than on the V2 pair. Only routes starting at an
+        // enabled base token are simulated, so the base has to be registered.
+        let market = MarketBuilder::new()
+            .base(base)
+            .pool(v3, pool_v3(base, x, 1.1, units(1_000_000), 2500))
+            .pool(v2, pool_v2(base, x, units(100_000), units(100_000)))
+            .route(&[base, x, base], &[v3, v2])
+            .route(&[base, x, base], &[v2, v3])
+            .build();
+
+        let routes = simulate_all_paths_for_token_x(
+            market.index(x),
+            units(1),
+            v3,
+            &market.route_cache,
+            &market.reserve_cache,
+            &market.token_index,
+            &market.token_tax_map,
+            &market.config,
+        );
+        let best = routes.iter().max_by_key(|r| r.profit).expect("no route simulated");
+        assert_eq!(best.buy_pools, vec![v3]);
+        assert_eq!(best.sell_pools, vec![v2]);
+        assert!(best.profit > U256::exp10(16), "profit {}", best.profit);
+        // Buying on V2 and selling on V3 loses money
+        assert!(routes.iter().filter(|r| r.buy_pools == vec![v2]).all(|r| r.profit.is_zero()));
+    }
+}
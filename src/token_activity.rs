@@ -0,0 +1,192 @@
+use crate::config::Config;
+use crate::schema::{from_versioned_json, to_versioned_json};
+use dashmap::DashMap;
+use ethers::types::H160;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Detection history of one tokenX. Times are unix seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenStats {
+    pub first_seen: u64,      // first event for the token; starts its grace window
+    pub last_profitable: u64, // last profitable opportunity (0 = never)
+    pub events: u64,          // events that reached detection, simulated or not
+    pub hits: u64,            // events that produced a profitable opportunity
+}
+
+impl TokenStats {
+    /// No profitable opportunity within `window_secs` of `now`, counting from the later of
+    /// the first sighting and the last hit so newly listed tokens get a full window
+    pub fn is_idle(&self, now: u64, window_secs: u64) -> bool {
+        window_secs > 0 && now.saturating_sub(self.first_seen.max(self.last_profitable)) >= window_secs
+    }
+}
+
+/// Events skipped because their tokenX was idle and not due for a probe
+pub static IDLE_TOKEN_SKIPS: AtomicU64 = AtomicU64::new(0);
+
+/// Per-token profitability, keyed by address so it survives token index changes between runs
+#[derive(Debug, Default)]
+pub struct TokenActivity {
+    stats: DashMap<H160, TokenStats>,
+}
+
+impl TokenActivity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count an event for `token` and decide whether its routes are simulated. Idle tokens
+    /// are only simulated on every `idle_token_probe_rate`-th event, so one that becomes
+    /// profitable again is picked back up.
+    pub fn should_simulate(&self, token: H160, now: u64, config: &Config) -> bool {
+        let mut stats = self.stats.entry(token).or_insert_with(|| TokenStats { first_seen: now, ..Default::default() });
+        stats.events += 1;
+        if !stats.is_idle(now, config.idle_token_window_secs) {
+            return true;
+        }
+        config.idle_token_probe_rate <= 1 || stats.events % config.idle_token_probe_rate == 0
+    }
+
+    pub fn record_profitable(&self, token: H160, now: u64) {
+        let mut stats = self.stats.entry(token).or_insert_with(|| TokenStats { first_seen: now, ..Default::default() });
+        stats.hits += 1;
+        stats.last_profitable = now;
+    }
+
+    pub fn get(&self, token: &H160) -> Option<TokenStats> {
+        self.stats.get(token).map(|s| *s)
+    }
+
+    /// (tracked, idle) token counts
+    pub fn summary(&self, now: u64, window_secs: u64) -> (usize, usize) {
+        let idle = self.stats.iter().filter(|s| s.value().is_idle(now, window_secs)).count();
+        (self.stats.len(), idle)
+    }
+
+    /// Versioned JSON: `{"schema_version": N, "data": {"0x…": TokenStats, ...}}`
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let sorted: BTreeMap<H160, TokenStats> = self.stats.iter().map(|s| (*s.key(), *s.value())).collect();
+        to_versioned_json(&sorted)
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let stats: BTreeMap<H160, TokenStats> = from_versioned_json(json)?;
+        Ok(Self { stats: stats.into_iter().collect() })
+    }
+
+    /// Write to a temp file and rename, so a crash mid-write keeps the previous file
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.to_json()?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Stats from `path`, or empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Self::from_json(&json),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Activity tracked by live detection; unset when idle-token demotion is off
+static TOKEN_ACTIVITY: OnceCell<TokenActivity> = OnceCell::new();
+
+/// Enable per-token tracking for live detection. Call once at startup.
+pub fn init_token_activity(activity: TokenActivity) {
+    let _ = TOKEN_ACTIVITY.set(activity);
+}
+
+pub fn token_activity() -> Option<&'static TokenActivity> {
+    TOKEN_ACTIVITY.get()
+}
+
+pub fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+/// Persist the live stats to `path` every `interval_secs` and log how many tokens are idle
+pub fn spawn_activity_saver(path: PathBuf, interval_secs: u64, window_secs: u64) {
+    let Some(activity) = token_activity() else {
+        return;
+    };
+    if interval_secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        interval.tick().await; // first tick fires immediately
+        loop {
+            interval.tick().await;
+            let (tracked, idle) = activity.summary(unix_now(), window_secs);
+            println!(
+                "[ACTIVITY] {} tokens tracked, {} idle (probed only), {} events skipped",
+                tracked,
+                idle,
+                IDLE_TOKEN_SKIPS.load(Ordering::Relaxed)
+            );
+            if let Err(e) = activity.save(&path) {
+                eprintln!("❌ [ACTIVITY] Failed to save {}: {}", path.display(), e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86_400;
+
+    fn idle_config(window_secs: u64, probe_rate: u64) -> Config {
+        Config { idle_token_window_secs: window_secs, idle_token_probe_rate: probe_rate, ..Config::default() }
+    }
+
+    #[test]
+    fn test_idle_tokens_are_only_probed() {
+        let config = idle_config(7 * DAY, 4);
+        let activity = TokenActivity::new();
+        let (new, stale) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let start = 1_700_000_000;
+
+        // Newly seen tokens get a full window before anything is skipped
+        assert!((0..10).all(|i| activity.should_simulate(new, start + i * DAY / 2, &config)));
+
+        activity.record_profitable(stale, start);
+        let later = start + 8 * DAY;
+        let simulated: Vec<bool> = (0..8).map(|_| activity.should_simulate(stale, later, &config)).collect();
+        assert_eq!(simulated, [false, false, false, true, false, false, false, true]);
+
+        // A hit found by a probe brings the token back
+        activity.record_profitable(stale, later);
+        assert!(activity.should_simulate(stale, later + 1, &config));
+        assert_eq!(activity.get(&stale).unwrap().hits, 2);
+        assert_eq!(activity.summary(later + 1, config.idle_token_window_secs), (2, 1));
+
+        // Window 0 never demotes
+        assert!(activity.should_simulate(new, later + 100 * DAY, &idle_config(0, 4)));
+    }
+
+    #[test]
+    fn test_stats_round_trip() {
+        let activity = TokenActivity::new();
+        activity.record_profitable(H160::from_low_u64_be(7), 1_700_000_000);
+        activity.should_simulate(H160::from_low_u64_be(8), 1_700_000_100, &Config::default());
+
+        let back = TokenActivity::from_json(&activity.to_json().unwrap()).unwrap();
+        for token in [H160::from_low_u64_be(7), H160::from_low_u64_be(8)] {
+            assert_eq!(back.get(&token), activity.get(&token));
+        }
+        let missing = std::env::temp_dir().join(format!("token_activity_missing_{}.json", std::process::id()));
+        assert_eq!(TokenActivity::load(&missing).unwrap().summary(0, 0), (0, 0));
+    }
+}
@@ -1,15 +1,25 @@
-use ethers::types::H160;
+use ethers::types::{H160, U256};
 use dashmap::DashMap;
 use std::collections::HashMap;
 
-use crate::cache::{ReserveCache, PoolType};
+use crate::cache::{ReserveCache, ReserveCacheExt, PoolState, PoolType};
+use crate::config::{Config, DexVersion};
+use crate::route_cache::{DEXType, RoutePath};
 use crate::token_index::TokenIndexMap;
+use crate::stable_math::get_dy;
+use crate::v3_math::simulate_v3_swap;
+
+/// Maximum number of hops explored by `TokenGraph::best_path`
+pub const MAX_PATH_HOPS: usize = 4;
 
 #[derive(Clone, Debug)]
 pub struct GraphEdge {
     pub to: u32,              // destination token index
     pub pool: H160,           // pool address
     pub pool_type: PoolType,  // V2 or V3
+    pub zero_for_one: bool,   // true if swapping token0 → token1 through this pool
+    pub fee: u32,             // V2: basis points, V3: hundredths of a bip, StableN: 1e10 denominator
+    pub coins: Option<(usize, usize)>, // StableN: (i, j) coin indices of this edge
 }
 
 #[derive(Debug)]
@@ -21,14 +31,46 @@ impl TokenGraph {
     pub fn build(
         reserve_cache: &ReserveCache,
         token_index: &TokenIndexMap,
+        config: &Config,
     ) -> Self {
         let edges = DashMap::new();
 
-        for entry in reserve_cache.iter() {
+        for entry in reserve_cache.active_pools() {
+            // Stable pools get an edge for every ordered pair of their coins
+            if let PoolType::StableN { tokens, .. } = &entry.value().pool_type {
+                let fee = entry.value().fee.unwrap_or(0);
+                for (i, from) in tokens.iter().enumerate() {
+                    for (j, to) in tokens.iter().enumerate() {
+                        let (Some(from_idx), Some(to_idx)) =
+                            (token_index.address_to_index.get(from), token_index.address_to_index.get(to))
+                        else { continue };
+                        if i == j {
+                            continue;
+                        }
+                        edges.entry(*from_idx).or_insert(Vec::new()).push(GraphEdge {
+                            to: *to_idx,
+                            pool: *entry.key(),
+                            pool_type: entry.value().pool_type.clone(),
+                            zero_for_one: i < j,
+                            fee,
+                            coins: Some((i, j)),
+                        });
+                    }
+                }
+                continue;
+            }
             let token0 = entry.value().token0;
             let token1 = entry.value().token1;
             let pool = *entry.key();
             let pool_type = entry.value().pool_type.clone();
+            let fee = match pool_type {
+                PoolType::V2 => entry.value().dex_name.as_deref().map(|d| config.get_v2_fee(d)).unwrap_or(25),
+                PoolType::V3 => match entry.value().fee {
+                    Some(fee) => fee,
+                    None => continue, // unknown fee tier: no edge rather than a guessed one
+                },
+                PoolType::StableN { .. } => continue, // edges added above
+            };
 
             let index0 = token_index.address_to_index.get(&token0).unwrap();
             let index1 = token_index.address_to_index.get(&token1).unwrap();
@@ -38,6 +80,9 @@ impl TokenGraph {
                 to: *index1,
                 pool,
                 pool_type: pool_type.clone(),
+                zero_for_one: true,
+                fee,
+                coins: None,
             });
 
             // Add edge: token1 → token0
@@ -45,9 +90,154 @@ impl TokenGraph {
                 to: *index0,
                 pool,
                 pool_type: pool_type.clone(),
+                zero_for_one: false,
+                fee,
+                coins: None,
             });
         }
 
         Self { edges }
     }
-} 
\ No newline at end of file
+
+    /// Find the route from `from` to `to` (token indices) that returns the most output for `amount`.
+    /// Each hop is simulated against the reserve cache; paths never revisit a token and are at most
+    /// `MAX_PATH_HOPS` long. Returns None if `to` is unreachable.
+    pub fn best_path(
+        &self,
+        from: u32,
+        to: u32,
+        amount: U256,
+        reserve_cache: &ReserveCache,
+    ) -> Option<RoutePath> {
+        if from == to || amount.is_zero() {
+            return None;
+        }
+
+        // token index → (best amount reached so far, route that reaches it)
+        let mut best: HashMap<u32, (U256, RoutePath)> = HashMap::new();
+        best.insert(from, (amount, RoutePath { hops: vec![from], pools: vec![], dex_types: vec![], ..Default::default() }));
+        let mut frontier = vec![from];
+
+        for _ in 0..MAX_PATH_HOPS {
+            let mut next = Vec::new();
+            for token in frontier {
+                if token == to {
+                    continue;
+                }
+                let (amount_in, route) = best[&token].clone();
+                let Some(edges) = self.edges.get(&token) else { continue };
+                for edge in edges.iter() {
+                    if route.hops.contains(&edge.to) {
+                        continue;
+                    }
+                    let Some(state) = reserve_cache.get(&edge.pool) else { continue };
+                    let Some(amount_out) = simulate_edge(state.value(), edge, amount_in) else { continue };
+                    if amount_out.is_zero() {
+                        continue;
+                    }
+                    if best.get(&edge.to).map_or(true, |(reached, _)| amount_out > *reached) {
+                        let mut extended = route.clone();
+                        extended.hops.push(edge.to);
+                        extended.pools.push(edge.pool);
+                        extended.dex_types.push(dex_type_for(state.value()));
+                        best.insert(edge.to, (amount_out, extended));
+                        if !next.contains(&edge.to) {
+                            next.push(edge.to);
+                        }
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+
+        best.remove(&to).map(|(_, route)| route)
+    }
+}
+
+/// Output of swapping `amount_in` through a single edge
+fn simulate_edge(state: &PoolState, edge: &GraphEdge, amount_in: U256) -> Option<U256> {
+    match &state.pool_type {
+        PoolType::V2 => {
+            let (reserve_in, reserve_out) = if edge.zero_for_one {
+                (state.reserve0?, state.reserve1?)
+            } else {
+                (state.reserve1?, state.reserve0?)
+            };
+            let amount_in_with_fee = amount_in.checked_mul(U256::from(10_000u32.checked_sub(edge.fee)?))?;
+            let numerator = amount_in_with_fee.checked_mul(reserve_out)?;
+            let denominator = reserve_in.checked_mul(U256::from(10_000u32))?.checked_add(amount_in_with_fee)?;
+            numerator.checked_div(denominator)
+        }
+        PoolType::V3 => simulate_v3_swap(
+            amount_in,
+            state.sqrt_price_x96?,
+            state.liquidity?,
+            edge.fee,
+            edge.zero_for_one,
+        ),
+        PoolType::StableN { balances, amp, .. } => {
+            let (i, j) = edge.coins?;
+            get_dy(i, j, amount_in, balances, *amp, edge.fee)
+        }
+    }
+}
+
+fn dex_type_for(state: &PoolState) -> DEXType {
+    let version = match state.pool_type {
+        PoolType::V2 => DexVersion::V2,
+        PoolType::V3 => DexVersion::V3,
+        PoolType::StableN { .. } => return DEXType::StableSwap,
+    };
+    DEXType::from_dex_name(state.dex_name.as_deref().unwrap_or(""), &version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2_pool(token0: H160, token1: H160, reserve0: u64, reserve1: u64) -> PoolState {
+        PoolState {
+            pool_type: PoolType::V2,
+            token0,
+            token1,
+            reserve0: Some(U256::from(reserve0)),
+            reserve1: Some(U256::from(reserve1)),
+            dex_name: Some("PancakeSwap V2".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_best_path_prefers_higher_output() {
+        let a = H160::from_low_u64_be(1);
+        let b = H160::from_low_u64_be(2);
+        let c = H160::from_low_u64_be(3);
+        let d = H160::from_low_u64_be(4);
+        let direct = H160::from_low_u64_be(100);
+        let ac = H160::from_low_u64_be(101);
+        let cb = H160::from_low_u64_be(102);
+
+        let cache = ReserveCache::new();
+        // Thin direct pool A→B (bad price), deep A→C→B route (better price)
+        cache.insert(direct, v2_pool(a, b, 1_000_000, 1_000_000));
+        cache.insert(ac, v2_pool(a, c, 1_000_000_000, 2_000_000_000));
+        cache.insert(cb, v2_pool(c, b, 1_000_000_000, 1_000_000_000));
+        // Unconnected pool so D is in the index but unreachable from A
+        cache.insert(H160::from_low_u64_be(103), v2_pool(d, H160::from_low_u64_be(5), 1000, 1000));
+
+        let index = TokenIndexMap::build_from_reserve_cache(&cache);
+        let graph = TokenGraph::build(&cache, &index, &Config::default());
+        let idx = |t: &H160| index.address_to_index[t];
+
+        let route = graph.best_path(idx(&a), idx(&b), U256::from(10_000u64), &cache).unwrap();
+        assert_eq!(route.hops, vec![idx(&a), idx(&c), idx(&b)]);
+        assert_eq!(route.pools, vec![ac, cb]);
+        assert_eq!(route.dex_types, vec![DEXType::PancakeV2, DEXType::PancakeV2]);
+
+        assert!(graph.best_path(idx(&a), idx(&d), U256::from(10_000u64), &cache).is_none());
+        assert!(graph.best_path(idx(&a), idx(&a), U256::from(10_000u64), &cache).is_none());
+    }
+}
@@ -1,6 +1,6 @@
 use ethers::types::H160;
 use std::collections::HashMap;
-use crate::cache::ReserveCache;
+use crate::cache::{PoolType, ReserveCache};
 
 #[derive(Debug)]
 pub struct TokenIndexMap {
@@ -17,8 +17,13 @@ impl TokenIndexMap {
         for entry in reserve_cache.iter() {
             let token0 = entry.value().token0;
             let token1 = entry.value().token1;
+            // Stable pools can hold more than two coins
+            let extra: &[H160] = match &entry.value().pool_type {
+                PoolType::StableN { tokens, .. } => tokens,
+                _ => &[],
+            };
 
-            for token in [token0, token1] {
+            for &token in [token0, token1].iter().chain(extra) {
                 if !address_to_index.contains_key(&token) {
                     address_to_index.insert(token, next_index);
                     index_to_address.insert(next_index, token);
@@ -1,6 +1,23 @@
-use ethers::types::H160;
+use ethers::types::{H160, U256};
 use serde::Deserialize;
 use dashmap::DashMap;
+use crate::sim_trace::fork_db_at;
+use alloy_eips::BlockId;
+use alloy_provider::DynProvider;
+use async_trait::async_trait;
+use ethers::abi::{decode, encode, ParamType, Token};
+use ethers::utils::keccak256;
+use once_cell::sync::OnceCell;
+use revm::context::TxEnv;
+use revm::context_interface::result::ExecutionResult;
+use revm::database::CacheDB;
+use revm::database_interface::DatabaseRef;
+use revm::handler::{ExecuteCommitEvm, MainBuilder};
+use revm::primitives::{Address as RevmAddress, Bytes as RevmBytes, Log, TxKind, U256 as RevmU256};
+use revm::state::AccountInfo;
+use revm::{Context, MainContext};
+use std::collections::HashSet;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct TokenTaxInfo {
@@ -16,6 +33,38 @@ pub struct TokenTaxInfo {
 
 pub type TokenTaxMap = DashMap<H160, TokenTaxInfo>;
 
+/// Resolution of the integer tax math: a tax is applied in parts per million
+pub const TAX_PPM: u64 = 1_000_000;
+
+/// `tax` (a fraction, 0.05 = 5%) in parts per million, rounded up so the estimate errs on the
+/// taxed side; the small allowance keeps float noise like 0.05 * 1e6 = 50000.000000000004 exact
+pub fn tax_ppm(tax: f64) -> u64 {
+    if !(tax > 0.0) {
+        return 0;
+    }
+    ((tax * TAX_PPM as f64 - 1e-6).ceil().max(0.0) as u64).min(TAX_PPM)
+}
+
+/// Amount to send so that `amount` still arrives after a `tax` taken on transfer, rounded up.
+/// None for a 100% tax, or if the result doesn't fit in a U256.
+pub fn gross_up_for_tax(amount: U256, tax: f64) -> Option<U256> {
+    let kept = U256::from(TAX_PPM - tax_ppm(tax));
+    if kept.is_zero() {
+        return None;
+    }
+    let numerator = amount.checked_mul(U256::from(TAX_PPM))?;
+    Some(numerator.checked_add(kept - U256::one())? / kept)
+}
+
+/// What arrives of `amount` after a `tax` taken on transfer, rounded down
+pub fn apply_tax(amount: U256, tax: f64) -> U256 {
+    let kept = U256::from(TAX_PPM - tax_ppm(tax));
+    match amount.checked_mul(kept) {
+        Some(product) => product / U256::from(TAX_PPM),
+        None => amount / U256::from(TAX_PPM) * kept,
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct TokenTaxInfoLine {
     #[serde(rename = "token")]
@@ -30,10 +79,11 @@ struct TokenTaxInfoLine {
     simulation_success: bool,
 }
 
-pub fn load_token_tax_map(path: &str) -> TokenTaxMap {
+pub fn load_token_tax_map<P: AsRef<std::path::Path>>(path: P) -> TokenTaxMap {
     use std::fs::File;
     use std::io::{BufRead, BufReader};
-    let file = File::open(path).expect("token_tax_report.jsonl not found");
+    let file = File::open(path.as_ref())
+        .unwrap_or_else(|e| panic!("token tax file {} not found: {}", path.as_ref().display(), e));
     let reader = BufReader::new(file);
     let map = TokenTaxMap::new();
 
@@ -52,4 +102,353 @@ pub fn load_token_tax_map(path: &str) -> TokenTaxMap {
         }
     }
     map
-} 
\ No newline at end of file
+} 
+
+/// Amounts seen during one test buy and sell of a token through a V2 pair
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaxProbeResult {
+    pub buy_expected: U256,  // token the pair sent out for the buy (its Swap amountOut)
+    pub buy_received: U256,  // what reached the buyer
+    pub sell_sent: U256,     // token the buyer sold back
+    pub sell_received: U256, // what reached the pair (its Swap amountIn)
+}
+
+/// Performs a test buy and sell of a token. An error means the round trip didn't complete
+/// (no WBNB pair, reverting sell, ...).
+#[async_trait]
+pub trait TaxProbe: Send + Sync {
+    async fn probe(&self, token: H160) -> anyhow::Result<TaxProbeResult>;
+}
+
+/// Share of `expected` that didn't arrive, in percent like the tax file
+fn shortfall_pct(expected: U256, realized: U256) -> f64 {
+    if expected.is_zero() {
+        return 100.0;
+    }
+    let kept = crate::simulate_swap_path::u256_to_f64_lossy(&realized.min(expected))
+        / crate::simulate_swap_path::u256_to_f64_lossy(&expected);
+    ((1.0 - kept) * 100.0).clamp(0.0, 100.0)
+}
+
+/// Buy and sell tax from one probe. Transfers between wallets aren't probed, so
+/// `transfer_tax` is taken as the lower of the two.
+pub fn infer_token_tax(result: &TaxProbeResult) -> TokenTaxInfo {
+    let buy_tax = shortfall_pct(result.buy_expected, result.buy_received);
+    let sell_tax = shortfall_pct(result.sell_sent, result.sell_received);
+    TokenTaxInfo { buy_tax, sell_tax, transfer_tax: buy_tax.min(sell_tax), simulation_success: true }
+}
+
+/// Probe `token` and infer its taxes. A failed round trip is recorded like a failed entry in
+/// the tax file (`simulation_success: false`, 100% taxes), which keeps the token out of routes.
+pub async fn detect_token_tax(token: H160, probe: &dyn TaxProbe) -> TokenTaxInfo {
+    match probe.probe(token).await {
+        Ok(result) => infer_token_tax(&result),
+        Err(e) => {
+            eprintln!("[TAX] Probe of {:?} failed, treating it as untradeable: {}", token, e);
+            TokenTaxInfo { buy_tax: 100.0, sell_tax: 100.0, transfer_tax: 100.0, simulation_success: false }
+        }
+    }
+}
+
+/// BNB the probe wallet spends on its test buy
+const PROBE_AMOUNT_WEI: u64 = 50_000_000_000_000_000; // 0.05 BNB
+
+/// Address the probe trades from; funded on the fork only
+const PROBE_WALLET: [u8; 20] = [0x7a; 20];
+
+/// Buys a token with BNB through a V2 router on a fork of the latest block, then sells every
+/// token received back, on the same fork as the REVM simulations (`sim_trace::fork_db_at`, AlloyDB
+/// under a CacheDB), but committing each call so the sell sees the buy. Nothing is sent.
+pub struct RevmTaxProbe {
+    provider: Arc<DynProvider>,
+    router: H160,
+    chain_id: u64,
+}
+
+impl RevmTaxProbe {
+    pub fn new(provider: Arc<DynProvider>, router: H160, chain_id: u64) -> Self {
+        Self { provider, router, chain_id }
+    }
+}
+
+#[async_trait]
+impl TaxProbe for RevmTaxProbe {
+    async fn probe(&self, token: H160) -> anyhow::Result<TaxProbeResult> {
+        let (provider, router, chain_id) = (self.provider.as_ref().clone(), self.router, self.chain_id);
+        // AlloyDB blocks on every state read; keep that off the async workers
+        tokio::task::spawn_blocking(move || probe_on_fork(provider, router, chain_id, token)).await?
+    }
+}
+
+fn to_revm_address(address: H160) -> RevmAddress {
+    RevmAddress::from(address.0)
+}
+
+/// 4-byte selector followed by the ABI-encoded arguments
+fn calldata(signature: &str, args: &[Token]) -> Vec<u8> {
+    let mut data = keccak256(signature.as_bytes())[..4].to_vec();
+    data.extend(encode(args));
+    data
+}
+
+/// (amountIn, amountOut) of the first V2 `Swap` log, whichever side of the pair they're on
+fn v2_swap_amounts(logs: &[Log]) -> Option<(U256, U256)> {
+    let topic = keccak256("Swap(address,uint256,uint256,uint256,uint256,address)");
+    let log = logs.iter().find(|log| log.data.topics().first().is_some_and(|t| t.0 == topic))?;
+    let amounts = decode(&vec![ParamType::Uint(256); 4], &log.data.data).ok()?;
+    let amount = |i: usize| amounts[i].clone().into_uint().unwrap_or_default();
+    Some((amount(0).max(amount(1)), amount(2).max(amount(3))))
+}
+
+fn probe_on_fork(provider: DynProvider, router: H160, chain_id: u64, token: H160) -> anyhow::Result<TaxProbeResult> {
+    probe_in_db(fork_db_at(provider, BlockId::latest())?, router, chain_id, token)
+}
+
+/// The probe's round trip on `db`, which must hold `router`, its pair and `token`
+fn probe_in_db<ExtDB: DatabaseRef>(mut db: CacheDB<ExtDB>, router: H160, chain_id: u64, token: H160) -> anyhow::Result<TaxProbeResult> {
+    let wallet = H160(PROBE_WALLET);
+    let amount = U256::from(PROBE_AMOUNT_WEI);
+    db.insert_account_info(
+        to_revm_address(wallet),
+        AccountInfo { balance: RevmU256::from_limbs((amount * 100).0), ..Default::default() },
+    );
+    let mut ctx = Context::mainnet().with_db(db);
+    ctx.cfg.chain_id = chain_id;
+    ctx.cfg.disable_nonce_check = true;
+    let mut evm = ctx.build_mainnet();
+
+    // Commit one call from the probe wallet; returns its output and logs, errors on revert
+    let mut call = |to: H160, data: Vec<u8>, value: U256| -> anyhow::Result<(Vec<u8>, Vec<Log>)> {
+        let tx = TxEnv {
+            caller: to_revm_address(wallet),
+            kind: TxKind::Call(to_revm_address(to)),
+            data: RevmBytes::from(data),
+            value: RevmU256::from_limbs(value.0),
+            gas_limit: 5_000_000,
+            chain_id: Some(chain_id),
+            ..Default::default()
+        };
+        match evm.transact_commit(tx).map_err(|e| anyhow::anyhow!("invalid probe call: {:?}", e))? {
+            ExecutionResult::Success { output, logs, .. } => Ok((output.into_data().to_vec(), logs)),
+            other => anyhow::bail!("probe call to {:?} failed: {:?}", to, other),
+        }
+    };
+    let weth = call(router, calldata("WETH()", &[]), U256::zero())?.0;
+    let wbnb = decode(&[ParamType::Address], &weth)?[0].clone().into_address().unwrap_or_default();
+    let deadline = Token::Uint(U256::MAX);
+    let balance = |call: &mut dyn FnMut(H160, Vec<u8>, U256) -> anyhow::Result<(Vec<u8>, Vec<Log>)>| {
+        let output = call(token, calldata("balanceOf(address)", &[Token::Address(wallet)]), U256::zero())?.0;
+        anyhow::Ok(decode(&[ParamType::Uint(256)], &output)?[0].clone().into_uint().unwrap_or_default())
+    };
+
+    let buy_args = [
+        Token::Uint(U256::zero()),
+        Token::Array(vec![Token::Address(wbnb), Token::Address(token)]),
+        Token::Address(wallet),
+        deadline.clone(),
+    ];
+    let (_, logs) = call(
+        router,
+        calldata("swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)", &buy_args),
+        amount,
+    )?;
+    let (_, buy_expected) = v2_swap_amounts(&logs).ok_or_else(|| anyhow::anyhow!("buy emitted no V2 Swap"))?;
+    let buy_received = balance(&mut call)?;
+    if buy_received.is_zero() {
+        anyhow::bail!("buy delivered no tokens");
+    }
+
+    call(token, calldata("approve(address,uint256)", &[Token::Address(router), Token::Uint(U256::MAX)]), U256::zero())?;
+    let sell_args = [
+        Token::Uint(buy_received),
+        Token::Uint(U256::zero()),
+        Token::Array(vec![Token::Address(token), Token::Address(wbnb)]),
+        Token::Address(wallet),
+        deadline,
+    ];
+    let (_, logs) = call(
+        router,
+        calldata("swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)", &sell_args),
+        U256::zero(),
+    )?;
+    let (sell_received, _) = v2_swap_amounts(&logs).ok_or_else(|| anyhow::anyhow!("sell emitted no V2 Swap"))?;
+    Ok(TaxProbeResult { buy_expected, buy_received, sell_sent: buy_received, sell_received })
+}
+
+/// Fills the tax map lazily: tokens missing from it are probed once in the background and
+/// stay unroutable until the result is in
+pub struct TaxDetector {
+    probe: Arc<dyn TaxProbe>,
+    token_tax_map: Arc<TokenTaxMap>,
+    exempt: HashSet<H160>, // base tokens, never probed and treated as untaxed
+    pending: DashMap<H160, ()>,
+    runtime: tokio::runtime::Handle,
+}
+
+impl TaxDetector {
+    /// Must be called inside a tokio runtime; detection runs on it even when requested from
+    /// rayon threads
+    pub fn new(probe: Arc<dyn TaxProbe>, token_tax_map: Arc<TokenTaxMap>, exempt: HashSet<H160>) -> Self {
+        Self { probe, token_tax_map, exempt, pending: DashMap::new(), runtime: tokio::runtime::Handle::current() }
+    }
+
+    /// Whether `token`'s tax is known. If it isn't, starts its detection (once) and returns false.
+    pub fn is_known_or_detect(self: &Arc<Self>, token: H160) -> bool {
+        if self.exempt.contains(&token) || self.token_tax_map.contains_key(&token) {
+            return true;
+        }
+        if self.pending.insert(token, ()).is_none() {
+            let detector = self.clone();
+            self.runtime.spawn(async move {
+                detector.detect(token).await;
+            });
+        }
+        false
+    }
+
+    /// Probe `token` now and cache the result in the tax map
+    pub async fn detect(&self, token: H160) -> TokenTaxInfo {
+        let info = detect_token_tax(token, self.probe.as_ref()).await;
+        println!(
+            "[TAX] Detected {:?}: buy {:.2}%, sell {:.2}%{}",
+            token, info.buy_tax, info.sell_tax, if info.simulation_success { "" } else { " (probe failed)" }
+        );
+        self.token_tax_map.insert(token, info.clone());
+        self.pending.remove(&token);
+        info
+    }
+}
+
+/// Detector used by live route simulation; none until `init_tax_detector`, in which case
+/// missing tokens are assumed untaxed as before
+static TAX_DETECTOR: OnceCell<Arc<TaxDetector>> = OnceCell::new();
+
+pub fn init_tax_detector(detector: Arc<TaxDetector>) {
+    let _ = TAX_DETECTOR.set(detector);
+}
+
+pub fn tax_detector() -> Option<&'static Arc<TaxDetector>> {
+    TAX_DETECTOR.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::bytecode::Bytecode;
+    use revm::database::EmptyDB;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// ERC-20 that burns 10% of every transfer. Balances live at storage slot = holder address;
+    /// `approve` always succeeds and `transferFrom` skips the allowance check.
+    const FEE_ON_TRANSFER_TOKEN: &str = concat!(
+        "60003560e01c806370a0823114610037578063a9059cbb1461004457806323b872dd14610050578063095ea7b3146100",
+        "7a575b600080fd5b6004355460005260206000f35b6024356004353361005e565b60443560243560043561005e565b80",
+        "54838110610032578390039055600a60098302048154019055505b600160005260206000f3",
+    );
+
+    /// Router that is also the token's pair, priced 1 BNB : 1 token. `WETH()` returns WBNB. The
+    /// buy emits a V2 `Swap` sending `msg.value` tokens and transfers them from its own balance;
+    /// the sell pulls `amountIn` with `transferFrom` and emits a `Swap` whose amountIn is what its
+    /// balance actually grew by, as a pair does. The token address sits in storage slot 0.
+    const PAIR_ROUTER: &str = concat!(
+        "60003560e01c8063ad5c46481461002c578063b6f9de951461004a578063791ac947146100ca575b600080fd5b73bb4c",
+        "db9cbd36b01bd1cbaebf2de08d9173bc095c60005260206000f35b600060005234602052346040526000606052604435",
+        "337fd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d82260806000a37fa9059cbb00000000",
+        "000000000000000000000000000000000000000000000000600052604435600452346024526020600060446000600060",
+        "00545af11561002757005b7f70a082310000000000000000000000000000000000000000000000000000000060005230",
+        "60045260206000602460006000545afa15610027576000517f23b872dd00000000000000000000000000000000000000",
+        "0000000000000000006000523360045230602452600435604452602060006064600060006000545af115610027577f70",
+        "a08231000000000000000000000000000000000000000000000000000000006000523060045260206000602460006000",
+        "545afa1561002757600051038060005260006020526000604052606052606435337fd78ad95fa46c994b6551d0da85fc",
+        "275fe613ce37657fb8d5e3d130840159d82260806000a300",
+    );
+
+    /// A token that takes 10% of every transfer, traded through a pair that prices it 1:1
+    struct FeeOnTransferProbe {
+        fee_bps: u64,
+        probes: AtomicUsize,
+    }
+
+    impl FeeOnTransferProbe {
+        fn transfer(&self, amount: U256) -> U256 {
+            amount - amount * U256::from(self.fee_bps) / U256::from(10_000u64)
+        }
+    }
+
+    #[async_trait]
+    impl TaxProbe for FeeOnTransferProbe {
+        async fn probe(&self, _token: H160) -> anyhow::Result<TaxProbeResult> {
+            self.probes.fetch_add(1, Ordering::SeqCst);
+            let buy_expected = U256::exp10(18);
+            let buy_received = self.transfer(buy_expected);
+            Ok(TaxProbeResult { buy_expected, buy_received, sell_sent: buy_received, sell_received: self.transfer(buy_received) })
+        }
+    }
+
+    struct HoneypotProbe;
+
+    #[async_trait]
+    impl TaxProbe for HoneypotProbe {
+        async fn probe(&self, _token: H160) -> anyhow::Result<TaxProbeResult> {
+            anyhow::bail!("sell reverted")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_detects_ten_percent_fee_on_transfer_token() {
+        let probe = FeeOnTransferProbe { fee_bps: 1_000, probes: AtomicUsize::new(0) };
+        let info = detect_token_tax(H160::from_low_u64_be(1), &probe).await;
+        assert!(info.simulation_success);
+        assert!((info.buy_tax - 10.0).abs() < 1e-9, "buy tax {}", info.buy_tax);
+        assert!((info.sell_tax - 10.0).abs() < 1e-9, "sell tax {}", info.sell_tax);
+
+        let honeypot = detect_token_tax(H160::from_low_u64_be(2), &HoneypotProbe).await;
+        assert!(!honeypot.simulation_success);
+        assert_eq!(honeypot.sell_tax, 100.0);
+    }
+
+    #[test]
+    fn test_revm_probe_measures_fee_on_transfer_token() {
+        let (router, token) = (H160::from_low_u64_be(0x1111), H160::from_low_u64_be(0x2222));
+        let contract = |code: &str| AccountInfo { code: Some(Bytecode::new_raw(hex::decode(code).unwrap().into())), ..Default::default() };
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(to_revm_address(router), contract(PAIR_ROUTER));
+        db.insert_account_info(to_revm_address(token), contract(FEE_ON_TRANSFER_TOKEN));
+        db.insert_account_storage(to_revm_address(router), RevmU256::ZERO, RevmU256::from_be_slice(token.as_bytes())).unwrap();
+        let liquidity = RevmU256::from_limbs(U256::exp10(24).0);
+        db.insert_account_storage(to_revm_address(token), RevmU256::from_be_slice(router.as_bytes()), liquidity).unwrap();
+
+        let result = probe_in_db(db, router, 56, token).unwrap();
+        // The pair sent the whole buy, 10% was burned on the way to the wallet and again on the way back
+        let bought = U256::from(PROBE_AMOUNT_WEI);
+        assert_eq!(result.buy_expected, bought);
+        assert_eq!(result.buy_received, bought * 9 / 10);
+        assert_eq!(result.sell_sent, result.buy_received);
+        assert_eq!(result.sell_received, result.sell_sent * 9 / 10);
+        let info = infer_token_tax(&result);
+        assert!((info.buy_tax - 10.0).abs() < 1e-9, "buy tax {}", info.buy_tax);
+        assert!((info.sell_tax - 10.0).abs() < 1e-9, "sell tax {}", info.sell_tax);
+    }
+
+    #[tokio::test]
+    async fn test_missing_token_is_detected_once_and_cached() {
+        let (base, taxed) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let probe = Arc::new(FeeOnTransferProbe { fee_bps: 1_000, probes: AtomicUsize::new(0) });
+        let map = Arc::new(TokenTaxMap::new());
+        let detector = Arc::new(TaxDetector::new(probe.clone(), map.clone(), HashSet::from([base])));
+
+        assert!(detector.is_known_or_detect(base));
+        // Unknown until the background probe lands; asking again doesn't probe twice
+        assert!(!detector.is_known_or_detect(taxed));
+        assert!(!detector.is_known_or_detect(taxed));
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !map.contains_key(&taxed) {
+            assert!(std::time::Instant::now() < deadline, "detection never finished");
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        assert!(detector.is_known_or_detect(taxed));
+        assert!((map.get(&taxed).unwrap().buy_tax - 10.0).abs() < 1e-9);
+        assert_eq!(probe.probes.load(Ordering::SeqCst), 1);
+        assert!(!map.contains_key(&base));
+    }
+}
@@ -0,0 +1,186 @@
+use ethers::types::{H160, H256, I256, TransactionReceipt, U256};
+use ethers::utils::keccak256;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+pub const TRADE_LEDGER_FILE: &str = "trades.csv"; // inside the log directory, see `log_path`
+const LEDGER_HEADER: &str = "timestamp,tx_hash,block,base_token,input,realized_output,gas_used,net_profit,simulated_profit,status";
+
+/// One executed (mined) arbitrage trade
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeRecord {
+    pub timestamp: u64,
+    pub tx_hash: H256,
+    pub block: u64,
+    pub base_token: H160,
+    pub input: U256,
+    pub realized_output: U256,
+    pub gas_used: U256,
+    pub net_profit: Option<I256>, // realized base token delta, in wei; None if the receipt doesn't show it
+    pub simulated_profit: U256,
+    pub status: String,
+}
+
+impl TradeRecord {
+    /// Build a ledger row from a mined receipt, using the real on-chain base token transfers
+    pub fn from_receipt(
+        receipt: &TransactionReceipt,
+        base_token: H160,
+        executor: H160,
+        net_profit: Option<I256>,
+        simulated_profit: U256,
+    ) -> Self {
+        let (input, realized_output) = base_token_flows(receipt, base_token, executor);
+        let status = if receipt.status == Some(1u64.into()) { "success" } else { "reverted" };
+        Self {
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            tx_hash: receipt.transaction_hash,
+            block: receipt.block_number.map(|b| b.as_u64()).unwrap_or_default(),
+            base_token,
+            input,
+            realized_output,
+            gas_used: receipt.gas_used.unwrap_or_default(),
+            net_profit,
+            simulated_profit,
+            status: status.to_string(),
+        }
+    }
+
+    fn to_csv_row(&self) -> String {
+        format!(
+            "{},{:?},{},{:?},{},{},{},{},{},{}",
+            self.timestamp,
+            self.tx_hash,
+            self.block,
+            self.base_token,
+            self.input,
+            self.realized_output,
+            self.gas_used,
+            self.net_profit.map(|p| p.to_string()).unwrap_or_default(), // empty = unknown
+            self.simulated_profit,
+            self.status
+        )
+    }
+
+    fn from_csv_row(line: &str) -> Option<Self> {
+        let cols: Vec<&str> = line.trim().split(',').collect();
+        if cols.len() != 10 {
+            return None;
+        }
+        Some(Self {
+            timestamp: cols[0].parse().ok()?,
+            tx_hash: cols[1].parse().ok()?,
+            block: cols[2].parse().ok()?,
+            base_token: cols[3].parse().ok()?,
+            input: U256::from_dec_str(cols[4]).ok()?,
+            realized_output: U256::from_dec_str(cols[5]).ok()?,
+            gas_used: U256::from_dec_str(cols[6]).ok()?,
+            net_profit: match cols[7] {
+                "" => None,
+                profit => Some(I256::from_dec_str(profit).ok()?),
+            },
+            simulated_profit: U256::from_dec_str(cols[8]).ok()?,
+            status: cols[9].to_string(),
+        })
+    }
+}
+
+/// Sum base token Transfer logs out of and into the executor: (sent, received)
+pub fn base_token_flows(receipt: &TransactionReceipt, base_token: H160, executor: H160) -> (U256, U256) {
+    let transfer_topic = H256::from(keccak256("Transfer(address,address,uint256)"));
+    let mut sent = U256::zero();
+    let mut received = U256::zero();
+    for log in &receipt.logs {
+        if log.address != base_token || log.topics.len() != 3 || log.topics[0] != transfer_topic {
+            continue;
+        }
+        let from = H160::from(log.topics[1]);
+        let to = H160::from(log.topics[2]);
+        let amount = U256::from_big_endian(&log.data);
+        if from == executor {
+            sent = sent.saturating_add(amount);
+        }
+        if to == executor {
+            received = received.saturating_add(amount);
+        }
+    }
+    (sent, received)
+}
+
+/// Append a trade to the CSV ledger, writing the header for a new file
+pub fn append_trade(path: &Path, record: &TradeRecord) -> std::io::Result<()> {
+    let is_new = std::fs::metadata(path).map(|m| m.len() == 0).unwrap_or(true);
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if is_new {
+        writeln!(file, "{}", LEDGER_HEADER)?;
+    }
+    writeln!(file, "{}", record.to_csv_row())
+}
+
+/// Read all trades back from the ledger (malformed rows are skipped)
+pub fn read_trades(path: &Path) -> std::io::Result<Vec<TradeRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(reader
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.ok())
+        .filter_map(|line| TradeRecord::from_csv_row(&line))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{Bytes, Log};
+
+    fn transfer_log(token: H160, from: H160, to: H160, amount: u64) -> Log {
+        let mut data = [0u8; 32];
+        U256::from(amount).to_big_endian(&mut data);
+        Log {
+            address: token,
+            topics: vec![
+                H256::from(keccak256("Transfer(address,address,uint256)")),
+                H256::from(from),
+                H256::from(to),
+            ],
+            data: Bytes::from(data.to_vec()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_from_receipt_and_roundtrip() {
+        let base = H160::repeat_byte(0xaa);
+        let executor = H160::repeat_byte(0xee);
+        let pool = H160::repeat_byte(0x01);
+        let receipt = TransactionReceipt {
+            transaction_hash: H256::repeat_byte(0x42),
+            block_number: Some(123u64.into()),
+            gas_used: Some(210_000u64.into()),
+            status: Some(1u64.into()),
+            logs: vec![
+                transfer_log(base, executor, pool, 1_000),
+                transfer_log(H160::repeat_byte(0xbb), pool, executor, 5),
+                transfer_log(base, pool, executor, 1_030),
+            ],
+            ..Default::default()
+        };
+
+        let record = TradeRecord::from_receipt(&receipt, base, executor, Some(I256::from(30)), U256::from(35u64));
+        assert_eq!(record.input, U256::from(1_000u64));
+        assert_eq!(record.realized_output, U256::from(1_030u64));
+        assert_eq!(record.net_profit, Some(I256::from(30)));
+        assert_eq!(record.status, "success");
+
+        let path = std::env::temp_dir().join(format!("trades_test_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let unknown = TradeRecord { net_profit: None, ..record.clone() };
+        append_trade(&path, &record).unwrap();
+        append_trade(&path, &unknown).unwrap();
+        let trades = read_trades(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(trades, vec![record, unknown]);
+    }
+}
@@ -0,0 +1,130 @@
+use ethers::abi::{decode, ParamType, Token};
+use ethers::types::{H160, H256, U256};
+use once_cell::sync::Lazy;
+
+/// Uniswap V2 `Swap(address indexed sender, uint amount0In, uint amount1In, uint amount0Out, uint amount1Out, address indexed to)`,
+/// shared by PancakeSwap V2 and the other V2 forks
+pub static V2_SWAP_TOPIC: Lazy<H256> = Lazy::new(|| {
+    H256::from(ethers::utils::keccak256(
+        b"Swap(address,uint256,uint256,uint256,uint256,address)",
+    ))
+});
+
+const V2_SWAP_DATA_LEN: usize = 128;
+
+/// Decoded V2 Swap event data: what the swapper paid in and took out, per token
+#[derive(Debug, Clone, PartialEq)]
+pub struct V2SwapData {
+    pub amount0_in: U256,
+    pub amount1_in: U256,
+    pub amount0_out: U256,
+    pub amount1_out: U256,
+}
+
+impl V2SwapData {
+    /// Token that left the pool on net (the one the swapper bought) and how much of it.
+    /// Unlike reserve deltas this is exact even when the same transaction also skims or
+    /// donates to the pool. None if neither or both tokens left on net.
+    pub fn token_out(&self, token0: H160, token1: H160) -> Option<(H160, U256)> {
+        match (self.amount0_out > self.amount0_in, self.amount1_out > self.amount1_in) {
+            (true, false) => Some((token0, self.amount0_out - self.amount0_in)),
+            (false, true) => Some((token1, self.amount1_out - self.amount1_in)),
+            _ => None,
+        }
+    }
+}
+
+/// Decode the 128-byte data of a V2 Swap log (the sender and recipient are topics)
+pub fn decode_v2_swap(data: &[u8]) -> anyhow::Result<V2SwapData> {
+    if data.len() != V2_SWAP_DATA_LEN {
+        anyhow::bail!("invalid V2 Swap log size: {} (expected {})", data.len(), V2_SWAP_DATA_LEN);
+    }
+    let tokens = decode(&vec![ParamType::Uint(256); 4], data)?;
+    let uint = |t: &Token| t.clone().into_uint().ok_or_else(|| anyhow::anyhow!("expected uint, got {:?}", t));
+    Ok(V2SwapData {
+        amount0_in: uint(&tokens[0])?,
+        amount1_in: uint(&tokens[1])?,
+        amount0_out: uint(&tokens[2])?,
+        amount1_out: uint(&tokens[3])?,
+    })
+}
+
+/// What a V2 Sync reports relative to the cached reserves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncChange {
+    /// One reserve fell and the other rose: the token whose reserve fell was bought
+    Swap { token0_out: bool, amount_out: U256 },
+    /// Both reserves moved the same way (mint/burn), or only one moved (skim/donation)
+    Liquidity,
+}
+
+/// Classify a Sync against the cached reserves; only opposite moves are swaps
+pub fn classify_sync(old_reserve0: U256, old_reserve1: U256, new_reserve0: U256, new_reserve1: U256) -> SyncChange {
+    if new_reserve0 < old_reserve0 && new_reserve1 > old_reserve1 {
+        SyncChange::Swap { token0_out: true, amount_out: old_reserve0 - new_reserve0 }
+    } else if new_reserve1 < old_reserve1 && new_reserve0 > old_reserve0 {
+        SyncChange::Swap { token0_out: false, amount_out: old_reserve1 - new_reserve1 }
+    } else {
+        SyncChange::Liquidity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::encode;
+
+    fn swap_data(amounts: [u64; 4]) -> Vec<u8> {
+        encode(&amounts.map(|a| Token::Uint(U256::from(a))))
+    }
+
+    #[test]
+    fn test_decode_and_direction() {
+        let (token0, token1) = (H160::from_low_u64_be(1), H160::from_low_u64_be(2));
+        let swap = decode_v2_swap(&swap_data([0, 2_000, 990, 0])).unwrap();
+        assert_eq!(swap.amount1_in, U256::from(2_000u64));
+        assert_eq!(swap.amount0_out, U256::from(990u64));
+        assert_eq!(swap.token_out(token0, token1), Some((token0, U256::from(990u64))));
+
+        let swap = decode_v2_swap(&swap_data([1_000, 0, 0, 1_960])).unwrap();
+        assert_eq!(swap.token_out(token0, token1), Some((token1, U256::from(1_960u64))));
+
+        // Flash swap repaid partly in the borrowed token: only the net outflow counts
+        let swap = decode_v2_swap(&swap_data([400, 1_000, 1_000, 0])).unwrap();
+        assert_eq!(swap.token_out(token0, token1), Some((token0, U256::from(600u64))));
+
+        // Flash loan repaid in full, and a swap taking both tokens out: no direction
+        assert_eq!(decode_v2_swap(&swap_data([1_003, 0, 1_000, 0])).unwrap().token_out(token0, token1), None);
+        assert_eq!(decode_v2_swap(&swap_data([0, 0, 5, 5])).unwrap().token_out(token0, token1), None);
+    }
+
+    #[test]
+    fn test_decode_rejects_wrong_size() {
+        let data = swap_data([1, 2, 3, 4]);
+        assert!(decode_v2_swap(&data[..96]).is_err());
+        assert!(decode_v2_swap(&[&data[..], &[0u8; 32]].concat()).is_err());
+        assert!(decode_v2_swap(&[]).is_err());
+    }
+
+    #[test]
+    fn test_classify_sync() {
+        let r = |n: u64| U256::from(n);
+        // Swap: token0 bought, token1 paid in
+        assert_eq!(
+            classify_sync(r(1_000), r(2_000), r(900), r(2_230)),
+            SyncChange::Swap { token0_out: true, amount_out: r(100) }
+        );
+        // Swap the other way
+        assert_eq!(
+            classify_sync(r(1_000), r(2_000), r(1_060), r(1_890)),
+            SyncChange::Swap { token0_out: false, amount_out: r(110) }
+        );
+        // Add liquidity: both reserves up
+        assert_eq!(classify_sync(r(1_000), r(2_000), r(1_500), r(3_000)), SyncChange::Liquidity);
+        // Remove liquidity: both reserves down
+        assert_eq!(classify_sync(r(1_000), r(2_000), r(500), r(1_000)), SyncChange::Liquidity);
+        // Donation/skim on one side, or a no-op Sync
+        assert_eq!(classify_sync(r(1_000), r(2_000), r(1_000), r(2_500)), SyncChange::Liquidity);
+        assert_eq!(classify_sync(r(1_000), r(2_000), r(1_000), r(2_000)), SyncChange::Liquidity);
+    }
+}
@@ -1,5 +1,6 @@
-use ethers::types::U256;
+use ethers::types::{U256, U512};
 use primitive_types::U256 as PrimitiveU256;
+use serde::{Deserialize, Serialize};
 
 pub const Q96: u128 = 2u128.pow(96);
 
@@ -66,14 +67,15 @@ pub fn simulate_v3_swap(
     if zero_for_one {
         // Token0 -> Token1: price DECREASES (token0 becomes cheaper)
         // Formula: sqrtP_new = (L * Q96 * sqrtP_cur) / (L * Q96 + netIn_0 * sqrtP_cur)
-        let numerator = liquidity.checked_mul(U256::from(Q96))?.checked_mul(sqrt_price_x96)?;
-        let denominator = liquidity.checked_mul(U256::from(Q96))?.checked_add(amount_in_with_fee.checked_mul(sqrt_price_x96)?)?;
+        // L * Q96 * sqrtP_cur needs 512 bits at realistic liquidity
+        let liquidity_x96 = liquidity.checked_mul(U256::from(Q96))?;
+        let denominator = liquidity_x96.checked_add(amount_in_with_fee.checked_mul(sqrt_price_x96)?)?;
         
         if denominator <= U256::zero() {
             return None; // Avoid division by zero
         }
         
-        let sqrt_price_new = numerator.checked_div(denominator)?;
+        let sqrt_price_new = mul_div_full(liquidity_x96, sqrt_price_x96, denominator, false)?;
         
         // Amount1 out = L * (sqrtP_cur - sqrtP_new) / Q96
         let delta_sqrt = sqrt_price_x96.checked_sub(sqrt_price_new)?;
@@ -97,7 +99,7 @@ pub fn simulate_v3_swap(
         
         // Compute output via fraction: (L * delta_sqrt * Q96) / (sqrt_price_new * sqrt_price_current)
         let numerator = liquidity.checked_mul(delta_sqrt)?.checked_mul(U256::from(Q96))?;
-        let denominator = sqrt_price_new.checked_mul(sqrt_price_x96)?.checked_div(U256::from(Q96))?;
+        let denominator = sqrt_price_new.checked_mul(sqrt_price_x96)?;
         
         if denominator <= U256::zero() {
             return None; // Avoid division by zero
@@ -114,6 +116,230 @@ pub fn simulate_v3_swap(
     }
 }
 
+/// Tick range of a V3 pool (`TickMath.MIN_TICK` / `MAX_TICK`)
+pub const MIN_TICK: i32 = -887_272;
+pub const MAX_TICK: i32 = 887_272;
+/// `TickMath.MIN_SQRT_RATIO`: sqrt price at `MIN_TICK`
+pub const MIN_SQRT_RATIO: U256 = U256([4_295_128_739, 0, 0, 0]);
+/// `TickMath.MAX_SQRT_RATIO`: sqrt price at `MAX_TICK`
+pub const MAX_SQRT_RATIO: U256 = U256([0x5d951d5263988d26, 0xefd1fc6a50648849, 0xfffd8963, 0]);
+
+/// An initialized tick of a V3 pool. Crossing it with the price going up adds `liquidity_net`
+/// to the active liquidity; going down subtracts it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickInfo {
+    pub index: i32,
+    pub liquidity_net: i128,
+}
+
+/// `TickMath.getSqrtRatioAtTick`: sqrt(1.0001^tick) as Q64.96, rounded up, bit-exact with the pool
+pub fn sqrt_price_at_tick(tick: i32) -> Option<U256> {
+    const FACTORS: [(u32, u128); 19] = [
+        (0x2, 0xfff97272373d413259a46990580e213a),
+        (0x4, 0xfff2e50f5f656932ef12357cf3c7fdcc),
+        (0x8, 0xffe5caca7e10e4e61c3624eaa0941cd0),
+        (0x10, 0xffcb9843d60f6159c9db58835c926644),
+        (0x20, 0xff973b41fa98c081472e6896dfb254c0),
+        (0x40, 0xff2ea16466c96a3843ec78b326b52861),
+        (0x80, 0xfe5dee046a99a2a811c461f1969c3053),
+        (0x100, 0xfcbe86c7900a88aedcffc83b479aa3a4),
+        (0x200, 0xf987a7253ac413176f2b074cf7815e54),
+        (0x400, 0xf3392b0822b70005940c7a398e4b70f3),
+        (0x800, 0xe7159475a2c29b7443b29c7fa6e889d9),
+        (0x1000, 0xd097f3bdfd2022b8845ad8f792aa5825),
+        (0x2000, 0xa9f746462d870fdf8a65dc1f90e061e5),
+        (0x4000, 0x70d869a156d2a1b890bb3df62baf32f7),
+        (0x8000, 0x31be135f97d08fd981231505542fcfa6),
+        (0x10000, 0x9aa508b5b7a84e1c677de54f3e99bc9),
+        (0x20000, 0x5d6af8dedb81196699c329225ee604),
+        (0x40000, 0x2216e584f5fa1ea926041bedfe98),
+        (0x80000, 0x48a170391f7dc42444e8fa2),
+    ];
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return None;
+    }
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from(0xfffcb933bd6fad37aa2d162d1a594001u128)
+    } else {
+        U256::one() << 128
+    };
+    for (bit, factor) in FACTORS {
+        if abs_tick & bit != 0 {
+            ratio = (ratio * U256::from(factor)) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+    let round_up = if (ratio & U256::from(u32::MAX)).is_zero() { U256::zero() } else { U256::one() };
+    Some((ratio >> 32) + round_up)
+}
+
+/// a * b / denominator with a 512-bit intermediate (`FullMath.mulDiv` / `mulDivRoundingUp`)
+fn mul_div_full(a: U256, b: U256, denominator: U256, round_up: bool) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    let (quotient, remainder) = a.full_mul(b).div_mod(U512::from(denominator));
+    let quotient = if round_up && !remainder.is_zero() { quotient + U512::one() } else { quotient };
+    U256::try_from(quotient).ok()
+}
+
+fn div_rounding_up(a: U256, b: U256) -> Option<U256> {
+    let (quotient, remainder) = (a.checked_div(b)?, a % b);
+    Some(if remainder.is_zero() { quotient } else { quotient + U256::one() })
+}
+
+/// `SqrtPriceMath.getAmount0Delta`: token0 between two sqrt prices at liquidity `l`
+fn amount0_delta(a: U256, b: U256, l: U256, round_up: bool) -> Option<U256> {
+    let (lower, upper) = if a < b { (a, b) } else { (b, a) };
+    if lower.is_zero() {
+        return None;
+    }
+    let numerator = l << 96;
+    if round_up {
+        div_rounding_up(mul_div_full(numerator, upper - lower, upper, true)?, lower)
+    } else {
+        Some(mul_div_full(numerator, upper - lower, upper, false)? / lower)
+    }
+}
+
+/// `SqrtPriceMath.getAmount1Delta`: token1 between two sqrt prices at liquidity `l`
+fn amount1_delta(a: U256, b: U256, l: U256, round_up: bool) -> Option<U256> {
+    let (lower, upper) = if a < b { (a, b) } else { (b, a) };
+    mul_div_full(l, upper - lower, U256::from(Q96), round_up)
+}
+
+/// `SqrtPriceMath.getNextSqrtPriceFromInput`, rounded the way the pool rounds
+fn next_sqrt_price_from_input(sqrt_price_x96: U256, l: U256, amount_in: U256, zero_for_one: bool) -> Option<U256> {
+    if l.is_zero() || sqrt_price_x96.is_zero() {
+        return None;
+    }
+    if zero_for_one {
+        if amount_in.is_zero() {
+            return Some(sqrt_price_x96);
+        }
+        let numerator = l << 96;
+        if let Some(product) = amount_in.checked_mul(sqrt_price_x96) {
+            if let Some(denominator) = numerator.checked_add(product) {
+                return mul_div_full(numerator, sqrt_price_x96, denominator, true);
+            }
+        }
+        div_rounding_up(numerator, (numerator / sqrt_price_x96).checked_add(amount_in)?)
+    } else {
+        let quotient = if amount_in <= (U256::one() << 160) - 1 {
+            (amount_in << 96) / l
+        } else {
+            mul_div_full(amount_in, U256::from(Q96), l, false)?
+        };
+        sqrt_price_x96.checked_add(quotient)
+    }
+}
+
+/// One `SwapMath.computeSwapStep` for an exact input: (next sqrt price, amount in, amount out, fee)
+fn swap_step(sqrt_price_x96: U256, target: U256, l: U256, remaining: U256, fee: u32) -> Option<(U256, U256, U256, U256)> {
+    let zero_for_one = sqrt_price_x96 >= target;
+    let fee_complement = U256::from(1_000_000u32.checked_sub(fee)?);
+    let remaining_less_fee = mul_div_full(remaining, fee_complement, U256::from(1_000_000u32), false)?;
+    let to_target = if zero_for_one {
+        amount0_delta(target, sqrt_price_x96, l, true)?
+    } else {
+        amount1_delta(sqrt_price_x96, target, l, true)?
+    };
+    let next = if remaining_less_fee >= to_target {
+        target
+    } else {
+        next_sqrt_price_from_input(sqrt_price_x96, l, remaining_less_fee, zero_for_one)?
+    };
+    let reached = next == target;
+    let amount_in = match (reached, zero_for_one) {
+        (true, _) => to_target,
+        (false, true) => amount0_delta(next, sqrt_price_x96, l, true)?,
+        (false, false) => amount1_delta(sqrt_price_x96, next, l, true)?,
+    };
+    let amount_out = if zero_for_one {
+        amount1_delta(next, sqrt_price_x96, l, false)?
+    } else {
+        amount0_delta(sqrt_price_x96, next, l, false)?
+    };
+    // Whatever isn't swapped inside the range is kept as fee, as the pool does
+    let fee_amount = if reached {
+        mul_div_full(amount_in, U256::from(fee), fee_complement, true)?
+    } else {
+        remaining.checked_sub(amount_in)?
+    };
+    Some((next, amount_in, amount_out, fee_amount))
+}
+
+/// Exact-input V3 swap that crosses initialized ticks the way the pool does: swap to the next
+/// tick in the swap direction, apply its `liquidity_net`, and continue until the input is used.
+/// `tick` is the pool's current tick and `ticks` its initialized ticks in ascending order, which
+/// must hold every initialized tick in `range` (inclusive, e.g. the loaded bitmap words). Past
+/// `range` the liquidity is unknown, so a walk that would leave it, or starts outside it, is None;
+/// so is a pool that runs out of liquidity before the whole input is swapped.
+/// Without tick data use `simulate_v3_swap`, which keeps the current range throughout.
+pub fn simulate_v3_swap_ticks(
+    amount_in: U256,
+    sqrt_price_x96: U256,
+    liquidity: U256,
+    tick: i32,
+    ticks: &[TickInfo],
+    range: (i32, i32),
+    fee: u32,
+    zero_for_one: bool,
+) -> Option<U256> {
+    if sqrt_price_x96.is_zero() || tick < range.0 || tick > range.1 {
+        return None;
+    }
+    // Uniswap's `nextInitializedTick`: at or below the current tick going down, above it going up
+    let split = ticks.partition_point(|t| t.index <= tick);
+    let initialized = |crossed: usize| {
+        let next = if zero_for_one { split.checked_sub(crossed + 1).map(|i| &ticks[i]) } else { ticks.get(split + crossed) };
+        next.filter(|t| t.index >= range.0 && t.index <= range.1)
+    };
+    // No initialized tick is left before the edge of `range`: the walk may go that far and no further
+    let price_limit = if zero_for_one {
+        sqrt_price_at_tick(range.0.max(MIN_TICK))?.max(MIN_SQRT_RATIO + U256::one())
+    } else {
+        sqrt_price_at_tick(range.1.min(MAX_TICK))?.min(MAX_SQRT_RATIO - U256::one())
+    };
+
+    let (mut sqrt_price, mut active, mut remaining, mut amount_out) = (sqrt_price_x96, liquidity, amount_in, U256::zero());
+    let mut crossed = 0;
+    while !remaining.is_zero() {
+        let next_tick = initialized(crossed);
+        let target = match next_tick {
+            Some(next) => sqrt_price_at_tick(next.index)?,
+            None => price_limit,
+        };
+        if active.is_zero() {
+            // A gap between positions: the price jumps to the next tick for free
+            next_tick?;
+            sqrt_price = target;
+        } else {
+            let (next, step_in, step_out, step_fee) = swap_step(sqrt_price, target, active, remaining, fee)?;
+            remaining = remaining.checked_sub(step_in.checked_add(step_fee)?)?;
+            amount_out = amount_out.checked_add(step_out)?;
+            sqrt_price = next;
+        }
+        match next_tick {
+            Some(next) if sqrt_price == target => {
+                let net = if zero_for_one { next.liquidity_net.checked_neg()? } else { next.liquidity_net };
+                active = if net < 0 {
+                    active.checked_sub(U256::from(net.unsigned_abs()))?
+                } else {
+                    active.checked_add(U256::from(net as u128))?
+                };
+                crossed += 1;
+            }
+            _ if !remaining.is_zero() => return None, // hit the price limit or the edge of the loaded ticks
+            _ => {}
+        }
+    }
+    Some(amount_out)
+}
+
 /// Calculate V3 buy amount needed for a given output (reverse calculation)
 pub fn calculate_v3_buy_amount(
     amount_out: U256,
@@ -424,4 +650,78 @@ pub fn test_v3_math() {
     }
     
     println!("\n✅ V3 math test completed!");
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Price 1 (tick 0) with two positions: [-600, 600] holding 1e21 and [-60, 120] holding 2e21
+    fn two_positions() -> (U256, Vec<TickInfo>) {
+        let l = 10i128.pow(21);
+        let ticks = vec![
+            TickInfo { index: -600, liquidity_net: l },
+            TickInfo { index: -60, liquidity_net: 2 * l },
+            TickInfo { index: 120, liquidity_net: -2 * l },
+            TickInfo { index: 600, liquidity_net: -l },
+        ];
+        (U256::from(3 * l as u128), ticks)
+    }
+
+    #[test]
+    fn test_sqrt_price_at_tick_matches_tick_math() {
+        assert_eq!(sqrt_price_at_tick(0), Some(U256::from(Q96)));
+        assert_eq!(sqrt_price_at_tick(MIN_TICK), Some(MIN_SQRT_RATIO));
+        assert_eq!(sqrt_price_at_tick(MAX_TICK), Some(MAX_SQRT_RATIO));
+        assert_eq!(sqrt_price_at_tick(120), U256::from_dec_str("79704936542881920863903188246").ok());
+        assert_eq!(sqrt_price_at_tick(-60), U256::from_dec_str("78990846045029531151608375686").ok());
+        assert_eq!(sqrt_price_at_tick(MAX_TICK + 1), None);
+    }
+
+    #[test]
+    fn test_tick_walk_matches_uniswap_quote() {
+        // Expected amounts are the pool's own exact-input math (TickMath, SqrtPriceMath and
+        // SwapMath from v3-core) run over the same positions at the 0.3% tier
+        let (liquidity, ticks) = two_positions();
+        let price = U256::from(Q96);
+        let walk = |amount: u128, zero_for_one, ticks: &[TickInfo]| {
+            simulate_v3_swap_ticks(U256::from(amount), price, liquidity, 0, ticks, (MIN_TICK, MAX_TICK), 3000, zero_for_one)
+        };
+        let single = |amount: u128, zero_for_one| simulate_v3_swap(U256::from(amount), price, liquidity, 3000, zero_for_one);
+        let quote = |amount: &str| U256::from_dec_str(amount).ok();
+
+        // Inside the current range the walk, the single-range path and the pool agree
+        for zero_for_one in [true, false] {
+            assert_eq!(walk(10u128.pow(18), zero_for_one, &ticks), quote("996668773744192346"));
+            assert_eq!(single(10u128.pow(18), zero_for_one), quote("996668773744192346"));
+        }
+
+        // Large swaps leave the [-60, 120] position behind; the single range overstates them
+        assert_eq!(walk(3 * 10u128.pow(19), false, &ticks), quote("29524118714128423755"));
+        assert_eq!(single(3 * 10u128.pow(19), false), quote("29614741031911838965"));
+        assert_eq!(walk(2 * 10u128.pow(19), true, &ticks), quote("19730587540768712914"));
+        assert_eq!(single(2 * 10u128.pow(19), true), quote("19808340563057544189"));
+
+        // No tick data: the current range carries on, like the fast path
+        assert_eq!(walk(3 * 10u128.pow(19), false, &[]), single(3 * 10u128.pow(19), false));
+        // Past tick 600 nothing is left to swap against
+        assert_eq!(walk(10u128.pow(20), false, &ticks), None);
+    }
+
+    #[test]
+    fn test_tick_walk_stops_at_the_loaded_range() {
+        let (liquidity, ticks) = two_positions();
+        let price = U256::from(Q96);
+        let walk = |amount: u128, zero_for_one, range| {
+            simulate_v3_swap_ticks(U256::from(amount), price, liquidity, 0, &ticks, range, 3000, zero_for_one)
+        };
+        // Ticks loaded for [-1000, 1000]: every crossing is known, as with the full range
+        assert_eq!(walk(3 * 10u128.pow(19), false, (-1000, 1000)), walk(3 * 10u128.pow(19), false, (MIN_TICK, MAX_TICK)));
+        // Only [-100, 300] loaded: a swap that stays inside is still quoted...
+        assert_eq!(walk(10u128.pow(18), false, (-100, 300)), U256::from_dec_str("996668773744192346").ok());
+        // ...but one that would cross tick 300 (or -100) can't be
+        assert_eq!(walk(3 * 10u128.pow(19), false, (-100, 300)), None);
+        assert_eq!(walk(2 * 10u128.pow(19), true, (-100, 300)), None);
+        // A current tick outside the loaded words has no known liquidity map at all
+        assert_eq!(walk(10u128.pow(18), false, (100, 1000)), None);
+    }
+}
@@ -0,0 +1,328 @@
+use dashmap::DashMap;
+use ethers::abi::{decode, ParamType, Token};
+use ethers::types::{H160, H256, I256, U256};
+use once_cell::sync::Lazy;
+
+/// Uniswap V3 `Swap(address,address,int256,int256,uint160,uint128,int24)`
+pub static UNISWAP_V3_SWAP_TOPIC: Lazy<H256> = Lazy::new(|| {
+    H256::from(ethers::utils::keccak256(
+        b"Swap(address,address,int256,int256,uint160,uint128,int24)",
+    ))
+});
+
+/// PancakeSwap V3 `Swap(...)`: the Uniswap V3 layout plus two protocol fee fields
+pub static PANCAKE_V3_SWAP_TOPIC: Lazy<H256> = Lazy::new(|| {
+    H256::from(ethers::utils::keccak256(
+        b"Swap(address,address,int256,int256,uint160,uint128,int24,uint128,uint128)",
+    ))
+});
+
+/// Range of a Solidity `int24` (ticks, tick spacings)
+pub const INT24_MIN: i32 = -8_388_608;
+pub const INT24_MAX: i32 = 8_388_607;
+
+/// Something holding one 32-byte ABI word: a decoded `Token::Int`/`Token::Uint`, the raw
+/// word as a (two's complement) `U256`, or the 32 bytes themselves
+pub trait AbiWord {
+    fn abi_word(&self) -> Option<U256>;
+}
+
+impl AbiWord for Token {
+    fn abi_word(&self) -> Option<U256> {
+        match self {
+            Token::Int(raw) | Token::Uint(raw) => Some(*raw),
+            _ => None,
+        }
+    }
+}
+
+impl AbiWord for U256 {
+    fn abi_word(&self) -> Option<U256> {
+        Some(*self)
+    }
+}
+
+impl AbiWord for [u8] {
+    fn abi_word(&self) -> Option<U256> {
+        (self.len() == 32).then(|| U256::from_big_endian(self))
+    }
+}
+
+/// Decode a signed `int24` (a V3 tick) from its ABI word. The word must be the sign-extended
+/// two's complement form; anything outside the int24 range is an error, not a wrapped value.
+pub fn decode_int24<W: AbiWord + ?Sized>(word: &W) -> anyhow::Result<i32> {
+    let raw = word.abi_word().ok_or_else(|| anyhow::anyhow!("not a 32-byte ABI word"))?;
+    let value = I256::from_raw(raw);
+    if value < I256::from(INT24_MIN) || value > I256::from(INT24_MAX) {
+        anyhow::bail!("value out of int24 range: {}", value);
+    }
+    Ok(value.as_i32())
+}
+
+const UNISWAP_V3_SWAP_DATA_LEN: usize = 160;
+const PANCAKE_V3_SWAP_DATA_LEN: usize = 224;
+
+/// Decoded V3 Swap event data. Amounts are pool deltas: positive = paid into the pool,
+/// negative = paid out. Protocol fees are zero for the Uniswap layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct V3SwapData {
+    pub amount0: I256,
+    pub amount1: I256,
+    pub sqrt_price_x96: U256,
+    pub liquidity: U256,
+    pub tick: i32,
+    pub protocol_fees_token0: U256,
+    pub protocol_fees_token1: U256,
+}
+
+impl V3SwapData {
+    /// Token paid out of the pool (the one the swapper bought) and how much of it
+    pub fn token_out(&self, token0: H160, token1: H160) -> Option<(H160, U256)> {
+        if self.amount0.is_negative() {
+            Some((token0, self.amount0.unsigned_abs()))
+        } else if self.amount1.is_negative() {
+            Some((token1, self.amount1.unsigned_abs()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Decode a Uniswap V3 (160-byte) or PancakeSwap V3 (224-byte) Swap log, selected by `topic0`.
+pub fn decode_v3_swap(topic0: H256, data: &[u8]) -> anyhow::Result<V3SwapData> {
+    let mut param_types = vec![
+        ParamType::Int(256),  // amount0
+        ParamType::Int(256),  // amount1
+        ParamType::Uint(160), // sqrtPriceX96
+        ParamType::Uint(128), // liquidity
+        ParamType::Int(24),   // tick
+    ];
+    let expected_len = if topic0 == *UNISWAP_V3_SWAP_TOPIC {
+        UNISWAP_V3_SWAP_DATA_LEN
+    } else if topic0 == *PANCAKE_V3_SWAP_TOPIC {
+        param_types.push(ParamType::Uint(128)); // protocolFeesToken0
+        param_types.push(ParamType::Uint(128)); // protocolFeesToken1
+        PANCAKE_V3_SWAP_DATA_LEN
+    } else {
+        anyhow::bail!("not a V3 Swap topic: {:?}", topic0);
+    };
+    if data.len() != expected_len {
+        anyhow::bail!("invalid V3 Swap log size: {} (expected {})", data.len(), expected_len);
+    }
+
+    let tokens = decode(&param_types, data)?;
+    let int = |t: &Token| match t {
+        Token::Int(raw) => Ok(I256::from_raw(*raw)),
+        other => Err(anyhow::anyhow!("expected int, got {:?}", other)),
+    };
+    let uint = |t: &Token| t.clone().into_uint().ok_or_else(|| anyhow::anyhow!("expected uint, got {:?}", t));
+
+    let tick = decode_int24(&tokens[4]).map_err(|e| anyhow::anyhow!("tick: {}", e))?;
+    Ok(V3SwapData {
+        amount0: int(&tokens[0])?,
+        amount1: int(&tokens[1])?,
+        sqrt_price_x96: uint(&tokens[2])?,
+        liquidity: uint(&tokens[3])?,
+        tick,
+        protocol_fees_token0: tokens.get(5).map(uint).transpose()?.unwrap_or_default(),
+        protocol_fees_token1: tokens.get(6).map(uint).transpose()?.unwrap_or_default(),
+    })
+}
+
+/// Running per-pool swap volume (raw token units) from decoded V3 swaps
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolVolume {
+    pub swaps: u64,
+    pub token0_in: U256,
+    pub token1_in: U256,
+    pub token0_out: U256,
+    pub token1_out: U256,
+    pub protocol_fees_token0: U256,
+    pub protocol_fees_token1: U256,
+}
+
+impl PoolVolume {
+    pub fn record(&mut self, swap: &V3SwapData) {
+        let split = |amount: I256| {
+            if amount.is_negative() {
+                (U256::zero(), amount.unsigned_abs())
+            } else {
+                (amount.into_raw(), U256::zero())
+            }
+        };
+        let (in0, out0) = split(swap.amount0);
+        let (in1, out1) = split(swap.amount1);
+        self.swaps += 1;
+        self.token0_in = self.token0_in.saturating_add(in0);
+        self.token1_in = self.token1_in.saturating_add(in1);
+        self.token0_out = self.token0_out.saturating_add(out0);
+        self.token1_out = self.token1_out.saturating_add(out1);
+        self.protocol_fees_token0 = self.protocol_fees_token0.saturating_add(swap.protocol_fees_token0);
+        self.protocol_fees_token1 = self.protocol_fees_token1.saturating_add(swap.protocol_fees_token1);
+    }
+}
+
+/// Volume per V3 pool since startup, fed by every decoded Swap log
+pub static V3_POOL_VOLUME: Lazy<DashMap<H160, PoolVolume>> = Lazy::new(DashMap::new);
+
+/// Add one decoded swap to `pool`'s running volume
+pub fn record_v3_volume(pool: H160, swap: &V3SwapData) {
+    V3_POOL_VOLUME.entry(pool).or_default().record(swap);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::encode;
+
+    fn swap_tokens(tick: i64) -> Vec<Token> {
+        vec![
+            Token::Int(I256::from(-1_000i64).into_raw()),
+            Token::Int(I256::from(2_000i64).into_raw()),
+            Token::Uint(U256::one() << 96),
+            Token::Uint(U256::from(5_000u64)),
+            Token::Int(I256::from(tick).into_raw()),
+        ]
+    }
+
+    #[test]
+    fn test_decode_both_layouts() {
+        let uniswap = encode(&swap_tokens(-887_220));
+        let swap = decode_v3_swap(*UNISWAP_V3_SWAP_TOPIC, &uniswap).unwrap();
+        assert_eq!(swap.amount0, I256::from(-1_000i64));
+        assert_eq!(swap.amount1, I256::from(2_000i64));
+        assert_eq!(swap.sqrt_price_x96, U256::one() << 96);
+        assert_eq!(swap.liquidity, U256::from(5_000u64));
+        assert_eq!(swap.tick, -887_220);
+        assert!(swap.protocol_fees_token0.is_zero() && swap.protocol_fees_token1.is_zero());
+
+        let mut tokens = swap_tokens(42);
+        tokens.push(Token::Uint(U256::from(7u64)));
+        tokens.push(Token::Uint(U256::zero()));
+        let pancake = encode(&tokens);
+        let swap = decode_v3_swap(*PANCAKE_V3_SWAP_TOPIC, &pancake).unwrap();
+        assert_eq!(swap.tick, 42);
+        assert_eq!(swap.protocol_fees_token0, U256::from(7u64));
+
+        let token0 = H160::from_low_u64_be(1);
+        let token1 = H160::from_low_u64_be(2);
+        assert_eq!(swap.token_out(token0, token1), Some((token0, U256::from(1_000u64))));
+    }
+
+    #[test]
+    fn test_decode_pancake_v3_swap_amounts() {
+        // Pancake V3 Swap log data: token1 paid in, token0 paid out, protocol fee taken in token1
+        let data_hex = "0xffffffffffffffffffffffffffffffffffffffffffffffffeadbc652075915cd0000000000000000000000000000000000000000000000318770da465ba50000000000000000000000000000000000000000001ead4b24dda4ef13defd8289350000000000000000000000000000000000000000000112e50f7b7746968be7e200000000000000000000000000000000000000000000000000000000000102c5000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000289250d8c11e00";
+        let data = hex::decode(data_hex.trim_start_matches("0x")).unwrap();
+        let swap = decode_v3_swap(*PANCAKE_V3_SWAP_TOPIC, &data).unwrap();
+        assert_eq!(swap.amount0, I256::from(-1523405993321884211i64));
+        assert_eq!(swap.amount1, I256::from_dec_str("913650000000000000000").unwrap());
+        assert_eq!(swap.sqrt_price_x96, U256::from_dec_str("2430476625657302485270958082357").unwrap());
+        assert_eq!(swap.liquidity, U256::from_dec_str("1298153836315640037369826").unwrap());
+        assert_eq!(swap.tick, 66245);
+        assert_eq!(swap.protocol_fees_token0, U256::zero());
+        assert_eq!(swap.protocol_fees_token1, U256::from(11419875000000000u64));
+
+        let token0 = H160::from_low_u64_be(1);
+        let token1 = H160::from_low_u64_be(2);
+        assert_eq!(swap.token_out(token0, token1), Some((token0, U256::from(1523405993321884211u64))));
+
+        let pool = H160::from_low_u64_be(0x3f);
+        record_v3_volume(pool, &swap);
+        record_v3_volume(pool, &swap);
+        let volume = V3_POOL_VOLUME.get(&pool).unwrap().clone();
+        assert_eq!(volume.swaps, 2);
+        assert_eq!(volume.token1_in, U256::from_dec_str("1827300000000000000000").unwrap());
+        assert_eq!(volume.token0_out, U256::from(3046811986643768422u64));
+        assert_eq!(volume.token0_in, U256::zero());
+        assert_eq!(volume.protocol_fees_token1, U256::from(22839750000000000u64));
+
+        // Truncated data (Uniswap layout, no protocol fees) is not a Pancake swap
+        assert!(decode_v3_swap(*PANCAKE_V3_SWAP_TOPIC, &data[..160]).is_err());
+    }
+
+    /// The byte-offset slicing `process_simulation_events_and_arbitrage` used before switching to `decode_v3_swap`
+    fn legacy_offset_decode(data_bytes: &[u8]) -> (U256, U256, i32) {
+        let sqrt_price_x96 = U256::from_big_endian(&data_bytes[64..84]);
+        let liquidity = U256::from_big_endian(&data_bytes[84..100]);
+        let mut buf = [0u8; 32];
+        buf[8..32].copy_from_slice(&data_bytes[100..124]);
+        // (the original `as_u32` could also panic here; low_u32 keeps the comparison running)
+        (sqrt_price_x96, liquidity, U256::from_big_endian(&buf).low_u32() as i32)
+    }
+
+    #[test]
+    fn test_sim_v3_decode_matches_price_tracker() {
+        let sqrt_price_x96 = U256::from_dec_str("2430476625657302485270958082357").unwrap();
+        let liquidity = U256::from_dec_str("1298153836315640037369826").unwrap();
+        let data = encode(&[
+            Token::Int(I256::from(-1_000i64).into_raw()),
+            Token::Int(I256::from(2_000i64).into_raw()),
+            Token::Uint(sqrt_price_x96),
+            Token::Uint(liquidity),
+            Token::Int(I256::from(-27_500i64).into_raw()),
+        ]);
+
+        // Old slicing reads the wrong bytes of each 32-byte word and never sign-extends the tick
+        let (legacy_price, legacy_liquidity, legacy_tick) = legacy_offset_decode(&data);
+        assert_ne!(legacy_price, sqrt_price_x96);
+        assert_ne!(legacy_liquidity, liquidity);
+        assert_ne!(legacy_tick, -27_500);
+
+        // The simulation path now decodes with `decode_v3_swap`, exactly what the price tracker writes to the cache
+        let swap = decode_v3_swap(*UNISWAP_V3_SWAP_TOPIC, &data).unwrap();
+        assert_eq!(swap.sqrt_price_x96, sqrt_price_x96);
+        assert_eq!(swap.liquidity, liquidity);
+        assert_eq!(swap.tick, -27_500);
+    }
+
+    #[test]
+    fn test_decode_rejects_mismatched_layout() {
+        let uniswap = encode(&swap_tokens(0));
+        assert!(decode_v3_swap(*PANCAKE_V3_SWAP_TOPIC, &uniswap).is_err());
+        assert!(decode_v3_swap(H256::zero(), &uniswap).is_err());
+        assert!(decode_v3_swap(*UNISWAP_V3_SWAP_TOPIC, &uniswap[..128]).is_err());
+        // Tick outside int24
+        let out_of_range = encode(&swap_tokens(8_388_608));
+        assert!(decode_v3_swap(*UNISWAP_V3_SWAP_TOPIC, &out_of_range).is_err());
+    }
+
+    #[test]
+    fn test_decode_int24_boundaries() {
+        for tick in [INT24_MIN, INT24_MIN + 1, -887_272, -1, 0, 1, 887_272, INT24_MAX] {
+            let raw = I256::from(tick).into_raw();
+            let mut word = [0u8; 32];
+            raw.to_big_endian(&mut word);
+            assert_eq!(decode_int24(&Token::Int(raw)).unwrap(), tick);
+            assert_eq!(decode_int24(&raw).unwrap(), tick);
+            assert_eq!(decode_int24(&word[..]).unwrap(), tick);
+        }
+        // One past either end, and the low 24 bits of a negative tick without sign extension
+        for raw in [I256::from(INT24_MIN as i64 - 1).into_raw(), I256::from(INT24_MAX as i64 + 1).into_raw(), U256::from(0xff_ffffu64)] {
+            assert!(decode_int24(&raw).is_err(), "{:x}", raw);
+        }
+        assert!(decode_int24(&[0u8; 31][..]).is_err());
+        assert!(decode_int24(&Token::Bool(true)).is_err());
+    }
+
+    #[test]
+    fn test_decode_random_payloads_never_panic() {
+        use rand::{Rng, RngCore};
+        let mut rng = rand::thread_rng();
+        for _ in 0..2_000 {
+            // Mostly the exact layout sizes so the ABI decoder and tick check get exercised
+            let len = match rng.gen_range(0..4) {
+                0 => UNISWAP_V3_SWAP_DATA_LEN,
+                1 => PANCAKE_V3_SWAP_DATA_LEN,
+                _ => rng.gen_range(0..300),
+            };
+            let mut data = vec![0u8; len];
+            rng.fill_bytes(&mut data);
+            for topic in [*UNISWAP_V3_SWAP_TOPIC, *PANCAKE_V3_SWAP_TOPIC] {
+                if let Ok(swap) = decode_v3_swap(topic, &data) {
+                    assert!((INT24_MIN..=INT24_MAX).contains(&swap.tick));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Dry runs must log the built trade to `executor.log` without a single RPC request.
+//! The provider points at a local listener that counts connections, so any gas lookup, nonce
+//! fetch, simulation call or send would show up.
+
+use arb_rust_bot::config::{ExecutorStrategy, GasStrategy};
+use arb_rust_bot::executor::{dispatch_arbitrage, run_trade, AdaptiveSlippage, BuySellExecutionData, NonceAllocator, Trade, TradeContext};
+use arb_rust_bot::failover_provider::{FailoverProvider, HttpProvider};
+use arb_rust_bot::inventory::InventoryManager;
+use arb_rust_bot::log_maintenance::{init_log_dir, log_path};
+use arb_rust_bot::metrics::metrics;
+use arb_rust_bot::submitter::PublicSubmitter;
+use arb_rust_bot::token_index::TokenIndexMap;
+use ethers::signers::LocalWallet;
+use ethers::types::{H160, U256};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+const PRIVATE_KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+/// A provider whose endpoint only counts the connections made to it
+async fn counting_provider() -> (Arc<HttpProvider>, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    tokio::spawn({
+        let connections = connections.clone();
+        async move {
+            while listener.accept().await.is_ok() {
+                connections.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    });
+    (Arc::new(FailoverProvider::connect(&[url]).unwrap()), connections)
+}
+
+/// Logs of every test in this file go to one per-process directory
+fn init_logs() {
+    init_log_dir(&std::env::temp_dir().join(format!("dry_run_{}", std::process::id()))).unwrap();
+}
+
+fn swap_data(opportunity_id: u64) -> BuySellExecutionData {
+    BuySellExecutionData {
+        buy_tokens: vec![H160::from_low_u64_be(1), H160::from_low_u64_be(2)],
+        buy_pools: vec![H160::from_low_u64_be(3)],
+        buy_pool_types: vec![0],
+        buy_amounts: vec![U256::from(10u64), U256::from(20u64)],
+        sell_tokens: vec![H160::from_low_u64_be(2), H160::from_low_u64_be(1)],
+        sell_pools: vec![H160::from_low_u64_be(4)],
+        sell_pool_types: vec![0],
+        sell_amounts: vec![U256::from(20u64), U256::from(11u64)],
+        strategy: ExecutorStrategy::V2,
+        deadline: U256::from(u64::MAX),
+        slippage_bps: 0,
+        min_amount_out: U256::from(11u64),
+        opportunity_id,
+    }
+}
+
+#[tokio::test]
+async fn test_dry_run_never_contacts_the_provider() {
+    init_logs();
+    let (provider, connections) = counting_provider().await;
+    let submitter = PublicSubmitter::new(provider.clone());
+    let wallet: LocalWallet = PRIVATE_KEY.parse().unwrap();
+
+    let result = dispatch_arbitrage(
+        H160::from_low_u64_be(0xc0),
+        swap_data(42),
+        wallet,
+        provider,
+        &GasStrategy::OracleMultiple(1.0),
+        &NonceAllocator::default(),
+        U256::from(1u64),
+        true,
+        &submitter,
+    )
+    .await
+    .unwrap();
+    assert!(result.is_none(), "a dry run has no receipt");
+
+    // Give a stray request time to arrive before counting
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(connections.load(Ordering::SeqCst), 0, "dry run reached the RPC endpoint");
+    let log = std::fs::read_to_string(log_path("executor.log")).unwrap();
+    assert!(log.contains("[opp #42] Dry run: not sent, expected_profit=1"), "{}", log);
+}
+
+#[tokio::test]
+async fn test_main_loop_trade_in_dry_run_logs_the_built_trade_and_frees_inventory() {
+    init_logs();
+    let (provider, connections) = counting_provider().await;
+    let (executor, input_token) = (H160::from_low_u64_be(0xc1), H160::from_low_u64_be(1));
+    let inventory = Arc::new(InventoryManager::new());
+    inventory.set_balance(executor, input_token, U256::from(100u64));
+    assert!(inventory.try_reserve(executor, input_token, U256::from(10u64)));
+    let context = TradeContext {
+        wallet: PRIVATE_KEY.parse().unwrap(),
+        provider: provider.clone(),
+        gas_strategy: GasStrategy::OracleMultiple(1.0),
+        nonces: Arc::new(NonceAllocator::default()),
+        submitter: Arc::new(PublicSubmitter::new(provider)),
+        inventory: inventory.clone(),
+        token_index: Arc::new(TokenIndexMap { address_to_index: Default::default(), index_to_address: Default::default() }),
+        slippage: Arc::new(AdaptiveSlippage::new(30, 10, 500, 25)),
+        adaptive_slippage: true,
+        dry_run: true,
+    };
+    let trade = Trade {
+        contract_address: executor,
+        swap_data: swap_data(43),
+        simulated_profit: U256::from(1u64),
+        base_token_idx: 0,
+        reserved_in: Some(executor),
+    };
+
+    run_trade(context, trade).await;
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(connections.load(Ordering::SeqCst), 0, "dry run reached the RPC endpoint");
+    assert_eq!(metrics().executions_attempted.get(), 0, "a dry run is not an attempted execution");
+    assert_eq!(inventory.available(executor, &input_token), U256::from(100u64), "reservation released");
+    // The whole built trade is logged: tokens, pools, amounts, minimum out and deadline
+    let log = std::fs::read_to_string(log_path("executor.log")).unwrap();
+    let line = log.lines().find(|l| l.contains("[opp #43] Dry run: not sent")).unwrap_or_else(|| panic!("{}", log));
+    for field in ["buy_tokens", "buy_pools", "buy_amounts", "sell_tokens", "sell_pools", "sell_amounts", "min_amount_out: 11", "deadline"] {
+        assert!(line.contains(field), "{} missing from {}", field, line);
+    }
+}
@@ -0,0 +1,334 @@
+//! End-to-end check that a detected opportunity executes profitably on a forked chain
+//! (`cargo test --features fork-tests --test fork_execution`). Needs:
+//! - `ARCHIVE_RPC_URL`: archive node that can serve state at the fixture block
+//! - `CONTRACT_ADDRESS`: the deployed executor (must exist at the fixture block)
+//! - `EXECUTOR_OWNER`: account allowed to call it; impersonated on the fork, no key needed
+//!
+//! The fixture (`tests/fixtures/fork_arb.json`) pins the block and the pools. The test makes its
+//! own opportunity there: it donates base token to one pair and `sync()`s it, so the price gap
+//! is known, then runs detection on reserves read from the fork, builds `BuySellExecutionData`,
+//! sends `buySellExecution` through REVM and checks the executor's real balance change. The
+//! same trade with `min_amount_out` above the achievable output has to revert first.
+//! A second test checks that the fork really reads state at the pinned block, not the head.
+//!
+//! `test_recorded_opportunity_replays_on_fork` replays a real opportunity instead: the first
+//! decision in `tests/fixtures/fork_recorded_arb.jsonl` (a `mirror.jsonl` line through V2 pairs
+//! only, see the README's "Mirror Mode"), forked at the block it was recorded at. That block pins the test, and
+//! `CONTRACT_ADDRESS` must already be deployed and hold the decision's input there.
+
+use alloy_eips::BlockId;
+use alloy_provider::{DynProvider, Provider, ProviderBuilder};
+use arb_rust_bot::arbitrage_finder::simulate_all_paths_for_token_x;
+use arb_rust_bot::config::{Config, DexVersion};
+use arb_rust_bot::executor::BuySellExecutionData;
+use arb_rust_bot::mirror_log::read_decisions;
+use arb_rust_bot::route_cache::{DEXType, PoolMeta};
+use arb_rust_bot::sim_trace::fork_db_at;
+use arb_rust_bot::simulate_swap_path::quote_exact_in;
+use arb_rust_bot::testkit::{pool_v2, MarketBuilder};
+use ethers::abi::{decode, encode, ParamType, Token};
+use ethers::types::{H160, U256};
+use ethers::utils::keccak256;
+use revm::context::TxEnv;
+use revm::context_interface::result::ExecutionResult;
+use revm::primitives::{Address, Bytes, TxKind, U256 as RevmU256};
+use revm::handler::{ExecuteCommitEvm, MainBuilder};
+use revm::{Context, DatabaseRef, MainContext};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    block: u64,
+    base_token: H160,
+    token_x: H160,
+    dislocated_dex: String, // its pair receives the donation, making base token cheap there
+    reference_dex: String,
+    donation_bps: u64,      // donation as a share of the dislocated pair's base reserve
+    executor_funding: u64,  // whole base tokens moved into the executor as inventory
+}
+
+fn env_address(name: &str) -> H160 {
+    std::env::var(name)
+        .unwrap_or_else(|_| panic!("{} must be set for fork tests", name))
+        .parse()
+        .unwrap_or_else(|e| panic!("invalid {}: {}", name, e))
+}
+
+fn to_revm_address(address: H160) -> Address {
+    Address::from(address.0)
+}
+
+fn to_revm_u256(value: U256) -> RevmU256 {
+    RevmU256::from_limbs(value.0)
+}
+
+/// 4-byte selector followed by the ABI-encoded arguments
+fn calldata(signature: &str, args: &[Token]) -> Vec<u8> {
+    let mut data = keccak256(signature.as_bytes())[..4].to_vec();
+    data.extend(encode(args));
+    data
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fork_reads_state_at_the_pinned_block() {
+    let fixture: Fixture = serde_json::from_str(include_str!("fixtures/fork_arb.json")).expect("fixture");
+    let rpc_url = std::env::var("ARCHIVE_RPC_URL").expect("ARCHIVE_RPC_URL must be set for fork tests");
+    let provider: DynProvider = ProviderBuilder::new().connect_http(rpc_url.parse().expect("ARCHIVE_RPC_URL")).erased();
+    // WBNB's native balance moves with almost every block, so a fork of the head would differ
+    let wbnb = to_revm_address(fixture.base_token);
+    let block = BlockId::number(fixture.block);
+    let at_block = provider.get_balance(wbnb).block_id(block).await.expect("balance at fixture block");
+    let latest = provider.get_balance(wbnb).await.expect("latest balance");
+    assert_ne!(at_block, latest, "WBNB balance unchanged since the fixture block; pick another block");
+
+    let db = fork_db_at(provider, block).expect("fork at fixture block");
+    let forked = db.basic_ref(wbnb).expect("WBNB account").unwrap_or_default().balance;
+    assert_eq!(forked, at_block);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_detected_opportunity_executes_profitably_on_fork() {
+    let fixture: Fixture = serde_json::from_str(include_str!("fixtures/fork_arb.json")).expect("fixture");
+    let rpc_url = std::env::var("ARCHIVE_RPC_URL").expect("ARCHIVE_RPC_URL must be set for fork tests");
+    let executor = env_address("CONTRACT_ADDRESS");
+    let owner = env_address("EXECUTOR_OWNER");
+    let config = Config::default();
+
+    let provider: DynProvider = ProviderBuilder::new().connect_http(rpc_url.parse().expect("ARCHIVE_RPC_URL")).erased();
+    let block = BlockId::number(fixture.block);
+    let header = provider
+        .get_block(block)
+        .await
+        .expect("fetch fixture block")
+        .expect("fixture block not found")
+        .header;
+
+    // Fork at the fixture block; the owner gets gas money and pays nothing for it
+    let mut db = fork_db_at(provider, block).expect("fork at fixture block");
+    let mut owner_info = db.basic_ref(to_revm_address(owner)).expect("owner account").unwrap_or_default();
+    owner_info.balance = to_revm_u256(U256::exp10(24));
+    db.insert_account_info(to_revm_address(owner), owner_info);
+    let mut ctx = Context::mainnet().with_db(db);
+    ctx.cfg.chain_id = config.chain_id;
+    ctx.cfg.disable_nonce_check = true;
+    ctx.block.number = header.number;
+    ctx.block.timestamp = header.timestamp;
+    ctx.block.basefee = header.base_fee_per_gas.unwrap_or_default();
+    let basefee = ctx.block.basefee as u128;
+    let evm = RefCell::new(ctx.build_mainnet());
+
+    // Commit one transaction and return its output; panics on revert
+    let transact = |tx: TxEnv| -> Vec<u8> {
+        let to = tx.kind;
+        match evm.borrow_mut().transact_commit(tx).expect("transaction is valid") {
+            ExecutionResult::Success { output, .. } => output.into_data().to_vec(),
+            other => panic!("call to {:?} failed: {:?}", to, other),
+        }
+    };
+    // Commit one call from the owner
+    let mut call = |to: H160, data: Vec<u8>, value: U256| -> Vec<u8> {
+        transact(TxEnv {
+            caller: to_revm_address(owner),
+            kind: TxKind::Call(to_revm_address(to)),
+            data: Bytes::from(data),
+            value: to_revm_u256(value),
+            gas_limit: 3_000_000,
+            gas_price: basefee,
+            chain_id: Some(config.chain_id),
+            ..Default::default()
+        })
+    };
+    // i-th word of a call output, read as uint256
+    let uint = |output: &[u8], i: usize| decode(&vec![ParamType::Uint(256); i + 1], output).expect("uint output")[i].clone().into_uint().unwrap();
+    let balance_of = |call: &mut dyn FnMut(H160, Vec<u8>, U256) -> Vec<u8>, token: H160, holder: H160| {
+        let output = call(token, calldata("balanceOf(address)", &[Token::Address(holder)]), U256::zero());
+        uint(&output, 0)
+    };
+    let get_pair = |call: &mut dyn FnMut(H160, Vec<u8>, U256) -> Vec<u8>, dex: &str| {
+        let factory = config.get_dex_by_name(dex).expect("fixture dex in config").factory_address;
+        let args = [Token::Address(fixture.base_token), Token::Address(fixture.token_x)];
+        let output = call(factory, calldata("getPair(address,address)", &args), U256::zero());
+        let pair = decode(&[ParamType::Address], &output).unwrap()[0].clone().into_address().unwrap();
+        assert!(!pair.is_zero(), "no {} pair for the fixture tokens", dex);
+        pair
+    };
+    // (token0, token1, reserve0, reserve1) as the pair reports them
+    let pair_state = |call: &mut dyn FnMut(H160, Vec<u8>, U256) -> Vec<u8>, pair: H160| {
+        let token = |call: &mut dyn FnMut(H160, Vec<u8>, U256) -> Vec<u8>, signature: &str| {
+            let output = call(pair, calldata(signature, &[]), U256::zero());
+            decode(&[ParamType::Address], &output).unwrap()[0].clone().into_address().unwrap()
+        };
+        let (token0, token1) = (token(call, "token0()"), token(call, "token1()"));
+        let reserves = call(pair, calldata("getReserves()", &[]), U256::zero());
+        (token0, token1, uint(&reserves, 0), uint(&reserves, 1))
+    };
+
+    let dislocated = get_pair(&mut call, &fixture.dislocated_dex);
+    let reference = get_pair(&mut call, &fixture.reference_dex);
+
+    // Wrap base token (WBNB) for the executor's inventory and the donation
+    let (token0, _, reserve0, reserve1) = pair_state(&mut call, dislocated);
+    let base_reserve = if token0 == fixture.base_token { reserve0 } else { reserve1 };
+    let donation = base_reserve * U256::from(fixture.donation_bps) / U256::from(10_000u64);
+    let funding = U256::from(fixture.executor_funding) * U256::exp10(18);
+    call(fixture.base_token, calldata("deposit()", &[]), donation + funding);
+    call(fixture.base_token, calldata("transfer(address,uint256)", &[Token::Address(executor), Token::Uint(funding)]), U256::zero());
+    call(fixture.base_token, calldata("transfer(address,uint256)", &[Token::Address(dislocated), Token::Uint(donation)]), U256::zero());
+    call(dislocated, calldata("sync()", &[]), U256::zero());
+
+    // Detection on the forked reserves, the way the bot sees them
+    let mut market = MarketBuilder::new();
+    let mut pool_meta = HashMap::new();
+    for (pair, dex) in [(dislocated, &fixture.dislocated_dex), (reference, &fixture.reference_dex)] {
+        let (token0, token1, reserve0, reserve1) = pair_state(&mut call, pair);
+        let mut state = pool_v2(token0, token1, reserve0, reserve1);
+        state.dex_name = Some(dex.clone());
+        market = market.pool(pair, state);
+        let dex_type = DEXType::from_dex_name(dex, &DexVersion::V2);
+        pool_meta.insert(pair, PoolMeta { token0, token1, address: pair, dex_type, factory: None, fee: None });
+    }
+    let (base, x) = (fixture.base_token, fixture.token_x);
+    let market = market
+        .route(&[base, x, base], &[reference, dislocated])
+        .route(&[base, x, base], &[dislocated, reference])
+        .config(config.clone())
+        .build();
+    // 0.1% of the reference pair's X: well inside the 3% gap after both pools' fees
+    let (token0, _, reserve0, reserve1) = pair_state(&mut call, reference);
+    let token_x_amount = if token0 == x { reserve0 } else { reserve1 } / U256::from(1_000u64);
+    let routes = simulate_all_paths_for_token_x(
+        market.index(x),
+        token_x_amount,
+        dislocated,
+        &market.route_cache,
+        &market.reserve_cache,
+        &market.token_index,
+        &market.token_tax_map,
+        &market.config,
+    );
+    let best = routes.iter().max_by_key(|r| r.profit).expect("no route simulated");
+    assert!(!best.profit.is_zero(), "detection found no profit on the fork");
+    // Base token is cheap on the dislocated pair: buy X with base elsewhere, buy base back there
+    assert_eq!((best.buy_pools.clone(), best.sell_pools.clone()), (vec![reference], vec![dislocated]));
+
+    let swap_data = BuySellExecutionData::from_simulated_route(
+        best,
+        &pool_meta,
+        &market.token_index,
+        &market.reserve_cache,
+        &market.token_tax_map,
+        &market.config,
+    )
+    .expect("execution data");
+    assert!(swap_data.buy_amounts[0] <= funding, "executor inventory too small for the route");
+
+    let before = balance_of(&mut call, base, executor);
+    // The minimum travels as the sell leg's last amount; one the fill can't reach must revert
+    let simulated_out = *swap_data.sell_amounts.last().unwrap();
+    let unreachable = BuySellExecutionData { min_amount_out: simulated_out + simulated_out / 100, ..swap_data.clone() };
+    let tx = TxEnv { gas_price: basefee, chain_id: Some(config.chain_id), ..unreachable.to_tx_env(executor, owner) };
+    let result = evm.borrow_mut().transact_commit(tx).expect("transaction is valid");
+    assert!(matches!(result, ExecutionResult::Revert { .. }), "sell below min_amount_out did not revert: {:?}", result);
+    assert_eq!(balance_of(&mut call, base, executor), before, "reverted trade moved funds");
+
+    // Sent as the bot would send it, with the same calldata and gas limit
+    transact(TxEnv { gas_price: basefee, chain_id: Some(config.chain_id), ..swap_data.to_tx_env(executor, owner) });
+    let after = balance_of(&mut call, base, executor);
+    assert!(after > before, "realized profit: {} -> {}", before, after);
+    println!("[FORK] simulated profit {}, realized {}", best.profit, after - before);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_recorded_opportunity_replays_on_fork() {
+    let decisions = read_decisions(std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/fork_recorded_arb.jsonl")))
+        .expect("recorded decisions fixture");
+    let rpc_url = std::env::var("ARCHIVE_RPC_URL").expect("ARCHIVE_RPC_URL must be set for fork tests");
+    let executor = env_address("CONTRACT_ADDRESS");
+    let owner = env_address("EXECUTOR_OWNER");
+    let config = Config::default();
+    let provider: DynProvider = ProviderBuilder::new().connect_http(rpc_url.parse().expect("ARCHIVE_RPC_URL")).erased();
+
+    // The first recorded decision; its pools must be V2 pairs (rebuilt from getReserves() below)
+    let record = decisions.into_iter().next().expect("fixture holds no decision: add a mirror.jsonl line recorded against CONTRACT_ADDRESS");
+    let mut db = fork_db_at(provider.clone(), BlockId::number(record.block)).expect("fork at recorded block");
+    let block = BlockId::number(record.block);
+    let executor_code = db.basic_ref(to_revm_address(executor)).expect("executor account").unwrap_or_default();
+    assert!(!executor_code.is_empty_code_hash(), "CONTRACT_ADDRESS has no code at block {}", record.block);
+    let header = provider.get_block(block).await.expect("fetch recorded block").expect("recorded block not found").header;
+
+    let mut owner_info = db.basic_ref(to_revm_address(owner)).expect("owner account").unwrap_or_default();
+    owner_info.balance = to_revm_u256(U256::exp10(24));
+    db.insert_account_info(to_revm_address(owner), owner_info);
+    let mut ctx = Context::mainnet().with_db(db);
+    ctx.cfg.chain_id = config.chain_id;
+    ctx.cfg.disable_nonce_check = true;
+    ctx.block.number = header.number;
+    ctx.block.timestamp = header.timestamp;
+    ctx.block.basefee = header.base_fee_per_gas.unwrap_or_default();
+    let basefee = ctx.block.basefee as u128;
+    let evm = RefCell::new(ctx.build_mainnet());
+    let transact = |tx: TxEnv| -> Vec<u8> {
+        let to = tx.kind;
+        match evm.borrow_mut().transact_commit(tx).expect("transaction is valid") {
+            ExecutionResult::Success { output, .. } => output.into_data().to_vec(),
+            other => panic!("call to {:?} failed: {:?}", to, other),
+        }
+    };
+    let mut call = |to: H160, data: Vec<u8>| -> Vec<u8> {
+        transact(TxEnv {
+            caller: to_revm_address(owner),
+            kind: TxKind::Call(to_revm_address(to)),
+            data: Bytes::from(data),
+            gas_limit: 3_000_000,
+            gas_price: basefee,
+            chain_id: Some(config.chain_id),
+            ..Default::default()
+        })
+    };
+    let uint = |output: &[u8], i: usize| decode(&vec![ParamType::Uint(256); i + 1], output).expect("uint output")[i].clone().into_uint().unwrap();
+    let address = |output: &[u8]| decode(&[ParamType::Address], output).unwrap()[0].clone().into_address().unwrap();
+
+    // Rebuild the recorded route from the pairs as they stood at the recorded block
+    let mut market = MarketBuilder::new();
+    let mut pool_meta = HashMap::new();
+    let mut tokens = vec![record.base_token];
+    for pool in &record.pools {
+        let (token0, token1) = (address(&call(*pool, calldata("token0()", &[]))), address(&call(*pool, calldata("token1()", &[]))));
+        let reserves = call(*pool, calldata("getReserves()", &[]));
+        market = market.pool(*pool, pool_v2(token0, token1, uint(&reserves, 0), uint(&reserves, 1)));
+        pool_meta.insert(*pool, PoolMeta { token0, token1, address: *pool, dex_type: DEXType::PancakeV2, factory: None, fee: None });
+        let last = *tokens.last().unwrap();
+        tokens.push(if token0 == last { token1 } else { token0 });
+    }
+    assert_eq!(tokens.last(), Some(&record.base_token), "recorded route is not a cycle");
+    let market = market.base(record.base_token).route(&tokens, &record.pools).config(config.clone()).build();
+
+    // The recorded input buys this much X, which is what detection starts from
+    let amounts = quote_exact_in(&market.route(&tokens, &record.pools), record.input, &market.reserve_cache, &market.token_index, &market.token_tax_map, &market.config)
+        .expect("recorded route simulates");
+    let x_hop = tokens.iter().position(|t| *t == record.token).expect("recorded token on the route");
+    let routes = simulate_all_paths_for_token_x(
+        market.index(record.token),
+        amounts[x_hop],
+        record.pools[0],
+        &market.route_cache,
+        &market.reserve_cache,
+        &market.token_index,
+        &market.token_tax_map,
+        &market.config,
+    );
+    let best = routes.iter().find(|r| r.merged_pools == record.pools).expect("recorded route not simulated");
+    assert!(!best.profit.is_zero(), "recorded opportunity has no profit at its block");
+
+    let swap_data = BuySellExecutionData::from_simulated_route(best, &pool_meta, &market.token_index, &market.reserve_cache, &market.token_tax_map, &market.config)
+        .expect("execution data");
+    let balance_of = |call: &mut dyn FnMut(H160, Vec<u8>) -> Vec<u8>| uint(&call(record.base_token, calldata("balanceOf(address)", &[Token::Address(executor)])), 0);
+    let before = balance_of(&mut call);
+    assert!(swap_data.buy_amounts[0] <= before, "executor held {} at the recorded block, route needs {}", before, swap_data.buy_amounts[0]);
+    transact(TxEnv { gas_price: basefee, chain_id: Some(config.chain_id), ..swap_data.to_tx_env(executor, owner) });
+    let after = balance_of(&mut call);
+    assert!(after > before, "realized profit: {} -> {}", before, after);
+    println!("[FORK] recorded profit {}, simulated {}, realized {}", record.expected_profit, best.profit, after - before);
+}
@@ -0,0 +1,95 @@
+//! Live detection with a tax detector installed: a route through a token missing from the tax
+//! map is held back until the background probe fills it in. The detector is process-wide, so
+//! this runs in its own test binary rather than next to the unit tests of untaxed markets.
+
+use arb_rust_bot::arbitrage_finder::find_arbitrage_opportunity_blocking;
+use arb_rust_bot::cache::{PoolState, PoolType, ReserveCache};
+use arb_rust_bot::config::Config;
+use arb_rust_bot::opportunity::DecodedSwap;
+use arb_rust_bot::price_oracle::{seed_prices, PriceOracle};
+use arb_rust_bot::route_cache::{DEXType, RoutePath};
+use arb_rust_bot::split_route_path::precompute_route_legs;
+use arb_rust_bot::token_index::TokenIndexMap;
+use arb_rust_bot::token_tax::{init_tax_detector, TaxDetector, TaxProbe, TaxProbeResult, TokenTaxMap};
+use async_trait::async_trait;
+use dashmap::DashMap;
+use ethers::types::{H160, U256};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Probe of a token without any transfer tax
+struct UntaxedProbe;
+
+#[async_trait]
+impl TaxProbe for UntaxedProbe {
+    async fn probe(&self, _token: H160) -> anyhow::Result<TaxProbeResult> {
+        let amount = U256::exp10(16);
+        Ok(TaxProbeResult { buy_expected: amount, buy_received: amount, sell_sent: amount, sell_received: amount })
+    }
+}
+
+fn pool_v2(token0: H160, token1: H160, reserve0: u64, reserve1: u64) -> PoolState {
+    PoolState {
+        pool_type: PoolType::V2,
+        token0,
+        token1,
+        reserve0: Some(U256::from(reserve0) * U256::exp10(18)),
+        reserve1: Some(U256::from(reserve1) * U256::exp10(18)),
+        dex_name: Some("PancakeSwap V2".to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_route_through_unmapped_token_waits_for_tax_detection() {
+    let config = Config::default();
+    let wbnb = config.get_base_token_by_symbol("WBNB").unwrap().address;
+    let x = H160::from_low_u64_be(2);
+    let (cheap, dear) = (H160::from_low_u64_be(0x1_0001), H160::from_low_u64_be(0x1_0002));
+
+    // WBNB/X priced apart on two pairs, with the cycle cached under X
+    let reserve_cache = Arc::new(ReserveCache::new());
+    reserve_cache.insert(cheap, pool_v2(wbnb, x, 1_000, 1_100));
+    reserve_cache.insert(dear, pool_v2(wbnb, x, 1_000, 950));
+    let token_index = Arc::new(TokenIndexMap {
+        address_to_index: HashMap::from([(wbnb, 0), (x, 1)]),
+        index_to_address: HashMap::from([(0, wbnb), (1, x)]),
+    });
+    let route = RoutePath { hops: vec![0, 1, 0], pools: vec![cheap, dear], dex_types: vec![DEXType::PancakeV2; 2], ..Default::default() };
+    let route_cache = Arc::new(DashMap::from_iter([(1u32, Arc::new(vec![route]))]));
+    precompute_route_legs(&route_cache);
+    let price_oracle = PriceOracle::new(seed_prices());
+    price_oracle.set_gas_price(U256::from(config.gas_price));
+
+    let token_tax_map = Arc::new(TokenTaxMap::new());
+    init_tax_detector(Arc::new(TaxDetector::new(Arc::new(UntaxedProbe), token_tax_map.clone(), HashSet::from([wbnb]))));
+
+    let decoded_swap = DecodedSwap { pool_address: dear, token_x: x, token_x_amount: U256::exp10(18) * 5, block_number: 1, ..Default::default() };
+    let detect = || {
+        find_arbitrage_opportunity_blocking(
+            Instant::now(),
+            &decoded_swap,
+            &reserve_cache,
+            &token_index,
+            &route_cache,
+            &token_tax_map,
+            &config,
+            &config.route_filter,
+            &price_oracle,
+        )
+    };
+
+    // X's tax isn't known yet: the route is held back and X goes to the detector
+    assert!(detect().is_none(), "route through an unprobed token was simulated");
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !token_tax_map.contains_key(&x) {
+        assert!(Instant::now() < deadline, "tax detection never finished");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert!(!token_tax_map.contains_key(&wbnb), "base tokens are exempt from probing");
+
+    // Once the probe has landed the same swap finds the route
+    let (opportunity, _) = detect().expect("route not picked up after tax detection");
+    assert_eq!(opportunity.best_route.unwrap().merged_pools, vec![cheap, dear]);
+}